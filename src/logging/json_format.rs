@@ -0,0 +1,114 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to format each log event as a single line of JSON, so that the
+//! logs can be shipped to a log aggregator.
+
+// Import standard library features
+use std::fmt;
+
+// Import tracing features
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+// Import JSON features
+use serde_json::{Map, Value, json};
+
+/// A structure to format log events as JSON. Each line contains the
+/// timestamp, level, message, any other fields of the event, and the spans
+/// that the event occurred within.
+///
+pub struct JsonFormat;
+
+// Implement the event formatting
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        context: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        // Format the timestamp
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        // Collect the fields of the event
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+
+        // Collect the spans of the event, from the outermost span
+        let mut spans = Vec::new();
+        if let Some(scope) = context.event_scope() {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let fields = extensions
+                    .get::<FormattedFields<N>>()
+                    .map(|fields| fields.fields.as_str())
+                    .unwrap_or_default();
+                spans.push(json!({ "name": span.name(), "fields": fields }));
+            }
+        }
+
+        // Compose and write the line
+        let line = json!({
+            "timestamp": timestamp,
+            "level": event.metadata().level().as_str(),
+            "fields": Value::Object(visitor.fields),
+            "spans": spans,
+        });
+        writeln!(writer, "{}", line)
+    }
+}
+
+/// A helper structure to collect the fields of an event as JSON values
+///
+#[derive(Default)]
+struct JsonVisitor {
+    fields: Map<String, Value>, // the fields collected so far
+}
+
+// Implement the field visitor
+impl Visit for JsonVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields
+            .insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+}
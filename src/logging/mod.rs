@@ -0,0 +1,183 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to configure the logging of the program, either as text or as
+//! JSON, and either to the terminal or to a rotating log file. The request
+//! spans can also be exported to an OpenTelemetry collector.
+
+// Define submodules
+mod json_format;
+mod otlp;
+mod rotating_file;
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import submodule definitions
+use json_format::JsonFormat;
+use otlp::OtlpExporter;
+use rotating_file::RotatingFile;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import tracing features
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{Registry, reload};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A structure to set up the logging of the program
+///
+pub struct Logging;
+
+// Implement the logging features
+impl Logging {
+    /// A function to set up the logging from the configuration and return
+    /// a handle to change the log level while the program is running. If
+    /// the log file cannot be opened, the logs are written to the terminal
+    /// instead. The export of spans, if enabled, runs in the background
+    /// whatever the log level.
+    ///
+    pub fn setup(config: &Config) -> LogLevel {
+        // Try to convert the string to a log level
+        let log_level = Logging::parse_level(&config.log_level).unwrap_or_else(|_| {
+            // Otherwise, print a nice error
+            println!(
+                "Unable to parse parameter for option 'logLevel'. Options are Trace, Debug, Info, Warn, and Error."
+            );
+            LevelFilter::INFO
+        });
+
+        // Select the destination of the logs
+        let mut is_terminal = true;
+        let writer = match config.log_file.as_deref() {
+            // Try to open the log file
+            Some(path) => match RotatingFile::new(
+                path,
+                config.log_max_size * 1_000_000,
+                Duration::from_secs(config.log_max_age * 3600),
+                config.log_max_files,
+            ) {
+                Ok(file) => {
+                    is_terminal = false;
+                    BoxMakeWriter::new(file)
+                }
+                Err(error) => {
+                    println!("Unable to open log file {}: {}.", path.display(), error);
+                    BoxMakeWriter::new(std::io::stdout)
+                }
+            },
+
+            // Otherwise, write to the terminal
+            None => BoxMakeWriter::new(std::io::stdout),
+        };
+
+        // Create the output in the selected format
+        let output = tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(writer);
+        let output = match config.log_format {
+            LogFormat::Text => output.with_ansi(is_terminal).boxed(),
+            LogFormat::Json => output.with_ansi(false).event_format(JsonFormat).boxed(),
+        };
+
+        // Prepare the export of the request spans (which are at the debug
+        // level), if enabled
+        let (exporter, spans) = match config.otlp.as_ref().map(OtlpExporter::new) {
+            Some(Ok((exporter, layer))) => {
+                (Some(exporter), Some(layer.with_filter(LevelFilter::DEBUG)))
+            }
+            Some(Err(error)) => {
+                println!("Unable to export spans: {}", error);
+                (None, None)
+            }
+            None => (None, None),
+        };
+
+        // Initialize tracing with a log level that can be changed later
+        let (filter, handle) = reload::Layer::new(log_level);
+        tracing_subscriber::registry()
+            .with(output.with_filter(filter))
+            .with(spans)
+            .init();
+
+        // Start exporting the spans
+        if let Some(exporter) = exporter {
+            tokio::spawn(exporter.run());
+        }
+        LogLevel { handle }
+    }
+
+    /// A helper function to convert a string to a log level
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the string is not a log level.
+    ///
+    fn parse_level(level: &str) -> Result<LevelFilter> {
+        match level {
+            "Trace" => Ok(LevelFilter::TRACE),
+            "Debug" => Ok(LevelFilter::DEBUG),
+            "Info" => Ok(LevelFilter::INFO),
+            "Warn" => Ok(LevelFilter::WARN),
+            "Error" => Ok(LevelFilter::ERROR),
+            _ => Err(ErrorCode::InvalidLogLevel {
+                level: level.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// A structure to change the log level while the program is running
+///
+#[derive(Clone)]
+pub struct LogLevel {
+    handle: reload::Handle<LevelFilter, Registry>, // the handle to the log level filter
+}
+
+// Implement the log level features
+impl LogLevel {
+    /// A function to create a handle that is not connected to any logs, for
+    /// tests
+    ///
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        let (_filter, handle) = reload::Layer::new(LevelFilter::INFO);
+        LogLevel { handle }
+    }
+
+    /// A method to change the log level
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the string is not a log level or
+    /// the logging system is no longer running.
+    ///
+    pub fn set(&self, level: &str) -> Result<()> {
+        let level = Logging::parse_level(level)?;
+        self.handle
+            .reload(level)
+            .map_err(|error| anyhow!("Unable to change the log level: {}.", error))
+    }
+}
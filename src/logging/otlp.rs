@@ -0,0 +1,453 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to export the request spans to an OpenTelemetry collector over
+//! OTLP/HTTP (as JSON). Each span keeps the trace of its parent, so a web
+//! request, the work of the system interface, and the DMX write share one
+//! trace even across the channels between them.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use crate::webhooks::Webhooks;
+
+// Import standard library features
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Import Tokio and warp features
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use warp::http::Uri;
+
+// Import tracing features
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber, info, warn};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+// Import JSON features
+use serde_json::{Value, json};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the number of finished spans waiting to be exported
+const SPAN_QUEUE: usize = 2048;
+
+// Define the most spans in one export
+const MAX_BATCH: usize = 512;
+
+// Define the time to collect more spans before each export
+const EXPORT_DELAY: Duration = Duration::from_secs(1);
+
+// Define the most events kept for one span
+const MAX_EVENTS: usize = 32;
+
+// Define the kinds of spans (root spans are the requests from clients)
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_SERVER: u8 = 2;
+
+// Define the status of spans with an error event
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 2;
+
+/// A structure to hold one span until it is exported
+///
+struct SpanData {
+    trace_id: u128,         // the trace of the span (shared with its parent)
+    span_id: u64,           // the unique id of the span
+    parent_id: Option<u64>, // the id of the parent span, if any
+    name: &'static str,     // the name of the span
+    start: SystemTime,      // the time the span was created
+    end: SystemTime,        // the time the span closed
+    attributes: Vec<(&'static str, Value)>, // the fields of the span, as OTLP values
+    events: Vec<SpanEvent>, // the events within the span, oldest first
+    is_error: bool,         // a flag to indicate there was an error event
+}
+
+/// A structure to hold one event within a span
+///
+struct SpanEvent {
+    time: SystemTime,                       // the time of the event
+    name: String,                           // the message of the event
+    attributes: Vec<(&'static str, Value)>, // the other fields of the event, as OTLP values
+}
+
+/// A structure to record each span and pass it to the exporter once it
+/// closes
+///
+pub struct OtlpLayer {
+    spans: mpsc::Sender<SpanData>, // the send line to the exporter
+    dropped: Arc<AtomicU64>,       // the number of spans dropped because the exporter fell behind
+}
+
+/// A structure to post the finished spans to the collector in batches
+///
+pub struct OtlpExporter {
+    uri: Uri,                        // the location to post the spans
+    service_name: String,            // the name of the service in the traces
+    spans: mpsc::Receiver<SpanData>, // the receiving line for finished spans
+    dropped: Arc<AtomicU64>,         // the number of spans dropped since the last export
+}
+
+// Implement key features of the exporter
+impl OtlpExporter {
+    /// A function to create a new exporter and the layer that feeds it
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the endpoint is not a complete
+    /// http url.
+    ///
+    pub fn new(config: &OtlpConfig) -> Result<(Self, OtlpLayer)> {
+        // Check the location of the collector
+        let uri = Webhooks::parse_url(&format!(
+            "{}/v1/traces",
+            config.endpoint.trim_end_matches('/')
+        ))?;
+
+        // Connect the layer to the exporter
+        let (send, spans) = mpsc::channel(SPAN_QUEUE);
+        let dropped = Arc::new(AtomicU64::new(0));
+        Ok((
+            Self {
+                uri,
+                service_name: config
+                    .service_name
+                    .clone()
+                    .unwrap_or_else(|| "vulcan".to_string()),
+                spans,
+                dropped: dropped.clone(),
+            },
+            OtlpLayer {
+                spans: send,
+                dropped,
+            },
+        ))
+    }
+
+    /// A method to export the spans as they finish, until the program
+    /// closes. A collector that can't be reached is only noted once, until
+    /// it recovers.
+    ///
+    pub async fn run(mut self) {
+        let mut is_failing = false;
+        while let Some(span) = self.spans.recv().await {
+            // Collect the spans that follow shortly after
+            sleep(EXPORT_DELAY).await;
+            let mut batch = vec![span];
+            while batch.len() < MAX_BATCH
+                && let Ok(span) = self.spans.try_recv()
+            {
+                batch.push(span);
+            }
+
+            // Note any spans that were dropped
+            let dropped = self.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                warn!(
+                    "Dropped {} spans before the export to {}.",
+                    dropped, self.uri
+                );
+            }
+
+            // Post the batch to the collector
+            let body = compose(&batch, &self.service_name).to_string();
+            match Webhooks::post(&self.uri, &body).await {
+                Ok(()) if is_failing => {
+                    info!("Resumed the export of spans to {}.", self.uri);
+                    is_failing = false;
+                }
+                Ok(()) => (),
+                Err(error) if !is_failing => {
+                    warn!("Unable to export spans to {}: {}", self.uri, error);
+                    is_failing = true;
+                }
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+// Implement the layer to record the spans
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let Some(span) = context.span(id) else {
+            return;
+        };
+
+        // Join the trace of the parent, if there is one
+        let parent = if let Some(parent) = attributes.parent() {
+            context.span(parent)
+        } else if attributes.is_contextual() {
+            context.lookup_current()
+        } else {
+            None
+        };
+        let parent = parent.and_then(|parent| {
+            parent
+                .extensions()
+                .get::<SpanData>()
+                .map(|data| (data.trace_id, data.span_id))
+        });
+        let trace_id = match parent {
+            Some((trace_id, _)) => trace_id,
+            None => ((random_id() as u128) << 64) | random_id() as u128,
+        };
+
+        // Record the span and its fields
+        let mut visitor = AttributeVisitor::default();
+        attributes.record(&mut visitor);
+        let now = SystemTime::now();
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: random_id(),
+            parent_id: parent.map(|(_, span_id)| span_id),
+            name: attributes.metadata().name(),
+            start: now,
+            end: now,
+            attributes: visitor.attributes,
+            events: Vec::new(),
+            is_error: false,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, context: Context<'_, S>) {
+        if let Some(span) = context.span(id)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            let mut visitor = AttributeVisitor::default();
+            values.record(&mut visitor);
+            data.attributes.extend(visitor.attributes);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, context: Context<'_, S>) {
+        let Some(span) = context.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+
+        // Note an error, and keep the event if there is room
+        let level = *event.metadata().level();
+        data.is_error |= level == Level::ERROR;
+        if data.events.len() < MAX_EVENTS {
+            let mut visitor = AttributeVisitor::default();
+            event.record(&mut visitor);
+            let mut attributes = visitor.attributes;
+            attributes.push(("level", json!({ "stringValue": level.as_str() })));
+            data.events.push(SpanEvent {
+                time: SystemTime::now(),
+                name: visitor.message.unwrap_or_default(),
+                attributes,
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, context: Context<'_, S>) {
+        let Some(span) = context.span(&id) else {
+            return;
+        };
+        let Some(mut data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+
+        // Pass the span to the exporter (or drop it, if the exporter fell behind)
+        data.end = SystemTime::now();
+        if self.spans.try_send(data).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A helper structure to collect the fields of a span or event as OTLP
+/// values, apart from the message
+///
+#[derive(Default)]
+struct AttributeVisitor {
+    attributes: Vec<(&'static str, Value)>, // the fields collected so far
+    message: Option<String>,                // the message, if any
+}
+
+// Implement key features of the attribute visitor
+impl AttributeVisitor {
+    /// A helper method to add one field
+    ///
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.attributes.push((field.name(), value));
+    }
+}
+
+// Implement the field visitor
+impl Visit for AttributeVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!({ "boolValue": value }));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, json!({ "stringValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.insert(field, json!({ "stringValue": format!("{:?}", value) }));
+        }
+    }
+}
+
+/// A helper function to compose the body of an export, in the JSON
+/// encoding of OTLP
+///
+fn compose(spans: &[SpanData], service_name: &str) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            json!({
+                "traceId": format!("{:032x}", span.trace_id),
+                "spanId": format!("{:016x}", span.span_id),
+                "parentSpanId": span.parent_id.map(|id| format!("{:016x}", id)).unwrap_or_default(),
+                "name": span.name,
+                "kind": if span.parent_id.is_some() { SPAN_KIND_INTERNAL } else { SPAN_KIND_SERVER },
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+                "attributes": compose_attributes(&span.attributes),
+                "events": span.events.iter().map(|event| json!({
+                    "timeUnixNano": unix_nanos(event.time),
+                    "name": event.name,
+                    "attributes": compose_attributes(&event.attributes),
+                })).collect::<Vec<Value>>(),
+                "status": { "code": if span.is_error { STATUS_ERROR } else { STATUS_OK } },
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "vulcan", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// A helper function to compose a list of attributes
+///
+fn compose_attributes(attributes: &[(&'static str, Value)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": value }))
+        .collect()
+}
+
+/// A helper function to express a time in nanoseconds since the unix epoch
+/// (as a string, like other 64-bit integers in OTLP)
+///
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0)
+        .to_string()
+}
+
+/// A helper function to create a random, nonzero id
+///
+fn random_id() -> u64 {
+    RandomState::new().hash_one(0u8).max(1)
+}
+
+// Tests of the OTLP module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::debug_span;
+    use tracing_subscriber::prelude::*;
+
+    // Test that child spans join the trace of their parent
+    #[test]
+    fn record_spans() {
+        // Record a request with a nested span and an event
+        let (spans, mut receive) = mpsc::channel(SPAN_QUEUE);
+        let layer = OtlpLayer {
+            spans,
+            dropped: Arc::default(),
+        };
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let request = debug_span!("request", id = 7, kind = "fade");
+            let _guard = request.enter();
+            let write = debug_span!("write");
+            write.in_scope(|| tracing::error!(channel = 4, "Late frame."));
+        });
+
+        // The nested span closes first, within the same trace
+        let write = receive.try_recv().unwrap();
+        let request = receive.try_recv().unwrap();
+        assert_eq!(write.trace_id, request.trace_id);
+        assert_eq!(write.parent_id, Some(request.span_id));
+        assert_eq!(request.parent_id, None);
+        assert!(write.is_error && !request.is_error);
+
+        // Compose the export
+        let body = compose(&[request, write], "vulcan");
+        let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["name"], "request");
+        assert_eq!(spans[0]["kind"], SPAN_KIND_SERVER);
+        assert_eq!(spans[0]["parentSpanId"], "");
+        assert_eq!(
+            spans[0]["attributes"],
+            json!([
+                { "key": "id", "value": { "intValue": "7" } },
+                { "key": "kind", "value": { "stringValue": "fade" } },
+            ])
+        );
+        assert_eq!(spans[1]["events"][0]["name"], "Late frame.");
+        assert_eq!(
+            spans[1]["events"][0]["attributes"][0],
+            json!({ "key": "channel", "value": { "intValue": "4" } })
+        );
+        assert_eq!(spans[1]["status"]["code"], STATUS_ERROR);
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to write the logs to a file which is rotated when it grows
+//! too large or too old. Rotated files are renamed with a numbered suffix
+//! (log.1 is the most recent) and the oldest files are removed.
+
+// Import standard library features
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Import tracing features
+use tracing_subscriber::fmt::MakeWriter;
+
+/// A structure to write to a log file and rotate it as needed. The file is
+/// shared by every writer made from this structure.
+///
+#[derive(Clone)]
+pub struct RotatingFile {
+    state: Arc<Mutex<FileState>>, // the current file and its rotation settings
+}
+
+/// A helper structure to hold the current log file
+///
+struct FileState {
+    path: PathBuf,     // the location of the current log file
+    file: File,        // the current log file
+    size: u64,         // the current size of the log file, in bytes
+    opened: Instant,   // the time the current log file was opened
+    max_size: u64,     // the size that triggers a rotation (zero to disable)
+    max_age: Duration, // the age that triggers a rotation (zero to disable)
+    max_files: usize,  // the number of rotated files to keep
+}
+
+// Implement the rotating file features
+impl RotatingFile {
+    /// A function to open (or create) the log file with the provided
+    /// rotation settings
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the log file cannot be opened.
+    ///
+    pub fn new(
+        path: &Path,
+        max_size: u64,
+        max_age: Duration,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        // Open the log file and note its current size
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        // Return the new rotating file
+        Ok(Self {
+            state: Arc::new(Mutex::new(FileState {
+                path: path.to_path_buf(),
+                file,
+                size,
+                opened: Instant::now(),
+                max_size,
+                max_age,
+                max_files,
+            })),
+        })
+    }
+}
+
+// Implement the file state features
+impl FileState {
+    /// A method to check whether the log file should be rotated before the
+    /// next write
+    ///
+    fn is_rotation_due(&self) -> bool {
+        ((self.max_size > 0) && (self.size >= self.max_size))
+            || (!self.max_age.is_zero() && (self.opened.elapsed() >= self.max_age))
+    }
+
+    /// A method to rename the log file (and the older log files), remove the
+    /// oldest log file, and open a new log file
+    ///
+    fn rotate(&mut self) -> io::Result<()> {
+        // Make sure everything has been written to the current file
+        self.file.flush()?;
+
+        // Shift the older files back by one, dropping the oldest
+        if self.max_files > 0 {
+            fs::remove_file(self.numbered(self.max_files)).unwrap_or(());
+            for number in (1..self.max_files).rev() {
+                fs::rename(self.numbered(number), self.numbered(number + 1)).unwrap_or(());
+            }
+            fs::rename(&self.path, self.numbered(1))?;
+
+        // If no files are kept, just discard the current file
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        // Open the new log file
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+
+    /// A helper method to return the path of a rotated log file
+    ///
+    fn numbered(&self, number: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", number));
+        PathBuf::from(path)
+    }
+}
+
+// Implement the writer for the tracing subscriber
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// Implement writing to the log file
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Lock the log file (recovering from a panic in another writer)
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Rotate the file if needed (keep writing to the old file on failure)
+        if state.is_rotation_due()
+            && let Err(error) = state.rotate()
+        {
+            eprintln!("Unable to rotate log file: {}.", error);
+            state.opened = Instant::now();
+        }
+
+        // Write to the file and update the size
+        let written = state.file.write(buf)?;
+        state.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .file
+            .flush()
+    }
+}
+
+// Tests of the rotating file module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the log file is rotated once it is too large
+    #[test]
+    fn rotate_by_size() {
+        // Create a fresh directory for the log files
+        let directory = std::env::temp_dir().join(format!("vulcan-log-{}", std::process::id()));
+        fs::remove_dir_all(&directory).unwrap_or(());
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("vulcan.log");
+
+        // Write more lines than fit in one file
+        let mut file = RotatingFile::new(&path, 10, Duration::ZERO, 2).unwrap();
+        for line in [
+            "first line\n",
+            "second line\n",
+            "third line\n",
+            "fourth line\n",
+        ] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        // Check that only the newest files were kept
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth line\n");
+        assert_eq!(
+            fs::read_to_string(directory.join("vulcan.log.1")).unwrap(),
+            "third line\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("vulcan.log.2")).unwrap(),
+            "second line\n"
+        );
+        assert!(!directory.join("vulcan.log.3").exists());
+        fs::remove_dir_all(&directory).unwrap_or(());
+    }
+}
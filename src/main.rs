@@ -25,50 +25,208 @@
 extern crate serde;
 
 // Define program modules
-#[macro_use]
-mod definitions;
+mod logging;
+mod network;
+mod remote;
 mod system_interface;
+mod trigger_interface;
 mod web_interface;
+mod webhooks;
+
+// Import the definitions from the core library
+use vulcan_core::definitions;
 
 // Import crate definitions
 use crate::definitions::*;
 
 // Import other structures into this module
-use self::system_interface::SystemInterface;
+use self::logging::Logging;
+use self::network::{Bridge, NetworkInterface};
+use self::remote::{Remote, RemoteCommand};
+use self::system_interface::{SystemInterface, Systemd};
+use self::trigger_interface::TriggerInterface;
 use self::web_interface::WebInterface;
+use self::webhooks::Webhooks;
 
 // Import standard library features
 use std::path::PathBuf;
+use std::time::Duration;
 
 // Import anyhow features
 #[macro_use]
 extern crate anyhow;
 
 // Import tracing features
-use tracing_subscriber::filter::LevelFilter;
+use tracing::{error, info, warn};
+
+// Import the core library features
+use vulcan_core::{DryRunDriver, EnttecUsbPro};
+
+// Import Tokio features
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::oneshot;
+
+// Import nix signal features
+use nix::sys::signal::{SigSet, Signal};
 
 // Import clap features
 use clap::Parser;
 
-/// Struct to hold the optional arguments for Minerva
-#[derive(Parser, Debug)]
+/// Struct to hold the optional arguments for Vulcan. Any argument provided
+/// overrides the matching field of the configuration file.
+#[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Arguments {
+    /// Configuration file (YAML) with the full configuration
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
     /// Hardware address for the DMX connection
     #[arg(short, long)]
-    path: PathBuf,
+    path: Option<PathBuf>,
+
+    /// Simulate the output without writing to the DMX hardware
+    #[arg(long)]
+    dry_run: bool,
 
-    /// Address for the web interface
-    #[arg(short, long, default_value = DEFAULT_ADDRESS)]
-    address: String,
+    /// Serve the state of the output but refuse every change from clients
+    #[arg(long)]
+    read_only: bool,
+
+    /// Address for the web interface [default: 127.0.0.1:8852]
+    #[arg(short, long)]
+    address: Option<String>,
 
     /// Address for the backup server
-    #[arg(short, long, default_value = None)]
+    #[arg(short, long)]
     backup: Option<String>,
 
-    /// Flag to set the log level
-    #[arg(short, long, default_value = DEFAULT_LOGLEVEL)]
-    log_level: String,
+    /// Name of this instance in the backup keys [default: the address]
+    #[arg(long)]
+    instance_id: Option<String>,
+
+    /// Flag to set the log level [default: Info]
+    #[arg(short, long)]
+    log_level: Option<String>,
+
+    /// Format of the log output [default: text]
+    #[arg(long, value_enum)]
+    log_format: Option<LogFormat>,
+
+    /// File for the log output, which is rotated as it grows
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Time between fade updates, in milliseconds [default: 50]
+    #[arg(short, long)]
+    resolution: Option<u64>,
+
+    /// Behavior of the output when the program closes [default: hold]
+    #[arg(short, long, value_enum)]
+    exit_behavior: Option<ExitBehavior>,
+
+    /// Time to apply the exit behavior before closing, in seconds [default: 0]
+    #[arg(short = 't', long)]
+    exit_time: Option<u64>,
+
+    /// Unix socket to expose the web interface on, in addition to the address
+    #[arg(short, long)]
+    socket: Option<PathBuf>,
+
+    /// Directory of a custom web UI to serve instead of the bundled UI
+    #[arg(short, long)]
+    web_root: Option<PathBuf>,
+
+    /// Token for the commands sent to a running instance
+    #[arg(long, env = "VULCAN_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Command to send to a running instance, instead of starting Vulcan
+    #[command(subcommand)]
+    command: Option<RemoteCommand>,
+}
+
+// Implement the conversion of the arguments
+impl Arguments {
+    /// A method to load the configuration file (if provided) and override
+    /// any fields that were specified on the commandline
+    ///
+    fn to_config(&self) -> anyhow::Result<Config> {
+        // Load the configuration file or the default configuration
+        let mut config = match &self.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        // Override the fields that were provided
+        if let Some(path) = self.path.clone() {
+            config.path = Some(path);
+        }
+        if self.dry_run {
+            config.dry_run = true;
+        }
+        if self.read_only {
+            config.read_only = true;
+        }
+        if let Some(address) = self.address.clone() {
+            config.address = address;
+        }
+        if let Some(backup) = self.backup.clone() {
+            config.backup = Some(backup);
+        }
+        if let Some(instance_id) = self.instance_id.clone() {
+            config.instance_id = Some(instance_id);
+        }
+        if let Some(log_level) = self.log_level.clone() {
+            config.log_level = log_level;
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        if let Some(log_file) = self.log_file.clone() {
+            config.log_file = Some(log_file);
+        }
+        if let Some(resolution) = self.resolution {
+            config.resolution = resolution;
+        }
+        if let Some(exit_behavior) = self.exit_behavior {
+            config.exit_behavior = exit_behavior;
+        }
+        if let Some(exit_time) = self.exit_time {
+            config.exit_time = exit_time;
+        }
+        if let Some(socket) = self.socket.clone() {
+            config.socket = Some(socket);
+        }
+        if let Some(web_root) = self.web_root.clone() {
+            config.web_root = Some(web_root);
+        }
+
+        // Make sure the hardware address was specified somewhere (unless
+        // this is a dry run or a bridge without hardware)
+        let has_hardware = config.bridge.as_ref().is_none_or(|bridge| bridge.hardware);
+        if config.path.is_none() && !config.dry_run && has_hardware {
+            return Err(anyhow!(
+                "A path to the DMX hardware must be specified with '--path' or in the configuration file (or use '--dry-run')."
+            ));
+        }
+
+        // Return the completed configuration
+        Ok(config)
+    }
+
+    /// A method to find the address of the running instance for a command,
+    /// from the arguments or the configuration file
+    ///
+    fn to_remote_address(&self) -> anyhow::Result<String> {
+        if let Some(address) = self.address.clone() {
+            return Ok(address);
+        }
+        match &self.config {
+            Some(path) => Ok(Config::from_file(path)?.address),
+            None => Ok(Config::default().address),
+        }
+    }
 }
 
 /// The Vulcan structure to contain the program launching and overall
@@ -78,53 +236,168 @@ struct Vulcan;
 
 // Implement the Vulcan functionality
 impl Vulcan {
-    /// A function to setup the logging configuration
+    /// A function to block the shutdown signals on the current thread. Every
+    /// thread spawned afterwards inherits the mask, so the signals are only
+    /// received where they are explicitly waited for.
     ///
-    fn setup_logging(log_string: String) {
-        // Try to convert the string to a log level
-        let log_level = match log_string.as_str() {
-            "Trace" => LevelFilter::TRACE,
-            "Debug" => LevelFilter::DEBUG,
-            "Info" => LevelFilter::INFO,
-            "Warn" => LevelFilter::WARN,
-            "Error" => LevelFilter::ERROR,
-
-            // Otherwise, print a nice error
-            _ => {
-                println!(
-                    "Unable to parse parameter for option 'logLevel'. Options are Trace, Debug, Info, Warn, and Error."
-                );
-                LevelFilter::INFO
+    fn block_signals() -> SigSet {
+        // Collect the shutdown and reload signals
+        let mut signals = SigSet::empty();
+        signals.add(Signal::SIGINT);
+        signals.add(Signal::SIGTERM);
+        signals.add(Signal::SIGHUP);
+
+        // Block them for this thread and all future threads
+        signals
+            .thread_block()
+            .expect("Unable to block the shutdown signals.");
+        signals
+    }
+
+    /// A function to wait for signals on a dedicated thread and route them
+    /// through the normal requests. A hangup signal reloads the configuration.
+    /// The first shutdown signal closes the program normally, and a second
+    /// shutdown signal closes the program immediately.
+    ///
+    fn listen_for_signals(signals: SigSet, web_send: WebSend) {
+        // Get a handle to the current runtime
+        let handle = Handle::current();
+
+        // Wait for signals in a new thread
+        std::thread::spawn(move || {
+            let mut is_closing = false;
+            loop {
+                match signals.wait() {
+                    // If this is a hangup, reload the configuration
+                    Ok(Signal::SIGHUP) => {
+                        info!("Received SIGHUP. Reloading configuration ...");
+                        let (reply_to, _) = oneshot::channel();
+                        handle.block_on(web_send.send(reply_to, Request::ReloadConfig));
+                    }
+
+                    // If this is the first signal, close normally
+                    Ok(signal) if !is_closing => {
+                        info!("Received {}. Closing ...", signal);
+                        is_closing = true;
+                        let (reply_to, _) = oneshot::channel();
+                        handle.block_on(web_send.send(reply_to, Request::Close));
+                    }
+
+                    // Otherwise, close immediately
+                    Ok(signal) => {
+                        warn!("Received {} while closing. Exiting now.", signal);
+                        std::process::exit(1);
+                    }
+
+                    // Note any errors
+                    Err(error) => {
+                        error!("Unable to wait for signals: {}.", error);
+                        return;
+                    }
+                }
             }
+        });
+    }
+
+    /// A function to run a bridge instead of the main program, until a
+    /// shutdown signal arrives
+    ///
+    async fn run_bridge(config: Config, bridge: BridgeConfig, signals: SigSet) {
+        // Wait for a shutdown signal (ignoring any hangup) on another thread
+        let close = async move {
+            tokio::task::spawn_blocking(move || {
+                while let Ok(Signal::SIGHUP) = signals.wait() {
+                    info!("The bridge has nothing to reload.");
+                }
+            })
+            .await
+            .unwrap_or(());
+            info!("Closing the bridge ...");
         };
 
-        // Initialize tracing
-        tracing_subscriber::fmt()
-            .with_max_level(log_level)
-            .with_target(false)
-            .init();
+        // Open the DMX hardware (or not, if this is a dry run or there is no
+        // hardware) and run the bridge
+        let resolution = Duration::from_millis(config.resolution);
+        Systemd::notify("READY=1");
+        if !bridge.hardware {
+            Bridge::new(DryRunDriver, bridge, config.art_net, resolution)
+                .run(close)
+                .await;
+        } else if config.dry_run {
+            warn!("Dry run. The DMX hardware will not be written.");
+            Bridge::new(DryRunDriver, bridge, config.art_net, resolution)
+                .run(close)
+                .await;
+        } else {
+            let path = config.path.clone().unwrap_or_default();
+            match EnttecUsbPro::open(&path) {
+                Ok(driver) => {
+                    Bridge::new(driver, bridge, config.art_net, resolution)
+                        .run(close)
+                        .await
+                }
+                Err(error) => error!("Unable to open the DMX hardware: {}.", error),
+            }
+        }
     }
 
     /// A function to build the main program and the web interface
     ///
-    async fn run(arguments: Arguments) {
-        // Initialize logging (guard is held until the end of run())
-        let _guard = Vulcan::setup_logging(arguments.log_level);
+    async fn run(arguments: Arguments, config: Config, signals: SigSet) {
+        // Initialize logging
+        let log_level = Logging::setup(&config);
+
+        // If this is a bridge, only run the bridge
+        if let Some(bridge) = config.bridge.clone() {
+            Vulcan::run_bridge(config, bridge, signals).await;
+            return;
+        }
+
+        // Start sending events to any webhooks
+        let events = EventSend::new();
+        let webhooks = Webhooks::new(config.webhooks.clone(), events.subscribe());
+        tokio::spawn(webhooks.run());
 
         // Launch the system interface to connect and control the DMX signals
+        let web_config = config.clone();
+        let reload = Box::new(move || arguments.to_config());
         let (system_interface, web_send) =
-            SystemInterface::new(arguments.path, arguments.address.clone(), arguments.backup)
+            SystemInterface::new(config, reload, log_level, events.clone())
                 .await
                 .expect("Unable to create the System Interface.");
 
+        // Route any shutdown signals to the system interface
+        Vulcan::listen_for_signals(signals, web_send.clone());
+
+        // Listen for any external triggers
+        TriggerInterface::start(
+            web_send.clone(),
+            system_interface.dmx_controller(),
+            &web_config,
+        );
+
+        // Present the program on any show networks
+        NetworkInterface::start(system_interface.dmx_controller(), &web_config);
+
         // Create the web interface
-        let mut web_interface = WebInterface::new(web_send, arguments.address);
+        let mut web_interface = WebInterface::new(
+            web_send,
+            web_config,
+            events,
+            system_interface.dmx_controller(),
+        );
 
         // Run the web interface in a new thread
+        let (ready, is_ready) = oneshot::channel();
         tokio::spawn(async move {
-            web_interface.run().await;
+            web_interface.run(ready).await;
         });
 
+        // Once the web interface is listening, notify systemd
+        if is_ready.await.is_ok() {
+            Systemd::notify("READY=1");
+        }
+
         // Block on the system interface
         system_interface.run().await;
     }
@@ -132,11 +405,37 @@ impl Vulcan {
 
 /// The main function of the program, simplified to as high a level as possible.
 ///
-#[tokio::main]
-async fn main() {
-    // Get the commandline arguments
+fn main() {
+    // Get the commandline arguments and the configuration
     let arguments = Arguments::parse();
 
+    // If there is a command, send it to the running instance and exit
+    if let Some(command) = arguments.command.clone() {
+        let result = arguments.to_remote_address().and_then(|address| {
+            let remote = Remote::new(address, arguments.token.clone());
+            Runtime::new()?.block_on(remote.run(command))
+        });
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Otherwise, load the configuration
+    let config = match arguments.to_config() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // Block the shutdown signals before any runtime threads are started
+    let signals = Vulcan::block_signals();
+
     // Create the program and run until directed otherwise
-    Vulcan::run(arguments).await;
+    Runtime::new()
+        .expect("Unable to create the runtime.")
+        .block_on(Vulcan::run(arguments, config, signals));
 }
@@ -0,0 +1,424 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements an Art-Net node that answers each ArtPoll with an
+//! ArtPollReply, so that Vulcan appears in the network views of other
+//! consoles with its name, its universe, and the state of its output. It
+//! also reads the ArtDmx packets of other consoles.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+// Import standard library features
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::net::UdpSocket;
+use tokio::time::interval;
+
+// Import tracing features
+use tracing::{debug, error, info};
+
+// Define the Art-Net packet constants
+const ART_NET_ID: &[u8; 8] = b"Art-Net\0"; // the header of every Art-Net packet
+const ART_NET_PORT: u16 = 6454; // the udp port of every Art-Net node
+const OP_POLL: u16 = 0x2000; // the opcode of a poll from a controller
+const OP_POLL_REPLY: u16 = 0x2100; // the opcode of a reply to a poll
+const OP_DMX: u16 = 0x5000; // the opcode of a packet of DMX values
+const POLL_REPLY_LENGTH: usize = 239; // the length of a reply to a poll
+pub const MAX_PACKET: usize = 1024; // the largest Art-Net packet that will be read
+
+// Define the poll flags
+const REPLY_ON_CHANGE: u8 = 0x02; // the flag to send a reply whenever the node changes
+const TARGETED: u8 = 0x20; // the flag to only reply within the range of port-addresses
+
+// Define the node constants
+const MAX_CONTROLLERS: usize = 8; // the most controllers notified of changes
+const STATUS_INTERVAL: Duration = Duration::from_secs(1); // the time between checks of the output
+const STATUS_TIMEOUT: Duration = Duration::from_millis(100); // the time to wait for the state of the output
+
+/// A structure to answer the polls of Art-Net controllers
+///
+pub struct ArtNetNode {
+    dmx_interface: DmxController, // the controller to check the state of the output
+    config: ArtNetConfig,         // the name and universe of the node
+    refresh_rate: u16,            // the frames written each second
+    replies: u32,                 // the number of replies sent, for the node report
+}
+
+// Implement key features of the Art-Net node
+impl ArtNetNode {
+    /// A function to create a new Art-Net node, with the fade resolution
+    /// (in ms) of the output
+    ///
+    pub fn new(dmx_interface: DmxController, config: ArtNetConfig, resolution: u64) -> Self {
+        Self {
+            dmx_interface,
+            config,
+            refresh_rate: (1000 / resolution.max(1)).min(u16::MAX as u64) as u16,
+            replies: 0,
+        }
+    }
+
+    /// A method to answer polls until the program closes. Controllers that
+    /// ask to be told of changes are sent a new reply whenever the output
+    /// starts or stops.
+    ///
+    pub async fn run(mut self) {
+        // Try to bind to the address
+        let socket = match UdpSocket::bind(&self.config.address).await {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!(
+                    "Unable to listen for Art-Net at {}: {}.",
+                    self.config.address, error
+                );
+                return;
+            }
+        };
+        info!("Listening for Art-Net on {}.", self.config.address);
+
+        // Read each packet and check the output regularly
+        let mut buffer = vec![0; MAX_PACKET];
+        let mut controllers: Vec<SocketAddr> = Vec::new();
+        let mut is_writing = self.is_writing().await;
+        let mut checks = interval(STATUS_INTERVAL);
+        loop {
+            tokio::select! {
+                // Answer any poll
+                received = socket.recv_from(&mut buffer) => {
+                    let (length, source) = match received {
+                        Ok(received) => received,
+                        Err(error) => {
+                            error!("Unable to read Art-Net packet: {}.", error);
+                            continue;
+                        }
+                    };
+                    let Some(poll) = parse_poll(&buffer[..length]) else {
+                        continue;
+                    };
+                    if !poll.includes(self.config.universe) {
+                        continue;
+                    }
+
+                    // Note the controller if it wants every change
+                    if poll.reply_on_change && !controllers.contains(&source) {
+                        if controllers.len() >= MAX_CONTROLLERS {
+                            controllers.remove(0);
+                        }
+                        controllers.push(source);
+                    }
+                    is_writing = self.is_writing().await;
+                    self.reply(&socket, source, is_writing).await;
+                }
+
+                // Tell the controllers if the output started or stopped
+                _ = checks.tick() => {
+                    let was_writing = is_writing;
+                    is_writing = self.is_writing().await;
+                    if is_writing != was_writing {
+                        for controller in controllers.clone() {
+                            self.reply(&socket, controller, is_writing).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// A helper method to check that the output is reaching the hardware
+    ///
+    async fn is_writing(&self) -> bool {
+        self.dmx_interface
+            .get_status(STATUS_TIMEOUT)
+            .await
+            .is_some_and(|status| status.is_writing)
+    }
+
+    /// A helper method to send a reply to a controller
+    ///
+    async fn reply(&mut self, socket: &UdpSocket, controller: SocketAddr, is_writing: bool) {
+        // Find the address of this node, as seen by the controller
+        let address = match socket.local_addr() {
+            Ok(SocketAddr::V4(local)) if !local.ip().is_unspecified() => *local.ip(),
+            _ => local_address(controller).unwrap_or(Ipv4Addr::UNSPECIFIED),
+        };
+
+        // Send the reply
+        self.replies = self.replies.wrapping_add(1);
+        let reply = encode_poll_reply(
+            &self.config,
+            address,
+            self.refresh_rate,
+            is_writing,
+            self.replies,
+        );
+        if let Err(error) = socket.send_to(&reply, controller).await {
+            debug!(
+                "Unable to reply to Art-Net poll from {}: {}.",
+                controller, error
+            );
+        }
+    }
+}
+
+/// A struct to describe a poll from a controller
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ArtPoll {
+    reply_on_change: bool, // a flag to indicate the controller wants every change
+    targets: Option<(u16, u16)>, // the lowest and highest port-addresses to reply for, if targeted
+}
+
+// Implement key features of the poll
+impl ArtPoll {
+    /// A method to check if the node should reply for the port-address
+    ///
+    pub fn includes(&self, universe: u16) -> bool {
+        self.targets
+            .is_none_or(|(bottom, top)| (bottom..=top).contains(&universe))
+    }
+}
+
+/// A helper function to read an ArtPoll, returning None if the packet is
+/// not a poll
+///
+pub fn parse_poll(packet: &[u8]) -> Option<ArtPoll> {
+    // Check the header and the opcode
+    if !packet.starts_with(ART_NET_ID) || packet.len() < 14 {
+        return None;
+    }
+    if u16::from_le_bytes([packet[8], packet[9]]) != OP_POLL {
+        return None;
+    }
+
+    // Read the flags and any targeted range (from Art-Net 4)
+    let flags = packet[12];
+    let targets = match packet.get(14..18) {
+        Some(range) if flags & TARGETED != 0 => Some((
+            u16::from_be_bytes([range[2], range[3]]),
+            u16::from_be_bytes([range[0], range[1]]),
+        )),
+        _ => None,
+    };
+    Some(ArtPoll {
+        reply_on_change: flags & REPLY_ON_CHANGE != 0,
+        targets,
+    })
+}
+
+/// A struct to describe a packet of DMX values from a console
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArtDmx {
+    pub universe: u16,   // the port-address of the values
+    pub sequence: u8,    // the sequence number of the packet (zero if not used)
+    pub frame: Universe, // the value of each channel in the packet
+}
+
+/// A helper function to read an ArtDmx packet, returning None if the packet
+/// is not a packet of DMX values
+///
+pub fn parse_dmx(packet: &[u8]) -> Option<ArtDmx> {
+    // Check the header and the opcode
+    if !packet.starts_with(ART_NET_ID) || packet.len() < 18 {
+        return None;
+    }
+    if u16::from_le_bytes([packet[8], packet[9]]) != OP_DMX {
+        return None;
+    }
+
+    // Read the port-address and the values of the channels
+    let length = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+    let mut frame = Universe::new();
+    for (index, value) in packet[18..]
+        .iter()
+        .take(length.min(DMX_MAX as usize))
+        .enumerate()
+    {
+        frame.set(index as u32 + 1, *value);
+    }
+    Some(ArtDmx {
+        universe: u16::from_le_bytes([packet[14], packet[15]]) & 0x7FFF,
+        sequence: packet[12],
+        frame,
+    })
+}
+
+/// A helper function to create an ArtDmx packet with every channel of the
+/// frame
+///
+pub fn encode_dmx(universe: u16, sequence: u8, frame: &Universe) -> Vec<u8> {
+    let mut packet = ART_NET_ID.to_vec();
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&[0, 14, sequence, 0]); // the version, sequence, and physical port
+    packet.extend_from_slice(&(universe & 0x7FFF).to_le_bytes());
+    packet.extend_from_slice(&(DMX_MAX as u16).to_be_bytes());
+    packet.extend_from_slice(&frame.as_bytes());
+    packet
+}
+
+/// A helper function to create an ArtPollReply for the node, with one
+/// output port for the universe of the DMX hardware
+///
+pub fn encode_poll_reply(
+    config: &ArtNetConfig,
+    address: Ipv4Addr,
+    refresh_rate: u16,
+    is_writing: bool,
+    replies: u32,
+) -> Vec<u8> {
+    // Write the header, the address, and the version
+    let mut reply = vec![0; POLL_REPLY_LENGTH];
+    reply[..8].copy_from_slice(ART_NET_ID);
+    reply[8..10].copy_from_slice(&OP_POLL_REPLY.to_le_bytes());
+    reply[10..14].copy_from_slice(&address.octets());
+    reply[14..16].copy_from_slice(&ART_NET_PORT.to_le_bytes());
+    let mut version = env!("CARGO_PKG_VERSION")
+        .split('.')
+        .map(|part| part.parse::<u8>().unwrap_or(0));
+    reply[16] = version.next().unwrap_or(0);
+    reply[17] = version.next().unwrap_or(0);
+
+    // Write the net and sub-net of the universe
+    reply[18] = ((config.universe >> 8) & 0x7F) as u8;
+    reply[19] = ((config.universe >> 4) & 0x0F) as u8;
+
+    // Note an unknown manufacturer, normal indicators, and a port-address
+    // set by the configuration
+    reply[20..22].copy_from_slice(&0x00FFu16.to_be_bytes());
+    reply[23] = 0xD0;
+
+    // Write the names and the report of the node
+    write_name(&mut reply[26..44], &config.short_name);
+    write_name(&mut reply[44..108], &config.long_name);
+    let report = if is_writing {
+        "Writing DMX."
+    } else {
+        "DMX hardware unavailable."
+    };
+    write_name(
+        &mut reply[108..172],
+        &format!("#0001 [{:04}] {}", replies % 10_000, report),
+    );
+
+    // Describe one DMX output port, with its universe and its state
+    reply[173] = 1;
+    reply[174] = 0x80; // an output of DMX512
+    reply[178] = 0x08; // no input
+    reply[182] = if is_writing { 0x80 } else { 0x00 };
+    reply[190] = (config.universe & 0x0F) as u8;
+
+    // Describe the node as a node with 15-bit port-addresses, bound to
+    // this address
+    reply[200] = 0x00;
+    reply[207..211].copy_from_slice(&address.octets());
+    reply[211] = 1;
+    reply[212] = 0x08;
+    reply[213] = 0xC0; // continuous output without RDM
+    reply[226..228].copy_from_slice(&refresh_rate.to_be_bytes());
+    reply
+}
+
+/// A helper function to write a name into a field, truncated to leave a
+/// terminating null
+///
+fn write_name(field: &mut [u8], name: &str) {
+    let length = name.len().min(field.len() - 1);
+    field[..length].copy_from_slice(&name.as_bytes()[..length]);
+}
+
+/// A helper function to find the address of the interface that reaches a
+/// controller
+///
+pub fn local_address(controller: SocketAddr) -> Option<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect(controller).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(address) => Some(address),
+        IpAddr::V6(_) => None,
+    }
+}
+
+// Tests of the Art-Net module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a targeted poll is read and the reply describes the node
+    #[test]
+    fn reply_to_poll() {
+        // Read a targeted poll for port-addresses 16 to 31
+        let mut poll = ART_NET_ID.to_vec();
+        poll.extend([
+            0x00,
+            0x20,
+            0,
+            14,
+            TARGETED | REPLY_ON_CHANGE,
+            0,
+            0,
+            31,
+            0,
+            16,
+        ]);
+        let poll = parse_poll(&poll).unwrap();
+        assert!(poll.reply_on_change);
+        assert!(poll.includes(0x13));
+        assert!(!poll.includes(0x00));
+        assert!(parse_poll(b"Art-Net\0\0\x50\0\x0e\0\0").is_none());
+
+        // Create a reply for universe 3 of sub-net 1 of net 2
+        let config = ArtNetConfig {
+            universe: 0x0213,
+            short_name: "A very long short name".into(),
+            ..ArtNetConfig::default()
+        };
+        let reply = encode_poll_reply(&config, Ipv4Addr::new(10, 0, 0, 5), 40, true, 12);
+        assert_eq!(reply.len(), POLL_REPLY_LENGTH);
+        assert_eq!(&reply[8..16], &[0x00, 0x21, 10, 0, 0, 5, 0x36, 0x19]);
+        assert_eq!((reply[18], reply[19], reply[190]), (2, 1, 3));
+        assert_eq!(&reply[26..44], b"A very long short\0");
+        assert!(reply[44..].starts_with(b"Vulcan DMX Controller\0"));
+        assert!(reply[108..].starts_with(b"#0001 [0012] Writing DMX.\0"));
+        assert_eq!((reply[173], reply[174], reply[182]), (1, 0x80, 0x80));
+        assert_eq!(&reply[226..228], &[0, 40]);
+
+        // Read the values of port-address 0x0213
+        let mut dmx = ART_NET_ID.to_vec();
+        dmx.extend([0x00, 0x50, 0, 14, 7, 0, 0x13, 0x02, 0, 2, 255, 9]);
+        let dmx = parse_dmx(&dmx).unwrap();
+        assert_eq!((dmx.universe, dmx.sequence), (0x0213, 7));
+        assert_eq!(
+            (dmx.frame.get(1), dmx.frame.get(2), dmx.frame.get(3)),
+            (255, 9, 0)
+        );
+        assert!(parse_dmx(&reply).is_none());
+
+        // Read back a packet with every channel
+        let mut frame = Universe::new();
+        frame.set(512, 4);
+        let packet = encode_dmx(0x0213, 8, &frame);
+        assert_eq!(packet.len(), 18 + 512);
+        let dmx = parse_dmx(&packet).unwrap();
+        assert_eq!((dmx.universe, dmx.sequence, dmx.frame), (0x0213, 8, frame));
+    }
+}
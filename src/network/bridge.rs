@@ -0,0 +1,430 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a bridge, which passes one universe from the show
+//! network straight to the DMX hardware (and to any other universes of the
+//! show network, in either protocol). The bridge writes the latest frame at
+//! the rate of the output, however fast (or slow) the frames arrive, so the
+//! same binary can serve as a simple network DMX node or gateway.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::art_net;
+use super::follower::FollowState;
+use super::sacn;
+
+// Import the core library features
+use vulcan_core::DmxDriver;
+
+// Import standard library features
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+// Import Tokio features
+use tokio::net::UdpSocket;
+use tokio::time::{MissedTickBehavior, interval};
+
+// Import tracing features
+use tracing::{debug, error, info, warn};
+
+/// A structure to receive a universe and write it to the DMX hardware and
+/// the outputs
+///
+pub struct Bridge<D: DmxDriver> {
+    driver: D,            // the driver of the DMX hardware
+    config: BridgeConfig, // the protocol and universe to receive
+    names: ArtNetConfig,  // the names of the node, for replies to Art-Net polls and sACN output
+    resolution: Duration, // the time between frames of the output
+    frame: Universe,      // the latest frame received
+    has_frame: bool,      // a flag to indicate a frame has been received
+    sources: FollowState, // the source of the sACN input
+    outputs: Vec<Output>, // the outputs to the show network
+    cid: [u8; 16],        // the unique identifier of the sACN output
+    is_writing: bool,     // a flag to indicate the last frame reached the hardware
+    replies: u32,         // the number of poll replies sent, for the node report
+}
+
+// Implement key features of the bridge
+impl<D: DmxDriver> Bridge<D> {
+    /// A function to create a new bridge to the driver. If provided, the
+    /// names of the Art-Net node are used in replies to polls.
+    ///
+    pub fn new(
+        driver: D,
+        config: BridgeConfig,
+        art_net: Option<ArtNetConfig>,
+        resolution: Duration,
+    ) -> Self {
+        let names = ArtNetConfig {
+            universe: config.universe,
+            ..art_net.unwrap_or_default()
+        };
+        let outputs = config
+            .outputs
+            .iter()
+            .filter_map(|output| Output::new(output.clone()))
+            .collect();
+        Self {
+            driver,
+            sources: FollowState::new(config.universe),
+            config,
+            cid: sacn::source_cid(&names.short_name),
+            names,
+            resolution,
+            frame: Universe::new(),
+            has_frame: false,
+            outputs,
+            is_writing: true,
+            replies: 0,
+        }
+    }
+
+    /// A method to pass the universe to the hardware and the outputs until
+    /// the close future completes
+    ///
+    /// # Errors
+    ///
+    /// This method will log an error and return if the sockets cannot be
+    /// opened.
+    ///
+    pub async fn run(mut self, close: impl Future<Output = ()>) {
+        // Open the socket for the protocol
+        let address = self
+            .config
+            .address
+            .clone()
+            .unwrap_or_else(|| match self.config.protocol {
+                BridgeProtocol::ArtNet => DEFAULT_ART_NET_ADDRESS.to_string(),
+                BridgeProtocol::Sacn => DEFAULT_FOLLOW_ADDRESS.to_string(),
+            });
+        let socket = match self.config.protocol {
+            BridgeProtocol::ArtNet => UdpSocket::bind(&address).await,
+            BridgeProtocol::Sacn => sacn::open_receiver(&address, self.config.universe),
+        };
+        let socket = match socket {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("Unable to listen for the bridge at {}: {}.", address, error);
+                return;
+            }
+        };
+        info!(
+            "Bridging {} universe {} from {} to the {} output.",
+            protocol_name(self.config.protocol),
+            self.config.universe,
+            address,
+            self.driver.name()
+        );
+
+        // Open a socket for the outputs
+        let output_socket = match open_sender().await {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("Unable to open a socket for the bridge: {}.", error);
+                return;
+            }
+        };
+        for output in self.outputs.iter() {
+            info!(
+                "Sending the bridge to {} universe {} at {}.",
+                protocol_name(output.config.protocol),
+                output.config.universe,
+                output.destination
+            );
+        }
+
+        // Write the latest frame at the rate of the output
+        let mut ticks = interval(self.resolution);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut buffer = vec![0; art_net::MAX_PACKET.max(sacn::MAX_PACKET)];
+        tokio::pin!(close);
+        loop {
+            tokio::select! {
+                // Read any packet from the network
+                received = socket.recv_from(&mut buffer) => match received {
+                    Ok((length, source)) => self.receive(&socket, &buffer[..length], source).await,
+                    Err(error) => error!("Unable to read bridge packet: {}.", error),
+                },
+
+                // Write the latest frame
+                _ = ticks.tick() => {
+                    self.write_frame().await;
+                    self.send_outputs(&output_socket).await;
+                }
+
+                // Stop when the program closes
+                _ = &mut close => return,
+            }
+        }
+    }
+
+    /// A helper method to read a packet, keeping any new frame and
+    /// answering any Art-Net poll
+    ///
+    async fn receive(&mut self, socket: &UdpSocket, packet: &[u8], source: SocketAddr) {
+        match self.config.protocol {
+            BridgeProtocol::ArtNet => {
+                // Keep the values of the universe
+                if let Some(dmx) = art_net::parse_dmx(packet) {
+                    if dmx.universe == self.config.universe {
+                        self.frame = dmx.frame;
+                        self.has_frame = true;
+                    }
+
+                // Or reply to a poll for the universe
+                } else if let Some(poll) = art_net::parse_poll(packet)
+                    && poll.includes(self.config.universe)
+                {
+                    self.reply(socket, source).await;
+                }
+            }
+            BridgeProtocol::Sacn => {
+                // Apply the changes from the current source
+                if let Some(data) = sacn::parse_data(packet)
+                    && let Some(changes) = self.sources.receive(data, source, Instant::now())
+                {
+                    for (channel, value) in changes {
+                        self.frame.set(channel, value);
+                    }
+                    self.has_frame = true;
+                }
+            }
+        }
+    }
+
+    /// A helper method to write the latest frame, noting when the hardware
+    /// stops (or starts) accepting frames
+    ///
+    async fn write_frame(&mut self) {
+        self.sources.expire(Instant::now());
+        match self.driver.write_frame(&self.frame, self.resolution).await {
+            Ok(()) if !self.is_writing => {
+                info!("The DMX hardware is accepting frames again.");
+                self.is_writing = true;
+            }
+            Err(error) if self.is_writing => {
+                warn!("Unable to write to the DMX hardware: {}.", error);
+                self.is_writing = false;
+            }
+            _ => (),
+        }
+    }
+
+    /// A helper method to send the latest frame to every output, once a
+    /// frame has been received. The sACN outputs have the priority of the
+    /// output, or the priority of the sACN input (if any).
+    ///
+    async fn send_outputs(&mut self, socket: &UdpSocket) {
+        if !self.has_frame {
+            return;
+        }
+        let input_priority = self.sources.priority();
+        for output in self.outputs.iter_mut() {
+            let packet = match output.config.protocol {
+                BridgeProtocol::ArtNet => {
+                    output.sequence = output.sequence % 255 + 1; // zero disables sequencing
+                    art_net::encode_dmx(output.config.universe, output.sequence, &self.frame)
+                }
+                BridgeProtocol::Sacn => {
+                    let config = SacnConfig {
+                        universe: output.config.universe,
+                        source_name: self.names.short_name.clone(),
+                        priority: output
+                            .config
+                            .priority
+                            .or(input_priority)
+                            .unwrap_or(DEFAULT_SACN_PRIORITY),
+                        destination: None,
+                    };
+                    output.sequence = output.sequence.wrapping_add(1);
+                    sacn::encode_data(&config, &self.cid, output.sequence, &self.frame)
+                }
+            };
+            if let Err(error) = socket.send_to(&packet, output.destination).await {
+                debug!(
+                    "Unable to send the bridge to {}: {}.",
+                    output.destination, error
+                );
+            }
+        }
+    }
+
+    /// A helper method to reply to an Art-Net poll
+    ///
+    async fn reply(&mut self, socket: &UdpSocket, controller: SocketAddr) {
+        let address = match socket.local_addr() {
+            Ok(SocketAddr::V4(local)) if !local.ip().is_unspecified() => *local.ip(),
+            _ => art_net::local_address(controller).unwrap_or(Ipv4Addr::UNSPECIFIED),
+        };
+        self.replies = self.replies.wrapping_add(1);
+        let refresh_rate = (1000 / self.resolution.as_millis().max(1)).min(u16::MAX as u128) as u16;
+        let reply = art_net::encode_poll_reply(
+            &self.names,
+            address,
+            refresh_rate,
+            self.is_writing,
+            self.replies,
+        );
+        if let Err(error) = socket.send_to(&reply, controller).await {
+            debug!(
+                "Unable to reply to Art-Net poll from {}: {}.",
+                controller, error
+            );
+        }
+    }
+}
+
+/// A structure to describe one output of the bridge
+///
+struct Output {
+    config: BridgeOutput,    // the protocol, universe, and priority of the output
+    destination: SocketAddr, // the udp address to send to
+    sequence: u8,            // the sequence number of the latest packet
+}
+
+// Implement key features of the output
+impl Output {
+    /// A function to create a new output, returning None (and logging an
+    /// error) if the universe or destination is not valid
+    ///
+    fn new(config: BridgeOutput) -> Option<Self> {
+        // Check the universe
+        if config.protocol == BridgeProtocol::Sacn
+            && !(1..=sacn::MAX_UNIVERSE).contains(&config.universe)
+        {
+            error!(
+                "The sACN universe of a bridge output must be between 1 and {}.",
+                sacn::MAX_UNIVERSE
+            );
+            return None;
+        }
+
+        // Find the destination
+        let destination = match (&config.destination, config.protocol) {
+            (Some(destination), _) => destination.parse::<SocketAddr>(),
+            (None, BridgeProtocol::ArtNet) => DEFAULT_ART_NET_BROADCAST.parse(),
+            (None, BridgeProtocol::Sacn) => Ok(sacn::multicast_address(config.universe)),
+        };
+        match destination {
+            Ok(destination) => Some(Self {
+                config,
+                destination,
+                sequence: 0,
+            }),
+            Err(error) => {
+                error!("Invalid bridge destination: {}.", error);
+                None
+            }
+        }
+    }
+}
+
+/// A helper function to open a socket to send the outputs, which may
+/// broadcast
+///
+async fn open_sender() -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.set_broadcast(true)?;
+    Ok(socket)
+}
+
+/// A helper function to return the name of a protocol
+///
+fn protocol_name(protocol: BridgeProtocol) -> &'static str {
+    match protocol {
+        BridgeProtocol::ArtNet => "Art-Net",
+        BridgeProtocol::Sacn => "sACN",
+    }
+}
+
+// Tests of the bridge module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vulcan_core::DryRunDriver;
+
+    // Test that the bridge keeps the frames of its universe and answers polls
+    #[tokio::test]
+    async fn bridge_art_net() {
+        // Create a bridge for port-address 3
+        let config = BridgeConfig {
+            universe: 3,
+            ..BridgeConfig::default()
+        };
+        let mut bridge = Bridge::new(DryRunDriver, config, None, Duration::from_millis(25));
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let console = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let source = console.local_addr().unwrap();
+
+        // Only the values of the universe are kept
+        let header = [b"Art-Net\0".as_slice(), &[0x00, 0x50, 0, 14, 0, 0]].concat();
+        let other = [header.as_slice(), &[4, 0, 0, 1, 50]].concat();
+        bridge.receive(&socket, &other, source).await;
+        assert_eq!(bridge.frame.get(1), 0);
+        let packet = [header.as_slice(), &[3, 0, 0, 2, 60, 70]].concat();
+        bridge.receive(&socket, &packet, source).await;
+        assert_eq!((bridge.frame.get(1), bridge.frame.get(2)), (60, 70));
+
+        // A poll is answered with a reply for the universe
+        bridge
+            .receive(&socket, b"Art-Net\0\x00\x20\0\x0e\0\0", source)
+            .await;
+        let mut buffer = vec![0; art_net::MAX_PACKET];
+        let (length, _) = console.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(length, 239);
+        assert_eq!(buffer[190], 3);
+    }
+
+    // Test that the outputs find their destinations
+    #[test]
+    fn output_destinations() {
+        // Art-Net is broadcast and sACN is multicast by default
+        let art_net = Output::new(BridgeOutput {
+            protocol: BridgeProtocol::ArtNet,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            art_net.destination,
+            DEFAULT_ART_NET_BROADCAST.parse().unwrap()
+        );
+        let sacn = Output::new(BridgeOutput {
+            universe: 7,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(sacn.destination, sacn::multicast_address(7));
+
+        // A destination replaces the default, and invalid outputs are skipped
+        let direct = Output::new(BridgeOutput {
+            destination: Some("10.0.0.20:5568".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(direct.destination, "10.0.0.20:5568".parse().unwrap());
+        assert!(
+            Output::new(BridgeOutput {
+                universe: 0,
+                ..Default::default()
+            })
+            .is_none()
+        );
+    }
+}
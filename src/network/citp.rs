@@ -0,0 +1,259 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a CITP peer for previsualization tools. The peer
+//! announces itself as a lighting console with PINF location messages, and
+//! each visualizer that connects receives the output as SDMX channel
+//! blocks.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::watch;
+use tokio::time::{interval, timeout};
+
+// Import tracing features
+use tracing::{debug, error, info};
+
+// Define the CITP packet constants
+const CITP_PORT: u16 = 4809; // the udp port of the location messages
+const MULTICAST_GROUPS: [Ipv4Addr; 2] = [
+    Ipv4Addr::new(239, 224, 0, 180), // the group of current peers
+    Ipv4Addr::new(224, 0, 0, 180),   // the group of older peers
+];
+const HEADER_LENGTH: usize = 24; // the length of the CITP and layer headers
+const PEER_TYPE: &str = "LightingConsole"; // the type of this peer
+
+// Define the timing constants
+const LOCATION_INTERVAL: Duration = Duration::from_secs(1); // the time between location messages
+const KEEP_ALIVE: Duration = Duration::from_secs(1); // the longest time between channel blocks
+
+/// A structure to announce the peer and send the output to visualizers
+///
+pub struct CitpPeer {
+    frames: watch::Receiver<Universe>, // the frames of the output
+    config: CitpConfig,                // the name and address of the peer
+}
+
+// Implement key features of the CITP peer
+impl CitpPeer {
+    /// A function to create a new CITP peer for the frames
+    ///
+    pub fn new(frames: watch::Receiver<Universe>, config: CitpConfig) -> Self {
+        Self { frames, config }
+    }
+
+    /// A method to announce the peer and accept visualizers until the
+    /// program closes
+    ///
+    pub async fn run(self) {
+        // Try to listen for visualizers
+        let listener = match TcpListener::bind(&self.config.address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                error!(
+                    "Unable to listen for CITP at {}: {}.",
+                    self.config.address, error
+                );
+                return;
+            }
+        };
+        let port = listener.local_addr().map(|local| local.port()).unwrap_or(0);
+        info!("Listening for CITP on port {}.", port);
+
+        // Announce the location of the peer
+        let location = encode_location(port, &self.config.name, "Running");
+        tokio::spawn(announce(location));
+
+        // Send the output to each visualizer that connects
+        loop {
+            match listener.accept().await {
+                Ok((stream, address)) => {
+                    info!("CITP visualizer connected from {}.", address);
+                    let frames = self.frames.clone();
+                    let name = self.config.name.clone();
+                    tokio::spawn(async move {
+                        if let Err(error) = serve(stream, frames, &name).await {
+                            debug!("CITP visualizer {} disconnected: {}.", address, error);
+                        }
+                    });
+                }
+                Err(error) => error!("Unable to accept CITP connection: {}.", error),
+            }
+        }
+    }
+}
+
+/// A helper function to send the location message to every multicast group
+/// once a second
+///
+async fn announce(location: Vec<u8>) {
+    // Open a socket to send the messages
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(error) => {
+            error!("Unable to open a socket for CITP: {}.", error);
+            return;
+        }
+    };
+
+    // Send the location regularly
+    let mut ticks = interval(LOCATION_INTERVAL);
+    loop {
+        ticks.tick().await;
+        for group in MULTICAST_GROUPS {
+            let destination = SocketAddr::from((group, CITP_PORT));
+            if let Err(error) = socket.send_to(&location, destination).await {
+                debug!(
+                    "Unable to send CITP location to {}: {}.",
+                    destination, error
+                );
+            }
+        }
+    }
+}
+
+/// A helper function to send the name of the peer and every new frame (at
+/// least once a second) to a visualizer, until it disconnects
+///
+async fn serve(
+    mut stream: TcpStream,
+    mut frames: watch::Receiver<Universe>,
+    name: &str,
+) -> std::io::Result<()> {
+    // Introduce the peer and the universe
+    stream.write_all(&encode_name(name)).await?;
+    stream.write_all(&encode_universe_name(name)).await?;
+    frames.mark_changed();
+
+    // Send the frames, discarding anything the visualizer sends
+    let mut buffer = vec![0; 1024];
+    loop {
+        tokio::select! {
+            changed = timeout(KEEP_ALIVE, frames.changed()) => {
+                if let Ok(Err(_)) = changed {
+                    return Ok(()); // the output has closed
+                }
+                let frame = frames.borrow_and_update().clone();
+                stream.write_all(&encode_channel_block(&frame)).await?;
+            }
+            read = stream.read(&mut buffer) => {
+                if read? == 0 {
+                    return Ok(()); // the visualizer has disconnected
+                }
+            }
+        }
+    }
+}
+
+/// A helper function to create a CITP message with the layer headers
+///
+fn encode_message(layer: &[u8; 4], content: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_LENGTH + body.len());
+    message.extend_from_slice(b"CITP");
+    message.extend_from_slice(&[1, 0]); // the version of the protocol
+    message.extend_from_slice(&0u16.to_le_bytes()); // no request index
+    message.extend_from_slice(&((HEADER_LENGTH + body.len()) as u32).to_le_bytes());
+    message.extend_from_slice(&1u16.to_le_bytes()); // the number of parts
+    message.extend_from_slice(&0u16.to_le_bytes()); // the index of this part
+    message.extend_from_slice(layer);
+    message.extend_from_slice(content);
+    message.extend_from_slice(body);
+    message
+}
+
+/// A helper function to append a null-terminated string
+///
+fn push_string(body: &mut Vec<u8>, string: &str) {
+    body.extend_from_slice(string.as_bytes());
+    body.push(0);
+}
+
+/// A helper function to create the location message of the peer
+///
+fn encode_location(port: u16, name: &str, state: &str) -> Vec<u8> {
+    let mut body = port.to_le_bytes().to_vec();
+    push_string(&mut body, PEER_TYPE);
+    push_string(&mut body, name);
+    push_string(&mut body, state);
+    encode_message(b"PINF", b"PLoc", &body)
+}
+
+/// A helper function to create the name message of the peer
+///
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_string(&mut body, name);
+    encode_message(b"PINF", b"PNam", &body)
+}
+
+/// A helper function to create the name message of the only universe
+///
+fn encode_universe_name(name: &str) -> Vec<u8> {
+    let mut body = vec![0];
+    push_string(&mut body, name);
+    encode_message(b"SDMX", b"UNam", &body)
+}
+
+/// A helper function to create a channel block with every channel of the
+/// frame
+///
+fn encode_channel_block(frame: &Universe) -> Vec<u8> {
+    let mut body = vec![0, 0]; // not blind, the first universe
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(&(DMX_MAX as u16).to_le_bytes());
+    body.extend_from_slice(&frame.as_bytes());
+    encode_message(b"SDMX", b"ChBk", &body)
+}
+
+// Tests of the CITP module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the location and channel block messages are laid out
+    #[test]
+    fn encode_messages() {
+        // Check the headers and body of the location
+        let location = encode_location(0x1234, "Vulcan", "Running");
+        assert_eq!(&location[..6], b"CITP\x01\x00");
+        assert_eq!(
+            u32::from_le_bytes(location[8..12].try_into().unwrap()) as usize,
+            location.len()
+        );
+        assert_eq!(&location[16..24], b"PINFPLoc");
+        assert_eq!(&location[24..26], &[0x34, 0x12]);
+        assert_eq!(&location[26..], b"LightingConsole\0Vulcan\0Running\0");
+
+        // Check the channels of a block
+        let mut frame = Universe::new();
+        frame.set(1, 10);
+        frame.set(512, 20);
+        let block = encode_channel_block(&frame);
+        assert_eq!(block.len(), HEADER_LENGTH + 6 + 512);
+        assert_eq!(&block[16..24], b"SDMXChBk");
+        assert_eq!(&block[24..30], &[0, 0, 0, 0, 0x00, 0x02]);
+        assert_eq!((block[30], block[541]), (10, 20));
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a follower that mirrors the sACN output of a
+//! leader Vulcan on the local DMX hardware, so that one logical rig can
+//! span universes that are physically far apart.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::sacn::{self, SacnData};
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+// Import standard library features
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+// Import Tokio features
+use tokio::time::timeout;
+
+// Import tracing features
+use tracing::{error, info, warn};
+
+// Define the follower constants
+const LEADER_TIMEOUT: Duration = Duration::from_millis(2500); // the time before a quiet leader is lost
+const SEQUENCE_WINDOW: i8 = -20; // the oldest sequence number treated as out of order
+
+/// A structure to receive the output of a leader and mirror it on the
+/// local output
+///
+pub struct SacnFollower {
+    dmx_interface: DmxController, // the controller of the local output
+    config: FollowConfig,         // the universe and the leader to follow
+}
+
+// Implement key features of the follower
+impl SacnFollower {
+    /// A function to create a new follower for the controller
+    ///
+    pub fn new(dmx_interface: DmxController, config: FollowConfig) -> Self {
+        Self {
+            dmx_interface,
+            config,
+        }
+    }
+
+    /// A method to mirror the leader until the program closes. If the
+    /// leader goes quiet, the last look is held until it returns.
+    ///
+    pub async fn run(self) {
+        // Check the universe and any address of the leader
+        if !(1..=sacn::MAX_UNIVERSE).contains(&self.config.universe) {
+            error!(
+                "The followed sACN universe must be between 1 and {}.",
+                sacn::MAX_UNIVERSE
+            );
+            return;
+        }
+        let leader = match &self.config.leader {
+            Some(leader) => match leader.parse::<IpAddr>() {
+                Ok(leader) => Some(leader),
+                Err(error) => {
+                    error!("Invalid sACN leader {}: {}.", leader, error);
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        // Open a socket to receive the packets
+        let socket = match sacn::open_receiver(&self.config.address, self.config.universe) {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!(
+                    "Unable to listen for sACN at {}: {}.",
+                    self.config.address, error
+                );
+                return;
+            }
+        };
+        info!(
+            "Following sACN universe {} on {}.",
+            self.config.universe, self.config.address
+        );
+
+        // Mirror each frame from the leader
+        let mut state = FollowState::new(self.config.universe);
+        let mut buffer = vec![0; sacn::MAX_PACKET];
+        loop {
+            match timeout(LEADER_TIMEOUT, socket.recv_from(&mut buffer)).await {
+                // Ignore packets from other sources or of other types
+                Ok(Ok((length, source))) => {
+                    if leader.is_some_and(|leader| leader != source.ip()) {
+                        continue;
+                    }
+                    let Some(data) = sacn::parse_data(&buffer[..length]) else {
+                        continue;
+                    };
+                    if let Some(changes) = state.receive(data, source, Instant::now())
+                        && !changes.is_empty()
+                    {
+                        self.dmx_interface.set_channels(changes).await;
+                    }
+                }
+                Ok(Err(error)) => error!("Unable to read sACN packet: {}.", error),
+
+                // Note if the leader has gone quiet
+                Err(_) => state.expire(Instant::now()),
+            }
+        }
+    }
+}
+
+/// A struct to describe the source being followed
+///
+struct Leader {
+    cid: [u8; 16],      // the unique identifier of the source
+    priority: u8,       // the priority of the latest data
+    sequence: u8,       // the sequence number of the latest packet
+    last_seen: Instant, // the time of the latest packet
+}
+
+/// A structure to choose the leader and find the channels that changed
+///
+pub struct FollowState {
+    universe: u16,          // the universe to follow
+    leader: Option<Leader>, // the source being followed, if any
+    last: Universe,         // the last frame from the leader
+}
+
+// Implement key features of the follow state
+impl FollowState {
+    /// A function to create a new state without a leader
+    ///
+    pub fn new(universe: u16) -> Self {
+        Self {
+            universe,
+            leader: None,
+            last: Universe::new(),
+        }
+    }
+
+    /// A method to read a data packet, returning the channels that changed
+    /// if the packet is from the leader. A new leader replaces the current
+    /// one if it has a higher priority (or the current one has gone quiet),
+    /// and every channel is loaded from its first frame.
+    ///
+    pub fn receive(
+        &mut self,
+        data: SacnData,
+        source: SocketAddr,
+        now: Instant,
+    ) -> Option<Vec<(u32, u8)>> {
+        // Check the universe and ignore visualizer data
+        if data.universe != self.universe || data.is_preview {
+            return None;
+        }
+        self.expire(now);
+
+        // Check the packet against the current leader
+        let is_new = match self.leader.as_mut() {
+            Some(leader) if leader.cid == data.cid => {
+                if data.is_terminated {
+                    info!("The sACN leader stopped sending. Holding the last look.");
+                    self.leader = None;
+                    return None;
+                }
+                let difference = data.sequence.wrapping_sub(leader.sequence) as i8;
+                if difference <= 0 && difference > SEQUENCE_WINDOW {
+                    return None; // out of order
+                }
+                leader.priority = data.priority;
+                leader.sequence = data.sequence;
+                leader.last_seen = now;
+                false
+            }
+            Some(leader) if leader.priority >= data.priority => return None,
+            _ if data.is_terminated => return None,
+            _ => {
+                info!("Following the sACN leader at {}.", source);
+                self.leader = Some(Leader {
+                    cid: data.cid,
+                    priority: data.priority,
+                    sequence: data.sequence,
+                    last_seen: now,
+                });
+                true
+            }
+        };
+
+        // Find the channels that changed
+        let changes = (1..=DMX_MAX)
+            .map(|channel| (channel, data.frame.get(channel)))
+            .filter(|(channel, value)| is_new || self.last.get(*channel) != *value)
+            .collect();
+        self.last = data.frame;
+        Some(changes)
+    }
+
+    /// A method to return the priority of the leader, if any
+    ///
+    pub fn priority(&self) -> Option<u8> {
+        self.leader.as_ref().map(|leader| leader.priority)
+    }
+
+    /// A method to release the leader if it has gone quiet
+    ///
+    pub fn expire(&mut self, now: Instant) {
+        if self
+            .leader
+            .as_ref()
+            .is_some_and(|leader| now.duration_since(leader.last_seen) >= LEADER_TIMEOUT)
+        {
+            warn!("Lost the sACN leader. Holding the last look.");
+            self.leader = None;
+        }
+    }
+}
+
+// Tests of the follower module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A helper function to create a data packet from a source
+    fn data(cid: u8, priority: u8, sequence: u8, value: u8) -> SacnData {
+        let mut frame = Universe::new();
+        frame.set(1, value);
+        SacnData {
+            cid: [cid; 16],
+            priority,
+            sequence,
+            is_preview: false,
+            is_terminated: false,
+            universe: 1,
+            frame,
+        }
+    }
+
+    // Test that the follower mirrors the changes of one leader
+    #[test]
+    fn follow_leader() {
+        let source: SocketAddr = "10.0.0.2:5568".parse().unwrap();
+        let start = Instant::now();
+        let mut state = FollowState::new(1);
+
+        // The first frame loads every channel, and later frames only the changes
+        let changes = state.receive(data(1, 100, 5, 10), source, start).unwrap();
+        assert_eq!(changes.len(), DMX_MAX as usize);
+        assert_eq!(changes[0], (1, 10));
+        let changes = state.receive(data(1, 100, 6, 20), source, start).unwrap();
+        assert_eq!(changes, vec![(1, 20)]);
+
+        // Out of order packets and lower priority sources are ignored
+        assert!(state.receive(data(1, 100, 4, 30), source, start).is_none());
+        assert!(state.receive(data(2, 50, 1, 30), source, start).is_none());
+
+        // A quiet leader is replaced by another source
+        let later = start + LEADER_TIMEOUT;
+        let changes = state.receive(data(2, 50, 2, 30), source, later).unwrap();
+        assert_eq!(changes.len(), DMX_MAX as usize);
+        assert_eq!(changes[0], (1, 30));
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to present Vulcan on show networks, so that other consoles and
+//! tools can discover it and receive its output (or so that it can pass
+//! the output of another console to its hardware).
+
+// Define private submodules
+mod art_net;
+mod bridge;
+mod citp;
+mod follower;
+mod sacn;
+
+// Reexport the bridge
+pub use bridge::Bridge;
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use art_net::ArtNetNode;
+use citp::CitpPeer;
+use follower::SacnFollower;
+use sacn::SacnSource;
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+/// A structure to start every network node in the configuration
+///
+pub struct NetworkInterface;
+
+// Implement key features of the network interface
+impl NetworkInterface {
+    /// A function to start a node for each show network in the
+    /// configuration. Each node runs in the background until the program
+    /// closes.
+    ///
+    pub fn start(dmx_interface: DmxController, config: &Config) {
+        // Start the Art-Net node, if specified
+        if let Some(art_net) = config.art_net.clone() {
+            let node = ArtNetNode::new(dmx_interface.clone(), art_net, config.resolution);
+            tokio::spawn(node.run());
+        }
+
+        // Start the sACN output, if specified
+        if let Some(sacn) = config.sacn.clone() {
+            let source = SacnSource::new(dmx_interface.frames(), sacn);
+            tokio::spawn(source.run());
+        }
+
+        // Start the CITP peer, if specified
+        if let Some(citp) = config.citp.clone() {
+            let peer = CitpPeer::new(dmx_interface.frames(), citp);
+            tokio::spawn(peer.run());
+        }
+
+        // Follow the output of a leader, if specified
+        if let Some(follow) = config.follow.clone() {
+            let follower = SacnFollower::new(dmx_interface.clone(), follow);
+            tokio::spawn(follower.run());
+        }
+    }
+}
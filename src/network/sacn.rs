@@ -0,0 +1,383 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements an sACN (E1.31) source that sends each frame of
+//! the output, and advertises its universes with universe discovery packets
+//! so that receivers and visualizers can subscribe without configuration.
+//! It also reads the data packets of other sources.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio::time::{interval, timeout};
+
+// Import tracing features
+use tracing::{debug, error, info};
+
+// Define the E1.31 packet constants
+const SACN_PORT: u16 = 5568; // the udp port of every sACN receiver
+const ACN_ID: &[u8; 12] = b"ASC-E1.17\0\0\0"; // the identifier of every ACN packet
+const VECTOR_ROOT_DATA: u32 = 0x04; // the root vector of a data packet
+const VECTOR_ROOT_EXTENDED: u32 = 0x08; // the root vector of a discovery packet
+const VECTOR_FRAMING_DATA: u32 = 0x02; // the framing vector of a data packet
+const VECTOR_FRAMING_DISCOVERY: u32 = 0x02; // the framing vector of a discovery packet
+const VECTOR_DMP_SET: u8 = 0x02; // the vector to set the values of the channels
+const VECTOR_UNIVERSE_LIST: u32 = 0x01; // the vector of a list of universes
+const OPTION_PREVIEW: u8 = 0x80; // the option for data only meant for visualizers
+const OPTION_TERMINATED: u8 = 0x40; // the option for the last packet of a stream
+const DATA_LENGTH: usize = 126 + DMX_MAX as usize; // the length of a data packet with a full universe
+pub const MAX_PACKET: usize = 1144; // the largest sACN packet that will be read
+
+// Define the universe constants
+pub const MAX_UNIVERSE: u16 = 63_999; // the highest sACN universe
+const DISCOVERY_UNIVERSE: u16 = 64_214; // the universe of the discovery packets
+const MAX_PRIORITY: u8 = 200; // the highest sACN priority
+
+// Define the timing constants
+const KEEP_ALIVE: Duration = Duration::from_secs(1); // the longest time between frames
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(10); // the time between discovery packets
+
+/// A structure to send the output as sACN
+///
+pub struct SacnSource {
+    frames: watch::Receiver<Universe>, // the frames of the output
+    config: SacnConfig,                // the universe, name, and priority of the output
+    cid: [u8; 16],                     // the unique identifier of this source
+    sequence: u8,                      // the sequence number of the next data packet
+}
+
+// Implement key features of the sACN source
+impl SacnSource {
+    /// A function to create a new sACN source for the frames
+    ///
+    pub fn new(frames: watch::Receiver<Universe>, config: SacnConfig) -> Self {
+        let cid = source_cid(&config.source_name);
+        Self {
+            frames,
+            config,
+            cid,
+            sequence: 0,
+        }
+    }
+
+    /// A method to send each new frame (and repeat the last frame at least
+    /// once a second) until the program closes, advertising the universe
+    /// every ten seconds
+    ///
+    pub async fn run(mut self) {
+        // Check the universe and find the destination
+        if !(1..=MAX_UNIVERSE).contains(&self.config.universe) {
+            error!("The sACN universe must be between 1 and {}.", MAX_UNIVERSE);
+            return;
+        }
+        let destination = match &self.config.destination {
+            Some(destination) => match destination.parse::<SocketAddr>() {
+                Ok(destination) => destination,
+                Err(error) => {
+                    error!("Invalid sACN destination {}: {}.", destination, error);
+                    return;
+                }
+            },
+            None => multicast_address(self.config.universe),
+        };
+
+        // Open a socket to send the packets
+        let socket = match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("Unable to open a socket for sACN: {}.", error);
+                return;
+            }
+        };
+        info!(
+            "Sending sACN universe {} to {}.",
+            self.config.universe, destination
+        );
+
+        // Send each frame and each discovery packet
+        let mut discovery = interval(DISCOVERY_INTERVAL);
+        loop {
+            tokio::select! {
+                // Send the frame when it changes (or once it is due again)
+                changed = timeout(KEEP_ALIVE, self.frames.changed()) => {
+                    if let Ok(Err(_)) = changed {
+                        return; // the output has closed
+                    }
+                    let frame = self.frames.borrow_and_update().clone();
+                    let packet = encode_data(&self.config, &self.cid, self.sequence, &frame);
+                    self.sequence = self.sequence.wrapping_add(1);
+                    send(&socket, &packet, destination).await;
+                }
+
+                // Advertise the universes of this source
+                _ = discovery.tick() => {
+                    let packet = encode_discovery(&self.config, &self.cid, &[self.config.universe]);
+                    send(&socket, &packet, multicast_address(DISCOVERY_UNIVERSE)).await;
+                }
+            }
+        }
+    }
+}
+
+/// A helper function to send a packet, noting any failure
+///
+async fn send(socket: &UdpSocket, packet: &[u8], destination: SocketAddr) {
+    if let Err(error) = socket.send_to(packet, destination).await {
+        debug!("Unable to send sACN packet to {}: {}.", destination, error);
+    }
+}
+
+/// A struct to describe a data packet from a source
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SacnData {
+    pub cid: [u8; 16],       // the unique identifier of the source
+    pub priority: u8,        // the priority of the data
+    pub sequence: u8,        // the sequence number of the packet
+    pub is_preview: bool,    // a flag to indicate the data is only for visualizers
+    pub is_terminated: bool, // a flag to indicate the source has stopped sending
+    pub universe: u16,       // the universe of the data
+    pub frame: Universe,     // the value of each channel in the packet
+}
+
+/// A helper function to read a data packet, returning None if the packet is
+/// not a data packet (or has values other than DMX levels)
+///
+pub fn parse_data(packet: &[u8]) -> Option<SacnData> {
+    // Check the identifier, the vector of each layer, and the start code
+    let vector = |offset: usize| {
+        u32::from_be_bytes([
+            packet[offset],
+            packet[offset + 1],
+            packet[offset + 2],
+            packet[offset + 3],
+        ])
+    };
+    if packet.len() < 126
+        || &packet[4..16] != ACN_ID
+        || vector(18) != VECTOR_ROOT_DATA
+        || vector(40) != VECTOR_FRAMING_DATA
+        || packet[117] != VECTOR_DMP_SET
+        || packet[125] != 0
+    {
+        return None;
+    }
+
+    // Read the values of the channels, after the start code
+    let count = u16::from_be_bytes([packet[123], packet[124]]).saturating_sub(1) as usize;
+    let mut frame = Universe::new();
+    for (index, value) in packet[126..]
+        .iter()
+        .take(count.min(DMX_MAX as usize))
+        .enumerate()
+    {
+        frame.set(index as u32 + 1, *value);
+    }
+
+    // Read the source and the options
+    let mut cid = [0; 16];
+    cid.copy_from_slice(&packet[22..38]);
+    Some(SacnData {
+        cid,
+        priority: packet[108],
+        sequence: packet[111],
+        is_preview: packet[112] & OPTION_PREVIEW != 0,
+        is_terminated: packet[112] & OPTION_TERMINATED != 0,
+        universe: u16::from_be_bytes([packet[113], packet[114]]),
+        frame,
+    })
+}
+
+/// A helper function to open a socket to receive packets at the address,
+/// joining the multicast group of the universe (where possible)
+///
+pub fn open_receiver(address: &str, universe: u16) -> std::io::Result<UdpSocket> {
+    let socket = std::net::UdpSocket::bind(address)?;
+    if let SocketAddr::V4(group) = multicast_address(universe)
+        && let Err(error) = socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED)
+    {
+        debug!("Unable to join the sACN group {}: {}.", group.ip(), error);
+    }
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket)
+}
+
+/// A helper function to find the multicast group of a universe
+///
+pub fn multicast_address(universe: u16) -> SocketAddr {
+    let [high, low] = universe.to_be_bytes();
+    SocketAddr::from((Ipv4Addr::new(239, 255, high, low), SACN_PORT))
+}
+
+/// A helper function to find a unique identifier for this source, which
+/// stays the same across restarts (from the machine id, if available, or
+/// from the name of the source)
+///
+pub fn source_cid(name: &str) -> [u8; 16] {
+    // Try to use the machine id
+    let mut cid = [0; 16];
+    if let Ok(id) = std::fs::read_to_string("/etc/machine-id") {
+        let id = id.trim();
+        if id.len() == 32
+            && let Ok(bytes) = (0..16)
+                .map(|index| u8::from_str_radix(&id[index * 2..index * 2 + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+        {
+            cid.copy_from_slice(&bytes);
+            return cid;
+        }
+    }
+
+    // Otherwise, spread a hash of the name across the identifier
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for (index, byte) in cid.iter_mut().enumerate() {
+        for value in name.bytes().chain([index as u8]) {
+            hash = (hash ^ value as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+        *byte = (hash >> 56) as u8;
+    }
+    cid
+}
+
+/// A helper function to write the root layer of a packet, returning the
+/// packet with space for the remaining layers
+///
+fn root_layer(length: usize, vector: u32, cid: &[u8; 16]) -> Vec<u8> {
+    let mut packet = vec![0; length];
+    packet[0..2].copy_from_slice(&0x0010u16.to_be_bytes());
+    packet[4..16].copy_from_slice(ACN_ID);
+    write_length(&mut packet, 16);
+    packet[18..22].copy_from_slice(&vector.to_be_bytes());
+    packet[22..38].copy_from_slice(cid);
+    packet
+}
+
+/// A helper function to write the flags and length of the layer that starts
+/// at the offset
+///
+fn write_length(packet: &mut [u8], offset: usize) {
+    let length = 0x7000 | (packet.len() - offset) as u16;
+    packet[offset..offset + 2].copy_from_slice(&length.to_be_bytes());
+}
+
+/// A helper function to write the name of the source, truncated to leave a
+/// terminating null
+///
+fn write_name(packet: &mut [u8], name: &str) {
+    let length = name.len().min(63);
+    packet[44..44 + length].copy_from_slice(&name.as_bytes()[..length]);
+}
+
+/// A helper function to create a data packet with every channel of the
+/// frame
+///
+pub fn encode_data(config: &SacnConfig, cid: &[u8; 16], sequence: u8, frame: &Universe) -> Vec<u8> {
+    // Write the framing layer
+    let mut packet = root_layer(DATA_LENGTH, VECTOR_ROOT_DATA, cid);
+    write_length(&mut packet, 38);
+    packet[40..44].copy_from_slice(&VECTOR_FRAMING_DATA.to_be_bytes());
+    write_name(&mut packet, &config.source_name);
+    packet[108] = config.priority.min(MAX_PRIORITY);
+    packet[111] = sequence;
+    packet[113..115].copy_from_slice(&config.universe.to_be_bytes());
+
+    // Write the channels, after the start code
+    write_length(&mut packet, 115);
+    packet[117] = VECTOR_DMP_SET;
+    packet[118] = 0xA1; // the address and data types
+    packet[121..123].copy_from_slice(&1u16.to_be_bytes());
+    packet[123..125].copy_from_slice(&(DMX_MAX as u16 + 1).to_be_bytes());
+    packet[126..].copy_from_slice(&frame.as_bytes());
+    packet
+}
+
+/// A helper function to create a discovery packet listing the universes
+/// (a single page, up to 512 universes)
+///
+fn encode_discovery(config: &SacnConfig, cid: &[u8; 16], universes: &[u16]) -> Vec<u8> {
+    // Write the framing layer
+    let mut universes = universes.to_vec();
+    universes.sort_unstable();
+    universes.truncate(512);
+    let mut packet = root_layer(120 + universes.len() * 2, VECTOR_ROOT_EXTENDED, cid);
+    write_length(&mut packet, 38);
+    packet[40..44].copy_from_slice(&VECTOR_FRAMING_DISCOVERY.to_be_bytes());
+    write_name(&mut packet, &config.source_name);
+
+    // Write the list of universes, on the only page
+    write_length(&mut packet, 112);
+    packet[114..118].copy_from_slice(&VECTOR_UNIVERSE_LIST.to_be_bytes());
+    for (index, universe) in universes.iter().enumerate() {
+        packet[120 + index * 2..122 + index * 2].copy_from_slice(&universe.to_be_bytes());
+    }
+    packet
+}
+
+// Tests of the sACN module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the data and discovery packets describe the universe
+    #[test]
+    fn encode_packets() {
+        let config = SacnConfig {
+            universe: 300,
+            ..SacnConfig::default()
+        };
+        let cid = source_cid("test");
+        let mut frame = Universe::new();
+        frame.set(1, 255);
+        frame.set(512, 7);
+
+        // Check the layers of a data packet
+        let data = encode_data(&config, &cid, 9, &frame);
+        assert_eq!(data.len(), 638);
+        assert_eq!(&data[4..16], ACN_ID);
+        assert_eq!(&data[16..18], &[0x72, 0x6E]);
+        assert_eq!(&data[38..40], &[0x72, 0x58]);
+        assert!(data[44..].starts_with(b"Vulcan\0"));
+        assert_eq!((data[108], data[111]), (DEFAULT_SACN_PRIORITY, 9));
+        assert_eq!(&data[113..117], &[0x01, 0x2C, 0x72, 0x0B]);
+        assert_eq!((data[125], data[126], data[637]), (0, 255, 7));
+
+        // Read the data packet back
+        let parsed = parse_data(&data).unwrap();
+        assert_eq!((parsed.universe, parsed.sequence), (300, 9));
+        assert_eq!((parsed.cid, parsed.frame), (cid, frame));
+        assert!(!parsed.is_preview && !parsed.is_terminated);
+        assert!(parse_data(&data[..120]).is_none());
+
+        // Check the list of a discovery packet
+        let discovery = encode_discovery(&config, &cid, &[300, 2]);
+        assert_eq!(discovery.len(), 124);
+        assert_eq!(&discovery[18..22], &[0, 0, 0, 8]);
+        assert_eq!(&discovery[112..114], &[0x70, 0x0C]);
+        assert_eq!(&discovery[120..], &[0, 2, 0x01, 0x2C]);
+        assert_eq!(
+            multicast_address(DISCOVERY_UNIVERSE),
+            "239.255.250.214:5568".parse().unwrap()
+        );
+    }
+}
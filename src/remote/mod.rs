@@ -0,0 +1,312 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to control a running instance of Vulcan from the commandline.
+//! Each command is sent to the web interface of the running instance, so
+//! the command follows the same rules as any other client.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+// Import clap features
+use clap::Subcommand;
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the time to wait for the running instance to reply
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An enum to define the commands for a running instance
+///
+#[derive(Subcommand, Clone, Debug)]
+pub enum RemoteCommand {
+    /// Play a fade on a channel of a running instance
+    Fade {
+        /// Channel to fade (1-512)
+        channel: u32,
+
+        /// Final value of the channel (0-255)
+        value: u8,
+
+        /// Duration of the fade, in seconds [default: instantaneous]
+        #[arg(short, long)]
+        duration: Option<f64>,
+    },
+
+    /// Recall or list the scenes of a running instance
+    Scene {
+        #[command(subcommand)]
+        command: SceneCommand,
+    },
+
+    /// Read the universe of a running instance
+    Universe {
+        #[command(subcommand)]
+        command: UniverseCommand,
+    },
+}
+
+/// An enum to define the scene commands
+///
+#[derive(Subcommand, Clone, Debug)]
+pub enum SceneCommand {
+    /// Recall a scene by name
+    Recall {
+        /// Name of the scene
+        name: String,
+
+        /// Duration of the fade, in seconds [default: instantaneous]
+        #[arg(short, long)]
+        duration: Option<f64>,
+    },
+
+    /// List the names of the available scenes
+    List,
+}
+
+/// An enum to define the universe commands
+///
+#[derive(Subcommand, Clone, Debug)]
+pub enum UniverseCommand {
+    /// Print the current value of every channel as JSON
+    Dump,
+}
+
+/// A structure to send commands to the web interface of a running instance
+///
+pub struct Remote {
+    address: String,       // the address of the web interface
+    token: Option<String>, // the token for the requests, if any
+}
+
+// Implement key features of the remote
+impl Remote {
+    /// A function to create a new remote for the web interface at the
+    /// address
+    ///
+    pub fn new(address: String, token: Option<String>) -> Self {
+        Self { address, token }
+    }
+
+    /// A method to send the command and print the result
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the running instance cannot be
+    /// reached or the request fails.
+    ///
+    pub async fn run(&self, command: RemoteCommand) -> Result<()> {
+        match command {
+            // Play the fade
+            RemoteCommand::Fade {
+                channel,
+                value,
+                duration,
+            } => {
+                let fade = Fade {
+                    channel,
+                    value,
+                    duration: Remote::to_duration(duration)?,
+                };
+                let reply = self.send("POST", "playFade", Some(&fade)).await?;
+                println!("{}", Remote::message(&reply));
+            }
+
+            // Recall the scene
+            RemoteCommand::Scene {
+                command: SceneCommand::Recall { name, duration },
+            } => {
+                let recall = SceneRecall {
+                    name,
+                    duration: Remote::to_duration(duration)?,
+                };
+                let reply = self.send("POST", "recallScene", Some(&recall)).await?;
+                println!("{}", Remote::message(&reply));
+            }
+
+            // List the scenes, one per line
+            RemoteCommand::Scene {
+                command: SceneCommand::List,
+            } => match self.send::<()>("GET", "scenes", None).await? {
+                WebReply::Scenes { scenes, .. } => {
+                    for scene in scenes {
+                        println!("{}", scene);
+                    }
+                }
+                reply => return Err(anyhow!("Unexpected reply: {:?}", reply)),
+            },
+
+            // Print the values of the universe
+            RemoteCommand::Universe {
+                command: UniverseCommand::Dump,
+            } => match self.send::<()>("GET", "universe", None).await? {
+                WebReply::Universe { universe, .. } => {
+                    println!("{}", serde_json::to_string(&universe)?);
+                }
+                reply => return Err(anyhow!("Unexpected reply: {:?}", reply)),
+            },
+        }
+        Ok(())
+    }
+
+    /// A helper function to convert a duration in seconds
+    ///
+    fn to_duration(duration: Option<f64>) -> Result<Option<Duration>> {
+        duration
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs)
+                    .map_err(|_| anyhow!("Invalid duration: {}.", secs))
+            })
+            .transpose()
+    }
+
+    /// A helper function to find the message of a reply
+    ///
+    fn message(reply: &WebReply) -> String {
+        match reply {
+            WebReply::Generic { message, .. } | WebReply::Error { message, .. } => message.clone(),
+            WebReply::Fade { message, id, .. } => format!("{} (id {})", message, id),
+            reply => format!("{:?}", reply),
+        }
+    }
+
+    /// A helper method to send a request to the running instance and read
+    /// the reply
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the running instance cannot be
+    /// reached, does not reply in time, or replies with a failure.
+    ///
+    async fn send<T: serde::Serialize>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&T>,
+    ) -> Result<WebReply> {
+        // Compose the request
+        let body = body.map(serde_json::to_string).transpose()?;
+        let mut request = format!(
+            "{} /{}/{} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n",
+            method, API_VERSION, path, self.address
+        );
+        if let Some(token) = self.token.as_ref() {
+            request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        if let Some(body) = body.as_ref() {
+            request.push_str(&format!(
+                "Content-Type: application/json\r\nContent-Length: {}\r\n",
+                body.len()
+            ));
+        }
+        request.push_str("\r\n");
+        request.push_str(body.as_deref().unwrap_or(""));
+
+        // Send the request and read the reply, within the time limit
+        let reply = timeout(REMOTE_TIMEOUT, async {
+            let mut stream = TcpStream::connect(&self.address).await?;
+            stream.write_all(request.as_bytes()).await?;
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).await?;
+            Ok::<Vec<u8>, std::io::Error>(reply)
+        })
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "No reply from {} within {:?}.",
+                self.address,
+                REMOTE_TIMEOUT
+            )
+        })?
+        .map_err(|error| anyhow!("Unable to reach Vulcan at {}: {}.", self.address, error))?;
+
+        // Read the reply and check for a failure
+        let reply: WebReply = serde_json::from_slice(&Remote::read_body(&reply)?)?;
+        if !reply.is_success() {
+            return Err(anyhow!("{}", Remote::message(&reply)));
+        }
+        Ok(reply)
+    }
+
+    /// A helper function to find the body of an http reply, joining the
+    /// chunks if the body was sent in chunks
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the reply is not valid.
+    ///
+    fn read_body(reply: &[u8]) -> Result<Vec<u8>> {
+        // Split the headers from the body
+        let split = reply
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| anyhow!("Invalid reply."))?;
+        let headers = String::from_utf8_lossy(&reply[..split]).to_lowercase();
+        let mut body = &reply[split + 4..];
+
+        // Return the body as is, unless it was sent in chunks
+        if !headers.contains("transfer-encoding: chunked") {
+            return Ok(body.to_vec());
+        }
+        let mut joined = Vec::new();
+        loop {
+            // Read the size of the next chunk
+            let line = body
+                .windows(2)
+                .position(|window| window == b"\r\n")
+                .ok_or_else(|| anyhow!("Invalid chunk."))?;
+            let size = usize::from_str_radix(String::from_utf8_lossy(&body[..line]).trim(), 16)?;
+            body = &body[line + 2..];
+
+            // Stop at the last chunk
+            if size == 0 {
+                return Ok(joined);
+            }
+            let chunk = body
+                .get(..size)
+                .ok_or_else(|| anyhow!("Incomplete chunk."))?;
+            joined.extend_from_slice(chunk);
+            body = body.get(size + 2..).unwrap_or_default();
+        }
+    }
+}
+
+// Tests of the remote module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the body of a reply is found, with or without chunks
+    #[test]
+    fn read_reply_body() {
+        let reply = b"HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\n{\"a\"";
+        assert_eq!(Remote::read_body(reply).unwrap(), b"{\"a\"");
+        let reply =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        assert_eq!(Remote::read_body(reply).unwrap(), b"abcde");
+        assert!(Remote::read_body(b"HTTP/1.1 200 OK").is_err());
+    }
+}
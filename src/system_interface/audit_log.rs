@@ -0,0 +1,207 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the audit log, which records every request that
+//! changes the state of the program. Recent entries are kept in memory for
+//! queries and, if a file is specified, every entry is appended to the file
+//! as one line of JSON.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Import tracing features
+use tracing::error;
+
+// Define the audit constants
+const MAX_ENTRIES: usize = 10_000; // the number of entries kept in memory
+const DEFAULT_LIMIT: usize = 100; // the default number of entries returned by a query
+
+/// A structure to record requests and search the record
+///
+pub struct AuditLog {
+    file: Option<File>,            // the file for the audit log, if any
+    entries: VecDeque<AuditEntry>, // the most recent entries, oldest first
+}
+
+// Implement key features of the audit log
+impl AuditLog {
+    /// A function to create a new audit log. If there is an existing file,
+    /// the most recent entries are loaded from it.
+    ///
+    /// # Errors
+    ///
+    /// This function will log an error if the file cannot be opened. The
+    /// audit log is then kept in memory only.
+    ///
+    pub fn new(path: Option<&Path>) -> Self {
+        // Load any existing entries
+        let mut entries = VecDeque::new();
+        if let Some(contents) = path.and_then(|path| fs::read_to_string(path).ok()) {
+            for line in contents.lines() {
+                if let Ok(entry) = serde_json::from_str(line) {
+                    entries.push_back(entry);
+                }
+                if entries.len() > MAX_ENTRIES {
+                    entries.pop_front();
+                }
+            }
+        }
+
+        // Try to open the file for appending
+        let file = path.and_then(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|error| error!("Unable to open audit log {}: {}.", path.display(), error))
+                .ok()
+        });
+
+        // Return the new audit log
+        Self { file, entries }
+    }
+
+    /// A method to record a request and its result
+    ///
+    pub fn record(&mut self, client: &Client, request: &Request, is_success: bool) {
+        // Compose the entry
+        let entry = AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or(0),
+            identity: client.identity.clone(),
+            address: client.address.clone(),
+            client_id: client.client_id.clone(),
+            request: request.name().to_string(),
+            parameters: serde_json::to_value(request).unwrap_or_default(),
+            is_success,
+        };
+
+        // Append the entry to the file
+        if let Some(file) = self.file.as_mut() {
+            match serde_json::to_string(&entry) {
+                Ok(line) => {
+                    if let Err(error) = writeln!(file, "{}", line) {
+                        error!("Unable to write to audit log: {}.", error);
+                    }
+                }
+                Err(error) => error!("Unable to serialize audit entry: {}.", error),
+            }
+        }
+
+        // Save the entry in memory, dropping the oldest entry if needed
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// A method to return the newest entries that match the query, oldest
+    /// first
+    ///
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        // Collect the newest matching entries
+        let mut entries: Vec<AuditEntry> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| query.since.is_none_or(|since| entry.timestamp >= since))
+            .filter(|entry| {
+                query
+                    .identity
+                    .as_ref()
+                    .is_none_or(|identity| entry.identity == *identity)
+            })
+            .filter(|entry| {
+                query
+                    .client_id
+                    .as_ref()
+                    .is_none_or(|client_id| entry.client_id.as_ref() == Some(client_id))
+            })
+            .filter(|entry| {
+                query
+                    .request
+                    .as_ref()
+                    .is_none_or(|request| entry.request == *request)
+            })
+            .take(query.limit.unwrap_or(DEFAULT_LIMIT))
+            .cloned()
+            .collect();
+
+        // Return them in order
+        entries.reverse();
+        entries
+    }
+}
+
+// Tests of the audit log module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that queries filter and limit the entries
+    #[test]
+    fn query_audit() {
+        // Record several requests from two clients
+        let mut audit_log = AuditLog::new(None);
+        let operator = Client::from_token("operator-token", Role::Operator, None);
+        let admin = Client::from_token("admin-token", Role::Admin, None);
+        let booth = Client {
+            client_id: Some("booth".into()),
+            ..operator.clone()
+        };
+        audit_log.record(&operator, &Request::AllStop, true);
+        audit_log.record(&admin, &Request::ReloadConfig, false);
+        audit_log.record(&operator, &Request::ReloadConfig, true);
+        audit_log.record(&booth, &Request::Tap, true);
+
+        // Check the filters
+        let query = AuditQuery {
+            identity: Some(operator.identity.clone()),
+            ..Default::default()
+        };
+        let entries = audit_log.query(&query);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].request, "allStop");
+        let query = AuditQuery {
+            client_id: Some("booth".into()),
+            ..Default::default()
+        };
+        let entries = audit_log.query(&query);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].request, "tap");
+
+        // Check the limit returns the newest entry
+        let query = AuditQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let entries = audit_log.query(&query);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].identity, operator.identity);
+        assert_eq!(entries[0].client_id.as_deref(), Some("booth"));
+        assert!(entries[0].is_success);
+    }
+}
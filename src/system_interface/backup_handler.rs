@@ -15,96 +15,182 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! This module implements the connection to a Redis backup server to maintain
-//! a backup of the program state. This handler syncs the current media playlist
-//!  to the server. This module does nothing if a Redis server is not connected.
+//! This module implements the connections to the backup targets (Redis
+//! servers or local files) to maintain a backup of the program state. Every
+//! change is written to each target independently, so a single failed target
+//! does not eliminate crash recovery. Each kind of target is a backup store,
+//! so a new kind only needs to implement the store trait. This module does
+//! nothing if no target is specified.
 //!
 //! WARNING: This module assumes no authorized systems/operators are compromised.
 
 // Import crate definitions
 use crate::definitions::*;
 
+// Import standard library features
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Import the memory store features, for tests
+#[cfg(test)]
+use fnv::FnvHashMap;
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
 // Import tracing features
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 // Imprt redis client library
 use redis::{Commands, ConnectionLike, RedisResult};
 
-// Import YAML processing library
-use serde_yaml;
-
-/// A structure which holds a reference to the Redis server (if it exists) and
-/// syncronizes local data to and from the server.
+/// A structure which holds a reference to each backup target and
+/// syncronizes local data to and from the targets.
 ///
 /// # Notes
 ///
-/// When created, the status handler will attempt to connect to the requested
-/// redis server. If the status handler cannot make the connection, the status
-/// handler will raise an error and return none.
+/// When created, the backup handler will attempt to open every requested
+/// target. Any target that cannot be opened is reported as disconnected,
+/// and the other targets are still written.
 ///
 pub struct BackupHandler {
-    address: String, // the listening address for this instance of the controller for unique identification
-    connection: Option<redis::Connection>, // the Redis connection, if it exists
-    universe: Universe, // the current state of all the DMX channels
+    key: String, // the key of the universe on each target, from the name of this instance
+    targets: Vec<BackupTarget>, // the places the backup is written, in the order they were specified
+    universe: Universe,         // the current state of all the DMX channels
+    events: EventSend,          // the line to announce backup failures
+    max_age: Option<u64>, // the age of the oldest backup restored at startup, in secs, if limited
+    expired: Option<BackupData>, // the backup found at startup that was too old to restore, if any
 }
 
-// Implement key features for the status handler
-impl BackupHandler {
-    /// A function to create and return a new backup handler.
+/// A structure to hold the universe with the time it was backed up
+///
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupData {
+    timestamp: Option<u64>, // the time of the backup in ms since the unix epoch, if known
+    universe: Universe,     // the backed up state of all the DMX channels
+}
+
+/// A structure to hold one backup target and the result of its writes
+///
+struct BackupTarget {
+    location: String, // the location of the target, without any password
+    store: Option<Box<dyn BackupStore>>, // the open target, if it could be opened
+    last_success: Option<u64>, // the time of the last successful write, in ms since the unix epoch
+    last_failure: Option<u64>, // the time of the last failed write, in ms since the unix epoch
+    pending: usize,   // the size of the latest universe that has not been written, in bytes
+}
+
+/// A trait for a place to keep the backup, so that the logic of the backup
+/// handler does not depend on any one kind of storage
+///
+pub trait BackupStore: Send {
+    /// A method to write the universe under the key, returning whether it
+    /// was written
+    ///
+    fn write(&mut self, key: &str, universe_string: &str) -> bool;
+
+    /// A method to read the universe under the key, if the store holds one
     ///
     /// # Errors
     ///
-    /// This function will raise an error if it is unable to connect to the
-    /// Redis server provided.
-    ///
-    pub async fn new(address: String, server_location: Option<String>) -> Self {
-        // If a server location was specified
-        if let Some(location) = server_location {
-            // Try to connect to the Redis server
-            if let Ok(client) = redis::Client::open(location.as_str()) {
-                // Try to get a copy of the Redis connection
-                if let Ok(mut connection) = client.get_connection() {
-                    // Set the snapshot settings
-                    let result: RedisResult<redis::Value> = connection.req_command(
-                        redis::Cmd::new()
-                            .arg("CONFIG")
-                            .arg("SET")
-                            .arg("save")
-                            .arg("60 1"),
-                    );
+    /// This method will raise an error if the store cannot be read.
+    ///
+    fn read(&mut self, key: &str) -> Result<Option<String>, ()>;
 
-                    // Unpack the result from the operation
-                    if let Err(..) = result {
-                        // Warn that it wasn't possible to update the current scene
-                        error!("Unable to set Redis snapshot settings.");
-                    }
+    /// A method to check whether the store can still be written
+    ///
+    fn is_connected(&mut self) -> bool;
 
-                    // Return the new backup handler
-                    return Self {
-                        address,
-                        connection: Some(connection),
-                        universe: Universe::new(),
-                    };
+    /// A method to remove the universe under the key, ignoring any errors
+    ///
+    fn clear(&mut self, key: &str);
+}
 
-                // Indicate that there was a failure to connect to the server
-                } else {
-                    error!("Unable to connect to backup server: {}.", location);
-                }
+/// A structure to keep the backup on a Redis server
+///
+struct RedisStore {
+    connection: redis::Connection, // the open connection to the server
+}
 
-            // Indicate that there was a failure to connect to the server
-            } else {
-                error!("Unable to connect to backup server: {}.", location);
-            }
-        }
+/// A structure to keep the backup in a local file
+///
+struct FileStore {
+    path: PathBuf, // the location of the file
+}
+
+/// A structure to keep the backup in memory, for tests. Clones share the
+/// same values, so a test can inspect what was written.
+///
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    values: Arc<Mutex<FnvHashMap<String, String>>>, // the values under each key
+}
+
+// Implement key features for the status handler
+impl BackupHandler {
+    /// A function to create and return a new backup handler for every
+    /// location (a Redis url, or a local file as file:///path), with the
+    /// universe stored under the instance id on each server. Backups
+    /// older than the maximum age (if any) are not restored at startup. Any
+    /// failure to save a backup is announced on the event line.
+    ///
+    /// # Errors
+    ///
+    /// This function will log an error for each target that it is unable to
+    /// open. Those targets are reported as disconnected.
+    ///
+    pub async fn new(
+        instance_id: String,
+        locations: Vec<String>,
+        max_age: Option<u64>,
+        events: EventSend,
+    ) -> Self {
+        let stores = locations
+            .iter()
+            .map(|location| (redact(location), open_store(location)))
+            .collect();
+        BackupHandler::with_stores(&instance_id, stores, max_age, events)
+    }
 
-        // If a location was not specified or the connection failed, return without a redis connection
+    /// A function to create and return a new backup handler for stores that
+    /// are already open (or that could not be opened, as None), with the
+    /// location of each to report
+    ///
+    pub fn with_stores(
+        instance_id: &str,
+        stores: Vec<(String, Option<Box<dyn BackupStore>>)>,
+        max_age: Option<u64>,
+        events: EventSend,
+    ) -> Self {
+        let targets = stores
+            .into_iter()
+            .map(|(location, store)| BackupTarget {
+                location,
+                store,
+                last_success: None,
+                last_failure: None,
+                pending: 0,
+            })
+            .collect();
         Self {
-            address,
-            connection: None,
+            key: format!("vulcan:{}:universe", instance_id),
+            targets,
             universe: Universe::new(),
+            events,
+            max_age,
+            expired: None,
         }
     }
 
+    /// A method to replace the maximum age of a backup that is restored
+    /// without being forced
+    ///
+    pub fn set_max_age(&mut self, max_age: Option<u64>) {
+        self.max_age = max_age;
+    }
+
     /// A method to backup a new fade to the backup server.
     ///
     /// # Errors
@@ -113,37 +199,46 @@ impl BackupHandler {
     /// Redis server.
     ///
     pub async fn backup_fade(&mut self, fade: Fade) {
-        // If the redis connection exists
-        if let Some(mut connection) = self.connection.take() {
-            // Add the channel to the current universe
-            self.universe.set(fade.channel, fade.value);
-
-            // Try to serialize the universe
-            let universe_string = match serde_yaml::to_string(&self.universe) {
-                Ok(string) => string,
-                Err(error) => {
-                    error!("Unable to parse universe: {}.", error);
-
-                    // Put the connection back
-                    self.connection = Some(connection);
-                    return;
-                }
-            };
+        // Add the channel to the current universe
+        self.universe.set(fade.channel, fade.value);
 
-            // Try to copy the data to the server
-            let result: RedisResult<bool> = connection.set(
-                &format!("vulcan:{}:universe", self.address),
-                &universe_string,
-            );
+        // Copy the universe to the server
+        self.write_universe("fade");
+    }
 
-            // Alert that the channel list was not set
-            if let Err(..) = result {
-                error!("Unable to backup fade onto backup server.");
-            }
+    /// A method to backup a fade of a 16-bit value to the backup server.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to connect to the
+    /// Redis server.
+    ///
+    pub async fn backup_wide_fade(&mut self, fade: &WideFade) {
+        // Add the channels to the current universe
+        self.universe.set(fade.channel, (fade.value >> 8) as u8);
+        if let Some(fine_channel) = fade.fine_channel {
+            self.universe.set(fine_channel, fade.value as u8);
+        }
+
+        // Copy the universe to the server
+        self.write_universe("fade");
+    }
 
-            // Put the connection back
-            self.connection = Some(connection);
+    /// A method to backup a recalled scene to the backup server.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if it is unable to connect to the
+    /// Redis server.
+    ///
+    pub async fn backup_scene(&mut self, scene: &Scene) {
+        // Add each of the channels to the current universe
+        for (channel, value) in scene.values.iter() {
+            self.universe.set(*channel, *value);
         }
+
+        // Copy the universe to the server
+        self.write_universe("scene");
     }
 
     /// A method to backup the full universe to the backup server.
@@ -154,87 +249,264 @@ impl BackupHandler {
     /// Redis server.
     ///
     pub async fn backup_universe(&mut self, universe: Universe) {
-        // If the redis connection exists
-        if let Some(mut connection) = self.connection.take() {
-            // Replace the current universe
-            self.universe = universe;
-
-            // Try to serialize the universe
-            let universe_string = match serde_yaml::to_string(&self.universe) {
-                Ok(string) => string,
-                Err(error) => {
-                    error!("Unable to parse universe: {}.", error);
-
-                    // Put the connection back
-                    self.connection = Some(connection);
-                    return;
-                }
-            };
+        // Replace the current universe
+        self.universe = universe;
+
+        // Copy the universe to the server
+        self.write_universe("universe");
+    }
+
+    /// A helper method to copy the current universe to every backup target.
+    /// The description of the change is used in any error messages.
+    ///
+    fn write_universe(&mut self, description: &str) {
+        // Try to serialize the universe with the time
+        if self.targets.is_empty() {
+            return;
+        }
+        let data = BackupData {
+            timestamp: Some(timestamp()),
+            universe: self.universe.clone(),
+        };
+        let universe_string = match serde_yaml::to_string(&data) {
+            Ok(string) => string,
+            Err(error) => {
+                error!("Unable to parse universe: {}.", error);
+                return;
+            }
+        };
 
-            // Try to copy the data to the server
-            let result: RedisResult<bool> = connection.set(
-                &format!("vulcan:{}:universe", self.address),
-                &universe_string,
-            );
+        // Copy the data to each target, regardless of the others
+        for target in self.targets.iter_mut() {
+            target.pending = universe_string.len();
+            let Some(store) = target.store.as_mut() else {
+                continue;
+            };
 
-            // Alert that the channel list was not set
-            if let Err(..) = result {
-                error!("Unable to backup universe onto backup server.");
+            // Alert that the universe was not written
+            if !store.write(&self.key, &universe_string) {
+                error!(
+                    "Unable to backup {} onto backup target {}.",
+                    description, target.location
+                );
+                target.last_failure = Some(timestamp());
+                self.events.send(Event::BackupFailed {
+                    message: format!(
+                        "Unable to backup {} onto backup target {}.",
+                        description, target.location
+                    ),
+                });
+
+            // Otherwise, note the success
+            } else {
+                target.last_success = Some(timestamp());
+                target.pending = 0;
             }
+        }
+    }
+
+    /// A method to return the current universe (the final value of every
+    /// channel, including any fades still in progress)
+    ///
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
 
-            // Put the connection back
-            self.connection = Some(connection);
+    /// A method to return the state of the backup and of each target,
+    /// including the last successful write and any changes that have not
+    /// been written. The backup is connected as long as any target is
+    /// connected, and it is as recent as its most recent target.
+    ///
+    pub fn status(&mut self) -> BackupStatus {
+        // Check every target
+        let targets: Vec<BackupTargetStatus> = self
+            .targets
+            .iter_mut()
+            .map(|target| BackupTargetStatus {
+                location: target.location.clone(),
+                state: if target
+                    .store
+                    .as_mut()
+                    .is_some_and(|store| store.is_connected())
+                {
+                    BackupState::Connected
+                } else {
+                    BackupState::Disconnected
+                },
+                last_success: target.last_success,
+                last_failure: target.last_failure,
+                pending_bytes: target.pending,
+            })
+            .collect();
+
+        // Combine the targets
+        let state = if targets.is_empty() {
+            BackupState::Disabled
+        } else if targets
+            .iter()
+            .any(|target| target.state == BackupState::Connected)
+        {
+            BackupState::Connected
+        } else {
+            BackupState::Disconnected
+        };
+        BackupStatus {
+            state,
+            last_success: targets
+                .iter()
+                .filter_map(|target| target.last_success)
+                .max(),
+            last_failure: targets
+                .iter()
+                .filter_map(|target| target.last_failure)
+                .max(),
+            pending_bytes: targets
+                .iter()
+                .map(|target| target.pending_bytes)
+                .min()
+                .unwrap_or(0),
+            is_expired: self.expired.is_some(),
+            targets,
         }
     }
 
-    /// A method to reload an existing backup from the backup server. If the
-    /// data exists, this function returns the existing backup data.
+    /// A method to write the current universe to every backup target right
+    /// away, to confirm that the backup works
     ///
     /// # Errors
     ///
-    /// This function will raise an error if it is unable to connect to the
-    /// Redis server.
+    /// This method will raise an error if any target is not connected or
+    /// the universe could not be written to it.
     ///
-    pub fn reload_backup(&mut self) -> Option<Universe> {
-        // If the redis connection exists
-        if let Some(mut connection) = self.connection.take() {
-            // Check to see if there is a universe
-            let result: RedisResult<String> =
-                connection.get(&format!("vulcan:{}:universe", self.address));
+    pub fn flush(&mut self) -> Result<(), ErrorCode> {
+        let is_connected = self.targets.iter_mut().all(|target| {
+            target
+                .store
+                .as_mut()
+                .is_some_and(|store| store.is_connected())
+        });
+        if self.targets.is_empty() || !is_connected {
+            return Err(ErrorCode::BackupUnavailable);
+        }
+        self.write_universe("flush");
+        if self.targets.iter().any(|target| target.pending > 0) {
+            return Err(ErrorCode::BackupUnavailable);
+        }
+        info!("Flushed the backup to every backup target.");
+        Ok(())
+    }
 
-            // If something was received
-            if let Ok(universe_string) = result {
-                // Warn that existing data was found
-                warn!("Vulcan detected lingering backup data. Reloading ...");
+    /// A method to read the universe back from the first backup target that
+    /// holds one, as it would be after a crash, and make it the current
+    /// universe. A backup older than the maximum age is only restored if the
+    /// restore is forced, and a forced restore prefers the backup that was
+    /// too old to restore at startup (as the targets have since been
+    /// overwritten).
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if no target can be read, no target
+    /// holds a backup, or the backup is too old and the restore is not
+    /// forced.
+    ///
+    pub fn restore(&mut self, is_forced: bool) -> Result<Universe, ErrorCode> {
+        // Use the expired backup from startup, if forced
+        if is_forced && let Some(data) = self.expired.take() {
+            info!("Restored the expired universe from startup.");
+            self.universe = data.universe.clone();
+            return Ok(data.universe);
+        }
 
-                // Try to parse the data
-                let mut universe = Universe::new();
-                if let Ok(new_universe) = serde_yaml::from_str(universe_string.as_str()) {
-                    universe = new_universe;
+        // Try each target in order
+        let mut error = ErrorCode::BackupUnavailable;
+        for target in self.targets.iter_mut() {
+            let Some(store) = target.store.as_mut() else {
+                continue;
+            };
+            match store.read(&self.key) {
+                Ok(Some(universe_string)) => {
+                    // Try to parse the backup and check its age
+                    let Some(data) = parse(&universe_string) else {
+                        error = ErrorCode::BackupNotFound;
+                        continue;
+                    };
+                    if !is_forced && let Some(age) = expired_age(self.max_age, &data) {
+                        return Err(ErrorCode::BackupExpired { age });
+                    }
+                    info!(
+                        "Restored the universe from backup target {}.",
+                        target.location
+                    );
+                    self.universe = data.universe.clone();
+                    return Ok(data.universe);
                 }
+                Ok(None) => error = ErrorCode::BackupNotFound,
+                Err(()) => (),
+            }
+        }
+        Err(error)
+    }
 
-                // Save the universe
-                self.universe = universe.clone();
+    /// A method to reload an existing backup from the first backup target
+    /// that holds one. If the data exists and is not older than the maximum
+    /// age, this function returns the existing backup data. An older backup
+    /// is kept so that it can still be restored by force.
+    ///
+    /// # Errors
+    ///
+    /// This function will silently return nothing if no target can be read.
+    ///
+    pub fn reload_backup(&mut self) -> Option<Universe> {
+        // Check each target in order for a universe
+        for target in self.targets.iter_mut() {
+            let Some(store) = target.store.as_mut() else {
+                continue;
+            };
 
-                // Put the connection back
-                self.connection = Some(connection);
+            // If something was received
+            if let Ok(Some(universe_string)) = store.read(&self.key) {
+                // Try to parse the data
+                let data = parse(&universe_string).unwrap_or(BackupData {
+                    timestamp: None,
+                    universe: Universe::new(),
+                });
+
+                // Keep a backup that is too old, without restoring it
+                if let Some(age) = expired_age(self.max_age, &data) {
+                    match age {
+                        Some(age) => warn!(
+                            "Vulcan found a backup on {} from {} secs ago, older than the maximum age. It was not restored.",
+                            target.location, age
+                        ),
+                        None => warn!(
+                            "Vulcan found a backup on {} of unknown age. It was not restored.",
+                            target.location
+                        ),
+                    }
+                    self.expired = Some(data);
+                    return None;
+                }
 
-                // Return all the backup information
-                return Some(universe);
+                // Warn that existing data was found
+                warn!(
+                    "Vulcan detected lingering backup data on {}. Reloading ...",
+                    target.location
+                );
+
+                // Save and return the universe
+                self.universe = data.universe.clone();
+                return Some(data.universe);
             }
-
-            // Put the connection back
-            self.connection = Some(connection);
         }
 
-        // Silently return nothing if the connection does not exist or there was not any data
+        // Silently return nothing if no target holds any data
         None
     }
 }
 
 // Implement the drop trait for the backup handler struct.
 impl Drop for BackupHandler {
-    /// This method removes all the the existing data from the server.
+    /// This method removes all the the existing data from every target.
     ///
     /// # Errors
     ///
@@ -242,14 +514,191 @@ impl Drop for BackupHandler {
     /// connection is being closed.
     ///
     fn drop(&mut self) {
-        // If the redis connection exists
-        if let Some(mut connection) = self.connection.take() {
-            // Try to delete the universe backup if it exists
-            let _: RedisResult<bool> = connection.del(&format!("vulcan:{}:universe", self.address));
+        for target in self.targets.iter_mut() {
+            if let Some(store) = target.store.as_mut() {
+                store.clear(&self.key);
+            }
+        }
+    }
+}
+
+/// A helper function to open a backup target: a local file for a location
+/// that starts with file:// and otherwise a Redis server
+///
+fn open_store(location: &str) -> Option<Box<dyn BackupStore>> {
+    // Open a local file
+    if let Some(path) = location.strip_prefix("file://") {
+        return Some(Box::new(FileStore {
+            path: PathBuf::from(path),
+        }));
+    }
+
+    // Try to connect to the Redis server
+    let Ok(mut connection) =
+        redis::Client::open(location).and_then(|client| client.get_connection())
+    else {
+        error!("Unable to connect to backup server: {}.", redact(location));
+        return None;
+    };
+
+    // Set the snapshot settings
+    let result: RedisResult<redis::Value> = connection.req_command(
+        redis::Cmd::new()
+            .arg("CONFIG")
+            .arg("SET")
+            .arg("save")
+            .arg("60 1"),
+    );
+
+    // Unpack the result from the operation
+    if result.is_err() {
+        // Warn that it wasn't possible to update the current scene
+        error!("Unable to set Redis snapshot settings.");
+    }
+    Some(Box::new(RedisStore { connection }))
+}
+
+// Implement the Redis store
+impl BackupStore for RedisStore {
+    fn write(&mut self, key: &str, universe_string: &str) -> bool {
+        let result: RedisResult<bool> = self.connection.set(key, universe_string);
+        result.is_ok()
+    }
+
+    fn read(&mut self, key: &str) -> Result<Option<String>, ()> {
+        self.connection.get(key).map_err(|_| ())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connection.check_connection()
+    }
+
+    fn clear(&mut self, key: &str) {
+        let _: RedisResult<bool> = self.connection.del(key);
+    }
+}
+
+// Implement the file store (the file holds only one universe, so the key is
+// not needed)
+impl BackupStore for FileStore {
+    /// A file is replaced in one step, so a crash never leaves half a backup
+    ///
+    fn write(&mut self, _key: &str, universe_string: &str) -> bool {
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, universe_string)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .is_ok()
+    }
+
+    fn read(&mut self, _key: &str) -> Result<Option<String>, ()> {
+        match fs::read_to_string(&self.path) {
+            Ok(universe_string) => Ok(Some(universe_string)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(()),
+        }
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .is_none_or(|parent| parent.is_dir())
+    }
+
+    fn clear(&mut self, _key: &str) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Implement key features of the memory store
+#[cfg(test)]
+impl MemoryStore {
+    /// A method to return the value under the key, if any
+    ///
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values.lock().ok()?.get(key).cloned()
+    }
+}
+
+// Implement the memory store
+#[cfg(test)]
+impl BackupStore for MemoryStore {
+    fn write(&mut self, key: &str, universe_string: &str) -> bool {
+        self.values
+            .lock()
+            .map(|mut values| values.insert(key.to_string(), universe_string.to_string()))
+            .is_ok()
+    }
+
+    fn read(&mut self, key: &str) -> Result<Option<String>, ()> {
+        self.values
+            .lock()
+            .map(|values| values.get(key).cloned())
+            .map_err(|_| ())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        true
+    }
+
+    fn clear(&mut self, key: &str) {
+        if let Ok(mut values) = self.values.lock() {
+            values.remove(key);
+        }
+    }
+}
+
+/// A helper function to parse a backup, including a backup of only the
+/// universe (from an earlier version, without a time)
+///
+fn parse(universe_string: &str) -> Option<BackupData> {
+    serde_yaml::from_str(universe_string).ok().or_else(|| {
+        serde_yaml::from_str(universe_string)
+            .ok()
+            .map(|universe| BackupData {
+                timestamp: None,
+                universe,
+            })
+    })
+}
+
+/// A helper function to return the age of a backup (in secs, if known) if
+/// it is older than the maximum age. A backup without a time is too old for
+/// any maximum age.
+///
+fn expired_age(max_age: Option<u64>, data: &BackupData) -> Option<Option<u64>> {
+    let max_age = max_age?;
+    match data.timestamp {
+        Some(time) => {
+            let age = timestamp().saturating_sub(time) / 1000;
+            (age > max_age).then_some(Some(age))
         }
+        None => Some(None),
     }
 }
 
+/// A helper function to remove any password from the location of a target,
+/// so that it can be logged and reported
+///
+fn redact(location: &str) -> String {
+    match (location.find("://"), location.rfind('@')) {
+        (Some(scheme), Some(at)) if at > scheme => {
+            format!("{}://***{}", &location[..scheme], &location[at..])
+        }
+        _ => location.to_string(),
+    }
+}
+
+/// A helper function to return the current time, in ms since the unix
+/// epoch
+///
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 // Tests of the status module
 #[cfg(test)]
 mod tests {
@@ -261,10 +710,11 @@ mod tests {
         // Create the backup handler
         let mut backup_handler = BackupHandler::new(
             String::from("127.0.0.1:27655"),
-            Some(String::from("redis://127.0.0.1:6379")),
+            vec![String::from("redis://127.0.0.1:6379")],
+            None,
+            EventSend::new(),
         )
         .await;
-
         // Make sure there is no existing backup
         if backup_handler.reload_backup().is_some() {
             panic!("Backup already existed before beginning of the test.");
@@ -309,4 +759,121 @@ mod tests {
             panic!("Backup was not reloaded.");
         }
     }
+
+    // Test that a failed target is reported while another keeps the backup
+    #[tokio::test]
+    async fn failed_target() {
+        // Create a backup handler for a server that is not listening and a file
+        let path = std::env::temp_dir().join(format!("vulcan-backup-{}.yaml", std::process::id()));
+        let mut backup_handler = BackupHandler::new(
+            String::from("127.0.0.1:27656"),
+            vec![
+                String::from("redis://:secret@127.0.0.1:1"),
+                format!("file://{}", path.display()),
+            ],
+            None,
+            EventSend::new(),
+        )
+        .await;
+
+        // A change is pending for the server, but the file is up to date
+        let mut universe = Universe::new();
+        universe.set(3, 42);
+        backup_handler.backup_universe(universe).await;
+        let status = backup_handler.status();
+        assert_eq!(status.state, BackupState::Connected);
+        assert_eq!(status.pending_bytes, 0);
+        assert_eq!(status.targets[0].location, "redis://***@127.0.0.1:1");
+        assert_eq!(status.targets[0].state, BackupState::Disconnected);
+        assert!(status.targets[0].pending_bytes > 0);
+        assert!(status.targets[1].last_success.is_some());
+
+        // Flushing reports the missing server, and restoring uses the file
+        assert_eq!(backup_handler.flush(), Err(ErrorCode::BackupUnavailable));
+        assert_eq!(
+            backup_handler
+                .restore(false)
+                .map(|universe| universe.get(3)),
+            Ok(42)
+        );
+
+        // The file is removed when the program closes
+        drop(backup_handler);
+        assert!(!path.exists());
+    }
+
+    // Test that an old backup is only restored by force
+    #[tokio::test]
+    async fn expired_backup() {
+        // Write a backup from two hours ago to a file
+        let path = std::env::temp_dir().join(format!("vulcan-expired-{}.yaml", std::process::id()));
+        let mut universe = Universe::new();
+        universe.set(7, 99);
+        let data = BackupData {
+            timestamp: Some(timestamp() - 7_200_000),
+            universe,
+        };
+        fs::write(&path, serde_yaml::to_string(&data).unwrap()).unwrap();
+
+        // Create a backup handler with a maximum age of one hour
+        let mut backup_handler = BackupHandler::new(
+            String::from("127.0.0.1:27657"),
+            vec![format!("file://{}", path.display())],
+            Some(3600),
+            EventSend::new(),
+        )
+        .await;
+
+        // The backup is held instead of restored at startup
+        assert!(backup_handler.reload_backup().is_none());
+        assert!(backup_handler.status().is_expired);
+
+        // A restore is refused unless it is forced
+        assert!(matches!(
+            backup_handler.restore(false),
+            Err(ErrorCode::BackupExpired { age: Some(age) }) if age >= 7200
+        ));
+        assert_eq!(
+            backup_handler.restore(true).map(|universe| universe.get(7)),
+            Ok(99)
+        );
+        assert!(!backup_handler.status().is_expired);
+    }
+
+    // Test the backup logic against a store in memory
+    #[tokio::test]
+    async fn memory_store() {
+        // Create a backup handler for a store in memory
+        let store = MemoryStore::default();
+        let mut backup_handler = BackupHandler::with_stores(
+            "memory",
+            vec![(String::from("memory"), Some(Box::new(store.clone())))],
+            None,
+            EventSend::new(),
+        );
+
+        // Every change is written to the store under the instance key
+        backup_handler
+            .backup_fade(Fade {
+                channel: 9,
+                value: 77,
+                duration: None,
+            })
+            .await;
+        assert!(store.get("vulcan:memory:universe").is_some());
+        assert_eq!(backup_handler.status().state, BackupState::Connected);
+        assert_eq!(backup_handler.status().pending_bytes, 0);
+
+        // The universe is reloaded from the store
+        assert_eq!(
+            backup_handler
+                .reload_backup()
+                .map(|universe| universe.get(9)),
+            Some(77)
+        );
+
+        // The store is cleared when the program closes
+        drop(backup_handler);
+        assert_eq!(store.get("vulcan:memory:universe"), None);
+    }
 }
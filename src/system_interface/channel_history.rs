@@ -0,0 +1,243 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module records the values of the output over time, so operators can
+//! chart what a channel actually did (such as a flicker last night). The
+//! output is sampled at the resolution of the history, and only the samples
+//! that change a channel are kept, up to a limit for each channel.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Import Tokio features
+use tokio::sync::watch;
+
+/// A structure to sample the output and hold the recent changes of each
+/// channel
+///
+pub struct ChannelHistory {
+    config: Option<HistoryConfig>, // the settings of the history, if enabled
+    channels: Vec<VecDeque<HistorySample>>, // the changes of each channel, oldest first, zero-indexed
+    frames: watch::Receiver<Universe>,      // the frames of the output
+    next_sample: Option<Instant>,           // the time of the next sample, if enabled
+}
+
+// Implement key features of the channel history
+impl ChannelHistory {
+    /// A function to create a new channel history of the frames
+    ///
+    pub fn new(config: Option<HistoryConfig>, frames: watch::Receiver<Universe>) -> Self {
+        let mut history = Self {
+            config: None,
+            channels: vec![VecDeque::new(); DMX_MAX as usize],
+            frames,
+            next_sample: None,
+        };
+        history.set_config(config);
+        history
+    }
+
+    /// A method to replace the settings of the history, keeping the samples
+    /// that are still within the new limits. Without settings, every sample
+    /// is forgotten.
+    ///
+    pub fn set_config(&mut self, config: Option<HistoryConfig>) {
+        // Take the next sample right away, at the new resolution
+        self.next_sample = config.as_ref().map(|_| Instant::now());
+
+        // Forget the samples beyond the new limits
+        self.config = config;
+        self.trim(timestamp());
+    }
+
+    /// A method to return the time of the next sample, if the history is
+    /// enabled
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next_sample
+    }
+
+    /// A method to record the latest frame if the next sample is due. Any
+    /// samples that were missed are skipped.
+    ///
+    pub fn sample(&mut self, now: Instant) {
+        // Check that the sample is due
+        let (Some(config), Some(next_sample)) = (&self.config, self.next_sample) else {
+            return;
+        };
+        if next_sample > now {
+            return;
+        }
+
+        // Schedule the next sample, then record the frame
+        let resolution = Duration::from_millis(config.resolution.max(1));
+        let mut next_sample = next_sample + resolution;
+        if next_sample <= now {
+            next_sample = now + resolution;
+        }
+        self.next_sample = Some(next_sample);
+        let frame = self.frames.borrow().clone();
+        self.record(timestamp(), &frame);
+    }
+
+    /// A method to return the samples of a channel within the range (in secs
+    /// before now, or every sample if not specified). The sample before the
+    /// range is included, so that a chart starts at the right value.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the history is disabled or the
+    /// channel is outside the universe.
+    ///
+    pub fn query(&self, channel: u32, range: Option<u64>) -> Result<Vec<HistorySample>, ErrorCode> {
+        self.query_at(channel, range, timestamp())
+    }
+
+    /// A helper method to return the samples of a channel within the range
+    /// before the given time
+    ///
+    fn query_at(
+        &self,
+        channel: u32,
+        range: Option<u64>,
+        now: u64,
+    ) -> Result<Vec<HistorySample>, ErrorCode> {
+        // Check the history and the channel
+        if self.config.is_none() {
+            return Err(ErrorCode::HistoryDisabled);
+        }
+        if !(1..=DMX_MAX).contains(&channel) {
+            return Err(ErrorCode::ChannelOutOfRange { channel });
+        }
+
+        // Find the first sample of the range, including the one before it
+        let samples = &self.channels[channel as usize - 1];
+        let start = range.map_or(0, |range| now.saturating_sub(range * 1000));
+        let first = samples
+            .partition_point(|sample| sample.timestamp < start)
+            .saturating_sub(1);
+        Ok(samples.iter().skip(first).cloned().collect())
+    }
+
+    /// A helper method to record each channel of the frame that changed,
+    /// then forget the samples beyond the limits
+    ///
+    fn record(&mut self, now: u64, frame: &Universe) {
+        for (index, samples) in self.channels.iter_mut().enumerate() {
+            let value = frame.get(index as u32 + 1);
+            if samples.back().is_none_or(|last| last.value != value) {
+                samples.push_back(HistorySample {
+                    timestamp: now,
+                    value,
+                });
+            }
+        }
+        self.trim(now);
+    }
+
+    /// A helper method to forget the samples that are too old or beyond the
+    /// limit of each channel. The newest sample of each channel is kept
+    /// regardless of its age, as it is the current value.
+    ///
+    fn trim(&mut self, now: u64) {
+        // Without settings, forget every sample
+        let Some(config) = &self.config else {
+            self.channels.iter_mut().for_each(VecDeque::clear);
+            return;
+        };
+
+        // Remove the oldest samples of each channel
+        let oldest = now.saturating_sub(config.retention * 1000);
+        for samples in self.channels.iter_mut() {
+            while samples.len() > config.max_samples.max(1)
+                || (samples.len() > 1 && samples[1].timestamp <= oldest)
+            {
+                samples.pop_front();
+            }
+        }
+    }
+}
+
+/// A helper function to return the current time, in ms since the unix
+/// epoch
+///
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Tests of the channel history module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that only changes are kept, within the limits and the range
+    #[tokio::test]
+    async fn record_changes() {
+        let config: HistoryConfig = serde_yaml::from_str("retention: 60\nmaxSamples: 3\n").unwrap();
+        let (_, frames) = watch::channel(Universe::new());
+        let mut history = ChannelHistory::new(Some(config), frames);
+
+        // Record a flicker on channel 1, and a steady channel 2
+        let mut frame = Universe::new();
+        for (second, value) in [(0, 0), (1, 0), (2, 255), (3, 0), (4, 255)] {
+            frame.set(1, value);
+            history.record(second * 1000, &frame);
+        }
+        let values = |samples: Vec<HistorySample>| -> Vec<(u64, u8)> {
+            samples
+                .into_iter()
+                .map(|sample| (sample.timestamp, sample.value))
+                .collect()
+        };
+
+        // Only the newest changes fit in the limit
+        assert_eq!(
+            values(history.query_at(1, None, 4000).unwrap()),
+            vec![(2000, 255), (3000, 0), (4000, 255)]
+        );
+        assert_eq!(
+            values(history.query_at(2, None, 4000).unwrap()),
+            vec![(0, 0)]
+        );
+
+        // A range includes the sample before it
+        assert_eq!(
+            values(history.query_at(1, Some(1), 4500).unwrap()),
+            vec![(3000, 0), (4000, 255)]
+        );
+
+        // Old samples are forgotten, except the current value
+        history.record(120_000, &frame);
+        assert_eq!(
+            values(history.query_at(1, None, 120_000).unwrap()),
+            vec![(4000, 255)]
+        );
+
+        // Channels outside the universe are rejected
+        assert_eq!(
+            history.query_at(513, None, 0),
+            Err(ErrorCode::ChannelOutOfRange { channel: 513 })
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module tracks the request (and the client) that last wrote each
+//! channel, so that several operators sharing a rig can find out who
+//! changed a look.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the last writer of each channel
+///
+#[derive(Default)]
+pub struct ChannelWriters {
+    writers: FnvHashMap<u32, ChannelWriter>, // the last writer of each channel that has been written
+}
+
+// Implement key features of the channel writers
+impl ChannelWriters {
+    /// A function to create a new record, without any writers
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to note the client of a successful request as the writer of
+    /// every channel that the request writes
+    ///
+    pub fn record(&mut self, config: &Config, client: &Client, request: &Request) {
+        // Find the channels of the request
+        let channels = written_channels(config, request);
+        if channels.is_empty() {
+            return;
+        }
+
+        // Note the writer of each channel
+        let writer = ChannelWriter {
+            identity: client.identity.clone(),
+            client_id: client.client_id.clone(),
+            request: request.name().to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        for channel in channels {
+            self.writers.insert(channel, writer.clone());
+        }
+    }
+
+    /// A method to return the last writer of a channel, if any
+    ///
+    pub fn writer(&self, channel: u32) -> Option<ChannelWriter> {
+        self.writers.get(&channel).cloned()
+    }
+}
+
+/// A helper function to list the channels that a request writes. Requests
+/// that replace the whole look write every channel, and requests that only
+/// start something later (such as a cue or a preset) write none.
+///
+fn written_channels(config: &Config, request: &Request) -> Vec<u32> {
+    match request {
+        // The channel of a fade
+        Request::PlayFade { fade } => vec![fade.channel],
+
+        // Every channel of the look
+        Request::LoadUniverse { .. }
+        | Request::AllStop
+        | Request::EmergencyStop { .. }
+        | Request::Undo
+        | Request::Redo
+        | Request::RestoreBackup { .. } => (1..=DMX_MAX).collect(),
+
+        // The channels of a scene
+        Request::RecallScene { recall } => config
+            .scene(&recall.name)
+            .map(|scene| scene.values.keys().copied().collect())
+            .unwrap_or_default(),
+
+        // The channels of an effect or an override
+        Request::PlayEffect { effect } => effect.channels.clone(),
+        Request::SetOverride { values } => values.values.keys().copied().collect(),
+
+        // The channels of a fixture
+        Request::SetPosition { position } => config
+            .fixture(&position.fixture)
+            .and_then(|(fixture, profile)| position.to_fades(fixture, profile))
+            .map(|fades| wide_channels(&fades))
+            .unwrap_or_default(),
+        Request::SetColor { color } => config
+            .fixture(&color.fixture)
+            .and_then(|(fixture, profile)| color.to_fades(fixture, profile))
+            .map(|fades| wide_channels(&fades))
+            .unwrap_or_default(),
+        Request::SetFixtureIntensity { name, .. } => {
+            config.intensity_channels(name).unwrap_or_default()
+        }
+
+        // The channels of a group
+        Request::PlayFan { fan } => group_channels(config, &fan.group),
+        Request::SetGroupLevel { name, .. } | Request::SetGroupBlackout { name, .. } => {
+            group_channels(config, name)
+        }
+
+        // The channels of each request of a batch
+        Request::Batch { requests } => requests
+            .iter()
+            .flat_map(|request| written_channels(config, request))
+            .collect(),
+
+        // Any other request doesn't write channels directly
+        _ => Vec::new(),
+    }
+}
+
+/// A helper function to list the coarse and fine channels of wide fades
+///
+fn wide_channels(fades: &[WideFade]) -> Vec<u32> {
+    fades
+        .iter()
+        .flat_map(|fade| std::iter::once(fade.channel).chain(fade.fine_channel))
+        .collect()
+}
+
+/// A helper function to list the channels of a group, if it exists
+///
+fn group_channels(config: &Config, name: &str) -> Vec<u32> {
+    config
+        .groups
+        .get(name)
+        .map(|group| group.channels.clone())
+        .unwrap_or_default()
+}
+
+// Tests of the channel writers module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that each channel keeps its most recent writer
+    #[test]
+    fn record_writers() {
+        // A booth loads the universe, then a trigger fades one channel
+        let config = Config::default();
+        let mut writers = ChannelWriters::new();
+        let booth = Client {
+            client_id: Some("booth".into()),
+            ..Client::from_token("booth-token", Role::Operator, None)
+        };
+        let trigger = Client::trigger("osc");
+        writers.record(&config, &booth, &Request::from(Universe::new()));
+        let fade = Request::Batch {
+            requests: vec![Request::PlayFade {
+                fade: Fade {
+                    channel: 5,
+                    value: 100,
+                    duration: None,
+                },
+            }],
+        };
+        writers.record(&config, &trigger, &fade);
+
+        // Check the writer of each channel
+        let writer = writers.writer(1).unwrap();
+        assert_eq!(writer.client_id.as_deref(), Some("booth"));
+        assert_eq!(writer.request, "loadUniverse");
+        let writer = writers.writer(5).unwrap();
+        assert_eq!(writer.identity, "trigger");
+        assert_eq!(writer.request, "batch");
+
+        // Requests without channels change nothing
+        writers.record(&config, &trigger, &Request::Tap);
+        assert_eq!(writers.writer(1).unwrap().identity, booth.identity);
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the client tracker, which remembers the clients
+//! that have made requests recently so that operators can see who is
+//! controlling the output.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Define the client constants
+const MAX_CLIENTS: usize = 256; // the most clients that are remembered
+const CONNECTED_TIME: Duration = Duration::from_secs(30); // the time a client is connected after its last request
+const RECENT_TIME: Duration = Duration::from_secs(3600); // the time a client is remembered after its last request
+
+/// A struct to hold the activity of one client with the time it was seen
+///
+struct ClientRecord {
+    activity: ClientActivity, // the activity of the client
+    last_seen: Instant,       // the time of the last request
+}
+
+/// A structure to remember the recent activity of each client
+///
+pub struct ClientTracker {
+    clients: FnvHashMap<String, ClientRecord>, // the records, keyed by client
+}
+
+// Implement key features of the client tracker
+impl ClientTracker {
+    /// A function to create a new, empty client tracker
+    ///
+    pub fn new() -> Self {
+        Self {
+            clients: FnvHashMap::default(),
+        }
+    }
+
+    /// A method to record a request from a client. A heartbeat names the
+    /// client with its identifier, and other requests from the same identity
+    /// and host are counted toward the most recent named client. Requests
+    /// from within the program and from scripts are not recorded.
+    ///
+    pub fn record(&mut self, client: &Client, request: &Request, now: Instant) {
+        // Ignore requests that do not come from a client
+        if client.identity == "internal" || client.identity == "script" {
+            return;
+        }
+
+        // Find the host of the client (the port changes between connections)
+        let host = client.address.as_ref().map(|address| {
+            address
+                .parse::<std::net::SocketAddr>()
+                .map(|address| address.ip().to_string())
+                .unwrap_or_else(|_| address.clone())
+        });
+
+        // Find the record of the client
+        let client_id = match request {
+            Request::Heartbeat { heartbeat } => Some(heartbeat.client_id.clone()),
+            _ if client.client_id.is_some() => client.client_id.clone(),
+            _ => self
+                .clients
+                .values()
+                .filter(|record| {
+                    record.activity.client_id.is_some()
+                        && record.activity.identity == client.identity
+                        && record.activity.address == host
+                })
+                .max_by_key(|record| record.last_seen)
+                .and_then(|record| record.activity.client_id.clone()),
+        };
+        let key = match &client_id {
+            Some(client_id) => format!("{}/{}", client.identity, client_id),
+            None => format!(
+                "{}@{}",
+                client.identity,
+                host.as_deref().unwrap_or("unknown")
+            ),
+        };
+        let record = self.clients.entry(key).or_insert_with(|| ClientRecord {
+            activity: ClientActivity {
+                client_id,
+                identity: client.identity.clone(),
+                address: host,
+                last_seen: 0,
+                last_request: String::new(),
+                requests: 0,
+                is_connected: true,
+            },
+            last_seen: now,
+        });
+
+        // Update the activity of the client
+        record.activity.last_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or(0);
+        record.activity.last_request = request.name().to_string();
+        record.activity.requests += 1;
+        record.last_seen = now;
+
+        // Forget the least recent client, if there are too many
+        if self.clients.len() > MAX_CLIENTS
+            && let Some(oldest) = self
+                .clients
+                .iter()
+                .min_by_key(|(_, record)| record.last_seen)
+                .map(|(key, _)| key.clone())
+        {
+            self.clients.remove(&oldest);
+        }
+    }
+
+    /// A method to return the clients seen recently, most recently seen
+    /// first. Clients that have not been seen for an hour are forgotten.
+    ///
+    pub fn clients(&mut self, now: Instant) -> Vec<ClientActivity> {
+        // Forget the clients that are no longer recent
+        self.clients
+            .retain(|_, record| now.saturating_duration_since(record.last_seen) <= RECENT_TIME);
+
+        // List the remaining clients
+        let mut records: Vec<&ClientRecord> = self.clients.values().collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.last_seen));
+        records
+            .into_iter()
+            .map(|record| ClientActivity {
+                is_connected: now.saturating_duration_since(record.last_seen) <= CONNECTED_TIME,
+                ..record.activity.clone()
+            })
+            .collect()
+    }
+}
+
+// Tests of the client tracker module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that heartbeats name clients and that old clients disconnect
+    #[test]
+    fn track_clients() {
+        // A kiosk sends a heartbeat, then a fade from another port
+        let mut tracker = ClientTracker::new();
+        let start = Instant::now();
+        let kiosk = Client::from_token("kiosk", Role::Operator, Some("10.0.0.5:4000".into()));
+        let heartbeat = Request::Heartbeat {
+            heartbeat: Heartbeat {
+                client_id: "lobby".into(),
+            },
+        };
+        tracker.record(&kiosk, &heartbeat, start);
+        let kiosk = Client {
+            address: Some("10.0.0.5:4001".into()),
+            ..kiosk
+        };
+        tracker.record(&kiosk, &Request::AllStop, start + Duration::from_secs(1));
+
+        // A trigger and a script also make requests
+        let trigger = Client::trigger("osc");
+        tracker.record(&trigger, &Request::Tap, start + Duration::from_secs(2));
+        tracker.record(&Client::script("show"), &Request::Tap, start);
+
+        // Check the order and attribution of the clients
+        let clients = tracker.clients(start + Duration::from_secs(20));
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].identity, "trigger");
+        assert_eq!(clients[1].client_id.as_deref(), Some("lobby"));
+        assert_eq!(clients[1].address.as_deref(), Some("10.0.0.5"));
+        assert_eq!(clients[1].last_request, "allStop");
+        assert_eq!(clients[1].requests, 2);
+        assert!(clients[1].is_connected);
+
+        // Quiet clients disconnect, and are eventually forgotten
+        let clients = tracker.clients(start + Duration::from_secs(60));
+        assert!(clients.iter().all(|client| !client.is_connected));
+        assert!(
+            tracker
+                .clients(start + Duration::from_secs(7200))
+                .is_empty()
+        );
+    }
+}
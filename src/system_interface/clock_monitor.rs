@@ -0,0 +1,214 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module watches the system clock for timed installations. The wall
+//! clock is compared with the monotonic clock every second, so a step (such
+//! as a manual change or a large NTP correction) is reported instead of
+//! silently shifting the timestamps, and the NTP sync status is checked
+//! regularly.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+// Import Tokio features
+use tokio::sync::watch;
+use tokio::time::interval;
+
+// Import tracing features
+use tracing::{info, warn};
+
+// Define the clock constants
+const CHECK_INTERVAL: Duration = Duration::from_secs(1); // the time between comparisons of the clocks
+const SYNC_INTERVAL: u32 = 60; // the number of comparisons between checks of the NTP sync status
+const STEP_THRESHOLD: i64 = 500; // the smallest jump of the wall clock that is reported as a step, in ms
+
+/// A structure to compare the wall clock with the monotonic clock
+///
+pub struct ClockMonitor {
+    last_wall: SystemTime,     // the wall clock at the previous comparison
+    last_instant: Instant,     // the monotonic clock at the previous comparison
+    status: ClockStatus,       // the current state of the clock
+    events: Option<EventSend>, // the line to announce clock events, if any
+}
+
+// Implement key features of the clock monitor
+impl ClockMonitor {
+    /// A function to create a new clock monitor, starting from the given
+    /// times
+    ///
+    pub fn new(wall: SystemTime, instant: Instant) -> Self {
+        Self {
+            last_wall: wall,
+            last_instant: instant,
+            status: ClockStatus::default(),
+            events: None,
+        }
+    }
+
+    /// A function to start monitoring the clock in the background, returning
+    /// the line with the latest state of the clock. This function must be
+    /// called within a tokio runtime.
+    ///
+    pub fn start(events: EventSend) -> watch::Receiver<ClockStatus> {
+        let mut monitor = Self::new(SystemTime::now(), Instant::now());
+        monitor.events = Some(events);
+        let (status_send, status_receive) = watch::channel(monitor.status.clone());
+        tokio::spawn(async move {
+            let mut ticks = interval(CHECK_INTERVAL);
+            let mut count = 0;
+            loop {
+                ticks.tick().await;
+
+                // Check the sync status regularly (starting right away)
+                if count % SYNC_INTERVAL == 0 {
+                    let is_synchronized = tokio::task::spawn_blocking(read_synchronized)
+                        .await
+                        .unwrap_or(None);
+                    monitor.set_synchronized(is_synchronized);
+                }
+                count = count.wrapping_add(1);
+
+                // Compare the clocks and share the result
+                monitor.check(SystemTime::now(), Instant::now());
+                status_send.send_replace(monitor.status.clone());
+            }
+        });
+        status_receive
+    }
+
+    /// A method to compare the clocks since the previous comparison,
+    /// returning the size of the step of the wall clock (in ms, behind is
+    /// negative), if it stepped
+    ///
+    pub fn check(&mut self, wall: SystemTime, instant: Instant) -> Option<i64> {
+        // Find the difference between the clocks since the last comparison
+        let wall_elapsed = match wall.duration_since(self.last_wall) {
+            Ok(elapsed) => elapsed.as_millis() as i64,
+            Err(error) => -(error.duration().as_millis() as i64),
+        };
+        let offset = wall_elapsed - instant.duration_since(self.last_instant).as_millis() as i64;
+        self.last_wall = wall;
+        self.last_instant = instant;
+
+        // Gradual corrections are drift, and large jumps are steps
+        if offset.abs() < STEP_THRESHOLD {
+            self.status.drift += offset;
+            return None;
+        }
+        warn!(
+            "The system clock stepped by {} ms. Timestamps from before the step are not comparable.",
+            offset
+        );
+        self.status.steps += 1;
+        self.status.last_step = Some(offset);
+        if let Some(events) = &self.events {
+            events.send(Event::ClockStepped { offset });
+        }
+        Some(offset)
+    }
+
+    /// A method to update the NTP sync status, warning when the clock is (or
+    /// becomes) unsynchronized
+    ///
+    pub fn set_synchronized(&mut self, is_synchronized: Option<bool>) {
+        if is_synchronized == self.status.is_synchronized {
+            return;
+        }
+        match is_synchronized {
+            Some(true) => info!("The system clock is synchronized."),
+            Some(false) => warn!(
+                "The system clock is not synchronized. Scheduled shows may run at the wrong time."
+            ),
+            None => (),
+        }
+        if let (Some(events), Some(is_synchronized)) = (&self.events, is_synchronized) {
+            events.send(Event::ClockSync { is_synchronized });
+        }
+        self.status.is_synchronized = is_synchronized;
+    }
+}
+
+/// A helper function to ask systemd whether the system clock is
+/// synchronized by NTP (with any NTP service), if the answer is known
+///
+fn read_synchronized() -> Option<bool> {
+    let output = Command::new("timedatectl")
+        .args(["show", "--property=NTPSynchronized", "--value"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+// Tests of the clock monitor module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that small differences are drift and large jumps are steps
+    #[test]
+    fn detect_steps() {
+        let wall = SystemTime::now();
+        let start = Instant::now();
+        let mut monitor = ClockMonitor::new(wall, start);
+
+        // A slightly fast wall clock drifts
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            monitor.check(wall + Duration::from_millis(1010), start + second),
+            None
+        );
+        assert_eq!(monitor.status.drift, 10);
+
+        // Jumping back an hour is a step, not drift
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            monitor.check(
+                wall + Duration::from_millis(2010) - hour,
+                start + 2 * second
+            ),
+            Some(-3_600_000)
+        );
+        assert_eq!(
+            (
+                monitor.status.steps,
+                monitor.status.last_step,
+                monitor.status.drift
+            ),
+            (1, Some(-3_600_000), 10)
+        );
+
+        // The next comparison starts from the new time
+        assert_eq!(
+            monitor.check(
+                wall + Duration::from_millis(3010) - hour,
+                start + 3 * second
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright (c) 2019 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module coalesces bursts of fades. When fades arrive faster than
+//! they are processed (such as from a fader), only the last fade to each
+//! channel from each client is played, and the fades it replaces are
+//! cancelled. Fades from different clients are never merged, so every
+//! client gets the reply to its own request.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A function to coalesce a run of requests that arrived together. The run
+/// should only hold consecutive fades, so that every other request keeps
+/// its place. Returns each request to process, in order, with the earlier
+/// fades from the same client to the same channel that it replaces.
+///
+pub fn coalesce(run: Vec<WebRequest>) -> Vec<(WebRequest, Vec<WebRequest>)> {
+    // Find the last fade to each channel from each client
+    let mut last_fades: Vec<(u32, &Client, usize)> = Vec::new();
+    for (index, request) in run.iter().enumerate() {
+        if let Request::PlayFade { fade } = &request.request {
+            match last_fades.iter_mut().find(|(channel, client, _)| {
+                (*channel == fade.channel) && (**client == request.client)
+            }) {
+                Some(last) => last.2 = index,
+                None => last_fades.push((fade.channel, &request.client, index)),
+            }
+        }
+    }
+    let last_index: Vec<Option<usize>> = run
+        .iter()
+        .map(|request| match &request.request {
+            Request::PlayFade { fade } => last_fades
+                .iter()
+                .find(|(channel, client, _)| {
+                    (*channel == fade.channel) && (**client == request.client)
+                })
+                .map(|last| last.2),
+            _ => None,
+        })
+        .collect();
+
+    // Attach each fade to the last fade to its channel from its client
+    let mut replaced: FnvHashMap<usize, Vec<WebRequest>> = FnvHashMap::default();
+    let mut kept = Vec::new();
+    for (index, request) in run.into_iter().enumerate() {
+        match last_index[index] {
+            Some(last) if last != index => replaced.entry(last).or_default().push(request),
+            _ => kept.push((index, request)),
+        }
+    }
+
+    // Return the fades that remain, with the fades they replace
+    kept.into_iter()
+        .map(|(index, request)| (request, replaced.remove(&index).unwrap_or_default()))
+        .collect()
+}
+
+// Tests of the coalescer module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+    use tracing::Span;
+
+    // A helper function to create a fade request from the program
+    fn fade_request(channel: u32, value: u8) -> WebRequest {
+        fade_from(Client::internal(), channel, value)
+    }
+
+    // A helper function to create a fade request from a client
+    fn fade_from(client: Client, channel: u32, value: u8) -> WebRequest {
+        let (reply_to, _) = oneshot::channel();
+        WebRequest {
+            reply_to,
+            request: Request::PlayFade {
+                fade: Fade {
+                    channel,
+                    value,
+                    duration: None,
+                },
+            },
+            client,
+            span: Span::none(),
+        }
+    }
+
+    // Test that only the last fade to each channel remains, in order
+    #[test]
+    fn coalesce_fades() {
+        let run = vec![
+            fade_request(1, 10),
+            fade_request(2, 20),
+            fade_request(1, 30),
+            fade_request(1, 40),
+        ];
+        let coalesced = coalesce(run);
+        let values: Vec<(Fade, usize)> = coalesced
+            .iter()
+            .map(|(request, replaced)| match &request.request {
+                Request::PlayFade { fade } => (fade.clone(), replaced.len()),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (
+                    Fade {
+                        channel: 2,
+                        value: 20,
+                        duration: None
+                    },
+                    0
+                ),
+                (
+                    Fade {
+                        channel: 1,
+                        value: 40,
+                        duration: None
+                    },
+                    2
+                ),
+            ]
+        );
+    }
+
+    // Test that fades from different clients are never merged
+    #[test]
+    fn coalesce_clients() {
+        let first = Client::from_token("first", Role::Operator, None);
+        let second = Client::from_token("second", Role::Operator, None);
+        let run = vec![
+            fade_from(first.clone(), 1, 10),
+            fade_from(second.clone(), 1, 20),
+            fade_from(first.clone(), 1, 30),
+        ];
+        let coalesced: Vec<(String, u8, usize)> = coalesce(run)
+            .iter()
+            .map(|(request, replaced)| match &request.request {
+                Request::PlayFade { fade } => {
+                    (request.client.identity.clone(), fade.value, replaced.len())
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+
+        // The second client keeps its own fade, and only the first fade
+        // of the first client is replaced (by its own later fade)
+        assert_eq!(
+            coalesced,
+            vec![
+                (second.identity.clone(), 20, 0),
+                (first.identity.clone(), 30, 1),
+            ]
+        );
+    }
+}
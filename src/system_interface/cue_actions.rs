@@ -0,0 +1,176 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module sends the actions of a cue, so that sound or video can follow
+//! the lighting. Each action is sent in the background so that a slow or
+//! missing system does not delay the cue.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use crate::webhooks::Webhooks;
+
+// Import standard library features
+use std::path::Path;
+
+// Import Tokio features
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UdpSocket;
+
+// Import tracing features
+use tracing::{debug, warn};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A function to send every action of a cue in the background
+///
+pub fn send_actions(actions: &[CueAction], list: &str, number: usize, scene: &str) {
+    for action in actions {
+        let action = action.clone();
+        let body = serde_json::json!({ "list": list, "number": number, "scene": scene });
+        tokio::spawn(async move {
+            if let Err(error) = send_action(&action, &body.to_string()).await {
+                warn!("Unable to send action of cue {}: {}", number, error);
+            }
+        });
+    }
+}
+
+/// A helper function to send one action of a cue
+///
+/// # Errors
+///
+/// This function will raise an error if the action cannot be sent.
+///
+async fn send_action(action: &CueAction, body: &str) -> Result<()> {
+    match action {
+        CueAction::Osc {
+            target,
+            address,
+            arguments,
+        } => {
+            let socket = UdpSocket::bind("0.0.0.0:0").await?;
+            socket
+                .send_to(&encode_osc(address, arguments), target.as_str())
+                .await?;
+            debug!("Sent OSC {} to {}.", address, target);
+        }
+        CueAction::Webhook { url } => {
+            Webhooks::post(&Webhooks::parse_url(url)?, body).await?;
+            debug!("Sent webhook to {}.", url);
+        }
+        CueAction::Midi { device, message } => send_midi(device, message).await?,
+    }
+    Ok(())
+}
+
+/// A helper function to write a message to a raw MIDI device
+///
+/// # Errors
+///
+/// This function will raise an error if the device cannot be written.
+///
+async fn send_midi(device: &Path, message: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new().write(true).open(device).await?;
+    file.write_all(message).await?;
+    file.flush().await?;
+    debug!("Sent MIDI {:?} to {}.", message, device.display());
+    Ok(())
+}
+
+/// A helper function to encode an OSC message with its arguments
+///
+fn encode_osc(address: &str, arguments: &[OscArgument]) -> Vec<u8> {
+    // Write the address and the type tags
+    let mut message = Vec::new();
+    push_string(&mut message, address);
+    let tags: String = std::iter::once(',')
+        .chain(arguments.iter().map(|argument| match argument {
+            OscArgument::Int(_) => 'i',
+            OscArgument::Float(_) => 'f',
+            OscArgument::String(_) => 's',
+        }))
+        .collect();
+    push_string(&mut message, &tags);
+
+    // Write each argument
+    for argument in arguments {
+        match argument {
+            OscArgument::Int(value) => message.extend_from_slice(&value.to_be_bytes()),
+            OscArgument::Float(value) => message.extend_from_slice(&value.to_be_bytes()),
+            OscArgument::String(value) => push_string(&mut message, value),
+        }
+    }
+    message
+}
+
+/// A helper function to append a null-terminated string, padded to four
+/// bytes
+///
+fn push_string(message: &mut Vec<u8>, string: &str) {
+    message.extend_from_slice(string.as_bytes());
+    message.resize((message.len() / 4 + 1) * 4, 0);
+}
+
+// Tests of the cue actions module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that OSC messages are padded and tagged
+    #[test]
+    fn encode_osc_message() {
+        assert_eq!(encode_osc("/go", &[]), b"/go\0,\0\0\0".to_vec());
+        assert_eq!(
+            encode_osc(
+                "/cue/12/start",
+                &[OscArgument::Int(1), OscArgument::String("abc".to_string())]
+            ),
+            b"/cue/12/start\0\0\0,is\0\0\0\0\x01abc\0".to_vec()
+        );
+    }
+
+    // Test that OSC and MIDI actions reach their targets
+    #[tokio::test]
+    async fn send_cue_actions() {
+        // Send an OSC message to a local socket
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let osc = CueAction::Osc {
+            target: socket.local_addr().unwrap().to_string(),
+            address: "/go".to_string(),
+            arguments: Vec::new(),
+        };
+        send_action(&osc, "{}").await.unwrap();
+        let mut buffer = [0; 64];
+        let length = socket.recv(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..length], b"/go\0,\0\0\0");
+
+        // Write a MIDI message to a file in place of the device
+        let device = std::env::temp_dir().join(format!("vulcan-midi-{}", std::process::id()));
+        std::fs::write(&device, b"").unwrap();
+        let midi = CueAction::Midi {
+            device: device.clone(),
+            message: vec![0x90, 60, 127],
+        };
+        send_action(&midi, "{}").await.unwrap();
+        assert_eq!(std::fs::read(&device).unwrap(), vec![0x90, 60, 127]);
+        std::fs::remove_file(device).unwrap();
+    }
+}
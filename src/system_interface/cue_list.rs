@@ -0,0 +1,360 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the cue list, which steps through its cues one
+//! GO at a time. Each cue may wait before it starts and may continue to the
+//! next cue by itself, so that a multi-part sequence runs after one GO.
+//! Several cue lists play independently, each with its own position.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold every cue list by name
+///
+pub struct CueLists {
+    lists: FnvHashMap<String, CueList>, // the cue lists, by name
+}
+
+// Implement key features of the cue lists
+impl CueLists {
+    /// A function to create the cue lists, each starting at the first cue
+    ///
+    pub fn new(lists: FnvHashMap<String, Vec<Cue>>) -> Self {
+        Self {
+            lists: lists
+                .into_iter()
+                .map(|(name, cues)| (name, CueList::new(cues)))
+                .collect(),
+        }
+    }
+
+    /// A method to replace the cue lists. Lists with the same cues keep
+    /// their position, and the rest return to the first cue.
+    ///
+    pub fn set_lists(&mut self, lists: FnvHashMap<String, Vec<Cue>>) {
+        let mut previous = std::mem::take(&mut self.lists);
+        for (name, cues) in lists {
+            let list = match previous.remove(&name) {
+                Some(list) if list.cues == cues => list,
+                _ => CueList::new(cues),
+            };
+            self.lists.insert(name, list);
+        }
+    }
+
+    /// A method to return a cue list by name
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the cue list does not exist.
+    ///
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut CueList, ErrorCode> {
+        self.lists
+            .get_mut(name)
+            .ok_or_else(|| ErrorCode::CueListNotFound {
+                list: name.to_string(),
+            })
+    }
+
+    /// A method to cancel the waiting cue of every list
+    ///
+    pub fn cancel(&mut self) {
+        self.lists.values_mut().for_each(CueList::cancel);
+    }
+
+    /// A method to return the time the waiting cue of each list starts, if
+    /// any, with the name of the list
+    ///
+    pub fn deadlines(&self) -> impl Iterator<Item = (&String, Option<Instant>)> {
+        self.lists
+            .iter()
+            .map(|(name, list)| (name, list.deadline()))
+    }
+
+    /// A method to take the waiting cue of the list if it is due, returning
+    /// its index
+    ///
+    pub fn due(&mut self, name: &str, now: Instant) -> Option<usize> {
+        self.lists.get_mut(name)?.due(now)
+    }
+}
+
+/// A structure to track the position in the cue list and the cue waiting
+/// to start, if any
+///
+pub struct CueList {
+    cues: Vec<Cue>,           // the cues of the list, in order
+    next: usize,              // the index of the cue that the next GO starts
+    pending: Option<Pending>, // the cue waiting to start, if any
+    current: Option<Running>, // the most recently started cue, if any
+}
+
+/// A helper structure to hold a cue that is waiting to start
+///
+struct Pending {
+    index: usize,   // the index of the cue
+    start: Instant, // the time the cue starts
+}
+
+/// A helper structure to hold the most recently started cue
+///
+struct Running {
+    index: usize,   // the index of the cue
+    start: Instant, // the time the cue started
+    fade: Duration, // the duration of the fade of the cue
+}
+
+// Implement key features of the cue list
+impl CueList {
+    /// A function to create a new cue list, starting at the first cue
+    ///
+    pub fn new(cues: Vec<Cue>) -> Self {
+        Self {
+            cues,
+            next: 0,
+            pending: None,
+            current: None,
+        }
+    }
+
+    /// A method to return to the first cue, cancelling any cue that is
+    /// waiting to start
+    ///
+    pub fn reset(&mut self) {
+        self.next = 0;
+        self.pending = None;
+        self.current = None;
+    }
+
+    /// A method to cancel any cue that is waiting to start, without changing
+    /// the position in the list
+    ///
+    pub fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.next = pending.index;
+        }
+    }
+
+    /// A method to return a cue by index, if it exists
+    ///
+    pub fn cue(&self, index: usize) -> Option<&Cue> {
+        self.cues.get(index)
+    }
+
+    /// A method to advance the cue list for a GO. A GO while a cue is
+    /// waiting starts that cue at once. Otherwise, the next cue starts (or
+    /// begins its wait).
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if there are no cues left.
+    ///
+    pub fn go(&mut self, now: Instant) -> Result<Option<usize>, ErrorCode> {
+        // Start a waiting cue without the rest of its wait
+        if let Some(pending) = self.pending.take() {
+            return Ok(Some(pending.index));
+        }
+
+        // Otherwise, start the next cue or its wait
+        let index = self.next;
+        let cue = self.cues.get(index).ok_or(ErrorCode::EndOfCueList)?;
+        self.next = index + 1;
+        match cue.wait.filter(|wait| !wait.is_zero()) {
+            Some(wait) => {
+                self.pending = Some(Pending {
+                    index,
+                    start: now + wait,
+                });
+                Ok(None)
+            }
+            None => Ok(Some(index)),
+        }
+    }
+
+    /// A method to note that a cue has started with a fade of the given
+    /// duration, which schedules the next cue if this cue has a follow time
+    ///
+    pub fn started(&mut self, index: usize, fade: Option<Duration>, now: Instant) {
+        self.current = Some(Running {
+            index,
+            start: now,
+            fade: fade.unwrap_or_default(),
+        });
+        self.next = index + 1;
+        let follow = self.cues.get(index).and_then(|cue| cue.follow);
+        if let (Some(follow), Some(next)) = (follow, self.cues.get(self.next)) {
+            self.pending = Some(Pending {
+                index: self.next,
+                start: now + follow + next.wait.unwrap_or_default(),
+            });
+            self.next += 1;
+        }
+    }
+
+    /// A method to describe the playback position of the cue list
+    ///
+    pub fn status(&self, now: Instant) -> CueListStatus {
+        // Describe a cue by index, if it exists
+        let describe = |index: usize| {
+            self.cues.get(index).map(|cue| CueStatus {
+                number: index + 1,
+                scene: cue.scene.clone(),
+            })
+        };
+
+        // Find the progress of the fade of the current cue
+        let (progress, fade_remaining) = match &self.current {
+            Some(running) if !running.fade.is_zero() => {
+                let elapsed = now.saturating_duration_since(running.start);
+                (
+                    (elapsed.as_secs_f64() / running.fade.as_secs_f64()).min(1.0),
+                    running.fade.saturating_sub(elapsed),
+                )
+            }
+            _ => (1.0, Duration::ZERO),
+        };
+
+        // Combine the position with the cue that starts next
+        let next = self
+            .pending
+            .as_ref()
+            .map_or(self.next, |pending| pending.index);
+        CueListStatus {
+            length: self.cues.len(),
+            current: self
+                .current
+                .as_ref()
+                .and_then(|running| describe(running.index)),
+            next: describe(next),
+            progress,
+            fade_remaining,
+            starts_in: self
+                .deadline()
+                .map(|start| start.saturating_duration_since(now)),
+        }
+    }
+
+    /// A method to return the time the waiting cue starts, if any
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|pending| pending.start)
+    }
+
+    /// A helper method to take the waiting cue if it is due
+    ///
+    fn due(&mut self, now: Instant) -> Option<usize> {
+        if self.deadline()? > now {
+            return None;
+        }
+        self.pending.take().map(|pending| pending.index)
+    }
+}
+
+// Tests of the cue list module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that waits and follows run a sequence after one GO
+    #[test]
+    fn follow_cues() {
+        let cue = |scene: &str, wait: u64, follow: Option<u64>| Cue {
+            scene: scene.to_string(),
+            duration: None,
+            wait: Some(Duration::from_secs(wait)),
+            follow: follow.map(Duration::from_secs),
+            actions: Vec::new(),
+        };
+        let mut cues = CueList::new(vec![
+            cue("one", 0, Some(5)),
+            cue("two", 2, None),
+            cue("three", 3, None),
+        ]);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // The first cue starts at once and follows to the second after its
+        // follow time and wait
+        assert_eq!(cues.go(start), Ok(Some(0)));
+        cues.started(0, Some(Duration::from_secs(2)), start);
+        assert_eq!(cues.deadline(), Some(at(7)));
+        assert_eq!(cues.due(at(6)), None);
+        let status = cues.status(at(1));
+        assert_eq!(status.current.map(|cue| cue.number), Some(1));
+        assert_eq!(status.next.map(|cue| cue.number), Some(2));
+        assert_eq!(status.progress, 0.5);
+        assert_eq!(status.fade_remaining, Duration::from_secs(1));
+        assert_eq!(status.starts_in, Some(Duration::from_secs(6)));
+        assert_eq!(cues.due(at(7)), Some(1));
+        cues.started(1, None, at(7));
+        assert_eq!(cues.deadline(), None);
+
+        // The third cue waits after its GO, unless GO is pressed again
+        assert_eq!(cues.go(at(10)), Ok(None));
+        assert_eq!(cues.deadline(), Some(at(13)));
+        assert_eq!(cues.go(at(11)), Ok(Some(2)));
+        cues.started(2, None, at(11));
+        assert_eq!(cues.status(at(11)).next, None);
+
+        // The end of the list is an error, until the list is reset
+        assert_eq!(cues.go(at(12)), Err(ErrorCode::EndOfCueList));
+        cues.reset();
+        assert_eq!(cues.go(at(12)), Ok(Some(0)));
+    }
+
+    // Test that each cue list keeps its own position, including across a
+    // reload that leaves it unchanged
+    #[test]
+    fn independent_lists() {
+        let cue = |scene: &str| Cue {
+            scene: scene.to_string(),
+            duration: None,
+            wait: None,
+            follow: None,
+            actions: Vec::new(),
+        };
+        let mut lists = FnvHashMap::default();
+        lists.insert("main".to_string(), vec![cue("one"), cue("two")]);
+        lists.insert("lobby".to_string(), vec![cue("loop")]);
+        let mut cue_lists = CueLists::new(lists.clone());
+        let now = Instant::now();
+
+        // A GO on one list leaves the other in place
+        assert_eq!(cue_lists.get_mut("main").unwrap().go(now), Ok(Some(0)));
+        assert_eq!(cue_lists.get_mut("lobby").unwrap().go(now), Ok(Some(0)));
+        assert_eq!(cue_lists.get_mut("main").unwrap().go(now), Ok(Some(1)));
+
+        // A changed list returns to its first cue, and an unchanged one does not
+        lists.insert("lobby".to_string(), vec![cue("loop"), cue("close")]);
+        cue_lists.set_lists(lists);
+        assert_eq!(
+            cue_lists.get_mut("main").unwrap().go(now),
+            Err(ErrorCode::EndOfCueList)
+        );
+        assert_eq!(cue_lists.get_mut("lobby").unwrap().go(now), Ok(Some(0)));
+        assert!(matches!(
+            cue_lists.get_mut("stage"),
+            Err(ErrorCode::CueListNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to simulate requests without changing the output. Each fade is
+//! treated as complete, so the result is the universe once every fade of the
+//! requests has finished.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A structure to hold the simulated state while requests are applied
+///
+pub struct DryRun<'a> {
+    config: &'a Config,  // the configuration with the scenes and fixtures
+    universe: Universe,  // the simulated value of all the channels
+    emergency: Universe, // the simulated emergency values of all the channels
+}
+
+// Implement key features of the dry run
+impl<'a> DryRun<'a> {
+    /// A function to start a new dry run from the current state
+    ///
+    pub fn new(config: &'a Config, universe: Universe, emergency: Universe) -> Self {
+        Self {
+            config,
+            universe,
+            emergency,
+        }
+    }
+
+    /// A method to apply a request to the simulated state
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the request would fail, or if the
+    /// request cannot be simulated.
+    ///
+    pub fn apply(&mut self, request: Request) -> Result<()> {
+        match request {
+            // Complete the fade
+            Request::PlayFade { fade } => self.set(fade.channel, fade.value)?,
+
+            // Replace every channel
+            Request::LoadUniverse { universe, .. } => self.universe = universe,
+
+            // Complete the fade of every channel in the scene
+            Request::RecallScene { recall } => {
+                let scene = self.config.scene(&recall.name)?;
+                for (channel, value) in scene.values.iter() {
+                    self.set(*channel, *value)?;
+                }
+            }
+
+            // Complete the movement of the fixture
+            Request::SetPosition { position } => {
+                let (fixture, profile) = self.config.fixture(&position.fixture)?;
+                for fade in position.to_fades(fixture, profile)? {
+                    self.universe.set(fade.channel, (fade.value >> 8) as u8);
+                    if let Some(fine_channel) = fade.fine_channel {
+                        self.universe.set(fine_channel, fade.value as u8);
+                    }
+                }
+            }
+
+            // Complete the color of the fixture
+            Request::SetColor { color } => {
+                let (fixture, profile) = self.config.fixture(&color.fixture)?;
+                for fade in color.to_fades(fixture, profile)? {
+                    self.universe.set(fade.channel, (fade.value >> 8) as u8);
+                }
+            }
+
+            // Change or recall the emergency values
+            Request::DefineAllStop { universe } => self.emergency = universe,
+            Request::AllStop => self.universe = self.emergency.clone(),
+
+            // Apply each request of a batch in order
+            Request::Batch { requests } | Request::DryRun { requests } => {
+                for request in requests {
+                    self.apply(request)?;
+                }
+            }
+
+            // Any other request cannot be simulated
+            request => {
+                return Err(ErrorCode::DryRunUnsupported {
+                    request: request.name().to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// A method to return the simulated universe
+    ///
+    pub fn into_universe(self) -> Universe {
+        self.universe
+    }
+
+    /// A helper method to set a channel, checking its range
+    ///
+    fn set(&mut self, channel: u32, value: u8) -> Result<()> {
+        if !(1..=DMX_MAX).contains(&channel) {
+            return Err(ErrorCode::ChannelOutOfRange { channel }.into());
+        }
+        self.universe.set(channel, value);
+        Ok(())
+    }
+}
+
+// Tests of the dry run module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that requests are simulated in order until one fails
+    #[test]
+    fn simulate_requests() {
+        // Create a scene and a dry run
+        let mut config = Config::default();
+        let mut scene = Scene::default();
+        scene.values.insert(3, 30);
+        config.scenes.insert("warm".to_string(), scene);
+        let mut dry_run = DryRun::new(&config, Universe::new(), Universe::new());
+
+        // Simulate a fade and a scene
+        dry_run
+            .apply(Request::Batch {
+                requests: vec![
+                    Request::PlayFade {
+                        fade: Fade {
+                            channel: 1,
+                            value: 10,
+                            duration: None,
+                        },
+                    },
+                    Request::RecallScene {
+                        recall: SceneRecall {
+                            name: "warm".to_string(),
+                            duration: None,
+                        },
+                    },
+                ],
+            })
+            .unwrap();
+
+        // A missing scene, a bad channel, or an undo is rejected
+        let missing = SceneRecall {
+            name: "cool".to_string(),
+            duration: None,
+        };
+        assert!(dry_run.apply(missing.into()).is_err());
+        let fade = Fade {
+            channel: 513,
+            value: 1,
+            duration: None,
+        };
+        assert!(dry_run.apply(fade.into()).is_err());
+        assert!(dry_run.apply(Request::Undo).is_err());
+
+        // Check the resulting universe
+        let universe = dry_run.into_universe();
+        assert_eq!(
+            (universe.get(1), universe.get(2), universe.get(3)),
+            (10, 0, 30)
+        );
+    }
+}
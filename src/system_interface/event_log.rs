@@ -0,0 +1,210 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the event log, which records every event that
+//! Vulcan announces (such as scenes recalled, fades finished, and problems
+//! with the hardware or the backup) for a show log. Recent entries are kept
+//! in memory for queries and, if a file is specified, every entry is
+//! appended to the file as one line of JSON.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Import tracing features
+use tracing::error;
+
+// Define the event log constants
+const MAX_ENTRIES: usize = 1_000; // the number of entries kept in memory
+const DEFAULT_LIMIT: usize = 100; // the default number of entries returned by a query
+
+/// A structure to record events and search the record
+///
+pub struct EventLog {
+    file: Option<File>,            // the file for the event log, if any
+    entries: VecDeque<EventEntry>, // the most recent entries, oldest first
+    next_sequence: u64,            // the sequence number of the next entry
+}
+
+// Implement key features of the event log
+impl EventLog {
+    /// A function to create a new event log. If there is an existing file,
+    /// the most recent entries are loaded from it, and new entries continue
+    /// from the last sequence number.
+    ///
+    /// # Errors
+    ///
+    /// This function will log an error if the file cannot be opened. The
+    /// event log is then kept in memory only.
+    ///
+    pub fn new(path: Option<&Path>) -> Self {
+        // Load any existing entries
+        let mut entries = VecDeque::new();
+        if let Some(contents) = path.and_then(|path| fs::read_to_string(path).ok()) {
+            for line in contents.lines() {
+                if let Ok(entry) = serde_json::from_str(line) {
+                    entries.push_back(entry);
+                }
+                if entries.len() > MAX_ENTRIES {
+                    entries.pop_front();
+                }
+            }
+        }
+        let next_sequence = entries
+            .back()
+            .map_or(1, |entry: &EventEntry| entry.sequence + 1);
+
+        // Try to open the file for appending
+        let file = path.and_then(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|error| error!("Unable to open event log {}: {}.", path.display(), error))
+                .ok()
+        });
+
+        // Return the new event log
+        Self {
+            file,
+            entries,
+            next_sequence,
+        }
+    }
+
+    /// A method to record an event
+    ///
+    pub fn record(&mut self, event: Event) {
+        // Compose the entry
+        let entry = EventEntry {
+            sequence: self.next_sequence,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or(0),
+            event,
+        };
+        self.next_sequence += 1;
+
+        // Append the entry to the file
+        if let Some(file) = self.file.as_mut() {
+            match serde_json::to_string(&entry) {
+                Ok(line) => {
+                    if let Err(error) = writeln!(file, "{}", line) {
+                        error!("Unable to write to event log: {}.", error);
+                    }
+                }
+                Err(error) => error!("Unable to serialize event entry: {}.", error),
+            }
+        }
+
+        // Save the entry in memory, dropping the oldest entry if needed
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// A method to return the newest entries that match the query, oldest
+    /// first
+    ///
+    pub fn query(&self, query: &EventQuery) -> Vec<EventEntry> {
+        // Collect the newest matching entries
+        let mut entries: Vec<EventEntry> = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| query.since.is_none_or(|since| entry.sequence > since))
+            .filter(|entry| {
+                query
+                    .event
+                    .as_ref()
+                    .is_none_or(|event| entry.event.name() == event)
+            })
+            .take(query.limit.unwrap_or(DEFAULT_LIMIT))
+            .cloned()
+            .collect();
+
+        // Return them in order
+        entries.reverse();
+        entries
+    }
+}
+
+// Tests of the event log module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that queries return the entries after a sequence number, and
+    // that the numbers continue from an existing file
+    #[test]
+    fn query_events() {
+        // Record several events to a new file
+        let path = std::env::temp_dir().join(format!("vulcan-events-{}.log", std::process::id()));
+        fs::remove_file(&path).unwrap_or(());
+        let mut event_log = EventLog::new(Some(&path));
+        event_log.record(Event::AllStop);
+        event_log.record(Event::SceneRecalled {
+            name: "warm".into(),
+        });
+        event_log.record(Event::DeviceUnavailable {
+            reason: "Serial port was not ready.".into(),
+        });
+
+        // Check the entries after a sequence number, and the filter
+        let query = EventQuery {
+            since: Some(1),
+            ..Default::default()
+        };
+        let entries = event_log.query(&query);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<u64>>(),
+            vec![2, 3]
+        );
+        let query = EventQuery {
+            event: Some("allStop".into()),
+            ..Default::default()
+        };
+        assert_eq!(event_log.query(&query)[0].event, Event::AllStop);
+
+        // Reload the file and check the numbers continue
+        let mut event_log = EventLog::new(Some(&path));
+        event_log.record(Event::DeviceAvailable);
+        let query = EventQuery {
+            since: Some(2),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let entries = event_log.query(&query);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].sequence, &entries[0].event),
+            (4, &Event::DeviceAvailable)
+        );
+        fs::remove_file(&path).unwrap_or(());
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the queue of fan fades that are waiting for
+//! their staggered start times, so that a fan can ripple across a group.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+/// A structure to hold each fade of a fan until its start time
+///
+#[derive(Default)]
+pub struct FanQueue {
+    pending: Vec<(Instant, Fade)>, // the start time and fade of each waiting channel
+}
+
+// Implement key features of the fan queue
+impl FanQueue {
+    /// A function to create a new, empty fan queue
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to add the waiting fades of a fan, replacing any fade that
+    /// is still waiting on the same channels
+    ///
+    pub fn add(&mut self, now: Instant, fades: Vec<(Duration, Fade)>) {
+        self.pending.retain(|(_, pending)| {
+            !fades
+                .iter()
+                .any(|(_, fade)| fade.channel == pending.channel)
+        });
+        self.pending
+            .extend(fades.into_iter().map(|(delay, fade)| (now + delay, fade)));
+    }
+
+    /// A method to empty the queue, so that no waiting fade starts
+    ///
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    /// A method to return the time the next fade is due, if any
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.iter().map(|(start, _)| *start).min()
+    }
+
+    /// A method to remove and return every fade that is due, in order of
+    /// their start times
+    ///
+    pub fn due(&mut self, now: Instant) -> Vec<Fade> {
+        let (mut due, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(start, _)| *start <= now);
+        self.pending = pending;
+        due.sort_by_key(|(start, _)| *start);
+        due.into_iter().map(|(_, fade)| fade).collect()
+    }
+}
+
+// Tests of the fan queue module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that fades start in order and a new fan replaces waiting fades
+    #[test]
+    fn start_in_order() {
+        let fade = |channel, value| Fade {
+            channel,
+            value,
+            duration: None,
+        };
+        let start = Instant::now();
+        let mut queue = FanQueue::new();
+        queue.add(
+            start,
+            vec![
+                (Duration::from_secs(2), fade(2, 20)),
+                (Duration::from_secs(1), fade(1, 10)),
+            ],
+        );
+        assert_eq!(queue.deadline(), Some(start + Duration::from_secs(1)));
+
+        // A new fan on a channel replaces its waiting fade
+        queue.add(start, vec![(Duration::from_secs(3), fade(2, 30))]);
+        assert_eq!(queue.due(start + Duration::from_secs(2)), vec![fade(1, 10)]);
+        assert_eq!(queue.due(start + Duration::from_secs(5)), vec![fade(2, 30)]);
+        assert_eq!(queue.deadline(), None);
+
+        // Cancelling the queue drops every waiting fade
+        queue.add(start, vec![(Duration::from_secs(1), fade(3, 10))]);
+        queue.cancel();
+        assert!(queue.due(start + Duration::from_secs(5)).is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a bounded history of the universe-level changes to
+//! the output, so that a look can be undone (and redone).
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::VecDeque;
+
+/// A structure to hold the previous and undone looks of the output
+///
+pub struct History {
+    undo: VecDeque<Universe>, // the previous looks, oldest first
+    redo: Vec<Universe>,      // the looks that were undone, oldest undo last
+    depth: usize,             // the maximum number of previous looks to keep
+}
+
+// Implement key features of the history
+impl History {
+    /// A function to create a new, empty history with the provided depth
+    ///
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            depth,
+        }
+    }
+
+    /// A method to record the current look before a new look replaces it.
+    /// This clears any looks that were undone.
+    ///
+    pub fn record(&mut self, current: Universe) {
+        self.undo.push_back(current);
+        self.redo.clear();
+        self.trim();
+    }
+
+    /// A method to return the previous look (if any), saving the current look
+    /// so that it can be redone
+    ///
+    pub fn undo(&mut self, current: Universe) -> Option<Universe> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// A method to return the most recently undone look (if any), saving the
+    /// current look so that it can be undone again
+    ///
+    pub fn redo(&mut self, current: Universe) -> Option<Universe> {
+        let next = self.redo.pop()?;
+        self.undo.push_back(current);
+        self.trim();
+        Some(next)
+    }
+
+    /// A method to change the maximum number of previous looks
+    ///
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        self.trim();
+        self.redo.truncate(depth);
+    }
+
+    /// A helper method to drop the oldest looks beyond the depth
+    ///
+    fn trim(&mut self) {
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+// Tests of the history module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test undoing and redoing several looks
+    #[test]
+    fn undo_redo() {
+        // Create three looks
+        let looks: Vec<Universe> = (1..=3)
+            .map(|value| {
+                let mut universe = Universe::new();
+                universe.set(1, value);
+                universe
+            })
+            .collect();
+
+        // Record the first two looks in a history with a depth of one
+        let mut history = History::new(1);
+        history.record(looks[0].clone());
+        history.record(looks[1].clone());
+
+        // Only the newest look can be undone
+        assert_eq!(history.undo(looks[2].clone()), Some(looks[1].clone()));
+        assert_eq!(history.undo(looks[1].clone()), None);
+
+        // The undone look can be redone, but only once
+        assert_eq!(history.redo(looks[1].clone()), Some(looks[2].clone()));
+        assert_eq!(history.redo(looks[2].clone()), None);
+
+        // A new look clears the looks that were undone
+        assert_eq!(history.undo(looks[2].clone()), Some(looks[1].clone()));
+        history.record(looks[1].clone());
+        assert_eq!(history.redo(looks[0].clone()), None);
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a watchdog for control traffic, so that an
+//! abandoned installation returns to a safe look once its clients have
+//! been quiet for too long.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+/// A structure to track the control traffic and engage the safe look once
+///
+pub struct IdleWatchdog {
+    config: Option<IdleConfig>, // the settings of the watchdog, if enabled
+    last_activity: Instant,     // the time of the most recent control traffic
+    is_idle: bool,              // a flag to indicate the safe look is engaged
+}
+
+// Implement key features of the idle watchdog
+impl IdleWatchdog {
+    /// A function to create a new watchdog, starting from the given time
+    ///
+    pub fn new(config: Option<IdleConfig>, now: Instant) -> Self {
+        Self {
+            config,
+            last_activity: now,
+            is_idle: false,
+        }
+    }
+
+    /// A method to replace the settings of the watchdog. The time without
+    /// traffic starts again.
+    ///
+    pub fn set_config(&mut self, config: Option<IdleConfig>, now: Instant) {
+        self.config = config;
+        self.last_activity = now;
+        self.is_idle = false;
+    }
+
+    /// A method to note control traffic, which releases the watchdog
+    ///
+    pub fn activity(&mut self, now: Instant) {
+        self.last_activity = now;
+        self.is_idle = false;
+    }
+
+    /// A method to return the time the safe look is due, if the watchdog is
+    /// enabled and has not already engaged
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        match &self.config {
+            Some(config) if !self.is_idle => {
+                Some(self.last_activity + Duration::from_secs(config.timeout))
+            }
+            _ => None,
+        }
+    }
+
+    /// A method to engage the watchdog if the safe look is due, returning
+    /// the settings of the safe look
+    ///
+    pub fn engage(&mut self, now: Instant) -> Option<IdleConfig> {
+        if self.deadline()? > now {
+            return None;
+        }
+        self.is_idle = true;
+        self.config.clone()
+    }
+}
+
+// Tests of the idle watchdog module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the watchdog engages once and is released by traffic
+    #[test]
+    fn engage_once() {
+        // Create a watchdog with a ten second timeout
+        let config: IdleConfig = serde_yaml::from_str("timeout: 10\nscene: safe\n").unwrap();
+        let start = Instant::now();
+        let mut watchdog = IdleWatchdog::new(Some(config.clone()), start);
+        assert_eq!(watchdog.deadline(), Some(start + Duration::from_secs(10)));
+
+        // Traffic moves the deadline, and the watchdog engages after it
+        watchdog.activity(start + Duration::from_secs(5));
+        assert_eq!(watchdog.engage(start + Duration::from_secs(12)), None);
+        assert_eq!(
+            watchdog.engage(start + Duration::from_secs(15)),
+            Some(config)
+        );
+
+        // Once engaged, the watchdog waits for new traffic
+        assert_eq!(watchdog.deadline(), None);
+        assert_eq!(watchdog.engage(start + Duration::from_secs(60)), None);
+        watchdog.activity(start + Duration::from_secs(60));
+        assert_eq!(watchdog.deadline(), Some(start + Duration::from_secs(70)));
+
+        // A disabled watchdog never engages
+        watchdog.set_config(None, start);
+        assert_eq!(watchdog.engage(start + Duration::from_secs(3600)), None);
+    }
+}
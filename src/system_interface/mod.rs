@@ -20,21 +20,68 @@
 //! to the application window.
 
 // Define submodules
+mod audit_log;
 mod backup_handler;
-mod dmx_interface;
+mod channel_history;
+mod channel_writers;
+mod client_tracker;
+mod clock_monitor;
+mod coalescer;
+mod cue_actions;
+mod cue_list;
+mod dry_run;
+mod event_log;
+mod fan_queue;
+mod history;
+mod idle_watchdog;
+mod scheduler;
+mod script_engine;
+mod systemd;
+mod timeline_player;
+
+// Reexport the systemd notifications
+pub use systemd::Systemd;
 
 // Import crate definitions
 use crate::definitions::*;
+use crate::logging::LogLevel;
+
+// Import the DMX controller from the core library
+use vulcan_core::{DmxController, DryRunDriver, EnttecUsbPro, ThreadedDriver};
 
 // Import submodute definitions
+use audit_log::AuditLog;
 use backup_handler::BackupHandler;
-use dmx_interface::DmxInterface;
+use channel_history::ChannelHistory;
+use channel_writers::ChannelWriters;
+use client_tracker::ClientTracker;
+use clock_monitor::ClockMonitor;
+use cue_list::CueLists;
+use dry_run::DryRun;
+use event_log::EventLog;
+use fan_queue::FanQueue;
+use history::History;
+use idle_watchdog::IdleWatchdog;
+use scheduler::{Scheduler, Timer};
+use script_engine::ScriptEngine;
+use timeline_player::TimelinePlayer;
 
 // Import standard library features
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::{FnvHashMap, FnvHashSet};
+
+// Define the time to wait for the status of the DMX hardware
+const STATUS_TIMEOUT: Duration = Duration::from_secs(1);
 
 // Import Tokio features
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::sleep;
+
+// Import tracing features
+use tracing::{Instrument, debug, error, info, warn};
 
 // Import anyhow features
 use anyhow::Result;
@@ -44,111 +91,2540 @@ use anyhow::Result;
 ///
 pub struct SystemInterface {
     web_receive: mpsc::Receiver<WebRequest>, // the receiving line for web requests
-    dmx_interface: DmxInterface,             // the structure for controlling dmx playback
+    backlog: VecDeque<(WebRequest, Vec<WebRequest>)>, // the waiting requests, with replaced fades
+    dmx_interface: DmxController,            // the structure for controlling dmx playback
     backup_handler: BackupHandler,           // the structure for maintaining the backup
+    audit_log: AuditLog,                     // the record of every request that changes the state
+    event_log: EventLog,                     // the record of every event, for the show log
+    event_receive: broadcast::Receiver<Event>, // the line to receive events for the event log
+    clients: ClientTracker,                  // the recent activity of each client
+    channel_writers: ChannelWriters,         // the request and client that last wrote each channel
+    emergency: Universe,                     // the emergency values of all the channels
+    group_levels: FnvHashMap<String, u8>,    // the current level of each group, in percent
+    group_blackouts: FnvHashSet<String>,     // the groups that are blacked out
+    fixture_levels: FnvHashMap<String, u8>,  // the virtual intensity of each fixture, in percent
+    fade_multiplier: f64,                    // the multiplier of the duration of every fade
+    effect_speed: EffectSpeed,               // the speed of the effects not locked to the beat
+    is_preheat_enabled: bool,                // a flag to keep the warmed channels at their preheat
+    look_version: u64,                       // the number of changes to the look
+    is_emergency: bool,                      // a flag to indicate the emergency stop is engaged
+    history: History,                        // the previous looks that can be undone
+    scripts: ScriptEngine,                   // the scripts and any that are running
+    cue_lists: CueLists,                     // the position and waiting cues of each cue list
+    timelines: TimelinePlayer,               // the timelines and the transport of each one
+    config: Config,                          // the current configuration of the program
+    reload: ConfigLoader,                    // the function to reload the configuration
+    log_level: LogLevel,                     // the handle to change the log level
+    events: EventSend,                       // the line to announce events
+    watchdog: Option<Duration>,              // the period of the systemd watchdog pings
+    next_ping: Option<Instant>,              // the time of the next systemd watchdog ping
+    scheduler: Scheduler,                    // the deadline of every timer of the interface
+    idle_watchdog: IdleWatchdog,             // the watchdog for control traffic
+    fans: FanQueue,                          // the fan fades waiting for their start times
+    clock: watch::Receiver<ClockStatus>,     // the latest state of the system clock
+    channel_history: ChannelHistory,         // the recent changes of each channel of the output
+    start_time: Instant,                     // the time the program started
 }
 
 // Implement key SystemInterface functionality
 impl SystemInterface {
     /// A function to create a new, blank instance of the system interface.
+    /// The reload function is used to reload the configuration at runtime,
+    /// and the log level handle is used to change the log level at runtime.
+    /// Events are announced on the provided event line.
     ///
     pub async fn new(
-        path: PathBuf,
-        address: String,
-        server_location: Option<String>,
+        config: Config,
+        reload: ConfigLoader,
+        log_level: LogLevel,
+        events: EventSend,
     ) -> Result<(Self, WebSend)> {
+        // Try to initialize the dmx interface (without the hardware, if
+        // this is a dry run)
+        let resolution = Duration::from_millis(config.resolution);
+        let dmx_interface = match (config.dry_run, &config.dmx_thread) {
+            (true, None) => {
+                warn!("Dry run. The DMX hardware will not be written.");
+                DmxController::new(DryRunDriver, resolution, events.clone())
+            }
+            (true, Some(thread)) => {
+                warn!("Dry run. The DMX hardware will not be written.");
+                let driver = ThreadedDriver::spawn(|| Ok(DryRunDriver), thread.priority)?;
+                DmxController::new(driver, resolution, events.clone())
+            }
+            (false, thread) => {
+                let path = config
+                    .path
+                    .clone()
+                    .ok_or_else(|| anyhow!("No path to the DMX hardware was specified."))?;
+                match thread {
+                    // Open the hardware on a dedicated thread, if specified
+                    Some(thread) => {
+                        let driver = ThreadedDriver::spawn(
+                            move || EnttecUsbPro::open(&path),
+                            thread.priority,
+                        )?;
+                        DmxController::new(driver, resolution, events.clone())
+                    }
+                    None => DmxController::open(&path, resolution, events.clone())?,
+                }
+            }
+        };
+
+        // Try to initialize the backup handler (a dry run never touches the
+        // backup of a live show)
+        let backup = if config.dry_run {
+            Vec::new()
+        } else {
+            config.all_backups()
+        };
+        let backup_handler = BackupHandler::new(
+            config.instance_id(),
+            backup,
+            config.backup_max_age,
+            events.clone(),
+        )
+        .await;
+
+        // Start the system interface, reloading any backup
+        Ok(SystemInterface::start(
+            config,
+            reload,
+            log_level,
+            events,
+            dmx_interface,
+            backup_handler,
+        )
+        .await)
+    }
+
+    /// A helper function to create the system interface from an open dmx
+    /// interface and backup handler. If the backup holds a universe, it is
+    /// loaded onto the output, and otherwise the startup scene is recalled.
+    ///
+    async fn start(
+        config: Config,
+        reload: ConfigLoader,
+        log_level: LogLevel,
+        events: EventSend,
+        dmx_interface: DmxController,
+        mut backup_handler: BackupHandler,
+    ) -> (Self, WebSend) {
         // Create the web send for the web interface
         let (web_send, web_receive) = WebSend::new();
 
-        // Try to initialize the dmx interface
-        let dmx_interface = DmxInterface::new(&path)?;
+        // Open the audit log
+        let audit_log = AuditLog::new(config.audit_file.as_deref());
+
+        // Open the event log and listen for every event
+        let event_log = EventLog::new(config.event_file.as_deref());
+        let event_receive = events.subscribe();
 
-        // Try to initialize the backup handler
-        let mut backup_handler = BackupHandler::new(address, server_location).await;
+        // Start the script engine, sending requests back to this interface
+        let scripts = ScriptEngine::new(config.scripts.clone(), web_send.clone());
 
         // Check for existing data from the backup handler
-        if let Some(universe) = backup_handler.reload_backup() {
-            // Load the universe onto the dmx hardware
-            dmx_interface.set_universe(universe).await;
-        }
+        let backup = backup_handler.reload_backup();
+
+        // Start every cue list at the first cue
+        let cue_lists = CueLists::new(config.all_cue_lists());
+
+        // Stop every timeline, sending their actions back to this interface
+        let timelines = TimelinePlayer::new(config.timelines.clone(), web_send.clone());
+
+        // Start the idle watchdog, counting from now
+        let idle_watchdog = IdleWatchdog::new(config.idle.clone(), Instant::now());
+
+        // Watch the system clock for steps and the NTP sync status
+        let clock = ClockMonitor::start(events.clone());
+
+        // Ping the systemd watchdog right away and then periodically, if
+        // enabled
+        let watchdog = Systemd::watchdog_interval();
+
+        // Record the output over time, if enabled
+        let channel_history =
+            ChannelHistory::new(config.channel_history.clone(), dmx_interface.frames());
 
         // Create the new system interface instance
-        let sys_interface = SystemInterface {
+        let mut sys_interface = SystemInterface {
             web_receive,
+            backlog: VecDeque::new(),
             dmx_interface,
             backup_handler,
+            audit_log,
+            event_log,
+            event_receive,
+            clients: ClientTracker::new(),
+            channel_writers: ChannelWriters::new(),
+            emergency: Universe::new(),
+            group_levels: FnvHashMap::default(),
+            group_blackouts: FnvHashSet::default(),
+            fixture_levels: FnvHashMap::default(),
+            fade_multiplier: 1.0,
+            effect_speed: EffectSpeed::default(),
+            is_preheat_enabled: config.is_preheat_enabled,
+            look_version: 0,
+            is_emergency: false,
+            history: History::new(config.undo_depth),
+            scripts,
+            cue_lists,
+            timelines,
+            config,
+            reload,
+            log_level,
+            events,
+            watchdog,
+            next_ping: watchdog.map(|_| Instant::now()),
+            scheduler: Scheduler::new(),
+            idle_watchdog,
+            fans: FanQueue::new(),
+            clock,
+            channel_history,
+            start_time: Instant::now(),
         };
 
+        // Warn about any problems with the patch
+        SystemInterface::check_patch(&sys_interface.config);
+
+        // Set the starting level of every group, the limits of the fades, and
+        // the preheat
+        let groups = sys_interface.config.groups.clone();
+        sys_interface.update_groups(&groups).await;
+        sys_interface.update_fade_limits().await;
+        sys_interface.update_preheat().await;
+
+        // If there was a backup, load the universe onto the dmx hardware
+        if let Some(universe) = backup {
+            sys_interface.dmx_interface.set_universe(universe).await;
+
+        // Otherwise, recall the startup scene (if specified)
+        } else if let Some(name) = sys_interface.config.startup_scene.clone() {
+            info!("Recalling startup scene: {}.", name);
+            let recall = SceneRecall {
+                name,
+                duration: None,
+            };
+            if let Err(error) = sys_interface.recall_scene(recall).await {
+                error!("Unable to recall startup scene: {}", error);
+            }
+        }
+
         // Regardless, return the new SystemInterface and general send line
-        Ok((sys_interface, web_send))
+        (sys_interface, web_send)
     }
 
     /// A method to run one iteration of the system interface to update the underlying system of any event changes.
     ///
     async fn run_once(&mut self) -> bool {
+        // Process any requests that were already received
+        if let Some((request, replaced)) = self.backlog.pop_front() {
+            return self.handle_request(request, replaced).await;
+        }
+
+        // Bring the deadline of every timer up to date
+        self.reschedule();
+
         // Check for updates on any line
         tokio::select! {
+            // Run each timer as it comes due
+            timer = self.scheduler.wait() => self.run_timer(timer).await,
+
+            // Record each event in the event log
+            event = self.event_receive.recv() => match event {
+                Ok(event) => self.event_log.record(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("The event log missed {} events.", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => (), // unreachable, this holds a sender
+            },
+
             // Updates from the Web Interface
-            Some(request) = self.web_receive.recv() => {
-                // Match the request subtype
-                match request.request {
-                    // If performing a fade
-                    Request::PlayFade { fade } => {
-                        // Try to pass new fade to the dmx inferface
-                        if let Err(error) = self.dmx_interface.play_fade(fade.clone()).await {
-                            request.reply_to.send(WebReply::failure(format!("{}", error))).unwrap_or(());
-
-                        // Otherwise
-                        } else {
-                            // Save to the backup
-                            self.backup_handler.backup_fade(fade).await;
-
-                            // And indicate success
-                            request.reply_to.send(WebReply::success()).unwrap_or(());
-                        }
+            Some(request) = self.web_receive.recv() => return self.receive_request(request).await,
+        }
+
+        // In most cases, indicate to continue normally
+        true
+    }
+
+    /// A helper method to receive a request, gathering any fades that are
+    /// already waiting behind a fade so that redundant fades to the same
+    /// channel are coalesced
+    ///
+    async fn receive_request(&mut self, request: WebRequest) -> bool {
+        // Gather the consecutive fades that are already waiting
+        let mut run = vec![request];
+        let mut next = None;
+        if matches!(run[0].request, Request::PlayFade { .. }) {
+            while let Ok(request) = self.web_receive.try_recv() {
+                if !matches!(request.request, Request::PlayFade { .. }) {
+                    next = Some((request, Vec::new()));
+                    break;
+                }
+                run.push(request);
+            }
+        }
+
+        // Queue the coalesced fades and any request after them
+        self.backlog.extend(coalescer::coalesce(run));
+        self.backlog.extend(next);
+
+        // Process the first request
+        match self.backlog.pop_front() {
+            Some((request, replaced)) => self.handle_request(request, replaced).await,
+            None => true,
+        }
+    }
+
+    /// A helper method to process a request from the web interface, record
+    /// it, and reply. Any fades the request replaced (from the same client)
+    /// are cancelled, each with its own identifier. Returns false if the
+    /// program should close.
+    ///
+    async fn handle_request(&mut self, request: WebRequest, replaced: Vec<WebRequest>) -> bool {
+        // If closing the program
+        if let Request::Close = request.request {
+            // Refuse clients in read-only mode
+            if self.config.read_only
+                && SystemInterface::is_refused(&request.client, &request.request)
+            {
+                self.audit_log
+                    .record(&request.client, &request.request, false);
+                request
+                    .reply_to
+                    .send(WebReply::error(ErrorCode::ReadOnly))
+                    .unwrap_or(());
+                return true;
+            }
+
+            // Record and reply success before releasing the output
+            self.audit_log
+                .record(&request.client, &request.request, true);
+            request.reply_to.send(WebReply::success()).unwrap_or(());
+
+            // End the loop
+            return false;
+        }
+
+        // Note the activity of the client, and any control traffic (other
+        // than from scripts) or heartbeat for the idle watchdog
+        let now = Instant::now();
+        self.clients.record(&request.client, &request.request, now);
+        if (request.request.is_mutating() && request.client.identity != "script")
+            || matches!(request.request, Request::Heartbeat { .. })
+        {
+            self.idle_watchdog.activity(now);
+        }
+
+        // Otherwise, process the request (within the span of the request,
+        // unless the emergency stop holds the look), record it, and reply to
+        // the web interface
+        let audited = request
+            .request
+            .is_mutating()
+            .then(|| request.request.clone());
+        let reply = if self.config.read_only
+            && SystemInterface::is_refused(&request.client, &request.request)
+        {
+            WebReply::error(ErrorCode::ReadOnly)
+        } else if self.is_emergency && SystemInterface::is_held(&request.request) {
+            WebReply::error(ErrorCode::EmergencyStop)
+        } else {
+            self.process_request(request.request)
+                .instrument(request.span)
+                .await
+        };
+        if let Some(audited) = audited {
+            self.audit_log
+                .record(&request.client, &audited, reply.is_success());
+
+            // Any successful change to the look moves it to a new version,
+            // and every successful change notes the writer of its channels
+            if reply.is_success() {
+                if audited.changes_look() {
+                    self.look_version += 1;
+                }
+                self.channel_writers
+                    .record(&self.config, &request.client, &audited);
+            }
+        }
+        request.reply_to.send(reply.clone()).unwrap_or(());
+
+        // Record and reply to the fades that were replaced (which came from
+        // the same client, so they would have passed the same checks)
+        for replaced in replaced {
+            self.clients
+                .record(&replaced.client, &replaced.request, now);
+            self.audit_log
+                .record(&replaced.client, &replaced.request, reply.is_success());
+            let reply = match (&reply, &replaced.request) {
+                (WebReply::Fade { .. }, Request::PlayFade { fade }) => {
+                    // Give the fade its own identifier, and announce that it
+                    // was cancelled before it started
+                    let id = self.dmx_interface.reserve_fade_id();
+                    self.events.send(Event::FadeCancelled {
+                        id,
+                        channel: fade.channel,
+                    });
+                    WebReply::Fade {
+                        is_valid: true,
+                        message: "Fade replaced by a later fade.".to_string(),
+                        id,
+                    }
+                }
+                (reply, _) => reply.clone(),
+            };
+            replaced.reply_to.send(reply).unwrap_or(());
+        }
+
+        // Continue normally
+        true
+    }
+
+    /// A helper method to process a request from the web interface and return
+    /// the reply
+    ///
+    async fn process_request(&mut self, request: Request) -> WebReply {
+        // Match the request subtype
+        match request {
+            // If setting all the lights to their emergency values
+            Request::AllStop => {
+                // Cancel any cues and fans waiting to start and stop the
+                // timelines
+                self.cue_lists.cancel();
+                self.fans.cancel();
+                self.timelines.stop_all();
+
+                // Save the current look so it can be undone
+                self.history.record(self.backup_handler.universe().clone());
+
+                // Release every override right away, then pass the
+                // emergency universe to the dmx interface
+                self.dmx_interface
+                    .release_override(Vec::new(), Duration::ZERO)
+                    .await;
+                self.dmx_interface
+                    .set_universe(self.emergency.clone())
+                    .await;
+
+                // Save the universe to the backup
+                self.backup_handler
+                    .backup_universe(self.emergency.clone())
+                    .await;
+
+                // Announce the all stop
+                self.events.send(Event::AllStop);
+
+                // Reply success to the web interface
+                WebReply::success()
+            }
+
+            // If engaging or releasing the emergency stop
+            Request::EmergencyStop { is_engaged } => {
+                // Ignore a repeat of the current state
+                if is_engaged == self.is_emergency {
+                    return WebReply::success();
+                }
+                self.is_emergency = is_engaged;
+                self.events.send(Event::EmergencyStop { is_engaged });
+
+                // Hold the emergency values until released
+                if is_engaged {
+                    warn!("Emergency stop engaged.");
+                    return Box::pin(self.process_request(Request::AllStop)).await;
+                }
+                info!("Emergency stop released.");
+                WebReply::success()
+            }
+
+            // If defining the emergency values
+            Request::DefineAllStop { universe } => {
+                // Replace the emergency universe
+                self.emergency = universe;
+
+                // Reply success to the web interface
+                WebReply::success()
+            }
+
+            // If performing a fade
+            Request::PlayFade { mut fade } => {
+                // Apply the default duration and the multiplier
+                fade.duration = self.fade_time(fade.duration);
+
+                // Try to pass new fade to the dmx inferface
+                let id = match self.dmx_interface.play_fade(fade.clone()).await {
+                    Ok(id) => id,
+                    Err(error) => return WebReply::from_error(&error),
+                };
+
+                // Save to the backup
+                self.backup_handler.backup_fade(fade).await;
+
+                // And reply with the identifier of the fade
+                WebReply::Fade {
+                    is_valid: true,
+                    message: "Fade started.".to_string(),
+                    id,
+                }
+            }
+
+            // If loading the dmx universe
+            Request::LoadUniverse {
+                universe,
+                expected_version,
+            } => {
+                // Make sure the look hasn't changed since the client read it
+                if let Err(error) = self.check_version(expected_version) {
+                    return WebReply::error(error);
+                }
+
+                // Save the current look so it can be undone
+                self.history.record(self.backup_handler.universe().clone());
+
+                // Pass the universe settings to the dmx interface
+                self.dmx_interface.set_universe(universe.clone()).await;
+
+                // Save the universe to the backup
+                self.backup_handler.backup_universe(universe).await;
+
+                // Reply success to the web interface
+                WebReply::success()
+            }
+
+            // If recalling a scene
+            Request::RecallScene { mut recall } => {
+                recall.duration = self.fade_time(recall.duration);
+                match self.recall_scene(recall).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If starting an effect
+            Request::PlayEffect { effect } => match self.dmx_interface.play_effect(effect).await {
+                Ok(()) => WebReply::success(),
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If starting an effect preset
+            Request::PlayPreset { preset } => match self.config.effect_presets.get(&preset.name) {
+                Some(effect) => Box::pin(self.process_request(effect.to_request())).await,
+                None => WebReply::error(ErrorCode::PresetNotFound {
+                    preset: preset.name,
+                }),
+            },
+
+            // If starting a movement
+            Request::PlayMovement { movement } => {
+                let movement = match movement.to_movement(&self.config) {
+                    Ok(movement) => movement,
+                    Err(error) => return WebReply::from_error(&error),
+                };
+                match self.dmx_interface.play_movement(movement).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If starting a pixel generator
+            Request::PlayPixels { play } => {
+                let map = match self.config.pixel_map(&play.map) {
+                    Ok(map) => map.clone(),
+                    Err(error) => return WebReply::from_error(&error),
+                };
+                match self.dmx_interface.play_pixels(play, map).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If stopping effects, including every channel of the fixtures
+            // and pixel maps
+            Request::StopEffect { stop } => {
+                let mut channels = stop.channels;
+                for name in stop.fixtures.iter() {
+                    match self.config.fixture(name) {
+                        Ok((fixture, profile)) => channels.extend(fixture.channels(profile)),
+                        Err(error) => return WebReply::from_error(&error),
+                    }
+                }
+                for name in stop.pixel_maps.iter() {
+                    match self.config.pixel_map(name) {
+                        Ok(map) => channels.extend(map.channels()),
+                        Err(error) => return WebReply::from_error(&error),
+                    }
+                }
+                if channels.is_empty() && !(stop.fixtures.is_empty() && stop.pixel_maps.is_empty())
+                {
+                    return WebReply::success(); // the fixtures have no channels
+                }
+                self.dmx_interface.stop_effect(channels).await;
+                WebReply::success()
+            }
+
+            // If tuning effects
+            Request::TuneEffect { tune } => {
+                self.dmx_interface.tune_effect(tune).await;
+                WebReply::success()
+            }
+
+            // If moving a fixture
+            Request::SetPosition { mut position } => {
+                position.duration = self.fade_time(position.duration);
+                match self.set_position(position).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If changing the color of a fixture
+            Request::SetColor { mut color } => {
+                color.duration = self.fade_time(color.duration);
+                match self.set_color(color).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If fanning a group
+            Request::PlayFan { mut fan } => {
+                fan.duration = self.fade_time(fan.duration);
+                fan.spread = fan
+                    .spread
+                    .map(|spread| spread.mul_f64(self.fade_multiplier));
+                match self.play_fan(fan).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If changing the level of a group
+            Request::SetGroupLevel { name, level } => {
+                match self.set_group_level(&name, level.level).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If changing the virtual intensity of a fixture
+            Request::SetFixtureIntensity { name, level } => {
+                match self.set_fixture_intensity(&name, level.level).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If blacking out a group (or releasing it)
+            Request::SetGroupBlackout { name, blackout } => {
+                match self.set_group_blackout(&name, blackout.is_blackout).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If overriding channels
+            Request::SetOverride { values } => {
+                match self.dmx_interface.set_override(values).await {
+                    Ok(()) => WebReply::success(),
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If releasing overrides (over the release time, by default)
+            Request::ReleaseOverride { release } => {
+                let duration = release
+                    .duration
+                    .unwrap_or(Duration::from_millis(self.config.override_release_time));
+                self.dmx_interface
+                    .release_override(release.channels, duration)
+                    .await;
+                WebReply::success()
+            }
+
+            // If tapping the beat
+            Request::Tap => match self.dmx_interface.tap().await {
+                Ok(bpm) => {
+                    debug!("Tapped the beat: {:.1} bpm.", bpm);
+                    self.tempo_reply().await
+                }
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If changing the tempo
+            Request::SetTempo { tempo } => {
+                info!("Changed the tempo: {} bpm.", tempo.bpm);
+                self.dmx_interface.set_tempo(tempo.bpm).await;
+                self.tempo_reply().await
+            }
+
+            // If changing the multiplier of every fade
+            Request::SetFadeMultiplier { multiplier } => {
+                info!("Changed the fade multiplier: x{}.", multiplier.multiplier);
+                self.fade_multiplier = multiplier.multiplier;
+                WebReply::FadeMultiplier {
+                    is_valid: true,
+                    multiplier: self.fade_multiplier,
+                }
+            }
+
+            // If changing the speed of the effects
+            Request::SetEffectSpeed { speed } => {
+                self.effect_speed.update(speed);
+                info!("Changed the effect speed: {:?}.", self.effect_speed);
+                self.dmx_interface.set_effect_speed(self.effect_speed).await;
+                WebReply::EffectSpeed {
+                    is_valid: true,
+                    speed: self.effect_speed,
+                }
+            }
+
+            // If turning the preheat on or off
+            Request::SetPreheat { preheat } => {
+                info!(
+                    "Changed the preheat: {}.",
+                    if preheat.is_enabled { "on" } else { "off" }
+                );
+                self.is_preheat_enabled = preheat.is_enabled;
+                self.update_preheat().await;
+                self.preheat_reply()
+            }
+
+            // If changing the labels (until the configuration is reloaded)
+            Request::SetLabels { labels } => {
+                self.config.labels.merge(labels);
+                WebReply::success()
+            }
+
+            // If getting the labels
+            Request::GetLabels => WebReply::Labels {
+                is_valid: true,
+                labels: self.config.labels.clone(),
+            },
+
+            // If changing the patch of a fixture (until the configuration is
+            // reloaded)
+            Request::PatchFixture { name, patch } => {
+                match self.config.patch_fixture(&name, patch.clone()) {
+                    Ok(()) => {
+                        info!("Patched {}: {:?}.", name, patch);
+                        self.update_fixture_intensities().await;
+                        WebReply::success()
+                    }
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If changing a palette (until the configuration is reloaded)
+            Request::SetPalette { name, palette } => {
+                info!("Updated the {} palette.", name);
+                self.config.palettes.insert(name, palette);
+                WebReply::success()
+            }
+
+            // If getting the patch of the fixtures
+            Request::GetPatch => WebReply::Patch {
+                is_valid: true,
+                patch: self.config.patch_report(),
+            },
+
+            // If getting the palettes
+            Request::GetPalettes => WebReply::Palettes {
+                is_valid: true,
+                palettes: self.config.palettes.clone(),
+            },
+
+            // If changing a layer
+            Request::SetLayer { layer, update } => {
+                info!("Changed the {} layer: {:?}.", layer, update);
+                self.dmx_interface.set_layer(layer, update).await;
+                WebReply::success()
+            }
+
+            // If undoing the last look
+            Request::Undo => {
+                let current = self.backup_handler.universe().clone();
+                match self.history.undo(current) {
+                    Some(universe) => {
+                        self.crossfade_to(universe).await;
+                        WebReply::success()
+                    }
+                    None => WebReply::error(ErrorCode::NothingToUndo),
+                }
+            }
+
+            // If redoing the last undone look
+            Request::Redo => {
+                let current = self.backup_handler.universe().clone();
+                match self.history.redo(current) {
+                    Some(universe) => {
+                        self.crossfade_to(universe).await;
+                        WebReply::success()
                     }
+                    None => WebReply::error(ErrorCode::NothingToRedo),
+                }
+            }
+
+            // If running the next cue of a cue list
+            Request::Go { list } => {
+                let go = self
+                    .cue_lists
+                    .get_mut(&list)
+                    .and_then(|cues| cues.go(Instant::now()));
+                match go {
+                    Ok(Some(index)) => match self.start_cue(&list, index).await {
+                        Ok(()) => WebReply::success(),
+                        Err(error) => WebReply::from_error(&error),
+                    },
+                    Ok(None) => {
+                        self.announce_waiting_cue(&list);
+                        WebReply::success()
+                    }
+                    Err(code) => WebReply::error(code),
+                }
+            }
+
+            // If returning a cue list to the first cue
+            Request::ResetCues { list } => match self.cue_lists.get_mut(&list) {
+                Ok(cues) => {
+                    cues.reset();
+                    self.events.send(Event::CueListReset { list });
+                    WebReply::success()
+                }
+                Err(code) => WebReply::error(code),
+            },
 
-                    // If loading the dmx universe
-                    Request::LoadUniverse { universe } => {
-                        // Pass the universe settings to the dmx interface
-                        self.dmx_interface.set_universe(universe.clone()).await;
+            // If getting the playback position of a cue list
+            Request::GetCueList { list } => match self.cue_lists.get_mut(&list) {
+                Ok(cues) => WebReply::CueList {
+                    is_valid: true,
+                    status: cues.status(Instant::now()),
+                },
+                Err(code) => WebReply::error(code),
+            },
 
-                        // Save the universe to the backup
-                        self.backup_handler.backup_universe(universe).await;
+            // If changing the transport of a timeline
+            Request::ControlTimeline { name, control } => {
+                match self.timelines.control(&name, control, Instant::now()) {
+                    Ok(()) => WebReply::success(),
+                    Err(code) => WebReply::error(code),
+                }
+            }
+
+            // If getting the transport of a timeline
+            Request::GetTimeline { name } => match self.timelines.status(&name, Instant::now()) {
+                Ok(status) => WebReply::Timeline {
+                    is_valid: true,
+                    status,
+                },
+                Err(code) => WebReply::error(code),
+            },
+
+            // If starting a script
+            Request::RunScript { name } => match self.scripts.run(&name) {
+                Ok(()) => WebReply::success(),
+                Err(error) => WebReply::from_error(&error),
+            },
 
-                        // Reply success to the web interface
-                        request.reply_to.send(WebReply::success()).unwrap_or(());
+            // If stopping a script (stopping a script that isn't running is
+            // not an error)
+            Request::StopScript { name } => {
+                if self.scripts.stop(&name) {
+                    info!("Stopped script: {}.", name);
+                }
+                WebReply::success()
+            }
+
+            // If applying a batch, process each request until one fails
+            Request::Batch { requests } => {
+                let mut replies = Vec::new();
+                for request in requests {
+                    let reply = Box::pin(self.process_request(request)).await;
+                    let is_success = reply.is_success();
+                    replies.push(reply);
+                    if !is_success {
+                        return WebReply::Batch {
+                            is_valid: false,
+                            replies,
+                        };
                     }
+                }
+                WebReply::Batch {
+                    is_valid: true,
+                    replies,
+                }
+            }
+
+            // If simulating requests, reply with the resulting universe
+            Request::DryRun { requests } => match self.dry_run(requests).await {
+                Ok(universe) => WebReply::Universe {
+                    is_valid: true,
+                    universe,
+                    version: self.look_version,
+                    labels: self.config.labels.clone(),
+                },
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If getting the current universe
+            Request::GetUniverse => match self.dmx_interface.get_universe().await {
+                Ok(universe) => WebReply::Universe {
+                    is_valid: true,
+                    universe,
+                    version: self.look_version,
+                    labels: self.config.labels.clone(),
+                },
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If listing the available scenes
+            Request::GetScenes { query } => {
+                // Sort the matching scenes by name for a consistent order
+                let mut summaries: Vec<SceneSummary> = self
+                    .config
+                    .scenes
+                    .iter()
+                    .filter(|(_, scene)| query.matches(scene))
+                    .map(|(name, scene)| SceneSummary {
+                        name: name.clone(),
+                        category: scene.category.clone(),
+                        tags: scene.tags.clone(),
+                    })
+                    .collect();
+                summaries.sort_by(|first, second| first.name.cmp(&second.name));
+                WebReply::Scenes {
+                    is_valid: true,
+                    scenes: summaries.iter().map(|scene| scene.name.clone()).collect(),
+                    summaries,
+                }
+            }
+
+            // If getting the level of a group
+            Request::GetGroupLevel { name } => match self.group_levels.get(&name) {
+                Some(level) => WebReply::GroupLevel {
+                    is_valid: true,
+                    level: *level,
+                    is_blackout: self.group_blackouts.contains(&name),
+                    name,
+                },
+                None => WebReply::error(ErrorCode::GroupNotFound { group: name }),
+            },
+
+            // If getting the virtual intensity of a fixture
+            Request::GetFixtureIntensity { name } => match self.config.intensity_channels(&name) {
+                Ok(_) => WebReply::FixtureIntensity {
+                    is_valid: true,
+                    level: self
+                        .fixture_levels
+                        .get(&name)
+                        .copied()
+                        .unwrap_or(FULL_LEVEL),
+                    name,
+                },
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If getting the state of the layers
+            Request::GetLayers => match self.dmx_interface.get_layers().await {
+                Ok(layers) => WebReply::Layers {
+                    is_valid: true,
+                    layers,
+                },
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If getting the tempo of the beat clock
+            Request::GetTempo => self.tempo_reply().await,
+
+            // If getting the multiplier of every fade
+            Request::GetFadeMultiplier => WebReply::FadeMultiplier {
+                is_valid: true,
+                multiplier: self.fade_multiplier,
+            },
+
+            // If getting the speed of the effects
+            Request::GetEffectSpeed => WebReply::EffectSpeed {
+                is_valid: true,
+                speed: self.effect_speed,
+            },
+
+            // If getting the preheat of the warmed channels
+            Request::GetPreheat => self.preheat_reply(),
+
+            // If getting the current status
+            Request::GetStatus => WebReply::Status {
+                is_valid: true,
+                status: self.get_status().await,
+            },
+
+            // If listing the supported features
+            Request::GetCapabilities => WebReply::Capabilities {
+                is_valid: true,
+                capabilities: self.get_capabilities(),
+            },
 
-                    // If closing the program
-                    Request::Close => {
-                        // End the loop
-                        return false;
+            // If searching the audit log
+            Request::GetAudit { query } => WebReply::Audit {
+                is_valid: true,
+                entries: self.audit_log.query(&query),
+            },
+
+            // If searching the event log
+            Request::GetEvents { query } => WebReply::Events {
+                is_valid: true,
+                events: self.event_log.query(&query),
+            },
+
+            // If getting the statistics of the channels
+            Request::GetChannelStats { query } => {
+                if let Some(channel) = query
+                    .channel
+                    .filter(|channel| !(1..=DMX_MAX).contains(channel))
+                {
+                    return WebReply::error(ErrorCode::ChannelOutOfRange { channel });
+                }
+                match self.dmx_interface.get_channel_stats().await {
+                    Ok(channels) => WebReply::ChannelStats {
+                        is_valid: true,
+                        channels: channels
+                            .into_iter()
+                            .filter(|stats| {
+                                query.channel.is_none_or(|channel| stats.channel == channel)
+                            })
+                            .filter(|stats| !query.is_changed || stats.changes > 0)
+                            .map(|stats| ChannelStats {
+                                writer: self.channel_writers.writer(stats.channel),
+                                ..stats
+                            })
+                            .collect(),
+                    },
+                    Err(error) => WebReply::from_error(&error),
+                }
+            }
+
+            // If getting the state of the backup
+            Request::GetBackupStatus => WebReply::Backup {
+                is_valid: true,
+                backup: self.backup_handler.status(),
+            },
+
+            // If writing the backup right away
+            Request::FlushBackup => match self.backup_handler.flush() {
+                Ok(()) => WebReply::Backup {
+                    is_valid: true,
+                    backup: self.backup_handler.status(),
+                },
+                Err(error) => WebReply::error(error),
+            },
+
+            // If loading the universe from the backup
+            Request::RestoreBackup { query } => {
+                // Make sure the look hasn't changed since the client read it
+                if let Err(error) = self.check_version(query.expected_version) {
+                    return WebReply::error(error);
+                }
+                let current = self.backup_handler.universe().clone();
+                match self.backup_handler.restore(query.is_forced) {
+                    Ok(universe) => {
+                        self.history.record(current);
+                        self.dmx_interface.set_universe(universe).await;
+                        WebReply::success()
                     }
+                    Err(error) => WebReply::error(error),
+                }
+            }
+
+            // If getting the recent values of a channel
+            Request::GetChannelHistory { channel, query } => {
+                match self.channel_history.query(channel, query.range) {
+                    Ok(samples) => WebReply::ChannelHistory {
+                        is_valid: true,
+                        channel,
+                        samples,
+                    },
+                    Err(error) => WebReply::error(error),
                 }
             }
+
+            // If noting that a client is still connected (already recorded)
+            Request::Heartbeat { .. } => WebReply::success(),
+
+            // If listing the clients seen recently
+            Request::GetClients => WebReply::Clients {
+                is_valid: true,
+                clients: self.clients.clients(Instant::now()),
+            },
+
+            // If changing the log level
+            Request::SetLogLevel { level } => match self.log_level.set(&level) {
+                Ok(()) => {
+                    info!("Changed the log level to {}.", level);
+                    self.config.log_level = level;
+                    WebReply::success()
+                }
+                Err(error) => WebReply::from_error(&error),
+            },
+
+            // If reloading the configuration
+            Request::ReloadConfig => match self.reload_config().await {
+                Ok(()) => WebReply::success(),
+                Err(error) => {
+                    error!("{}", error);
+                    WebReply::error(ErrorCode::InvalidConfig {
+                        reason: error.to_string(),
+                    })
+                }
+            },
+
+            // The close request is handled by the run loop
+            Request::Close => WebReply::success(),
         }
+    }
 
-        // In most cases, indicate to continue normally
-        true
+    /// A helper method to find the duration of a fade or scene recall from
+    /// a request, using the default duration if there is none and applying
+    /// the multiplier
+    ///
+    fn fade_time(&self, duration: Option<Duration>) -> Option<Duration> {
+        duration
+            .or_else(|| {
+                Some(Duration::from_millis(self.config.default_fade))
+                    .filter(|default| !default.is_zero())
+            })
+            .map(|duration| duration.mul_f64(self.fade_multiplier))
     }
 
-    /// A method to run an infinite number of interations of the system
-    /// interface to update the underlying system of any media changes.
+    /// A helper method to recall a scene from the configuration
     ///
-    /// When this loop completes, it will consume the system interface and drop
-    /// all associated data.
+    /// # Errors
     ///
-    pub async fn run(mut self) {
-        // Loop the structure indefinitely
-        loop {
-            // Repeat endlessly until run_once reaches close
-            if !self.run_once().await {
-                break;
-            }
+    /// This method will raise an error if the scene (or one of its
+    /// palettes) does not exist or contains a channel that is out of range.
+    ///
+    async fn recall_scene(&mut self, recall: SceneRecall) -> Result<()> {
+        // Try to find the scene
+        let scene = self.config.scene(&recall.name)?;
+
+        // Save the current look so it can be undone
+        self.history.record(self.backup_handler.universe().clone());
+
+        // Pass each channel of the scene to the dmx interface
+        for (channel, value) in scene.values.iter() {
+            self.dmx_interface
+                .play_fade(Fade {
+                    channel: *channel,
+                    value: *value,
+                    duration: recall.duration,
+                })
+                .await?;
         }
 
-        // Drop all associated data in system interface
-        drop(self);
+        // Save the scene to the backup
+        self.backup_handler.backup_scene(&scene).await;
+
+        // Announce the scene
+        self.events.send(Event::SceneRecalled { name: recall.name });
+        Ok(())
+    }
+
+    /// A helper method to move a fixture to a position, fading each axis
+    /// over the duration
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not exist or
+    /// cannot reach the position.
+    ///
+    async fn set_position(&mut self, position: Position) -> Result<()> {
+        // Convert the position into fades
+        let (fixture, profile) = self.config.fixture(&position.fixture)?;
+        let fades = position.to_fades(fixture, profile)?;
+        self.play_wide_fades(fades).await
+    }
+
+    /// A helper method to fade a fixture to a color
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not exist or
+    /// does not have red, green, and blue emitters.
+    ///
+    async fn set_color(&mut self, color: Chromaticity) -> Result<()> {
+        // Convert the color into fades
+        let (fixture, profile) = self.config.fixture(&color.fixture)?;
+        let fades = color.to_fades(fixture, profile)?;
+        self.play_wide_fades(fades).await
+    }
+
+    /// A helper method to pass each fade to the dmx interface and save it
+    /// to the backup
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a fade is out of range.
+    ///
+    async fn play_wide_fades(&mut self, fades: Vec<WideFade>) -> Result<()> {
+        for fade in fades {
+            self.dmx_interface.play_wide_fade(fade.clone()).await?;
+            self.backup_handler.backup_wide_fade(&fade).await;
+        }
+        Ok(())
+    }
+
+    /// A helper method to fan a group, starting each fade without a delay
+    /// right away and queueing the rest until their start times
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the group does not exist or
+    /// contains a channel that is out of range.
+    ///
+    async fn play_fan(&mut self, fan: GroupFan) -> Result<()> {
+        let group = self
+            .config
+            .groups
+            .get(&fan.group)
+            .ok_or_else(|| ErrorCode::GroupNotFound {
+                group: fan.group.clone(),
+            })?;
+        let (now, waiting): (Vec<_>, Vec<_>) = fan
+            .to_fades(&group.channels)
+            .into_iter()
+            .partition(|(delay, _)| delay.is_zero());
+        for (_, fade) in now {
+            self.dmx_interface.play_fade(fade.clone()).await?;
+            self.backup_handler.backup_fade(fade).await;
+        }
+        self.fans.add(Instant::now(), waiting);
+        Ok(())
+    }
+
+    /// A helper method to change the level of a group
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the group does not exist or
+    /// contains a channel that is out of range.
+    ///
+    async fn set_group_level(&mut self, name: &str, level: u8) -> Result<()> {
+        let group = self
+            .config
+            .groups
+            .get(name)
+            .ok_or_else(|| ErrorCode::GroupNotFound {
+                group: name.to_string(),
+            })?;
+        self.dmx_interface
+            .set_group_level(name, group.channels.clone(), level)
+            .await?;
+        self.group_levels.insert(name.to_string(), level);
+        Ok(())
+    }
+
+    /// A helper method to change the virtual intensity of a fixture, which
+    /// scales its color channels like a group of their own
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not exist, has
+    /// its own intensity channel, or has no color channels.
+    ///
+    async fn set_fixture_intensity(&mut self, name: &str, level: u8) -> Result<()> {
+        let channels = self.config.intensity_channels(name)?;
+        self.dmx_interface
+            .set_group_level(&SystemInterface::fixture_master(name), channels, level)
+            .await?;
+        self.fixture_levels.insert(name.to_string(), level);
+        Ok(())
+    }
+
+    /// A helper method to move the virtual intensity of each fixture to its
+    /// current color channels, releasing any fixture that was removed or
+    /// can no longer be dimmed
+    ///
+    async fn update_fixture_intensities(&mut self) {
+        for (name, level) in std::mem::take(&mut self.fixture_levels) {
+            let (channels, level) = match self.config.intensity_channels(&name) {
+                Ok(channels) => (channels, level),
+                Err(_) => {
+                    info!("Released the virtual intensity of fixture {}.", name);
+                    (Vec::new(), FULL_LEVEL)
+                }
+            };
+            let is_dimmed = !channels.is_empty();
+            if let Err(error) = self
+                .dmx_interface
+                .set_group_level(&SystemInterface::fixture_master(&name), channels, level)
+                .await
+            {
+                warn!("Unable to dim fixture {}: {}", name, error);
+                continue;
+            }
+            if is_dimmed {
+                self.fixture_levels.insert(name, level);
+            }
+        }
+    }
+
+    /// A helper method to black out a group (or release it)
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the group does not exist or
+    /// contains a channel that is out of range.
+    ///
+    async fn set_group_blackout(&mut self, name: &str, is_blackout: bool) -> Result<()> {
+        let group = self
+            .config
+            .groups
+            .get(name)
+            .ok_or_else(|| ErrorCode::GroupNotFound {
+                group: name.to_string(),
+            })?;
+        self.dmx_interface
+            .set_group_blackout(name, group.channels.clone(), is_blackout)
+            .await?;
+        if is_blackout {
+            info!("Blacked out group {}.", name);
+            self.group_blackouts.insert(name.to_string());
+        } else {
+            info!("Released the blackout of group {}.", name);
+            self.group_blackouts.remove(name);
+        }
+        Ok(())
+    }
+
+    /// A helper method to send the maximum rate of each channel in the
+    /// configuration to the dmx queue
+    ///
+    async fn update_fade_limits(&mut self) {
+        if let Err(error) = self
+            .dmx_interface
+            .set_fade_limits(self.config.max_fade_rates.clone())
+            .await
+        {
+            warn!("Unable to limit the fades: {}", error);
+        }
+    }
+
+    /// A helper method to send the preheat in the configuration to the dmx
+    /// queue (or to clear it, if the preheat is turned off)
+    ///
+    async fn update_preheat(&mut self) {
+        let values = if self.is_preheat_enabled {
+            self.config.preheat.clone()
+        } else {
+            FnvHashMap::default()
+        };
+        if let Err(error) = self.dmx_interface.set_preheat(values).await {
+            warn!("Unable to preheat the channels: {}", error);
+        }
+    }
+
+    /// A helper method to create a reply with the preheat of the warmed
+    /// channels
+    ///
+    fn preheat_reply(&self) -> WebReply {
+        WebReply::Preheat {
+            is_valid: true,
+            is_enabled: self.is_preheat_enabled,
+            values: self.config.preheat.clone(),
+        }
+    }
+
+    /// A helper method to replace the groups, keeping the current level of
+    /// any group that remains and starting any new group at its configured
+    /// level
+    ///
+    async fn update_groups(&mut self, groups: &FnvHashMap<String, ChannelGroup>) {
+        // Release every channel from the previous groups
+        for name in self.group_levels.keys() {
+            if let Err(error) = self
+                .dmx_interface
+                .set_group_level(name, Vec::new(), FULL_LEVEL)
+                .await
+            {
+                warn!("Unable to release group {}: {}", name, error);
+            }
+        }
+
+        // Apply the level of each group
+        let mut group_levels = FnvHashMap::default();
+        for (name, group) in groups.iter() {
+            let level = self
+                .group_levels
+                .get(name)
+                .copied()
+                .unwrap_or(group.level)
+                .min(FULL_LEVEL);
+            if let Err(error) = self
+                .dmx_interface
+                .set_group_level(name, group.channels.clone(), level)
+                .await
+            {
+                warn!("Unable to set the level of group {}: {}", name, error);
+                continue;
+            }
+            group_levels.insert(name.clone(), level);
+        }
+        self.group_levels = group_levels;
+
+        // Keep the blackout of each group that remains, with its new channels
+        for name in std::mem::take(&mut self.group_blackouts) {
+            let channels = match groups.get(&name) {
+                Some(group) => group.channels.clone(),
+                None => Vec::new(),
+            };
+            let is_blackout = !channels.is_empty();
+            if let Err(error) = self
+                .dmx_interface
+                .set_group_blackout(&name, channels, is_blackout)
+                .await
+            {
+                warn!("Unable to black out group {}: {}", name, error);
+                continue;
+            }
+            if is_blackout {
+                self.group_blackouts.insert(name);
+            }
+        }
+    }
+
+    /// A helper method to simulate the requests, starting from the current
+    /// universe, and return the universe once every fade has finished
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if any of the requests would fail or
+    /// cannot be simulated.
+    ///
+    async fn dry_run(&mut self, requests: Vec<Request>) -> Result<Universe> {
+        let universe = self.dmx_interface.get_universe().await?;
+        let mut dry_run = DryRun::new(&self.config, universe, self.emergency.clone());
+        for request in requests {
+            dry_run.apply(request)?;
+        }
+        Ok(dry_run.into_universe())
+    }
+
+    /// A helper function to indicate whether a request is held back while
+    /// the emergency stop is engaged (any change to the look)
+    ///
+    fn is_held(request: &Request) -> bool {
+        request.is_mutating()
+            && !matches!(
+                request,
+                Request::EmergencyStop { .. }
+                    | Request::AllStop
+                    | Request::DefineAllStop { .. }
+                    | Request::SetLabels { .. }
+                    | Request::PatchFixture { .. }
+                    | Request::SetLogLevel { .. }
+                    | Request::FlushBackup
+                    | Request::ReloadConfig
+            )
+    }
+
+    /// A helper function to check if a request is refused in read-only mode:
+    /// any change from outside the program, except the emergency stop
+    ///
+    fn is_refused(client: &Client, request: &Request) -> bool {
+        request.is_mutating()
+            && (client.identity != Client::internal().identity)
+            && !matches!(request, Request::EmergencyStop { .. })
+    }
+
+    /// A helper method to start a cue of a cue list, recalling its scene.
+    /// Cue lists share the channels of the base layer: the most recent cue
+    /// to set a channel takes it.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the cue does not exist or its scene
+    /// cannot be recalled.
+    ///
+    async fn start_cue(&mut self, list: &str, index: usize) -> Result<()> {
+        let cue = self
+            .cue_lists
+            .get_mut(list)?
+            .cue(index)
+            .cloned()
+            .ok_or_else(|| anyhow!(ErrorCode::EndOfCueList))?;
+        let duration = self.fade_time(cue.duration);
+        self.cue_lists
+            .get_mut(list)?
+            .started(index, duration, Instant::now());
+        info!("Running cue {} of {}: {}.", index + 1, list, cue.scene);
+        self.events.send(Event::CueStarted {
+            list: list.to_string(),
+            number: index + 1,
+            scene: cue.scene.clone(),
+        });
+
+        // Send the actions of the cue (a dry run never reaches other systems)
+        if self.config.dry_run {
+            if !cue.actions.is_empty() {
+                info!("Dry run. Skipping {} cue actions.", cue.actions.len());
+            }
+        } else {
+            cue_actions::send_actions(&cue.actions, list, index + 1, &cue.scene);
+        }
+        self.announce_waiting_cue(list);
+        let recall = SceneRecall {
+            name: cue.scene,
+            duration,
+        };
+        self.recall_scene(recall).await
+    }
+
+    /// A helper method to announce the cue waiting to start, if any
+    ///
+    fn announce_waiting_cue(&mut self, list: &str) {
+        let Ok(cues) = self.cue_lists.get_mut(list) else {
+            return;
+        };
+        let status = cues.status(Instant::now());
+        if let (Some(next), Some(starts_in)) = (status.next, status.starts_in) {
+            self.events.send(Event::CueWaiting {
+                list: list.to_string(),
+                number: next.number,
+                starts_in,
+            });
+        }
+    }
+
+    /// A helper method to start a cue that was waiting for its wait or
+    /// follow time. The emergency stop cancels the cue instead.
+    ///
+    async fn follow_cue(&mut self, list: &str, index: usize) {
+        if self.is_emergency {
+            self.cue_lists.cancel();
+            return;
+        }
+        match self.start_cue(list, index).await {
+            Ok(()) => self.look_version += 1,
+            Err(error) => error!("Unable to run cue {} of {}: {}", index + 1, list, error),
+        }
+    }
+
+    /// A helper method to check that the look is still at the version the
+    /// client expects, if any
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the look has changed since the
+    /// client read it.
+    ///
+    fn check_version(&self, expected: Option<u64>) -> Result<(), ErrorCode> {
+        match expected {
+            Some(expected) if expected != self.look_version => Err(ErrorCode::VersionConflict {
+                expected,
+                current: self.look_version,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// A helper method to fade to the safe look of the idle watchdog: the
+    /// scene (if any) or otherwise the all stop values
+    ///
+    async fn engage_idle(&mut self, idle: IdleConfig) {
+        // The emergency stop already holds a safe look
+        if self.is_emergency {
+            return;
+        }
+        warn!(
+            "No control traffic for {} secs. Fading to the safe look.",
+            idle.timeout
+        );
+        let duration = Some(Duration::from_millis(idle.fade));
+        match idle.scene {
+            Some(name) => {
+                if let Err(error) = self.recall_scene(SceneRecall { name, duration }).await {
+                    error!("Unable to recall the safe look: {}", error);
+                }
+            }
+            None => {
+                self.history.record(self.backup_handler.universe().clone());
+                self.dmx_interface
+                    .fade_universe(&self.emergency, duration)
+                    .await;
+                self.backup_handler
+                    .backup_universe(self.emergency.clone())
+                    .await;
+            }
+        }
+        self.look_version += 1;
+        self.events.send(Event::IdleEngaged {
+            timeout: idle.timeout,
+        });
+    }
+
+    /// A helper method to crossfade the output to a look from the history
+    ///
+    async fn crossfade_to(&mut self, universe: Universe) {
+        // Fade every channel over the undo time
+        self.dmx_interface
+            .fade_universe(
+                &universe,
+                Some(Duration::from_millis(self.config.undo_time)),
+            )
+            .await;
+
+        // Save the universe to the backup
+        self.backup_handler.backup_universe(universe).await;
+    }
+
+    /// A helper method to collect the current status of the program
+    ///
+    async fn get_status(&mut self) -> Status {
+        // Check the state of the dmx queue (an unresponsive queue has no fades)
+        let (active_fades, active_effects, is_dmx_ready, timings, writes) =
+            match self.dmx_interface.get_status(STATUS_TIMEOUT).await {
+                Some(status) => (
+                    status.active_fades,
+                    status.active_effects,
+                    status.is_writing,
+                    status.timings,
+                    status.writes,
+                ),
+                None => (0, 0, false, FrameTimings::default(), WriteStatus::default()),
+            };
+
+        // Check the state of the backup server
+        let backup = self.backup_handler.status().state;
+
+        // Compose the status
+        Status {
+            instance_id: self.config.instance_id(),
+            driver: self.dmx_interface.driver().to_string(),
+            universes: 1,
+            active_fades,
+            active_effects,
+            is_dmx_ready,
+            backup,
+            uptime: self.start_time.elapsed().as_secs(),
+            is_emergency: self.is_emergency,
+            is_read_only: self.config.read_only,
+            clock: self.clock.borrow().clone(),
+            timings,
+            writes,
+        }
+    }
+
+    /// A helper method to reply with the tempo of the beat clock
+    ///
+    async fn tempo_reply(&self) -> WebReply {
+        match self.dmx_interface.get_tempo().await {
+            Ok((bpm, beat)) => WebReply::Tempo {
+                is_valid: true,
+                bpm,
+                beat,
+            },
+            Err(error) => WebReply::from_error(&error),
+        }
+    }
+
+    /// A helper method to list the features supported by the program
+    ///
+    fn get_capabilities(&self) -> Capabilities {
+        // List the inputs for requests and triggers
+        let mut protocols = vec!["http", "osc", "midi", "audio"];
+        if cfg!(feature = "gpio") {
+            protocols.push("gpio");
+        }
+
+        // Compose the capabilities
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_versions: vec![API_VERSION.to_string()],
+            drivers: vec![
+                EnttecUsbPro::NAME.to_string(),
+                DryRunDriver::NAME.to_string(),
+            ],
+            effects: self.dmx_interface.effects(),
+            pixel_generators: self.dmx_interface.pixel_generators(),
+            protocols: protocols.into_iter().map(String::from).collect(),
+            universes: 1,
+            channels: DMX_MAX,
+            scenes: self.config.scenes.len(),
+            features: [
+                "scenes",
+                "fades",
+                "allStop",
+                "undo",
+                "audit",
+                "triggers",
+                "webhooks",
+                "backup",
+                "graphql",
+                "scripts",
+                "effects",
+                "dryRun",
+                "fixtures",
+                "movements",
+                "groups",
+                "layers",
+                "overrides",
+                "labels",
+                "tempo",
+                "fadeMultiplier",
+                "pixels",
+                "clients",
+                "changes",
+                "expectedVersion",
+                "eventLog",
+                "frameCapture",
+                "channelStats",
+                "emergencyStop",
+                "confirmation",
+                "readOnly",
+                "sceneTags",
+                "palettes",
+                "effectPresets",
+                "cueList",
+                "timelines",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+
+    /// A helper method to reload the configuration and apply any changes that
+    /// can be made without disrupting the output
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the configuration could not be
+    /// loaded. The current configuration is kept in that case.
+    ///
+    async fn reload_config(&mut self) -> Result<()> {
+        // Try to load the new configuration
+        let mut config = (self.reload)()?;
+
+        // Keep the running settings that require a restart, warning about any
+        // changes to them
+        let changes = SystemInterface::keep_restart_settings(&self.config, &mut config);
+        if !changes.is_empty() {
+            warn!("Changes to {} require a restart.", changes.join(", "));
+        }
+
+        // Update the log level, if it changed
+        if config.log_level != self.config.log_level {
+            self.log_level.set(&config.log_level)?;
+        }
+
+        // Restart the idle watchdog, if it changed
+        if config.idle != self.config.idle {
+            self.idle_watchdog
+                .set_config(config.idle.clone(), Instant::now());
+        }
+
+        // Replace the timelines, if they changed
+        if config.timelines != self.config.timelines {
+            self.timelines.set_timelines(config.timelines.clone());
+        }
+
+        // Replace the cues, if they changed
+        if (config.cues != self.config.cues) || (config.cue_lists != self.config.cue_lists) {
+            self.cue_lists.set_lists(config.all_cue_lists());
+        }
+
+        // Update the depth of the history, if it changed
+        if config.undo_depth != self.config.undo_depth {
+            self.history.set_depth(config.undo_depth);
+        }
+
+        // Replace the scripts, if they changed
+        if config.scripts != self.config.scripts {
+            self.scripts.set_scripts(config.scripts.clone());
+        }
+
+        // Replace the groups, if they changed
+        if config.groups != self.config.groups {
+            self.update_groups(&config.groups).await;
+        }
+
+        // Replace the limits of the fades, if they changed
+        if config.max_fade_rates != self.config.max_fade_rates {
+            self.config.max_fade_rates = config.max_fade_rates.clone();
+            self.update_fade_limits().await;
+        }
+
+        // Replace the maximum age of the backup, if it changed
+        if config.backup_max_age != self.config.backup_max_age {
+            self.config.backup_max_age = config.backup_max_age;
+            self.backup_handler.set_max_age(config.backup_max_age);
+        }
+
+        // Replace the settings of the channel history, if they changed
+        if config.channel_history != self.config.channel_history {
+            self.config.channel_history = config.channel_history.clone();
+            self.channel_history
+                .set_config(config.channel_history.clone());
+        }
+
+        // Replace the preheat, if it changed (keeping whether it is on)
+        if config.preheat != self.config.preheat {
+            self.config.preheat = config.preheat.clone();
+            self.update_preheat().await;
+        }
+
+        // Update the fade resolution, if it changed
+        if config.resolution != self.config.resolution {
+            self.dmx_interface
+                .set_resolution(Duration::from_millis(config.resolution))
+                .await;
+        }
+
+        // Check the patch, if it changed
+        let is_repatched =
+            (config.fixtures != self.config.fixtures) || (config.profiles != self.config.profiles);
+        if is_repatched {
+            SystemInterface::check_patch(&config);
+        }
+
+        // Save the new configuration (the exit behavior is read when closing)
+        self.config = config;
+
+        // Move the virtual intensity of each fixture, if the patch changed
+        if is_repatched {
+            self.update_fixture_intensities().await;
+        }
+        info!("Reloaded the configuration.");
+        Ok(())
+    }
+
+    /// A helper function to keep the running value of each setting that only
+    /// takes effect after a restart, listing those that the new configuration
+    /// would have changed
+    ///
+    fn keep_restart_settings(running: &Config, new: &mut Config) -> Vec<&'static str> {
+        // Copy each restart-only setting back, noting it if it differs
+        let mut changes = Vec::new();
+        macro_rules! keep {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if new.$field != running.$field {
+                        new.$field = running.$field.clone();
+                        changes.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+        keep!(
+            path,
+            dry_run,
+            dmx_thread,
+            address,
+            swagger_ui,
+            backup,
+            instance_id,
+            backups,
+            log_format,
+            log_file,
+            log_max_size,
+            log_max_age,
+            log_max_files,
+            otlp,
+            tokens,
+            cors,
+            socket,
+            audit_file,
+            event_file,
+            web_root,
+            max_fade_duration,
+            webhooks,
+            triggers,
+            osc_address,
+            midi_device,
+            gpio,
+            emergency_stop,
+            audio,
+            art_net,
+            sacn,
+            citp,
+            follow,
+            bridge,
+            rate_limit,
+            max_concurrent_requests,
+            confirm,
+        );
+        changes
+    }
+
+    /// A helper function to warn about any channel patched to more than one
+    /// fixture, and any fixture that cannot be patched, returning the warnings
+    ///
+    fn check_patch(config: &Config) -> Vec<String> {
+        let report = config.patch_report();
+        let mut warnings = Vec::new();
+        for conflict in report.conflicts {
+            warnings.push(format!(
+                "Channel {} is patched to more than one fixture: {}.",
+                conflict.channel,
+                conflict.fixtures.join(", ")
+            ));
+        }
+        for name in report.invalid {
+            warnings.push(format!(
+                "Fixture {} has a missing profile or mode, or ends beyond the universe.",
+                name
+            ));
+        }
+        for (name, profile) in config.profiles.iter() {
+            if let Err(error) = profile.color_conversion() {
+                warnings.push(format!(
+                    "Profile {} has an invalid color calibration: {}",
+                    name, error
+                ));
+            }
+        }
+        for warning in warnings.iter() {
+            warn!("{}", warning);
+        }
+        warnings
+    }
+
+    /// A helper function to name the submaster of the virtual intensity of
+    /// a fixture, apart from the names of the groups
+    ///
+    fn fixture_master(name: &str) -> String {
+        format!("fixture:{}", name)
+    }
+
+    /// A helper method to set the deadline of every timer from the current
+    /// state of each feature
+    ///
+    fn reschedule(&mut self) {
+        self.scheduler.set(Timer::SystemdWatchdog, self.next_ping);
+        self.scheduler
+            .set(Timer::IdleWatchdog, self.idle_watchdog.deadline());
+        for (name, deadline) in self.cue_lists.deadlines() {
+            self.scheduler.set(Timer::CueList(name.clone()), deadline);
+        }
+        self.scheduler
+            .set(Timer::Timelines, self.timelines.deadline());
+        self.scheduler.set(Timer::Fans, self.fans.deadline());
+        self.scheduler
+            .set(Timer::ChannelHistory, self.channel_history.deadline());
+    }
+
+    /// A helper method to run a timer that has come due
+    ///
+    async fn run_timer(&mut self, timer: Timer) {
+        let now = Instant::now();
+        match timer {
+            // Ping the systemd watchdog, if enabled
+            Timer::SystemdWatchdog => {
+                let Some(period) = self.watchdog else {
+                    return;
+                };
+                self.next_ping = Some(now + period);
+
+                // Only ping the watchdog if the dmx queue is still responsive
+                if self.dmx_interface.is_responsive(period).await {
+                    Systemd::notify("WATCHDOG=1");
+                } else {
+                    error!("DMX queue is unresponsive. Withholding watchdog ping.");
+                }
+            }
+
+            // Engage the safe look if the clients have been quiet too long
+            Timer::IdleWatchdog => {
+                if let Some(idle) = self.idle_watchdog.engage(now) {
+                    self.engage_idle(idle).await;
+                }
+            }
+
+            // Start the waiting cue once its wait or follow time has passed
+            Timer::CueList(list) => {
+                if let Some(index) = self.cue_lists.due(&list, now) {
+                    self.follow_cue(&list, index).await;
+                }
+            }
+
+            // Run the actions of the timelines as their times pass
+            Timer::Timelines => {
+                let actions = self.timelines.due(now);
+                if !actions.is_empty() {
+                    self.timelines.send(actions);
+                }
+            }
+
+            // Start each fan fade that is due
+            Timer::Fans => {
+                for fade in self.fans.due(now) {
+                    if let Err(error) = self.dmx_interface.play_fade(fade.clone()).await {
+                        warn!(
+                            "Unable to start the fan on channel {}: {}",
+                            fade.channel, error
+                        );
+                        continue;
+                    }
+                    self.backup_handler.backup_fade(fade).await;
+                }
+            }
+
+            // Record the output in the channel history
+            Timer::ChannelHistory => self.channel_history.sample(now),
+        }
+    }
+
+    /// A method to return a controller that shares the dmx queue of this
+    /// interface (for inputs that change the output directly)
+    ///
+    pub fn dmx_controller(&self) -> DmxController {
+        self.dmx_interface.clone()
+    }
+
+    /// A method to run an infinite number of interations of the system
+    /// interface to update the underlying system of any media changes.
+    ///
+    /// When this loop completes, it will consume the system interface and drop
+    /// all associated data.
+    ///
+    pub async fn run(mut self) {
+        // Loop the structure indefinitely
+        loop {
+            // Repeat endlessly until run_once reaches close
+            if !self.run_once().await {
+                break;
+            }
+        }
+
+        // Notify systemd, stop any scripts, and apply the exit behavior to the output
+        Systemd::notify("STOPPING=1");
+        self.scripts.stop_all();
+        self.exit().await;
+
+        // Drop all associated data in system interface
+        drop(self);
+    }
+
+    /// A helper method to apply the exit behavior before the output is
+    /// released
+    ///
+    async fn exit(&mut self) {
+        // Match the exit behavior
+        let exit_time = Duration::from_secs(self.config.exit_time);
+        match self.config.exit_behavior {
+            // Keep the current output for the exit time
+            ExitBehavior::Hold => {
+                info!("Holding the last look for {:?}.", exit_time);
+                sleep(exit_time).await;
+            }
+
+            // Fade all channels to zero and wait for the fade to complete
+            ExitBehavior::Blackout => {
+                // Stop the effects (holding their values) and release the
+                // overrides alongside the fade, so that no layer stays lit
+                info!("Fading to black over {:?}.", exit_time);
+                self.dmx_interface.stop_effect(Vec::new()).await;
+                self.dmx_interface
+                    .release_override(Vec::new(), exit_time)
+                    .await;
+                self.dmx_interface
+                    .fade_universe(&Universe::new(), Some(exit_time))
+                    .await;
+                sleep(exit_time).await;
+
+                // Make sure the final frame is exactly black, without the
+                // preheat
+                self.is_preheat_enabled = false;
+                self.update_preheat().await;
+                self.dmx_interface.set_universe(Universe::new()).await;
+            }
+
+            // Snap to the emergency universe and hold it for the exit time
+            ExitBehavior::Emergency => {
+                info!("Holding the emergency look for {:?}.", exit_time);
+                self.dmx_interface
+                    .set_universe(self.emergency.clone())
+                    .await;
+                sleep(exit_time).await;
+            }
+        }
+    }
+}
+
+// Tests of the system interface module
+#[cfg(test)]
+mod tests {
+    use super::backup_handler::{BackupStore, MemoryStore};
+    use super::*;
+    use tokio::sync::oneshot;
+    use tracing::Span;
+
+    // A helper function to start a system interface with an empty store
+    async fn start_interface(config: Config) -> SystemInterface {
+        let events = EventSend::new();
+        let store = MemoryStore::default();
+        let backup_handler = BackupHandler::with_stores(
+            "test",
+            vec![(String::from("memory"), Some(Box::new(store)))],
+            None,
+            events.clone(),
+        );
+        let dmx_interface =
+            DmxController::new(DryRunDriver, Duration::from_millis(50), events.clone());
+        let (sys_interface, _web_send) = SystemInterface::start(
+            config,
+            Box::new(|| Ok(Config::default())),
+            LogLevel::detached(),
+            events,
+            dmx_interface,
+            backup_handler,
+        )
+        .await;
+        sys_interface
+    }
+
+    // A helper function to create a request from the client, with the line
+    // for its reply
+    fn web_request(client: Client, request: Request) -> (WebRequest, oneshot::Receiver<WebReply>) {
+        let (reply_to, reply) = oneshot::channel();
+        let request = WebRequest {
+            reply_to,
+            request,
+            client,
+            span: Span::none(),
+        };
+        (request, reply)
+    }
+
+    // A helper function to handle one request from the client, returning
+    // the reply and whether the program should keep running
+    async fn handle(
+        sys_interface: &mut SystemInterface,
+        client: Client,
+        request: Request,
+    ) -> (WebReply, bool) {
+        let (request, reply) = web_request(client, request);
+        let is_running = sys_interface.handle_request(request, Vec::new()).await;
+        (reply.await.unwrap(), is_running)
+    }
+
+    // A helper function to create a fade request
+    fn fade(channel: u32, value: u8) -> Request {
+        Request::PlayFade {
+            fade: Fade {
+                channel,
+                value,
+                duration: None,
+            },
+        }
+    }
+
+    // Test that a backup in the store is loaded onto the output at startup
+    #[tokio::test]
+    async fn reload_from_backup() {
+        // Leave a universe in the store, as after a crash
+        let mut universe = Universe::new();
+        universe.set(4, 200);
+        let mut store = MemoryStore::default();
+        store.write(
+            "vulcan:test:universe",
+            &serde_yaml::to_string(&universe).unwrap(),
+        );
+
+        // Start the system interface with the store
+        let events = EventSend::new();
+        let backup_handler = BackupHandler::with_stores(
+            "test",
+            vec![(String::from("memory"), Some(Box::new(store.clone())))],
+            None,
+            events.clone(),
+        );
+        let dmx_interface =
+            DmxController::new(DryRunDriver, Duration::from_millis(50), events.clone());
+        let (sys_interface, _web_send) = SystemInterface::start(
+            Config::default(),
+            Box::new(|| Ok(Config::default())),
+            LogLevel::detached(),
+            events,
+            dmx_interface,
+            backup_handler,
+        )
+        .await;
+
+        // The universe is restored and the store is cleared on close
+        let output = sys_interface.dmx_interface.get_universe().await.unwrap();
+        assert_eq!(output.get(4), 200);
+        assert_eq!(sys_interface.backup_handler.universe().get(4), 200);
+        drop(sys_interface);
+        assert_eq!(store.get("vulcan:test:universe"), None);
+    }
+
+    // Test that one request is backed up to every target together, with a
+    // single reply
+    #[tokio::test]
+    async fn backup_targets() {
+        // Start the system interface with two stores
+        let (first, second) = (MemoryStore::default(), MemoryStore::default());
+        let events = EventSend::new();
+        let backup_handler = BackupHandler::with_stores(
+            "test",
+            vec![
+                (String::from("first"), Some(Box::new(first.clone()))),
+                (String::from("second"), Some(Box::new(second.clone()))),
+            ],
+            None,
+            events.clone(),
+        );
+        let dmx_interface =
+            DmxController::new(DryRunDriver, Duration::from_millis(50), events.clone());
+        let (mut sys_interface, _web_send) = SystemInterface::start(
+            Config::default(),
+            Box::new(|| Ok(Config::default())),
+            LogLevel::detached(),
+            events,
+            dmx_interface,
+            backup_handler,
+        )
+        .await;
+
+        // Loading a universe writes the same backup to both stores
+        let mut universe = Universe::new();
+        universe.set(8, 120);
+        let (reply, _) = handle(
+            &mut sys_interface,
+            Client::internal(),
+            Request::from(universe),
+        )
+        .await;
+        assert!(reply.is_success());
+        let backup = first.get("vulcan:test:universe");
+        assert!(backup.is_some());
+        assert_eq!(second.get("vulcan:test:universe"), backup);
+
+        // One flush confirms every target in a single reply
+        let (reply, _) = handle(&mut sys_interface, Client::internal(), Request::FlushBackup).await;
+        let WebReply::Backup { is_valid, backup } = reply else {
+            panic!("Expected a backup reply, found {:?}.", reply);
+        };
+        assert!(is_valid);
+        assert_eq!(backup.targets.len(), 2);
+        assert!(
+            backup
+                .targets
+                .iter()
+                .all(|target| { target.last_success.is_some() && target.pending_bytes == 0 })
+        );
+    }
+
+    // Test that clients cannot close the program in read-only mode
+    #[tokio::test]
+    async fn read_only_close() {
+        let config = Config {
+            read_only: true,
+            ..Config::default()
+        };
+        let mut sys_interface = start_interface(config).await;
+
+        // A client with an admin token is refused
+        let client = Client::from_token("token", Role::Admin, None);
+        let (reply, is_running) = handle(&mut sys_interface, client, Request::Close).await;
+        assert!(is_running);
+        assert!(matches!(
+            reply,
+            WebReply::Error {
+                error: ErrorCode::ReadOnly,
+                ..
+            }
+        ));
+
+        // The program itself may still close
+        let (reply, is_running) =
+            handle(&mut sys_interface, Client::internal(), Request::Close).await;
+        assert!(!is_running);
+        assert!(reply.is_success());
+    }
+
+    // Test that only changes to the look move it to a new version
+    #[tokio::test]
+    async fn version_looks() {
+        let mut sys_interface = start_interface(Config::default()).await;
+
+        // Settings leave the version as is
+        let labels = Request::SetLabels {
+            labels: Labels::default(),
+        };
+        let (reply, _) = handle(&mut sys_interface, Client::internal(), labels).await;
+        assert!(reply.is_success());
+        assert_eq!(sys_interface.look_version, 0);
+
+        // A fade moves the look to a new version
+        let (reply, _) = handle(&mut sys_interface, Client::internal(), fade(1, 10)).await;
+        assert!(reply.is_success());
+        assert_eq!(sys_interface.look_version, 1);
+
+        // A restore from the previous version is rejected
+        let restore = Request::RestoreBackup {
+            query: RestoreQuery {
+                is_forced: false,
+                expected_version: Some(0),
+            },
+        };
+        let (reply, _) = handle(&mut sys_interface, Client::internal(), restore).await;
+        assert!(matches!(
+            reply,
+            WebReply::Error {
+                error: ErrorCode::VersionConflict {
+                    expected: 0,
+                    current: 1
+                },
+                ..
+            }
+        ));
+    }
+
+    // Test that only the restart-only settings are kept and reported
+    #[test]
+    fn keep_restart_settings() {
+        // Change one restart-only setting and one that reloads
+        let old = Config::default();
+        let mut new = old.clone();
+        new.sacn = Some(SacnConfig::default());
+        new.read_only = true;
+
+        // Only the restart-only setting is reported and put back
+        let mut same = old.clone();
+        assert!(SystemInterface::keep_restart_settings(&old, &mut same).is_empty());
+        assert_eq!(
+            SystemInterface::keep_restart_settings(&old, &mut new),
+            vec!["sacn"]
+        );
+        assert_eq!(new.sacn, None);
+        assert!(new.read_only);
+    }
+
+    // Test that reloading a change to the dry run setting has no effect
+    #[tokio::test]
+    async fn reload_dry_run() {
+        let mut sys_interface = start_interface(Config::default()).await;
+        sys_interface.reload = Box::new(|| {
+            Ok(Config {
+                dry_run: true,
+                undo_depth: 3,
+                ..Config::default()
+            })
+        });
+
+        // The reloadable setting changes but the output stays live
+        sys_interface.reload_config().await.unwrap();
+        assert!(!sys_interface.config.dry_run);
+        assert_eq!(sys_interface.config.undo_depth, 3);
+    }
+
+    // Test that the blackout on exit clears every layer of the output frame
+    #[tokio::test]
+    async fn exit_blackout() {
+        let config = Config {
+            exit_behavior: ExitBehavior::Blackout,
+            exit_time: 0,
+            ..Config::default()
+        };
+        let mut sys_interface = start_interface(config).await;
+        let mut frames = sys_interface.dmx_interface.frames();
+
+        // Light the base, an effect, and an override
+        let effect = Request::PlayEffect {
+            effect: EffectPlay {
+                effect: "sine".to_string(),
+                channels: vec![2],
+                low: 100,
+                high: 200,
+                period: Duration::from_secs(10),
+                beats: None,
+                seed: 0,
+                chance: 1.0,
+                intensity: 1.0,
+            },
+        };
+        let values = Override {
+            values: [(3, 150)].into_iter().collect(),
+        };
+        let requests = [fade(1, 50), effect, Request::SetOverride { values }];
+        for request in requests {
+            let (reply, _) = handle(&mut sys_interface, Client::internal(), request).await;
+            assert!(reply.is_success());
+        }
+        let lit =
+            |frame: &Universe| frame.get(1) == 50 && frame.get(2) >= 100 && frame.get(3) == 150;
+        tokio::time::timeout(Duration::from_secs(1), frames.wait_for(lit))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Every channel of the output is dark after the exit
+        sys_interface.exit().await;
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            frames.wait_for(|frame| *frame == Universe::new()),
+        )
+        .await
+        .expect("The output frame did not go dark.")
+        .unwrap();
+    }
+
+    // Test that fixtures overlapping on a channel are reported
+    #[test]
+    fn check_patch() {
+        let config: Config = serde_yaml::from_str(
+            "profiles:
+  wash: { channels: [red, green, blue, white] }
+fixtures:
+  washLeft: { profile: wash, address: 1 }
+  washRight: { profile: wash, address: 3 }
+",
+        )
+        .unwrap();
+
+        // Each shared channel is reported once, naming both fixtures
+        assert_eq!(
+            SystemInterface::check_patch(&config),
+            vec![
+                "Channel 3 is patched to more than one fixture: washLeft, washRight.",
+                "Channel 4 is patched to more than one fixture: washLeft, washRight.",
+            ]
+        );
+
+        // Fixtures side by side are not reported
+        let mut config = config;
+        config.fixtures.get_mut("washRight").unwrap().address = 5;
+        assert!(SystemInterface::check_patch(&config).is_empty());
+    }
+
+    // Test that a batch stops at a failing request, keeping the earlier ones
+    #[tokio::test]
+    async fn batch_failure() {
+        let mut sys_interface = start_interface(Config::default()).await;
+
+        // Recall a missing scene between two fades
+        let missing = Request::RecallScene {
+            recall: SceneRecall {
+                name: "missing".to_string(),
+                duration: None,
+            },
+        };
+        let batch = Request::Batch {
+            requests: vec![fade(1, 10), missing, fade(2, 20)],
+        };
+        let reply = sys_interface.process_request(batch).await;
+
+        // The batch is invalid, with the replies up to the failure
+        let WebReply::Batch { is_valid, replies } = reply else {
+            panic!("Expected a batch reply, found {:?}.", reply);
+        };
+        assert!(!is_valid);
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0].is_success());
+        assert!(matches!(
+            replies[1],
+            WebReply::Error {
+                error: ErrorCode::SceneNotFound { .. },
+                ..
+            }
+        ));
+
+        // The fade before the failure is applied and the one after is not
+        let universe = sys_interface.dmx_interface.get_universe().await.unwrap();
+        assert_eq!(universe.get(1), 10);
+        assert_eq!(universe.get(2), 0);
+    }
+
+    // Test that the calibration of a profile changes the output of its
+    // fixtures, but not of a raw channel
+    #[tokio::test]
+    async fn calibrated_color() {
+        let config: Config = serde_yaml::from_str(
+            "profiles:
+  calibrated:
+    channels: [red, green, blue, white]
+    calibration:
+      red: {x: 0.69, y: 0.31}
+      green: {x: 0.17, y: 0.70}
+      blue: {x: 0.14, y: 0.04}
+      whitePoint: {x: 0.31, y: 0.32}
+  plain: { channels: [red, green, blue, white] }
+fixtures:
+  washCalibrated: { profile: calibrated, address: 1 }
+  washPlain: { profile: plain, address: 5 }
+",
+        )
+        .unwrap();
+        let mut sys_interface = start_interface(config).await;
+        let mut frames = sys_interface.dmx_interface.frames();
+
+        // Send the same white to both fixtures, and a raw value to a channel
+        for fixture in ["washCalibrated", "washPlain"] {
+            let color = Request::SetColor {
+                color: Chromaticity {
+                    fixture: fixture.to_string(),
+                    x: 0.3127,
+                    y: 0.3290,
+                    brightness: 0.5,
+                    duration: None,
+                },
+            };
+            let (reply, _) = handle(&mut sys_interface, Client::internal(), color).await;
+            assert!(reply.is_success());
+        }
+        let (reply, _) = handle(&mut sys_interface, Client::internal(), fade(20, 200)).await;
+        assert!(reply.is_success());
+        let frame = tokio::time::timeout(
+            Duration::from_secs(1),
+            frames.wait_for(|frame| frame.get(20) == 200),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .clone();
+
+        // The plain fixture makes white from (nearly) equal emitters, the
+        // calibrated fixture corrects its emitters, and the raw channel is
+        // unchanged
+        let spread = |address: u32| {
+            let emitters: Vec<u8> = (address..address + 3)
+                .map(|channel| frame.get(channel))
+                .collect();
+            emitters.iter().max().unwrap() - emitters.iter().min().unwrap()
+        };
+        assert!(frame.get(5) > 0);
+        assert!(spread(5) <= 1);
+        assert!(spread(1) > 1);
+        assert_eq!(frame.get(20), 200);
+    }
+
+    // Test that a replaced fade is cancelled with its own identifier
+    #[tokio::test]
+    async fn replaced_fades() {
+        let mut sys_interface = start_interface(Config::default()).await;
+        let mut events = sys_interface.events.subscribe();
+
+        // Replace an earlier fade from the same client
+        let client = Client::from_token("token", Role::Operator, None);
+        let (kept, kept_reply) = web_request(client.clone(), fade(1, 30));
+        let (replaced, replaced_reply) = web_request(client, fade(1, 10));
+        assert!(sys_interface.handle_request(kept, vec![replaced]).await);
+
+        // Each fade has its own identifier, and the replaced fade is cancelled
+        let (
+            WebReply::Fade { id: kept_id, .. },
+            WebReply::Fade {
+                id: replaced_id, ..
+            },
+        ) = (kept_reply.await.unwrap(), replaced_reply.await.unwrap())
+        else {
+            panic!("Expected fade replies.");
+        };
+        assert_ne!(kept_id, replaced_id);
+        loop {
+            if let Event::FadeCancelled { id, channel } = events.recv().await.unwrap() {
+                assert_eq!((id, channel), (replaced_id, 1));
+                break;
+            }
+        }
     }
 }
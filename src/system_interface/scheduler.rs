@@ -0,0 +1,163 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the scheduler of the system interface: one queue,
+//! ordered by deadline, for every timer of the interface (the watchdogs, the
+//! waiting cues, the timelines, the fans, and the channel history). The
+//! interface waits on the scheduler alone, and runs each timer as it comes
+//! due.
+
+// Import standard library features
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Instant;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import Tokio features
+use tokio::time::sleep_until;
+
+/// An enum to name each timer of the system interface
+///
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Timer {
+    /// a variant for the next ping of the systemd watchdog
+    SystemdWatchdog,
+
+    /// a variant for the safe look of the idle watchdog
+    IdleWatchdog,
+
+    /// a variant for the waiting cue of a cue list, with the name of the list
+    CueList(String),
+
+    /// a variant for the next action of the playing timelines
+    Timelines,
+
+    /// a variant for the next waiting fade of the fans
+    Fans,
+
+    /// a variant for the next sample of the channel history
+    ChannelHistory,
+}
+
+/// A structure to hold the deadline of each timer, in order
+///
+#[derive(Default)]
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<(Instant, Timer)>>, // the deadlines, earliest first (including replaced deadlines)
+    deadlines: FnvHashMap<Timer, Instant>,        // the current deadline of each timer
+}
+
+// Implement key features of the scheduler
+impl Scheduler {
+    /// A function to create a new scheduler without any timers
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to set the deadline of a timer, replacing any earlier
+    /// deadline, or to cancel the timer if there is no deadline
+    ///
+    pub fn set(&mut self, timer: Timer, deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) if self.deadlines.get(&timer) != Some(&deadline) => {
+                self.deadlines.insert(timer.clone(), deadline);
+                self.queue.push(Reverse((deadline, timer)));
+            }
+            Some(_) => (), // the deadline is unchanged
+            None => {
+                self.deadlines.remove(&timer);
+            }
+        }
+    }
+
+    /// A method to wait until the earliest timer is due and return it. If
+    /// there are no timers, this waits forever.
+    ///
+    pub async fn wait(&mut self) -> Timer {
+        loop {
+            let Some(deadline) = self.next_deadline() else {
+                return std::future::pending().await;
+            };
+            sleep_until(deadline.into()).await;
+            if let Some(timer) = self.take_due(Instant::now()) {
+                return timer;
+            }
+        }
+    }
+
+    /// A helper method to return the earliest current deadline, discarding
+    /// any deadlines that were replaced or cancelled
+    ///
+    fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some(Reverse((deadline, timer))) = self.queue.peek() {
+            if self.deadlines.get(timer) == Some(deadline) {
+                return Some(*deadline);
+            }
+            self.queue.pop();
+        }
+        None
+    }
+
+    /// A helper method to take the earliest timer, if it is due
+    ///
+    fn take_due(&mut self, now: Instant) -> Option<Timer> {
+        if self.next_deadline()? > now {
+            return None;
+        }
+        let Reverse((_, timer)) = self.queue.pop()?;
+        self.deadlines.remove(&timer);
+        Some(timer)
+    }
+}
+
+// Tests of the scheduler module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Test that timers come due in order, with replaced deadlines ignored
+    #[test]
+    fn order_timers() {
+        let start = Instant::now();
+        let at = |secs| Some(start + Duration::from_secs(secs));
+        let mut scheduler = Scheduler::new();
+
+        // Schedule three timers, then move one and cancel another
+        scheduler.set(Timer::Timelines, at(5));
+        scheduler.set(Timer::CueList(String::from("main")), at(2));
+        scheduler.set(Timer::IdleWatchdog, at(3));
+        scheduler.set(Timer::Timelines, at(1));
+        scheduler.set(Timer::IdleWatchdog, None);
+
+        // Only the current deadlines come due, in order
+        assert_eq!(scheduler.take_due(start), None);
+        assert_eq!(
+            scheduler.take_due(start + Duration::from_secs(10)),
+            Some(Timer::Timelines)
+        );
+        assert_eq!(
+            scheduler.take_due(start + Duration::from_secs(10)),
+            Some(Timer::CueList(String::from("main")))
+        );
+        assert_eq!(scheduler.take_due(start + Duration::from_secs(10)), None);
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the script engine, which runs each script from the
+//! configuration in the background. Every action of a script is sent back to
+//! the system interface as a request, so scripts are recorded in the audit
+//! log like any other client.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import Tokio features
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, interval_at, sleep};
+
+// Import tracing features
+use tracing::{debug, info, warn};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A structure to start, stop, and schedule the scripts
+///
+pub struct ScriptEngine {
+    scripts: FnvHashMap<String, Script>, // the available scripts, by name
+    web_send: WebSend,                   // the line to send the requests of each script
+    flags: Arc<Mutex<FnvHashMap<String, bool>>>, // the flags shared by every script
+    running: FnvHashMap<String, JoinHandle<()>>, // the scripts in progress, by name
+    timers: Vec<JoinHandle<()>>,         // the timers of the scripts that run on a schedule
+}
+
+// Implement key features of the script engine
+impl ScriptEngine {
+    /// A function to create a new script engine and start the timer of each
+    /// scheduled script. This function must be called within a tokio
+    /// runtime.
+    ///
+    pub fn new(scripts: FnvHashMap<String, Script>, web_send: WebSend) -> Self {
+        let mut engine = Self {
+            scripts: FnvHashMap::default(),
+            web_send,
+            flags: Arc::new(Mutex::new(FnvHashMap::default())),
+            running: FnvHashMap::default(),
+            timers: Vec::new(),
+        };
+        engine.set_scripts(scripts);
+        engine
+    }
+
+    /// A method to replace the scripts, stopping any scripts in progress and
+    /// restarting the timers. The flags are kept.
+    ///
+    pub fn set_scripts(&mut self, scripts: FnvHashMap<String, Script>) {
+        // Stop everything from the old scripts
+        self.stop_all();
+        for timer in self.timers.drain(..) {
+            timer.abort();
+        }
+
+        // Start a timer for each scheduled script
+        for (name, script) in scripts.iter() {
+            if let Some(every) = script.every.filter(|every| !every.is_zero()) {
+                let name = name.clone();
+                let web_send = self.web_send.clone();
+                self.timers.push(tokio::spawn(async move {
+                    let mut timer = interval_at(Instant::now() + every, every);
+                    loop {
+                        timer.tick().await;
+                        let (reply_to, _) = oneshot::channel();
+                        web_send
+                            .send_as(
+                                Client::script(&name),
+                                reply_to,
+                                Request::RunScript { name: name.clone() },
+                            )
+                            .await;
+                    }
+                }));
+            }
+        }
+        self.scripts = scripts;
+    }
+
+    /// A method to start a script in the background. If the script is
+    /// already running, it starts again from the beginning.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the script does not exist.
+    ///
+    pub fn run(&mut self, name: &str) -> Result<()> {
+        // Try to find the script
+        let steps = self
+            .scripts
+            .get(name)
+            .map(|script| script.steps.clone())
+            .ok_or_else(|| ErrorCode::ScriptNotFound {
+                script: name.to_string(),
+            })?;
+
+        // Forget any scripts that have finished and stop this script
+        self.running.retain(|_, task| !task.is_finished());
+        self.stop(name);
+
+        // Start the script
+        info!("Running script: {}.", name);
+        let runner = ScriptRunner {
+            name: name.to_string(),
+            web_send: self.web_send.clone(),
+            flags: self.flags.clone(),
+        };
+        self.running.insert(
+            name.to_string(),
+            tokio::spawn(async move {
+                match runner.perform(&steps).await {
+                    Ok(()) => debug!("Finished script: {}.", runner.name),
+                    Err(error) => warn!("Stopped script {}: {}", runner.name, error),
+                }
+            }),
+        );
+        Ok(())
+    }
+
+    /// A method to stop a script, if it is running. Returns true if the
+    /// script was running.
+    ///
+    pub fn stop(&mut self, name: &str) -> bool {
+        match self.running.remove(name) {
+            Some(task) => {
+                let is_running = !task.is_finished();
+                task.abort();
+                is_running
+            }
+            None => false,
+        }
+    }
+
+    /// A method to stop every script in progress (the timers keep running)
+    ///
+    pub fn stop_all(&mut self) {
+        for (_, task) in self.running.drain() {
+            task.abort();
+        }
+    }
+}
+
+/// A helper structure to perform the steps of one script
+///
+struct ScriptRunner {
+    name: String,                                // the name of the script
+    web_send: WebSend,                           // the line to send the requests
+    flags: Arc<Mutex<FnvHashMap<String, bool>>>, // the flags shared by every script
+}
+
+// Implement the script runner
+impl ScriptRunner {
+    /// A method to perform each step in order
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if any action fails. The rest of the
+    /// script is skipped.
+    ///
+    async fn perform(&self, steps: &[ScriptStep]) -> Result<()> {
+        for step in steps {
+            match step {
+                // Pause the script
+                ScriptStep::Wait { duration } => sleep(*duration).await,
+
+                // Pause the script until the next multiple of the beats
+                ScriptStep::WaitBeats { beats } => sleep(self.until_beats(*beats).await?).await,
+
+                // Perform the matching steps
+                ScriptStep::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    if self.check(condition).await? {
+                        Box::pin(self.perform(then)).await?;
+                    } else {
+                        Box::pin(self.perform(otherwise)).await?;
+                    }
+                }
+
+                // Perform the steps the number of times (or until stopped)
+                ScriptStep::Repeat { count, steps } => {
+                    let mut remaining = *count;
+                    while remaining.is_none_or(|remaining| remaining > 0) {
+                        Box::pin(self.perform(steps)).await?;
+                        remaining = remaining.map(|remaining| remaining - 1);
+
+                        // Make sure an endless loop yields to other tasks
+                        tokio::task::yield_now().await;
+                    }
+                }
+
+                // Set the flag
+                ScriptStep::SetFlag { name, value } => {
+                    if let Ok(mut flags) = self.flags.lock() {
+                        flags.insert(name.clone(), *value);
+                    }
+                }
+
+                // Send the action to the system interface
+                ScriptStep::Action(action) => {
+                    let reply = self.send(action.to_request()).await?;
+                    if !reply.is_success() {
+                        return Err(anyhow!(
+                            "{} failed: {:?}",
+                            action.to_request().name(),
+                            reply
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A helper method to find the time until the next multiple of some
+    /// beats on the beat clock
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the number of beats is invalid or
+    /// the tempo cannot be read.
+    ///
+    async fn until_beats(&self, beats: f64) -> Result<Duration> {
+        if !(beats > 0.0 && beats <= MAX_BEATS) {
+            return Err(anyhow!(
+                "Beats must be more than zero and at most {}.",
+                MAX_BEATS
+            ));
+        }
+        match self.send(Request::GetTempo).await? {
+            WebReply::Tempo { bpm, beat, .. } => {
+                let next = ((beat / beats).floor() + 1.0) * beats;
+                Ok(Duration::from_secs_f64((next - beat) * 60.0 / bpm))
+            }
+            reply => Err(anyhow!("Unable to read the tempo: {:?}", reply)),
+        }
+    }
+
+    /// A helper method to check a condition
+    ///
+    async fn check(&self, condition: &Condition) -> Result<bool> {
+        match condition {
+            // Compare the current value of the channel
+            Condition::Channel {
+                channel,
+                above,
+                below,
+            } => {
+                if !(1..=DMX_MAX).contains(channel) {
+                    return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+                }
+                match self.send(Request::GetUniverse).await? {
+                    WebReply::Universe { universe, .. } => Ok(Condition::is_channel_met(
+                        universe.get(*channel),
+                        *above,
+                        *below,
+                    )),
+                    reply => Err(anyhow!("Unable to read the universe: {:?}", reply)),
+                }
+            }
+
+            // Look up the flag (unset flags are false)
+            Condition::Flag { name, value } => {
+                let flags = self
+                    .flags
+                    .lock()
+                    .map_err(|_| anyhow!("The flags are unavailable."))?;
+                Ok(flags.get(name).copied().unwrap_or(false) == *value)
+            }
+        }
+    }
+
+    /// A helper method to send a request on behalf of the script and wait
+    /// for the reply
+    ///
+    async fn send(&self, request: Request) -> Result<WebReply> {
+        let (reply_to, reply) = oneshot::channel();
+        self.web_send
+            .send_as(Client::script(&self.name), reply_to, request)
+            .await;
+        reply
+            .await
+            .map_err(|_| anyhow!("The system interface has closed."))
+    }
+}
+
+// Tests of the script engine module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a script sends its actions in order and follows conditions
+    #[tokio::test]
+    async fn run_script() {
+        // Create a script that checks a flag and a channel
+        let script: Script = serde_yaml::from_str(
+            "
+            steps:
+              - {type: setFlag, name: armed}
+              - type: if
+                condition: {type: flag, name: armed}
+                then:
+                  - {type: playFade, fade: {channel: 1, value: 10}}
+              - type: if
+                condition: {type: channel, channel: 1, above: 5}
+                then:
+                  - {type: recallScene, name: warm}
+                else:
+                  - {type: allStop}
+            ",
+        )
+        .unwrap();
+        let mut scripts = FnvHashMap::default();
+        scripts.insert("sensor".to_string(), script);
+        let (web_send, mut web_receive) = WebSend::new();
+        let mut engine = ScriptEngine::new(scripts, web_send);
+
+        // Unknown scripts are rejected
+        assert!(engine.run("missing").is_err());
+
+        // Reply to each request from the script
+        engine.run("sensor").unwrap();
+        let mut names = Vec::new();
+        let mut universe = Universe::new();
+        universe.set(1, 10);
+        for _ in 0..3 {
+            let request = web_receive.recv().await.unwrap();
+            assert_eq!(request.client.identity, "script");
+            names.push(request.request.name());
+            let reply = match request.request {
+                Request::GetUniverse => WebReply::Universe {
+                    is_valid: true,
+                    universe: universe.clone(),
+                    version: 0,
+                    labels: Labels::default(),
+                },
+                _ => WebReply::success(),
+            };
+            request.reply_to.send(reply).unwrap();
+        }
+        assert_eq!(names, vec!["playFade", "getUniverse", "recallScene"]);
+    }
+}
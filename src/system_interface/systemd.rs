@@ -0,0 +1,83 @@
+// Copyright (c) 2026 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to report the program status to systemd. This module does nothing
+//! unless the program was started by systemd with a notification socket (for
+//! example, with Type=notify in the service file).
+
+// Import standard library features
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+// Import tracing features
+use tracing::warn;
+
+/// A structure to send status notifications to systemd
+///
+pub struct Systemd;
+
+// Implement the systemd notifications
+impl Systemd {
+    /// A function to send a notification to systemd (such as "READY=1").
+    /// This function fails silently if there is no notification socket.
+    ///
+    pub fn notify(state: &str) {
+        // Check for the notification socket
+        let Some(path) = env::var("NOTIFY_SOCKET").ok() else {
+            return;
+        };
+
+        // Convert the path to a socket address (a leading @ is an abstract socket)
+        let address = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+            None => SocketAddr::from_pathname(&path),
+        };
+
+        // Try to send the notification
+        let result = address
+            .and_then(|address| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address));
+
+        // Warn if the notification failed
+        if let Err(error) = result {
+            warn!("Unable to notify systemd: {}.", error);
+        }
+    }
+
+    /// A function to return the interval for pinging the systemd watchdog, if
+    /// the watchdog is enabled for this process. The interval is half of the
+    /// watchdog timeout, as systemd recommends.
+    ///
+    pub fn watchdog_interval() -> Option<Duration> {
+        // Make sure the watchdog is intended for this process
+        if let Ok(pid) = env::var("WATCHDOG_PID")
+            && pid.parse::<u32>().ok()? != std::process::id()
+        {
+            return None;
+        }
+
+        // Read the watchdog timeout
+        let timeout = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+        if timeout == 0 {
+            return None;
+        }
+
+        // Return half the timeout
+        Some(Duration::from_micros(timeout / 2))
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the player of the timelines. Each timeline plays
+//! independently, running its actions as their times pass, and can be
+//! paused, stopped, moved to a position, or looped.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import Tokio features
+use tokio::sync::oneshot;
+
+/// A structure to hold every timeline and the transport of each one that is
+/// playing or paused
+///
+pub struct TimelinePlayer {
+    timelines: FnvHashMap<String, Timeline>, // the timelines with their entries in order, by name
+    playbacks: FnvHashMap<String, Playback>, // the transport of each timeline that is not stopped, by name
+    web_send: WebSend, // the line to send the actions back to the system interface
+}
+
+/// A helper structure to hold the transport of a timeline
+///
+struct Playback {
+    offset: Duration,       // the position of the timeline at the start time
+    start: Option<Instant>, // the time the timeline started playing (None while paused)
+    next: usize,            // the index of the next entry to run
+}
+
+// Implement key features of the playback
+impl Playback {
+    /// A method to return the position of the timeline
+    ///
+    fn position(&self, now: Instant) -> Duration {
+        match self.start {
+            Some(start) => self.offset + now.saturating_duration_since(start),
+            None => self.offset,
+        }
+    }
+}
+
+// Implement key features of the timeline player
+impl TimelinePlayer {
+    /// A function to create the player with every timeline stopped
+    ///
+    pub fn new(timelines: FnvHashMap<String, Timeline>, web_send: WebSend) -> Self {
+        let mut player = Self {
+            timelines: FnvHashMap::default(),
+            playbacks: FnvHashMap::default(),
+            web_send,
+        };
+        player.set_timelines(timelines);
+        player
+    }
+
+    /// A method to replace the timelines. Any timeline that changed (or was
+    /// removed) is stopped.
+    ///
+    pub fn set_timelines(&mut self, timelines: FnvHashMap<String, Timeline>) {
+        self.timelines = timelines
+            .into_iter()
+            .map(|(name, mut timeline)| {
+                timeline.entries.sort_by_key(|entry| entry.at);
+                (name, timeline)
+            })
+            .collect();
+        self.playbacks
+            .retain(|name, _| self.timelines.contains_key(name));
+    }
+
+    /// A method to stop every timeline
+    ///
+    pub fn stop_all(&mut self) {
+        self.playbacks.clear();
+    }
+
+    /// A method to change the transport of a timeline
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the timeline does not exist.
+    ///
+    pub fn control(
+        &mut self,
+        name: &str,
+        control: TimelineControl,
+        now: Instant,
+    ) -> Result<(), ErrorCode> {
+        let timeline = self
+            .timelines
+            .get_mut(name)
+            .ok_or_else(|| ErrorCode::TimelineNotFound {
+                timeline: name.to_string(),
+            })?;
+        match control {
+            // Resume from the current position, or start from the beginning
+            TimelineControl::Play => {
+                let playback = self.playbacks.entry(name.to_string()).or_insert(Playback {
+                    offset: Duration::ZERO,
+                    start: None,
+                    next: 0,
+                });
+                if playback.start.is_none() {
+                    playback.start = Some(now);
+                }
+            }
+
+            // Hold the current position
+            TimelineControl::Pause => {
+                if let Some(playback) = self.playbacks.get_mut(name) {
+                    playback.offset = playback.position(now);
+                    playback.start = None;
+                }
+            }
+
+            // Return to the beginning
+            TimelineControl::Stop => {
+                self.playbacks.remove(name);
+            }
+
+            // Move to the position, skipping the entries before it
+            TimelineControl::Seek { position } => {
+                let position = position.min(timeline.length());
+                let playback = self.playbacks.entry(name.to_string()).or_insert(Playback {
+                    offset: Duration::ZERO,
+                    start: None,
+                    next: 0,
+                });
+                playback.offset = position;
+                playback.start = playback.start.map(|_| now);
+                playback.next = timeline
+                    .entries
+                    .partition_point(|entry| entry.at < position);
+            }
+
+            // Change the loop (until the configuration is reloaded)
+            TimelineControl::SetLoop { is_looping } => timeline.is_looping = is_looping,
+        }
+        Ok(())
+    }
+
+    /// A method to describe the transport of a timeline
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the timeline does not exist.
+    ///
+    pub fn status(&self, name: &str, now: Instant) -> Result<TimelineStatus, ErrorCode> {
+        let timeline = self
+            .timelines
+            .get(name)
+            .ok_or_else(|| ErrorCode::TimelineNotFound {
+                timeline: name.to_string(),
+            })?;
+        let playback = self.playbacks.get(name);
+        Ok(TimelineStatus {
+            is_playing: playback.is_some_and(|playback| playback.start.is_some()),
+            is_paused: playback.is_some_and(|playback| playback.start.is_none()),
+            position: playback.map_or(Duration::ZERO, |playback| {
+                playback.position(now).min(timeline.length())
+            }),
+            length: timeline.length(),
+            is_looping: timeline.is_looping,
+        })
+    }
+
+    /// A method to return the time of the next entry or the end of any
+    /// playing timeline, if any are playing
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        self.playbacks
+            .iter()
+            .filter_map(|(name, playback)| {
+                let start = playback.start?;
+                let timeline = self.timelines.get(name)?;
+                let end = timeline.length();
+                let at = timeline
+                    .entries
+                    .get(playback.next)
+                    .map_or(end, |entry| entry.at.min(end));
+                Some(start + at.saturating_sub(playback.offset))
+            })
+            .min()
+    }
+
+    /// A method to send actions back to the system interface in the
+    /// background, so that they are checked and recorded like any other
+    /// request
+    ///
+    pub fn send(&self, actions: Vec<(String, TriggerAction)>) {
+        let web_send = self.web_send.clone();
+        tokio::spawn(async move {
+            for (name, action) in actions {
+                let (reply_to, _) = oneshot::channel();
+                web_send
+                    .send_as(Client::timeline(&name), reply_to, action.to_request())
+                    .await;
+            }
+        });
+    }
+
+    /// A method to take the actions of the playing timelines that are due,
+    /// with the name of each timeline, looping or stopping each timeline
+    /// that has reached its end
+    ///
+    pub fn due(&mut self, now: Instant) -> Vec<(String, TriggerAction)> {
+        let mut actions = Vec::new();
+        let timelines = &self.timelines;
+        self.playbacks.retain(|name, playback| {
+            let Some(timeline) = timelines.get(name) else {
+                return false;
+            };
+            if playback.start.is_none() {
+                return true; // the timeline is paused
+            }
+            let length = timeline.length();
+            loop {
+                // Run each entry that has passed
+                let position = playback.position(now);
+                if let Some(entry) = timeline.entries.get(playback.next)
+                    && entry.at <= position.min(length)
+                {
+                    actions.push((name.clone(), entry.action.clone()));
+                    playback.next += 1;
+                    continue;
+                }
+
+                // Loop or stop the timeline at the end
+                if position < length {
+                    return true;
+                }
+                if !timeline.is_looping || length.is_zero() {
+                    return false;
+                }
+                if let Some(start) = playback.start.as_mut() {
+                    *start += length.saturating_sub(playback.offset);
+                }
+                playback.offset = Duration::ZERO;
+                playback.next = 0;
+            }
+        });
+        actions
+    }
+}
+
+// Tests of the timeline player module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test the transport of a looping timeline
+    #[test]
+    fn play_timeline() {
+        // Create a timeline with two entries and a ten second loop
+        let timeline: Timeline = serde_yaml::from_str(
+            "length: { secs: 10, nanos: 0 }
+isLooping: true
+entries:
+  - { at: { secs: 5, nanos: 0 }, action: { type: undo } }
+  - { at: { secs: 0, nanos: 0 }, action: { type: tap } }
+",
+        )
+        .unwrap();
+        let mut timelines = FnvHashMap::default();
+        timelines.insert("show".to_string(), timeline);
+        let (web_send, _) = WebSend::new();
+        let mut player = TimelinePlayer::new(timelines, web_send);
+        let show = |action| vec![("show".to_string(), action)];
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Playing runs the entries in order, and looping returns to the start
+        player
+            .control("show", TimelineControl::Play, start)
+            .unwrap();
+        assert_eq!(player.due(start), show(TriggerAction::Tap));
+        assert_eq!(player.deadline(), Some(at(5)));
+        assert_eq!(player.due(at(5)), show(TriggerAction::Undo));
+        assert_eq!(player.deadline(), Some(at(10)));
+        assert_eq!(player.due(at(11)), show(TriggerAction::Tap));
+        assert_eq!(
+            player.status("show", at(11)).unwrap().position,
+            Duration::from_secs(1)
+        );
+
+        // Pausing holds the position, and seeking skips the earlier entries
+        player
+            .control("show", TimelineControl::Pause, at(12))
+            .unwrap();
+        assert_eq!(player.deadline(), None);
+        let seek = TimelineControl::Seek {
+            position: Duration::from_secs(6),
+        };
+        player.control("show", seek, at(20)).unwrap();
+        player
+            .control("show", TimelineControl::Play, at(20))
+            .unwrap();
+        assert_eq!(player.deadline(), Some(at(24)));
+
+        // Without the loop, the timeline stops at the end
+        let stop_loop = TimelineControl::SetLoop { is_looping: false };
+        player.control("show", stop_loop, at(20)).unwrap();
+        assert_eq!(player.due(at(24)), vec![]);
+        assert!(!player.status("show", at(24)).unwrap().is_playing);
+        assert!(matches!(
+            player.control("other", TimelineControl::Play, start),
+            Err(ErrorCode::TimelineNotFound { .. })
+        ));
+    }
+}
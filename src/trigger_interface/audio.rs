@@ -0,0 +1,388 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a listener for a raw audio stream (such as a pipe
+//! from a capture device). The stream is split into frequency bands, the
+//! energy of each band scales the output of its channels, and each beat in
+//! a band fires any audio trigger for the band.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::TriggerSend;
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+// Import standard library features
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+
+// Import tracing features
+use tracing::{error, info, warn};
+
+// Define the time to wait before reopening the stream
+const RETRY_TIME: Duration = Duration::from_secs(5);
+
+// Define the analysis constants
+const WINDOWS_PER_SECOND: u32 = 50; // the number of times each band is measured per second
+const PEAK_DECAY: f64 = 0.995; // the share of the loudest energy kept after each window
+const AVERAGE_SHARE: f64 = 0.2; // the share of each window in the average energy
+const NOISE_FLOOR: f64 = 0.01; // the quietest energy that is not silence, as a share of full scale
+const BEAT_WINDOWS: u32 = 10; // the fewest windows between beats in a band
+
+/// A structure to listen for audio and follow each band
+///
+pub struct AudioListener {
+    trigger_send: TriggerSend,    // the line to fire triggers
+    dmx_interface: DmxController, // the controller to scale the channels of each band
+    config: AudioConfig,          // the stream and the bands to follow
+}
+
+// Implement key features of the audio listener
+impl AudioListener {
+    /// A function to create a new audio listener
+    ///
+    pub fn new(
+        trigger_send: TriggerSend,
+        dmx_interface: DmxController,
+        config: AudioConfig,
+    ) -> Self {
+        Self {
+            trigger_send,
+            dmx_interface,
+            config,
+        }
+    }
+
+    /// A method to listen to the stream until the program closes. If the
+    /// stream closes, the channels return to full and the stream is
+    /// reopened once it reappears.
+    ///
+    pub async fn run(self) {
+        // Check the bands against the sample rate
+        let path = self.config.device.clone();
+        let mut analyzer = AudioAnalyzer::new(&self.config);
+        if analyzer.bands.is_empty() {
+            error!("No valid audio bands for {}.", path.display());
+            return;
+        }
+
+        loop {
+            // Try to open the stream
+            let mut device = match File::open(&path).await {
+                Ok(device) => device,
+                Err(error) => {
+                    error!("Unable to open audio input {}: {}.", path.display(), error);
+                    sleep(RETRY_TIME).await;
+                    continue;
+                }
+            };
+            info!("Listening for audio on {}.", path.display());
+
+            // Read the samples until the stream closes
+            let source = format!("audio:{}", path.display());
+            let mut buffer = vec![0; 4096];
+            let mut remainder = None;
+            let mut levels = vec![None; analyzer.bands.len()];
+            loop {
+                let length = match device.read(&mut buffer).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(length) => length,
+                };
+
+                // Combine the bytes into samples, keeping any odd byte for the next read
+                let mut samples = Vec::with_capacity(length / 2 + 1);
+                let mut bytes = remainder
+                    .take()
+                    .into_iter()
+                    .chain(buffer[..length].iter().copied());
+                while let Some(low) = bytes.next() {
+                    match bytes.next() {
+                        Some(high) => samples.push(i16::from_le_bytes([low, high])),
+                        None => remainder = Some(low),
+                    }
+                }
+
+                // Follow each band through the samples
+                for measures in analyzer.read(&samples) {
+                    for ((band, measure), level) in
+                        analyzer.bands.iter().zip(measures).zip(levels.iter_mut())
+                    {
+                        // Fire any trigger for a beat
+                        if measure.is_beat {
+                            let trigger_send = self.trigger_send.clone();
+                            let source = source.clone();
+                            let input = TriggerInput::Audio {
+                                band: band.name.clone(),
+                            };
+                            tokio::spawn(async move { trigger_send.fire(&source, input).await });
+                        }
+
+                        // Scale the channels of the band, if the level changed
+                        let new_level = band.level(measure.energy);
+                        if !band.channels.is_empty() && *level != Some(new_level) {
+                            *level = Some(new_level);
+                            self.set_level(band, new_level).await;
+                        }
+                    }
+                }
+            }
+
+            // Return every channel to full and try again
+            error!("Audio input {} closed.", path.display());
+            for band in analyzer
+                .bands
+                .iter()
+                .filter(|band| !band.channels.is_empty())
+            {
+                self.set_level(band, FULL_LEVEL).await;
+            }
+            sleep(RETRY_TIME).await;
+        }
+    }
+
+    /// A helper method to scale the channels of a band, in percent
+    ///
+    async fn set_level(&self, band: &AudioBand, level: u8) {
+        if let Err(error) = self
+            .dmx_interface
+            .set_group_level(
+                &format!("audio:{}", band.name),
+                band.channels.clone(),
+                level,
+            )
+            .await
+        {
+            warn!("Unable to follow audio band {}: {}", band.name, error);
+        }
+    }
+}
+
+/// A struct to describe one band during one window of the stream
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct BandMeasure {
+    energy: f64,   // the energy of the band compared to its recent peak, from zero to one
+    is_beat: bool, // a flag to indicate a beat started in this window
+}
+
+/// A helper structure to split the stream into bands and measure each band
+/// once per window
+///
+struct AudioAnalyzer {
+    bands: Vec<AudioBand>,    // the bands to follow
+    filters: Vec<BandFilter>, // the filter and recent energy of each band
+    window: usize,            // the number of samples in each window
+    count: usize,             // the number of samples read in the current window
+}
+
+// Implement key features of the audio analyzer
+impl AudioAnalyzer {
+    /// A function to create a new analyzer for the bands, skipping any band
+    /// outside the range of the sample rate
+    ///
+    fn new(config: &AudioConfig) -> Self {
+        // Create a filter for each valid band
+        let sample_rate = config.sample_rate.max(1) as f64;
+        let mut bands = Vec::new();
+        let mut filters = Vec::new();
+        for band in config.bands.iter() {
+            if band.low <= 0.0 || band.high <= band.low || band.high >= sample_rate / 2.0 {
+                error!(
+                    "Audio band {} must be between 0 and {} Hz.",
+                    band.name,
+                    sample_rate / 2.0
+                );
+                continue;
+            }
+            bands.push(band.clone());
+            filters.push(BandFilter::new(band, sample_rate));
+        }
+
+        // Measure each band many times per second
+        Self {
+            bands,
+            filters,
+            window: (config.sample_rate / WINDOWS_PER_SECOND).max(1) as usize,
+            count: 0,
+        }
+    }
+
+    /// A method to read the next samples, returning the measure of every
+    /// band for each window that was completed
+    ///
+    fn read(&mut self, samples: &[i16]) -> Vec<Vec<BandMeasure>> {
+        let mut measures = Vec::new();
+        for sample in samples.iter() {
+            // Filter the sample into every band
+            let sample = *sample as f64 / i16::MAX as f64;
+            for filter in self.filters.iter_mut() {
+                filter.read(sample);
+            }
+
+            // Measure every band at the end of the window
+            self.count += 1;
+            if self.count >= self.window {
+                let window = self.count as f64;
+                measures.push(
+                    self.filters
+                        .iter_mut()
+                        .zip(self.bands.iter())
+                        .map(|(filter, band)| filter.measure(window, band.threshold))
+                        .collect(),
+                );
+                self.count = 0;
+            }
+        }
+        measures
+    }
+}
+
+/// A helper structure to hold the band-pass filter of one band and its
+/// recent energy
+///
+struct BandFilter {
+    coefficients: [f64; 5], // the filter coefficients (b0, b1, b2, a1, a2), divided by a0
+    inputs: [f64; 2],       // the last two samples
+    outputs: [f64; 2],      // the last two filtered samples
+    sum: f64,               // the sum of the squares of the filtered samples in this window
+    peak: f64,              // the loudest recent energy
+    average: f64,           // the average recent energy
+    since_beat: u32,        // the number of windows since the last beat
+}
+
+// Implement key features of the band filter
+impl BandFilter {
+    /// A function to create a band-pass filter around the center of the
+    /// band, with unity gain at the center
+    ///
+    fn new(band: &AudioBand, sample_rate: f64) -> Self {
+        let center = (band.low * band.high).sqrt();
+        let quality = center / (band.high - band.low);
+        let angle = TAU * center / sample_rate;
+        let alpha = angle.sin() / (2.0 * quality);
+        let scale = 1.0 + alpha;
+        Self {
+            coefficients: [
+                alpha / scale,
+                0.0,
+                -alpha / scale,
+                -2.0 * angle.cos() / scale,
+                (1.0 - alpha) / scale,
+            ],
+            inputs: [0.0; 2],
+            outputs: [0.0; 2],
+            sum: 0.0,
+            peak: 0.0,
+            average: 0.0,
+            since_beat: BEAT_WINDOWS,
+        }
+    }
+
+    /// A method to filter the next sample
+    ///
+    fn read(&mut self, sample: f64) {
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        let output = b0 * sample + b1 * self.inputs[0] + b2 * self.inputs[1]
+            - a1 * self.outputs[0]
+            - a2 * self.outputs[1];
+        self.inputs = [sample, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        self.sum += output * output;
+    }
+
+    /// A method to measure the band at the end of a window. The energy is
+    /// compared to the recent peak, so the levels follow the music at any
+    /// volume, and a beat is a jump well above the recent average.
+    ///
+    fn measure(&mut self, window: f64, threshold: f64) -> BandMeasure {
+        // Find the energy of the window
+        let energy = (self.sum / window).sqrt();
+        self.sum = 0.0;
+        self.peak = energy.max(self.peak * PEAK_DECAY);
+
+        // Look for a beat, then include the window in the average
+        self.since_beat = self.since_beat.saturating_add(1);
+        let is_beat = energy > NOISE_FLOOR
+            && energy > self.average * threshold
+            && self.since_beat >= BEAT_WINDOWS;
+        if is_beat {
+            self.since_beat = 0;
+        }
+        self.average += (energy - self.average) * AVERAGE_SHARE;
+
+        // Compare the energy to the peak, treating near silence as silence
+        BandMeasure {
+            energy: if self.peak > NOISE_FLOOR {
+                energy / self.peak
+            } else {
+                0.0
+            },
+            is_beat,
+        }
+    }
+}
+
+// Tests of the audio module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a bass tone fills the bass band and beats once
+    #[test]
+    fn analyze_bands() {
+        // Create a bass band and a treble band
+        let config: AudioConfig = serde_yaml::from_str(
+            "device: /tmp/audio\nsampleRate: 8000\nbands:\n  - {name: bass, low: 40, high: 160, channels: [1], floor: 20}\n  - {name: treble, low: 2000, high: 3500}\n  - {name: invalid, low: 3000, high: 5000}\n",
+        )
+        .unwrap();
+        let mut analyzer = AudioAnalyzer::new(&config);
+        assert_eq!(analyzer.bands.len(), 2);
+
+        // Play a second of silence, then a second of an 80 Hz tone
+        let mut samples = vec![0; 8000];
+        samples.extend(
+            (0..8000).map(|index| ((TAU * 80.0 * index as f64 / 8000.0).sin() * 16_000.0) as i16),
+        );
+        let measures = analyzer.read(&samples);
+        assert_eq!(measures.len(), 100);
+
+        // The bass beats at the start of the tone and holds near its peak
+        let beats: Vec<usize> = measures
+            .iter()
+            .enumerate()
+            .filter(|(_, measure)| measure[0].is_beat)
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(beats, vec![50]);
+        let last = measures.last().unwrap();
+        assert!(last[0].energy > 0.9);
+        assert!(last[1].energy < 0.1);
+        assert!(measures.iter().all(|measure| !measure[1].is_beat));
+
+        // The level of the channels starts from the floor
+        assert_eq!(analyzer.bands[0].level(0.0), 20);
+        assert_eq!(analyzer.bands[0].level(1.0), FULL_LEVEL);
+    }
+}
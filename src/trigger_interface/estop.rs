@@ -0,0 +1,270 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the hardwired emergency stop, which reads a GPIO
+//! pin or a control line of a serial port and holds the rig at its
+//! emergency look while the input is engaged. The input is read locally,
+//! so it works even if the network is down. An input that cannot be read
+//! (such as an unplugged serial adapter) is treated as engaged.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::TriggerSend;
+#[cfg(feature = "gpio")]
+use super::gpio;
+
+// Import the core library features
+use vulcan_core::SerialLine;
+
+// Import standard library features
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Import Tokio features
+use tokio::time::{MissedTickBehavior, interval};
+
+// Import tracing features
+use tracing::{debug, error, info};
+
+// Define the emergency stop constants
+const POLL_TIME: Duration = Duration::from_millis(10); // the time between reads of the input
+const REOPEN_TIME: Duration = Duration::from_secs(1); // the time between attempts to reopen a serial port
+const SOURCE: &str = "emergencyStop"; // the source of the requests, for the audit log
+
+/// A structure to read the emergency stop input and engage or release the
+/// emergency stop
+///
+pub struct EmergencyStop {
+    trigger_send: TriggerSend,   // the line to send the requests
+    config: EmergencyStopConfig, // the settings of the input
+}
+
+// Implement key features of the emergency stop
+impl EmergencyStop {
+    /// A function to create a new emergency stop
+    ///
+    pub fn new(trigger_send: TriggerSend, config: EmergencyStopConfig) -> Self {
+        Self {
+            trigger_send,
+            config,
+        }
+    }
+
+    /// A method to read the input until the program closes. If the input is
+    /// not configured correctly, this logs an error and returns.
+    ///
+    pub async fn run(self) {
+        // Prepare the input
+        let Some(mut input) = self.open().await else {
+            return;
+        };
+
+        // Read the input at a steady interval
+        let debounce = Duration::from_millis(self.config.debounce);
+        let mut state = Debounce::default();
+        let mut ticker = interval(POLL_TIME);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            let is_engaged = input
+                .read(now)
+                .await
+                .is_none_or(|is_high| is_high != self.config.active_low);
+            if let Some(is_engaged) = state.update(is_engaged, now, debounce) {
+                self.trigger_send
+                    .send(SOURCE, Request::EmergencyStop { is_engaged })
+                    .await;
+            }
+        }
+    }
+
+    /// A helper method to prepare the input from the settings
+    ///
+    async fn open(&self) -> Option<Input> {
+        match (self.config.gpio, self.config.serial.clone()) {
+            // Prepare the GPIO pin
+            #[cfg(feature = "gpio")]
+            (Some(pin), None) => {
+                match gpio::open_pin(pin).await {
+                    Ok(()) => info!("Listening for the emergency stop on GPIO pin {}.", pin),
+                    Err(error) => error!("Unable to open GPIO pin {}: {}.", pin, error),
+                }
+                Some(Input::Gpio(pin))
+            }
+            #[cfg(not(feature = "gpio"))]
+            (Some(_), None) => {
+                error!(
+                    "A GPIO emergency stop requires Vulcan to be compiled with the gpio feature."
+                );
+                None
+            }
+
+            // Prepare the serial port (which is opened on the first read)
+            (None, Some(path)) => {
+                info!("Listening for the emergency stop on {}.", path.display());
+                Some(Input::Serial(SerialInput {
+                    path,
+                    line: self.config.line,
+                    port: None,
+                    next_open: Instant::now(),
+                    is_failing: false,
+                }))
+            }
+
+            // Otherwise, the input is not clear
+            _ => {
+                error!("The emergency stop needs either a gpio pin or a serial port.");
+                None
+            }
+        }
+    }
+}
+
+/// An enum to hold the input of the emergency stop
+///
+enum Input {
+    /// a GPIO pin
+    #[cfg(feature = "gpio")]
+    Gpio(u32),
+
+    /// a control line of a serial port
+    Serial(SerialInput),
+}
+
+// Implement key features of the input
+impl Input {
+    /// A method to read the current level of the input, returning None if
+    /// it cannot be read
+    ///
+    async fn read(&mut self, now: Instant) -> Option<bool> {
+        match self {
+            #[cfg(feature = "gpio")]
+            Input::Gpio(pin) => gpio::read_pin(*pin).await.ok(),
+            Input::Serial(serial) => serial.read(now),
+        }
+    }
+}
+
+/// A helper structure to hold a serial port that may come and go
+///
+struct SerialInput {
+    path: PathBuf,            // the path of the serial port
+    line: ControlLine,        // the control line to read
+    port: Option<SerialLine>, // the open serial port, if any
+    next_open: Instant,       // the time of the next attempt to open the port
+    is_failing: bool,         // a flag to indicate the failure was already logged
+}
+
+// Implement key features of the serial input
+impl SerialInput {
+    /// A method to read the control line, opening the serial port if
+    /// needed. Returns None if the port is not available.
+    ///
+    fn read(&mut self, now: Instant) -> Option<bool> {
+        // Try to open the port, if it isn't open
+        if self.port.is_none() && now >= self.next_open {
+            self.next_open = now + REOPEN_TIME;
+            match SerialLine::open(&self.path, self.line) {
+                Ok(port) => {
+                    info!("Opened the emergency stop at {}.", self.path.display());
+                    self.port = Some(port);
+                    self.is_failing = false;
+                }
+                Err(error) if !self.is_failing => {
+                    error!(
+                        "Unable to open the emergency stop at {}: {}.",
+                        self.path.display(),
+                        error
+                    );
+                    self.is_failing = true;
+                }
+                Err(error) => debug!("Still unable to open the emergency stop: {}.", error),
+            }
+        }
+
+        // Read the line, closing the port if it fails
+        match self.port.as_mut()?.read() {
+            Ok(is_high) => Some(is_high),
+            Err(error) => {
+                error!(
+                    "Unable to read the emergency stop at {}: {}.",
+                    self.path.display(),
+                    error
+                );
+                self.port = None;
+                None
+            }
+        }
+    }
+}
+
+/// A helper structure to track the debounced state of the input
+///
+#[derive(Default)]
+struct Debounce {
+    is_engaged: bool,            // the debounced state of the input
+    changed_at: Option<Instant>, // the time the raw state first differed from the debounced state, if it does
+}
+
+// Implement key features of the debounce
+impl Debounce {
+    /// A method to update the state with the current raw state, returning
+    /// the new state once a change has held steady for the debounce time
+    ///
+    fn update(&mut self, is_engaged: bool, now: Instant, debounce: Duration) -> Option<bool> {
+        if is_engaged == self.is_engaged {
+            self.changed_at = None;
+            return None;
+        }
+        let changed_at = *self.changed_at.get_or_insert(now);
+        if now.duration_since(changed_at) < debounce {
+            return None;
+        }
+        self.is_engaged = is_engaged;
+        self.changed_at = None;
+        Some(is_engaged)
+    }
+}
+
+// Tests of the emergency stop module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the input must hold steady to engage or release
+    #[test]
+    fn debounce_input() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let debounce = Duration::from_millis(20);
+        let mut state = Debounce::default();
+
+        // A short glitch is ignored
+        assert_eq!(state.update(true, at(0), debounce), None);
+        assert_eq!(state.update(false, at(10), debounce), None);
+        assert_eq!(state.update(true, at(30), debounce), None);
+
+        // A steady press engages once, and a steady release releases
+        assert_eq!(state.update(true, at(50), debounce), Some(true));
+        assert_eq!(state.update(true, at(60), debounce), None);
+        assert_eq!(state.update(false, at(100), debounce), None);
+        assert_eq!(state.update(false, at(120), debounce), Some(false));
+    }
+}
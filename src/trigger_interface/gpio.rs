@@ -0,0 +1,252 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a listener for buttons and contact closures on
+//! GPIO pins, using the sysfs interface at /sys/class/gpio. Each pin is
+//! debounced and can fire separate triggers for short and long presses.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::TriggerSend;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Import Tokio features
+use tokio::fs;
+use tokio::time::{MissedTickBehavior, interval};
+
+// Import tracing features
+use tracing::{error, info};
+
+// Define the GPIO constants
+const GPIO_ROOT: &str = "/sys/class/gpio"; // the location of the sysfs interface
+const POLL_TIME: Duration = Duration::from_millis(10); // the time between reads of each pin
+
+/// A structure to listen for presses on GPIO pins and fire the matching
+/// triggers
+///
+pub struct GpioListener {
+    trigger_send: TriggerSend, // the line to fire triggers
+    config: GpioConfig,        // the settings of the inputs
+}
+
+// Implement key features of the GPIO listener
+impl GpioListener {
+    /// A function to create a new GPIO listener
+    ///
+    pub fn new(trigger_send: TriggerSend, config: GpioConfig) -> Self {
+        Self {
+            trigger_send,
+            config,
+        }
+    }
+
+    /// A method to read the pins until the program closes. Any pin that
+    /// cannot be opened is skipped with an error.
+    ///
+    pub async fn run(self, pins: Vec<u32>) {
+        // Prepare each pin as an input
+        let mut buttons = Vec::new();
+        for pin in pins {
+            match open_pin(pin).await {
+                Ok(()) => {
+                    info!("Listening for GPIO on pin {}.", pin);
+                    buttons.push((pin, Button::default()));
+                }
+                Err(error) => error!("Unable to open GPIO pin {}: {}.", pin, error),
+            }
+        }
+
+        // Read the pins at a steady interval
+        let debounce = Duration::from_millis(self.config.debounce);
+        let long_press = Duration::from_millis(self.config.long_press);
+        let mut ticker = interval(POLL_TIME);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        while !buttons.is_empty() {
+            ticker.tick().await;
+            let now = Instant::now();
+            for (pin, button) in buttons.iter_mut() {
+                // Read the current level of the pin
+                let Ok(is_high) = read_pin(*pin).await else {
+                    continue;
+                };
+                let is_pressed = is_high != self.config.active_low;
+
+                // Check for any changes to the button
+                let has_long = self.trigger_send.is_bound(&TriggerInput::Gpio {
+                    pin: *pin,
+                    long_press: true,
+                });
+                if let Some(long_press) =
+                    button.update(is_pressed, now, debounce, has_long.then_some(long_press))
+                {
+                    self.trigger_send
+                        .fire(
+                            &format!("gpio:{}", pin),
+                            TriggerInput::Gpio {
+                                pin: *pin,
+                                long_press,
+                            },
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+/// A function to export a pin through sysfs and set it as an input
+///
+/// # Errors
+///
+/// This function will raise an error if the pin cannot be set as an input.
+///
+pub async fn open_pin(pin: u32) -> std::io::Result<()> {
+    // Export the pin, unless it was already exported
+    let path = format!("{}/gpio{}", GPIO_ROOT, pin);
+    if fs::metadata(&path).await.is_err() {
+        fs::write(format!("{}/export", GPIO_ROOT), pin.to_string()).await?;
+    }
+
+    // Set the pin as an input
+    fs::write(format!("{}/direction", path), "in").await
+}
+
+/// A function to read the current level of a pin, returning true if the
+/// pin is high
+///
+/// # Errors
+///
+/// This function will raise an error if the pin cannot be read.
+///
+pub async fn read_pin(pin: u32) -> std::io::Result<bool> {
+    let value = fs::read_to_string(format!("{}/gpio{}/value", GPIO_ROOT, pin)).await?;
+    Ok(value.trim() == "1")
+}
+
+/// A helper structure to track the debounced state of one button
+///
+#[derive(Default)]
+struct Button {
+    is_pressed: bool,            // the debounced state of the button
+    changed_at: Option<Instant>, // the time the raw state first differed from the debounced state, if it does
+    pressed_at: Option<Instant>, // the time of the current press, if any
+    is_long_fired: bool, // a flag to indicate the long press already fired for the current press
+}
+
+// Implement key features of the button
+impl Button {
+    /// A method to update the button with the current raw state, returning
+    /// Some(false) for a short press and Some(true) for a long press. If there
+    /// is no long press time, short presses fire as soon as the button is
+    /// pressed. Otherwise, short presses fire when a button is released
+    /// before the long press time.
+    ///
+    fn update(
+        &mut self,
+        is_pressed: bool,
+        now: Instant,
+        debounce: Duration,
+        long_press: Option<Duration>,
+    ) -> Option<bool> {
+        // Wait for any change to hold steady before accepting it
+        if is_pressed == self.is_pressed {
+            self.changed_at = None;
+        } else {
+            let changed_at = *self.changed_at.get_or_insert(now);
+            if now.duration_since(changed_at) >= debounce {
+                self.is_pressed = is_pressed;
+                self.changed_at = None;
+
+                // If the button was pressed
+                if is_pressed {
+                    self.pressed_at = Some(now);
+                    self.is_long_fired = false;
+                    if long_press.is_none() {
+                        return Some(false);
+                    }
+
+                // If the button was released before a long press
+                } else if self.pressed_at.take().is_some()
+                    && long_press.is_some()
+                    && !self.is_long_fired
+                {
+                    return Some(false);
+                }
+            }
+        }
+
+        // Check for a long press
+        if let (Some(pressed_at), Some(long_press)) = (self.pressed_at, long_press)
+            && !self.is_long_fired
+            && now.duration_since(pressed_at) >= long_press
+        {
+            self.is_long_fired = true;
+            return Some(true);
+        }
+        None
+    }
+}
+
+// Tests of the GPIO module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that bounces are ignored and that short and long presses are separate
+    #[test]
+    fn debounce_and_long_press() {
+        // Define the timing
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let debounce = Duration::from_millis(50);
+        let long_press = Some(Duration::from_millis(1000));
+
+        // A bounce shorter than the debounce time is ignored
+        let mut button = Button::default();
+        assert_eq!(button.update(true, at(0), debounce, long_press), None);
+        assert_eq!(button.update(false, at(20), debounce, long_press), None);
+        assert!(!button.is_pressed);
+
+        // A short press fires on release
+        assert_eq!(button.update(true, at(100), debounce, long_press), None);
+        assert_eq!(button.update(true, at(150), debounce, long_press), None);
+        assert_eq!(button.update(false, at(300), debounce, long_press), None);
+        assert_eq!(
+            button.update(false, at(350), debounce, long_press),
+            Some(false)
+        );
+
+        // A long press fires once while held, and not again on release
+        assert_eq!(button.update(true, at(400), debounce, long_press), None);
+        assert_eq!(button.update(true, at(450), debounce, long_press), None);
+        assert_eq!(
+            button.update(true, at(1450), debounce, long_press),
+            Some(true)
+        );
+        assert_eq!(button.update(true, at(1500), debounce, long_press), None);
+        assert_eq!(button.update(false, at(1600), debounce, long_press), None);
+        assert_eq!(button.update(false, at(1650), debounce, long_press), None);
+
+        // Without a long press, short presses fire immediately
+        assert_eq!(button.update(true, at(1700), debounce, None), None);
+        assert_eq!(button.update(true, at(1750), debounce, None), Some(false));
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a listener for note on messages from a raw MIDI
+//! device (such as /dev/snd/midiC1D0). Any MIDI clock from the device sets
+//! the tempo of the beat clock.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::TriggerSend;
+
+// Import standard library features
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Import Tokio features
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+
+// Import tracing features
+use tracing::{error, info};
+
+// Define the time to wait before reopening the device
+const RETRY_TIME: Duration = Duration::from_secs(5);
+
+// Define the MIDI constants
+const NOTE_ON: u8 = 0x90; // the status of a note on message, before the channel
+const SYSTEM: u8 = 0xF0; // the first system status, which does not carry a channel
+const CLOCK: u8 = 0xF8; // the timing clock message, sent 24 times per beat
+const START: u8 = 0xFA; // the start message, which restarts the clock on the next pulse
+const PULSES_PER_BEAT: u32 = 24; // the number of clock messages in each beat
+const TEMPO_CHANGE: f64 = 0.5; // the smallest change in tempo that is passed on, in beats per minute
+
+/// A structure to listen for MIDI notes and fire the matching triggers
+///
+pub struct MidiListener {
+    trigger_send: TriggerSend, // the line to fire triggers
+}
+
+// Implement key features of the MIDI listener
+impl MidiListener {
+    /// A function to create a new MIDI listener
+    ///
+    pub fn new(trigger_send: TriggerSend) -> Self {
+        Self { trigger_send }
+    }
+
+    /// A method to listen for notes from the device until the program
+    /// closes. If the device is disconnected, it is reopened once it
+    /// reappears.
+    ///
+    pub async fn run(self, path: &Path) {
+        loop {
+            // Try to open the device
+            let mut device = match File::open(path).await {
+                Ok(device) => device,
+                Err(error) => {
+                    error!("Unable to open MIDI device {}: {}.", path.display(), error);
+                    sleep(RETRY_TIME).await;
+                    continue;
+                }
+            };
+            info!("Listening for MIDI on {}.", path.display());
+
+            // Read each message until the device closes
+            let mut parser = MidiParser::default();
+            let mut clock = MidiClock::default();
+            let mut buffer = [0; 256];
+            loop {
+                let length = match device.read(&mut buffer).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(length) => length,
+                };
+                let now = Instant::now();
+                for byte in buffer[..length].iter() {
+                    // Follow the tempo of any clock
+                    if let Some(bpm) = clock.read(*byte, now) {
+                        self.trigger_send
+                            .send(
+                                &format!("midi:{}", path.display()),
+                                Request::SetTempo {
+                                    tempo: Tempo { bpm },
+                                },
+                            )
+                            .await;
+                    }
+
+                    // Fire the trigger of any note
+                    if let Some((channel, note)) = parser.read(*byte) {
+                        self.trigger_send
+                            .fire(
+                                &format!("midi:{}", path.display()),
+                                TriggerInput::Midi { channel, note },
+                            )
+                            .await;
+                    }
+                }
+            }
+
+            // Note that the device closed and try again
+            error!("MIDI device {} closed.", path.display());
+            sleep(RETRY_TIME).await;
+        }
+    }
+}
+
+/// A helper structure to read note on messages from a stream of MIDI bytes,
+/// including messages that use running status
+///
+#[derive(Default)]
+struct MidiParser {
+    status: Option<u8>, // the current running status, if it is a note on
+    note: Option<u8>,   // the note of the message in progress, if any
+}
+
+// Implement key features of the MIDI parser
+impl MidiParser {
+    /// A method to read the next byte, returning the channel (1-16) and note
+    /// of each complete note on message. Notes with zero velocity (which
+    /// mean note off) are ignored.
+    ///
+    fn read(&mut self, byte: u8) -> Option<(u8, u8)> {
+        // Real-time messages may appear anywhere and are ignored
+        if byte >= 0xF8 {
+            return None;
+
+        // A new status replaces the running status
+        } else if byte >= 0x80 {
+            self.note = None;
+            self.status = if (byte & 0xF0) == NOTE_ON && byte < SYSTEM {
+                Some(byte)
+            } else {
+                None
+            };
+            return None;
+        }
+
+        // Otherwise, read the note and the velocity
+        let status = self.status?;
+        match self.note.take() {
+            None => {
+                self.note = Some(byte);
+                None
+            }
+            Some(note) if byte > 0 => Some(((status & 0x0F) + 1, note)),
+            Some(_) => None,
+        }
+    }
+}
+
+/// A helper structure to measure the tempo of a MIDI clock, one beat at a
+/// time
+///
+#[derive(Default)]
+struct MidiClock {
+    pulses: u32,                 // the number of pulses since the start of the beat
+    beat_start: Option<Instant>, // the time of the first pulse of the beat, if the clock is running
+    bpm: Option<f64>,            // the last tempo that was passed on, if any
+}
+
+// Implement key features of the MIDI clock
+impl MidiClock {
+    /// A method to read the next byte at the given time, returning the new
+    /// tempo at the end of any beat where the tempo changed
+    ///
+    fn read(&mut self, byte: u8, now: Instant) -> Option<f64> {
+        match byte {
+            // Restart the beat on the next pulse
+            START => {
+                self.beat_start = None;
+                None
+            }
+
+            // Count the pulse and measure each full beat
+            CLOCK => {
+                let Some(beat_start) = self.beat_start else {
+                    self.beat_start = Some(now);
+                    self.pulses = 0;
+                    return None;
+                };
+                self.pulses += 1;
+                if self.pulses < PULSES_PER_BEAT {
+                    return None;
+                }
+
+                // Find the tempo of the beat and start the next beat
+                self.pulses = 0;
+                self.beat_start = Some(now);
+                let elapsed = now.saturating_duration_since(beat_start).as_secs_f64();
+                if elapsed <= 0.0 {
+                    return None;
+                }
+                let bpm = (60.0 / elapsed).clamp(MIN_BPM, MAX_BPM);
+                if self
+                    .bpm
+                    .is_some_and(|last| (last - bpm).abs() < TEMPO_CHANGE)
+                {
+                    return None;
+                }
+                self.bpm = Some(bpm);
+                Some(bpm)
+            }
+
+            // Ignore every other byte
+            _ => None,
+        }
+    }
+}
+
+// Tests of the MIDI module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test reading notes with running status
+    #[test]
+    fn parse_midi_notes() {
+        // Read a note on, a running status note off, a control change, and a note on
+        let bytes = [0x92, 60, 100, 60, 0, 0xB0, 7, 127, 0x90, 0xF8, 61, 1];
+        let mut parser = MidiParser::default();
+        let notes: Vec<(u8, u8)> = bytes.iter().filter_map(|byte| parser.read(*byte)).collect();
+        assert_eq!(notes, vec![(3, 60), (1, 61)]);
+    }
+
+    // Test measuring the tempo of a MIDI clock
+    #[test]
+    fn follow_midi_clock() {
+        // Pulse at 100 beats per minute (25 ms per pulse) for two beats
+        let start = Instant::now();
+        let mut clock = MidiClock::default();
+        let mut tempos = Vec::new();
+        clock.read(START, start);
+        for pulse in 0..=48 {
+            if let Some(bpm) = clock.read(CLOCK, start + Duration::from_millis(25 * pulse)) {
+                tempos.push(bpm);
+            }
+        }
+
+        // The tempo is only passed on when it changes
+        assert_eq!(tempos.len(), 1);
+        assert!((tempos[0] - 100.0).abs() < 1e-9);
+    }
+}
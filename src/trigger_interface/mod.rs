@@ -0,0 +1,166 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to listen for inputs from outside the program (other than the
+//! web interface) and pass the actions of any matching triggers to the
+//! system interface.
+
+// Define private submodules
+mod audio;
+mod estop;
+#[cfg(feature = "gpio")]
+mod gpio;
+mod midi;
+mod osc;
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use audio::AudioListener;
+use estop::EmergencyStop;
+#[cfg(feature = "gpio")]
+use gpio::GpioListener;
+use midi::MidiListener;
+use osc::OscListener;
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+// Import Tokio features
+use tokio::sync::oneshot;
+
+// Import tracing features
+use tracing::{debug, warn};
+
+/// A structure to pass the actions of triggers to the system interface
+///
+#[derive(Clone)]
+pub struct TriggerSend {
+    web_send: WebSend,      // the send line to the system interface
+    triggers: Vec<Trigger>, // the triggers and their actions
+}
+
+// Implement key features of the trigger send
+impl TriggerSend {
+    /// A method to check if there is a trigger bound to an input
+    ///
+    #[cfg(feature = "gpio")]
+    pub fn is_bound(&self, input: &TriggerInput) -> bool {
+        self.triggers.iter().any(|trigger| trigger.input == *input)
+    }
+
+    /// A method to perform the action bound to an input, if any. The source
+    /// is recorded in the audit log.
+    ///
+    pub async fn fire(&self, source: &str, input: TriggerInput) {
+        // Look for a trigger bound to the input
+        let Some(request) = find_trigger(&self.triggers, &input) else {
+            debug!("No trigger for {:?}.", input);
+            return;
+        };
+
+        // Send the request
+        self.send(source, request).await;
+    }
+
+    /// A method to send a request from an input directly (without a
+    /// trigger) and note any failure. The source is recorded in the audit
+    /// log.
+    ///
+    pub async fn send(&self, source: &str, request: Request) {
+        let name = request.name();
+        let (reply_to, rx) = oneshot::channel();
+        self.web_send
+            .send_as(Client::trigger(source), reply_to, request)
+            .await;
+        if let Ok(reply) = rx.await
+            && !reply.is_success()
+        {
+            warn!("Request {} from {} failed: {:?}", name, source, reply);
+        }
+    }
+}
+
+/// A structure to start every trigger listener in the configuration
+///
+pub struct TriggerInterface;
+
+// Implement key features of the trigger interface
+impl TriggerInterface {
+    /// A function to start a listener for each input in the configuration.
+    /// Each listener runs in the background until the program closes. The
+    /// audio input changes the output directly through the dmx controller.
+    ///
+    pub fn start(web_send: WebSend, dmx_interface: DmxController, config: &Config) {
+        // Create the line for the listeners to fire triggers
+        let trigger_send = TriggerSend {
+            web_send,
+            triggers: config.triggers.clone(),
+        };
+
+        // Start the OSC listener, if specified
+        if let Some(address) = config.osc_address.clone() {
+            let listener = OscListener::new(trigger_send.clone());
+            tokio::spawn(async move {
+                listener.run(&address).await;
+            });
+        }
+
+        // Start the MIDI listener, if specified
+        if let Some(path) = config.midi_device.clone() {
+            let listener = MidiListener::new(trigger_send.clone());
+            tokio::spawn(async move {
+                listener.run(&path).await;
+            });
+        }
+
+        // Start the emergency stop, if specified
+        if let Some(emergency_stop) = config.emergency_stop.clone() {
+            tokio::spawn(EmergencyStop::new(trigger_send.clone(), emergency_stop).run());
+        }
+
+        // Start the audio listener, if specified
+        if let Some(audio) = config.audio.clone() {
+            let listener = AudioListener::new(trigger_send.clone(), dmx_interface, audio);
+            tokio::spawn(listener.run());
+        }
+
+        // Collect the pins of any GPIO triggers
+        let mut pins: Vec<u32> = config
+            .triggers
+            .iter()
+            .filter_map(|trigger| match trigger.input {
+                TriggerInput::Gpio { pin, .. } => Some(pin),
+                _ => None,
+            })
+            .collect();
+        pins.sort_unstable();
+        pins.dedup();
+
+        // Start the GPIO listener, if there are any pins
+        if !pins.is_empty() {
+            #[cfg(feature = "gpio")]
+            {
+                let listener = GpioListener::new(trigger_send, config.gpio.clone());
+                tokio::spawn(listener.run(pins));
+            }
+            #[cfg(not(feature = "gpio"))]
+            tracing::error!("GPIO triggers require Vulcan to be compiled with the gpio feature.");
+        }
+    }
+}
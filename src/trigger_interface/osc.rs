@@ -0,0 +1,176 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a listener for OSC messages over udp. Only the
+//! address and the first argument of each message are read.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::TriggerSend;
+
+// Import Tokio features
+use tokio::net::UdpSocket;
+
+// Import tracing features
+use tracing::{error, info};
+
+// Define the largest OSC packet that will be read
+const MAX_PACKET: usize = 65_536;
+
+/// A structure to listen for OSC messages and fire the matching triggers
+///
+pub struct OscListener {
+    trigger_send: TriggerSend, // the line to fire triggers
+}
+
+// Implement key features of the OSC listener
+impl OscListener {
+    /// A function to create a new OSC listener
+    ///
+    pub fn new(trigger_send: TriggerSend) -> Self {
+        Self { trigger_send }
+    }
+
+    /// A method to listen for OSC messages at the address until the program
+    /// closes. Messages whose first argument is zero (such as the release
+    /// of a button) are ignored.
+    ///
+    pub async fn run(self, address: &str) {
+        // Try to bind to the address
+        let socket = match UdpSocket::bind(address).await {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("Unable to listen for OSC at {}: {}.", address, error);
+                return;
+            }
+        };
+        info!("Listening for OSC on {}.", address);
+
+        // Read each packet
+        let mut buffer = vec![0; MAX_PACKET];
+        loop {
+            let (length, source) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(error) => {
+                    error!("Unable to read OSC packet: {}.", error);
+                    continue;
+                }
+            };
+
+            // Fire a trigger for every message in the packet
+            let mut messages = Vec::new();
+            parse_packet(&buffer[..length], &mut messages);
+            for (address, argument) in messages {
+                if argument != Some(0.0) {
+                    self.trigger_send
+                        .fire(&format!("osc:{}", source), TriggerInput::Osc { address })
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+/// A helper function to read the address and first numeric argument of every
+/// message in an OSC packet (including any bundles). Invalid messages are
+/// skipped.
+///
+fn parse_packet(packet: &[u8], messages: &mut Vec<(String, Option<f32>)>) {
+    // If the packet is a bundle, read each element (after the time tag)
+    if packet.starts_with(b"#bundle\0") {
+        let mut position = 16;
+        while let Some(size) = read_u32(packet, position) {
+            let start = position + 4;
+            let Some(element) = packet.get(start..start + size as usize) else {
+                return;
+            };
+            parse_packet(element, messages);
+            position = start + size as usize;
+        }
+        return;
+    }
+
+    // Otherwise, read the address of the message
+    let Some((address, position)) = read_string(packet, 0) else {
+        return;
+    };
+    if !address.starts_with('/') {
+        return;
+    }
+
+    // Read the first argument, if it is a number
+    let argument = match read_string(packet, position) {
+        Some((tags, position)) => match tags.as_bytes().get(1) {
+            Some(b'i') => read_u32(packet, position).map(|value| value as i32 as f32),
+            Some(b'f') => read_u32(packet, position).map(f32::from_bits),
+            Some(b'T') => Some(1.0),
+            Some(b'F') => Some(0.0),
+            _ => None,
+        },
+        None => None,
+    };
+    messages.push((address, argument));
+}
+
+/// A helper function to read a null-terminated string (padded to four bytes)
+/// and return it with the position after the padding
+///
+fn read_string(packet: &[u8], position: usize) -> Option<(String, usize)> {
+    let remaining = packet.get(position..)?;
+    let length = remaining.iter().position(|byte| *byte == 0)?;
+    let string = String::from_utf8(remaining[..length].to_vec()).ok()?;
+    Some((string, position + (length / 4 + 1) * 4))
+}
+
+/// A helper function to read a big-endian 32 bit value
+///
+fn read_u32(packet: &[u8], position: usize) -> Option<u32> {
+    let bytes = packet.get(position..position + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Tests of the OSC module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test reading messages from a bundle
+    #[test]
+    fn parse_osc_bundle() {
+        // Compose a bundle of two messages
+        let first = b"/vulcan/warm\0\0\0\0,f\0\0\x3f\x80\0\0".to_vec();
+        let second = b"/vulcan/stop\0\0\0\0,i\0\0\0\0\0\0".to_vec();
+        let mut packet = b"#bundle\0\0\0\0\0\0\0\0\x01".to_vec();
+        for message in [&first, &second] {
+            packet.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            packet.extend_from_slice(message);
+        }
+
+        // Check the addresses and arguments
+        let mut messages = Vec::new();
+        parse_packet(&packet, &mut messages);
+        assert_eq!(
+            messages,
+            vec![
+                ("/vulcan/warm".to_string(), Some(1.0)),
+                ("/vulcan/stop".to_string(), Some(0.0)),
+            ]
+        );
+    }
+}
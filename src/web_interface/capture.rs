@@ -0,0 +1,135 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the frame captures of the web interface, which
+//! record the next frames written to the hardware (with their timestamps)
+//! so that flicker and timing problems can be found without a hardware DMX
+//! analyzer.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::sync::broadcast;
+use tokio::time::{Instant, timeout_at};
+
+// Define the pcap file constants
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4; // the magic number of microsecond timestamps
+const PCAP_SNAPLEN: u32 = 65535; // the longest record in the file
+const LINKTYPE_USER0: u32 = 147; // the link type reserved for private use
+const DMX_START_CODE: u8 = 0; // the start code of dimmer data
+
+/// A function to collect the next frames from the captures, until the count
+/// is reached or the limit expires. Any frames missed by a slow capture are
+/// skipped.
+///
+pub async fn capture(
+    mut captures: broadcast::Receiver<CapturedFrame>,
+    count: usize,
+    limit: Duration,
+) -> Vec<CapturedFrame> {
+    let deadline = Instant::now() + limit;
+    let mut frames = Vec::with_capacity(count);
+    while frames.len() < count {
+        match timeout_at(deadline, captures.recv()).await {
+            Ok(Ok(frame)) => frames.push(frame),
+            Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+            _ => break, // the limit expired or the output closed
+        }
+    }
+    frames
+}
+
+/// A function to encode the frames as a pcap file, with one record for each
+/// frame the hardware accepted. Each record holds the start code and every
+/// channel of the frame.
+///
+pub fn encode_pcap(frames: &[CapturedFrame]) -> Vec<u8> {
+    // Add the file header
+    let mut file = Vec::new();
+    file.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes()); // the major version
+    file.extend_from_slice(&4u16.to_le_bytes()); // the minor version
+    file.extend_from_slice(&0i32.to_le_bytes()); // timestamps are in utc
+    file.extend_from_slice(&0u32.to_le_bytes()); // the accuracy of the timestamps
+    file.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    file.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+
+    // Add a record for each written frame
+    for captured in frames.iter().filter(|captured| captured.is_written) {
+        let mut packet = vec![DMX_START_CODE];
+        packet.append(&mut captured.frame.as_bytes());
+        file.extend_from_slice(&((captured.timestamp / 1_000_000) as u32).to_le_bytes());
+        file.extend_from_slice(&((captured.timestamp % 1_000_000) as u32).to_le_bytes());
+        file.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        file.append(&mut packet);
+    }
+    file
+}
+
+// Tests of the capture module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that captures stop at the count and only written frames are saved
+    #[tokio::test]
+    async fn capture_frames() {
+        // Capture two of three frames
+        let (send, captures) = broadcast::channel(8);
+        let mut frame = Universe::new();
+        for (timestamp, is_written) in [(1_500_000, true), (1_550_000, false), (1_600_000, true)] {
+            frame.set(1, timestamp as u8);
+            send.send(CapturedFrame {
+                timestamp,
+                is_written,
+                frame: frame.clone(),
+            })
+            .unwrap();
+        }
+        let frames = capture(captures, 2, Duration::from_secs(1)).await;
+        assert_eq!(frames.len(), 2);
+        assert!(!frames[1].is_written);
+
+        // A capture that runs out of frames waits at most the limit
+        let frames = capture(send.subscribe(), 2, Duration::from_millis(10)).await;
+        assert!(frames.is_empty());
+
+        // Check the header and the only written record
+        frame.set(1, 200);
+        let file = encode_pcap(&[
+            CapturedFrame {
+                timestamp: 1_500_000,
+                is_written: true,
+                frame: frame.clone(),
+            },
+            CapturedFrame {
+                timestamp: 1_550_000,
+                is_written: false,
+                frame,
+            },
+        ]);
+        assert_eq!(file.len(), 24 + 16 + 513);
+        assert_eq!(&file[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&file[24..32], &[1, 0, 0, 0, 0x20, 0xa1, 0x07, 0]);
+        assert_eq!((file[40], file[41]), (0, 200));
+    }
+}
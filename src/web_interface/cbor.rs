@@ -0,0 +1,277 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module converts between CBOR and JSON values. Only the types that
+//! have a JSON equivalent are supported (byte strings are read as an array
+//! of bytes, and tags are ignored).
+
+// Import other structures into this module
+use super::format::{MAX_DEPTH, Reader};
+
+// Import JSON features
+use serde_json::{Map, Number, Value};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the CBOR constants
+const INDEFINITE: u8 = 31; // the argument of an item with an indefinite length
+const BREAK: u8 = 0xff; // the marker at the end of an indefinite item
+
+/// A function to encode a JSON value as CBOR
+///
+pub fn encode(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Null => bytes.push(0xf6),
+        Value::Bool(false) => bytes.push(0xf4),
+        Value::Bool(true) => bytes.push(0xf5),
+        Value::Number(number) => {
+            if let Some(number) = number.as_u64() {
+                encode_head(0, number, bytes);
+            } else if let Some(number) = number.as_i64() {
+                encode_head(1, (-1 - number) as u64, bytes);
+            } else {
+                bytes.push(0xfb);
+                bytes.extend(number.as_f64().unwrap_or(0.0).to_be_bytes());
+            }
+        }
+        Value::String(string) => {
+            encode_head(3, string.len() as u64, bytes);
+            bytes.extend(string.as_bytes());
+        }
+        Value::Array(array) => {
+            encode_head(4, array.len() as u64, bytes);
+            for item in array {
+                encode(item, bytes);
+            }
+        }
+        Value::Object(map) => {
+            encode_head(5, map.len() as u64, bytes);
+            for (key, item) in map {
+                encode_head(3, key.len() as u64, bytes);
+                bytes.extend(key.as_bytes());
+                encode(item, bytes);
+            }
+        }
+    }
+}
+
+/// A helper function to encode the major type and argument of an item
+///
+fn encode_head(major: u8, argument: u64, bytes: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => bytes.push(major | argument as u8),
+        24..=0xff => bytes.extend([major | 24, argument as u8]),
+        0x100..=0xffff => {
+            bytes.push(major | 25);
+            bytes.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            bytes.push(major | 26);
+            bytes.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            bytes.push(major | 27);
+            bytes.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+/// A function to decode a JSON value from CBOR
+///
+/// # Errors
+///
+/// This function will raise an error if the bytes are not valid CBOR or
+/// contain a type without a JSON equivalent.
+///
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+    let mut reader = Reader::new(bytes);
+    let value = decode_value(&mut reader, 0)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+/// A helper function to decode the next value
+///
+fn decode_value(reader: &mut Reader, depth: usize) -> Result<Value> {
+    // Limit the nesting of arrays, maps, and tags
+    if depth > MAX_DEPTH {
+        return Err(anyhow!("CBOR is nested too deeply."));
+    }
+
+    // Read the major type and argument
+    let initial = reader.byte()?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+
+    // Read simple values and floats, which use the argument differently
+    if major == 7 {
+        return Ok(match info {
+            20 => Value::Bool(false),
+            21 => Value::Bool(true),
+            22 | 23 => Value::Null,
+            25 => float(half_to_f64(u16::from_be_bytes(reader.array()?)))?,
+            26 => float(f32::from_be_bytes(reader.array()?) as f64)?,
+            27 => float(f64::from_be_bytes(reader.array()?))?,
+            _ => return Err(anyhow!("Unsupported CBOR value {:#04x}.", initial)),
+        });
+    }
+
+    // Read items with an indefinite length
+    if info == INDEFINITE {
+        return match major {
+            2 | 3 => decode_chunks(reader, major),
+            4 => {
+                let mut array = Vec::new();
+                while !is_break(reader)? {
+                    array.push(decode_value(reader, depth + 1)?);
+                }
+                Ok(Value::Array(array))
+            }
+            5 => {
+                let mut map = Map::new();
+                while !is_break(reader)? {
+                    let key = decode_key(reader, depth)?;
+                    map.insert(key, decode_value(reader, depth + 1)?);
+                }
+                Ok(Value::Object(map))
+            }
+            _ => Err(anyhow!("Invalid CBOR length.")),
+        };
+    }
+
+    // Otherwise, read the item with the argument
+    let argument = decode_argument(reader, info)?;
+    Ok(match major {
+        0 => Value::from(argument),
+        1 => Value::from(
+            i64::try_from(argument)
+                .map(|argument| -1 - argument)
+                .map_err(|_| anyhow!("CBOR integer is out of range."))?,
+        ),
+        2 => Value::Array(
+            reader
+                .bytes(length(argument)?)?
+                .iter()
+                .map(|byte| Value::from(*byte))
+                .collect(),
+        ),
+        3 => Value::String(String::from_utf8(
+            reader.bytes(length(argument)?)?.to_vec(),
+        )?),
+        4 => {
+            let length = length(argument)?;
+            let mut array = Vec::with_capacity(length.min(reader.remaining()));
+            for _ in 0..length {
+                array.push(decode_value(reader, depth + 1)?);
+            }
+            Value::Array(array)
+        }
+        5 => {
+            let mut map = Map::new();
+            for _ in 0..length(argument)? {
+                let key = decode_key(reader, depth)?;
+                map.insert(key, decode_value(reader, depth + 1)?);
+            }
+            Value::Object(map)
+        }
+        _ => decode_value(reader, depth + 1)?, // ignore the tag of the item
+    })
+}
+
+/// A helper function to read the argument of an item
+///
+fn decode_argument(reader: &mut Reader, info: u8) -> Result<u64> {
+    Ok(match info {
+        0..=23 => info as u64,
+        24 => reader.byte()? as u64,
+        25 => u16::from_be_bytes(reader.array()?) as u64,
+        26 => u32::from_be_bytes(reader.array()?) as u64,
+        27 => u64::from_be_bytes(reader.array()?),
+        _ => return Err(anyhow!("Invalid CBOR argument.")),
+    })
+}
+
+/// A helper function to convert an argument to a length
+///
+fn length(argument: u64) -> Result<usize> {
+    usize::try_from(argument).map_err(|_| anyhow!("CBOR length is too long."))
+}
+
+/// A helper function to check for (and skip) the end of an indefinite item
+///
+fn is_break(reader: &mut Reader) -> Result<bool> {
+    if reader.peek()? == BREAK {
+        reader.byte()?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// A helper function to decode the key of a map (the keys must be strings)
+///
+fn decode_key(reader: &mut Reader, depth: usize) -> Result<String> {
+    match decode_value(reader, depth + 1)? {
+        Value::String(key) => Ok(key),
+        _ => Err(anyhow!("CBOR map keys must be strings.")),
+    }
+}
+
+/// A helper function to decode a string (or byte string) with an indefinite
+/// length, made of chunks with the same major type
+///
+fn decode_chunks(reader: &mut Reader, major: u8) -> Result<Value> {
+    // Collect the bytes of each chunk
+    let mut bytes = Vec::new();
+    while !is_break(reader)? {
+        let initial = reader.byte()?;
+        if initial >> 5 != major {
+            return Err(anyhow!("Invalid CBOR chunk."));
+        }
+        let argument = decode_argument(reader, initial & 0x1f)?;
+        bytes.extend(reader.bytes(length(argument)?)?);
+    }
+
+    // Convert the bytes to the matching value
+    if major == 3 {
+        Ok(Value::String(String::from_utf8(bytes)?))
+    } else {
+        Ok(Value::Array(bytes.into_iter().map(Value::from).collect()))
+    }
+}
+
+/// A helper function to convert a half-precision float
+///
+fn half_to_f64(half: u16) -> f64 {
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f64;
+    let value = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent - 25),
+    };
+    if half & 0x8000 != 0 { -value } else { value }
+}
+
+/// A helper function to convert a float to a JSON number
+///
+fn float(value: f64) -> Result<Value> {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .ok_or_else(|| anyhow!("Invalid number in CBOR."))
+}
@@ -0,0 +1,169 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the change tracker of the web interface, which
+//! numbers each new frame of the output with a state version so that
+//! clients without websockets can long-poll for the channels that changed.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+/// A helper structure to hold the latest frame and the version of the last
+/// change to each channel
+///
+struct State {
+    version: u64,               // the version of the latest frame
+    frame: Universe,            // the latest frame of the output
+    channel_versions: Vec<u64>, // the version of the last change to each channel, zero-indexed
+}
+
+// Implement key features of the state
+impl State {
+    /// A function to create the first version of the state
+    ///
+    fn new(frame: Universe) -> Self {
+        Self {
+            version: 1,
+            frame,
+            channel_versions: vec![1; DMX_MAX as usize],
+        }
+    }
+
+    /// A method to record a new frame, returning true if any channel changed
+    /// (and a new version was created)
+    ///
+    fn record(&mut self, frame: &Universe) -> bool {
+        // Find the changed channels
+        let changed: Vec<u32> = (1..=DMX_MAX)
+            .filter(|channel| frame.get(*channel) != self.frame.get(*channel))
+            .collect();
+        if changed.is_empty() {
+            return false;
+        }
+
+        // Mark them with the new version
+        self.version += 1;
+        for channel in changed {
+            self.channel_versions[channel as usize - 1] = self.version;
+        }
+        self.frame = frame.clone();
+        true
+    }
+
+    /// A method to return the channels that changed after the version. A
+    /// version of zero (or a version from before a restart) returns every
+    /// channel.
+    ///
+    fn changes_since(&self, since: u64) -> Changes {
+        let is_full = since == 0 || since > self.version;
+        Changes {
+            version: self.version,
+            is_full,
+            values: (1..=DMX_MAX)
+                .filter(|channel| is_full || self.channel_versions[*channel as usize - 1] > since)
+                .map(|channel| (channel, self.frame.get(channel)))
+                .collect(),
+        }
+    }
+}
+
+/// A structure to follow the frames of the output and share their versions.
+/// The tracker can be cloned to share the versions.
+///
+#[derive(Clone)]
+pub struct ChangeTracker {
+    state: watch::Receiver<State>, // the latest state, updated in the background
+}
+
+// Implement key features of the change tracker
+impl ChangeTracker {
+    /// A function to create a new change tracker and follow the frames in
+    /// the background until the output closes
+    ///
+    pub fn start(mut frames: watch::Receiver<Universe>) -> Self {
+        // Start from the current frame
+        let (send_state, state) = watch::channel(State::new(frames.borrow_and_update().clone()));
+
+        // Record each new frame
+        tokio::spawn(async move {
+            while frames.changed().await.is_ok() {
+                let frame = frames.borrow_and_update().clone();
+                send_state.send_if_modified(|state| state.record(&frame));
+            }
+        });
+        Self { state }
+    }
+
+    /// A method to return the version of the latest frame
+    ///
+    pub fn version(&self) -> u64 {
+        self.state.borrow().version
+    }
+
+    /// A method to return the channels that changed after the version,
+    /// waiting up to the limit for a change if there are none yet (in which
+    /// case there may be no values)
+    ///
+    pub async fn wait(&self, since: u64, limit: Duration) -> Changes {
+        let mut state = self.state.clone();
+        let _ = timeout(limit, state.wait_for(|state| state.version != since)).await;
+        state.borrow().changes_since(since)
+    }
+}
+
+// Tests of the change tracker module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that each change creates a version with only the changed channels
+    #[test]
+    fn track_versions() {
+        // Start with every channel at zero
+        let mut state = State::new(Universe::new());
+        assert_eq!(state.changes_since(0).values.len(), DMX_MAX as usize);
+
+        // Change two channels, then one of them again
+        let mut frame = Universe::new();
+        frame.set(1, 10);
+        frame.set(2, 20);
+        assert!(state.record(&frame));
+        assert!(!state.record(&frame));
+        frame.set(2, 30);
+        assert!(state.record(&frame));
+        assert_eq!(state.version, 3);
+
+        // Check the changes since each version
+        let changes = state.changes_since(1);
+        assert!(!changes.is_full);
+        assert_eq!(changes.values.len(), 2);
+        let changes = state.changes_since(2);
+        assert_eq!(changes.values.get(&2), Some(&30));
+        assert_eq!(changes.values.len(), 1);
+        assert!(state.changes_since(3).values.is_empty());
+
+        // A version from before a restart returns every channel
+        assert!(state.changes_since(99).is_full);
+    }
+}
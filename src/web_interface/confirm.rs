@@ -0,0 +1,220 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the confirmation of destructive requests, so that
+//! a single accidental request from a generic HTTP client can't close the
+//! program or replace the look. The first request is answered with a
+//! token, and the request only runs when it is repeated with that token.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::{FnvHashMap, FnvHasher};
+
+// Define the confirmation constants
+const MAX_PENDING: usize = 256; // the number of tokens to keep before forgetting expired tokens
+
+// Count the tokens, so that each token is unique
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+/// A structure to hold the requests that need confirmation and the tokens
+/// that have been issued. The structure can be cloned to share the tokens.
+///
+#[derive(Clone)]
+pub struct Confirmations {
+    requests: Vec<String>, // the names of the requests that need confirmation
+    timeout: Duration,     // the time to repeat a request with its token
+    pending: Arc<Mutex<FnvHashMap<String, Pending>>>, // the tokens that have not been used, by token
+}
+
+/// A helper structure to hold the request that a token confirms
+///
+struct Pending {
+    identity: String, // the identity of the client that received the token
+    fingerprint: u64, // the fingerprint of the request, including its parameters
+    expires: Instant, // the time the token expires
+}
+
+// Implement key features of the confirmations
+impl Confirmations {
+    /// A function to create new confirmations from the configuration (if
+    /// there is none, no request needs confirmation)
+    ///
+    pub fn new(config: Option<&ConfirmConfig>) -> Self {
+        Self {
+            requests: config
+                .map(|config| config.requests.clone())
+                .unwrap_or_default(),
+            timeout: Duration::from_secs(config.map_or(0, |config| config.timeout)),
+            pending: Arc::new(Mutex::new(FnvHashMap::default())),
+        }
+    }
+
+    /// A method to check that the request is confirmed by the token, if the
+    /// request needs confirmation. Each token confirms only one identical
+    /// request from the same client, and only once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a confirmation error with a new token if
+    /// the request needs confirmation and the token is missing, expired, or
+    /// for a different request.
+    ///
+    pub fn check(
+        &self,
+        client: &Client,
+        request: &Request,
+        token: Option<&str>,
+        now: Instant,
+    ) -> Result<(), ErrorCode> {
+        // Allow any request that does not need confirmation
+        if !self.is_required(request) {
+            return Ok(());
+        }
+        let Ok(mut pending) = self.pending.lock() else {
+            return Ok(());
+        };
+
+        // Forget any expired tokens
+        if pending.len() >= MAX_PENDING {
+            pending.retain(|_, confirm| confirm.expires > now);
+        }
+
+        // Allow the request if the token matches
+        let fingerprint = Confirmations::fingerprint(request);
+        if let Some(confirm) = token.and_then(|token| pending.remove(token))
+            && (confirm.identity == client.identity)
+            && (confirm.fingerprint == fingerprint)
+            && (confirm.expires > now)
+        {
+            return Ok(());
+        }
+
+        // Otherwise, issue a new token for the request
+        let confirm_token = Confirmations::new_token();
+        pending.insert(
+            confirm_token.clone(),
+            Pending {
+                identity: client.identity.clone(),
+                fingerprint,
+                expires: now + self.timeout,
+            },
+        );
+        Err(ErrorCode::ConfirmationRequired {
+            request: request.name().to_string(),
+            confirm_token,
+            expires_in: self.timeout.as_secs(),
+        })
+    }
+
+    /// A helper method to check if the request (or any request of a batch)
+    /// needs confirmation
+    ///
+    fn is_required(&self, request: &Request) -> bool {
+        match request {
+            Request::Batch { requests }
+                if requests.iter().any(|request| self.is_required(request)) =>
+            {
+                true
+            }
+            request => self.requests.iter().any(|name| name == request.name()),
+        }
+    }
+
+    /// A helper function to fingerprint the request with its parameters
+    ///
+    fn fingerprint(request: &Request) -> u64 {
+        let mut hasher = FnvHasher::default();
+        hasher.write(
+            serde_json::to_string(request)
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        hasher.finish()
+    }
+
+    /// A helper function to create a new, unpredictable token
+    ///
+    fn new_token() -> String {
+        let count = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        format!(
+            "{:016x}{:016x}",
+            RandomState::new().hash_one(count),
+            RandomState::new().hash_one(count)
+        )
+    }
+}
+
+// Tests of the confirm module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a token confirms only the same request from the same client
+    #[test]
+    fn confirm_once() {
+        let confirmations = Confirmations::new(Some(&ConfirmConfig {
+            requests: vec!["close".to_string()],
+            timeout: 30,
+        }));
+        let client = |identity: &str| Client {
+            role: Role::Admin,
+            identity: identity.to_string(),
+            address: None,
+            client_id: None,
+        };
+        let token = |result: Result<(), ErrorCode>| match result {
+            Err(ErrorCode::ConfirmationRequired { confirm_token, .. }) => confirm_token,
+            result => panic!("Expected a confirmation, found {:?}.", result),
+        };
+        let start = Instant::now();
+
+        // Other requests run at once
+        let check = |request, token, now| confirmations.check(&client("a"), &request, token, now);
+        assert!(check(Request::AllStop, None, start).is_ok());
+
+        // The request runs once it is repeated with its token
+        let first = token(check(Request::Close, None, start));
+        assert!(check(Request::Close, Some(&first), start).is_ok());
+        assert!(check(Request::Close, Some(&first), start).is_err());
+
+        // A token from another client or an expired token is refused
+        let second = token(check(Request::Close, None, start));
+        assert!(
+            confirmations
+                .check(&client("b"), &Request::Close, Some(&second), start)
+                .is_err()
+        );
+        let third = token(check(Request::Close, None, start));
+        let later = start + Duration::from_secs(31);
+        assert!(check(Request::Close, Some(&third), later).is_err());
+
+        // A batch that includes the request also needs confirmation
+        let batch = Request::Batch {
+            requests: vec![Request::AllStop, Request::Close],
+        };
+        assert!(check(batch, None, start).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the files for exporting and importing the
+//! universe. Each file has the same structure as the body of loadUniverse,
+//! so a look can be moved between installations or kept in version control.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// An enum to describe the format of an exported file
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    /// a variant for a YAML file
+    #[default]
+    Yaml,
+
+    /// a variant for a JSON file
+    Json,
+}
+
+/// A struct to read the format of an export from the query
+///
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportQuery {
+    pub format: ExportFormat, // the format of the file (YAML by default)
+}
+
+// Implement key features of the export format
+impl ExportFormat {
+    /// A function to find the format of a file from its media type (JSON if
+    /// the media type is not YAML)
+    ///
+    pub fn from_media_type(media_type: Option<&str>) -> Self {
+        let media_type = media_type
+            .and_then(|media_type| media_type.split(';').next())
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                ExportFormat::Yaml
+            }
+            _ => ExportFormat::Json,
+        }
+    }
+
+    /// A method to return the media type of the format
+    ///
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Yaml => "application/yaml",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    /// A method to return the name of the downloaded file
+    ///
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Yaml => "universe.yaml",
+            ExportFormat::Json => "universe.json",
+        }
+    }
+
+    /// A method to write the universe as a file in this format
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the universe cannot be encoded.
+    ///
+    pub fn encode(&self, universe: &Universe) -> Result<String> {
+        Ok(match self {
+            ExportFormat::Yaml => serde_yaml::to_string(universe)?,
+            ExportFormat::Json => serde_json::to_string_pretty(universe)?,
+        })
+    }
+
+    /// A method to read the universe from a file in this format
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the file is not a valid universe.
+    ///
+    pub fn decode(&self, file: &[u8]) -> Result<Universe> {
+        Ok(match self {
+            ExportFormat::Yaml => serde_yaml::from_slice(file)?,
+            ExportFormat::Json => serde_json::from_slice(file)?,
+        })
+    }
+}
+
+// Tests of the export module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that an exported universe can be imported in either format
+    #[test]
+    fn export_and_import() {
+        let mut universe = Universe::new();
+        universe.set(1, 255);
+        universe.set(512, 7);
+        for format in [ExportFormat::Yaml, ExportFormat::Json] {
+            let file = format.encode(&universe).unwrap();
+            assert_eq!(format.decode(file.as_bytes()).unwrap(), universe);
+        }
+        assert_eq!(
+            ExportFormat::from_media_type(Some("text/yaml; charset=utf-8")),
+            ExportFormat::Yaml
+        );
+        assert_eq!(ExportFormat::from_media_type(None), ExportFormat::Json);
+        assert!(ExportFormat::Yaml.decode(b"values: [1, 2").is_err());
+    }
+}
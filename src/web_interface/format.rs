@@ -0,0 +1,263 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the formats of request and reply bodies. Every
+//! body has the same structure as the JSON body, so the binary formats are
+//! converted through JSON values.
+
+// Import other structures into this module
+use super::{cbor, msgpack};
+
+// Import serde features
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the format constants
+pub const MAX_DEPTH: usize = 64; // the deepest nesting of arrays and maps in a binary body
+
+/// An enum to describe the format of a body
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+// Implement key features of the format
+impl Format {
+    /// A function to find the format of a media type, if it is supported
+    ///
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        // Ignore any parameters and capitalization
+        let media_type = media_type.split(';').next().unwrap_or("").trim();
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "application/cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    /// A function to select the format of the reply from the accept header,
+    /// using the first supported media type (if none, the reply is JSON)
+    ///
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        accept
+            .and_then(|accept| accept.split(',').find_map(Format::from_media_type))
+            .unwrap_or(Format::Json)
+    }
+
+    /// A method to return the media type of the format
+    ///
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    /// A method to encode an item in this format
+    ///
+    pub fn encode<T>(&self, item: &T) -> Vec<u8>
+    where
+        T: Serialize,
+    {
+        // Convert the item to a JSON value
+        let Ok(value) = serde_json::to_value(item) else {
+            return Vec::new();
+        };
+
+        // Encode the value
+        let mut bytes = Vec::new();
+        match self {
+            Format::Json => return serde_json::to_vec(&value).unwrap_or_default(),
+            Format::MessagePack => msgpack::encode(&value, &mut bytes),
+            Format::Cbor => cbor::encode(&value, &mut bytes),
+        }
+        bytes
+    }
+
+    /// A method to decode an item in this format
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the bytes are not valid in this
+    /// format or do not match the structure of the item.
+    ///
+    pub fn decode<T>(&self, bytes: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let value = match self {
+            Format::Json => return Ok(serde_json::from_slice(bytes)?),
+            Format::MessagePack => msgpack::decode(bytes)?,
+            Format::Cbor => cbor::decode(bytes)?,
+        };
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// A helper structure to read a binary body, making sure every read stays
+/// inside the body
+///
+pub struct Reader<'a> {
+    bytes: &'a [u8], // the bytes that have not been read
+}
+
+// Implement key features of the reader
+impl<'a> Reader<'a> {
+    /// A function to create a new reader
+    ///
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// A method to return the number of bytes that have not been read
+    ///
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// A method to look at the next byte without reading it
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the body has ended.
+    ///
+    pub fn peek(&self) -> Result<u8> {
+        self.bytes
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("The body ended unexpectedly."))
+    }
+
+    /// A method to read the next byte
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the body has ended.
+    ///
+    pub fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// A method to read the next few bytes as an array
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the body has ended.
+    ///
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    /// A method to read the next bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the body has ended.
+    ///
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            return Err(anyhow!("The body ended unexpectedly."));
+        }
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    /// A method to make sure the whole body was read
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if there are bytes left over.
+    ///
+    pub fn finish(&self) -> Result<()> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Unexpected bytes after the end of the body."))
+        }
+    }
+}
+
+// Tests of the format module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::*;
+
+    // Test that requests and replies survive each format unchanged
+    #[test]
+    fn roundtrip_formats() {
+        // Select the formats from the headers
+        assert_eq!(
+            Format::from_accept(Some("text/html, application/cbor;q=0.9")),
+            Format::Cbor
+        );
+        assert_eq!(
+            Format::from_media_type("Application/MsgPack"),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(Format::from_accept(None), Format::Json);
+
+        // Encode and decode a variety of values
+        let value = serde_json::json!({
+            "name": "x".repeat(300),
+            "values": [0, 24, 127, 128, 255, 256, 70000, 5_000_000_000u64],
+            "negative": [-1, -32, -33, -200, -40000, -3_000_000_000i64],
+            "float": 0.5,
+            "flags": [true, false, null],
+            "nested": {"fade": {"channel": 1, "value": 255}},
+        });
+        let mut universe = Universe::new();
+        universe.set(DMX_MAX, 255);
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let bytes = format.encode(&value);
+            assert_eq!(
+                format.decode::<serde_json::Value>(&bytes).unwrap(),
+                value,
+                "{:?}",
+                format
+            );
+            let bytes = format.encode(&universe);
+            assert_eq!(format.decode::<Universe>(&bytes).unwrap(), universe);
+        }
+
+        // Check a known encoding and reject a truncated body
+        let fade = serde_json::json!({"channel": 1});
+        assert_eq!(
+            Format::MessagePack.encode(&fade),
+            b"\x81\xa7channel\x01".to_vec()
+        );
+        assert_eq!(Format::Cbor.encode(&fade), b"\xa1\x67channel\x01".to_vec());
+        assert!(
+            Format::Cbor
+                .decode::<serde_json::Value>(b"\xa1\x67chan")
+                .is_err()
+        );
+    }
+}
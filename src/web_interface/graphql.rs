@@ -0,0 +1,388 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a small subset of GraphQL to query the state of
+//! the program and subscribe to events. A document has a single query or
+//! subscription with nested fields, aliases, and comments (fragments,
+//! variables, arguments, and mutations are not supported).
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import JSON features
+use serde_json::{Map, Value};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the GraphQL constants
+const MAX_DEPTH: usize = 32; // the deepest nesting of selections
+
+/// A helper structure to receive a GraphQL request
+///
+#[derive(Deserialize)]
+pub struct GraphqlRequest {
+    pub query: String, // the GraphQL document
+}
+
+/// A structure to reply to a GraphQL request, in the GraphQL response format
+///
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct GraphqlResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>, // the selected fields, if the document was valid
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<GraphqlError>, // any problems with the document or fields
+}
+
+// Implement key features of the GraphQL response
+impl GraphqlResponse {
+    /// A function to create a response for a document that could not be
+    /// executed
+    ///
+    pub fn failure(error: &anyhow::Error) -> Self {
+        Self {
+            data: None,
+            errors: vec![GraphqlError {
+                message: error.to_string(),
+                path: Vec::new(),
+                extensions: None,
+            }],
+        }
+    }
+}
+
+/// A structure to describe a problem with a GraphQL document or field
+///
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct GraphqlError {
+    pub message: String, // a description of the problem
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>, // the response key of the field with the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<ErrorCode>, // the error code, if the request failed
+}
+
+/// An enum to describe the type of a GraphQL operation
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Query,
+    Subscription,
+}
+
+/// A structure to hold a parsed GraphQL document
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Document {
+    pub operation: Operation,       // the type of the operation
+    pub selections: Vec<Selection>, // the root fields of the operation
+}
+
+/// A structure to hold one selected field and the fields selected from it
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Selection {
+    pub alias: Option<String>, // the key of the field in the response, if different
+    pub name: String,          // the name of the field
+    pub selections: Vec<Selection>, // the fields selected from this field, if any
+}
+
+// Implement key features of the selection
+impl Selection {
+    /// A method to return the key of this field in the response
+    ///
+    pub fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// A method to select the fields from a value. Fields that are missing
+    /// from the value (such as the channels of a scene recalled event)
+    /// are null.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the selection does not match
+    /// the shape of the value.
+    ///
+    pub fn select(&self, value: Value) -> Result<Value> {
+        match value {
+            // Select the fields from each item of a list
+            Value::Array(items) => Ok(Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.select(item))
+                    .collect::<Result<_>>()?,
+            )),
+
+            // Select the fields from an object
+            Value::Object(mut object) => {
+                if self.selections.is_empty() {
+                    return Err(anyhow!(
+                        "Field {} must have a selection of subfields.",
+                        self.name
+                    ));
+                }
+                let mut selected = Map::new();
+                for selection in self.selections.iter() {
+                    let value = object.remove(&selection.name).unwrap_or(Value::Null);
+                    selected.insert(selection.key().to_string(), selection.select(value)?);
+                }
+                Ok(Value::Object(selected))
+            }
+
+            // Return null as is
+            Value::Null => Ok(Value::Null),
+
+            // Return any other scalar, without a selection
+            value => {
+                if !self.selections.is_empty() {
+                    return Err(anyhow!("Field {} has no subfields.", self.name));
+                }
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// A function to parse a GraphQL document
+///
+/// # Errors
+///
+/// This function will raise an error if the document is not valid or uses
+/// a feature that is not supported.
+///
+pub fn parse(document: &str) -> Result<Document> {
+    // Read the operation type, if specified
+    let mut parser = Parser::new(document);
+    let operation = match parser.peek()? {
+        Token::Name(name) => {
+            let operation = match name.as_str() {
+                "query" => Operation::Query,
+                "subscription" => Operation::Subscription,
+                "mutation" => return Err(anyhow!("Mutations are not supported.")),
+                _ => return Err(anyhow!("Unexpected name {}.", name)),
+            };
+            parser.next()?;
+
+            // Skip the name of the operation, if specified
+            if let Token::Name(_) = parser.peek()? {
+                parser.next()?;
+            }
+            if parser.peek()? == Token::Punctuator('(') {
+                return Err(anyhow!("Variables are not supported."));
+            }
+            operation
+        }
+        _ => Operation::Query,
+    };
+
+    // Read the root fields
+    let selections = parser.selection_set(0)?;
+    if parser.peek()? != Token::End {
+        return Err(anyhow!("Only one operation per document is supported."));
+    }
+    Ok(Document {
+        operation,
+        selections,
+    })
+}
+
+/// A helper enum to describe each token of a document
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Name(String),
+    Punctuator(char),
+    Spread,
+    End,
+}
+
+/// A helper structure to read the tokens of a document
+///
+struct Parser<'a> {
+    characters: std::iter::Peekable<std::str::Chars<'a>>, // the remaining characters
+    peeked: Option<Token>,                                // the next token, if it has been read
+}
+
+// Implement the parser
+impl<'a> Parser<'a> {
+    /// A function to create a new parser
+    ///
+    fn new(document: &'a str) -> Self {
+        Self {
+            characters: document.chars().peekable(),
+            peeked: None,
+        }
+    }
+
+    /// A method to look at the next token without reading it
+    ///
+    fn peek(&mut self) -> Result<Token> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read()?);
+        }
+        Ok(self.peeked.clone().unwrap_or(Token::End))
+    }
+
+    /// A method to read the next token
+    ///
+    fn next(&mut self) -> Result<Token> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.read(),
+        }
+    }
+
+    /// A helper method to read the next token from the characters
+    ///
+    fn read(&mut self) -> Result<Token> {
+        // Skip whitespace, commas, and comments
+        while let Some(character) = self.characters.peek() {
+            match character {
+                ' ' | '\t' | '\n' | '\r' | ',' | '\u{feff}' => {
+                    self.characters.next();
+                }
+                '#' => while self.characters.next_if(|c| *c != '\n').is_some() {},
+                _ => break,
+            }
+        }
+
+        // Read the token
+        let Some(character) = self.characters.next() else {
+            return Ok(Token::End);
+        };
+        match character {
+            '_' | 'a'..='z' | 'A'..='Z' => {
+                let mut name = character.to_string();
+                while let Some(character) = self
+                    .characters
+                    .next_if(|c| *c == '_' || c.is_ascii_alphanumeric())
+                {
+                    name.push(character);
+                }
+                Ok(Token::Name(name))
+            }
+            '.' => {
+                if self.characters.next() == Some('.') && self.characters.next() == Some('.') {
+                    Ok(Token::Spread)
+                } else {
+                    Err(anyhow!("Unexpected character ."))
+                }
+            }
+            '{' | '}' | ':' | '(' | ')' | '$' | '@' => Ok(Token::Punctuator(character)),
+            character => Err(anyhow!("Unexpected character {}.", character)),
+        }
+    }
+
+    /// A method to read a selection set, including the braces
+    ///
+    fn selection_set(&mut self, depth: usize) -> Result<Vec<Selection>> {
+        // Limit the nesting of selections
+        if depth > MAX_DEPTH {
+            return Err(anyhow!("The document is nested too deeply."));
+        }
+
+        // Read each selection until the closing brace
+        if self.next()? != Token::Punctuator('{') {
+            return Err(anyhow!("Expected {{."));
+        }
+        let mut selections = Vec::new();
+        loop {
+            match self.next()? {
+                Token::Name(name) => selections.push(self.selection(name, depth)?),
+                Token::Punctuator('}') if !selections.is_empty() => return Ok(selections),
+                Token::Punctuator('}') => return Err(anyhow!("Selections must not be empty.")),
+                Token::Punctuator('$') => return Err(anyhow!("Variables are not supported.")),
+                Token::Spread => return Err(anyhow!("Fragments are not supported.")),
+                Token::End => return Err(anyhow!("Expected }}.")),
+                token => return Err(anyhow!("Unexpected token {:?}.", token)),
+            }
+        }
+    }
+
+    /// A helper method to read the rest of a selection, after the name
+    ///
+    fn selection(&mut self, name: String, depth: usize) -> Result<Selection> {
+        // Read the name of the field, if the first name was an alias
+        let (alias, name) = if self.peek()? == Token::Punctuator(':') {
+            self.next()?;
+            match self.next()? {
+                Token::Name(field) => (Some(name), field),
+                _ => return Err(anyhow!("Expected a field name after {}:.", name)),
+            }
+        } else {
+            (None, name)
+        };
+
+        // Read any subfields
+        match self.peek()? {
+            Token::Punctuator('(') => Err(anyhow!("Arguments are not supported.")),
+            Token::Punctuator('@') => Err(anyhow!("Directives are not supported.")),
+            Token::Punctuator('{') => Ok(Selection {
+                alias,
+                name,
+                selections: self.selection_set(depth + 1)?,
+            }),
+            _ => Ok(Selection {
+                alias,
+                name,
+                selections: Vec::new(),
+            }),
+        }
+    }
+}
+
+// Tests of the GraphQL module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a document is parsed and its fields are selected
+    #[test]
+    fn parse_and_select() {
+        // Parse a query with an alias and a comment
+        let document = parse(
+            "query Lights { # the current output
+                status { driver, fades: activeFades }
+                scenes
+            }",
+        )
+        .unwrap();
+        assert_eq!(document.operation, Operation::Query);
+        assert_eq!(document.selections.len(), 2);
+
+        // Select the fields from a value
+        let status = serde_json::json!({"driver": "usb", "activeFades": 2, "isReady": true});
+        assert_eq!(
+            document.selections[0].select(status).unwrap(),
+            serde_json::json!({"driver": "usb", "fades": 2})
+        );
+        assert!(
+            document.selections[1]
+                .select(serde_json::json!({}))
+                .is_err()
+        );
+
+        // Reject unsupported features
+        assert!(parse("subscription { events { event } }").is_ok());
+        assert!(parse("mutation { allStop }").is_err());
+        assert!(parse("{ universe(channel: 1) { values } }").is_err());
+        assert!(parse("{ ...lights }").is_err());
+        assert!(parse("{ status { driver }").is_err());
+    }
+}
@@ -18,136 +18,2184 @@
 //! A module to create the web interface to interface to connect the web UI
 //! and endpoints to the program.
 
+// Define private submodules
+mod capture;
+mod cbor;
+mod changes;
+mod confirm;
+mod export;
+mod format;
+mod graphql;
+mod msgpack;
+mod openapi;
+mod rate_limit;
+mod websocket;
+
 // Import crate definitions
 use crate::definitions::*;
 
+// Import other structures into this module
+use changes::ChangeTracker;
+use confirm::Confirmations;
+use export::{ExportFormat, ExportQuery};
+use format::Format;
+use graphql::{GraphqlError, GraphqlRequest, GraphqlResponse, Operation, Selection};
+use rate_limit::RateLimiter;
+use websocket::CommandChannel;
+
+// Import the core library features
+use vulcan_core::DmxController;
+
+// Import standard library features
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
 // Import Tokio and warp features
-use tokio::sync::oneshot;
+use tokio::net::{TcpListener, UnixListener, lookup_host};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, broadcast, oneshot};
 use warp::{Filter, http};
 
-// Import futures
-use futures_util::FutureExt;
+// Import tracing features
+use tracing::{Instrument, debug, debug_span, error, info};
+
+// Import futures
+use futures_util::future::join_all;
+use futures_util::{FutureExt, StreamExt, stream};
+
+// Import serde feaures
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Define the counter to identify each request
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// Define the largest bodies, in bytes
+const BODY_LIMIT: u64 = 1024 * 16;
+const BATCH_LIMIT: u64 = 1024 * 256;
+
+// Define the longest waits for a change, in secs
+const DEFAULT_CHANGES_WAIT: u64 = 25;
+const MAX_CHANGES_WAIT: u64 = 60;
+
+// Define the default and largest frame captures, and their waits in secs
+const DEFAULT_CAPTURE_COUNT: usize = 10;
+const MAX_CAPTURE_COUNT: usize = 1000;
+const DEFAULT_CAPTURE_WAIT: u64 = 10;
+
+// Define the header with the state version of every reply
+const VERSION_HEADER: &str = "x-state-version";
+
+// Define the bundled web UI
+const INDEX_HTML: &str = include_str!("index.html");
+
+/// A structure to contain the web interface and handle all updates to the
+/// to the interface.
+///
+pub struct WebInterface {
+    web_send: WebSend,             // send line to the system interface
+    config: Config,                // the configuration of the web interface
+    events: EventSend,             // the line to subscribe to events
+    changes: ChangeTracker,        // the state versions of the output
+    dmx_controller: DmxController, // the output, for frame captures
+}
+
+/// A helper structure to receive a new log level
+///
+#[derive(Deserialize)]
+struct LogLevelUpdate {
+    level: String, // the new log level
+}
+
+/// A helper structure to receive the state version and the wait of a long poll
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangesQuery {
+    #[serde(default)]
+    since: u64, // the last state version the client has seen (zero for every channel)
+    timeout: Option<u64>, // the longest time to wait for a change, in secs
+}
+
+/// A helper structure to receive the size of a frame capture
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureQuery {
+    count: Option<usize>, // the number of frames to capture
+    timeout: Option<u64>, // the longest time to wait for the frames, in secs
+    #[serde(default)]
+    pcap: bool, // a flag to reply with a pcap file instead
+}
+
+/// A helper structure to receive the version of the look a request
+/// expects to replace
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionQuery {
+    expected_version: Option<u64>, // the version from the last read of the universe, if any
+}
+
+/// A helper structure to receive the token that confirms a request
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfirmQuery {
+    confirm_token: Option<String>, // the token from the first request, if any
+}
+
+/// A helper structure to receive the dry run flag of a request
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DryRunQuery {
+    #[serde(default)]
+    dry_run: bool, // a flag to simulate the request without changing the output
+}
+
+/// A rejection for requests that are missing a valid token
+///
+#[derive(Debug)]
+struct Unauthorized;
+impl warp::reject::Reject for Unauthorized {}
+
+/// A rejection for request bodies with invalid fields
+///
+#[derive(Debug)]
+struct Invalid(Vec<FieldError>);
+impl warp::reject::Reject for Invalid {}
+
+/// A rejection for clients that have made too many requests, with the time
+/// until the next request is available
+///
+#[derive(Debug)]
+struct RateLimited(Duration);
+impl warp::reject::Reject for RateLimited {}
+
+/// A rejection for requests beyond the limit of concurrent requests
+///
+#[derive(Debug)]
+struct Busy;
+impl warp::reject::Reject for Busy {}
+
+// Implement key Web Interface functionality
+impl WebInterface {
+    /// A function to create a new web interface. The send channel should
+    /// connect directly to the system interface.
+    ///
+    pub fn new(
+        web_send: WebSend,
+        config: Config,
+        events: EventSend,
+        dmx_controller: DmxController,
+    ) -> Self {
+        // Return the new web interface and runtime handle
+        WebInterface {
+            web_send,
+            config,
+            events,
+            changes: ChangeTracker::start(dmx_controller.frames()),
+            dmx_controller,
+        }
+    }
+
+    /// A method to listen for connections from the internet. The ready line
+    /// is notified once the web interface is listening.
+    ///
+    pub async fn run(&mut self, ready: oneshot::Sender<()>) {
+        // Note the identity of each token for the audit log
+        for (token, role) in self.config.tokens.iter() {
+            debug!(
+                "Token with role {:?} has identity {}.",
+                role,
+                Client::from_token(token, *role, None).identity
+            );
+        }
+
+        // Serve the api on every listening address
+        let routes = self.routes();
+
+        // Bind to each of the listening addresses (separated by commas)
+        let mut servers = Vec::new();
+        for address in self.config.address.split(',').map(str::trim) {
+            // Resolve the address, which may be a hostname
+            let sockets = match lookup_host(address).await {
+                Ok(sockets) => sockets,
+                Err(error) => {
+                    error!(
+                        "Unable to resolve listening address {}: {}.",
+                        address, error
+                    );
+                    continue;
+                }
+            };
+
+            // Listen on every resolved socket
+            for socket in sockets {
+                match TcpListener::bind(socket).await {
+                    Ok(listener) => {
+                        info!("Listening on {}.", socket);
+                        servers.push(
+                            warp::serve(routes.clone()).incoming(listener).run().boxed(), // boxed to satisfy https://github.com/rust-lang/rust/issues/89976 and https://github.com/rust-lang/rust/issues/85516
+                        );
+                    }
+                    Err(error) => error!("Unable to listen at {}: {}.", socket, error),
+                }
+            }
+        }
+
+        // Listen on the unix socket, if specified
+        if let Some(path) = self.config.socket.as_deref() {
+            // Remove a stale socket from a previous run (but nothing else)
+            if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                fs::remove_file(path).unwrap_or(());
+            }
+
+            // Bind to the socket
+            match UnixListener::bind(path) {
+                Ok(listener) => {
+                    info!("Listening on {}.", path.display());
+                    servers.push(warp::serve(routes.clone()).incoming(listener).run().boxed());
+                }
+                Err(error) => error!("Unable to listen at {}: {}.", path.display(), error),
+            }
+        }
+
+        // Make sure the web interface is listening somewhere
+        if servers.is_empty() {
+            error!("Unable to listen at any of the specified addresses.");
+            return;
+        }
+
+        // Indicate that the web interface is ready
+        ready.send(()).unwrap_or(());
+
+        // Handle incoming requests on every listening address
+        join_all(servers).await;
+    }
+
+    /// A helper method to build the filters of every route, from the api to
+    /// the web UI
+    ///
+    fn routes(&self) -> warp::filters::BoxedFilter<(warp::reply::Response,)> {
+        // Create the authentication filter (with the rate limit, if enabled)
+        let limiter = self.config.rate_limit.as_ref().map(RateLimiter::new);
+        let auth = WebInterface::with_auth(self.config.tokens.clone())
+            .and(warp::header::optional::<String>("x-client-id"))
+            .and_then(|client: Client, client_id: Option<String>| async move {
+                WebInterface::check_client_id(client, client_id)
+            })
+            .and(WebInterface::with_clone(limiter.clone()))
+            .and_then(|client: Client, limiter: Option<RateLimiter>| async move {
+                WebInterface::check_rate(client, limiter)
+            });
+
+        // Limit the number of requests in progress, if enabled (the permit
+        // is held until the reply is ready, or until a websocket closes)
+        let semaphore = self
+            .config
+            .max_concurrent_requests
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+
+        // Create the reply format filter
+        let format = WebInterface::with_format();
+
+        // Create the confirmation filter for destructive requests
+        let confirmations = Confirmations::new(self.config.confirm.as_ref());
+        let confirm =
+            warp::query::<ConfirmQuery>().and(WebInterface::with_clone(confirmations.clone()));
+
+        // Create the cue list name filter (the main list, if not given)
+        let cue_list = warp::path::param::<String>()
+            .or(warp::any().map(|| MAIN_CUE_LIST.to_string()))
+            .unify();
+
+        // Note the longest fade that will be accepted
+        let max_duration = Duration::from_secs(self.config.max_fade_duration);
+
+        // Create the play fade filter
+        let play_fade = warp::post()
+            .and(warp::path("playFade"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_valid_body::<Fade>(max_duration).map(Request::from),
+            ))
+            .and_then(WebInterface::handle_request);
+
+        // Create the load universe filter
+        let load_universe = warp::post()
+            .and(warp::path("loadUniverse"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_expected_version(
+                    WebInterface::with_valid_body::<Universe>(max_duration).map(Request::from),
+                ),
+            ))
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the recall scene filter
+        let recall_scene = warp::post()
+            .and(warp::path("recallScene"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_valid_body::<SceneRecall>(max_duration).map(Request::from),
+            ))
+            .and_then(WebInterface::handle_request);
+
+        // Create the play effect filter
+        let play_effect = warp::post()
+            .and(warp::path("playEffect"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<EffectPlay>(max_duration)
+                    .map(|effect| Request::PlayEffect { effect }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the play preset filter
+        let play_preset = warp::post()
+            .and(warp::path("playPreset"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<PresetPlay>(max_duration)
+                    .map(|preset| Request::PlayPreset { preset }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the stop effect filter
+        let stop_effect = warp::post()
+            .and(warp::path("stopEffect"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<EffectStop>(max_duration)
+                    .map(|stop| Request::StopEffect { stop }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the tune effect filter
+        let tune_effect = warp::post()
+            .and(warp::path("tuneEffect"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<EffectTune>(max_duration)
+                    .map(|tune| Request::TuneEffect { tune }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the play movement filter
+        let play_movement = warp::post()
+            .and(warp::path("playMovement"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<MovementPlay>(max_duration)
+                    .map(|movement| Request::PlayMovement { movement }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the play pixels filter
+        let play_pixels = warp::post()
+            .and(warp::path("playPixels"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<PixelPlay>(max_duration)
+                    .map(|play| Request::PlayPixels { play }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the set position filter
+        let set_position = warp::post()
+            .and(warp::path("setPosition"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_valid_body::<Position>(max_duration)
+                    .map(|position| Request::SetPosition { position }),
+            ))
+            .and_then(WebInterface::handle_request);
+
+        // Create the set color filter
+        let set_color = warp::post()
+            .and(warp::path("setColor"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_valid_body::<Chromaticity>(max_duration)
+                    .map(|color| Request::SetColor { color }),
+            ))
+            .and_then(WebInterface::handle_request);
+
+        // Create the play fan filter
+        let play_fan = warp::post()
+            .and(warp::path("playFan"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<GroupFan>(max_duration)
+                    .map(|fan| Request::PlayFan { fan }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the set group level filter
+        let set_group_level = warp::put()
+            .and(warp::path("group"))
+            .and(warp::path::param::<String>())
+            .and(warp::path("level"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_valid_body::<GroupLevel>(max_duration))
+            .and_then(|name, client, format, web_send, level| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::SetGroupLevel { name, level },
+                )
+                .await
+            });
+
+        // Create the get group level filter
+        let get_group_level = warp::get()
+            .and(warp::path("group"))
+            .and(warp::path::param::<String>())
+            .and(warp::path("level"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|name, client, format, web_send| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::GetGroupLevel { name },
+                )
+                .await
+            });
+
+        // Create the set fixture intensity filter
+        let set_fixture_intensity = warp::put()
+            .and(warp::path("fixture"))
+            .and(warp::path::param::<String>())
+            .and(warp::path("intensity"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_valid_body::<GroupLevel>(max_duration))
+            .and_then(|name, client, format, web_send, level| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::SetFixtureIntensity { name, level },
+                )
+                .await
+            });
+
+        // Create the get fixture intensity filter
+        let get_fixture_intensity = warp::get()
+            .and(warp::path("fixture"))
+            .and(warp::path::param::<String>())
+            .and(warp::path("intensity"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|name, client, format, web_send| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::GetFixtureIntensity { name },
+                )
+                .await
+            });
+
+        // Create the group blackout filter
+        let set_group_blackout = warp::put()
+            .and(warp::path("group"))
+            .and(warp::path::param::<String>())
+            .and(warp::path("blackout"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_body::<GroupBlackout>(BODY_LIMIT))
+            .and_then(|name, client, format, web_send, blackout| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::SetGroupBlackout { name, blackout },
+                )
+                .await
+            });
+
+        // Create the override filter
+        let set_override = warp::post()
+            .and(warp::path("override"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<Override>(max_duration)
+                    .map(|values| Request::SetOverride { values }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the release override filter
+        let release_override = warp::post()
+            .and(warp::path("releaseOverride"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<OverrideRelease>(max_duration)
+                    .map(|release| Request::ReleaseOverride { release }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the set labels filter
+        let set_labels = warp::post()
+            .and(warp::path("labels"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<Labels>(max_duration)
+                    .map(|labels| Request::SetLabels { labels }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the get labels filter
+        let get_labels = warp::get()
+            .and(warp::path("labels"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetLabels))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get palettes filter
+        let get_palettes = warp::get()
+            .and(warp::path("palettes"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetPalettes))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get patch filter
+        let get_patch = warp::get()
+            .and(warp::path("patch"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetPatch))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get cue list filter
+        let get_cue_list = warp::get()
+            .and(warp::path("cueList"))
+            .and(
+                warp::path("status")
+                    .map(|| MAIN_CUE_LIST.to_string())
+                    .or(warp::path::param::<String>().and(warp::path("status")))
+                    .unify(),
+            )
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|list, client, format, web_send| async move {
+                WebInterface::handle_request(client, format, web_send, Request::GetCueList { list })
+                    .await
+            });
+
+        // Create the set palette filter
+        let set_palette = warp::put()
+            .and(warp::path("palette"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_valid_body::<Palette>(max_duration))
+            .and_then(|name, client, format, web_send, palette| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::SetPalette { name, palette },
+                )
+                .await
+            });
+
+        // Create the patch fixture filter
+        let patch_fixture = warp::put()
+            .and(warp::path("patch"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_valid_body::<FixturePatch>(max_duration))
+            .and_then(|name, client, format, web_send, patch| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::PatchFixture { name, patch },
+                )
+                .await
+            });
+
+        // Create the set layer filter
+        let set_layer = warp::put()
+            .and(warp::path("layer"))
+            .and(warp::path::param::<Layer>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_valid_body::<LayerUpdate>(max_duration))
+            .and_then(|layer, client, format, web_send, update| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::SetLayer { layer, update },
+                )
+                .await
+            });
+
+        // Create the get layers filter
+        let get_layers = warp::get()
+            .and(warp::path("layers"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetLayers))
+            .and_then(WebInterface::handle_request);
+
+        // Create the tap filter
+        let tap = warp::post()
+            .and(warp::path("tap"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::Tap))
+            .and_then(WebInterface::handle_request);
+
+        // Create the set tempo filter
+        let set_tempo = warp::put()
+            .and(warp::path("tempo"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<Tempo>(max_duration)
+                    .map(|tempo| Request::SetTempo { tempo }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the get tempo filter
+        let get_tempo = warp::get()
+            .and(warp::path("tempo"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetTempo))
+            .and_then(WebInterface::handle_request);
+
+        // Create the set fade multiplier filter
+        let set_fade_multiplier = warp::put()
+            .and(warp::path("fadeMultiplier"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<FadeMultiplier>(max_duration)
+                    .map(|multiplier| Request::SetFadeMultiplier { multiplier }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the get fade multiplier filter
+        let get_fade_multiplier = warp::get()
+            .and(warp::path("fadeMultiplier"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetFadeMultiplier))
+            .and_then(WebInterface::handle_request);
+
+        // Create the set effect speed filter
+        let set_effect_speed = warp::put()
+            .and(warp::path("effectSpeed"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<EffectSpeedUpdate>(max_duration)
+                    .map(|speed| Request::SetEffectSpeed { speed }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the get effect speed filter
+        let get_effect_speed = warp::get()
+            .and(warp::path("effectSpeed"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetEffectSpeed))
+            .and_then(WebInterface::handle_request);
+
+        // Create the set preheat filter
+        let set_preheat = warp::put()
+            .and(warp::path("preheat"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_body::<PreheatState>(BODY_LIMIT)
+                    .map(|preheat| Request::SetPreheat { preheat }),
+            )
+            .and_then(WebInterface::handle_request);
+
+        // Create the get preheat filter
+        let get_preheat = warp::get()
+            .and(warp::path("preheat"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetPreheat))
+            .and_then(WebInterface::handle_request);
+
+        // Create the all stop filter
+        let all_stop = warp::post()
+            .and(warp::path("allStop"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::AllStop))
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the undo filter
+        let undo = warp::post()
+            .and(warp::path("undo"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::Undo))
+            .and_then(WebInterface::handle_request);
+
+        // Create the redo filter
+        let redo = warp::post()
+            .and(warp::path("redo"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::Redo))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get timeline filter
+        let get_timeline = warp::get()
+            .and(warp::path("timeline"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|name, client, format, web_send| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::GetTimeline { name },
+                )
+                .await
+            });
+
+        // Create the control timeline filter
+        let control_timeline = warp::post()
+            .and(warp::path("timeline"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_body::<TimelineControl>(BODY_LIMIT))
+            .and_then(|name, client, format, web_send, control| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::ControlTimeline { name, control },
+                )
+                .await
+            });
+
+        // Create the go filter
+        let go = warp::post()
+            .and(warp::path("go"))
+            .and(cue_list)
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|list, client, format, web_send| async move {
+                WebInterface::handle_request(client, format, web_send, Request::Go { list }).await
+            });
+
+        // Create the reset cues filter
+        let reset_cues = warp::post()
+            .and(warp::path("resetCues"))
+            .and(cue_list)
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(|list, client, format, web_send| async move {
+                WebInterface::handle_request(client, format, web_send, Request::ResetCues { list })
+                    .await
+            });
+
+        // Create the trigger filter
+        let trigger = warp::post()
+            .and(warp::path("trigger"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(self.config.triggers.clone()))
+            .and_then(WebInterface::handle_trigger);
+
+        // Create the batch filter
+        let batch = warp::post()
+            .and(warp::path("batch"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_body::<Vec<BatchRequest>>(BATCH_LIMIT)
+                    .and_then(move |requests: Vec<BatchRequest>| async move {
+                        WebInterface::check_fields(requests, max_duration)
+                    })
+                    .map(|requests: Vec<BatchRequest>| Request::Batch {
+                        requests: requests.into_iter().map(Request::from).collect(),
+                    }),
+            ))
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the define all stop filter
+        let define_all_stop = warp::post()
+            .and(warp::path("defineAllStop"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(
+                WebInterface::with_valid_body::<Universe>(max_duration)
+                    .map(|universe| Request::DefineAllStop { universe }),
+            )
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the reload config filter
+        let reload_config = warp::post()
+            .and(warp::path("reloadConfig"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::ReloadConfig))
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the log level filter
+        let log_level = warp::put()
+            .and(warp::path("logLevel"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_body::<LogLevelUpdate>(BODY_LIMIT).map(
+                |update: LogLevelUpdate| Request::SetLogLevel {
+                    level: update.level,
+                },
+            ))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get universe filter
+        let get_universe = warp::get()
+            .and(warp::path("universe"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetUniverse))
+            .and_then(WebInterface::handle_request);
+
+        // Create the export universe filter
+        let export_universe = warp::get()
+            .and(warp::path("universe"))
+            .and(warp::path("export"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<ExportQuery>())
+            .and_then(WebInterface::handle_export);
+
+        // Create the import universe filter
+        let import_universe = warp::post()
+            .and(warp::path("universe"))
+            .and(warp::path("import"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_dry_run(
+                WebInterface::with_expected_version(
+                    WebInterface::with_import(max_duration).map(Request::from),
+                ),
+            ))
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
+
+        // Create the get scenes filter
+        let get_scenes = warp::get()
+            .and(warp::path("scenes"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<SceneQuery>().map(|query| Request::GetScenes { query }))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get status filter
+        let get_status = warp::get()
+            .and(warp::path("status"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetStatus))
+            .and_then(WebInterface::handle_request);
+
+        // Create the get capabilities filter
+        let get_capabilities = warp::get()
+            .and(warp::path("capabilities"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetCapabilities))
+            .and_then(WebInterface::handle_request);
+
+        // Create the audit log filter
+        let get_audit = warp::get()
+            .and(warp::path("audit"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<AuditQuery>().map(|query| Request::GetAudit { query }))
+            .and_then(WebInterface::handle_request);
+
+        // Create the event log filter
+        let get_events = warp::get()
+            .and(warp::path("events"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<EventQuery>().map(|query| Request::GetEvents { query }))
+            .and_then(WebInterface::handle_request);
+
+        // Create the channel statistics filter
+        let get_channel_stats = warp::get()
+            .and(warp::path("channelStats"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<ChannelStatsQuery>().map(|query| Request::GetChannelStats { query }))
+            .and_then(WebInterface::handle_request);
 
-// Import serde feaures
-use serde::de::DeserializeOwned;
+        // Create the channel history filter
+        let get_channel_history = warp::get()
+            .and(warp::path("history"))
+            .and(warp::path::param::<u32>())
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<HistoryQuery>())
+            .and_then(|channel, client, format, web_send, query| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::GetChannelHistory { channel, query },
+                )
+                .await
+            });
 
-// Define conversions from data types into a Request
-impl From<Fade> for Request {
-    fn from(fade: Fade) -> Self {
-        Request::PlayFade { fade }
-    }
-}
-impl From<Universe> for Request {
-    fn from(universe: Universe) -> Self {
-        Request::LoadUniverse { universe }
-    }
-}
+        // Create the backup status filter
+        let get_backup_status = warp::get()
+            .and(warp::path("backup"))
+            .and(warp::path("status"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::GetBackupStatus))
+            .and_then(WebInterface::handle_request);
 
-/// A structure to contain the web interface and handle all updates to the
-/// to the interface.
-///
-pub struct WebInterface {
-    web_send: WebSend, // send line to the system interface
-    address: String,   // web address endpoint
-}
+        // Create the backup flush filter
+        let flush_backup = warp::post()
+            .and(warp::path("backup"))
+            .and(warp::path("flush"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(Request::FlushBackup))
+            .and_then(WebInterface::handle_request);
 
-// Implement key Web Interface functionality
-impl WebInterface {
-    /// A function to create a new web interface. The send channel should
-    /// connect directly to the system interface.
-    ///
-    pub fn new(web_send: WebSend, address: String) -> Self {
-        // Return the new web interface and runtime handle
-        WebInterface { web_send, address }
-    }
+        // Create the backup restore filter
+        let restore_backup = warp::post()
+            .and(warp::path("backup"))
+            .and(warp::path("restore"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(warp::query::<RestoreQuery>())
+            .and_then(|client, format, web_send, query| async move {
+                WebInterface::handle_request(
+                    client,
+                    format,
+                    web_send,
+                    Request::RestoreBackup { query },
+                )
+                .await
+            });
 
-    /// A method to listen for connections from the internet
-    ///
-    pub async fn run(&mut self) {
-        // Create the play fade filter
-        let play_fade = warp::post()
-            .and(warp::path("playFade"))
+        // Create the heartbeat filter
+        let heartbeat = warp::post()
+            .and(warp::path("heartbeat"))
             .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
             .and(WebInterface::with_clone(self.web_send.clone()))
-            .and(WebInterface::with_json::<Fade>())
+            .and(
+                WebInterface::with_valid_body::<Heartbeat>(max_duration)
+                    .map(|heartbeat| Request::Heartbeat { heartbeat }),
+            )
             .and_then(WebInterface::handle_request);
 
-        // Create the load universe filter
-        let load_universe = warp::post()
-            .and(warp::path("loadUniverse"))
+        // Create the get clients filter
+        let get_clients = warp::get()
+            .and(warp::path("clients"))
             .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
             .and(WebInterface::with_clone(self.web_send.clone()))
-            .and(WebInterface::with_json::<Universe>())
+            .and(WebInterface::with_clone(Request::GetClients))
             .and_then(WebInterface::handle_request);
 
+        // Create the changes filter
+        let get_changes = warp::get()
+            .and(warp::path("changes"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.changes.clone()))
+            .and(warp::query::<ChangesQuery>())
+            .and_then(WebInterface::handle_changes);
+
+        // Create the frame capture filter
+        let capture_frames = warp::get()
+            .and(warp::path("debug"))
+            .and(warp::path("frames"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.dmx_controller.clone()))
+            .and(warp::query::<CaptureQuery>())
+            .and_then(WebInterface::handle_capture);
+
+        // Create the websocket command channel filter
+        let command_channel = warp::get()
+            .and(warp::path("ws"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(warp::header::exact_ignore_case("upgrade", "websocket"))
+            .and(warp::header::<String>("sec-websocket-key"))
+            .and(warp::ext::optional::<warp::hyper::upgrade::OnUpgrade>())
+            .and(WebInterface::with_permit(semaphore.clone()))
+            .and(WebInterface::with_clone(CommandChannel::new(
+                self.web_send.clone(),
+                self.changes.clone(),
+                limiter,
+                confirmations,
+                max_duration,
+            )))
+            .map(
+                |client, key: String, on_upgrade, permit, channel: CommandChannel| {
+                    channel.upgrade(client, &key, on_upgrade, permit)
+                },
+            );
+
+        // Create the GraphQL filter
+        let graphql = warp::post()
+            .and(warp::path("graphql"))
+            .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and(WebInterface::with_clone(self.events.clone()))
+            .and(WebInterface::with_body::<GraphqlRequest>(BODY_LIMIT))
+            .and_then(WebInterface::handle_graphql);
+
+        // Create the health filter (no token required)
+        let health = warp::get()
+            .and(warp::path("health"))
+            .and(warp::path::end())
+            .map(|| warp::reply::json(&WebReply::success()));
+
+        // Create the readiness filter (no token required)
+        let ready_check = warp::get()
+            .and(warp::path("ready"))
+            .and(warp::path::end())
+            .and(WebInterface::with_clone(self.web_send.clone()))
+            .and_then(WebInterface::handle_ready);
+
+        // Create the OpenAPI document filter (no token required)
+        let document = openapi::document().unwrap_or_else(|error| {
+            error!("Unable to describe the web interface: {}.", error);
+            Value::Null
+        });
+        let get_openapi = warp::get()
+            .and(warp::path("openapi.json"))
+            .and(warp::path::end())
+            .map(move || warp::reply::json(&document));
+
+        // Create the Swagger UI filter (no token required), if enabled
+        let is_swagger_ui = self.config.swagger_ui;
+        let swagger_ui = warp::get()
+            .and(warp::path("docs"))
+            .and(warp::path::end())
+            .and_then(move || async move {
+                if is_swagger_ui {
+                    Ok(warp::reply::html(openapi::SWAGGER_UI_HTML))
+                } else {
+                    Err(warp::reject::not_found())
+                }
+            });
+
+        // Create the web UI filter (from the web root, if specified)
+        let web_ui = match self.config.web_root.clone() {
+            Some(root) => warp::get()
+                .and(warp::fs::dir(root))
+                .map(warp::Reply::into_response)
+                .boxed(),
+            None => warp::get()
+                .and(warp::path::end())
+                .map(|| warp::Reply::into_response(warp::reply::html(INDEX_HTML)))
+                .boxed(),
+        };
+
         // Create the close filter
         let close = warp::post()
             .and(warp::path("close"))
             .and(warp::path::end())
+            .and(auth.clone())
+            .and(format.clone())
             .and(WebInterface::with_clone(self.web_send.clone()))
             .and(WebInterface::with_clone(Request::Close))
-            .and_then(WebInterface::handle_request);
+            .and(confirm.clone())
+            .and_then(WebInterface::handle_confirmed_request);
 
-        // Combine the filters
-        let routes = play_fade.or(load_universe).or(close);
+        // Combine the api filters (boxing each half, so that the nested
+        // filters do not overflow the stack of a debug build)
+        let changes = play_fade
+            .or(load_universe)
+            .or(recall_scene)
+            .or(play_effect)
+            .or(play_preset)
+            .or(stop_effect)
+            .or(tune_effect)
+            .or(play_movement)
+            .or(play_pixels)
+            .or(set_position)
+            .or(set_color)
+            .or(play_fan)
+            .or(patch_fixture)
+            .or(set_group_level)
+            .or(set_fixture_intensity)
+            .or(set_group_blackout)
+            .or(set_layer)
+            .or(set_override)
+            .or(set_labels)
+            .or(set_palette)
+            .or(release_override)
+            .or(tap)
+            .or(set_tempo)
+            .or(set_fade_multiplier)
+            .or(set_effect_speed)
+            .or(set_preheat)
+            .or(all_stop)
+            .or(undo)
+            .or(redo)
+            .or(go)
+            .or(reset_cues)
+            .or(control_timeline)
+            .or(trigger)
+            .or(batch)
+            .or(define_all_stop)
+            .or(reload_config)
+            .or(log_level)
+            .map(warp::Reply::into_response)
+            .boxed();
+        let queries = get_universe
+            .or(export_universe)
+            .or(import_universe)
+            .or(get_scenes)
+            .or(get_group_level)
+            .or(get_fixture_intensity)
+            .or(get_layers)
+            .or(get_tempo)
+            .or(get_fade_multiplier)
+            .or(get_effect_speed)
+            .or(get_preheat)
+            .or(get_labels)
+            .or(get_palettes)
+            .or(get_patch)
+            .or(get_cue_list)
+            .or(get_timeline)
+            .or(get_status)
+            .or(get_audit)
+            .or(heartbeat)
+            .or(get_clients)
+            .or(get_events)
+            .or(get_channel_stats)
+            .or(get_channel_history)
+            .or(get_backup_status)
+            .or(flush_backup)
+            .or(restore_backup)
+            .or(get_changes)
+            .or(capture_frames)
+            .or(health)
+            .or(ready_check)
+            .or(get_openapi)
+            .or(swagger_ui)
+            .or(get_capabilities)
+            .or(graphql)
+            .or(close)
+            .map(warp::Reply::into_response)
+            .boxed();
+        let api = WebInterface::with_permit(semaphore)
+            .and(changes.or(queries))
+            .map(|_permit: Option<OwnedSemaphorePermit>, reply| reply);
 
-        // Handle incoming requests on the media port
-        warp::serve(routes)
-            .run(
-                self.address
-                    .parse::<std::net::SocketAddr>()
-                    .expect("Unable to listen at specified address."),
-            )
-            .boxed() // boxed to satisfy https://github.com/rust-lang/rust/issues/89976 and https://github.com/rust-lang/rust/issues/85516
-            .await;
+        // Add the command channel (which holds its own permit) and the state
+        // version of every reply
+        let tracker = self.changes.clone();
+        let api = command_channel.or(api).map(move |reply| {
+            warp::reply::with_header(reply, VERSION_HEADER, tracker.version().to_string())
+        });
+
+        // Serve the api under the version prefix (and at the root, for
+        // existing clients), followed by the web UI
+        let routes = warp::path(API_VERSION)
+            .and(api.clone())
+            .or(api)
+            .or(web_ui)
+            .recover(WebInterface::handle_rejection)
+            .map(warp::Reply::into_response);
+
+        // Add the cross-origin headers, if enabled
+        match self.config.cors.as_ref() {
+            Some(cors) => routes
+                .with(WebInterface::build_cors(cors))
+                .map(warp::Reply::into_response)
+                .boxed(),
+            None => routes.boxed(),
+        }
+    }
+
+    /// A function to reply with the current universe as a file to download
+    ///
+    async fn handle_export(
+        client: Client,
+        web_send: WebSend,
+        query: ExportQuery,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        // Get the current universe
+        let universe =
+            match WebInterface::send_request(client, &web_send, Request::GetUniverse).await {
+                WebReply::Universe { universe, .. } => universe,
+                reply => return Ok(WebInterface::compose_reply(reply, Format::Json)),
+            };
+
+        // Encode the universe as a file
+        let file = match query.format.encode(&universe) {
+            Ok(file) => file,
+            Err(error) => {
+                return Ok(WebInterface::compose_reply(
+                    WebReply::from_error(&error),
+                    Format::Json,
+                ));
+            }
+        };
+        let mut response = warp::reply::Response::new(file.into());
+        let headers = response.headers_mut();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(query.format.content_type()),
+        );
+        if let Ok(disposition) = http::HeaderValue::from_str(&format!(
+            "attachment; filename=\"{}\"",
+            query.format.file_name()
+        )) {
+            headers.insert(http::header::CONTENT_DISPOSITION, disposition);
+        }
+        Ok(response)
     }
 
     /// A function to handle define channel requests
     ///
     async fn handle_request<R>(
+        client: Client,
+        format: Format,
         web_send: WebSend,
         request: R,
     ) -> Result<impl warp::Reply, warp::Rejection>
     where
         R: Into<Request>,
     {
-        // Send the message and wait for the reply
+        // Follow the request through the program with a new span
+        let request = request.into();
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        let span = debug_span!("request", id, kind = request.name());
+        WebInterface::process_request(client, format, web_send, request)
+            .instrument(span)
+            .await
+    }
+
+    /// A function to handle requests that may need confirmation, replying
+    /// with a token if the request is not yet confirmed
+    ///
+    async fn handle_confirmed_request(
+        client: Client,
+        format: Format,
+        web_send: WebSend,
+        request: Request,
+        query: ConfirmQuery,
+        confirmations: Confirmations,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        // Check the confirmation only if the client is allowed to make the request
+        if client.role >= request.required_role() {
+            let token = query.confirm_token.as_deref();
+            if let Err(error) = confirmations.check(&client, &request, token, Instant::now()) {
+                return Ok(WebInterface::compose_reply(WebReply::error(error), format));
+            }
+        }
+
+        // Otherwise, process the request as usual
+        WebInterface::handle_request(client, format, web_send, request)
+            .await
+            .map(warp::Reply::into_response)
+    }
+
+    /// A helper function to pass the request to the system interface and
+    /// compose the reply in the requested format
+    ///
+    async fn process_request(
+        client: Client,
+        format: Format,
+        web_send: WebSend,
+        request: Request,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let reply = WebInterface::send_request(client, &web_send, request).await;
+        Ok(WebInterface::compose_reply(reply, format))
+    }
+
+    /// A helper function to check the role of the client, pass the request to
+    /// the system interface, and wait for the reply
+    ///
+    async fn send_request(client: Client, web_send: &WebSend, request: Request) -> WebReply {
+        // Make sure the client is allowed to make the request
+        let required_role = request.required_role();
+        if client.role < required_role {
+            return WebReply::error(ErrorCode::NotPermitted { required_role });
+        }
+
+        // Send the message (unless the system interface is saturated) and
+        // wait for the reply
         let (reply_to, rx) = oneshot::channel();
-        web_send.send(reply_to, request.into()).await;
-
-        // Wait for the reply
-        if let Ok(reply) = rx.await {
-            // If the reply is a success
-            if reply.is_success() {
-                return Ok(warp::reply::with_status(
-                    warp::reply::json(&reply),
+        if let Err(error) = web_send.try_send_as(client, reply_to, request) {
+            return WebReply::error(error);
+        }
+
+        // Wait for the reply, noting if there was no reply
+        rx.await.unwrap_or_else(|_| {
+            WebReply::error(ErrorCode::Internal {
+                reason: "Unable to process request.".to_string(),
+            })
+        })
+    }
+
+    /// A helper function to compose the reply in the given format, with the
+    /// status that matches the result of the request
+    ///
+    fn compose_reply(reply: WebReply, format: Format) -> warp::reply::Response {
+        // Select the status for the reply
+        let status = match &reply {
+            WebReply::Error { error, .. } => match error {
+                ErrorCode::ChannelOutOfRange { .. }
+                | ErrorCode::DryRunUnsupported { .. }
+                | ErrorCode::InvalidLogLevel { .. }
+                | ErrorCode::InvalidConfig { .. } => http::StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidRequest { .. } => http::StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::SceneNotFound { .. }
+                | ErrorCode::TriggerNotFound { .. }
+                | ErrorCode::ScriptNotFound { .. }
+                | ErrorCode::FixtureNotFound { .. }
+                | ErrorCode::GroupNotFound { .. }
+                | ErrorCode::PaletteNotFound { .. }
+                | ErrorCode::PresetNotFound { .. }
+                | ErrorCode::CueListNotFound { .. }
+                | ErrorCode::TimelineNotFound { .. }
+                | ErrorCode::PixelMapNotFound { .. }
+                | ErrorCode::EffectNotFound { .. }
+                | ErrorCode::BackupNotFound
+                | ErrorCode::HistoryDisabled => http::StatusCode::NOT_FOUND,
+                ErrorCode::NothingToUndo
+                | ErrorCode::NothingToRedo
+                | ErrorCode::EndOfCueList
+                | ErrorCode::BackupExpired { .. }
+                | ErrorCode::AddressConflict { .. }
+                | ErrorCode::VersionConflict { .. } => http::StatusCode::CONFLICT,
+                ErrorCode::EmergencyStop => http::StatusCode::LOCKED,
+                ErrorCode::ConfirmationRequired { .. } => http::StatusCode::PRECONDITION_REQUIRED,
+                ErrorCode::Unauthorized => http::StatusCode::UNAUTHORIZED,
+                ErrorCode::NotPermitted { .. } | ErrorCode::ReadOnly => http::StatusCode::FORBIDDEN,
+                ErrorCode::RateLimited { .. } => http::StatusCode::TOO_MANY_REQUESTS,
+                ErrorCode::DeviceUnavailable
+                | ErrorCode::BackupUnavailable
+                | ErrorCode::NotReady
+                | ErrorCode::Busy => http::StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::Internal { .. } => http::StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            reply if reply.is_success() => http::StatusCode::OK,
+            _ => http::StatusCode::BAD_REQUEST,
+        };
+
+        // Compose the reply
+        WebInterface::encode_reply(&reply, status, format)
+    }
+
+    /// A helper function to encode any reply in the given format
+    ///
+    fn encode_reply<T>(reply: &T, status: http::StatusCode, format: Format) -> warp::reply::Response
+    where
+        T: Serialize,
+    {
+        let mut response = warp::reply::Response::new(format.encode(reply).into());
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(format.content_type()),
+        );
+        response
+    }
+
+    /// A function to reply with the channels that changed after the state
+    /// version of the query, waiting for a change if there are none yet
+    ///
+    async fn handle_changes(
+        _client: Client,
+        format: Format,
+        tracker: ChangeTracker,
+        query: ChangesQuery,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        let limit = query
+            .timeout
+            .unwrap_or(DEFAULT_CHANGES_WAIT)
+            .min(MAX_CHANGES_WAIT);
+        let changes = tracker.wait(query.since, Duration::from_secs(limit)).await;
+        Ok(WebInterface::compose_reply(
+            WebReply::Changes {
+                is_valid: true,
+                changes,
+            },
+            format,
+        ))
+    }
+
+    /// A function to reply with the next frames written to the hardware, as
+    /// a list or as a pcap file
+    ///
+    async fn handle_capture(
+        client: Client,
+        format: Format,
+        dmx_controller: DmxController,
+        query: CaptureQuery,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        // Make sure the client is allowed to capture the output
+        if client.role < Role::Admin {
+            return Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::NotPermitted {
+                    required_role: Role::Admin,
+                }),
+                format,
+            ));
+        }
+
+        // Capture the frames
+        let count = query
+            .count
+            .unwrap_or(DEFAULT_CAPTURE_COUNT)
+            .min(MAX_CAPTURE_COUNT);
+        let limit = query
+            .timeout
+            .unwrap_or(DEFAULT_CAPTURE_WAIT)
+            .min(MAX_CHANGES_WAIT);
+        let frames =
+            capture::capture(dmx_controller.captures(), count, Duration::from_secs(limit)).await;
+
+        // Reply with a list, unless a file was requested
+        if !query.pcap {
+            return Ok(WebInterface::compose_reply(
+                WebReply::Frames {
+                    is_valid: true,
+                    frames,
+                },
+                format,
+            ));
+        }
+        let mut response = warp::reply::Response::new(capture::encode_pcap(&frames).into());
+        let headers = response.headers_mut();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/vnd.tcpdump.pcap"),
+        );
+        headers.insert(
+            http::header::CONTENT_DISPOSITION,
+            http::HeaderValue::from_static("attachment; filename=\"frames.pcap\""),
+        );
+        Ok(response)
+    }
+
+    /// A function to handle GraphQL requests. Queries reply with the selected
+    /// fields, and subscriptions reply with a stream of server-sent events.
+    ///
+    async fn handle_graphql(
+        client: Client,
+        format: Format,
+        web_send: WebSend,
+        events: EventSend,
+        request: GraphqlRequest,
+    ) -> Result<warp::reply::Response, warp::Rejection> {
+        // Parse the document, replying with any problem
+        let document = match graphql::parse(&request.query) {
+            Ok(document) => document,
+            Err(error) => {
+                return Ok(WebInterface::encode_reply(
+                    &GraphqlResponse::failure(&error),
                     http::StatusCode::OK,
+                    format,
                 ));
+            }
+        };
 
-            // Otherwise, note the error
-            } else {
-                return Ok(warp::reply::with_status(
-                    warp::reply::json(&reply),
-                    http::StatusCode::BAD_REQUEST,
-                ));
+        // Stream the events of subscriptions
+        if document.operation == Operation::Subscription {
+            return Ok(WebInterface::subscribe_events(
+                document.selections,
+                events,
+                format,
+            ));
+        }
+
+        // Resolve each root field, noting any that failed
+        let mut data = Map::new();
+        let mut errors = Vec::new();
+        for selection in document.selections.iter() {
+            let value = match WebInterface::resolve_field(&client, &web_send, selection).await {
+                Ok(value) => value,
+                Err(error) => {
+                    errors.push(error);
+                    Value::Null
+                }
+            };
+            data.insert(selection.key().to_string(), value);
+        }
+
+        // Reply with the selected fields
+        let response = GraphqlResponse {
+            data: Some(Value::Object(data)),
+            errors,
+        };
+        Ok(WebInterface::encode_reply(
+            &response,
+            http::StatusCode::OK,
+            format,
+        ))
+    }
+
+    /// A helper function to resolve one root field of a query by passing the
+    /// matching request to the system interface
+    ///
+    async fn resolve_field(
+        client: &Client,
+        web_send: &WebSend,
+        selection: &Selection,
+    ) -> Result<Value, GraphqlError> {
+        // Describe any problem with the field
+        let failure = |message: String, extensions: Option<ErrorCode>| GraphqlError {
+            message,
+            path: vec![selection.key().to_string()],
+            extensions,
+        };
+
+        // Find the request for the field
+        let request = match selection.name.as_str() {
+            "universe" => Request::GetUniverse,
+            "scenes" => Request::GetScenes {
+                query: SceneQuery::default(),
+            },
+            "status" => Request::GetStatus,
+            "capabilities" => Request::GetCapabilities,
+            "labels" => Request::GetLabels,
+            "palettes" => Request::GetPalettes,
+            "patch" => Request::GetPatch,
+            "cueList" => Request::GetCueList {
+                list: MAIN_CUE_LIST.to_string(),
+            },
+            "clients" => Request::GetClients,
+            name => return Err(failure(format!("Unknown field {}.", name), None)),
+        };
+
+        // Extract the value from the reply
+        let value = match WebInterface::send_request(client.clone(), web_send, request).await {
+            WebReply::Universe { universe, .. } => serde_json::to_value(universe),
+            WebReply::Scenes { scenes, .. } => serde_json::to_value(scenes),
+            WebReply::Status { status, .. } => serde_json::to_value(status),
+            WebReply::Capabilities { capabilities, .. } => serde_json::to_value(capabilities),
+            WebReply::Labels { labels, .. } => serde_json::to_value(labels),
+            WebReply::Palettes { palettes, .. } => serde_json::to_value(palettes),
+            WebReply::Patch { patch, .. } => serde_json::to_value(patch),
+            WebReply::CueList { status, .. } => serde_json::to_value(status),
+            WebReply::Clients { clients, .. } => serde_json::to_value(clients),
+            WebReply::Error { message, error, .. } => return Err(failure(message, Some(error))),
+            reply => return Err(failure(format!("Unexpected reply: {:?}", reply), None)),
+        }
+        .map_err(|error| failure(error.to_string(), None))?;
+
+        // Select the requested fields
+        selection
+            .select(value)
+            .map_err(|error| failure(error.to_string(), None))
+    }
+
+    /// A helper function to stream each event to a subscription, using the
+    /// distinct connections mode of the GraphQL over server-sent events
+    /// protocol. The subscription must have the single root field events.
+    ///
+    fn subscribe_events(
+        selections: Vec<Selection>,
+        events: EventSend,
+        format: Format,
+    ) -> warp::reply::Response {
+        // Make sure the subscription is for events
+        let selection = match selections.as_slice() {
+            [selection] if selection.name == "events" => selection.clone(),
+            _ => {
+                return WebInterface::encode_reply(
+                    &GraphqlResponse::failure(&anyhow!(
+                        "Subscriptions must have the single field events."
+                    )),
+                    http::StatusCode::OK,
+                    format,
+                );
             }
+        };
 
-        // Otherwise, note the error
-        } else {
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&WebReply::failure("Unable to process request.")),
-                http::StatusCode::INTERNAL_SERVER_ERROR,
+        // Send the selected fields of each event until the program closes
+        let receiver = events.subscribe();
+        let stream = stream::unfold(receiver, move |mut receiver| {
+            let selection = selection.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            let response = match serde_json::to_value(&event)
+                                .map_err(anyhow::Error::from)
+                                .and_then(|event| selection.select(event))
+                            {
+                                Ok(value) => GraphqlResponse {
+                                    data: Some(Value::Object(Map::from_iter([(
+                                        selection.key().to_string(),
+                                        value,
+                                    )]))),
+                                    errors: Vec::new(),
+                                },
+                                Err(error) => GraphqlResponse::failure(&error),
+                            };
+                            let event = warp::sse::Event::default()
+                                .event("next")
+                                .data(serde_json::to_string(&response).unwrap_or_default());
+                            return Some((event, receiver));
+                        }
+
+                        // Skip any events that were missed
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+        .chain(stream::once(async {
+            warp::sse::Event::default().event("complete").data("")
+        }))
+        .map(Ok::<_, std::convert::Infallible>);
+
+        // Reply with the stream
+        warp::Reply::into_response(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
+    }
+
+    /// A function to handle http triggers, passing the action of the trigger
+    /// to the system interface
+    ///
+    async fn handle_trigger(
+        name: String,
+        client: Client,
+        format: Format,
+        web_send: WebSend,
+        triggers: Vec<Trigger>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        // Look for the trigger with this name
+        match find_trigger(&triggers, &TriggerInput::Http { name: name.clone() }) {
+            Some(request) => WebInterface::handle_request(client, format, web_send, request)
+                .await
+                .map(warp::Reply::into_response),
+            None => Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::TriggerNotFound { trigger: name }),
+                format,
+            )),
+        }
+    }
+
+    /// A function to handle readiness checks, replying with an error status
+    /// if the program is not ready to control the lights
+    ///
+    async fn handle_ready(web_send: WebSend) -> Result<impl warp::Reply, warp::Rejection> {
+        // Request the current status
+        let (reply_to, rx) = oneshot::channel();
+        web_send.send(reply_to, Request::GetStatus).await;
+
+        // Check the status for readiness
+        match rx.await {
+            Ok(WebReply::Status { status, .. }) if status.is_ready() => Ok(
+                WebInterface::compose_reply(WebReply::success(), Format::Json),
+            ),
+            Ok(WebReply::Status { .. }) => Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::NotReady),
+                Format::Json,
+            )),
+            _ => Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::Internal {
+                    reason: "Unable to process request.".to_string(),
+                }),
+                Format::Json,
+            )),
+        }
+    }
+
+    /// A function to convert authentication and validation failures into a
+    /// reply (always in JSON, since the rejection happens before the format
+    /// is known). All other rejections are passed through unchanged.
+    ///
+    async fn handle_rejection(
+        rejection: warp::Rejection,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        // Reply to authentication failures
+        if rejection.find::<Unauthorized>().is_some() {
+            return Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::Unauthorized),
+                Format::Json,
+            ));
+        }
+
+        // Reply to clients that have made too many requests
+        if let Some(RateLimited(wait)) = rejection.find::<RateLimited>() {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = WebInterface::compose_reply(
+                WebReply::error(ErrorCode::RateLimited { retry_after }),
+                Format::Json,
+            );
+            response.headers_mut().insert(
+                http::header::RETRY_AFTER,
+                http::HeaderValue::from(retry_after),
+            );
+            return Ok(response);
+        }
+
+        // Reply to requests beyond the limit of concurrent requests
+        if rejection.find::<Busy>().is_some() {
+            return Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::Busy),
+                Format::Json,
+            ));
+        }
+
+        // Reply to invalid fields in the body
+        if let Some(Invalid(fields)) = rejection.find::<Invalid>() {
+            return Ok(WebInterface::compose_reply(
+                WebReply::error(ErrorCode::InvalidRequest {
+                    fields: fields.clone(),
+                }),
+                Format::Json,
             ));
         }
+
+        // Otherwise, pass the rejection through
+        Err(rejection)
+    }
+
+    // A function to require a valid token in either the authorization header
+    // (as a bearer token) or the x-api-key header, and extract the client
+    // with the role of the token. If no tokens are configured, every client
+    // is an anonymous admin.
+    fn with_auth(
+        tokens: FnvHashMap<String, Role>,
+    ) -> impl Filter<Extract = (Client,), Error = warp::Rejection> + Clone {
+        warp::header::optional::<String>("authorization")
+            .and(warp::header::optional::<String>("x-api-key"))
+            .and(warp::addr::remote())
+            .and_then(
+                move |authorization: Option<String>,
+                      api_key: Option<String>,
+                      remote: Option<std::net::SocketAddr>| {
+                    // Note the address of the client (unknown for the unix socket)
+                    let address = remote.map(|remote| remote.to_string());
+
+                    // Check the provided token against the configured tokens
+                    let client = if tokens.is_empty() {
+                        Some(Client {
+                            role: Role::Admin,
+                            identity: "anonymous".to_string(),
+                            address,
+                            client_id: None,
+                        })
+                    } else {
+                        let token = authorization
+                            .as_deref()
+                            .and_then(|header| header.strip_prefix("Bearer "))
+                            .map(str::to_string)
+                            .or(api_key)
+                            .unwrap_or_default();
+                        tokens
+                            .iter()
+                            .find(|(valid, _)| WebInterface::is_same_token(valid, &token))
+                            .map(|(valid, role)| Client::from_token(valid, *role, address))
+                    };
+
+                    // Reject any requests without a valid token
+                    async move { client.ok_or_else(|| warp::reject::custom(Unauthorized)) }
+                },
+            )
+    }
+
+    // A function to add the identifier from the x-client-id header to the
+    // client, rejecting an identifier that is empty or too long
+    fn check_client_id(
+        mut client: Client,
+        client_id: Option<String>,
+    ) -> Result<Client, warp::Rejection> {
+        if let Some(client_id) = client_id {
+            if client_id.is_empty() || client_id.len() > MAX_CLIENT_ID {
+                return Err(warp::reject::custom(Invalid(vec![FieldError::new(
+                    "x-client-id",
+                    format!("Must be 1 to {} characters.", MAX_CLIENT_ID),
+                )])));
+            }
+            client.client_id = Some(client_id);
+        }
+        Ok(client)
+    }
+
+    // A function to take one request from the bucket of the client, if there
+    // is a rate limit
+    fn check_rate(client: Client, limiter: Option<RateLimiter>) -> Result<Client, warp::Rejection> {
+        match limiter.map(|limiter| limiter.check(&client, Instant::now())) {
+            Some(Err(wait)) => Err(warp::reject::custom(RateLimited(wait))),
+            _ => Ok(client),
+        }
+    }
+
+    // A function to take a permit for the request, if there is a limit on
+    // concurrent requests
+    fn with_permit(
+        semaphore: Option<Arc<Semaphore>>,
+    ) -> impl Filter<Extract = (Option<OwnedSemaphorePermit>,), Error = warp::Rejection> + Clone
+    {
+        warp::any().and_then(move || {
+            let semaphore = semaphore.clone();
+            async move {
+                match semaphore {
+                    Some(semaphore) => semaphore
+                        .try_acquire_owned()
+                        .map(Some)
+                        .map_err(|_| warp::reject::custom(Busy)),
+                    None => Ok(None),
+                }
+            }
+        })
+    }
+
+    // A function to build the cross-origin filter from the configuration.
+    // Any invalid origins, methods, or headers are skipped with an error.
+    fn build_cors(config: &CorsConfig) -> warp::cors::Cors {
+        let mut cors = warp::cors();
+
+        // Add the allowed origins
+        for origin in config.allowed_origins.iter() {
+            // Allow any origin for an asterisk
+            if origin == "*" {
+                cors = cors.allow_any_origin();
+
+            // Otherwise, only allow complete origins (scheme and host)
+            } else if let Ok(uri) = origin.parse::<http::Uri>()
+                && let (Some(scheme), Some(authority)) = (uri.scheme(), uri.authority())
+                && format!("{}://{}", scheme, authority) == *origin
+            {
+                cors = cors.allow_origin(origin.as_str());
+            } else {
+                error!("Invalid cors origin: {}.", origin);
+            }
+        }
+
+        // Add the allowed methods
+        for method in config.allowed_methods.iter() {
+            match http::Method::from_bytes(method.as_bytes()) {
+                Ok(method) => cors = cors.allow_method(method),
+                Err(_) => error!("Invalid cors method: {}.", method),
+            }
+        }
+
+        // Add the allowed headers
+        for header in config.allowed_headers.iter() {
+            match http::header::HeaderName::from_bytes(header.as_bytes()) {
+                Ok(header) => cors = cors.allow_header(header),
+                Err(_) => error!("Invalid cors header: {}.", header),
+            }
+        }
+
+        // Return the completed filter
+        cors.build()
     }
 
-    // A function to extract a helper type from the body of the message
-    fn with_json<T>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+    // A function to compare two tokens in constant time (for tokens of the
+    // same length), so the comparison doesn't reveal how much of a token matched
+    fn is_same_token(valid: &str, token: &str) -> bool {
+        (valid.len() == token.len())
+            && valid
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+
+    // A function to select the format of the reply from the accept header
+    fn with_format() -> impl Filter<Extract = (Format,), Error = std::convert::Infallible> + Clone {
+        warp::header::optional::<String>("accept")
+            .map(|accept: Option<String>| Format::from_accept(accept.as_deref()))
+            .or(warp::any().map(|| Format::Json))
+            .unify()
+    }
+
+    /// A function to extract a helper type from the body of the message, in
+    /// the format of the content type header (JSON if not specified). Bodies
+    /// that are larger than the limit or cannot be read are rejected.
+    ///
+    fn with_body<T>(limit: u64) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
     where
         T: Send + DeserializeOwned,
     {
-        // When accepting a body, we want a JSON body (reject large payloads)
-        warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+        warp::body::content_length_limit(limit)
+            .and(warp::header::optional::<String>("content-type"))
+            .and(warp::body::bytes())
+            .and_then(
+                |content_type: Option<String>, body: warp::hyper::body::Bytes| async move {
+                    let format = content_type
+                        .as_deref()
+                        .and_then(Format::from_media_type)
+                        .unwrap_or(Format::Json);
+                    format.decode::<T>(&body).map_err(|error| {
+                        warp::reject::custom(Invalid(vec![FieldError::new(
+                            "body",
+                            error.to_string(),
+                        )]))
+                    })
+                },
+            )
+    }
+
+    /// A function to accept an exported universe as the body, in YAML or
+    /// JSON (by the content type), and check each field
+    ///
+    fn with_import(
+        max_duration: Duration,
+    ) -> impl Filter<Extract = (Universe,), Error = warp::Rejection> + Clone {
+        warp::body::content_length_limit(BODY_LIMIT)
+            .and(warp::header::optional::<String>("content-type"))
+            .and(warp::body::bytes())
+            .and_then(
+                move |content_type: Option<String>, body: warp::hyper::body::Bytes| async move {
+                    let universe = ExportFormat::from_media_type(content_type.as_deref())
+                        .decode(&body)
+                        .map_err(|error| {
+                            warp::reject::custom(Invalid(vec![FieldError::new(
+                                "body",
+                                error.to_string(),
+                            )]))
+                        })?;
+                    WebInterface::check_fields(universe, max_duration)
+                },
+            )
+    }
+
+    /// A function to accept a body and check each field, rejecting the
+    /// request with every problem found
+    ///
+    fn with_valid_body<T>(
+        max_duration: Duration,
+    ) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+    where
+        T: Send + DeserializeOwned + Validate,
+    {
+        WebInterface::with_body::<T>(BODY_LIMIT)
+            .and_then(move |body: T| async move { WebInterface::check_fields(body, max_duration) })
+    }
+
+    /// A helper function to check each field of the body, rejecting the
+    /// request with every problem found
+    ///
+    fn check_fields<T>(body: T, max_duration: Duration) -> Result<T, warp::Rejection>
+    where
+        T: Validate,
+    {
+        let fields = body.validate(max_duration);
+        if fields.is_empty() {
+            Ok(body)
+        } else {
+            Err(warp::reject::custom(Invalid(fields)))
+        }
+    }
+
+    /// A function to convert the request into a dry run, if the dryRun
+    /// query parameter is set
+    ///
+    fn with_dry_run(
+        request: impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone + Send,
+    ) -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone {
+        warp::query::<DryRunQuery>()
+            .and(request)
+            .map(|query: DryRunQuery, request| match request {
+                request if !query.dry_run => request,
+                Request::Batch { requests } => Request::DryRun { requests },
+                request => Request::DryRun {
+                    requests: vec![request],
+                },
+            })
+    }
+
+    /// A function to add any expected version from the query to a request
+    /// that loads the universe
+    ///
+    fn with_expected_version(
+        request: impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone + Send,
+    ) -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone {
+        warp::query::<VersionQuery>()
+            .and(request)
+            .map(|query: VersionQuery, request| match request {
+                Request::LoadUniverse { universe, .. } => Request::LoadUniverse {
+                    universe,
+                    expected_version: query.expected_version,
+                },
+                request => request,
+            })
     }
 
     // A function to add the web send to the filter
@@ -160,3 +2208,245 @@ impl WebInterface {
         warp::any().map(move || item.clone())
     }
 }
+
+// Tests of the web interface module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::mpsc;
+    use vulcan_core::DryRunDriver;
+
+    // A helper function to serve the routes of the configuration on a free
+    // port, returning the address and the line of the requests
+    async fn serve(config: Config) -> (SocketAddr, mpsc::Receiver<WebRequest>) {
+        let (web_send, requests) = WebSend::new();
+        let events = EventSend::new();
+        let dmx_controller =
+            DmxController::new(DryRunDriver, Duration::from_millis(50), events.clone());
+        let web_interface = WebInterface::new(web_send, config, events, dmx_controller);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(warp::serve(web_interface.routes()).incoming(listener).run());
+        (address, requests)
+    }
+
+    // A helper function to answer every request with success, passing on
+    // the client of each one
+    fn answer(mut requests: mpsc::Receiver<WebRequest>) -> mpsc::UnboundedReceiver<Client> {
+        let (send_client, clients) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                send_client.send(request.client).unwrap_or(());
+                request.reply_to.send(WebReply::success()).unwrap_or(());
+            }
+        });
+        clients
+    }
+
+    // A helper function to send an http request with the headers, returning
+    // the status and the body of the reply
+    async fn send(
+        address: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n",
+            method, path
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .unwrap_or_default();
+        (status, response)
+    }
+
+    // A helper function to open a websocket, returning the connection and
+    // the status of the handshake
+    async fn open_socket(address: SocketAddr) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                b"GET /ws HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\n\
+                Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut buffer = [0; 1024];
+        let count = stream.read(&mut buffer).await.unwrap();
+        let response = String::from_utf8_lossy(&buffer[..count]).to_string();
+        let status = response.split(' ').nth(1).unwrap_or_default().to_string();
+        (stream, status)
+    }
+
+    // Test that an open websocket counts against the concurrent requests
+    #[tokio::test]
+    async fn websocket_permit() {
+        let config = Config {
+            max_concurrent_requests: Some(1),
+            ..Config::default()
+        };
+        let (address, _requests) = serve(config).await;
+
+        // The first socket is upgraded and an extra one is refused
+        let (first, status) = open_socket(address).await;
+        assert_eq!(status, "101");
+        let (_, status) = open_socket(address).await;
+        assert_eq!(status, "503");
+
+        // Once the first socket closes, another is accepted
+        drop(first);
+        let mut status = String::new();
+        for _ in 0..20 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            (_, status) = open_socket(address).await;
+            if status == "101" {
+                break;
+            }
+        }
+        assert_eq!(status, "101");
+    }
+
+    // Test that tokens are only equal if every byte matches
+    #[test]
+    fn is_same_token() {
+        assert!(WebInterface::is_same_token("secret", "secret"));
+        assert!(!WebInterface::is_same_token("secret", "secreT"));
+        assert!(!WebInterface::is_same_token("secret", "secret-and-more"));
+        assert!(!WebInterface::is_same_token("secret", ""));
+    }
+
+    // Test that only requests with a valid token reach the system interface
+    #[tokio::test]
+    async fn with_auth() {
+        let config = Config {
+            tokens: [
+                ("admin-token".to_string(), Role::Admin),
+                ("observer-token".to_string(), Role::Observer),
+            ]
+            .into_iter()
+            .collect(),
+            ..Config::default()
+        };
+        let (address, requests) = serve(config).await;
+        let mut clients = answer(requests);
+
+        // A missing, wrong, or partial token is refused
+        let (status, _) = send(address, "GET", "/v1/universe", &[]).await;
+        assert_eq!(status, 401);
+        let wrong = [("authorization", "Bearer wrong-token")];
+        let (status, _) = send(address, "GET", "/v1/universe", &wrong).await;
+        assert_eq!(status, 401);
+        let partial = [("authorization", "Bearer observer")];
+        let (status, _) = send(address, "GET", "/v1/universe", &partial).await;
+        assert_eq!(status, 401);
+        let unprefixed = [("authorization", "observer-token")];
+        let (status, _) = send(address, "GET", "/v1/universe", &unprefixed).await;
+        assert_eq!(status, 401);
+
+        // A valid bearer token (or api key) passes with the role of the token
+        let bearer = [("authorization", "Bearer observer-token")];
+        let (status, _) = send(address, "GET", "/v1/universe", &bearer).await;
+        assert_eq!(status, 200);
+        let client = clients.recv().await.unwrap();
+        assert_eq!(client.role, Role::Observer);
+        assert_eq!(
+            client.identity,
+            Client::from_token("observer-token", Role::Observer, None).identity
+        );
+        let api_key = [("x-api-key", "admin-token")];
+        let (status, _) = send(address, "GET", "/v1/universe", &api_key).await;
+        assert_eq!(status, 200);
+        assert_eq!(clients.recv().await.unwrap().role, Role::Admin);
+
+        // The health check needs no token
+        let (status, _) = send(address, "GET", "/v1/health", &[]).await;
+        assert_eq!(status, 200);
+        assert!(clients.try_recv().is_err());
+    }
+
+    // Test that every client is an anonymous admin if there are no tokens
+    #[tokio::test]
+    async fn anonymous_admin() {
+        let (address, requests) = serve(Config::default()).await;
+        let mut clients = answer(requests);
+
+        // Any token (or none) is accepted
+        let (status, _) = send(address, "GET", "/v1/universe", &[]).await;
+        assert_eq!(status, 200);
+        let wrong = [("authorization", "Bearer wrong-token")];
+        let (status, _) = send(address, "GET", "/v1/universe", &wrong).await;
+        assert_eq!(status, 200);
+
+        // Each client is an admin without an identity from a token
+        for _ in 0..2 {
+            let client = clients.recv().await.unwrap();
+            assert_eq!(client.role, Role::Admin);
+            assert_eq!(client.identity, "anonymous");
+        }
+    }
+
+    // Test that each role may only make the requests it is permitted over http
+    #[tokio::test]
+    async fn role_gating() {
+        let tokens = [
+            ("observer-token", Role::Observer),
+            ("operator-token", Role::Operator),
+            ("admin-token", Role::Admin),
+        ];
+        let config = Config {
+            tokens: tokens
+                .iter()
+                .map(|(token, role)| (token.to_string(), *role))
+                .collect(),
+            ..Config::default()
+        };
+        let (address, requests) = serve(config).await;
+        let mut clients = answer(requests);
+
+        // Try a read-only, a show control, and two admin requests with each
+        // role (the frame capture never reaches the system interface)
+        let requests = [
+            ("GET", "/v1/universe", Role::Observer, true),
+            ("POST", "/v1/allStop", Role::Operator, true),
+            ("POST", "/v1/reloadConfig", Role::Admin, true),
+            (
+                "GET",
+                "/v1/debug/frames?count=1&timeout=1",
+                Role::Admin,
+                false,
+            ),
+        ];
+        for (token, role) in tokens {
+            let authorization = format!("Bearer {}", token);
+            let headers = [("authorization", authorization.as_str())];
+            for (method, path, required_role, is_sent) in requests {
+                let (status, body) = send(address, method, path, &headers).await;
+                if role >= required_role {
+                    assert_eq!(status, 200, "{:?} {} {}", role, method, path);
+                    if is_sent {
+                        assert_eq!(clients.recv().await.unwrap().role, role);
+                    }
+                } else {
+                    assert_eq!(status, 403, "{:?} {} {}", role, method, path);
+                    assert!(body.contains("notPermitted"));
+                    assert!(clients.try_recv().is_err());
+                }
+            }
+        }
+    }
+}
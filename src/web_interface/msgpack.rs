@@ -0,0 +1,230 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module converts between MessagePack and JSON values. Only the types
+//! that have a JSON equivalent are supported (binary data is read as an
+//! array of bytes).
+
+// Import other structures into this module
+use super::format::{MAX_DEPTH, Reader};
+
+// Import JSON features
+use serde_json::{Map, Number, Value};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A function to encode a JSON value as MessagePack
+///
+pub fn encode(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Null => bytes.push(0xc0),
+        Value::Bool(false) => bytes.push(0xc2),
+        Value::Bool(true) => bytes.push(0xc3),
+        Value::Number(number) => {
+            if let Some(number) = number.as_u64() {
+                match number {
+                    0..=0x7f => bytes.push(number as u8),
+                    0x80..=0xff => bytes.extend([0xcc, number as u8]),
+                    0x100..=0xffff => {
+                        bytes.push(0xcd);
+                        bytes.extend((number as u16).to_be_bytes());
+                    }
+                    0x1_0000..=0xffff_ffff => {
+                        bytes.push(0xce);
+                        bytes.extend((number as u32).to_be_bytes());
+                    }
+                    _ => {
+                        bytes.push(0xcf);
+                        bytes.extend(number.to_be_bytes());
+                    }
+                }
+            } else if let Some(number) = number.as_i64() {
+                match number {
+                    -32..=-1 => bytes.push(number as i8 as u8),
+                    -0x80..=-33 => bytes.extend([0xd0, number as i8 as u8]),
+                    -0x8000..=-0x81 => {
+                        bytes.push(0xd1);
+                        bytes.extend((number as i16).to_be_bytes());
+                    }
+                    -0x8000_0000..=-0x8001 => {
+                        bytes.push(0xd2);
+                        bytes.extend((number as i32).to_be_bytes());
+                    }
+                    _ => {
+                        bytes.push(0xd3);
+                        bytes.extend(number.to_be_bytes());
+                    }
+                }
+            } else {
+                bytes.push(0xcb);
+                bytes.extend(number.as_f64().unwrap_or(0.0).to_be_bytes());
+            }
+        }
+        Value::String(string) => {
+            encode_length(string.len(), 0xa0, 32, (Some(0xd9), 0xda, 0xdb), bytes);
+            bytes.extend(string.as_bytes());
+        }
+        Value::Array(array) => {
+            encode_length(array.len(), 0x90, 16, (None, 0xdc, 0xdd), bytes);
+            for item in array {
+                encode(item, bytes);
+            }
+        }
+        Value::Object(map) => {
+            encode_length(map.len(), 0x80, 16, (None, 0xde, 0xdf), bytes);
+            for (key, item) in map {
+                encode(&Value::String(key.clone()), bytes);
+                encode(item, bytes);
+            }
+        }
+    }
+}
+
+/// A helper function to encode the length of a string, array, or map, using
+/// the short form below the limit and the smallest marker otherwise (arrays
+/// and maps have no 8 bit marker)
+///
+fn encode_length(
+    length: usize,
+    short: u8,
+    limit: usize,
+    markers: (Option<u8>, u8, u8),
+    bytes: &mut Vec<u8>,
+) {
+    match (length, markers) {
+        (length, _) if length < limit => bytes.push(short | length as u8),
+        (0..=0xff, (Some(marker), _, _)) => bytes.extend([marker, length as u8]),
+        (0..=0xffff, (_, marker, _)) => {
+            bytes.push(marker);
+            bytes.extend((length as u16).to_be_bytes());
+        }
+        (_, (_, _, marker)) => {
+            bytes.push(marker);
+            bytes.extend((length as u32).to_be_bytes());
+        }
+    }
+}
+
+/// A function to decode a JSON value from MessagePack
+///
+/// # Errors
+///
+/// This function will raise an error if the bytes are not valid MessagePack
+/// or contain a type without a JSON equivalent.
+///
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+    let mut reader = Reader::new(bytes);
+    let value = decode_value(&mut reader, 0)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+/// A helper function to decode the next value
+///
+fn decode_value(reader: &mut Reader, depth: usize) -> Result<Value> {
+    // Limit the nesting of arrays and maps
+    if depth > MAX_DEPTH {
+        return Err(anyhow!("MessagePack is nested too deeply."));
+    }
+
+    // Read the length of any string, binary data, array, or map
+    let marker = reader.byte()?;
+    let length = match marker {
+        0x80..=0x9f => (marker & 0x0f) as usize,
+        0xa0..=0xbf => (marker & 0x1f) as usize,
+        0xc4 | 0xd9 => reader.byte()? as usize,
+        0xc5 | 0xda | 0xdc | 0xde => u16::from_be_bytes(reader.array()?) as usize,
+        0xc6 | 0xdb | 0xdd | 0xdf => u32::from_be_bytes(reader.array()?) as usize,
+        _ => 0,
+    };
+
+    // Read the value based on the marker
+    Ok(match marker {
+        0x00..=0x7f => Value::from(marker),
+        0x80..=0x8f => decode_map(reader, length, depth)?,
+        0x90..=0x9f => decode_array(reader, length, depth)?,
+        0xa0..=0xbf => decode_string(reader, length)?,
+        0xc0 => Value::Null,
+        0xc2 => Value::Bool(false),
+        0xc3 => Value::Bool(true),
+        0xc4..=0xc6 => decode_binary(reader, length)?,
+        0xca => float(f32::from_be_bytes(reader.array()?) as f64)?,
+        0xcb => float(f64::from_be_bytes(reader.array()?))?,
+        0xcc => Value::from(reader.byte()?),
+        0xcd => Value::from(u16::from_be_bytes(reader.array()?)),
+        0xce => Value::from(u32::from_be_bytes(reader.array()?)),
+        0xcf => Value::from(u64::from_be_bytes(reader.array()?)),
+        0xd0 => Value::from(reader.byte()? as i8),
+        0xd1 => Value::from(i16::from_be_bytes(reader.array()?)),
+        0xd2 => Value::from(i32::from_be_bytes(reader.array()?)),
+        0xd3 => Value::from(i64::from_be_bytes(reader.array()?)),
+        0xd9..=0xdb => decode_string(reader, length)?,
+        0xdc | 0xdd => decode_array(reader, length, depth)?,
+        0xde | 0xdf => decode_map(reader, length, depth)?,
+        0xe0..=0xff => Value::from(marker as i8),
+        _ => return Err(anyhow!("Unsupported MessagePack type {:#04x}.", marker)),
+    })
+}
+
+/// A helper function to convert a float to a JSON number
+///
+fn float(value: f64) -> Result<Value> {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .ok_or_else(|| anyhow!("Invalid number in MessagePack."))
+}
+
+/// A helper function to decode a string of the given length
+///
+fn decode_string(reader: &mut Reader, length: usize) -> Result<Value> {
+    let bytes = reader.bytes(length)?;
+    Ok(Value::String(String::from_utf8(bytes.to_vec())?))
+}
+
+/// A helper function to decode binary data as an array of bytes
+///
+fn decode_binary(reader: &mut Reader, length: usize) -> Result<Value> {
+    let bytes = reader.bytes(length)?;
+    Ok(Value::Array(
+        bytes.iter().map(|byte| Value::from(*byte)).collect(),
+    ))
+}
+
+/// A helper function to decode an array of the given length
+///
+fn decode_array(reader: &mut Reader, length: usize, depth: usize) -> Result<Value> {
+    let mut array = Vec::with_capacity(length.min(reader.remaining()));
+    for _ in 0..length {
+        array.push(decode_value(reader, depth + 1)?);
+    }
+    Ok(Value::Array(array))
+}
+
+/// A helper function to decode a map of the given length (the keys must be
+/// strings)
+///
+fn decode_map(reader: &mut Reader, length: usize, depth: usize) -> Result<Value> {
+    let mut map = Map::new();
+    for _ in 0..length {
+        let Value::String(key) = decode_value(reader, depth + 1)? else {
+            return Err(anyhow!("MessagePack map keys must be strings."));
+        };
+        map.insert(key, decode_value(reader, depth + 1)?);
+    }
+    Ok(Value::Object(map))
+}
@@ -0,0 +1,725 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module describes the web interface as an OpenAPI document, so that
+//! integrators can browse the endpoints and generate typed clients. Each
+//! body is described by an example that is checked against the type the
+//! endpoint accepts, and the schema of the body is drawn from the example.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::GraphqlRequest;
+
+// Import serde feaures
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the page of the Swagger UI, which loads its scripts from a CDN
+pub const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Vulcan API</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });</script>
+  </body>
+</html>
+"##;
+
+/// A helper structure to describe one endpoint of the web interface
+///
+struct Endpoint {
+    method: &'static str,  // the method of the endpoint, in lower case
+    path: &'static str,    // the path of the endpoint, with parameters in braces
+    summary: &'static str, // a short description of the endpoint
+    id: String,            // the identifier of the operation
+    role: Option<Role>,    // the role required (None if no token is required)
+    query: Vec<(&'static str, &'static str)>, // the name and type of each query parameter
+    body: Option<Value>,   // an example body, if the endpoint takes a JSON body
+}
+
+// Implement key features of the endpoint
+impl Endpoint {
+    /// A function to create an endpoint for observers, named after its path
+    ///
+    fn new(method: &'static str, path: &'static str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            summary,
+            id: String::new(),
+            role: Some(Role::Observer),
+            query: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// A method to name the endpoint and find its role from the request it
+    /// makes
+    ///
+    fn request(mut self, request: Request) -> Self {
+        self.id = request.name().to_string();
+        self.role = Some(request.required_role());
+        self
+    }
+
+    /// A method to name an endpoint that does not make a request (or whose
+    /// request depends on the configuration), with its role
+    ///
+    fn named(mut self, id: &str, role: Option<Role>) -> Self {
+        self.id = id.to_string();
+        self.role = role;
+        self
+    }
+
+    /// A method to rename an endpoint that makes the same request as another
+    ///
+    fn id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    /// A method to add an example body, checked against the type of the
+    /// body, and the request the body makes
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the example is not a valid body.
+    ///
+    fn body<T: DeserializeOwned>(
+        self,
+        example: Value,
+        into: impl FnOnce(T) -> Request,
+    ) -> Result<Self> {
+        let body = serde_json::from_value(example.clone())?;
+        Ok(Self {
+            body: Some(example),
+            ..self.request(into(body))
+        })
+    }
+
+    /// A method to add an example body, checked against the type of the
+    /// body, for an endpoint that is already named
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the example is not a valid body.
+    ///
+    fn example<T: DeserializeOwned>(mut self, example: Value) -> Result<Self> {
+        serde_json::from_value::<T>(example.clone())?;
+        self.body = Some(example);
+        Ok(self)
+    }
+
+    /// A method to add query parameters, each with its name and type
+    ///
+    fn query(mut self, parameters: &[(&'static str, &'static str)]) -> Self {
+        self.query.extend_from_slice(parameters);
+        self
+    }
+
+    /// A method to add the dry run flag of the endpoint
+    ///
+    fn dry_run(self) -> Self {
+        self.query(&[("dryRun", "boolean")])
+    }
+
+    /// A method to add the confirmation token of a destructive endpoint
+    ///
+    fn confirmed(self) -> Self {
+        self.query(&[("confirmToken", "string")])
+    }
+
+    /// A method to describe the endpoint as an OpenAPI operation
+    ///
+    fn operation(&self) -> Value {
+        // Describe each parameter of the path and the query
+        let mut parameters: Vec<Value> = self
+            .path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| {
+                let schema = match name {
+                    "channel" => json!({ "type": "integer" }),
+                    "layer" => json!({ "type": "string", "enum": ["base", "effects", "override"] }),
+                    _ => json!({ "type": "string" }),
+                };
+                json!({ "name": name, "in": "path", "required": true, "schema": schema })
+            })
+            .collect();
+        parameters.extend(self.query.iter().map(
+            |(name, kind)| json!({ "name": name, "in": "query", "schema": { "type": kind } }),
+        ));
+        parameters.push(json!({ "$ref": "#/components/parameters/clientId" }));
+
+        // Compose the operation, with the role in the description
+        let mut operation = json!({
+            "operationId": self.id,
+            "summary": self.summary,
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "The reply to the request.",
+                    "content": {
+                        "application/json": { "schema": { "$ref": "#/components/schemas/Reply" }}
+                    }
+                },
+                "default": {
+                    "description": "The reason the request failed.",
+                    "content": {
+                        "application/json": { "schema": { "$ref": "#/components/schemas/Error" }}
+                    }
+                }
+            }
+        });
+        match self.role {
+            Some(role) => {
+                operation["description"] = json!(format!(
+                    "Requires the {} role.",
+                    serde_json::to_value(role)
+                        .unwrap_or_default()
+                        .as_str()
+                        .unwrap_or("observer")
+                ));
+            }
+            None => operation["security"] = json!([]),
+        }
+        if let Some(example) = &self.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema(example), "example": example }}
+            });
+        }
+        operation
+    }
+}
+
+/// A function to describe every endpoint of the web interface as an OpenAPI
+/// document
+///
+/// # Errors
+///
+/// This function will raise an error if an example body is not valid.
+///
+pub fn document() -> Result<Value> {
+    // Collect the operations under each path
+    let mut paths = Map::new();
+    for endpoint in endpoints()? {
+        let path = paths
+            .entry(endpoint.path.to_string())
+            .or_insert_with(|| json!({}));
+        path[endpoint.method] = endpoint.operation();
+    }
+
+    // Describe the replies from examples
+    let reply = serde_json::to_value(WebReply::success())?;
+    let mut reply_schema = schema(&reply);
+    reply_schema["example"] = reply;
+    let error = serde_json::to_value(WebReply::error(ErrorCode::GroupNotFound {
+        group: "frontWash".into(),
+    }))?;
+    let mut error_schema = schema(&error);
+    error_schema["example"] = error;
+
+    // Compose the document
+    Ok(json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Vulcan",
+            "description": "The web interface of the Vulcan DMX controller.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": format!("/{}", API_VERSION) }],
+        "security": [{ "bearer": [] }, { "apiKey": [] }],
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "x-api-key" },
+            },
+            "parameters": {
+                "clientId": {
+                    "name": "x-client-id",
+                    "in": "header",
+                    "description": "An identifier for the client, for the audit log.",
+                    "schema": { "type": "string", "maxLength": MAX_CLIENT_ID },
+                },
+            },
+            "schemas": {
+                "Reply": reply_schema,
+                "Error": error_schema,
+            },
+        },
+        "paths": paths,
+    }))
+}
+
+/// A helper function to create an endpoint for a GET request
+///
+fn get(path: &'static str, summary: &'static str) -> Endpoint {
+    Endpoint::new("get", path, summary)
+}
+
+/// A helper function to create an endpoint for a POST request
+///
+fn post(path: &'static str, summary: &'static str) -> Endpoint {
+    Endpoint::new("post", path, summary)
+}
+
+/// A helper function to create an endpoint for a PUT request
+///
+fn put(path: &'static str, summary: &'static str) -> Endpoint {
+    Endpoint::new("put", path, summary)
+}
+
+/// A helper function to list every endpoint of the web interface. The paths
+/// follow the filters of the web interface, and the examples are checked
+/// against the type of each body.
+///
+/// # Errors
+///
+/// This function will raise an error if an example body is not valid.
+///
+fn endpoints() -> Result<Vec<Endpoint>> {
+    // Note the examples used by several endpoints
+    let universe = serde_json::to_value(Universe::new())?;
+    let duration = json!({ "secs": 1, "nanos": 0 });
+    let name = String::new();
+
+    // List the endpoints that change the look
+    let mut endpoints = vec![
+        post("/playFade", "Play a fade on a channel")
+            .body(
+                json!({ "channel": 1, "value": 255, "duration": duration }),
+                |fade: Fade| fade.into(),
+            )?
+            .dry_run(),
+        post("/loadUniverse", "Load every channel to a new value")
+            .body(json!(universe), |universe: Universe| universe.into())?
+            .query(&[("expectedVersion", "integer")])
+            .dry_run()
+            .confirmed(),
+        post("/recallScene", "Recall a scene from the configuration")
+            .body(
+                json!({ "name": "warm", "duration": duration }),
+                |recall: SceneRecall| recall.into(),
+            )?
+            .dry_run(),
+        post("/playEffect", "Start an effect on some channels").body(
+            json!({
+                "effect": "sine", "channels": [1, 2, 3], "low": 0, "high": 200, "period": duration
+            }),
+            |effect| Request::PlayEffect { effect },
+        )?,
+        post(
+            "/playPreset",
+            "Start an effect preset from the configuration",
+        )
+        .body(json!({ "name": "slowPulse" }), |preset| {
+            Request::PlayPreset { preset }
+        })?,
+        post("/stopEffect", "Stop effects, holding the current values")
+            .body(json!({ "channels": [1, 2, 3] }), |stop| {
+                Request::StopEffect { stop }
+            })?,
+        post("/tuneEffect", "Tune the effects on some channels").body(
+            json!({ "channels": [1, 2, 3], "intensity": 0.5, "chance": 0.1 }),
+            |tune| Request::TuneEffect { tune },
+        )?,
+        post("/playMovement", "Start a movement on some fixtures").body(
+            json!({
+                "fixtures": ["spotLeft"], "shape": "circle", "pan": 270, "tilt": 90, "radius": 30,
+                "period": duration
+            }),
+            |movement| Request::PlayMovement { movement },
+        )?,
+        post("/playPixels", "Start a pixel generator on a pixel map").body(
+            json!({ "map": "stageWall", "generator": "rainbow", "period": duration }),
+            |play| Request::PlayPixels { play },
+        )?,
+        post("/setPosition", "Move a fixture to a position")
+            .body(
+                json!({ "fixture": "spotLeft", "pan": 270, "tilt": 45.5, "duration": duration }),
+                |position| Request::SetPosition { position },
+            )?
+            .dry_run(),
+        post(
+            "/setColor",
+            "Fade a fixture to a color in CIE xy chromaticity",
+        )
+        .body(
+            json!({ "fixture": "washLeft", "x": 0.3127, "y": 0.329, "brightness": 0.8 }),
+            |color| Request::SetColor { color },
+        )?
+        .dry_run(),
+        post(
+            "/playFan",
+            "Fade a group with spread values and start times",
+        )
+        .body(
+            json!({
+                "group": "frontWash", "from": 0, "to": 255, "duration": duration, "spread": duration
+            }),
+            |fan| Request::PlayFan { fan },
+        )?,
+        put("/group/{name}/level", "Change the level of a group").body(
+            json!({ "level": 50 }),
+            |level| Request::SetGroupLevel {
+                name: name.clone(),
+                level,
+            },
+        )?,
+        put(
+            "/group/{name}/blackout",
+            "Black out a group (or release it)",
+        )
+        .body(json!({ "isBlackout": true }), |blackout| {
+            Request::SetGroupBlackout {
+                name: name.clone(),
+                blackout,
+            }
+        })?,
+        put(
+            "/fixture/{name}/intensity",
+            "Change the virtual intensity of a fixture",
+        )
+        .body(json!({ "level": 50 }), |level| {
+            Request::SetFixtureIntensity {
+                name: name.clone(),
+                level,
+            }
+        })?,
+        post("/override", "Hold some channels above every playback")
+            .body(json!({ "values": { "1": 255, "5": 128 }}), |values| {
+                Request::SetOverride { values }
+            })?,
+        post("/releaseOverride", "Release manual overrides").body(
+            json!({ "channels": [1, 5], "duration": duration }),
+            |release| Request::ReleaseOverride { release },
+        )?,
+        put(
+            "/layer/{layer}",
+            "Change the opacity, mute, or solo of a layer",
+        )
+        .body(json!({ "opacity": 50 }), |update| Request::SetLayer {
+            layer: Layer::Effects,
+            update,
+        })?,
+        post("/tap", "Tap the beat of the beat clock").request(Request::Tap),
+        put("/tempo", "Change the tempo of the beat clock")
+            .body(json!({ "bpm": 128 }), |tempo| Request::SetTempo { tempo })?,
+        put(
+            "/fadeMultiplier",
+            "Change the multiplier of every fade duration",
+        )
+        .body(json!({ "multiplier": 2 }), |multiplier| {
+            Request::SetFadeMultiplier { multiplier }
+        })?,
+        put("/effectSpeed", "Change the speed of the effects")
+            .body(json!({ "master": 0.5 }), |speed| Request::SetEffectSpeed {
+                speed,
+            })?,
+        put(
+            "/preheat",
+            "Turn the preheat of the warmed channels on or off",
+        )
+        .body(json!({ "isEnabled": false }), |preheat| {
+            Request::SetPreheat { preheat }
+        })?,
+        post("/allStop", "Set every channel to its emergency value")
+            .request(Request::AllStop)
+            .confirmed(),
+        post("/undo", "Crossfade back to the previous look").request(Request::Undo),
+        post("/redo", "Crossfade forward to the look that was undone").request(Request::Redo),
+        post("/go", "Start the next cue of the main cue list")
+            .request(Request::Go { list: name.clone() }),
+        post("/go/{list}", "Start the next cue of a cue list")
+            .request(Request::Go { list: name.clone() })
+            .id("goList"),
+        post("/resetCues", "Return the main cue list to the first cue")
+            .request(Request::ResetCues { list: name.clone() }),
+        post("/resetCues/{list}", "Return a cue list to the first cue")
+            .request(Request::ResetCues { list: name.clone() })
+            .id("resetCuesList"),
+        post("/timeline/{name}", "Play, pause, or stop a timeline").body(
+            json!({ "action": "play" }),
+            |control| Request::ControlTimeline {
+                name: name.clone(),
+                control,
+            },
+        )?,
+        post(
+            "/trigger/{name}",
+            "Run the action of a trigger from the configuration",
+        )
+        .named("trigger", Some(Role::Operator)),
+        post("/batch", "Apply several requests in order")
+            .named("batch", Some(Role::Operator))
+            .example::<Vec<BatchRequest>>(json!([
+                { "playFade": { "channel": 1, "value": 255 }},
+                { "recallScene": { "name": "warm" }},
+                "undo"
+            ]))?
+            .dry_run()
+            .confirmed(),
+        post(
+            "/defineAllStop",
+            "Change the emergency value of every channel",
+        )
+        .body(json!(universe), |universe| Request::DefineAllStop {
+            universe,
+        })?
+        .confirmed(),
+    ];
+
+    // List the endpoints that change the configuration or the program
+    endpoints.extend(vec![
+        post(
+            "/labels",
+            "Add, replace, or remove the labels of channels and fixtures",
+        )
+        .body(
+            json!({ "channels": { "1": { "name": "Downstage left" }}}),
+            |labels| Request::SetLabels { labels },
+        )?,
+        put("/palette/{name}", "Add or replace a palette").body(
+            json!({ "values": { "red": 255, "green": 128 }}),
+            |palette| Request::SetPalette {
+                name: name.clone(),
+                palette,
+            },
+        )?,
+        put("/patch/{name}", "Change the mode and address of a fixture").body(
+            json!({ "address": 10 }),
+            |patch| Request::PatchFixture {
+                name: name.clone(),
+                patch,
+            },
+        )?,
+        put("/logLevel", "Change the log level")
+            .named("setLogLevel", Some(Role::Operator))
+            .example::<super::LogLevelUpdate>(json!({ "level": "Debug" }))?,
+        post("/reloadConfig", "Reload the configuration file")
+            .request(Request::ReloadConfig)
+            .confirmed(),
+        post(
+            "/universe/import",
+            "Load every channel from an exported file",
+        )
+        .named("importUniverse", Some(Role::Operator))
+        .query(&[("expectedVersion", "integer")])
+        .dry_run()
+        .confirmed(),
+        post(
+            "/backup/flush",
+            "Write the universe to the backup right away",
+        )
+        .request(Request::FlushBackup),
+        post("/backup/restore", "Load the universe from the backup")
+            .request(Request::RestoreBackup {
+                query: RestoreQuery::default(),
+            })
+            .query(&[("isForced", "boolean"), ("expectedVersion", "integer")]),
+        post("/heartbeat", "Note that a client is still connected")
+            .body(json!({ "clientId": "lobby-kiosk" }), |heartbeat| {
+                Request::Heartbeat { heartbeat }
+            })?,
+        post("/close", "Close the program")
+            .request(Request::Close)
+            .confirmed(),
+    ]);
+
+    // List the endpoints that read the state
+    endpoints.extend(vec![
+        get("/universe", "Get the value of every channel").request(Request::GetUniverse),
+        get("/universe/export", "Export the universe as a file")
+            .named("exportUniverse", Some(Role::Observer))
+            .query(&[("format", "string")]),
+        get("/scenes", "List the scenes of the configuration")
+            .request(Request::GetScenes {
+                query: SceneQuery::default(),
+            })
+            .query(&[("tag", "string"), ("category", "string")]),
+        get("/group/{name}/level", "Get the level of a group")
+            .request(Request::GetGroupLevel { name: name.clone() }),
+        get(
+            "/fixture/{name}/intensity",
+            "Get the virtual intensity of a fixture",
+        )
+        .request(Request::GetFixtureIntensity { name: name.clone() }),
+        get("/layers", "Get the opacity, mute, and solo of every layer")
+            .request(Request::GetLayers),
+        get("/tempo", "Get the tempo of the beat clock").request(Request::GetTempo),
+        get(
+            "/fadeMultiplier",
+            "Get the multiplier of every fade duration",
+        )
+        .request(Request::GetFadeMultiplier),
+        get("/effectSpeed", "Get the speed of the effects").request(Request::GetEffectSpeed),
+        get("/preheat", "Get the preheat of the warmed channels").request(Request::GetPreheat),
+        get("/labels", "Get the labels of channels and fixtures").request(Request::GetLabels),
+        get("/palettes", "Get every palette").request(Request::GetPalettes),
+        get("/patch", "Get the patch of every fixture").request(Request::GetPatch),
+        get("/cueList/status", "Get the state of the main cue list")
+            .request(Request::GetCueList { list: name.clone() }),
+        get("/cueList/{list}/status", "Get the state of a cue list")
+            .request(Request::GetCueList { list: name.clone() })
+            .id("getCueListByName"),
+        get("/timeline/{name}", "Get the state of a timeline")
+            .request(Request::GetTimeline { name: name.clone() }),
+        get("/status", "Get the status of the program").request(Request::GetStatus),
+        get("/capabilities", "Get the features of the program").request(Request::GetCapabilities),
+        get("/audit", "Search the audit log")
+            .request(Request::GetAudit {
+                query: AuditQuery::default(),
+            })
+            .query(&[
+                ("since", "integer"),
+                ("identity", "string"),
+                ("clientId", "string"),
+                ("request", "string"),
+                ("limit", "integer"),
+            ]),
+        get("/events", "Read the event log")
+            .request(Request::GetEvents {
+                query: EventQuery::default(),
+            })
+            .query(&[
+                ("since", "integer"),
+                ("event", "string"),
+                ("limit", "integer"),
+            ]),
+        get("/channelStats", "Get the statistics of the channels")
+            .request(Request::GetChannelStats {
+                query: ChannelStatsQuery::default(),
+            })
+            .query(&[("channel", "integer"), ("isChanged", "boolean")]),
+        get("/history/{channel}", "Get the recent values of a channel")
+            .request(Request::GetChannelHistory {
+                channel: 1,
+                query: HistoryQuery::default(),
+            })
+            .query(&[("range", "integer")]),
+        get("/backup/status", "Get the state of the backup").request(Request::GetBackupStatus),
+        get("/clients", "List the clients seen recently").request(Request::GetClients),
+        get("/changes", "Wait for the next change of the state")
+            .named("getChanges", Some(Role::Observer))
+            .query(&[("since", "integer"), ("timeout", "integer")]),
+        get("/ws", "Open the websocket command channel")
+            .named("commandChannel", Some(Role::Observer)),
+        get("/debug/frames", "Capture the frames written to the output")
+            .named("captureFrames", Some(Role::Admin))
+            .query(&[
+                ("count", "integer"),
+                ("timeout", "integer"),
+                ("pcap", "boolean"),
+            ]),
+        post("/graphql", "Query the state with GraphQL")
+            .named("graphql", Some(Role::Observer))
+            .example::<GraphqlRequest>(json!({ "query": "{ status { driver activeFades } }" }))?,
+        get("/health", "Check the program is running").named("health", None),
+        get("/ready", "Check the program is ready to control the lights").named("ready", None),
+        get("/openapi.json", "Get this description of the web interface").named("openapi", None),
+        get(
+            "/docs",
+            "Browse this description in the Swagger UI, if enabled",
+        )
+        .named("docs", None),
+    ]);
+
+    // Return the complete list
+    Ok(endpoints)
+}
+
+/// A helper function to draw a JSON schema from an example value
+///
+fn schema(example: &Value) -> Value {
+    match example {
+        Value::Null => json!({ "nullable": true }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => json!({
+            "type": "array",
+            "items": items.first().map(schema).unwrap_or_else(|| json!({})),
+        }),
+        Value::Object(fields) => json!({
+            "type": "object",
+            "properties": fields
+                .iter()
+                .map(|(name, value)| (name.clone(), schema(value)))
+                .collect::<Map<String, Value>>(),
+        }),
+    }
+}
+
+// Tests of the OpenAPI module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that every example is valid and every path of the web interface
+    // is described
+    #[test]
+    fn describe_every_path() {
+        // Check the document and one operation
+        let document = document().unwrap();
+        let operation = &document["paths"]["/group/{name}/level"]["put"];
+        assert_eq!(operation["operationId"], "setGroupLevel");
+        assert_eq!(operation["description"], "Requires the operator role.");
+        assert_eq!(operation["parameters"][0]["name"], "name");
+        assert_eq!(
+            operation["requestBody"]["content"]["application/json"]["schema"]["properties"]["level"]
+                ["type"],
+            "integer"
+        );
+
+        // Check that each operation has its own identifier
+        let mut ids: Vec<&Value> = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|path| path.as_object().unwrap().values())
+            .map(|operation| &operation["operationId"])
+            .collect();
+        let count = ids.len();
+        ids.sort_by_key(|id| id.to_string());
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+
+        // Check that each path segment of the web interface is described
+        let paths: Vec<&String> = document["paths"].as_object().unwrap().keys().collect();
+        for source in include_str!("mod.rs").split("warp::path(\"").skip(1) {
+            let segment = source.split('"').next().unwrap();
+            assert!(
+                paths
+                    .iter()
+                    .any(|path| path.split('/').any(|part| part == segment)),
+                "{} is not described",
+                segment
+            );
+        }
+    }
+}
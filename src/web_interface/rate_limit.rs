@@ -0,0 +1,160 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the rate limit of the web interface, so that one
+//! misbehaving client can't flood the system interface and delay requests
+//! from everyone else.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Define the rate limit constants
+const MAX_CLIENTS: usize = 1024; // the number of clients to track before forgetting idle clients
+
+/// A structure to limit the rate of requests from each client with a token
+/// bucket. The limiter can be cloned to share the buckets.
+///
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,  // the requests added to each bucket per second
+    burst: f64, // the size of each bucket
+    buckets: Arc<Mutex<FnvHashMap<String, Bucket>>>, // the bucket of each client
+}
+
+/// A helper structure to hold the requests available to one client
+///
+struct Bucket {
+    tokens: f64,      // the requests available as of the last update
+    updated: Instant, // the time of the last update
+}
+
+// Implement key features of the rate limiter
+impl RateLimiter {
+    /// A function to create a new rate limiter
+    ///
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            rate: config.requests_per_second as f64,
+            burst: config.burst.max(1) as f64,
+            buckets: Arc::new(Mutex::new(FnvHashMap::default())),
+        }
+    }
+
+    /// A method to take one request from the bucket of the client
+    ///
+    /// # Errors
+    ///
+    /// This function will return the time until the next request is
+    /// available if the bucket is empty.
+    ///
+    pub fn check(&self, client: &Client, now: Instant) -> Result<(), Duration> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+
+        // Forget any clients whose buckets have refilled
+        if buckets.len() >= MAX_CLIENTS {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+        }
+
+        // Refill the bucket for the time since the last request
+        let bucket = buckets.entry(RateLimiter::key(client)).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+
+        // Take a request from the bucket, if available
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.rate > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+
+    /// A helper method to find the requests in a bucket at the given time
+    ///
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    /// A helper function to identify the client: by token if there is one,
+    /// and otherwise by address (without the port, which changes with each
+    /// connection)
+    ///
+    fn key(client: &Client) -> String {
+        if client.identity != "anonymous" {
+            return client.identity.clone();
+        }
+        match client.address.as_deref() {
+            Some(address) => address
+                .parse::<SocketAddr>()
+                .map(|address| address.ip().to_string())
+                .unwrap_or_else(|_| address.to_string()),
+            None => "unix".to_string(),
+        }
+    }
+}
+
+// Tests of the rate limit module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that each client has a separate bucket that refills over time
+    #[test]
+    fn limit_each_client() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_second: 10,
+            burst: 2,
+        });
+        let client = |address: &str| Client {
+            role: Role::Admin,
+            identity: "anonymous".to_string(),
+            address: Some(address.to_string()),
+            client_id: None,
+        };
+        let start = Instant::now();
+
+        // The burst is available at once, even from new connections
+        assert!(limiter.check(&client("10.0.0.1:5000"), start).is_ok());
+        assert!(limiter.check(&client("10.0.0.1:5001"), start).is_ok());
+        let wait = limiter.check(&client("10.0.0.1:5002"), start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(100));
+
+        // Other clients are unaffected
+        assert!(limiter.check(&client("10.0.0.2:5000"), start).is_ok());
+
+        // The bucket refills at the rate
+        let later = start + Duration::from_millis(100);
+        assert!(limiter.check(&client("10.0.0.1:5003"), later).is_ok());
+        assert!(limiter.check(&client("10.0.0.1:5004"), later).is_err());
+    }
+}
@@ -0,0 +1,911 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a command channel over a websocket, so that a
+//! client can send requests as JSON-RPC calls without the cost of a new
+//! http request for each one (such as the dozens of fades of a fader drag).
+//! The same socket streams each change to the output as a notification.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import other structures into this module
+use super::changes::ChangeTracker;
+use super::confirm::Confirmations;
+use super::format::Format;
+use super::rate_limit::RateLimiter;
+use super::{NEXT_REQUEST_ID, WebInterface};
+
+// Import standard library features
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+// Import Tokio and warp features
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf};
+use tokio::sync::{OwnedSemaphorePermit, mpsc};
+use warp::http;
+use warp::hyper::upgrade::{OnUpgrade, Upgraded};
+
+// Import tracing features
+use tracing::{Instrument, debug, debug_span};
+
+// Import serde features
+use serde_json::Value;
+
+// Define the key of the websocket handshake
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// Define the longest message, in bytes
+const MAX_MESSAGE_LENGTH: usize = 1024 * 16;
+
+// Define the number of frames waiting to be written
+const FRAME_QUEUE: usize = 64;
+
+// Define the longest wait for a change before waiting again
+const CHANGES_WAIT: Duration = Duration::from_secs(25);
+
+// Define the opcodes of each frame
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+// Define the status codes of a closed connection
+const NORMAL_CLOSURE: u16 = 1000;
+const PROTOCOL_ERROR: u16 = 1002;
+const UNSUPPORTED_DATA: u16 = 1003;
+const INVALID_DATA: u16 = 1007;
+const MESSAGE_TOO_BIG: u16 = 1009;
+
+// Define the error codes of JSON-RPC
+const PARSE_ERROR: i32 = -32700;
+const INVALID_CALL: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const REQUEST_FAILED: i32 = -32000;
+
+/// A structure to accept websocket connections and answer the calls from
+/// each one. The channel can be cloned to share it between connections.
+///
+#[derive(Clone)]
+pub struct CommandChannel {
+    web_send: WebSend,            // send line to the system interface
+    tracker: ChangeTracker,       // the state versions of the output
+    limiter: Option<RateLimiter>, // the rate limit of each call, if enabled
+    confirmations: Confirmations, // the requests that need confirmation and their tokens
+    max_duration: Duration,       // the longest fade that will be accepted
+}
+
+/// A helper structure to receive one call (or notification, without an id)
+///
+#[derive(Deserialize)]
+struct Call {
+    jsonrpc: String, // the version of the protocol, always 2.0
+    #[serde(default)]
+    id: Option<Value>, // the id to include in the response, if any
+    method: String,  // the name of the request, as in a batch
+    #[serde(default)]
+    params: Option<Value>, // the body of the request, if any
+    #[serde(default, rename = "confirmToken")]
+    confirm_token: Option<String>, // the token from the first call, if needed
+}
+
+/// A helper structure to hold the request of a valid call
+///
+struct Command {
+    request: Request,              // the request of the call
+    confirm_token: Option<String>, // the token from the first call, if any
+}
+
+/// A helper structure to send the response to one call
+///
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str, // the version of the protocol, always 2.0
+    id: Value,             // the id of the call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<WebReply>, // the reply to a successful request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<CallError>, // the failure of the call
+}
+
+/// A helper structure to describe the failure of a call
+///
+#[derive(Clone, PartialEq, Debug, Serialize)]
+struct CallError {
+    code: i32,       // the JSON-RPC code of the failure
+    message: String, // a message describing the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>, // the details of the failure, if any
+}
+
+/// A helper structure to send the channels that changed
+///
+#[derive(Serialize)]
+struct Notification {
+    jsonrpc: &'static str, // the version of the protocol, always 2.0
+    method: &'static str,  // the name of the notification, always changes
+    params: Changes,       // the changed channels and the current state version
+}
+
+/// A helper structure to hold one frame from the client
+///
+#[derive(PartialEq, Eq, Debug)]
+struct Frame {
+    is_final: bool,   // a flag to indicate the last frame of a message
+    opcode: u8,       // the kind of the frame
+    payload: Vec<u8>, // the unmasked content of the frame
+}
+
+/// A helper structure to read and write the upgraded connection with the
+/// Tokio traits
+///
+struct Socket(Upgraded);
+
+// Implement key features of the command channel
+impl CommandChannel {
+    /// A function to create a new command channel
+    ///
+    pub fn new(
+        web_send: WebSend,
+        tracker: ChangeTracker,
+        limiter: Option<RateLimiter>,
+        confirmations: Confirmations,
+        max_duration: Duration,
+    ) -> Self {
+        Self {
+            web_send,
+            tracker,
+            limiter,
+            confirmations,
+            max_duration,
+        }
+    }
+
+    /// A method to accept the websocket handshake and serve the connection
+    /// in the background once it is upgraded
+    ///
+    pub fn upgrade(
+        self,
+        client: Client,
+        key: &str,
+        on_upgrade: Option<OnUpgrade>,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> warp::reply::Response {
+        // Only http/1 connections can be upgraded
+        let Some(on_upgrade) = on_upgrade else {
+            return WebInterface::compose_reply(
+                WebReply::error(ErrorCode::InvalidRequest {
+                    fields: vec![FieldError::new("upgrade", "Must be an http/1 connection.")],
+                }),
+                Format::Json,
+            );
+        };
+        tokio::spawn(self.serve(client, on_upgrade, permit));
+
+        // Reply to switch to the websocket
+        let mut response = warp::reply::Response::default();
+        *response.status_mut() = http::StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        headers.insert(
+            http::header::CONNECTION,
+            http::HeaderValue::from_static("upgrade"),
+        );
+        headers.insert(
+            http::header::UPGRADE,
+            http::HeaderValue::from_static("websocket"),
+        );
+        if let Ok(accept) = http::HeaderValue::from_str(&accept_key(key)) {
+            headers.insert(http::header::SEC_WEBSOCKET_ACCEPT, accept);
+        }
+        response
+    }
+
+    /// A helper method to answer the calls of the connection and stream the
+    /// changes, until either side closes it. The permit, if any, is held
+    /// for as long as the connection is open.
+    ///
+    async fn serve(
+        self,
+        client: Client,
+        on_upgrade: OnUpgrade,
+        _permit: Option<OwnedSemaphorePermit>,
+    ) {
+        // Wait for the connection to be upgraded
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(error) => {
+                debug!("Unable to upgrade websocket: {}.", error);
+                return;
+            }
+        };
+        debug!("Websocket connected for {}.", client.identity);
+        let (mut reader, mut writer) = tokio::io::split(Socket(upgraded));
+
+        // Write each frame in order, until every sender has closed
+        let (send_frame, mut frames) = mpsc::channel::<Vec<u8>>(FRAME_QUEUE);
+        let write_frames = tokio::spawn(async move {
+            while let Some(frame) = frames.recv().await {
+                if writer.write_all(&frame).await.is_err() {
+                    return;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        // Stream the changes alongside the calls
+        let stream_changes = tokio::spawn(stream_changes(self.tracker.clone(), send_frame.clone()));
+        let close = self.receive(&client, &mut reader, &send_frame).await;
+        stream_changes.abort();
+        let _ = stream_changes.await;
+
+        // Close the connection, with the reason if there is one
+        if let Some(code) = close {
+            let _ = send_frame
+                .send(encode_frame(CLOSE, &code.to_be_bytes()))
+                .await;
+        }
+        drop(send_frame);
+        let _ = write_frames.await;
+        debug!("Websocket closed for {}.", client.identity);
+    }
+
+    /// A helper method to read each message from the client and answer the
+    /// calls, returning the status to close the connection with (or none if
+    /// the connection was lost)
+    ///
+    async fn receive(
+        &self,
+        client: &Client,
+        reader: &mut ReadHalf<Socket>,
+        send_frame: &mpsc::Sender<Vec<u8>>,
+    ) -> Option<u16> {
+        let mut buffer = Vec::new();
+        let mut message: Option<(u8, Vec<u8>)> = None;
+        loop {
+            // Read until there is a complete frame
+            let (frame, length) = match decode_frame(&buffer) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => {
+                    let mut chunk = [0; 4096];
+                    match reader.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return None,
+                        Ok(count) => buffer.extend_from_slice(&chunk[..count]),
+                    }
+                    continue;
+                }
+                Err(code) => return Some(code),
+            };
+            buffer.drain(..length);
+
+            // Answer the control frames, which may arrive within a message
+            let (opcode, payload) = match (frame.opcode, message.take()) {
+                (PING, message_part) => {
+                    message = message_part;
+                    send_frame
+                        .send(encode_frame(PONG, &frame.payload))
+                        .await
+                        .ok()?;
+                    continue;
+                }
+                (PONG, message_part) => {
+                    message = message_part;
+                    continue;
+                }
+                (CLOSE, _) => return Some(NORMAL_CLOSURE),
+
+                // Join the frames of a message
+                (CONTINUATION, Some((opcode, mut payload))) => {
+                    payload.extend_from_slice(&frame.payload);
+                    (opcode, payload)
+                }
+                (CONTINUATION, None) | (_, Some(_)) => return Some(PROTOCOL_ERROR),
+                (opcode, None) => (opcode, frame.payload),
+            };
+            if payload.len() > MAX_MESSAGE_LENGTH {
+                return Some(MESSAGE_TOO_BIG);
+            }
+            if !frame.is_final {
+                message = Some((opcode, payload));
+                continue;
+            }
+
+            // Answer the call of each text message
+            if opcode == BINARY {
+                return Some(UNSUPPORTED_DATA);
+            }
+            let Ok(text) = String::from_utf8(payload) else {
+                return Some(INVALID_DATA);
+            };
+            if let Some(response) = self.answer(client, &text).await {
+                send_frame
+                    .send(encode_frame(TEXT, response.as_bytes()))
+                    .await
+                    .ok()?;
+            }
+        }
+    }
+
+    /// A helper method to process one call, returning the response (or
+    /// none for a notification)
+    ///
+    async fn answer(&self, client: &Client, text: &str) -> Option<String> {
+        // Process the request of the call, if it is valid and allowed
+        let (id, command) = parse_call(text, self.max_duration);
+        let result = match command {
+            Ok(command) => {
+                let span = debug_span!(
+                    "request",
+                    id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+                    kind = command.request.name()
+                );
+                let reply = match self.check_call(client, &command) {
+                    Ok(()) => {
+                        WebInterface::send_request(client.clone(), &self.web_send, command.request)
+                            .instrument(span)
+                            .await
+                    }
+                    Err(error) => WebReply::error(error),
+                };
+                if reply.is_success() {
+                    Ok(reply)
+                } else {
+                    Err(CallError {
+                        code: REQUEST_FAILED,
+                        message: "The request failed.".to_string(),
+                        data: serde_json::to_value(&reply).ok(),
+                    })
+                }
+            }
+            Err(error) => Err(error),
+        };
+
+        // Compose the response, if the call has an id
+        let (result, error) = match result {
+            Ok(reply) => (Some(reply), None),
+            Err(error) => (None, Some(error)),
+        };
+        serde_json::to_string(&Response {
+            jsonrpc: "2.0",
+            id: id?,
+            result,
+            error,
+        })
+        .ok()
+    }
+
+    /// A helper method to take one call from the bucket of the client (if
+    /// there is a rate limit), then check the confirmation of the request
+    /// like an http request (if the client is allowed to make it)
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the client has made too many
+    /// requests, or if the request needs confirmation and the token is
+    /// missing or not valid.
+    ///
+    fn check_call(&self, client: &Client, command: &Command) -> Result<(), ErrorCode> {
+        let now = Instant::now();
+        if let Some(Err(wait)) = self
+            .limiter
+            .as_ref()
+            .map(|limiter| limiter.check(client, now))
+        {
+            return Err(ErrorCode::RateLimited {
+                retry_after: wait.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+        if client.role >= command.request.required_role() {
+            let token = command.confirm_token.as_deref();
+            self.confirmations
+                .check(client, &command.request, token, now)?;
+        }
+        Ok(())
+    }
+}
+
+// Implement the Tokio reading trait for the socket
+impl AsyncRead for Socket {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut read = warp::hyper::rt::ReadBuf::new(buf.initialize_unfilled());
+        match warp::hyper::rt::Read::poll_read(Pin::new(&mut self.0), context, read.unfilled()) {
+            Poll::Ready(Ok(())) => {
+                let count = read.filled().len();
+                buf.advance(count);
+                Poll::Ready(Ok(()))
+            }
+            other => other,
+        }
+    }
+}
+
+// Implement the Tokio writing trait for the socket
+impl AsyncWrite for Socket {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        warp::hyper::rt::Write::poll_write(Pin::new(&mut self.0), context, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        warp::hyper::rt::Write::poll_flush(Pin::new(&mut self.0), context)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        warp::hyper::rt::Write::poll_shutdown(Pin::new(&mut self.0), context)
+    }
+}
+
+/// A helper function to send the channels that change as notifications,
+/// starting with every channel, until the connection closes
+///
+async fn stream_changes(tracker: ChangeTracker, send_frame: mpsc::Sender<Vec<u8>>) {
+    let mut version = 0;
+    loop {
+        // Wait for a new version (waiting again if there is none yet)
+        let changes = tracker.wait(version, CHANGES_WAIT).await;
+        if changes.version == version {
+            continue;
+        }
+        version = changes.version;
+
+        // Send the changes
+        let notification = Notification {
+            jsonrpc: "2.0",
+            method: "changes",
+            params: changes,
+        };
+        let text = serde_json::to_string(&notification).unwrap_or_default();
+        if send_frame
+            .send(encode_frame(TEXT, text.as_bytes()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// A helper function to convert the text of a call into a command,
+/// returning the id of the call (none for a notification, or null if the
+/// id could not be read)
+///
+fn parse_call(text: &str, max_duration: Duration) -> (Option<Value>, Result<Command, CallError>) {
+    let failure = |code, message: &str, data| CallError {
+        code,
+        message: message.to_string(),
+        data,
+    };
+
+    // Read the call
+    let Ok(value) = serde_json::from_str::<Value>(text) else {
+        return (
+            Some(Value::Null),
+            Err(failure(PARSE_ERROR, "Parse error.", None)),
+        );
+    };
+    let call = match serde_json::from_value::<Call>(value) {
+        Ok(call) if call.jsonrpc == "2.0" => call,
+        _ => {
+            return (
+                Some(Value::Null),
+                Err(failure(INVALID_CALL, "Invalid request.", None)),
+            );
+        }
+    };
+
+    // Read the request in the same format as a batch (with no params for
+    // requests without a body)
+    let body = match call.params {
+        Some(params) => Value::Object(serde_json::Map::from_iter([(call.method, params)])),
+        None => Value::String(call.method),
+    };
+    let request = match serde_json::from_value::<BatchRequest>(body) {
+        Ok(request) => request,
+        Err(error) if error.to_string().starts_with("unknown variant") => {
+            return (
+                call.id,
+                Err(failure(METHOD_NOT_FOUND, "Method not found.", None)),
+            );
+        }
+        Err(error) => {
+            let data = Value::String(error.to_string());
+            return (
+                call.id,
+                Err(failure(INVALID_PARAMS, "Invalid params.", Some(data))),
+            );
+        }
+    };
+
+    // Check the fields of the request
+    let fields = request.validate(max_duration);
+    if !fields.is_empty() {
+        let data = serde_json::to_value(fields).ok();
+        return (
+            call.id,
+            Err(failure(INVALID_PARAMS, "Invalid params.", data)),
+        );
+    }
+    let command = Command {
+        request: Request::from(request),
+        confirm_token: call.confirm_token,
+    };
+    (call.id, Ok(command))
+}
+
+/// A helper function to read the first frame from the buffer, returning
+/// the frame and its length in the buffer (or none if the frame is not
+/// complete)
+///
+/// # Errors
+///
+/// This function will return the status to close the connection with if
+/// the frame is not valid from a client.
+///
+fn decode_frame(buffer: &[u8]) -> Result<Option<(Frame, usize)>, u16> {
+    // Read the first two bytes of the header
+    let [first, second, ..] = *buffer else {
+        return Ok(None);
+    };
+    let is_final = first & 0x80 != 0;
+    let opcode = first & 0x0F;
+    if first & 0x70 != 0
+        || !matches!(opcode, CONTINUATION | TEXT | BINARY | CLOSE | PING | PONG)
+        || second & 0x80 == 0
+    {
+        return Err(PROTOCOL_ERROR); // reserved bits, unknown opcodes, and unmasked frames
+    }
+
+    // Read the length of the payload
+    let (length, start) = match second & 0x7F {
+        126 => match buffer.get(2..4) {
+            Some(bytes) => (u16::from_be_bytes([bytes[0], bytes[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match buffer.get(2..10) {
+            Some(bytes) => {
+                let mut length = [0; 8];
+                length.copy_from_slice(bytes);
+                (u64::from_be_bytes(length), 10)
+            }
+            None => return Ok(None),
+        },
+        length => (length as u64, 2),
+    };
+    if opcode >= CLOSE && (length > 125 || !is_final) {
+        return Err(PROTOCOL_ERROR);
+    }
+    if length > MAX_MESSAGE_LENGTH as u64 {
+        return Err(MESSAGE_TOO_BIG);
+    }
+
+    // Unmask the payload, once it has arrived
+    let end = start + 4 + length as usize;
+    let Some(masked) = buffer.get(start + 4..end) else {
+        return Ok(None);
+    };
+    let mask = &buffer[start..start + 4];
+    let payload = masked
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| byte ^ mask[index % 4])
+        .collect();
+    Ok(Some((
+        Frame {
+            is_final,
+            opcode,
+            payload,
+        },
+        end,
+    )))
+}
+
+/// A helper function to create a complete frame from the server (which
+/// is never masked)
+///
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A helper function to compute the accept header from the key of the
+/// websocket handshake
+///
+fn accept_key(key: &str) -> String {
+    encode_base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+/// A helper function to compute the SHA-1 digest of the data (only used for
+/// the handshake, which does not rely on its security)
+///
+fn sha1(data: &[u8]) -> [u8; 20] {
+    // Pad the data to a multiple of 64 bytes, ending with its length in bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    // Process each block
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (index, word) in block.chunks(4).enumerate() {
+            words[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..80 {
+            words[index] =
+                (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16])
+                    .rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    // Return the digest
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+/// A helper function to encode the data as standard base64, with padding
+///
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Tests of the websocket module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test the handshake and the frames from the examples of RFC 6455
+    #[test]
+    fn encode_frames() {
+        // Check the accept header
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        // Check a masked frame, one byte at a time
+        let masked = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        assert_eq!(decode_frame(&masked[..5]), Ok(None));
+        let frame = Frame {
+            is_final: true,
+            opcode: TEXT,
+            payload: b"Hello".to_vec(),
+        };
+        assert_eq!(decode_frame(&masked), Ok(Some((frame, 11))));
+
+        // Unmasked frames are rejected, and replies are never masked
+        let unmasked = encode_frame(TEXT, b"Hello");
+        assert_eq!(unmasked, [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]);
+        assert_eq!(decode_frame(&unmasked), Err(PROTOCOL_ERROR));
+        assert_eq!(
+            &encode_frame(BINARY, &[0; 256])[..4],
+            &[0x82, 126, 0x01, 0x00]
+        );
+    }
+
+    // Test that a call that needs confirmation only runs with its token
+    #[tokio::test]
+    async fn confirm_calls() {
+        // Answer every request that reaches the system interface
+        let (web_send, mut requests) = WebSend::new();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                request.reply_to.send(WebReply::success()).unwrap_or(());
+            }
+        });
+
+        // Create a channel where the all stop needs confirmation
+        let (_frames, receiver) = tokio::sync::watch::channel(Universe::new());
+        let confirmations = Confirmations::new(Some(&ConfirmConfig {
+            requests: vec!["allStop".to_string()],
+            timeout: 30,
+        }));
+        let channel = CommandChannel::new(
+            web_send,
+            ChangeTracker::start(receiver),
+            None,
+            confirmations,
+            Duration::from_secs(3600),
+        );
+        let client = Client::internal();
+        // The first call is refused with a token
+        let call = r#"{"jsonrpc": "2.0", "id": 1, "method": "allStop"}"#;
+        let response = channel.answer(&client, call).await.unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["error"]["code"], REQUEST_FAILED);
+        let error = &response["error"]["data"]["error"];
+        assert_eq!(error["code"], "confirmationRequired");
+        let token = error["confirmToken"].as_str().unwrap();
+
+        // The call runs once it is repeated with the token
+        let call = format!(
+            r#"{{"jsonrpc": "2.0", "id": 2, "method": "allStop", "confirmToken": "{}"}}"#,
+            token
+        );
+        let response = channel.answer(&client, &call).await.unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"]["generic"]["isValid"], true);
+    }
+
+    // Test that each role may only make the calls it is permitted (the
+    // read-only requests are not calls, so every call needs an operator)
+    #[tokio::test]
+    async fn role_gating() {
+        // Answer every request that reaches the system interface
+        let (web_send, mut requests) = WebSend::new();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                request.reply_to.send(WebReply::success()).unwrap_or(());
+            }
+        });
+        let (_frames, receiver) = tokio::sync::watch::channel(Universe::new());
+        let channel = CommandChannel::new(
+            web_send,
+            ChangeTracker::start(receiver),
+            None,
+            Confirmations::new(None),
+            Duration::from_secs(3600),
+        );
+
+        // Try two show control calls and an admin call with each role
+        let universe = serde_json::to_string(&Universe::new()).unwrap();
+        let calls = [
+            (
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "playFade",
+                    "params": {"channel": 1, "value": 255}}"#
+                    .to_string(),
+                Role::Operator,
+            ),
+            (
+                r#"{"jsonrpc": "2.0", "id": 2, "method": "allStop"}"#.to_string(),
+                Role::Operator,
+            ),
+            (
+                format!(
+                    r#"{{"jsonrpc": "2.0", "id": 3, "method": "defineAllStop", "params": {}}}"#,
+                    universe
+                ),
+                Role::Admin,
+            ),
+        ];
+        for role in [Role::Observer, Role::Operator, Role::Admin] {
+            let client = Client::from_token("token", role, None);
+            for (call, required_role) in calls.iter() {
+                let response = channel.answer(&client, call).await.unwrap();
+                let response: Value = serde_json::from_str(&response).unwrap();
+                if role >= *required_role {
+                    assert_eq!(response["result"]["generic"]["isValid"], true, "{}", call);
+                } else {
+                    assert_eq!(response["error"]["code"], REQUEST_FAILED);
+                    let error = &response["error"]["data"]["error"];
+                    assert_eq!(error["code"], "notPermitted", "{}", call);
+                }
+            }
+        }
+    }
+
+    // Test that calls are read like the requests of a batch
+    #[test]
+    fn parse_calls() {
+        let max_duration = Duration::from_secs(3600);
+
+        // Read a call and a notification
+        let text = r#"{"jsonrpc": "2.0", "id": 7, "method": "playFade",
+            "params": {"channel": 1, "value": 255}}"#;
+        let (id, command) = parse_call(text, max_duration);
+        assert_eq!(id, Some(Value::from(7)));
+        let request = command.map(|command| command.request);
+        assert!(matches!(request, Ok(Request::PlayFade { fade }) if fade.value == 255));
+        let (id, command) = parse_call(r#"{"jsonrpc": "2.0", "method": "undo"}"#, max_duration);
+        assert_eq!(id, None);
+        assert!(matches!(
+            command.map(|command| command.request),
+            Ok(Request::Undo)
+        ));
+
+        // Check the failures
+        let code = |text| {
+            parse_call(text, max_duration)
+                .1
+                .map(|_| ())
+                .map_err(|error| error.code)
+        };
+        assert_eq!(code("{"), Err(PARSE_ERROR));
+        assert_eq!(
+            code(r#"{"jsonrpc": "1.0", "method": "undo"}"#),
+            Err(INVALID_CALL)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "method": "fly"}"#),
+            Err(METHOD_NOT_FOUND)
+        );
+        let text =
+            r#"{"jsonrpc": "2.0", "method": "playFade", "params": {"channel": 0, "value": 1}}"#;
+        assert_eq!(code(text), Err(INVALID_PARAMS));
+    }
+}
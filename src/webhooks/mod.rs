@@ -0,0 +1,217 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to send events to external systems as webhooks. Each event is
+//! posted as JSON to every webhook that is interested in it.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Import Tokio and warp features
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::time::timeout;
+use warp::http::Uri;
+
+// Import tracing features
+use tracing::{debug, error, warn};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the time to wait for a webhook to reply
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A structure to send events to the configured webhooks
+///
+pub struct Webhooks {
+    webhooks: Vec<(Uri, WebhookConfig)>, // the valid webhooks and their locations
+    events: broadcast::Receiver<Event>,  // the receiving line for events
+}
+
+// Implement key features of the webhooks
+impl Webhooks {
+    /// A function to create the webhooks from the configuration. Any webhook
+    /// with an invalid url is skipped with an error.
+    ///
+    pub fn new(configs: Vec<WebhookConfig>, events: broadcast::Receiver<Event>) -> Self {
+        // Check the location of each webhook
+        let webhooks = configs
+            .into_iter()
+            .filter_map(|config| match Webhooks::parse_url(&config.url) {
+                Ok(uri) => Some((uri, config)),
+                Err(error) => {
+                    error!("{}", error);
+                    None
+                }
+            })
+            .collect();
+
+        // Return the new webhooks
+        Self { webhooks, events }
+    }
+
+    /// A method to send every event to the interested webhooks until the
+    /// program closes. Each webhook is sent in the background so that a slow
+    /// server does not delay the other webhooks.
+    ///
+    pub async fn run(mut self) {
+        // If there are no webhooks, there's nothing to do
+        if self.webhooks.is_empty() {
+            return;
+        }
+
+        // Wait for each event
+        loop {
+            let event = match self.events.recv().await {
+                Ok(event) => event,
+
+                // Note if any events were missed
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    warn!("Webhooks missed {} events.", count);
+                    continue;
+                }
+
+                // Stop when the program closes
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+
+            // Compose the body of the webhook
+            let mut body = serde_json::to_value(&event).unwrap_or_default();
+            if let Some(fields) = body.as_object_mut() {
+                fields.insert(
+                    "timestamp".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|time| time.as_millis() as u64)
+                        .unwrap_or(0)
+                        .into(),
+                );
+            }
+            let body = body.to_string();
+
+            // Send the event to each interested webhook
+            for (uri, config) in self.webhooks.iter() {
+                if config.events.is_empty() || config.events.iter().any(|name| name == event.name())
+                {
+                    let uri = uri.clone();
+                    let body = body.clone();
+                    tokio::spawn(async move {
+                        match Webhooks::post(&uri, &body).await {
+                            Ok(()) => debug!("Sent webhook to {}.", uri),
+                            Err(error) => warn!("Unable to send webhook to {}: {}", uri, error),
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    /// A helper function to check that the url is a complete http url
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the url is invalid or does not
+    /// use http.
+    ///
+    pub fn parse_url(url: &str) -> Result<Uri> {
+        // Try to parse the url
+        let uri: Uri = url
+            .parse()
+            .map_err(|error| anyhow!("Invalid url {}: {}.", url, error))?;
+
+        // Make sure the url uses http and includes a host
+        if uri.scheme_str() != Some("http") {
+            return Err(anyhow!("Url {} must start with http://.", url));
+        }
+        if uri.authority().is_none() {
+            return Err(anyhow!("Url {} must include a host.", url));
+        }
+        Ok(uri)
+    }
+
+    /// A helper function to post the body to the webhook and check the
+    /// status of the reply
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the webhook cannot be reached,
+    /// does not reply in time, or replies with an error status.
+    ///
+    pub async fn post(uri: &Uri, body: &str) -> Result<()> {
+        // Find the address and path of the webhook
+        let authority = uri.authority().ok_or_else(|| anyhow!("Missing host."))?;
+        let address = format!(
+            "{}:{}",
+            authority.host(),
+            authority.port_u16().unwrap_or(80)
+        );
+        let path = uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+
+        // Send the request and read the reply, within the time limit
+        let reply = timeout(WEBHOOK_TIMEOUT, async {
+            let mut stream = TcpStream::connect(&address).await?;
+            let request = format!(
+                "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                path,
+                authority,
+                body.len(),
+                body
+            );
+            stream.write_all(request.as_bytes()).await?;
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).await?;
+            Ok::<Vec<u8>, std::io::Error>(reply)
+        })
+        .await
+        .map_err(|_| anyhow!("No reply within {:?}.", WEBHOOK_TIMEOUT))??;
+
+        // Check the status of the reply
+        let reply = String::from_utf8_lossy(&reply);
+        let status = reply
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| anyhow!("Invalid reply."))?;
+        if !(200..300).contains(&status) {
+            return Err(anyhow!("Replied with status {}.", status));
+        }
+        Ok(())
+    }
+}
+
+// Tests of the webhooks module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that only complete http urls are accepted
+    #[test]
+    fn parse_webhook_url() {
+        assert!(Webhooks::parse_url("http://127.0.0.1:9000/hook").is_ok());
+        assert!(Webhooks::parse_url("https://example.com/hook").is_err());
+        assert!(Webhooks::parse_url("/hook").is_err());
+        assert!(Webhooks::parse_url("not a url").is_err());
+    }
+}
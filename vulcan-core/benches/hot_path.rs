@@ -0,0 +1,118 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarks of the real-time path of the DMX controller: the fade
+//! interpolation, the assembly of each frame, and the compositing of the
+//! layers. Run them with `cargo bench -p vulcan-core` and compare the time
+//! per iteration before and after a change.
+
+// Import standard library features
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// Import the core library features
+use vulcan_core::definitions::{DMX_MAX, Fade, Layer, Universe};
+use vulcan_core::{Compositor, FadeEngine, FadeLimits, Submasters};
+
+// Define the time spent measuring each benchmark
+const MEASURE_TIME: Duration = Duration::from_secs(2);
+
+/// A helper function to run a benchmark repeatedly and print the mean time
+/// of each iteration
+///
+fn bench(name: &str, mut routine: impl FnMut()) {
+    // Warm up, then count the iterations that fit in the measuring time
+    for _ in 0..100 {
+        routine();
+    }
+    let start = Instant::now();
+    let mut iterations = 0u64;
+    while start.elapsed() < MEASURE_TIME {
+        routine();
+        iterations += 1;
+    }
+    let mean = start.elapsed().as_nanos() / iterations as u128;
+    println!(
+        "{:<24} {:>10} ns/iter ({} iterations)",
+        name, mean, iterations
+    );
+}
+
+/// A helper function to create a universe with every channel set
+///
+fn full_universe(offset: u32) -> Universe {
+    let mut universe = Universe::new();
+    for channel in 1..=DMX_MAX {
+        universe.set(channel, ((channel + offset) % 256) as u8);
+    }
+    universe
+}
+
+/// A function to run every benchmark of the real-time path
+///
+fn main() {
+    // Interpolate a fade on every channel
+    let start = Instant::now();
+    let mut engine = FadeEngine::new();
+    for channel in 1..=DMX_MAX {
+        engine
+            .play_fade(
+                Fade {
+                    channel,
+                    value: 255,
+                    duration: Some(Duration::from_secs(3600)),
+                },
+                start,
+            )
+            .unwrap();
+    }
+    let mut step = 0;
+    bench("fade interpolation", || {
+        step += 1;
+        black_box(engine.update(start + Duration::from_millis(step % 3_600_000)));
+    });
+
+    // Compose the layers, with the effects and overrides on half the channels
+    let compositor = Compositor::new();
+    let base = full_universe(0);
+    let effects = full_universe(85);
+    let overrides = full_universe(170);
+    let shares: Vec<(u32, f64)> = (1..=DMX_MAX / 2).map(|channel| (channel, 0.5)).collect();
+    bench("merge compositing", || {
+        black_box(compositor.compose(&[
+            (Layer::Base, &base, None),
+            (Layer::Effects, &effects, Some(&shares)),
+            (Layer::Override, &overrides, Some(&shares)),
+        ]));
+    });
+
+    // Assemble a frame: compose, scale the groups, and limit the rate of change
+    let mut submasters = Submasters::new();
+    submasters.set_level("half", (1..=DMX_MAX / 2).collect(), 128);
+    let mut fade_limits = FadeLimits::new();
+    fade_limits.set_rates((1..=DMX_MAX).map(|channel| (channel, 100.0)).collect());
+    let mut now = Instant::now();
+    bench("frame assembly", || {
+        now += Duration::from_millis(25);
+        let composed = compositor.compose(&[
+            (Layer::Base, &base, None),
+            (Layer::Effects, &effects, Some(&shares)),
+        ]);
+        let scaled = submasters.apply(&composed);
+        black_box(fade_limits.apply(&scaled, now).into_owned());
+    });
+}
@@ -0,0 +1,148 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to keep the global beat clock. The tempo is set directly or by
+//! tapping, and each change of tempo (and each tap) starts a new beat, so
+//! that the effects locked to the clock land on the beat.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Define the time after which a tap starts a new measurement
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Define the most recent taps used to measure the tempo
+const MAX_TAPS: usize = 8;
+
+/// A structure to hold the tempo and the time of the first beat
+///
+#[derive(Clone, Debug)]
+pub struct BeatClock {
+    bpm: f64,           // the current tempo, in beats per minute
+    origin: Instant,    // the time of the beat that the clock counts from
+    taps: Vec<Instant>, // the recent taps, oldest first
+}
+
+// Implement the default beat clock, starting now at the default tempo
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+// Implement key features of the beat clock
+impl BeatClock {
+    /// A function to create a new beat clock at the default tempo, with a
+    /// beat at the given time
+    ///
+    pub fn new(now: Instant) -> Self {
+        Self {
+            bpm: DEFAULT_BPM,
+            origin: now,
+            taps: Vec::new(),
+        }
+    }
+
+    /// A method to return the current tempo, in beats per minute
+    ///
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    /// A method to return the number of beats (including the fraction of
+    /// the current beat) since the clock started counting
+    ///
+    pub fn beats(&self, now: Instant) -> f64 {
+        now.saturating_duration_since(self.origin).as_secs_f64() * self.bpm / 60.0
+    }
+
+    /// A method to change the tempo (limited to the allowed range), with a
+    /// beat at the given time
+    ///
+    pub fn set_bpm(&mut self, bpm: f64, now: Instant) {
+        self.bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+        self.origin = now;
+        self.taps.clear();
+    }
+
+    /// A method to tap the beat, returning the new tempo. The tempo is the
+    /// average time between the recent taps, and each tap lands on a beat.
+    /// A tap long after the last one starts a new measurement.
+    ///
+    pub fn tap(&mut self, now: Instant) -> f64 {
+        // Forget the taps from an earlier measurement
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now.saturating_duration_since(*last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+
+        // Keep only the most recent taps
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        // Find the average time between the taps, if there are enough
+        if let [first, .., last] = self.taps[..] {
+            let interval =
+                last.saturating_duration_since(first).as_secs_f64() / (self.taps.len() - 1) as f64;
+            if interval > 0.0 {
+                self.bpm = (60.0 / interval).clamp(MIN_BPM, MAX_BPM);
+            }
+        }
+
+        // Start the beat on the tap
+        self.origin = now;
+        self.bpm
+    }
+}
+
+// Tests of the beat clock module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that taps set the tempo and the clock counts beats from the last tap
+    #[test]
+    fn tap_tempo() {
+        let start = Instant::now();
+        let mut clock = BeatClock::new(start);
+        assert_eq!(clock.beats(start + Duration::from_secs(1)), 2.0);
+
+        // A single tap only moves the beat
+        assert_eq!(clock.tap(start), DEFAULT_BPM);
+
+        // Taps every 400 ms are 150 beats per minute
+        for index in 1..4 {
+            clock.tap(start + Duration::from_millis(400 * index));
+        }
+        assert!((clock.bpm() - 150.0).abs() < 1e-9);
+        let last = start + Duration::from_millis(1200);
+        assert!((clock.beats(last + Duration::from_millis(600)) - 1.5).abs() < 1e-9);
+
+        // A late tap starts a new measurement, and the range is limited
+        assert!((clock.tap(last + Duration::from_secs(5)) - 150.0).abs() < 1e-9);
+        clock.set_bpm(1000.0, last);
+        assert_eq!(clock.bpm(), MAX_BPM);
+    }
+}
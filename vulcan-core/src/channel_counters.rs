@@ -0,0 +1,107 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to count how often each channel of the output changes, so that
+//! a flickering channel can be found in a complex layered setup. Only the
+//! frames that reach the hardware are counted.
+
+// Import crate definitions
+use crate::definitions::*;
+
+/// A structure to hold the last written frame and the changes of every
+/// channel
+///
+#[derive(Clone)]
+pub struct ChannelCounters {
+    last: Universe,                 // the last frame written
+    changes: Vec<u64>,              // the number of changes of each channel, zero-indexed
+    last_changes: Vec<Option<u64>>, // the time of the last change of each channel, zero-indexed
+}
+
+// Implement the default counters, without any changes
+impl Default for ChannelCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement key features of the channel counters
+impl ChannelCounters {
+    /// A function to create new counters, starting from a dark frame
+    ///
+    pub fn new() -> Self {
+        Self {
+            last: Universe::new(),
+            changes: vec![0; DMX_MAX as usize],
+            last_changes: vec![None; DMX_MAX as usize],
+        }
+    }
+
+    /// A method to count the channels that changed in a written frame, at
+    /// the timestamp (in ms since the unix epoch)
+    ///
+    pub fn record(&mut self, frame: &Universe, timestamp: u64) {
+        for channel in 1..=DMX_MAX {
+            if frame.get(channel) != self.last.get(channel) {
+                let index = channel as usize - 1;
+                self.changes[index] += 1;
+                self.last_changes[index] = Some(timestamp);
+            }
+        }
+        self.last = frame.clone();
+    }
+
+    /// A method to return the statistics of a channel, with the source
+    /// that is currently controlling it
+    ///
+    pub fn stats(&self, channel: u32, source: ChannelSource) -> ChannelStats {
+        let index = (channel.clamp(1, DMX_MAX) - 1) as usize;
+        ChannelStats {
+            channel,
+            value: self.last.get(channel),
+            changes: self.changes[index],
+            last_change: self.last_changes[index],
+            source,
+            writer: None,
+        }
+    }
+}
+
+// Tests of the channel counters module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that only the channels that changed are counted
+    #[test]
+    fn count_changes() {
+        let mut counters = ChannelCounters::new();
+        let mut frame = Universe::new();
+        frame.set(37, 255);
+        counters.record(&frame, 1000);
+        counters.record(&frame, 1050);
+        frame.set(37, 0);
+        counters.record(&frame, 1100);
+
+        // The flickering channel has two changes, and the others have none
+        let stats = counters.stats(37, ChannelSource::Effect);
+        assert_eq!((stats.value, stats.changes), (0, 2));
+        assert_eq!(stats.last_change, Some(1100));
+        let stats = counters.stats(1, ChannelSource::Base);
+        assert_eq!((stats.changes, stats.last_change), (0, None));
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to provide the time to the DMX controller. The controller reads
+//! the time from its clock rather than the system, so that tests can step
+//! the clock by exact amounts and check the output at exact times.
+
+// Import standard library features
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A trait for the source of the time of the DMX controller
+///
+pub trait Clock: Send + Sync {
+    /// A method to return the current time
+    ///
+    fn now(&self) -> Instant;
+}
+
+/// A clock that follows the monotonic clock of the system
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+// Implement the system clock
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when it is advanced, for tests. Each clone of the
+/// clock shares the same time.
+///
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>, // the current time of the clock
+}
+
+// Implement key features of the manual clock
+impl ManualClock {
+    /// A function to create a new clock, starting at the given time
+    ///
+    pub fn new(start: Instant) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// A method to move the clock forward
+    ///
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += duration;
+        }
+    }
+}
+
+// Implement the manual clock
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now
+            .lock()
+            .map(|now| *now)
+            .unwrap_or_else(|_| Instant::now())
+    }
+}
+
+// Tests of the clock module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that every clone of a manual clock moves together
+    #[test]
+    fn advance_manual_clock() {
+        let start = Instant::now();
+        let clock = ManualClock::new(start);
+        let other = clock.clone();
+        assert_eq!(clock.now(), start);
+        other.advance(Duration::from_millis(250));
+        assert_eq!(clock.now(), start + Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to compose the output from the layers. Each layer is mixed over
+//! the layers below it by its opacity, starting from black, and a layer
+//! only covers the channels it controls (each with its own share, so that a
+//! channel can be released gradually).
+
+// Import crate definitions
+use crate::definitions::*;
+
+/// A type for the values of one layer: the layer, the values, and the
+/// channels it controls with the share of each channel (or None for every
+/// channel in full)
+///
+pub type LayerOutput<'a> = (Layer, &'a Universe, Option<&'a [(u32, f64)]>);
+
+/// A structure to hold the state of every layer
+///
+#[derive(Clone)]
+pub struct Compositor {
+    layers: Vec<LayerState>, // the state of each layer, from the lowest priority to the highest
+}
+
+// Implement the default compositor, with every layer at full opacity
+impl Default for Compositor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement key features of the compositor
+impl Compositor {
+    /// A function to create a new compositor, with every layer at full
+    /// opacity
+    ///
+    pub fn new() -> Self {
+        Self {
+            layers: Layer::ALL.into_iter().map(LayerState::new).collect(),
+        }
+    }
+
+    /// A method to return the state of every layer, from the lowest priority
+    /// to the highest
+    ///
+    pub fn layers(&self) -> &[LayerState] {
+        &self.layers
+    }
+
+    /// A method to indicate whether a layer is part of the output (not
+    /// muted, and solo if any layer is solo)
+    ///
+    pub fn is_heard(&self, layer: Layer) -> bool {
+        let is_solo = self.layers.iter().any(|state| state.is_solo);
+        self.layers
+            .iter()
+            .find(|state| state.layer == layer)
+            .is_some_and(|state| !state.is_muted && (state.is_solo || !is_solo))
+    }
+
+    /// A method to change the state of a layer
+    ///
+    pub fn update(&mut self, layer: Layer, update: &LayerUpdate) {
+        if let Some(state) = self.layers.iter_mut().find(|state| state.layer == layer) {
+            if let Some(opacity) = update.opacity {
+                state.opacity = opacity.min(FULL_LEVEL);
+            }
+            if let Some(is_muted) = update.is_muted {
+                state.is_muted = is_muted;
+            }
+            if let Some(is_solo) = update.is_solo {
+                state.is_solo = is_solo;
+            }
+        }
+    }
+
+    /// A method to compose the output from the values of each layer. The
+    /// share of each channel is from zero to one. A layer without values is
+    /// left out.
+    ///
+    pub fn compose(&self, outputs: &[LayerOutput]) -> Universe {
+        // Start from black
+        let mut levels = vec![0.0; DMX_MAX as usize];
+        let is_solo = self.layers.iter().any(|state| state.is_solo);
+
+        // Mix each layer that is heard over the layers below it
+        for state in self.layers.iter() {
+            if state.is_muted || (is_solo && !state.is_solo) {
+                continue;
+            }
+            let Some((_, values, channels)) =
+                outputs.iter().find(|(layer, ..)| *layer == state.layer)
+            else {
+                continue;
+            };
+            let opacity = state.opacity as f64 / FULL_LEVEL as f64;
+            let mut mix = |channel: u32, share: f64| {
+                let level = &mut levels[channel as usize - 1];
+                *level += (values.get(channel) as f64 - *level) * opacity * share.clamp(0.0, 1.0);
+            };
+            match channels {
+                Some(channels) => channels
+                    .iter()
+                    .filter(|(channel, _)| (1..=DMX_MAX).contains(channel))
+                    .for_each(|(channel, share)| mix(*channel, *share)),
+                None => (1..=DMX_MAX).for_each(|channel| mix(channel, 1.0)),
+            }
+        }
+
+        // Convert the levels into values
+        let mut universe = Universe::new();
+        for (index, level) in levels.into_iter().enumerate() {
+            universe.set(index as u32 + 1, level.round() as u8);
+        }
+        universe
+    }
+}
+
+// Tests of the compositor module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the layers are mixed by opacity, mute, and solo
+    #[test]
+    fn compose_layers() {
+        let mut compositor = Compositor::new();
+        let mut base = Universe::new();
+        base.set(1, 100);
+        base.set(2, 100);
+        let mut effects = Universe::new();
+        effects.set(1, 200);
+        effects.set(2, 200);
+        let outputs = [
+            (Layer::Base, &base, None),
+            (Layer::Effects, &effects, Some(&[(1, 1.0)][..])),
+        ];
+
+        // At full opacity, the effects cover only their channels
+        let output = compositor.compose(&outputs);
+        assert_eq!((output.get(1), output.get(2)), (200, 100));
+
+        // Half opacity mixes the effects with the base
+        let half = LayerUpdate {
+            opacity: Some(50),
+            ..Default::default()
+        };
+        compositor.update(Layer::Effects, &half);
+        assert_eq!(compositor.compose(&outputs).get(1), 150);
+
+        // A muted base leaves the effects over black
+        let mute = LayerUpdate {
+            is_muted: Some(true),
+            ..Default::default()
+        };
+        compositor.update(Layer::Base, &mute);
+        let output = compositor.compose(&outputs);
+        assert_eq!((output.get(1), output.get(2)), (100, 0));
+
+        // A solo layer silences every other layer
+        let solo = LayerUpdate {
+            is_muted: Some(false),
+            is_solo: Some(true),
+            ..Default::default()
+        };
+        compositor.update(Layer::Base, &solo);
+        assert_eq!(compositor.compose(&outputs).get(1), 100);
+    }
+}
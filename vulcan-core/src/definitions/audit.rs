@@ -0,0 +1,168 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the structures to record who made each request
+//! and to search the record of requests.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::hash::Hasher;
+
+// Import FNV HashMap
+use fnv::FnvHasher;
+
+/// A struct to describe the source of a request
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Client {
+    pub role: Role,              // the role of the client
+    pub identity: String,        // the identity of the client (never the token itself)
+    pub address: Option<String>, // the network address of the client, if known
+    #[serde(default)]
+    pub client_id: Option<String>, // the identifier from the x-client-id header, if any
+}
+
+// Implement key features of the client
+impl Client {
+    /// A function to create a client for requests from within the program
+    /// (such as signals)
+    ///
+    pub fn internal() -> Self {
+        Self {
+            role: Role::Admin,
+            identity: "internal".to_string(),
+            address: None,
+            client_id: None,
+        }
+    }
+
+    /// A function to create a client for requests from a trigger. Triggers
+    /// can perform any action an operator can.
+    ///
+    pub fn trigger(source: &str) -> Self {
+        Self {
+            role: Role::Operator,
+            identity: "trigger".to_string(),
+            address: Some(source.to_string()),
+            client_id: None,
+        }
+    }
+
+    /// A function to create a client for requests from a script. Scripts
+    /// can perform any action a trigger can.
+    ///
+    pub fn script(name: &str) -> Self {
+        Self {
+            role: Role::Operator,
+            identity: "script".to_string(),
+            address: Some(name.to_string()),
+            client_id: None,
+        }
+    }
+
+    /// A function to create a client for the actions of a timeline.
+    /// Timelines can perform any action a trigger can.
+    ///
+    pub fn timeline(name: &str) -> Self {
+        Self {
+            role: Role::Operator,
+            identity: "timeline".to_string(),
+            address: Some(name.to_string()),
+            client_id: None,
+        }
+    }
+
+    /// A function to create a client from a token. The identity is a short
+    /// fingerprint of the token so that the token is never recorded.
+    ///
+    pub fn from_token(token: &str, role: Role, address: Option<String>) -> Self {
+        let mut hasher = FnvHasher::default();
+        hasher.write(token.as_bytes());
+        Self {
+            role,
+            identity: format!("token-{:08x}", hasher.finish() as u32),
+            address,
+            client_id: None,
+        }
+    }
+}
+
+/// A struct to hold one entry of the audit log
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: u64,          // the time of the request, in ms since the unix epoch
+    pub identity: String,        // the identity of the client
+    pub address: Option<String>, // the network address of the client, if known
+    #[serde(default)]
+    pub client_id: Option<String>, // the identifier the client gave for itself, if any
+    pub request: String,         // the type of the request
+    pub parameters: serde_json::Value, // the parameters of the request
+    pub is_success: bool,        // a flag to indicate the result of the request
+}
+
+/// A struct to search the audit log. Every field is optional, and the
+/// newest matching entries are returned.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditQuery {
+    pub since: Option<u64>, // only entries at or after this time, in ms since the unix epoch
+    pub identity: Option<String>, // only entries from this identity
+    pub client_id: Option<String>, // only entries with this client identifier
+    pub request: Option<String>, // only entries of this request type
+    pub limit: Option<usize>, // the maximum number of entries to return
+}
+
+/// A struct to describe the request that last wrote a channel, and the
+/// client that sent it
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelWriter {
+    pub identity: String,          // the identity of the client
+    pub client_id: Option<String>, // the identifier the client gave for itself, if any
+    pub request: String,           // the type of the request
+    pub timestamp: u64,            // the time of the request, in ms since the unix epoch
+}
+
+/// A struct to describe a heartbeat from a client, which marks the client
+/// as connected (and counts as control traffic for the idle watchdog)
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heartbeat {
+    pub client_id: String, // the identifier chosen by the client
+}
+
+/// A struct to describe the recent activity of one client
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientActivity {
+    pub client_id: Option<String>, // the identifier from the heartbeats of the client, if any
+    pub identity: String,          // the identity of the client
+    pub address: Option<String>,   // the network host of the client, if known
+    pub last_seen: u64,            // the time of the last request, in ms since the unix epoch
+    pub last_request: String,      // the type of the last request
+    pub requests: u64,             // the number of requests from the client
+    pub is_connected: bool,        // a flag to indicate the client was seen recently
+}
@@ -0,0 +1,300 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements colors in CIE xy chromaticity, so that the same
+//! color can be requested from any fixture and converted through the color
+//! matrix (or the calibration) of its profile into the levels of its
+//! emitters.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A type to convert a color from CIE XYZ into the linear levels of the
+/// red, green, and blue emitters of a fixture (one row for each emitter)
+///
+pub type ColorMatrix = [[f64; 3]; 3];
+
+/// The conversion from CIE XYZ into linear sRGB (with a D65 white point),
+/// used for any profile without a color matrix of its own
+///
+pub const SRGB_MATRIX: ColorMatrix = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+/// The D65 white point, used for any calibration without a white point of
+/// its own
+///
+pub const D65_WHITE: ColorPoint = ColorPoint {
+    x: 0.3127,
+    y: 0.3290,
+};
+
+/// A struct to define one color in CIE xy chromaticity
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorPoint {
+    pub x: f64, // the x coordinate of the color
+    pub y: f64, // the y coordinate of the color
+}
+
+/// A struct to store the measured colors of a type of fixture: the color
+/// of each emitter at full, and the white the emitters make together
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorCalibration {
+    pub red: ColorPoint,   // the color of the red emitter
+    pub green: ColorPoint, // the color of the green emitter
+    pub blue: ColorPoint,  // the color of the blue emitter
+    #[serde(default = "default_white_point")]
+    pub white_point: ColorPoint, // the color of every emitter at full together
+}
+
+/// A helper function to default the white point of a calibration
+///
+fn default_white_point() -> ColorPoint {
+    D65_WHITE
+}
+
+/// A struct to request a color for a fixture in CIE xy chromaticity, with
+/// a brightness, over the (optional) duration
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chromaticity {
+    pub fixture: String,            // the name of the fixture
+    pub x: f64,                     // the x coordinate of the color
+    pub y: f64,                     // the y coordinate of the color
+    pub brightness: f64,            // the relative luminance of the color, from zero to one
+    pub duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+}
+
+// Implement key features of the chromaticity
+impl Chromaticity {
+    /// A method to convert the color into the linear level of the red,
+    /// green, and blue emitters (from zero to one) with the matrix. A color
+    /// beyond the gamut of the emitters loses its negative levels, and a
+    /// color too bright for the emitters is dimmed, keeping its hue.
+    ///
+    pub fn to_levels(&self, matrix: &ColorMatrix) -> [f64; 3] {
+        // Convert the color and keep it within the range of the emitters
+        let color = ColorPoint {
+            x: self.x,
+            y: self.y,
+        };
+        let levels =
+            multiply(matrix, color.tristimulus(self.brightness)).map(|level| level.max(0.0));
+        let highest = levels.iter().copied().fold(1.0, f64::max);
+        levels.map(|level| level / highest)
+    }
+
+    /// A method to convert the color into a fade for each emitter of the
+    /// fixture, with the color matrix of its profile. Any white emitter is
+    /// turned off, so that it does not change the color.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not have red,
+    /// green, and blue emitters.
+    ///
+    pub fn to_fades(&self, fixture: &Fixture, profile: &FixtureProfile) -> Result<Vec<WideFade>> {
+        // Find the level of each emitter
+        let matrix = profile.color_conversion()?;
+        let mut levels = vec![(Parameter::White, 0.0)];
+        for (parameter, level) in [Parameter::Red, Parameter::Green, Parameter::Blue]
+            .into_iter()
+            .zip(self.to_levels(&matrix))
+        {
+            if fixture.channel(profile, &parameter).is_none() {
+                return Err(ErrorCode::InvalidRequest {
+                    fields: vec![FieldError::new(
+                        "fixture",
+                        "The fixture must have red, green, and blue channels.",
+                    )],
+                }
+                .into());
+            }
+            levels.push((parameter, level));
+        }
+
+        // Fade each emitter the fixture has to its level
+        Ok(levels
+            .into_iter()
+            .filter_map(|(parameter, level)| {
+                Some(WideFade {
+                    channel: fixture.channel(profile, &parameter)?,
+                    fine_channel: None,
+                    value: (level * u16::MAX as f64).round() as u16,
+                    duration: self.duration,
+                })
+            })
+            .collect())
+    }
+}
+
+// Implement key features of the color point
+impl ColorPoint {
+    /// A method to find the CIE XYZ tristimulus values of the color at the
+    /// luminance
+    ///
+    pub fn tristimulus(&self, luminance: f64) -> [f64; 3] {
+        [
+            self.x * luminance / self.y,
+            luminance,
+            (1.0 - self.x - self.y) * luminance / self.y,
+        ]
+    }
+}
+
+// Implement key features of the color calibration
+impl ColorCalibration {
+    /// A method to find the matrix that converts CIE XYZ into the linear
+    /// levels of the emitters, so that every emitter at full makes the
+    /// white point
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the emitters do not surround the
+    /// white point, so that the calibration cannot be converted.
+    ///
+    pub fn to_matrix(&self) -> Result<ColorMatrix> {
+        // Find the color of each emitter at a luminance of one (one column
+        // for each emitter)
+        let invalid = || ErrorCode::InvalidConfig {
+            reason: "The emitters of the calibration must surround the white point.".into(),
+        };
+        let columns = [self.red, self.green, self.blue].map(|point| point.tristimulus(1.0));
+        let primaries: ColorMatrix =
+            std::array::from_fn(|row| std::array::from_fn(|column| columns[column][row]));
+
+        // Scale each emitter so that together they make the white point
+        let scales = multiply(
+            &invert(&primaries).ok_or_else(invalid)?,
+            self.white_point.tristimulus(1.0),
+        );
+        if !scales.iter().all(|scale| scale.is_finite() && *scale > 0.0) {
+            return Err(invalid().into());
+        }
+        let emitters =
+            primaries.map(|row| std::array::from_fn(|column| row[column] * scales[column]));
+        Ok(invert(&emitters).ok_or_else(invalid)?)
+    }
+}
+
+/// A helper function to multiply a vector by a matrix
+///
+fn multiply(matrix: &ColorMatrix, vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| {
+        row.iter()
+            .zip(vector)
+            .map(|(factor, value)| factor * value)
+            .sum()
+    })
+}
+
+/// A helper function to invert a matrix, if it can be inverted
+///
+fn invert(matrix: &ColorMatrix) -> Option<ColorMatrix> {
+    // Find the cofactor of each element
+    let [[a, b, c], [d, e, f], [g, h, i]] = *matrix;
+    let cofactors = [
+        [e * i - f * h, c * h - b * i, b * f - c * e],
+        [f * g - d * i, a * i - c * g, c * d - a * f],
+        [d * h - e * g, b * g - a * h, a * e - b * d],
+    ];
+
+    // Divide by the determinant, unless the matrix is singular
+    let determinant = a * cofactors[0][0] + b * cofactors[1][0] + c * cofactors[2][0];
+    if !determinant.is_finite() || determinant.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(cofactors.map(|row| row.map(|cofactor| cofactor / determinant)))
+}
+
+// Tests of the color module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that colors are converted into the levels of the emitters
+    #[test]
+    fn chromaticity_to_fades() {
+        // Read a profile with a white emitter and the default matrix
+        let profile: FixtureProfile =
+            serde_yaml::from_str("channels: [red, green, blue, white]\n").unwrap();
+        let fixture = Fixture {
+            profile: "wash".into(),
+            mode: None,
+            address: 1,
+        };
+
+        // The white point at full brightness lights every color emitter
+        let mut color = Chromaticity {
+            fixture: "washLeft".into(),
+            x: 0.3127,
+            y: 0.3290,
+            brightness: 1.0,
+            duration: None,
+        };
+        let values: Vec<(u32, u8)> = color
+            .to_fades(&fixture, &profile)
+            .unwrap()
+            .iter()
+            .map(|fade| (fade.channel, (fade.value >> 8) as u8))
+            .collect();
+        assert_eq!(values, vec![(4, 0), (1, 255), (2, 255), (3, 255)]);
+
+        // The red primary lights only the red emitter, dimmed to fit
+        color.x = 0.64;
+        color.y = 0.33;
+        let levels = color.to_levels(&SRGB_MATRIX);
+        assert!((levels[0] - 1.0).abs() < 0.01);
+        assert!(levels[1] < 0.01 && levels[2] < 0.01);
+
+        // A calibration with the sRGB primaries matches the default matrix
+        let calibration: ColorCalibration = serde_yaml::from_str(
+            "red: {x: 0.64, y: 0.33}\ngreen: {x: 0.30, y: 0.60}\nblue: {x: 0.15, y: 0.06}\n",
+        )
+        .unwrap();
+        let matrix = calibration.to_matrix().unwrap();
+        for (row, expected) in matrix.iter().zip(SRGB_MATRIX) {
+            for (factor, expected) in row.iter().zip(expected) {
+                assert!((factor - expected).abs() < 0.01);
+            }
+        }
+
+        // Emitters in a line cannot be calibrated
+        let calibration = ColorCalibration {
+            green: calibration.red,
+            ..calibration
+        };
+        assert!(calibration.to_matrix().is_err());
+
+        // A fixture without color emitters is rejected
+        let profile: FixtureProfile = serde_yaml::from_str("channels: [intensity]\n").unwrap();
+        assert!(color.to_fades(&fixture, &profile).is_err());
+    }
+}
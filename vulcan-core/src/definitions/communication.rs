@@ -0,0 +1,1125 @@
+// Copyright (c) 2019-2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements shared communication structures for communicating
+//! across the modules of the system.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import Tokio features
+use tokio::sync::{mpsc, oneshot};
+
+// Import tracing features
+use tracing::Span;
+
+// Define the length of the line to the system interface, and the room left
+// for requests from within the program once it is saturating
+const WEB_QUEUE_LENGTH: usize = 256;
+const RESERVED_REQUESTS: usize = 32;
+
+/// The stucture and methods to send WebRequests to the system interface
+///
+#[derive(Clone, Debug)]
+pub struct WebSend {
+    web_send: mpsc::Sender<WebRequest>, // the mpsc sending line to pass web requests
+}
+
+// Implement the key features of the web send struct
+impl WebSend {
+    /// A function to create a new WebSend
+    ///
+    /// The function returns the the Web Sent structure and the system
+    /// receive channel which will return the provided updates.
+    ///
+    pub fn new() -> (Self, mpsc::Receiver<WebRequest>) {
+        // Create the new channel
+        let (web_send, receive) = mpsc::channel(WEB_QUEUE_LENGTH);
+
+        // Create and return both new items
+        (WebSend { web_send }, receive)
+    }
+
+    /// A method to send a web request on behalf of a client, unless the line
+    /// is saturating. Some room is always left for the requests from within
+    /// the program (such as signals and scripts), which wait instead.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise the busy error if the line is close to full.
+    /// The request is not sent.
+    ///
+    pub fn try_send_as(
+        &self,
+        client: Client,
+        reply_to: oneshot::Sender<WebReply>,
+        request: Request,
+    ) -> Result<(), ErrorCode> {
+        // Refuse the request if the line is saturating
+        if self.web_send.capacity() <= RESERVED_REQUESTS {
+            return Err(ErrorCode::Busy);
+        }
+
+        // Otherwise, send the request (only closed if the program is closing)
+        self.web_send
+            .try_send(WebRequest {
+                reply_to,
+                request,
+                client,
+                span: Span::current(),
+            })
+            .map_err(|_| ErrorCode::Busy)
+    }
+
+    /// A method to send a web request from within the program. This method
+    /// fails silently.
+    ///
+    pub async fn send(&self, reply_to: oneshot::Sender<WebReply>, request: Request) {
+        self.send_as(Client::internal(), reply_to, request).await;
+    }
+
+    /// A method to send a web request on behalf of a client. The request
+    /// carries the current tracing span across to the system interface, and
+    /// waits for room on the line. This method fails silently.
+    ///
+    pub async fn send_as(
+        &self,
+        client: Client,
+        reply_to: oneshot::Sender<WebReply>,
+        request: Request,
+    ) {
+        self.web_send
+            .send(WebRequest {
+                reply_to,
+                request,
+                client,
+                span: Span::current(),
+            })
+            .await
+            .unwrap_or(());
+    }
+}
+
+/// A structure for carrying requests from the web interface
+///
+pub struct WebRequest {
+    pub reply_to: oneshot::Sender<WebReply>, // the handle for replying to the reqeust
+    pub request: Request,                    // the request
+    pub client: Client,                      // the source of the request
+    pub span: Span,                          // the tracing span of the request
+}
+
+/// An enum to define the roles of web interface clients. Each role includes
+/// all the permissions of the roles before it.
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+    /// a role that can read the current state
+    Observer,
+
+    /// a role that can also play fades and recall scenes
+    Operator,
+
+    /// a role that can also modify the configuration and close the program
+    Admin,
+}
+
+/// An enum to carry requests
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    /// A variant to set all lights to their emergency values
+    AllStop,
+
+    /// A variant to define the emergency values of all the lights
+    DefineAllStop {
+        universe: Universe, // the correct value of all the channels in emergency mode
+    },
+
+    /// A variant to engage or release the hardwired emergency stop. While
+    /// engaged, the lights hold their emergency values.
+    EmergencyStop {
+        is_engaged: bool, // a flag to indicate the emergency stop is engaged
+    },
+
+    /// A variant to play a fade on a channel
+    PlayFade {
+        fade: Fade, // the desired fade animation
+    },
+
+    /// A variant to load all the lights to a defined value
+    LoadUniverse {
+        universe: Universe, // the new value of all the channels
+        #[serde(default)]
+        expected_version: Option<u64>, // the version of the look the client expects to replace, if any
+    },
+
+    /// A variant to recall a scene from the configuration
+    RecallScene { recall: SceneRecall },
+
+    /// A variant to start an effect on some channels
+    PlayEffect { effect: EffectPlay },
+
+    /// A variant to start an effect preset from the configuration
+    PlayPreset { preset: PresetPlay },
+
+    /// A variant to stop effects, holding the current values
+    StopEffect { stop: EffectStop },
+
+    /// A variant to tune the effects in progress on some channels
+    TuneEffect { tune: EffectTune },
+
+    /// A variant to start a movement effect on some fixtures
+    PlayMovement { movement: MovementPlay },
+
+    /// A variant to start a pixel generator on a pixel map
+    PlayPixels { play: PixelPlay },
+
+    /// A variant to move a fixture to a position
+    SetPosition { position: Position },
+
+    /// A variant to fade a fixture to a color in CIE xy chromaticity
+    SetColor { color: Chromaticity },
+
+    /// A variant to fade a group with the values and start times spread
+    /// across its channels
+    PlayFan { fan: GroupFan },
+
+    /// A variant to change the level of a group of channels
+    SetGroupLevel { name: String, level: GroupLevel },
+
+    /// A variant to change the virtual intensity of a fixture without an
+    /// intensity channel
+    SetFixtureIntensity { name: String, level: GroupLevel },
+
+    /// A variant to black out a group of channels (or release it)
+    SetGroupBlackout {
+        name: String,
+        blackout: GroupBlackout,
+    },
+
+    /// A variant to change the opacity, mute, or solo of a layer
+    SetLayer { layer: Layer, update: LayerUpdate },
+
+    /// A variant to hold some channels above every playback
+    SetOverride { values: Override },
+
+    /// A variant to add, replace, or remove the labels of channels and
+    /// fixtures
+    SetLabels { labels: Labels },
+
+    /// A variant to add or replace a palette (until the configuration is
+    /// reloaded)
+    SetPalette { name: String, palette: Palette },
+
+    /// A variant to change the mode and address of a fixture (until the
+    /// configuration is reloaded)
+    PatchFixture { name: String, patch: FixturePatch },
+
+    /// A variant to release manual overrides
+    ReleaseOverride { release: OverrideRelease },
+
+    /// A variant to tap the beat of the beat clock
+    Tap,
+
+    /// A variant to change the tempo of the beat clock
+    SetTempo { tempo: Tempo },
+
+    /// A variant to change the multiplier of every fade duration
+    SetFadeMultiplier { multiplier: FadeMultiplier },
+
+    /// A variant to change the speed of the effects
+    SetEffectSpeed { speed: EffectSpeedUpdate },
+
+    /// A variant to turn the preheat of the warmed channels on or off
+    SetPreheat { preheat: PreheatState },
+
+    /// A variant to crossfade back to the previous look
+    Undo,
+
+    /// A variant to crossfade forward to the most recently undone look
+    Redo,
+
+    /// A variant to run the next cue of a cue list
+    Go { list: String },
+
+    /// A variant to return a cue list to the first cue
+    ResetCues { list: String },
+
+    /// A variant to play, pause, stop, seek, or loop a timeline
+    ControlTimeline {
+        name: String,
+        control: TimelineControl,
+    },
+
+    /// A variant to start a script (or restart it, if it is running)
+    RunScript { name: String },
+
+    /// A variant to stop a script, if it is running
+    StopScript { name: String },
+
+    /// A variant to apply several requests in order, in one pass
+    Batch { requests: Vec<Request> },
+
+    /// A variant to simulate several requests in order and return the
+    /// resulting universe, without changing the output
+    DryRun { requests: Vec<Request> },
+
+    /// A variant to get the current value of all the lights
+    GetUniverse,
+
+    /// A variant to list the names of the available scenes
+    GetScenes { query: SceneQuery },
+
+    /// A variant to get the current level of a group of channels
+    GetGroupLevel { name: String },
+
+    /// A variant to get the current virtual intensity of a fixture
+    GetFixtureIntensity { name: String },
+
+    /// A variant to get the current state of every layer
+    GetLayers,
+
+    /// A variant to get the tempo of the beat clock
+    GetTempo,
+
+    /// A variant to get the multiplier of every fade duration
+    GetFadeMultiplier,
+
+    /// A variant to get the speed of the effects
+    GetEffectSpeed,
+
+    /// A variant to get the preheat of the warmed channels
+    GetPreheat,
+
+    /// A variant to get the labels of the channels and fixtures
+    GetLabels,
+
+    /// A variant to get the available palettes
+    GetPalettes,
+
+    /// A variant to get the channels of every fixture, with any conflicts
+    GetPatch,
+
+    /// A variant to get the playback position of a cue list
+    GetCueList { list: String },
+
+    /// A variant to get the transport of a timeline
+    GetTimeline { name: String },
+
+    /// A variant to get the current status of the program
+    GetStatus,
+
+    /// A variant to list the features supported by the program
+    GetCapabilities,
+
+    /// A variant to search the audit log
+    GetAudit { query: AuditQuery },
+
+    /// A variant to search the event log
+    GetEvents { query: EventQuery },
+
+    /// A variant to get the statistics of the channels of the output
+    GetChannelStats { query: ChannelStatsQuery },
+
+    /// A variant to get the state of the backup
+    GetBackupStatus,
+
+    /// A variant to write the current universe to the backup right away
+    FlushBackup,
+
+    /// A variant to load the universe from the backup, as after a crash
+    RestoreBackup { query: RestoreQuery },
+
+    /// A variant to get the recent values of a channel
+    GetChannelHistory { channel: u32, query: HistoryQuery },
+
+    /// A variant to note that a client is still connected
+    Heartbeat { heartbeat: Heartbeat },
+
+    /// A variant to list the clients seen recently
+    GetClients,
+
+    /// A variant to change the log level while the program is running
+    SetLogLevel { level: String },
+
+    /// A variant to reload the configuration file
+    ReloadConfig,
+
+    /// A variant to close the program and unload all the data
+    Close,
+}
+
+// Define conversions from data types into a Request
+impl From<Fade> for Request {
+    fn from(fade: Fade) -> Self {
+        Request::PlayFade { fade }
+    }
+}
+impl From<Universe> for Request {
+    fn from(universe: Universe) -> Self {
+        Request::LoadUniverse {
+            universe,
+            expected_version: None,
+        }
+    }
+}
+impl From<SceneRecall> for Request {
+    fn from(recall: SceneRecall) -> Self {
+        Request::RecallScene { recall }
+    }
+}
+
+// Implement key features of the request
+impl Request {
+    /// A method to return the name of the request type (for logging)
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::AllStop => "allStop",
+            Request::EmergencyStop { .. } => "emergencyStop",
+            Request::DefineAllStop { .. } => "defineAllStop",
+            Request::PlayFade { .. } => "playFade",
+            Request::LoadUniverse { .. } => "loadUniverse",
+            Request::RecallScene { .. } => "recallScene",
+            Request::PlayEffect { .. } => "playEffect",
+            Request::PlayPreset { .. } => "playPreset",
+            Request::StopEffect { .. } => "stopEffect",
+            Request::TuneEffect { .. } => "tuneEffect",
+            Request::PlayMovement { .. } => "playMovement",
+            Request::PlayPixels { .. } => "playPixels",
+            Request::SetPosition { .. } => "setPosition",
+            Request::SetColor { .. } => "setColor",
+            Request::PlayFan { .. } => "playFan",
+            Request::SetGroupLevel { .. } => "setGroupLevel",
+            Request::SetFixtureIntensity { .. } => "setFixtureIntensity",
+            Request::SetGroupBlackout { .. } => "setGroupBlackout",
+            Request::SetLayer { .. } => "setLayer",
+            Request::SetOverride { .. } => "setOverride",
+            Request::SetLabels { .. } => "setLabels",
+            Request::SetPalette { .. } => "setPalette",
+            Request::PatchFixture { .. } => "patchFixture",
+            Request::ReleaseOverride { .. } => "releaseOverride",
+            Request::Tap => "tap",
+            Request::SetTempo { .. } => "setTempo",
+            Request::SetFadeMultiplier { .. } => "setFadeMultiplier",
+            Request::SetEffectSpeed { .. } => "setEffectSpeed",
+            Request::SetPreheat { .. } => "setPreheat",
+            Request::Undo => "undo",
+            Request::Redo => "redo",
+            Request::Go { .. } => "go",
+            Request::ResetCues { .. } => "resetCues",
+            Request::RunScript { .. } => "runScript",
+            Request::StopScript { .. } => "stopScript",
+            Request::Batch { .. } => "batch",
+            Request::DryRun { .. } => "dryRun",
+            Request::GetUniverse => "getUniverse",
+            Request::GetScenes { .. } => "getScenes",
+            Request::GetGroupLevel { .. } => "getGroupLevel",
+            Request::GetFixtureIntensity { .. } => "getFixtureIntensity",
+            Request::GetLayers => "getLayers",
+            Request::GetTempo => "getTempo",
+            Request::GetFadeMultiplier => "getFadeMultiplier",
+            Request::GetEffectSpeed => "getEffectSpeed",
+            Request::GetPreheat => "getPreheat",
+            Request::GetLabels => "getLabels",
+            Request::GetPalettes => "getPalettes",
+            Request::GetPatch => "getPatch",
+            Request::GetCueList { .. } => "getCueList",
+            Request::GetTimeline { .. } => "getTimeline",
+            Request::ControlTimeline { .. } => "controlTimeline",
+            Request::GetStatus => "getStatus",
+            Request::GetCapabilities => "getCapabilities",
+            Request::GetAudit { .. } => "getAudit",
+            Request::GetEvents { .. } => "getEvents",
+            Request::GetChannelStats { .. } => "getChannelStats",
+            Request::GetChannelHistory { .. } => "getChannelHistory",
+            Request::GetBackupStatus => "getBackupStatus",
+            Request::FlushBackup => "flushBackup",
+            Request::RestoreBackup { .. } => "restoreBackup",
+            Request::Heartbeat { .. } => "heartbeat",
+            Request::GetClients => "getClients",
+            Request::SetLogLevel { .. } => "setLogLevel",
+            Request::ReloadConfig => "reloadConfig",
+            Request::Close => "close",
+        }
+    }
+
+    /// A method to indicate whether the request changes the state of the
+    /// program (and should be recorded in the audit log)
+    ///
+    pub fn is_mutating(&self) -> bool {
+        !matches!(
+            self,
+            Request::GetUniverse
+                | Request::GetScenes { .. }
+                | Request::GetGroupLevel { .. }
+                | Request::GetFixtureIntensity { .. }
+                | Request::GetLayers
+                | Request::GetTempo
+                | Request::GetFadeMultiplier
+                | Request::GetEffectSpeed
+                | Request::GetPreheat
+                | Request::GetLabels
+                | Request::GetPalettes
+                | Request::GetPatch
+                | Request::GetCueList { .. }
+                | Request::GetTimeline { .. }
+                | Request::GetStatus
+                | Request::GetCapabilities
+                | Request::GetAudit { .. }
+                | Request::GetEvents { .. }
+                | Request::GetChannelStats { .. }
+                | Request::GetChannelHistory { .. }
+                | Request::GetBackupStatus
+                | Request::Heartbeat { .. }
+                | Request::GetClients
+                | Request::DryRun { .. }
+        )
+    }
+
+    /// A method to indicate the request changes the look on the output (and
+    /// so moves the look to a new version). Requests that only change the
+    /// settings, such as labels and the tempo, leave the version as is.
+    ///
+    pub fn changes_look(&self) -> bool {
+        match self {
+            Request::Batch { requests } => requests.iter().any(Request::changes_look),
+            Request::SetLabels { .. }
+            | Request::SetPalette { .. }
+            | Request::PatchFixture { .. }
+            | Request::DefineAllStop { .. }
+            | Request::Tap
+            | Request::SetTempo { .. }
+            | Request::SetFadeMultiplier { .. }
+            | Request::SetEffectSpeed { .. }
+            | Request::ResetCues { .. }
+            | Request::RunScript { .. }
+            | Request::StopScript { .. }
+            | Request::FlushBackup
+            | Request::SetLogLevel { .. }
+            | Request::ReloadConfig
+            | Request::Close => false,
+            request => request.is_mutating(),
+        }
+    }
+
+    /// A method to return the minimum role required to make the request
+    ///
+    pub fn required_role(&self) -> Role {
+        match self {
+            Request::Batch { requests } | Request::DryRun { requests } => requests
+                .iter()
+                .map(Request::required_role)
+                .max()
+                .unwrap_or(Role::Observer),
+            Request::GetUniverse
+            | Request::GetScenes { .. }
+            | Request::GetGroupLevel { .. }
+            | Request::GetFixtureIntensity { .. }
+            | Request::GetLayers
+            | Request::GetTempo
+            | Request::GetFadeMultiplier
+            | Request::GetEffectSpeed
+            | Request::GetPreheat
+            | Request::GetLabels
+            | Request::GetPalettes
+            | Request::GetPatch
+            | Request::GetCueList { .. }
+            | Request::GetTimeline { .. }
+            | Request::GetStatus
+            | Request::GetCapabilities
+            | Request::GetEvents { .. }
+            | Request::GetChannelStats { .. }
+            | Request::GetChannelHistory { .. }
+            | Request::GetBackupStatus
+            | Request::Heartbeat { .. }
+            | Request::GetClients => Role::Observer,
+            Request::AllStop
+            | Request::EmergencyStop { .. }
+            | Request::PlayFade { .. }
+            | Request::LoadUniverse { .. }
+            | Request::RecallScene { .. }
+            | Request::PlayEffect { .. }
+            | Request::PlayPreset { .. }
+            | Request::StopEffect { .. }
+            | Request::TuneEffect { .. }
+            | Request::PlayMovement { .. }
+            | Request::PlayPixels { .. }
+            | Request::SetPosition { .. }
+            | Request::SetColor { .. }
+            | Request::PlayFan { .. }
+            | Request::SetGroupLevel { .. }
+            | Request::SetFixtureIntensity { .. }
+            | Request::SetGroupBlackout { .. }
+            | Request::FlushBackup
+            | Request::RestoreBackup { .. }
+            | Request::SetLayer { .. }
+            | Request::SetOverride { .. }
+            | Request::SetPalette { .. }
+            | Request::ReleaseOverride { .. }
+            | Request::Tap
+            | Request::SetTempo { .. }
+            | Request::SetFadeMultiplier { .. }
+            | Request::SetEffectSpeed { .. }
+            | Request::SetPreheat { .. }
+            | Request::Undo
+            | Request::Redo
+            | Request::Go { .. }
+            | Request::ResetCues { .. }
+            | Request::ControlTimeline { .. }
+            | Request::RunScript { .. }
+            | Request::StopScript { .. }
+            | Request::SetLogLevel { .. } => Role::Operator,
+            Request::DefineAllStop { .. }
+            | Request::SetLabels { .. }
+            | Request::PatchFixture { .. }
+            | Request::GetAudit { .. }
+            | Request::ReloadConfig
+            | Request::Close => Role::Admin,
+        }
+    }
+}
+
+/// An enum to carry one request of a batch, in the same format as the body
+/// of the matching endpoint
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchRequest {
+    /// A variant to play a fade on a channel
+    PlayFade(Fade),
+
+    /// A variant to load all the lights to a defined value
+    LoadUniverse(Universe),
+
+    /// A variant to recall a scene from the configuration
+    RecallScene(SceneRecall),
+
+    /// A variant to start an effect on some channels
+    PlayEffect(EffectPlay),
+
+    /// A variant to start an effect preset from the configuration
+    PlayPreset(PresetPlay),
+
+    /// A variant to stop effects, holding the current values
+    StopEffect(EffectStop),
+
+    /// A variant to start a movement effect on some fixtures
+    PlayMovement(MovementPlay),
+
+    /// A variant to start a pixel generator on a pixel map
+    PlayPixels(PixelPlay),
+
+    /// A variant to move a fixture to a position
+    SetPosition(Position),
+
+    /// A variant to fade a fixture to a color in CIE xy chromaticity
+    SetColor(Chromaticity),
+
+    /// A variant to hold some channels above every playback
+    SetOverride(Override),
+
+    /// A variant to release manual overrides
+    ReleaseOverride(OverrideRelease),
+
+    /// A variant to define the emergency values of all the lights
+    DefineAllStop(Universe),
+
+    /// A variant to set all lights to their emergency values
+    AllStop,
+
+    /// A variant to crossfade back to the previous look
+    Undo,
+
+    /// A variant to crossfade forward to the most recently undone look
+    Redo,
+}
+
+// Convert a request of a batch into a full request
+impl From<BatchRequest> for Request {
+    fn from(request: BatchRequest) -> Self {
+        match request {
+            BatchRequest::PlayFade(fade) => Request::PlayFade { fade },
+            BatchRequest::LoadUniverse(universe) => Request::from(universe),
+            BatchRequest::RecallScene(recall) => Request::RecallScene { recall },
+            BatchRequest::PlayEffect(effect) => Request::PlayEffect { effect },
+            BatchRequest::PlayPreset(preset) => Request::PlayPreset { preset },
+            BatchRequest::StopEffect(stop) => Request::StopEffect { stop },
+            BatchRequest::PlayMovement(movement) => Request::PlayMovement { movement },
+            BatchRequest::PlayPixels(play) => Request::PlayPixels { play },
+            BatchRequest::SetPosition(position) => Request::SetPosition { position },
+            BatchRequest::SetColor(color) => Request::SetColor { color },
+            BatchRequest::SetOverride(values) => Request::SetOverride { values },
+            BatchRequest::ReleaseOverride(release) => Request::ReleaseOverride { release },
+            BatchRequest::DefineAllStop(universe) => Request::DefineAllStop { universe },
+            BatchRequest::AllStop => Request::AllStop,
+            BatchRequest::Undo => Request::Undo,
+            BatchRequest::Redo => Request::Redo,
+        }
+    }
+}
+
+/// A type to cover all web replies
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WebReply {
+    // A variant for replies with no specific content
+    #[serde(rename_all = "camelCase")]
+    Generic {
+        is_valid: bool,  // a flag to indicate the result of the request
+        message: String, // a message describing the success or failure
+    },
+
+    // A variant for replies to failed requests, with a code for the failure
+    #[serde(rename_all = "camelCase")]
+    Error {
+        is_valid: bool,  // a flag to indicate the result of the request
+        message: String, // a message describing the failure
+        #[serde(flatten)]
+        error: ErrorCode, // the code and details of the failure
+    },
+
+    // A variant for replies to a batch, with the reply to each request
+    #[serde(rename_all = "camelCase")]
+    Batch {
+        is_valid: bool,         // a flag to indicate that every request succeeded
+        replies: Vec<WebReply>, // the reply to each request that was processed, in order
+    },
+
+    // A variant for replies with the current universe
+    #[serde(rename_all = "camelCase")]
+    Universe {
+        is_valid: bool,     // a flag to indicate the result of the request
+        universe: Universe, // the current value of all the channels
+        version: u64,       // the version of the look, which changes with each change request
+        #[serde(default, skip_serializing_if = "Labels::is_empty")]
+        labels: Labels, // the labels of the channels and fixtures, if any
+    },
+
+    // A variant for replies with the available scenes
+    #[serde(rename_all = "camelCase")]
+    Scenes {
+        is_valid: bool,      // a flag to indicate the result of the request
+        scenes: Vec<String>, // the names of the matching scenes
+        #[serde(default)]
+        summaries: Vec<SceneSummary>, // the folder and tags of each matching scene
+    },
+
+    // A variant for replies with the current level of a group
+    #[serde(rename_all = "camelCase")]
+    GroupLevel {
+        is_valid: bool,    // a flag to indicate the result of the request
+        name: String,      // the name of the group
+        level: u8,         // the current level of the group, in percent
+        is_blackout: bool, // a flag to indicate the group is blacked out
+    },
+
+    // A variant for replies with the current virtual intensity of a fixture
+    #[serde(rename_all = "camelCase")]
+    FixtureIntensity {
+        is_valid: bool, // a flag to indicate the result of the request
+        name: String,   // the name of the fixture
+        level: u8,      // the current virtual intensity of the fixture, in percent
+    },
+
+    // A variant for replies with the state of every layer
+    #[serde(rename_all = "camelCase")]
+    Layers {
+        is_valid: bool,          // a flag to indicate the result of the request
+        layers: Vec<LayerState>, // the state of each layer, from the lowest priority to the highest
+    },
+
+    // A variant for replies with the tempo of the beat clock
+    #[serde(rename_all = "camelCase")]
+    Tempo {
+        is_valid: bool, // a flag to indicate the result of the request
+        bpm: f64,       // the current tempo, in beats per minute
+        beat: f64, // the number of beats since the last change of tempo, including the fraction of the current beat
+    },
+
+    // A variant for replies to a new fade
+    #[serde(rename_all = "camelCase")]
+    Fade {
+        is_valid: bool,  // a flag to indicate the result of the request
+        message: String, // a message describing the success
+        id: u64, // the identifier of the fade, for the fadeFinished and fadeCancelled events
+    },
+
+    // A variant for replies with the channels that changed
+    #[serde(rename_all = "camelCase")]
+    Changes {
+        is_valid: bool,   // a flag to indicate the result of the request
+        changes: Changes, // the changed channels and the current state version
+    },
+
+    // A variant for replies with the statistics of the channels
+    #[serde(rename_all = "camelCase")]
+    ChannelStats {
+        is_valid: bool,              // a flag to indicate the result of the request
+        channels: Vec<ChannelStats>, // the statistics of each channel
+    },
+
+    // A variant for replies with the state of the backup
+    #[serde(rename_all = "camelCase")]
+    Backup {
+        is_valid: bool,       // a flag to indicate the result of the request
+        backup: BackupStatus, // the state of the backup
+    },
+
+    // A variant for replies with the recent values of a channel
+    #[serde(rename_all = "camelCase")]
+    ChannelHistory {
+        is_valid: bool,              // a flag to indicate the result of the request
+        channel: u32,                // the channel
+        samples: Vec<HistorySample>, // the changes of the channel, oldest first
+    },
+
+    // A variant for replies with the frames written to the hardware
+    #[serde(rename_all = "camelCase")]
+    Frames {
+        is_valid: bool,             // a flag to indicate the result of the request
+        frames: Vec<CapturedFrame>, // the frames in the order they were written
+    },
+
+    // A variant for replies with the multiplier of every fade duration
+    #[serde(rename_all = "camelCase")]
+    FadeMultiplier {
+        is_valid: bool,  // a flag to indicate the result of the request
+        multiplier: f64, // the current multiplier of every fade duration
+    },
+
+    // A variant for replies with the speed of the effects
+    #[serde(rename_all = "camelCase")]
+    EffectSpeed {
+        is_valid: bool,     // a flag to indicate the result of the request
+        speed: EffectSpeed, // the current speed of the effects
+    },
+
+    // A variant for replies with the preheat of the warmed channels
+    #[serde(rename_all = "camelCase")]
+    Preheat {
+        is_valid: bool,              // a flag to indicate the result of the request
+        is_enabled: bool,            // a flag to indicate the preheat is applied
+        values: FnvHashMap<u32, u8>, // the preheat of each warmed channel
+    },
+
+    // A variant for replies with the labels of the channels and fixtures
+    #[serde(rename_all = "camelCase")]
+    Labels {
+        is_valid: bool, // a flag to indicate the result of the request
+        labels: Labels, // the labels of the channels and fixtures
+    },
+
+    // A variant for replies with the available palettes
+    #[serde(rename_all = "camelCase")]
+    Palettes {
+        is_valid: bool,                        // a flag to indicate the result of the request
+        palettes: FnvHashMap<String, Palette>, // the available palettes, by name
+    },
+
+    // A variant for replies with the channels of every fixture
+    #[serde(rename_all = "camelCase")]
+    Patch {
+        is_valid: bool,     // a flag to indicate the result of the request
+        patch: PatchReport, // the channels of every fixture, with any conflicts
+    },
+
+    // A variant for replies with the playback position of the cue list
+    #[serde(rename_all = "camelCase")]
+    CueList {
+        is_valid: bool,        // a flag to indicate the result of the request
+        status: CueListStatus, // the playback position of the cue list
+    },
+
+    // A variant for replies with the transport of a timeline
+    #[serde(rename_all = "camelCase")]
+    Timeline {
+        is_valid: bool,         // a flag to indicate the result of the request
+        status: TimelineStatus, // the transport of the timeline
+    },
+
+    // A variant for replies with the current status
+    #[serde(rename_all = "camelCase")]
+    Status {
+        is_valid: bool, // a flag to indicate the result of the request
+        status: Status, // the current status of the program
+    },
+
+    // A variant for replies with the supported features
+    #[serde(rename_all = "camelCase")]
+    Capabilities {
+        is_valid: bool,             // a flag to indicate the result of the request
+        capabilities: Capabilities, // the features supported by the program
+    },
+
+    // A variant for replies with entries from the audit log
+    #[serde(rename_all = "camelCase")]
+    Audit {
+        is_valid: bool,           // a flag to indicate the result of the request
+        entries: Vec<AuditEntry>, // the matching entries, oldest first
+    },
+
+    // A variant for replies with entries from the event log
+    #[serde(rename_all = "camelCase")]
+    Events {
+        is_valid: bool,          // a flag to indicate the result of the request
+        events: Vec<EventEntry>, // the matching entries, oldest first
+    },
+
+    // A variant for replies with the clients seen recently
+    #[serde(rename_all = "camelCase")]
+    Clients {
+        is_valid: bool,               // a flag to indicate the result of the request
+        clients: Vec<ClientActivity>, // the clients, most recently seen first
+    },
+}
+
+// Implement key features of the web reply
+impl WebReply {
+    /// A function to return a new, successful web reply
+    ///
+    pub fn success() -> WebReply {
+        WebReply::Generic {
+            is_valid: true,
+            message: "Request completed.".to_string(),
+        }
+    }
+
+    /// A function to return a new, failed web reply with the error code
+    ///
+    pub fn error(error: ErrorCode) -> WebReply {
+        WebReply::Error {
+            is_valid: false,
+            message: error.to_string(),
+            error,
+        }
+    }
+
+    /// A function to return a new, failed web reply from an error, using the
+    /// code of the error (if any)
+    ///
+    pub fn from_error(error: &anyhow::Error) -> WebReply {
+        WebReply::error(ErrorCode::from_error(error))
+    }
+
+    /// A method to check if the reply is a success
+    ///
+    pub fn is_success(&self) -> bool {
+        match self {
+            WebReply::Generic { is_valid, .. }
+            | WebReply::Error { is_valid, .. }
+            | WebReply::Batch { is_valid, .. }
+            | WebReply::Universe { is_valid, .. }
+            | WebReply::Scenes { is_valid, .. }
+            | WebReply::GroupLevel { is_valid, .. }
+            | WebReply::FixtureIntensity { is_valid, .. }
+            | WebReply::Layers { is_valid, .. }
+            | WebReply::Fade { is_valid, .. }
+            | WebReply::Changes { is_valid, .. }
+            | WebReply::Frames { is_valid, .. }
+            | WebReply::ChannelStats { is_valid, .. }
+            | WebReply::ChannelHistory { is_valid, .. }
+            | WebReply::Backup { is_valid, .. }
+            | WebReply::Tempo { is_valid, .. }
+            | WebReply::FadeMultiplier { is_valid, .. }
+            | WebReply::EffectSpeed { is_valid, .. }
+            | WebReply::Preheat { is_valid, .. }
+            | WebReply::Labels { is_valid, .. }
+            | WebReply::Palettes { is_valid, .. }
+            | WebReply::Patch { is_valid, .. }
+            | WebReply::CueList { is_valid, .. }
+            | WebReply::Timeline { is_valid, .. }
+            | WebReply::Status { is_valid, .. }
+            | WebReply::Capabilities { is_valid, .. }
+            | WebReply::Audit { is_valid, .. }
+            | WebReply::Events { is_valid, .. }
+            | WebReply::Clients { is_valid, .. } => *is_valid,
+        }
+    }
+}
+
+/// An enum to describe the connection to the backup server
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupState {
+    /// a variant indicating that no backup server was specified
+    Disabled,
+
+    /// a variant indicating that the backup server is connected
+    Connected,
+
+    /// a variant indicating that the backup server is not connected
+    Disconnected,
+}
+
+/// A struct to report the state of the backup, so operators can verify the
+/// recovery path
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatus {
+    pub state: BackupState,        // the state of the backup server connection
+    pub last_success: Option<u64>, // the time of the last successful write, in ms since the unix epoch
+    pub last_failure: Option<u64>, // the time of the last failed write, in ms since the unix epoch
+    pub pending_bytes: usize,      // the size of the latest universe that has not been written
+    pub is_expired: bool, // a flag to indicate a backup too old to restore at startup is held for a forced restore
+    pub targets: Vec<BackupTargetStatus>, // the state of each backup target
+}
+
+/// A struct to report the state of one backup target
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupTargetStatus {
+    pub location: String,   // the location of the target, without any password
+    pub state: BackupState, // the state of the connection to the target
+    pub last_success: Option<u64>, // the time of the last successful write, in ms since the unix epoch
+    pub last_failure: Option<u64>, // the time of the last failed write, in ms since the unix epoch
+    pub pending_bytes: usize,      // the size of the latest universe that has not been written
+}
+
+/// A struct to report the state of the system clock, for timed installations
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockStatus {
+    pub is_synchronized: Option<bool>, // a flag to indicate NTP has synchronized the clock, if known
+    pub drift: i64, // the gradual change of the wall clock against the monotonic clock since startup, in ms
+    pub steps: u64, // the number of times the wall clock stepped since startup
+    pub last_step: Option<i64>, // the size of the most recent step (behind is negative), in ms
+}
+
+/// A struct to report the timing of the frames of the output, so that a
+/// slow host or hardware line is noticed before the fades stutter
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameTimings {
+    pub frames: u64,        // the number of frames updated at a deadline
+    pub ticks_missed: u64,  // the number of deadlines skipped because an update ran late
+    pub late_frames: u64,   // the number of frames that finished after the next deadline
+    pub longest_frame: u64, // the longest time to update and write a frame, in microseconds
+}
+
+// Implement key features of the frame timings
+impl FrameTimings {
+    /// A method to record a frame that was updated at a deadline, given the
+    /// number of deadlines skipped before it and the time from the deadline
+    /// until the frame was written
+    ///
+    pub fn record(&mut self, skipped: u64, elapsed: Duration, resolution: Duration) {
+        self.frames += 1;
+        self.ticks_missed += skipped;
+        if elapsed > resolution {
+            self.late_frames += 1;
+        }
+        self.longest_frame = self.longest_frame.max(elapsed.as_micros() as u64);
+    }
+}
+
+/// A struct to report the frames that could not be written to the hardware,
+/// so that a dead output is noticed right away
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteStatus {
+    pub failures: u64, // the number of frames that could not be written since startup
+    pub consecutive_failures: u64, // the number of frames that could not be written since the last success
+    pub last_error: Option<String>, // the reason the most recent frame that failed could not be written, if any
+    pub failing_for: Option<u64>, // the time since the first failed write after the last success, in ms, if failing
+}
+
+/// A struct to report the current status of the program to monitoring tools
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub instance_id: String,   // the name of this instance in the backup keys
+    pub driver: String,        // the type of the DMX hardware
+    pub universes: u32,        // the number of DMX universes
+    pub active_fades: usize,   // the number of fades in progress
+    pub active_effects: usize, // the number of effects in progress
+    pub is_dmx_ready: bool,    // a flag to indicate the DMX hardware is accepting frames
+    pub backup: BackupState,   // the state of the backup server connection
+    pub uptime: u64,           // the time since the program started, in secs
+    pub is_emergency: bool,    // a flag to indicate the emergency stop is engaged
+    pub is_read_only: bool,    // a flag to indicate clients cannot change anything
+    pub clock: ClockStatus,    // the state of the system clock
+    pub timings: FrameTimings, // the timing of the frames of the output
+    pub writes: WriteStatus,   // the frames that could not be written to the hardware
+}
+
+// Implement key features of the status
+impl Status {
+    /// A method to indicate whether the program is ready to control the
+    /// lights (the DMX hardware is accepting frames and the backup server,
+    /// if specified, is connected)
+    ///
+    pub fn is_ready(&self) -> bool {
+        self.is_dmx_ready && (self.backup != BackupState::Disconnected)
+    }
+}
+
+/// A struct to describe the channels of the output that changed after a
+/// state version
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    pub version: u64,                // the current state version
+    pub is_full: bool,               // a flag to indicate every channel is included
+    pub values: FnvHashMap<u32, u8>, // the current value of each changed channel
+}
+
+/// A struct to describe one frame as it was written to the DMX hardware,
+/// for debugging the timing of the output
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedFrame {
+    pub timestamp: u64, // the time of the write, in microseconds since the unix epoch
+    pub is_written: bool, // a flag to indicate the hardware accepted the frame
+    pub frame: Universe, // the values of every channel of the frame
+}
+
+/// A struct to describe the features supported by the program, so that
+/// clients can adapt to the server they are connected to
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub version: String,               // the version of the program
+    pub api_versions: Vec<String>,     // the supported versions of the web api
+    pub drivers: Vec<String>,          // the supported types of DMX hardware
+    pub effects: Vec<String>,          // the available effects
+    pub pixel_generators: Vec<String>, // the available pixel generators
+    pub protocols: Vec<String>,        // the supported inputs for requests and triggers
+    pub universes: u32,                // the number of DMX universes
+    pub channels: u32,                 // the number of channels in each universe
+    pub scenes: usize,                 // the number of available scenes
+    pub features: Vec<String>,         // the supported optional features
+}
@@ -0,0 +1,975 @@
+// Copyright (c) 2026 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the configuration of the program, which can be
+//! loaded from a YAML file and adjusted from the commandline.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A type to reload the configuration of the program while it is running
+///
+pub type ConfigLoader = Box<dyn Fn() -> Result<Config> + Send>;
+
+/// A struct to hold the full configuration of the program. Any field left
+/// out of the configuration file is set to its default value.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    pub path: Option<PathBuf>,  // the hardware address for the DMX connection
+    pub dry_run: bool,          // a flag to simulate the output without writing to the hardware
+    pub read_only: bool,        // a flag to refuse every change from clients
+    pub swagger_ui: bool,       // a flag to serve the Swagger UI at /docs
+    pub address: String,        // the address for the web interface
+    pub backup: Option<String>, // the address for the backup server, if any
+    pub instance_id: Option<String>, // the name in the backup keys, if not the address
+    pub backups: Vec<String>,   // the other Redis servers or file:// backup targets
+    pub backup_max_age: Option<u64>, // the oldest backup to restore, in secs, if limited
+    pub log_level: String,      // the log level of the program
+    pub log_format: LogFormat,  // the format of the log output
+    pub log_file: Option<PathBuf>, // the file for the log output, if any (otherwise the terminal)
+    pub log_max_size: u64,      // the size before rotating the log file, in MB (zero to disable)
+    pub log_max_age: u64,       // the age before rotating the log file, in hours (zero to disable)
+    pub log_max_files: usize,   // the number of rotated log files to keep
+    pub otlp: Option<OtlpConfig>, // the collector for the request spans, if any
+    pub resolution: u64,        // the time between fade updates, in ms
+    pub dmx_thread: Option<DmxThreadConfig>, // the dedicated thread to write the frames, if any
+    pub exit_behavior: ExitBehavior, // the behavior of the output when the program closes
+    pub exit_time: u64,         // the time to apply the exit behavior, in secs
+    pub undo_depth: usize,      // the number of looks that can be undone
+    pub undo_time: u64,         // the time to crossfade when undoing or redoing a look, in ms
+    pub override_release_time: u64, // the time to release a manual override, in ms
+    pub default_fade: u64, // the duration of fades and scene recalls that do not give one, in ms (zero for instant)
+    pub idle: Option<IdleConfig>, // the watchdog that engages a safe look without control traffic, if any
+    pub channel_history: Option<HistoryConfig>, // the recording of the output over time, if any
+    pub max_fade_duration: u64,   // the longest fade accepted by the web interface, in secs
+    pub scenes: FnvHashMap<String, Scene>, // the available scenes, by name
+    pub startup_scene: Option<String>, // the scene to recall at startup if there is no backup
+    pub cues: Vec<Cue>,           // the cues of the main cue list, in order
+    pub cue_lists: FnvHashMap<String, Vec<Cue>>, // the other cue lists (such as a lobby loop), by name
+    pub timelines: FnvHashMap<String, Timeline>, // the actions placed at absolute times, by name
+    pub profiles: FnvHashMap<String, FixtureProfile>, // the types of fixtures, by name
+    pub fixtures: FnvHashMap<String, Fixture>,   // the patched fixtures, by name
+    pub groups: FnvHashMap<String, ChannelGroup>, // the groups of channels with a shared level, by name
+    pub palettes: FnvHashMap<String, Palette>, // the colors, positions, and beam states shared by scenes, by name
+    pub effect_presets: FnvHashMap<String, EffectPreset>, // the effects saved with their parameters, by name
+    pub max_fade_rates: FnvHashMap<u32, f64>, // the fastest each channel may change, in units per second, by channel
+    pub preheat: FnvHashMap<u32, u8>, // the lowest output of each tungsten channel, to keep the filament warm, by channel
+    pub is_preheat_enabled: bool,     // a flag to apply the preheat at startup
+    pub pixel_maps: FnvHashMap<String, PixelMap>, // the LED strips and matrices, by name
+    pub labels: Labels,               // the names of the channels and fixtures, for clients to show
+    pub tokens: FnvHashMap<String, Role>, // the tokens accepted by the web interface and their roles (if empty, no token is required)
+    pub cors: Option<CorsConfig>,         // the cross-origin settings of the web interface, if any
+    pub socket: Option<PathBuf>,          // the unix socket for the web interface, if any
+    pub audit_file: Option<PathBuf>, // the file for the audit log, if any (otherwise the audit log is kept in memory)
+    pub event_file: Option<PathBuf>, // the file for the event log, if any (otherwise the event log is kept in memory)
+    pub web_root: Option<PathBuf>, // the directory of a custom web UI, if any (otherwise the bundled UI is served)
+    pub webhooks: Vec<WebhookConfig>, // the webhooks to notify of events
+    pub triggers: Vec<Trigger>,    // the inputs that trigger actions
+    pub scripts: FnvHashMap<String, Script>, // the available scripts, by name
+    pub osc_address: Option<String>, // the udp address to listen for OSC triggers, if any
+    pub midi_device: Option<PathBuf>, // the raw MIDI device to listen for MIDI triggers, if any
+    pub gpio: GpioConfig,          // the settings of the GPIO triggers
+    pub emergency_stop: Option<EmergencyStopConfig>, // the hardwired emergency stop input, if any
+    pub audio: Option<AudioConfig>, // the audio input for sound-to-light, if any
+    pub art_net: Option<ArtNetConfig>, // the settings of the Art-Net node, if any
+    pub sacn: Option<SacnConfig>,  // the settings of the sACN output, if any
+    pub citp: Option<CitpConfig>,  // the settings of the CITP peer for visualizers, if any
+    pub follow: Option<FollowConfig>, // the leader to mirror on the local hardware, if this is a follower
+    pub bridge: Option<BridgeConfig>, // the network input to pass straight to the hardware, if this is a bridge
+    pub rate_limit: Option<RateLimitConfig>, // the limit on requests from each client, if any
+    pub max_concurrent_requests: Option<usize>, // the most requests the web interface handles at once, if limited
+    pub confirm: Option<ConfirmConfig>, // the requests that must be repeated with a token to run, if any
+}
+
+/// An enum to define the format of the log output
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    /// a variant for human-readable lines of text
+    Text,
+
+    /// a variant for one JSON object per line
+    Json,
+}
+
+/// A struct to hold the cross-origin (CORS) settings of the web interface,
+/// which allow browser-based control surfaces hosted elsewhere to make
+/// requests.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>, // the origins allowed to make requests ("*" allows any origin)
+    pub allowed_methods: Vec<String>, // the request methods allowed from those origins
+    pub allowed_headers: Vec<String>, // the request headers allowed from those origins
+}
+
+/// A struct to hold the settings of the OpenTelemetry export, which posts
+/// the request spans to a collector over OTLP/HTTP
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtlpConfig {
+    pub endpoint: String, // the http url of the collector (the spans are posted to /v1/traces)
+    #[serde(default)]
+    pub service_name: Option<String>, // the name of the service in the traces, if not vulcan
+}
+
+/// A struct to hold the settings of one webhook, which is sent each event
+/// as a JSON post
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    pub url: String, // the http url to post the events to
+    #[serde(default)]
+    pub events: Vec<String>, // the names of the events to send (if empty, every event is sent)
+}
+
+/// A struct to hold the settings of the GPIO inputs. The pins are taken from
+/// the GPIO triggers.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GpioConfig {
+    pub debounce: u64,    // the time a pin must hold steady to change state, in ms
+    pub long_press: u64,  // the time a button must be held for a long press, in ms
+    pub active_low: bool, // a flag to indicate the pins read low when pressed (such as with pull-ups)
+}
+
+/// A struct to hold the settings of a hardwired emergency stop. The input
+/// is either a GPIO pin or a control line of a serial port.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmergencyStopConfig {
+    pub gpio: Option<u32>,       // the GPIO pin of the input, if any
+    pub serial: Option<PathBuf>, // the serial port of the input, if any
+    #[serde(default)]
+    pub line: ControlLine, // the control line of the serial port to read
+    #[serde(default)]
+    pub active_low: bool, // a flag to indicate the input reads low when engaged (such as a normally closed loop)
+    #[serde(default = "default_estop_debounce")]
+    pub debounce: u64, // the time the input must hold steady to change state, in ms
+}
+
+/// An enum to select an input control line of a serial port
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ControlLine {
+    /// the clear to send line
+    #[default]
+    Cts,
+
+    /// the data set ready line
+    Dsr,
+
+    /// the data carrier detect line
+    Dcd,
+
+    /// the ring indicator line
+    Ri,
+}
+
+/// A struct to hold the settings of the audio input. The input is a raw
+/// stream of signed 16-bit little-endian mono samples (such as a pipe from
+/// arecord), which is split into frequency bands.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioConfig {
+    pub device: PathBuf, // the raw audio stream to analyze
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32, // the samples per second of the stream
+    pub bands: Vec<AudioBand>, // the frequency bands to follow
+}
+
+/// A struct to define one frequency band of the audio input. The energy of
+/// the band scales the output of its channels, and each beat in the band
+/// fires any audio trigger for the band.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioBand {
+    pub name: String, // the name of the band, for triggers
+    pub low: f64,     // the lowest frequency of the band, in Hz
+    pub high: f64,    // the highest frequency of the band, in Hz
+    #[serde(default)]
+    pub channels: Vec<u32>, // the channels that follow the energy of the band, if any
+    #[serde(default)]
+    pub floor: u8, // the level of the channels when the band is silent, in percent
+    #[serde(default = "default_beat_threshold")]
+    pub threshold: f64, // the ratio above the average energy of the band for a beat
+}
+
+/// A struct to hold the rate limit of the web interface. Each client (by
+/// token, or by address without a token) has a bucket of requests that
+/// refills at a steady rate.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RateLimitConfig {
+    pub requests_per_second: u32, // the rate the bucket of each client refills
+    pub burst: u32,               // the size of the bucket of each client
+}
+
+/// A struct to hold the requests that need confirmation. Each of these
+/// requests is answered with a token at first, and only runs when it is
+/// repeated with that token before the timeout.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmConfig {
+    pub requests: Vec<String>, // the names of the requests (such as close or loadUniverse)
+    #[serde(default = "default_confirm_timeout")]
+    pub timeout: u64, // the time to repeat the request with its token, in secs
+}
+
+/// A struct to hold the settings of the idle watchdog. If no control
+/// traffic arrives for the timeout, the output fades to the safe look: the
+/// scene (if any) or otherwise the all stop values.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleConfig {
+    pub timeout: u64, // the time without control traffic before the safe look, in secs
+    #[serde(default)]
+    pub scene: Option<String>, // the scene of the safe look, if any (otherwise the all stop values)
+    #[serde(default = "default_idle_fade")]
+    pub fade: u64, // the time to fade to the safe look, in ms
+}
+
+/// A struct to hold the settings of the dedicated thread that writes the
+/// frames to the hardware
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DmxThreadConfig {
+    pub priority: Option<u8>, // the real-time priority of the thread, from 1 to 99 (otherwise the normal priority)
+}
+
+/// A struct to hold the settings of the channel history. The output is
+/// sampled at the resolution, and the changes of each channel are kept until
+/// they are older than the retention (or beyond the limit of samples).
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryConfig {
+    #[serde(default = "default_history_resolution")]
+    pub resolution: u64, // the time between samples, in ms
+    #[serde(default = "default_history_retention")]
+    pub retention: u64, // the age of the oldest sample, in secs
+    #[serde(default = "default_history_samples")]
+    pub max_samples: usize, // the number of changes kept for each channel
+}
+
+/// A struct to hold the settings of the Art-Net node. The node answers the
+/// polls of other consoles on the network, reporting the universe that
+/// Vulcan outputs to its DMX hardware.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ArtNetConfig {
+    pub address: String,    // the udp address to listen for Art-Net packets
+    pub universe: u16,      // the Art-Net port-address of the universe (net, sub-net, and universe)
+    pub short_name: String, // the name of the node shown by other consoles (up to 17 characters)
+    pub long_name: String,  // the full description of the node (up to 63 characters)
+}
+
+// Implement the default Art-Net settings
+impl Default for ArtNetConfig {
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_ART_NET_ADDRESS.to_string(),
+            universe: 0,
+            short_name: DEFAULT_NODE_NAME.to_string(),
+            long_name: format!("{} DMX Controller", DEFAULT_NODE_NAME),
+        }
+    }
+}
+
+/// A struct to hold the settings of the sACN (E1.31) output. The output is
+/// sent to the multicast group of the universe (or to a single receiver),
+/// and the universe is advertised with universe discovery packets.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SacnConfig {
+    pub universe: u16,               // the sACN universe of the output (1-63999)
+    pub source_name: String, // the name of the source shown by receivers (up to 63 characters)
+    pub priority: u8,        // the priority of the output (0-200)
+    pub destination: Option<String>, // the udp address of a single receiver, if any (otherwise the output is multicast)
+}
+
+// Implement the default sACN settings
+impl Default for SacnConfig {
+    fn default() -> Self {
+        Self {
+            universe: 1,
+            source_name: DEFAULT_NODE_NAME.to_string(),
+            priority: DEFAULT_SACN_PRIORITY,
+            destination: None,
+        }
+    }
+}
+
+/// A struct to hold the settings of the CITP peer. Previsualization tools
+/// find the peer on the network and connect to receive the output.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CitpConfig {
+    pub name: String,    // the name of the peer shown by visualizers
+    pub address: String, // the tcp address to listen for visualizers
+}
+
+// Implement the default CITP settings
+impl Default for CitpConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_NODE_NAME.to_string(),
+            address: DEFAULT_CITP_ADDRESS.to_string(),
+        }
+    }
+}
+
+/// A struct to hold the settings of a follower, which mirrors the sACN
+/// output of a leader on its own DMX hardware
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FollowConfig {
+    pub universe: u16,          // the sACN universe of the leader (1-63999)
+    pub address: String,        // the udp address to listen for the leader
+    pub leader: Option<String>, // the ip address of the leader, if any (otherwise any source of the universe)
+}
+
+// Implement the default follower settings
+impl Default for FollowConfig {
+    fn default() -> Self {
+        Self {
+            universe: 1,
+            address: DEFAULT_FOLLOW_ADDRESS.to_string(),
+            leader: None,
+        }
+    }
+}
+
+/// An enum to select the protocol received by a bridge
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BridgeProtocol {
+    /// a variant to receive ArtDmx packets
+    #[default]
+    ArtNet,
+
+    /// a variant to receive sACN (E1.31) data packets
+    Sacn,
+}
+
+/// A struct to hold the settings of a bridge, which passes one universe
+/// from the show network straight to the DMX hardware and to any other
+/// universes of the show network (without any playback, web interface, or
+/// backup)
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BridgeConfig {
+    pub protocol: BridgeProtocol,   // the protocol to receive
+    pub universe: u16, // the universe to receive (the Art-Net port-address or the sACN universe)
+    pub address: Option<String>, // the udp address to listen on, if any (otherwise the usual address of the protocol)
+    pub hardware: bool,          // a flag to write the universe to the DMX hardware
+    pub outputs: Vec<BridgeOutput>, // the universes of the show network to send the universe to
+}
+
+// Implement the default bridge settings
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            protocol: BridgeProtocol::ArtNet,
+            universe: 1,
+            address: None,
+            hardware: true,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+/// A struct to hold the settings of one output of a bridge, which may use
+/// a different protocol and universe than the input
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BridgeOutput {
+    pub protocol: BridgeProtocol,    // the protocol to send
+    pub universe: u16, // the universe to send (the Art-Net port-address or the sACN universe)
+    pub destination: Option<String>, // the udp address to send to, if any (otherwise the Art-Net broadcast address or the sACN multicast group)
+    pub priority: Option<u8>, // the sACN priority, if any (otherwise the priority of an sACN input, or the default priority)
+}
+
+// Implement the default bridge output settings
+impl Default for BridgeOutput {
+    fn default() -> Self {
+        Self {
+            protocol: BridgeProtocol::Sacn,
+            universe: 1,
+            destination: None,
+            priority: None,
+        }
+    }
+}
+
+// Implement the default rate limit
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: DEFAULT_RATE_LIMIT,
+            burst: DEFAULT_RATE_BURST,
+        }
+    }
+}
+
+// Implement the default GPIO settings
+impl Default for GpioConfig {
+    fn default() -> Self {
+        Self {
+            debounce: DEFAULT_GPIO_DEBOUNCE,
+            long_press: DEFAULT_GPIO_LONG_PRESS,
+            active_low: true,
+        }
+    }
+}
+
+// Implement the default cross-origin settings
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec![
+                "content-type".to_string(),
+                "authorization".to_string(),
+                "x-api-key".to_string(),
+                "x-client-id".to_string(),
+            ],
+        }
+    }
+}
+
+// Implement the default configuration
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: None,
+            dry_run: false,
+            read_only: false,
+            swagger_ui: false,
+            address: DEFAULT_ADDRESS.to_string(),
+            backup: None,
+            instance_id: None,
+            backups: Vec::new(),
+            backup_max_age: None,
+            log_level: DEFAULT_LOGLEVEL.to_string(),
+            log_format: LogFormat::Text,
+            log_file: None,
+            log_max_size: DEFAULT_LOG_MAX_SIZE,
+            log_max_age: 0,
+            log_max_files: DEFAULT_LOG_MAX_FILES,
+            otlp: None,
+            resolution: DEFAULT_RESOLUTION,
+            dmx_thread: None,
+            exit_behavior: ExitBehavior::Hold,
+            exit_time: DEFAULT_EXIT_TIME,
+            undo_depth: DEFAULT_UNDO_DEPTH,
+            undo_time: DEFAULT_UNDO_TIME,
+            override_release_time: DEFAULT_RELEASE_TIME,
+            default_fade: 0,
+            idle: None,
+            channel_history: None,
+            max_fade_duration: DEFAULT_MAX_FADE_DURATION,
+            scenes: FnvHashMap::default(),
+            startup_scene: None,
+            cues: Vec::new(),
+            cue_lists: FnvHashMap::default(),
+            timelines: FnvHashMap::default(),
+            profiles: FnvHashMap::default(),
+            fixtures: FnvHashMap::default(),
+            groups: FnvHashMap::default(),
+            palettes: FnvHashMap::default(),
+            effect_presets: FnvHashMap::default(),
+            max_fade_rates: FnvHashMap::default(),
+            preheat: FnvHashMap::default(),
+            is_preheat_enabled: true,
+            pixel_maps: FnvHashMap::default(),
+            labels: Labels::default(),
+            tokens: FnvHashMap::default(),
+            cors: None,
+            socket: None,
+            audit_file: None,
+            event_file: None,
+            web_root: None,
+            webhooks: Vec::new(),
+            triggers: Vec::new(),
+            scripts: FnvHashMap::default(),
+            osc_address: None,
+            midi_device: None,
+            gpio: GpioConfig::default(),
+            emergency_stop: None,
+            audio: None,
+            art_net: None,
+            sacn: None,
+            citp: None,
+            follow: None,
+            bridge: None,
+            rate_limit: None,
+            max_concurrent_requests: None,
+            confirm: None,
+        }
+    }
+}
+
+// Implement key features of the audio band
+impl AudioBand {
+    /// A method to convert the energy of the band, from zero to one, into
+    /// the level of its channels, in percent
+    ///
+    pub fn level(&self, energy: f64) -> u8 {
+        let floor = self.floor.min(FULL_LEVEL) as f64;
+        (floor + (FULL_LEVEL as f64 - floor) * energy.clamp(0.0, 1.0)).round() as u8
+    }
+}
+
+/// A helper function to default the fade to the safe look of the idle
+/// watchdog
+///
+fn default_idle_fade() -> u64 {
+    DEFAULT_IDLE_FADE
+}
+
+/// A helper function to default the time between samples of the channel
+/// history
+///
+fn default_history_resolution() -> u64 {
+    DEFAULT_HISTORY_RESOLUTION
+}
+
+/// A helper function to default the age of the oldest sample of the channel
+/// history
+///
+fn default_history_retention() -> u64 {
+    DEFAULT_HISTORY_RETENTION
+}
+
+/// A helper function to default the number of changes kept for each channel
+///
+fn default_history_samples() -> usize {
+    DEFAULT_HISTORY_SAMPLES
+}
+
+/// A helper function to default the debounce of the emergency stop input
+///
+fn default_estop_debounce() -> u64 {
+    DEFAULT_ESTOP_DEBOUNCE
+}
+
+/// A helper function to default the time to confirm a request
+///
+fn default_confirm_timeout() -> u64 {
+    DEFAULT_CONFIRM_TIMEOUT
+}
+
+/// A helper function to default the sample rate of the audio input
+///
+fn default_sample_rate() -> u32 {
+    DEFAULT_SAMPLE_RATE
+}
+
+/// A helper function to default the beat threshold of an audio band
+///
+fn default_beat_threshold() -> f64 {
+    DEFAULT_BEAT_THRESHOLD
+}
+
+// Implement key features of the configuration
+impl Config {
+    /// A function to load a configuration from a YAML file
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the file cannot be read or is
+    /// not a valid configuration.
+    ///
+    pub fn from_file(path: &Path) -> Result<Self> {
+        // Try to read the file
+        let contents = fs::read_to_string(path).map_err(|error| {
+            anyhow!(
+                "Unable to read configuration file {}: {}.",
+                path.display(),
+                error
+            )
+        })?;
+
+        // Try to parse the configuration
+        serde_yaml::from_str(&contents).map_err(|error| {
+            anyhow!(
+                "Unable to parse configuration file {}: {}.",
+                path.display(),
+                error
+            )
+        })
+    }
+
+    /// A method to return the name of this instance, which is the address of
+    /// the web interface unless an instance id was specified
+    ///
+    pub fn instance_id(&self) -> String {
+        self.instance_id
+            .clone()
+            .unwrap_or_else(|| self.address.clone())
+    }
+
+    /// A method to return every backup target, starting with the backup
+    /// server (if any)
+    ///
+    pub fn all_backups(&self) -> Vec<String> {
+        self.backup
+            .iter()
+            .chain(self.backups.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// A method to return every cue list by name, including the main list
+    /// of the `cues` (unless it is empty and another list is named main)
+    ///
+    pub fn all_cue_lists(&self) -> FnvHashMap<String, Vec<Cue>> {
+        let mut lists = self.cue_lists.clone();
+        if !self.cues.is_empty() || !lists.contains_key(MAIN_CUE_LIST) {
+            lists.insert(MAIN_CUE_LIST.to_string(), self.cues.clone());
+        }
+        lists
+    }
+
+    /// A method to find a scene by name, including the values of its
+    /// palettes. The values of the scene itself take precedence over the
+    /// palettes.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the scene, one of its palettes,
+    /// or one of their fixtures does not exist.
+    ///
+    pub fn scene(&self, name: &str) -> Result<Scene> {
+        let scene = self
+            .scenes
+            .get(name)
+            .ok_or_else(|| ErrorCode::SceneNotFound {
+                scene: name.to_string(),
+            })?;
+
+        // Apply each palette in order, and then the values of the scene
+        let mut values = FnvHashMap::default();
+        for usage in scene.palettes.iter() {
+            let palette =
+                self.palettes
+                    .get(&usage.palette)
+                    .ok_or_else(|| ErrorCode::PaletteNotFound {
+                        palette: usage.palette.clone(),
+                    })?;
+            for name in usage.fixtures.iter() {
+                let (fixture, profile) = self.fixture(name)?;
+                values.extend(palette.channels(fixture, profile));
+            }
+        }
+        values.extend(
+            scene
+                .values
+                .iter()
+                .map(|(channel, value)| (*channel, *value)),
+        );
+        Ok(Scene {
+            values,
+            ..scene.clone()
+        })
+    }
+
+    /// A method to find a fixture and its profile by name
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not exist or its
+    /// profile is missing.
+    ///
+    pub fn fixture(&self, name: &str) -> Result<(&Fixture, &FixtureProfile)> {
+        let fixture = self
+            .fixtures
+            .get(name)
+            .ok_or_else(|| ErrorCode::FixtureNotFound {
+                fixture: name.to_string(),
+            })?;
+        let profile =
+            self.profiles
+                .get(&fixture.profile)
+                .ok_or_else(|| ErrorCode::InvalidConfig {
+                    reason: format!(
+                        "Fixture {} uses a missing profile: {}.",
+                        name, fixture.profile
+                    ),
+                })?;
+        if let Some(mode) = &fixture.mode
+            && !profile.modes.contains_key(mode)
+        {
+            return Err(ErrorCode::InvalidConfig {
+                reason: format!(
+                    "Fixture {} uses a missing mode of {}: {}.",
+                    name, fixture.profile, mode
+                ),
+            }
+            .into());
+        }
+        Ok((fixture, profile))
+    }
+
+    /// A method to find the channels that the virtual intensity of a
+    /// fixture dims: the color emitters of a fixture without an intensity
+    /// channel
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not exist, has
+    /// its own intensity channel, or has no color emitters.
+    ///
+    pub fn intensity_channels(&self, name: &str) -> Result<Vec<u32>> {
+        let (fixture, profile) = self.fixture(name)?;
+        let reason = if fixture.channel(profile, &Parameter::Intensity).is_some() {
+            "The fixture has its own intensity channel."
+        } else {
+            let channels = fixture.color_channels(profile);
+            if !channels.is_empty() {
+                return Ok(channels);
+            }
+            "The fixture has no color channels to dim."
+        };
+        Err(ErrorCode::InvalidRequest {
+            fields: vec![FieldError::new("fixture", reason)],
+        }
+        .into())
+    }
+
+    /// A method to change the mode and address of a fixture (until the
+    /// configuration is reloaded), after checking that the fixture still
+    /// fits in the universe without overlapping any other fixture
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture or its new mode does
+    /// not exist, the fixture would end beyond the universe, or it would
+    /// overlap other fixtures. The patch is unchanged in that case.
+    ///
+    pub fn patch_fixture(&mut self, name: &str, patch: FixturePatch) -> Result<()> {
+        // Find the channels of the fixture in the new mode
+        let (fixture, profile) = self.fixture(name)?;
+        if profile.parameters(patch.mode.as_deref()).is_none() {
+            return Err(ErrorCode::InvalidRequest {
+                fields: vec![FieldError::new(
+                    "mode",
+                    format!("The {} profile has no mode of this name.", fixture.profile),
+                )],
+            }
+            .into());
+        }
+        let patched = Fixture {
+            mode: patch.mode,
+            address: patch.address,
+            ..fixture.clone()
+        };
+        let channels = patched.channels(profile);
+        if channels.end > DMX_MAX + 1 {
+            return Err(ErrorCode::InvalidRequest {
+                fields: vec![FieldError::new(
+                    "address",
+                    format!("The fixture must end by channel {}.", DMX_MAX),
+                )],
+            }
+            .into());
+        }
+
+        // Make sure no other fixture would use the same channels
+        let mut fixtures = self.fixtures.clone();
+        fixtures.insert(name.to_string(), patched);
+        let conflicts: Vec<PatchConflict> = PatchReport::new(&fixtures, &self.profiles)
+            .conflicts
+            .into_iter()
+            .filter(|conflict| conflict.fixtures.iter().any(|fixture| fixture == name))
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(ErrorCode::AddressConflict { conflicts }.into());
+        }
+
+        // Replace the patch of the fixture
+        self.fixtures = fixtures;
+        Ok(())
+    }
+
+    /// A method to map every channel of the fixtures, with any conflicts
+    ///
+    pub fn patch_report(&self) -> PatchReport {
+        PatchReport::new(&self.fixtures, &self.profiles)
+    }
+
+    /// A method to find a pixel map by name
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the pixel map does not exist.
+    ///
+    pub fn pixel_map(&self, name: &str) -> Result<&PixelMap> {
+        Ok(self
+            .pixel_maps
+            .get(name)
+            .ok_or_else(|| ErrorCode::PixelMapNotFound {
+                map: name.to_string(),
+            })?)
+    }
+}
+
+// Tests of the configuration module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Test that missing fields are filled with defaults
+    #[test]
+    fn partial_config() {
+        // Parse a configuration with only a few fields
+        let config: Config =
+            serde_yaml::from_str("path: /dev/ttyUSB1\nexitBehavior: blackout\nexitTime: 5\n")
+                .unwrap();
+
+        // Check the specified and default fields
+        assert_eq!(config.path, Some(PathBuf::from("/dev/ttyUSB1")));
+        assert_eq!(config.exit_behavior, ExitBehavior::Blackout);
+        assert_eq!(config.exit_time, 5);
+        assert_eq!(config.address, DEFAULT_ADDRESS);
+        assert_eq!(config.resolution, DEFAULT_RESOLUTION);
+    }
+
+    // Test that effect presets become the request for their effect
+    #[test]
+    fn effect_presets() {
+        let config: Config = serde_yaml::from_str(
+            "effectPresets:
+  slowPulse: { type: effect, effect: sine, channels: [1, 2], period: { secs: 4, nanos: 0 }}
+  ballyhoo: { type: movement, fixtures: [spot], shape: circle, pan: 270, tilt: 90, radius: 30 }
+",
+        )
+        .unwrap();
+        match config.effect_presets["slowPulse"].to_request() {
+            Request::PlayEffect { effect } => {
+                assert_eq!(effect.channels, vec![1, 2]);
+                assert_eq!(effect.high, 255);
+                assert_eq!(effect.period, Duration::from_secs(4));
+            }
+            request => panic!("Expected an effect, found {:?}.", request),
+        }
+        assert!(matches!(
+            config.effect_presets["ballyhoo"].to_request(),
+            Request::PlayMovement { .. }
+        ));
+    }
+
+    // Test that a fixture changes mode only if it does not overlap another
+    #[test]
+    fn patch_fixture_mode() {
+        let mut config: Config = serde_yaml::from_str(
+            "profiles:
+  wash: { channels: [red, green, blue], modes: { extended: [intensity, red, green, blue, white] }}
+fixtures:
+  washLeft: { profile: wash, address: 1 }
+  washRight: { profile: wash, address: 5 }
+",
+        )
+        .unwrap();
+
+        // The extended mode would overlap the next fixture
+        let extended = FixturePatch {
+            mode: Some("extended".into()),
+            address: 1,
+        };
+        let error = config
+            .patch_fixture("washLeft", extended.clone())
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ErrorCode>(),
+            Some(&ErrorCode::AddressConflict {
+                conflicts: vec![PatchConflict {
+                    channel: 5,
+                    fixtures: vec!["washLeft".into(), "washRight".into()]
+                }]
+            })
+        );
+
+        // Moving the other fixture first makes room
+        config
+            .patch_fixture(
+                "washRight",
+                FixturePatch {
+                    mode: None,
+                    address: 6,
+                },
+            )
+            .unwrap();
+        config.patch_fixture("washLeft", extended).unwrap();
+        let (fixture, profile) = config.fixture("washLeft").unwrap();
+        assert_eq!(fixture.channels(profile), 1..6);
+        let report = config.patch_report();
+        assert_eq!(report.channels.len(), 8);
+        assert_eq!(
+            (&report.channels[4].fixture, &report.channels[4].parameter),
+            (&"washLeft".to_string(), &Parameter::White)
+        );
+        assert!(report.conflicts.is_empty() && report.invalid.is_empty());
+
+        // Unknown modes and fixtures beyond the universe are rejected
+        let unknown = FixturePatch {
+            mode: Some("basic".into()),
+            address: 1,
+        };
+        assert!(config.patch_fixture("washLeft", unknown).is_err());
+        let beyond = FixturePatch {
+            mode: None,
+            address: 511,
+        };
+        assert!(config.patch_fixture("washRight", beyond).is_err());
+    }
+}
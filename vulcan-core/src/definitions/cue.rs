@@ -0,0 +1,108 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the cues of the cue list, so that a sequence of
+//! scenes can be run one GO at a time (or run itself with follow times).
+//! Several cue lists can play at once, each with its own position.
+
+// Import standard library features
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Define the name of the main cue list
+pub const MAIN_CUE_LIST: &str = "main"; // the list of the `cues` in the configuration
+
+/// A struct to define one cue of the cue list: the scene to recall, and
+/// when to start it
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cue {
+    pub scene: String, // the name of the scene to recall
+    #[serde(default)]
+    pub duration: Option<Duration>, // the duration of the fade (None for the default fade)
+    #[serde(default)]
+    pub wait: Option<Duration>, // the delay before the cue starts, if any
+    #[serde(default)]
+    pub follow: Option<Duration>, // the time after the cue starts to continue to the next cue, if any
+    #[serde(default)]
+    pub actions: Vec<CueAction>, // the messages to send to other systems when the cue starts
+}
+
+/// An enum to define a message to send to another system (such as sound or
+/// video) when a cue starts
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CueAction {
+    /// A variant to send an OSC message over udp
+    Osc {
+        target: String,  // the host and port to send the message to
+        address: String, // the OSC address of the message
+        #[serde(default)]
+        arguments: Vec<OscArgument>, // the arguments of the message
+    },
+
+    /// A variant to post the cue as JSON to an http url
+    Webhook {
+        url: String, // the http url to post the cue to
+    },
+
+    /// A variant to write a message to a raw MIDI device
+    Midi {
+        device: PathBuf,  // the path to the device (such as /dev/snd/midiC1D0)
+        message: Vec<u8>, // the bytes of the message (such as [144, 60, 127] for a note on)
+    },
+}
+
+/// An enum to hold one argument of an OSC message
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OscArgument {
+    /// A variant for a 32 bit integer
+    Int(i32),
+
+    /// A variant for a 32 bit float
+    Float(f32),
+
+    /// A variant for a string
+    String(String),
+}
+
+/// A struct to describe one cue in the status of the cue list
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CueStatus {
+    pub number: usize, // the number of the cue, counting from one
+    pub scene: String, // the name of the scene of the cue
+}
+
+/// A struct to describe the playback position of the cue list, so that a
+/// display can mirror it
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CueListStatus {
+    pub length: usize,               // the number of cues in the list
+    pub current: Option<CueStatus>,  // the most recently started cue, if any
+    pub next: Option<CueStatus>,     // the cue that starts next, if any remain
+    pub progress: f64, // the completed fraction of the fade of the current cue, from 0 to 1
+    pub fade_remaining: Duration, // the time left in the fade of the current cue
+    pub starts_in: Option<Duration>, // the time until the next cue starts by itself, if it is waiting
+}
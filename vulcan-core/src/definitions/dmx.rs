@@ -0,0 +1,484 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements structures to communicate various mdmx parameters.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::sync::Arc;
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A struct to define a single fade of a dmx channel.
+/// A fade consists of a channel number, a desired final,
+/// and a duration of the change between values. All fades
+/// are linear (for now).
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fade {
+    pub channel: u32,               // the dmx channel to fade
+    pub value: u8,                  // the final value at the end of the fade
+    pub duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+}
+
+/// A struct to request a new multiplier for the duration of every fade and
+/// scene recall (for example, 2 to make every fade take twice as long)
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FadeMultiplier {
+    pub multiplier: f64, // the new multiplier of every fade duration
+}
+
+/// A struct to request that the preheat of the warmed channels be turned on
+/// or off
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreheatState {
+    pub is_enabled: bool, // a flag to indicate the warmed channels are kept at their preheat
+}
+
+/// A struct to define a fade of a 16-bit value, such as a position. The
+/// high byte is written to the coarse channel and the low byte to the fine
+/// channel, if there is one.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WideFade {
+    pub channel: u32,               // the coarse dmx channel to fade
+    pub fine_channel: Option<u32>,  // the fine dmx channel to fade, if any
+    pub value: u16,                 // the final value at the end of the fade
+    pub duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+}
+
+// Convert a fade into a fade of a 16-bit value (without a fine channel)
+impl From<Fade> for WideFade {
+    fn from(fade: Fade) -> Self {
+        WideFade {
+            channel: fade.channel,
+            fine_channel: None,
+            value: fade.value as u16 * 257, // so that the high byte is the value
+            duration: fade.duration,
+        }
+    }
+}
+
+/// A struct to define a scene: the values for some or all of the dmx
+/// channels. Channels that are not part of the scene are left unchanged when
+/// the scene is recalled.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scene {
+    pub values: FnvHashMap<u32, u8>, // the final value of each channel in the scene
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palettes: Vec<ScenePalette>, // the palettes of the scene, applied before the values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>, // the folder of the scene (such as House or Stage), if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>, // the tags of the scene, for clients to search
+}
+
+/// A struct to describe a scene to clients, without its values
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneSummary {
+    pub name: String,             // the name of the scene
+    pub category: Option<String>, // the folder of the scene, if any
+    pub tags: Vec<String>,        // the tags of the scene
+}
+
+/// A struct to search the scenes. Every field is optional, and the names
+/// are compared without regard to case.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SceneQuery {
+    pub tag: Option<String>,      // only scenes with this tag
+    pub category: Option<String>, // only scenes in this folder
+}
+
+// Implement key features of the scene query
+impl SceneQuery {
+    /// A method to check if the scene matches every field of the query
+    ///
+    pub fn matches(&self, scene: &Scene) -> bool {
+        let is_tagged = self.tag.as_ref().is_none_or(|tag| {
+            scene
+                .tags
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(tag))
+        });
+        let is_filed = self.category.as_ref().is_none_or(|category| {
+            scene
+                .category
+                .as_ref()
+                .is_some_and(|other| other.eq_ignore_ascii_case(category))
+        });
+        is_tagged && is_filed
+    }
+}
+
+/// A struct to request the recall of a scene by name, fading each of the
+/// channels in the scene over the (optional) duration.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneRecall {
+    pub name: String,               // the name of the scene
+    pub duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+}
+
+/// A struct to define a group of channels with a shared level, like a
+/// submaster on a console. The output of each channel in the group is scaled
+/// by the level of the group.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelGroup {
+    pub channels: Vec<u32>, // the channels in the group
+    #[serde(default = "default_group_level")]
+    pub level: u8, // the level of the group at startup, in percent
+}
+
+/// A struct to set the level of a group, in percent of the full output
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupLevel {
+    pub level: u8, // the level of the group, from 0 to 100 percent
+}
+
+/// A struct to black out a group (or release it)
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupBlackout {
+    pub is_blackout: bool, // a flag to hold the channels of the group at zero
+}
+
+/// An enum to define how a fan spreads across the channels of a group
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FanMode {
+    /// a variant to spread from the first channel of the group to the last
+    #[default]
+    Linear,
+
+    /// a variant to spread from the middle of the group out to both ends
+    CenterOut,
+}
+
+/// A struct to fade every channel of a group, with the values and start
+/// times spread across the channels
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupFan {
+    pub group: String,              // the name of the group
+    pub from: u8,                   // the final value of the first channel (or the middle)
+    pub to: Option<u8>, // the final value of the last channel (or the ends), if different
+    pub duration: Option<Duration>, // the duration of each fade (None if instantaneous)
+    pub spread: Option<Duration>, // the time between the first start and the last (None to start together)
+    #[serde(default)]
+    pub mode: FanMode, // the way the fan spreads across the group
+}
+
+/// An enum to define the behavior of the dmx output when the program closes.
+/// Each behavior is followed by the exit time before the output is released.
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ExitBehavior {
+    /// a variant to keep the last universe for the exit time
+    Hold,
+
+    /// a variant to fade all channels to zero over the exit time
+    Blackout,
+
+    /// a variant to snap to the emergency universe for the exit time
+    Emergency,
+}
+
+// Define the DMX constants
+pub const DMX_MAX: u32 = 512; // the highest channel of DMX, exclusive
+pub const FULL_LEVEL: u8 = 100; // the level of a group that leaves the output unchanged
+
+/// A helper function to default the level of a group to full
+///
+fn default_group_level() -> u8 {
+    FULL_LEVEL
+}
+
+/// A type definition for one set of Dmx channels
+///
+/// NOTE: the chennels are internally zero-indexed,
+/// rather than the one-indexed standard of DMX. Clones share the same
+/// values until one of them is changed, so a copy of the universe is cheap.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Universe {
+    values: Arc<Vec<u8>>, // Internal representation of the channel values
+}
+
+// Implement key features of the group fan
+impl GroupFan {
+    /// A method to spread the fan across the channels of the group,
+    /// returning the delay before each fade starts
+    ///
+    pub fn to_fades(&self, channels: &[u32]) -> Vec<(Duration, Fade)> {
+        let to = self.to.unwrap_or(self.from);
+        let last = channels.len().saturating_sub(1) as f64;
+        channels
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| {
+                // Find how far along the fan the channel is, from zero to one
+                let share = match self.mode {
+                    FanMode::Linear if last > 0.0 => index as f64 / last,
+                    FanMode::CenterOut if last > 0.0 => {
+                        (index as f64 - last / 2.0).abs() / (last / 2.0)
+                    }
+                    _ => 0.0,
+                };
+
+                // Spread the value and the start of the fade
+                let value = self.from as f64 + (to as f64 - self.from as f64) * share;
+                let delay = self.spread.unwrap_or_default().mul_f64(share);
+                (
+                    delay,
+                    Fade {
+                        channel: *channel,
+                        value: value.round() as u8,
+                        duration: self.duration,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+// Implement the default universe, with every channel at zero
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implement key features for the DmxUniverse
+impl Universe {
+    /// Function to create a new, initialized list of the dmx channels
+    ///
+    pub fn new() -> Self {
+        Self {
+            values: Arc::new(vec![0; DMX_MAX as usize]),
+        }
+    }
+
+    /// Method to get the value of a particular channel
+    ///
+    pub fn get(&self, channel: u32) -> u8 {
+        // Check the bounds
+        if !(1..=DMX_MAX).contains(&channel) {
+            return 0; // default to zero
+        }
+
+        // Otherwise, convert to zero-indexed and return the value
+        self.values[channel as usize - 1]
+    }
+
+    /// Method to set the value of a paticular channel
+    ///
+    pub fn set(&mut self, channel: u32, value: u8) {
+        // Check the bounds
+        if (channel <= DMX_MAX) & (channel > 0) {
+            // Convert to zero-indexed and set the value
+            // (copying the values first if they are shared, and changed)
+            if self.values[channel as usize - 1] != value {
+                Arc::make_mut(&mut self.values)[channel as usize - 1] = value;
+            }
+        } // Otherwise, do nothing
+    }
+
+    /// Method to export the universe as a set of bytes
+    ///
+    /// CAUTION: These bytes are zero-indexed!
+    ///
+    pub fn as_bytes(&self) -> Vec<u8> {
+        // Return the array
+        self.values.to_vec()
+    }
+}
+
+/// An enum to describe what is controlling a channel of the output, from
+/// the lowest priority to the highest
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChannelSource {
+    /// the channel holds its value in the base layer (from a finished fade,
+    /// a scene, or a universe)
+    Base,
+
+    /// a fade is in progress on the channel
+    Fade,
+
+    /// an effect, movement, or pixel generator is playing on the channel
+    Effect,
+
+    /// the channel is parked by a manual override (or is being released)
+    Override,
+}
+
+/// A struct to describe how one channel of the output has been written
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStats {
+    pub channel: u32,                  // the channel
+    pub value: u8,                     // the value of the channel in the last frame written
+    pub changes: u64,                  // the number of written frames that changed the channel
+    pub last_change: Option<u64>,      // the time of the last change, in ms since the unix epoch
+    pub source: ChannelSource,         // the layer currently controlling the channel
+    pub writer: Option<ChannelWriter>, // the request that last wrote the channel, if any
+}
+
+/// A struct to select the channels of the statistics
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChannelStatsQuery {
+    pub channel: Option<u32>, // only this channel (otherwise every channel)
+    pub is_changed: bool,     // only the channels that have changed
+}
+
+/// A struct to hold one sample of the channel history
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySample {
+    pub timestamp: u64, // the time of the sample, in ms since the unix epoch
+    pub value: u8,      // the value of the channel from this time until the next sample
+}
+
+/// A struct to select the samples of the channel history
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryQuery {
+    pub range: Option<u64>, // only the samples from this many secs ago (otherwise every sample)
+}
+
+/// A struct to hold the options of a backup restore
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestoreQuery {
+    pub is_forced: bool, // a flag to restore the backup even if it is older than the maximum age
+    pub expected_version: Option<u64>, // the version from the last read of the universe, if any
+}
+
+// Tests of the dmx module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that scenes are matched by tag and folder, without regard to case
+    #[test]
+    fn match_scenes() {
+        let scene: Scene =
+            serde_yaml::from_str("values:\n  1: 255\ncategory: House\ntags: [Holiday, warm]\n")
+                .unwrap();
+        let query = |tag: Option<&str>, category: Option<&str>| SceneQuery {
+            tag: tag.map(String::from),
+            category: category.map(String::from),
+        };
+        assert!(query(None, None).matches(&scene));
+        assert!(query(Some("holiday"), None).matches(&scene));
+        assert!(query(Some("Warm"), Some("house")).matches(&scene));
+        assert!(!query(Some("cool"), None).matches(&scene));
+        assert!(!query(None, Some("Stage")).matches(&scene));
+        assert!(!query(Some("warm"), None).matches(&Scene::default()));
+    }
+
+    // Test that a fan spreads the values and start times across a group
+    #[test]
+    fn spread_fans() {
+        let mut fan: GroupFan = serde_yaml::from_str(
+            "group: front
+from: 0
+to: 200
+spread: {secs: 2, nanos: 0}
+",
+        )
+        .unwrap();
+        let spread = |fan: &GroupFan| -> Vec<(u64, u8)> {
+            fan.to_fades(&[1, 2, 3, 4, 5])
+                .iter()
+                .map(|(delay, fade)| (delay.as_millis() as u64, fade.value))
+                .collect()
+        };
+        assert_eq!(
+            spread(&fan),
+            vec![(0, 0), (500, 50), (1000, 100), (1500, 150), (2000, 200)]
+        );
+
+        // From the center out, the ends start last
+        fan.mode = FanMode::CenterOut;
+        assert_eq!(
+            spread(&fan),
+            vec![(2000, 200), (1000, 100), (0, 0), (1000, 100), (2000, 200)]
+        );
+
+        // A single channel takes the first value right away
+        assert_eq!(fan.to_fades(&[7])[0].0, Duration::ZERO);
+        assert_eq!(fan.to_fades(&[7])[0].1.value, 0);
+    }
+
+    // Test that clones of a universe share values until one is changed
+    #[test]
+    fn share_universe() {
+        let mut universe = Universe::new();
+        universe.set(1, 10);
+        let mut copy = universe.clone();
+        assert!(Arc::ptr_eq(&universe.values, &copy.values));
+
+        // Setting the same value keeps the values shared
+        copy.set(1, 10);
+        assert!(Arc::ptr_eq(&universe.values, &copy.values));
+
+        // A change only affects the changed universe
+        copy.set(1, 20);
+        assert!(!Arc::ptr_eq(&universe.values, &copy.values));
+        assert_eq!((universe.get(1), copy.get(1)), (10, 20));
+        assert_eq!(
+            serde_json::to_string(&universe).unwrap(),
+            serde_json::to_string(&Universe::new())
+                .unwrap()
+                .replacen("[0", "[10", 1)
+        );
+    }
+}
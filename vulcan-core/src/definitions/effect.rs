@@ -0,0 +1,304 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements structures to start and stop effects, which move
+//! channels between two values (or fixtures around a shape) until they are
+//! stopped.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+/// A struct to request an effect on some channels. The effect moves each
+/// channel between the low and high values once per period (or once every
+/// few beats, locked to the beat clock).
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectPlay {
+    pub effect: String,     // the name of the effect
+    pub channels: Vec<u32>, // the channels of the effect
+    #[serde(default)]
+    pub low: u8, // the value at the bottom of the effect
+    #[serde(default = "default_high")]
+    pub high: u8, // the value at the top of the effect
+    #[serde(default)]
+    pub period: Duration, // the time for one cycle of the effect (ignored if locked to the beat)
+    #[serde(default)]
+    pub beats: Option<f64>, // the beats in one cycle of the effect, if locked to the beat clock
+    #[serde(default)]
+    pub seed: u64, // the seed of a random effect, so that its pattern repeats
+    #[serde(default = "default_chance")]
+    pub chance: f64, // the chance of each random event of the effect (such as a sparkle), from zero to one
+    #[serde(default = "default_intensity")]
+    pub intensity: f64, // the share of the range from low to high that the effect uses, from zero to one
+}
+
+/// A struct to tune the effects in progress on some channels, without
+/// restarting them
+///
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EffectTune {
+    pub channels: Vec<u32>, // the channels of the effects to tune (if empty, every effect)
+    pub intensity: Option<f64>, // the new intensity of the effects, if changed
+    pub chance: Option<f64>, // the new chance of random events of the effects, if changed
+}
+
+/// A struct to hold the speed of the effects that are not locked to the
+/// beat: a master for every effect, and a speed for each kind of effect
+/// (the speed of an effect is the product of the two)
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectSpeed {
+    pub master: f64,    // the multiplier of the speed of every effect
+    pub effects: f64,   // the multiplier of the speed of the effects on channels
+    pub movements: f64, // the multiplier of the speed of the movements
+    pub pixels: f64,    // the multiplier of the speed of the pixel generators
+}
+
+/// A struct to request a change to the speed of the effects. Any field
+/// that is left out is unchanged.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EffectSpeedUpdate {
+    pub master: Option<f64>,    // the new master of every effect
+    pub effects: Option<f64>,   // the new speed of the effects on channels
+    pub movements: Option<f64>, // the new speed of the movements
+    pub pixels: Option<f64>,    // the new speed of the pixel generators
+}
+
+/// An enum to save an effect with its parameters as a named preset, so
+/// that clients can start it with one request
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum EffectPreset {
+    /// a variant for an effect on some channels
+    Effect(EffectPlay),
+
+    /// a variant for a movement effect on some fixtures
+    Movement(MovementPlay),
+
+    /// a variant for a pixel generator on a pixel map
+    Pixels(PixelPlay),
+}
+
+// Implement key features of the effect preset
+impl EffectPreset {
+    /// A method to convert the preset into the request to start it
+    ///
+    pub fn to_request(&self) -> Request {
+        match self {
+            EffectPreset::Effect(effect) => Request::PlayEffect {
+                effect: effect.clone(),
+            },
+            EffectPreset::Movement(movement) => Request::PlayMovement {
+                movement: movement.clone(),
+            },
+            EffectPreset::Pixels(play) => Request::PlayPixels { play: play.clone() },
+        }
+    }
+}
+
+/// A struct to request an effect preset by name
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetPlay {
+    pub name: String, // the name of the preset
+}
+
+/// A struct to request that effects stop. The channels hold their current
+/// values.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EffectStop {
+    pub channels: Vec<u32>, // the channels to stop (if empty with no fixtures or pixel maps, every effect stops)
+    pub fixtures: Vec<String>, // the fixtures to stop, by name
+    pub pixel_maps: Vec<String>, // the pixel maps to stop, by name
+}
+
+/// An enum to define the shape of a movement effect
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Shape {
+    /// a variant to move in a circle
+    Circle,
+
+    /// a variant to move in a figure eight, with two tilt swings for each
+    /// pan swing
+    FigureEight,
+
+    /// a variant to sweep the pan back and forth, holding the tilt
+    Sweep,
+}
+
+// Implement key features of the shape
+impl Shape {
+    /// A method to return the offset of the pan and tilt, from minus one to
+    /// one, after some number of cycles
+    ///
+    pub fn offsets(&self, cycles: f64) -> (f64, f64) {
+        let angle = cycles.fract() * TAU;
+        match self {
+            Shape::Circle => (angle.sin(), angle.cos()),
+            Shape::FigureEight => (angle.sin(), (2.0 * angle).sin()),
+            Shape::Sweep => (angle.sin(), 0.0),
+        }
+    }
+}
+
+/// A struct to request a movement effect on a group of fixtures. Each
+/// fixture moves around the center, offset by the phase from the fixture
+/// before it.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovementPlay {
+    pub fixtures: Vec<String>, // the fixtures to move, by name
+    pub shape: Shape,          // the shape of the movement
+    pub pan: f64,              // the pan at the center of the movement, in degrees
+    pub tilt: f64,             // the tilt at the center of the movement, in degrees
+    pub radius: f64,           // the distance from the center to the edge, in degrees
+    #[serde(default)]
+    pub period: Duration, // the time for one cycle of the movement (ignored if locked to the beat)
+    #[serde(default)]
+    pub beats: Option<f64>, // the beats in one cycle of the movement, if locked to the beat clock
+    #[serde(default)]
+    pub phase: f64, // the fraction of a cycle between each fixture
+}
+
+/// A struct to define a movement effect on the channels of some fixtures,
+/// as played by the effect engine
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct Movement {
+    pub shape: Shape,           // the shape of the movement
+    pub radius: f64,            // the distance from the center to the edge, in degrees
+    pub period: Duration,       // the time for one cycle of the movement
+    pub beats: Option<f64>, // the beats in one cycle of the movement, if locked to the beat clock
+    pub heads: Vec<MovingHead>, // the channels of each fixture
+}
+
+// Implement key features of the movement
+impl Movement {
+    /// A method to list every channel of the movement
+    ///
+    pub fn channels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.heads.iter().flat_map(MovingHead::channels)
+    }
+}
+
+/// A struct to define the channels of one fixture in a movement
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct MovingHead {
+    pub pan: Option<Axis>,  // the pan of the fixture, if any
+    pub tilt: Option<Axis>, // the tilt of the fixture, if any
+    pub phase: f64,         // the fraction of a cycle ahead of the movement
+}
+
+// Implement key features of the moving head
+impl MovingHead {
+    /// A method to list the channels of the fixture
+    ///
+    pub fn channels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pan
+            .iter()
+            .chain(self.tilt.iter())
+            .flat_map(Axis::channels)
+    }
+}
+
+/// A struct to define one axis of a fixture in a movement
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct Axis {
+    pub channel: u32,              // the coarse channel of the axis
+    pub fine_channel: Option<u32>, // the fine channel of the axis, if any
+    pub range: u32,                // the full range of the axis, in degrees
+    pub center: f64,               // the center of the movement on this axis, in degrees
+}
+
+// Implement key features of the axis
+impl Axis {
+    /// A method to convert a position on the axis, in degrees, to the
+    /// 16-bit value (limited to the range of the axis)
+    ///
+    pub fn to_value(&self, degrees: f64) -> u16 {
+        let range = self.range.max(1) as f64;
+        (degrees.clamp(0.0, range) / range * u16::MAX as f64).round() as u16
+    }
+
+    /// A method to list the channels of the axis
+    ///
+    pub fn channels(&self) -> impl Iterator<Item = u32> {
+        Some(self.channel).into_iter().chain(self.fine_channel)
+    }
+}
+
+// Implement the default speed of the effects, at their own periods
+impl Default for EffectSpeed {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            effects: 1.0,
+            movements: 1.0,
+            pixels: 1.0,
+        }
+    }
+}
+
+// Implement key features of the effect speed
+impl EffectSpeed {
+    /// A method to apply a change to the speed, leaving any speed that is
+    /// left out unchanged
+    ///
+    pub fn update(&mut self, update: EffectSpeedUpdate) {
+        self.master = update.master.unwrap_or(self.master);
+        self.effects = update.effects.unwrap_or(self.effects);
+        self.movements = update.movements.unwrap_or(self.movements);
+        self.pixels = update.pixels.unwrap_or(self.pixels);
+    }
+}
+
+/// A helper function to default the top of an effect to full
+///
+fn default_high() -> u8 {
+    u8::MAX
+}
+
+/// A helper function to default the chance of random events of an effect
+///
+fn default_chance() -> f64 {
+    DEFAULT_EFFECT_CHANCE
+}
+
+/// A helper function to default the intensity of an effect to the full
+/// range
+///
+fn default_intensity() -> f64 {
+    1.0
+}
@@ -0,0 +1,332 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the error codes returned to clients, so that
+//! clients can handle each failure without parsing the message.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::fmt;
+
+/// An enum to describe each kind of failure, with any relevant details. The
+/// codes are stable, even if the messages change.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "code",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ErrorCode {
+    /// A variant indicating that a channel is outside the universe
+    ChannelOutOfRange {
+        channel: u32, // the channel that was requested
+    },
+
+    /// A variant indicating that a scene does not exist
+    SceneNotFound {
+        scene: String, // the name of the scene
+    },
+
+    /// A variant indicating that a trigger does not exist
+    TriggerNotFound {
+        trigger: String, // the name of the trigger
+    },
+
+    /// A variant indicating that an effect does not exist
+    EffectNotFound {
+        effect: String, // the name of the effect
+    },
+
+    /// A variant indicating that a fixture does not exist
+    FixtureNotFound {
+        fixture: String, // the name of the fixture
+    },
+
+    /// A variant indicating that a pixel map does not exist
+    PixelMapNotFound {
+        map: String, // the name of the pixel map
+    },
+
+    /// A variant indicating that a group does not exist
+    GroupNotFound {
+        group: String, // the name of the group
+    },
+
+    /// A variant indicating that an effect preset does not exist
+    PresetNotFound {
+        preset: String, // the name of the preset
+    },
+
+    /// A variant indicating that a palette does not exist
+    PaletteNotFound {
+        palette: String, // the name of the palette
+    },
+
+    /// A variant indicating that a script does not exist
+    ScriptNotFound {
+        script: String, // the name of the script
+    },
+
+    /// A variant indicating that a request cannot be simulated in a dry run
+    DryRunUnsupported {
+        request: String, // the name of the request
+    },
+
+    /// A variant indicating that the DMX hardware cannot be reached
+    DeviceUnavailable,
+
+    /// A variant indicating that there is no previous look to undo
+    NothingToUndo,
+
+    /// A variant indicating that there is no undone look to redo
+    NothingToRedo,
+
+    /// A variant indicating that every cue of the cue list has been run
+    EndOfCueList,
+
+    /// A variant indicating that a timeline does not exist
+    TimelineNotFound {
+        timeline: String, // the name of the timeline
+    },
+
+    /// A variant indicating that a cue list does not exist
+    CueListNotFound {
+        list: String, // the name of the cue list
+    },
+
+    /// A variant indicating that the backup server is not connected (or the
+    /// backup could not be written)
+    BackupUnavailable,
+
+    /// A variant indicating that the backup server holds no backup
+    BackupNotFound,
+
+    /// A variant indicating that the backup is older than the maximum age
+    BackupExpired {
+        age: Option<u64>, // the age of the backup in secs, if known
+    },
+
+    /// A variant indicating that the channel history is not enabled
+    HistoryDisabled,
+
+    /// A variant indicating that the emergency stop is engaged, so the look
+    /// cannot be changed
+    EmergencyStop,
+
+    /// A variant indicating that a fixture would overlap the channels of
+    /// other fixtures
+    AddressConflict {
+        conflicts: Vec<PatchConflict>, // the channels that would be used more than once
+    },
+
+    /// A variant indicating that the look has changed since the client read it
+    VersionConflict {
+        expected: u64, // the version the client expected
+        current: u64,  // the current version of the look
+    },
+
+    /// A variant indicating that a log level is not valid
+    InvalidLogLevel {
+        level: String, // the log level that was requested
+    },
+
+    /// A variant indicating that the configuration could not be loaded
+    InvalidConfig {
+        reason: String, // a description of the problem with the configuration
+    },
+
+    /// A variant indicating that some fields of the request are not valid
+    InvalidRequest {
+        fields: Vec<FieldError>, // the problem with each field
+    },
+
+    /// A variant indicating that the program is in read-only mode, so the
+    /// request cannot change anything
+    ReadOnly,
+
+    /// A variant indicating that the request must be repeated with the
+    /// token to run
+    ConfirmationRequired {
+        request: String,       // the name of the request
+        confirm_token: String, // the token to confirm the request
+        expires_in: u64,       // the time to repeat the request, in secs
+    },
+
+    /// A variant indicating that the request is missing a valid token
+    Unauthorized,
+
+    /// A variant indicating that the token does not allow the request
+    NotPermitted {
+        required_role: Role, // the role required for the request
+    },
+
+    /// A variant indicating that the program is not ready to control the lights
+    NotReady,
+
+    /// A variant indicating that the client has made too many requests
+    RateLimited {
+        retry_after: u64, // the time until the client may make another request, in secs
+    },
+
+    /// A variant indicating that the web interface is handling too many requests
+    Busy,
+
+    /// A variant indicating any other failure
+    Internal {
+        reason: String, // a description of the failure
+    },
+}
+
+// Implement key features of the error code
+impl ErrorCode {
+    /// A function to find the error code of an error, treating any error
+    /// without a code as internal
+    ///
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        error
+            .downcast_ref::<ErrorCode>()
+            .cloned()
+            .unwrap_or_else(|| ErrorCode::Internal {
+                reason: error.to_string(),
+            })
+    }
+}
+
+// Implement the message of each error code
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCode::ChannelOutOfRange { channel } => {
+                write!(f, "Channel {} is out of range (1-{}).", channel, DMX_MAX)
+            }
+            ErrorCode::SceneNotFound { scene } => write!(f, "Scene not found: {}.", scene),
+            ErrorCode::TriggerNotFound { trigger } => write!(f, "Trigger not found: {}.", trigger),
+            ErrorCode::EffectNotFound { effect } => write!(f, "Effect not found: {}.", effect),
+            ErrorCode::ScriptNotFound { script } => write!(f, "Script not found: {}.", script),
+            ErrorCode::FixtureNotFound { fixture } => write!(f, "Fixture not found: {}.", fixture),
+            ErrorCode::GroupNotFound { group } => write!(f, "Group not found: {}.", group),
+            ErrorCode::PresetNotFound { preset } => write!(f, "Preset not found: {}.", preset),
+            ErrorCode::PaletteNotFound { palette } => {
+                write!(f, "Palette not found: {}.", palette)
+            }
+            ErrorCode::PixelMapNotFound { map } => write!(f, "Pixel map not found: {}.", map),
+            ErrorCode::DryRunUnsupported { request } => {
+                write!(f, "Unable to simulate {} in a dry run.", request)
+            }
+            ErrorCode::DeviceUnavailable => write!(f, "The DMX hardware is unavailable."),
+            ErrorCode::NothingToUndo => write!(f, "Nothing to undo."),
+            ErrorCode::NothingToRedo => write!(f, "Nothing to redo."),
+            ErrorCode::EndOfCueList => write!(f, "No cues remain in the cue list."),
+            ErrorCode::CueListNotFound { list } => write!(f, "Cue list not found: {}.", list),
+            ErrorCode::TimelineNotFound { timeline } => {
+                write!(f, "Timeline not found: {}.", timeline)
+            }
+            ErrorCode::BackupUnavailable => write!(f, "The backup server is unavailable."),
+            ErrorCode::BackupNotFound => write!(f, "No backup was found on the backup server."),
+            ErrorCode::BackupExpired { age: Some(age) } => write!(
+                f,
+                "The backup is too old to restore ({} secs). Force the restore to use it anyway.",
+                age
+            ),
+            ErrorCode::BackupExpired { age: None } => write!(
+                f,
+                "The backup is of unknown age. Force the restore to use it anyway."
+            ),
+            ErrorCode::HistoryDisabled => write!(f, "The channel history is not enabled."),
+            ErrorCode::EmergencyStop => write!(
+                f,
+                "The emergency stop is engaged. Release it to change the look."
+            ),
+            ErrorCode::AddressConflict { conflicts } => write!(
+                f,
+                "The fixture would overlap other fixtures on channels {}.",
+                conflicts
+                    .iter()
+                    .map(|conflict| conflict.channel.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            ErrorCode::VersionConflict { expected, current } => write!(
+                f,
+                "The look has changed (version {}, expected {}).",
+                current, expected
+            ),
+            ErrorCode::InvalidLogLevel { level } => write!(
+                f,
+                "Invalid log level: {}. Options are Trace, Debug, Info, Warn, and Error.",
+                level
+            ),
+            ErrorCode::InvalidConfig { reason } => write!(f, "{}", reason),
+            ErrorCode::InvalidRequest { fields } => write!(
+                f,
+                "Invalid fields in the request: {}.",
+                fields
+                    .iter()
+                    .map(|error| error.field.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ),
+            ErrorCode::ReadOnly => write!(f, "Vulcan is in read-only mode."),
+            ErrorCode::ConfirmationRequired {
+                request,
+                confirm_token,
+                expires_in,
+            } => write!(
+                f,
+                "Repeat the {} request with confirmToken={} within {} seconds to confirm.",
+                request, confirm_token, expires_in
+            ),
+            ErrorCode::Unauthorized => write!(f, "Missing or invalid token."),
+            ErrorCode::NotPermitted { .. } => write!(f, "Not permitted for this token."),
+            ErrorCode::NotReady => write!(f, "Vulcan is not ready."),
+            ErrorCode::RateLimited { retry_after } => {
+                write!(f, "Too many requests. Try again in {} secs.", retry_after)
+            }
+            ErrorCode::Busy => write!(f, "Too many requests in progress. Try again later."),
+            ErrorCode::Internal { reason } => write!(f, "{}", reason),
+        }
+    }
+}
+
+// Allow the error code to be carried as an error
+impl std::error::Error for ErrorCode {}
+
+// Tests of the error module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the code and details appear alongside the message
+    #[test]
+    fn serialize_error_reply() {
+        let reply = WebReply::error(ErrorCode::SceneNotFound {
+            scene: "warm".to_string(),
+        });
+        assert_eq!(
+            serde_json::to_value(&reply).unwrap(),
+            serde_json::json!({"error": {
+                "isValid": false,
+                "message": "Scene not found: warm.",
+                "code": "sceneNotFound",
+                "scene": "warm",
+            }})
+        );
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the events that Vulcan announces to any interested
+//! part of the program (such as the webhooks), the line to send them, and
+//! the structures to search the event log.
+
+// Import standard library features
+use std::time::Duration;
+
+// Import Tokio features
+use tokio::sync::broadcast;
+
+/// An enum to describe the events of the program
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event {
+    /// A variant indicating that timed fades have completed
+    FadeComplete {
+        channels: Vec<u32>, // the channels whose fades completed
+    },
+
+    /// A variant indicating that a fade with an identifier reached its value
+    FadeFinished {
+        id: u64,      // the identifier of the fade
+        channel: u32, // the channel of the fade
+    },
+
+    /// A variant indicating that a fade with an identifier was replaced
+    /// before it finished (by another fade, an effect, or a new look)
+    FadeCancelled {
+        id: u64,      // the identifier of the fade
+        channel: u32, // the channel of the fade
+    },
+
+    /// A variant indicating that a scene was recalled
+    SceneRecalled {
+        name: String, // the name of the scene
+    },
+
+    /// A variant indicating that a cue of the cue list started
+    CueStarted {
+        list: String,  // the name of the cue list
+        number: usize, // the number of the cue, counting from one
+        scene: String, // the name of the scene of the cue
+    },
+
+    /// A variant indicating that a cue of the cue list is waiting to start
+    /// by itself
+    #[serde(rename_all = "camelCase")]
+    CueWaiting {
+        list: String,        // the name of the cue list
+        number: usize,       // the number of the cue, counting from one
+        starts_in: Duration, // the time until the cue starts
+    },
+
+    /// A variant indicating that a cue list returned to the first cue
+    CueListReset {
+        list: String, // the name of the cue list
+    },
+
+    /// A variant indicating that the all stop was triggered
+    AllStop,
+
+    /// A variant indicating that the hardwired emergency stop was engaged or
+    /// released
+    #[serde(rename_all = "camelCase")]
+    EmergencyStop {
+        is_engaged: bool, // a flag to indicate the emergency stop is engaged
+    },
+
+    /// A variant indicating that the DMX hardware stopped accepting frames
+    DeviceUnavailable {
+        reason: String, // the reason the most recent frame could not be written
+    },
+
+    /// A variant indicating that the DMX hardware is accepting frames again
+    DeviceAvailable,
+
+    /// A variant indicating that the idle watchdog engaged the safe look
+    IdleEngaged {
+        timeout: u64, // the time without control traffic, in secs
+    },
+
+    /// A variant indicating that the wall clock stepped (rather than
+    /// drifting), such as after a manual change or a large NTP correction
+    ClockStepped {
+        offset: i64, // the size of the step (behind is negative), in ms
+    },
+
+    /// A variant indicating that the NTP sync status of the clock changed
+    #[serde(rename_all = "camelCase")]
+    ClockSync {
+        is_synchronized: bool, // a flag to indicate NTP has synchronized the clock
+    },
+
+    /// A variant indicating that a change could not be saved to the backup server
+    BackupFailed {
+        message: String, // a message describing the failure
+    },
+}
+
+// Implement key features of the event
+impl Event {
+    /// A method to return the name of the event type, which matches the
+    /// name in the serialized event
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::FadeComplete { .. } => "fadeComplete",
+            Event::FadeFinished { .. } => "fadeFinished",
+            Event::FadeCancelled { .. } => "fadeCancelled",
+            Event::SceneRecalled { .. } => "sceneRecalled",
+            Event::CueStarted { .. } => "cueStarted",
+            Event::CueWaiting { .. } => "cueWaiting",
+            Event::CueListReset { .. } => "cueListReset",
+            Event::AllStop => "allStop",
+            Event::EmergencyStop { .. } => "emergencyStop",
+            Event::DeviceUnavailable { .. } => "deviceUnavailable",
+            Event::DeviceAvailable => "deviceAvailable",
+            Event::IdleEngaged { .. } => "idleEngaged",
+            Event::ClockStepped { .. } => "clockStepped",
+            Event::ClockSync { .. } => "clockSync",
+            Event::BackupFailed { .. } => "backupFailed",
+        }
+    }
+}
+
+/// A struct to hold one entry of the event log
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventEntry {
+    pub sequence: u64,  // the number of the entry, which increases with each event
+    pub timestamp: u64, // the time of the event, in ms since the unix epoch
+    #[serde(flatten)]
+    pub event: Event, // the event and its details
+}
+
+/// A struct to search the event log. Every field is optional, and the
+/// newest matching entries are returned.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EventQuery {
+    pub since: Option<u64>, // only entries after the entry with this sequence number
+    pub event: Option<String>, // only entries of this event type
+    pub limit: Option<usize>, // the maximum number of entries to return
+}
+
+/// The structure and methods to announce events to every listener
+///
+#[derive(Clone, Debug)]
+pub struct EventSend {
+    event_send: broadcast::Sender<Event>, // the broadcast line to pass events
+}
+
+// Implement the default event send
+impl Default for EventSend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement the key features of the event send struct
+impl EventSend {
+    /// A function to create a new EventSend
+    ///
+    pub fn new() -> Self {
+        // Create the new channel (slow listeners miss the oldest events)
+        let (event_send, _) = broadcast::channel(256);
+        EventSend { event_send }
+    }
+
+    /// A method to announce an event. This method fails silently if there
+    /// are no listeners.
+    ///
+    pub fn send(&self, event: Event) {
+        self.event_send.send(event).unwrap_or(0);
+    }
+
+    /// A method to create a new listener for events
+    ///
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.event_send.subscribe()
+    }
+}
@@ -0,0 +1,530 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements structures to describe fixtures: the profile of
+//! each type of fixture and where each fixture is patched, so that fixtures
+//! can be controlled in meaningful units instead of raw channels.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::ops::Range;
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// An enum to define what one channel of a fixture controls
+///
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Parameter {
+    /// the high byte of the pan position
+    Pan,
+
+    /// the low byte of the pan position
+    PanFine,
+
+    /// the high byte of the tilt position
+    Tilt,
+
+    /// the low byte of the tilt position
+    TiltFine,
+
+    /// the overall brightness of the fixture
+    Intensity,
+
+    /// the red emitter
+    Red,
+
+    /// the green emitter
+    Green,
+
+    /// the blue emitter
+    Blue,
+
+    /// the white emitter
+    White,
+
+    /// any other channel, by name
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// A struct to define a type of fixture: the parameter of each channel, in
+/// order, any other modes of the fixture, the range of movement, and the
+/// conversion of colors into its emitters
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureProfile {
+    pub channels: Vec<Parameter>, // the parameter of each channel of the default mode, starting at the address
+    #[serde(default, skip_serializing_if = "FnvHashMap::is_empty")]
+    pub modes: FnvHashMap<String, Vec<Parameter>>, // the parameter of each channel of every other mode, by name
+    #[serde(default = "default_pan_range")]
+    pub pan_range: u32, // the full range of the pan, in degrees
+    #[serde(default = "default_tilt_range")]
+    pub tilt_range: u32, // the full range of the tilt, in degrees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_matrix: Option<ColorMatrix>, // the conversion from CIE XYZ into the emitters, if measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<ColorCalibration>, // the measured colors of the emitters, if any
+}
+
+/// A struct to define one fixture, patched at an address
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fixture {
+    pub profile: String, // the name of the profile of the fixture
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>, // the mode of the fixture, if not the default mode
+    pub address: u32,    // the first channel of the fixture
+}
+
+/// A struct to request a new mode and address for a fixture
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixturePatch {
+    #[serde(default)]
+    pub mode: Option<String>, // the new mode of the fixture (None for the default mode)
+    pub address: u32, // the new first channel of the fixture
+}
+
+/// A struct to describe one patched channel: the fixture that uses it and
+/// the parameter it controls
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchChannel {
+    pub channel: u32,         // the channel of the universe
+    pub fixture: String,      // the name of the fixture
+    pub parameter: Parameter, // the parameter the channel controls
+}
+
+/// A struct to describe a channel that more than one fixture uses
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchConflict {
+    pub channel: u32,          // the channel of the universe
+    pub fixtures: Vec<String>, // the names of the fixtures that use the channel, in order
+}
+
+/// A struct to describe the complete patch, for clients to show a patch
+/// sheet
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchReport {
+    pub channels: Vec<PatchChannel>, // every patched channel, in order (a channel in conflict appears once for each fixture)
+    pub conflicts: Vec<PatchConflict>, // the channels that more than one fixture uses, in order
+    pub invalid: Vec<String>, // the fixtures with a missing profile or mode, or that end beyond the universe
+}
+
+/// A struct to request a movement of a fixture to a position, in degrees
+/// from the start of each range, over the (optional) duration
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Position {
+    pub fixture: String,            // the name of the fixture
+    pub pan: Option<f64>,           // the pan position, in degrees (None to leave unchanged)
+    pub tilt: Option<f64>,          // the tilt position, in degrees (None to leave unchanged)
+    pub duration: Option<Duration>, // the duration of the movement (None if instantaneous)
+}
+
+// Implement key features of the fixture profile
+impl FixtureProfile {
+    /// A method to find the position of a parameter within the default mode
+    /// of the fixture, counting from zero
+    ///
+    pub fn offset(&self, parameter: &Parameter) -> Option<u32> {
+        self.channels
+            .iter()
+            .position(|other| other == parameter)
+            .map(|offset| offset as u32)
+    }
+
+    /// A method to find the conversion from CIE XYZ into the emitters: the
+    /// color matrix of the profile, the matrix of its calibration, or the
+    /// matrix of sRGB
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the calibration cannot be
+    /// converted into a matrix.
+    ///
+    pub fn color_conversion(&self) -> Result<ColorMatrix> {
+        match (&self.color_matrix, &self.calibration) {
+            (Some(matrix), _) => Ok(*matrix),
+            (None, Some(calibration)) => calibration.to_matrix(),
+            (None, None) => Ok(SRGB_MATRIX),
+        }
+    }
+
+    /// A method to return the parameter of each channel of a mode (None for
+    /// the default mode), if the profile has the mode
+    ///
+    pub fn parameters(&self, mode: Option<&str>) -> Option<&[Parameter]> {
+        match mode {
+            Some(mode) => self.modes.get(mode).map(Vec::as_slice),
+            None => Some(&self.channels),
+        }
+    }
+}
+
+// Implement key features of the fixture
+impl Fixture {
+    /// A method to find the channel of a parameter of the fixture in its
+    /// mode, if the fixture has the parameter
+    ///
+    pub fn channel(&self, profile: &FixtureProfile, parameter: &Parameter) -> Option<u32> {
+        self.parameters(profile)
+            .iter()
+            .position(|other| other == parameter)
+            .map(|offset| self.address + offset as u32)
+    }
+
+    /// A method to list every channel of the fixture in its mode
+    ///
+    pub fn channels(&self, profile: &FixtureProfile) -> Range<u32> {
+        self.address..(self.address + self.parameters(profile).len() as u32)
+    }
+
+    /// A method to list the channels of the color emitters of the fixture
+    /// in its mode (red, green, blue, and white)
+    ///
+    pub fn color_channels(&self, profile: &FixtureProfile) -> Vec<u32> {
+        self.parameters(profile)
+            .iter()
+            .enumerate()
+            .filter(|(_, parameter)| {
+                matches!(
+                    parameter,
+                    Parameter::Red | Parameter::Green | Parameter::Blue | Parameter::White
+                )
+            })
+            .map(|(offset, _)| self.address + offset as u32)
+            .collect()
+    }
+
+    /// A helper method to find the parameters of the mode of the fixture (a
+    /// missing mode has no parameters)
+    ///
+    fn parameters<'a>(&self, profile: &'a FixtureProfile) -> &'a [Parameter] {
+        profile.parameters(self.mode.as_deref()).unwrap_or_default()
+    }
+
+    /// A helper method to find an axis of the fixture for a movement, if
+    /// the fixture has the axis
+    ///
+    fn axis(
+        &self,
+        profile: &FixtureProfile,
+        coarse: Parameter,
+        fine: Parameter,
+        range: u32,
+        center: f64,
+    ) -> Option<Axis> {
+        Some(Axis {
+            channel: self.channel(profile, &coarse)?,
+            fine_channel: self.channel(profile, &fine),
+            range,
+            center,
+        })
+    }
+}
+
+// Implement key features of the patch report
+impl PatchReport {
+    /// A function to map every channel of the fixtures, and find the
+    /// channels that more than one fixture uses
+    ///
+    pub fn new(
+        fixtures: &FnvHashMap<String, Fixture>,
+        profiles: &FnvHashMap<String, FixtureProfile>,
+    ) -> Self {
+        // Map the channels of each fixture, in order of the names
+        let mut report = PatchReport::default();
+        let mut names: Vec<&String> = fixtures.keys().collect();
+        names.sort();
+        for name in names {
+            let fixture = &fixtures[name];
+            let Some(parameters) = profiles
+                .get(&fixture.profile)
+                .and_then(|profile| profile.parameters(fixture.mode.as_deref()))
+            else {
+                report.invalid.push(name.clone());
+                continue;
+            };
+            if fixture.address + parameters.len() as u32 > DMX_MAX + 1 {
+                report.invalid.push(name.clone());
+            }
+            report.channels.extend(
+                parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, parameter)| PatchChannel {
+                        channel: fixture.address + offset as u32,
+                        fixture: name.clone(),
+                        parameter: parameter.clone(),
+                    })
+                    .filter(|patched| patched.channel <= DMX_MAX),
+            );
+        }
+        report.channels.sort_by_key(|patched| patched.channel);
+
+        // Collect the fixtures of any channel that is used more than once
+        for patched in report
+            .channels
+            .chunk_by(|first, second| first.channel == second.channel)
+        {
+            if patched.len() > 1 {
+                report.conflicts.push(PatchConflict {
+                    channel: patched[0].channel,
+                    fixtures: patched
+                        .iter()
+                        .map(|patched| patched.fixture.clone())
+                        .collect(),
+                });
+            }
+        }
+        report
+    }
+}
+
+// Implement key features of the movement request
+impl MovementPlay {
+    /// A method to find the axes of every fixture of the movement, offsetting
+    /// the phase of each fixture from the one before it
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a fixture does not exist or cannot
+    /// move.
+    ///
+    pub fn to_movement(&self, config: &Config) -> Result<Movement> {
+        let mut heads = Vec::new();
+        for (index, name) in self.fixtures.iter().enumerate() {
+            // Find the pan and tilt of the fixture
+            let (fixture, profile) = config.fixture(name)?;
+            let pan = fixture.axis(
+                profile,
+                Parameter::Pan,
+                Parameter::PanFine,
+                profile.pan_range,
+                self.pan,
+            );
+            let tilt = fixture.axis(
+                profile,
+                Parameter::Tilt,
+                Parameter::TiltFine,
+                profile.tilt_range,
+                self.tilt,
+            );
+            if pan.is_none() && tilt.is_none() {
+                return Err(ErrorCode::InvalidRequest {
+                    fields: vec![FieldError::new(
+                        &format!("fixtures[{}]", index),
+                        "The fixture cannot move.",
+                    )],
+                }
+                .into());
+            }
+
+            // Offset the phase of the fixture
+            heads.push(MovingHead {
+                pan,
+                tilt,
+                phase: self.phase * index as f64,
+            });
+        }
+
+        // Return the completed movement
+        Ok(Movement {
+            shape: self.shape,
+            radius: self.radius,
+            period: self.period,
+            beats: self.beats,
+            heads,
+        })
+    }
+}
+
+// Implement key features of the position
+impl Position {
+    /// A method to convert the position into a fade for each axis. Each
+    /// position is scaled to the full 16-bit range of the axis.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the fixture does not have an axis
+    /// of the position, or the position is beyond the range of the axis.
+    ///
+    pub fn to_fades(&self, fixture: &Fixture, profile: &FixtureProfile) -> Result<Vec<WideFade>> {
+        // Convert each axis that was provided
+        let mut fades = Vec::new();
+        let mut errors = Vec::new();
+        let axes = [
+            (
+                "pan",
+                self.pan,
+                Parameter::Pan,
+                Parameter::PanFine,
+                profile.pan_range,
+            ),
+            (
+                "tilt",
+                self.tilt,
+                Parameter::Tilt,
+                Parameter::TiltFine,
+                profile.tilt_range,
+            ),
+        ];
+        for (field, degrees, coarse, fine, range) in axes {
+            // Skip any axis that was not provided
+            let Some(degrees) = degrees else {
+                continue;
+            };
+
+            // Find the channels and check the range
+            let Some(channel) = fixture.channel(profile, &coarse) else {
+                errors.push(FieldError::new(
+                    field,
+                    "The fixture cannot move on this axis.",
+                ));
+                continue;
+            };
+            if !(0.0..=range as f64).contains(&degrees) {
+                errors.push(FieldError::new(
+                    field,
+                    format!("Must be between 0 and {} degrees.", range),
+                ));
+                continue;
+            }
+
+            // Scale the position to the full range of the channels
+            fades.push(WideFade {
+                channel,
+                fine_channel: fixture.channel(profile, &fine),
+                value: (degrees / range.max(1) as f64 * u16::MAX as f64).round() as u16,
+                duration: self.duration,
+            });
+        }
+
+        // Report every problem with the position
+        if !errors.is_empty() {
+            return Err(ErrorCode::InvalidRequest { fields: errors }.into());
+        }
+        Ok(fades)
+    }
+}
+
+/// A helper function to default the pan range of a profile
+///
+fn default_pan_range() -> u32 {
+    540
+}
+
+/// A helper function to default the tilt range of a profile
+///
+fn default_tilt_range() -> u32 {
+    270
+}
+
+// Tests of the fixture module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a position is converted into a fade for each axis
+    #[test]
+    fn position_to_fades() {
+        // Read a profile with a fine pan (but not a fine tilt)
+        let profile: FixtureProfile =
+            serde_yaml::from_str("channels: [intensity, pan, panFine, tilt, gobo]\n").unwrap();
+        assert_eq!(profile.offset(&Parameter::Other("gobo".into())), Some(4));
+        assert_eq!(profile.pan_range, 540);
+        let fixture = Fixture {
+            profile: "mover".into(),
+            mode: None,
+            address: 10,
+        };
+
+        // Half of each range is the middle of the channels
+        let mut position = Position {
+            fixture: "spot".into(),
+            pan: Some(270.0),
+            tilt: Some(135.0),
+            duration: None,
+        };
+        let fades = position.to_fades(&fixture, &profile).unwrap();
+        assert_eq!(fades.len(), 2);
+        assert_eq!((fades[0].channel, fades[0].fine_channel), (11, Some(12)));
+        assert_eq!((fades[1].channel, fades[1].fine_channel), (13, None));
+        assert_eq!(fades[0].value, 32768);
+
+        // A position beyond the range is rejected
+        position.tilt = Some(300.0);
+        assert!(position.to_fades(&fixture, &profile).is_err());
+    }
+
+    // Test that the channels of a fixture follow its mode
+    #[test]
+    fn fixture_modes() {
+        // Read a profile with an extended mode
+        let profile: FixtureProfile = serde_yaml::from_str(
+            "channels: [intensity, pan, tilt]\nmodes:\n  extended: [intensity, pan, panFine, tilt, tiltFine]\n",
+        )
+        .unwrap();
+        let mut fixture = Fixture {
+            profile: "mover".into(),
+            mode: None,
+            address: 10,
+        };
+        assert_eq!(fixture.channels(&profile), 10..13);
+        assert_eq!(fixture.channel(&profile, &Parameter::Tilt), Some(12));
+
+        // Switching the mode moves the parameters
+        fixture.mode = Some("extended".into());
+        assert_eq!(fixture.channels(&profile), 10..15);
+        assert_eq!(fixture.channel(&profile, &Parameter::Tilt), Some(13));
+        assert_eq!(fixture.channel(&profile, &Parameter::TiltFine), Some(14));
+    }
+
+    // Test that only the color emitters count as color channels
+    #[test]
+    fn color_channels() {
+        let profile: FixtureProfile = serde_yaml::from_str(
+            "channels: [red, green, strobe, blue, white]
+",
+        )
+        .unwrap();
+        let fixture = Fixture {
+            profile: "wash".into(),
+            mode: None,
+            address: 20,
+        };
+        assert_eq!(fixture.color_channels(&profile), vec![20, 21, 23, 24]);
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the labels of channels and fixtures, so that
+//! clients can show meaningful names instead of channel numbers.
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A struct to describe a channel or a fixture
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Label {
+    pub name: String,          // the name to show (if empty, the label is removed)
+    pub notes: Option<String>, // any other details, if any
+}
+
+/// A struct to hold the labels of the channels and fixtures
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Labels {
+    pub channels: FnvHashMap<u32, Label>, // the label of each channel, by channel
+    pub fixtures: FnvHashMap<String, Label>, // the label of each fixture, by fixture name
+}
+
+// Implement key features of the labels
+impl Labels {
+    /// A method to indicate that there are no labels
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty() && self.fixtures.is_empty()
+    }
+
+    /// A method to add or replace the labels, removing any label without a
+    /// name
+    ///
+    pub fn merge(&mut self, labels: Labels) {
+        for (channel, label) in labels.channels {
+            if label.name.is_empty() {
+                self.channels.remove(&channel);
+            } else {
+                self.channels.insert(channel, label);
+            }
+        }
+        for (fixture, label) in labels.fixtures {
+            if label.name.is_empty() {
+                self.fixtures.remove(&fixture);
+            } else {
+                self.fixtures.insert(fixture, label);
+            }
+        }
+    }
+}
+
+// Tests of the label module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that labels are added, replaced, and removed
+    #[test]
+    fn merge_labels() {
+        let mut labels: Labels = serde_yaml::from_str(
+            "channels:\n  1:\n    name: House Left Warm\n  2:\n    name: House Right Warm\n",
+        )
+        .unwrap();
+        let changes: Labels = serde_json::from_str(
+            r#"{"channels": {"1": {"name": ""}, "3": {"name": "Cyc", "notes": "Blue only"}}, "fixtures": {"spot1": {"name": "Stage Left Spot"}}}"#,
+        )
+        .unwrap();
+        labels.merge(changes);
+        let mut channels: Vec<u32> = labels.channels.keys().copied().collect();
+        channels.sort_unstable();
+        assert_eq!(channels, vec![2, 3]);
+        assert_eq!(labels.channels[&3].notes.as_deref(), Some("Blue only"));
+        assert_eq!(labels.fixtures["spot1"].name, "Stage Left Spot");
+    }
+}
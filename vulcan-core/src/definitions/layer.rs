@@ -0,0 +1,138 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements structures to describe the layers of the output.
+//! Each kind of playback has its own layer, and the layers are stacked by
+//! priority to compose each frame.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// An enum to define the layers of the output, from the lowest priority to
+/// the highest
+///
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Layer {
+    /// the layer of fades, scenes, universes, and scripts
+    Base,
+
+    /// the layer of effects and movements
+    Effects,
+
+    /// the layer of manual overrides, above every playback
+    Override,
+}
+
+// Implement key features of the layer
+impl Layer {
+    /// Every layer, from the lowest priority to the highest
+    pub const ALL: [Layer; 3] = [Layer::Base, Layer::Effects, Layer::Override];
+
+    /// A method to return the name of the layer, which matches the name in
+    /// the serialized layer
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Base => "base",
+            Layer::Effects => "effects",
+            Layer::Override => "override",
+        }
+    }
+}
+
+// Implement the name of each layer (for the web api)
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Implement reading a layer from its name (for the web api)
+impl FromStr for Layer {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Layer::ALL
+            .into_iter()
+            .find(|layer| layer.name() == name)
+            .ok_or_else(|| anyhow!("Layer not found: {}.", name))
+    }
+}
+
+/// A struct to describe the current state of a layer
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerState {
+    pub layer: Layer,   // the layer
+    pub opacity: u8,    // the share of the layer in the output, in percent
+    pub is_muted: bool, // a flag to leave the layer out of the output
+    pub is_solo: bool,  // a flag to leave every layer that is not solo out of the output
+}
+
+// Implement key features of the layer state
+impl LayerState {
+    /// A function to create the state of a layer at full opacity
+    ///
+    pub fn new(layer: Layer) -> Self {
+        Self {
+            layer,
+            opacity: FULL_LEVEL,
+            is_muted: false,
+            is_solo: false,
+        }
+    }
+}
+
+/// A struct to request a change to a layer. Any field that is left out is
+/// unchanged.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LayerUpdate {
+    pub opacity: Option<u8>,    // the new opacity of the layer, in percent
+    pub is_muted: Option<bool>, // the new mute of the layer
+    pub is_solo: Option<bool>,  // the new solo of the layer
+}
+
+/// A struct to request manual overrides of some channels. Each channel holds
+/// its value above every playback until it is released.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Override {
+    pub values: FnvHashMap<u32, u8>, // the value of each channel to override
+}
+
+/// A struct to request the release of manual overrides. Each channel fades
+/// back to the layers below it over the duration.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OverrideRelease {
+    pub channels: Vec<u32>, // the channels to release (if empty, every channel is released)
+    pub duration: Option<Duration>, // the time to release the channels (None for the configured release time)
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2021 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module defines all structures and types used across modules.
+
+// Define program constants
+pub const API_VERSION: &str = "v1"; // the current version of the web api
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8852";
+pub const DEFAULT_LOGLEVEL: &str = "Info";
+pub const DEFAULT_RESOLUTION: u64 = 50; // the default fade resolution, in ms
+pub const DEFAULT_EXIT_TIME: u64 = 0; // the default time before releasing the output, in secs
+pub const DEFAULT_LOG_MAX_SIZE: u64 = 10; // the default size before rotating the log file, in MB
+pub const DEFAULT_LOG_MAX_FILES: usize = 5; // the default number of rotated log files to keep
+pub const DEFAULT_UNDO_DEPTH: usize = 20; // the default number of looks that can be undone
+pub const DEFAULT_UNDO_TIME: u64 = 1000; // the default time to crossfade to an undone look, in ms
+pub const MAX_FADE_MULTIPLIER: f64 = 10.0; // the largest multiplier of the duration of every fade
+pub const MAX_EFFECT_SPEED: f64 = 10.0; // the largest multiplier of the speed of the effects
+pub const DEFAULT_RELEASE_TIME: u64 = 1000; // the default time to release a manual override, in ms
+pub const DEFAULT_IDLE_FADE: u64 = 3000; // the default time to fade to the safe look when idle, in ms
+pub const DEFAULT_HISTORY_RESOLUTION: u64 = 100; // the default time between samples of the channel history, in ms
+pub const DEFAULT_HISTORY_RETENTION: u64 = 86_400; // the default age of the oldest sample of the channel history, in secs
+pub const DEFAULT_HISTORY_SAMPLES: usize = 10_000; // the default number of changes kept for each channel
+pub const DEFAULT_ESTOP_DEBOUNCE: u64 = 20; // the default time an emergency stop input must hold steady, in ms
+pub const DEFAULT_MAX_FADE_DURATION: u64 = 3600; // the default longest fade accepted by the web interface, in secs
+pub const DEFAULT_GPIO_DEBOUNCE: u64 = 50; // the default time for a GPIO pin to settle, in ms
+pub const DEFAULT_GPIO_LONG_PRESS: u64 = 1000; // the default time to hold a GPIO button for a long press, in ms
+pub const DEFAULT_CONFIRM_TIMEOUT: u64 = 30; // the default time to repeat a request that needs confirmation, in secs
+pub const DEFAULT_RATE_LIMIT: u32 = 50; // the default requests per second for each client, when limited
+pub const DEFAULT_RATE_BURST: u32 = 100; // the default requests each client may make at once, when limited
+pub const DEFAULT_BPM: f64 = 120.0; // the starting tempo of the beat clock, in beats per minute
+pub const MIN_BPM: f64 = 20.0; // the slowest tempo of the beat clock, in beats per minute
+pub const MAX_BPM: f64 = 300.0; // the fastest tempo of the beat clock, in beats per minute
+pub const MAX_BEATS: f64 = 64.0; // the most beats in one cycle of an effect or one wait of a script
+pub const MAX_CLIENT_ID: usize = 64; // the longest identifier a client may give in a heartbeat or header
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100; // the default samples per second of the audio input
+pub const DEFAULT_BEAT_THRESHOLD: f64 = 1.5; // the default ratio above the average energy of a band for a beat
+pub const DEFAULT_ART_NET_ADDRESS: &str = "0.0.0.0:6454"; // the default udp address of the Art-Net node
+pub const DEFAULT_NODE_NAME: &str = "Vulcan"; // the default name of the node on show networks
+pub const DEFAULT_SACN_PRIORITY: u8 = 100; // the default priority of the sACN output
+pub const DEFAULT_CITP_ADDRESS: &str = "0.0.0.0:0"; // the default tcp address for CITP visualizers (any port)
+pub const DEFAULT_FOLLOW_ADDRESS: &str = "0.0.0.0:5568"; // the default udp address to listen for a leader
+pub const DEFAULT_ART_NET_BROADCAST: &str = "255.255.255.255:6454"; // the default destination of Art-Net output
+pub const DEFAULT_EFFECT_CHANCE: f64 = 0.25; // the default chance of each random event of an effect
+
+// Define submodules
+mod audit;
+mod color;
+mod communication;
+mod config;
+mod cue;
+mod dmx;
+mod effect;
+mod error;
+mod event;
+mod fixture;
+mod label;
+mod layer;
+mod palette;
+mod pixel;
+mod script;
+mod tempo;
+mod timeline;
+mod trigger;
+mod validation;
+
+// Reexport all the definitions from the submodules
+pub use self::audit::*;
+pub use self::color::*;
+pub use self::communication::*;
+pub use self::config::*;
+pub use self::cue::*;
+pub use self::dmx::*;
+pub use self::effect::*;
+pub use self::error::*;
+pub use self::event::*;
+pub use self::fixture::*;
+pub use self::label::*;
+pub use self::layer::*;
+pub use self::palette::*;
+pub use self::pixel::*;
+pub use self::script::*;
+pub use self::tempo::*;
+pub use self::timeline::*;
+pub use self::trigger::*;
+pub use self::validation::*;
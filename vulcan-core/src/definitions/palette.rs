@@ -0,0 +1,157 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements palettes: named colors, positions, and beam
+//! states that are stored apart from the scenes. Each scene refers to its
+//! palettes by name, so updating a palette once updates every scene that
+//! uses it.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// An enum to describe what a palette holds, for clients to organize the
+/// palettes
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PaletteKind {
+    /// a variant for the color of a fixture
+    Color,
+
+    /// a variant for the position of a fixture
+    Position,
+
+    /// a variant for the beam of a fixture (such as the zoom or gobo)
+    Beam,
+}
+
+/// A struct to define a palette: the values of some parameters, for every
+/// fixture that has those parameters
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Palette {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<PaletteKind>, // the kind of the palette, if any
+    pub values: FnvHashMap<Parameter, u8>, // the value of each parameter
+}
+
+/// A struct to apply a palette to some fixtures as part of a scene
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenePalette {
+    pub palette: String,       // the name of the palette
+    pub fixtures: Vec<String>, // the names of the fixtures
+}
+
+// Implement key features of the palette
+impl Palette {
+    /// A method to find the channel and value of each parameter of the
+    /// palette for the fixture. Parameters the fixture does not have are
+    /// skipped.
+    ///
+    pub fn channels<'a>(
+        &'a self,
+        fixture: &'a Fixture,
+        profile: &'a FixtureProfile,
+    ) -> impl Iterator<Item = (u32, u8)> + 'a {
+        self.values.iter().filter_map(|(parameter, value)| {
+            fixture
+                .channel(profile, parameter)
+                .map(|channel| (channel, *value))
+        })
+    }
+}
+
+// Tests of the palette module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a scene includes the values of its palettes
+    #[test]
+    fn resolve_palettes() {
+        let mut config: Config = serde_yaml::from_str(
+            "profiles:
+  wash:
+    channels: [intensity, red, green, blue]
+  spot:
+    channels: [intensity, red, green, blue, white, gobo]
+fixtures:
+  wash1:
+    profile: wash
+    address: 1
+  spot1:
+    profile: spot
+    address: 11
+palettes:
+  Company Blue:
+    kind: color
+    values:
+      red: 0
+      green: 40
+      blue: 255
+      white: 20
+scenes:
+  warm:
+    palettes:
+      - palette: Company Blue
+        fixtures: [wash1, spot1]
+    values:
+      1: 255
+      4: 200
+",
+        )
+        .unwrap();
+
+        // The palette fills the parameters each fixture has, and the values
+        // of the scene take precedence
+        let scene = config.scene("warm").unwrap();
+        let mut values: Vec<(u32, u8)> = scene.values.into_iter().collect();
+        values.sort_unstable();
+        assert_eq!(
+            values,
+            vec![
+                (1, 255),
+                (2, 0),
+                (3, 40),
+                (4, 200),
+                (12, 0),
+                (13, 40),
+                (14, 255),
+                (15, 20)
+            ]
+        );
+
+        // Updating the palette updates the scene
+        config
+            .palettes
+            .get_mut("Company Blue")
+            .unwrap()
+            .values
+            .insert(Parameter::Blue, 128);
+        assert_eq!(config.scene("warm").unwrap().values[&14], 128);
+
+        // A missing palette is an error
+        config.palettes.clear();
+        assert!(config.scene("warm").is_err());
+    }
+}
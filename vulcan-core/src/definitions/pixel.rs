@@ -0,0 +1,169 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements structures to map LED strips and matrices onto
+//! the universe, so that a generator can color every pixel at once.
+
+// Import standard library features
+use std::time::Duration;
+
+/// A struct to describe an LED strip or matrix. Each pixel takes three
+/// channels (red, green, and blue), starting from the first channel and
+/// running along each row.
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelMap {
+    pub start: u32, // the first channel of the first pixel
+    pub width: u32, // the number of pixels in each row
+    #[serde(default = "default_height")]
+    pub height: u32, // the number of rows (one for a strip)
+    #[serde(default)]
+    pub serpentine: bool, // a flag to indicate that every other row runs backwards
+}
+
+// Implement key features of the pixel map
+impl PixelMap {
+    /// A method to return the last channel of the map
+    ///
+    pub fn last_channel(&self) -> u32 {
+        self.start + self.width * self.height * 3 - 1
+    }
+
+    /// A method to list every channel of the map
+    ///
+    pub fn channels(&self) -> impl Iterator<Item = u32> {
+        self.start..=self.last_channel()
+    }
+
+    /// A method to list every pixel with its first channel and its position
+    /// in the map (from zero to one in each direction)
+    ///
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, f64, f64)> + '_ {
+        (0..self.height).flat_map(move |row| {
+            (0..self.width).map(move |index| {
+                let column = if self.serpentine && row % 2 == 1 {
+                    self.width - 1 - index
+                } else {
+                    index
+                };
+                let channel = self.start + (row * self.width + index) * 3;
+                (
+                    channel,
+                    position(column, self.width),
+                    position(row, self.height),
+                )
+            })
+        })
+    }
+}
+
+/// A struct to describe a color of a pixel
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Rgb {
+    pub red: u8,   // the level of red
+    pub green: u8, // the level of green
+    pub blue: u8,  // the level of blue
+}
+
+// Implement key features of the color
+impl Rgb {
+    /// A function to create a new color
+    ///
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// A method to mix this color with another, by the share of the other
+    /// color (from zero to one)
+    ///
+    pub fn mix(&self, other: &Rgb, share: f64) -> Rgb {
+        let share = share.clamp(0.0, 1.0);
+        let mix =
+            |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * share).round() as u8;
+        Rgb {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+        }
+    }
+
+    /// A method to scale the color by the level (from zero to one)
+    ///
+    pub fn scale(&self, level: f64) -> Rgb {
+        Rgb::default().mix(self, level)
+    }
+}
+
+/// A struct to request a pixel generator on a pixel map. The generator
+/// animates once per period (or once every few beats, locked to the beat
+/// clock).
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PixelPlay {
+    pub map: String,       // the name of the pixel map
+    pub generator: String, // the name of the generator
+    #[serde(default)]
+    pub colors: Vec<Rgb>, // the colors of the generator (if empty, red, green, and blue)
+    #[serde(default)]
+    pub period: Duration, // the time for one cycle of the animation (ignored if locked to the beat)
+    #[serde(default)]
+    pub beats: Option<f64>, // the beats in one cycle of the animation, if locked to the beat clock
+}
+
+/// A helper function to find the position of an index along a row or
+/// column, from zero to one
+///
+fn position(index: u32, count: u32) -> f64 {
+    if count > 1 {
+        index as f64 / (count - 1) as f64
+    } else {
+        0.0
+    }
+}
+
+/// A helper function to default the height of a pixel map to one row
+///
+fn default_height() -> u32 {
+    1
+}
+
+// Tests of the pixel module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a serpentine matrix lists its pixels along the wiring
+    #[test]
+    fn serpentine_pixels() {
+        let map: PixelMap =
+            serde_yaml::from_str("start: 10\nwidth: 3\nheight: 2\nserpentine: true\n").unwrap();
+        assert_eq!(map.last_channel(), 27);
+        let pixels: Vec<(u32, f64, f64)> = map.pixels().collect();
+        assert_eq!(pixels[0], (10, 0.0, 0.0));
+        assert_eq!(pixels[2], (16, 1.0, 0.0));
+        assert_eq!(pixels[3], (19, 1.0, 1.0));
+        assert_eq!(pixels[5], (25, 0.0, 1.0));
+        assert_eq!(
+            Rgb::new(0, 100, 200).mix(&Rgb::new(200, 100, 0), 0.25),
+            Rgb::new(50, 100, 150)
+        );
+    }
+}
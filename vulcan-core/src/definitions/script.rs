@@ -0,0 +1,178 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the scripts, which combine actions with waits,
+//! loops, and conditions for show logic that a single trigger can't express.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+/// A struct to hold one script from the configuration
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Script {
+    pub steps: Vec<ScriptStep>, // the steps of the script, in order
+    #[serde(default)]
+    pub every: Option<Duration>, // the time between runs of the script, if it runs on a timer
+}
+
+/// An enum to describe each step of a script
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ScriptStep {
+    /// A variant to pause the script
+    Wait {
+        duration: Duration, // the time to wait
+    },
+
+    /// A variant to pause the script until the next multiple of some beats
+    /// on the beat clock, so that the steps follow the tempo
+    WaitBeats {
+        beats: f64, // the beats between each step (such as 0.5, 1, or 4)
+    },
+
+    /// A variant to perform some steps only if a condition is met
+    If {
+        condition: Condition,  // the condition to check
+        then: Vec<ScriptStep>, // the steps if the condition is met
+        #[serde(default, rename = "else")]
+        otherwise: Vec<ScriptStep>, // the steps if the condition is not met
+    },
+
+    /// A variant to perform some steps several times
+    Repeat {
+        #[serde(default)]
+        count: Option<u32>, // the number of times to perform the steps (if none, until the script is stopped)
+        steps: Vec<ScriptStep>, // the steps to repeat
+    },
+
+    /// A variant to set (or clear) a flag shared by every script
+    SetFlag {
+        name: String, // the name of the flag
+        #[serde(default = "default_true")]
+        value: bool, // the new value of the flag
+    },
+
+    /// A variant to perform any trigger action (including starting and
+    /// stopping other scripts)
+    #[serde(untagged)]
+    Action(TriggerAction),
+}
+
+/// An enum to describe the conditions a script can check
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Condition {
+    /// A variant to compare the current value of a channel
+    Channel {
+        channel: u32, // the channel to check
+        #[serde(default)]
+        above: Option<u8>, // the value the channel must be above, if any
+        #[serde(default)]
+        below: Option<u8>, // the value the channel must be below, if any
+    },
+
+    /// A variant to check a flag set by a script
+    Flag {
+        name: String, // the name of the flag
+        #[serde(default = "default_true")]
+        value: bool, // the value the flag must have (unset flags are false)
+    },
+}
+
+// Implement key features of the condition
+impl Condition {
+    /// A method to check whether the value of a channel meets the condition
+    ///
+    pub fn is_channel_met(value: u8, above: Option<u8>, below: Option<u8>) -> bool {
+        above.is_none_or(|above| value > above) && below.is_none_or(|below| value < below)
+    }
+}
+
+/// A helper function to default flags to true
+///
+fn default_true() -> bool {
+    true
+}
+
+// Tests of the script module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that scripts read from YAML with actions alongside other steps
+    #[test]
+    fn read_script() {
+        let script: Script = serde_yaml::from_str(
+            "
+            every: {secs: 10, nanos: 0}
+            steps:
+              - type: if
+                condition: {type: flag, name: armed}
+                then:
+                  - {type: recallScene, name: warm}
+                else:
+                  - {type: playFade, fade: {channel: 2, value: 9}}
+              - {type: wait, duration: {secs: 1, nanos: 0}}
+              - {type: setFlag, name: armed, value: false}
+            ",
+        )
+        .unwrap();
+        assert_eq!(script.every, Some(Duration::from_secs(10)));
+        assert_eq!(
+            script.steps[0],
+            ScriptStep::If {
+                condition: Condition::Flag {
+                    name: "armed".into(),
+                    value: true,
+                },
+                then: vec![ScriptStep::Action(TriggerAction::RecallScene {
+                    name: "warm".into(),
+                    duration: None,
+                })],
+                otherwise: vec![ScriptStep::Action(TriggerAction::PlayFade {
+                    fade: Fade {
+                        channel: 2,
+                        value: 9,
+                        duration: None,
+                    },
+                })],
+            }
+        );
+
+        // Unknown steps are rejected
+        assert!(serde_yaml::from_str::<ScriptStep>("{type: jump}").is_err());
+
+        // Channel conditions check both bounds
+        assert!(Condition::is_channel_met(100, Some(50), Some(200)));
+        assert!(!Condition::is_channel_met(50, Some(50), None));
+    }
+}
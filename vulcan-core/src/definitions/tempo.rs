@@ -1,4 +1,4 @@
-// Copyright (c) 2021 Decode Detroit
+// Copyright (c) 2024 Decode Detroit
 // Author: Patton Doyle
 // Licence: GNU GPLv3
 //
@@ -15,16 +15,13 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! This module defines all structures and types used across modules.
+//! This module implements structures to set the tempo of the beat clock,
+//! which effects, movements, and scripts can follow.
 
-// Define program constants
-pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8852";
-pub const DEFAULT_LOGLEVEL: &str = "Info";
-
-// Define submodules
-mod communication;
-mod dmx;
-
-// Reexport all the definitions from the submodules
-pub use self::communication::*;
-pub use self::dmx::*;
+/// A struct to request a new tempo. The change starts a new beat.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tempo {
+    pub bpm: f64, // the new tempo, in beats per minute
+}
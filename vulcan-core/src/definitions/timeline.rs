@@ -0,0 +1,103 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements timelines: actions placed at absolute times and
+//! played with transport controls, for fixed-length installations and shows
+//! synchronized to media.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+/// A struct to define a timeline of actions
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timeline {
+    #[serde(default)]
+    pub length: Option<Duration>, // the length of the timeline (None to end with the last entry)
+    #[serde(default)]
+    pub is_looping: bool, // a flag to return to the start at the end of the timeline
+    pub entries: Vec<TimelineEntry>, // the actions of the timeline
+}
+
+// Implement key features of the timeline
+impl Timeline {
+    /// A method to return the length of the timeline: the configured length,
+    /// or otherwise the time of the last entry
+    ///
+    pub fn length(&self) -> Duration {
+        self.length.unwrap_or_else(|| {
+            self.entries
+                .iter()
+                .map(|entry| entry.at)
+                .max()
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// A struct to place one action on a timeline
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEntry {
+    pub at: Duration,          // the time of the action, from the start of the timeline
+    pub action: TriggerAction, // the action to run (any trigger action)
+}
+
+/// An enum to request a change to the transport of a timeline
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum TimelineControl {
+    /// A variant to play the timeline from the current position (or from the
+    /// start, if it is stopped)
+    Play,
+
+    /// A variant to hold the timeline at the current position
+    Pause,
+
+    /// A variant to stop the timeline and return to the start
+    Stop,
+
+    /// A variant to move the timeline to a position, without running the
+    /// actions before it
+    Seek {
+        position: Duration, // the new position of the timeline
+    },
+
+    /// A variant to change whether the timeline loops
+    #[serde(rename_all = "camelCase")]
+    SetLoop {
+        is_looping: bool, // a flag to return to the start at the end of the timeline
+    },
+}
+
+/// A struct to describe the transport of a timeline
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineStatus {
+    pub is_playing: bool,   // a flag to indicate the timeline is playing
+    pub is_paused: bool,    // a flag to indicate the timeline is paused
+    pub position: Duration, // the current position of the timeline
+    pub length: Duration,   // the length of the timeline
+    pub is_looping: bool,   // a flag to indicate the timeline loops
+}
@@ -0,0 +1,239 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the trigger map, which binds inputs from outside
+//! the program to the actions they perform.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+/// A struct to bind one input to one action
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trigger {
+    pub input: TriggerInput,   // the input that fires the trigger
+    pub action: TriggerAction, // the action to perform when the trigger fires
+}
+
+/// An enum to describe the inputs that can fire a trigger
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TriggerInput {
+    /// A variant for a post to /trigger/{name} on the web interface
+    Http {
+        name: String, // the name of the trigger in the path
+    },
+
+    /// A variant for an OSC message
+    Osc {
+        address: String, // the OSC address of the message
+    },
+
+    /// A variant for a MIDI note on message
+    Midi {
+        channel: u8, // the MIDI channel of the note (1-16)
+        note: u8,    // the number of the note (0-127)
+    },
+
+    /// A variant for a button or contact closure on a GPIO pin
+    Gpio {
+        pin: u32, // the sysfs number of the pin
+        #[serde(default)]
+        long_press: bool, // a flag to fire only when the button is held (otherwise fired by a short press)
+    },
+
+    /// A variant for a beat in a band of the audio input
+    Audio {
+        band: String, // the name of the band
+    },
+}
+
+/// An enum to describe the actions that a trigger can perform
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TriggerAction {
+    /// A variant to recall a scene
+    RecallScene {
+        name: String,               // the name of the scene
+        duration: Option<Duration>, // the duration of the fade (None if instantaneous)
+    },
+
+    /// A variant to play a fade on a channel
+    PlayFade {
+        fade: Fade, // the fade to play
+    },
+
+    /// A variant to start an effect on some channels
+    PlayEffect {
+        effect: EffectPlay, // the effect to play
+    },
+
+    /// A variant to start an effect preset
+    PlayPreset {
+        name: String, // the name of the preset
+    },
+
+    /// A variant to stop effects, holding the current values
+    StopEffect {
+        #[serde(default)]
+        channels: Vec<u32>, // the channels to stop (if empty with no fixtures or pixel maps, every effect stops)
+        #[serde(default)]
+        fixtures: Vec<String>, // the fixtures to stop, by name
+        #[serde(default)]
+        pixel_maps: Vec<String>, // the pixel maps to stop, by name
+    },
+
+    /// A variant to start a movement effect on some fixtures
+    PlayMovement {
+        movement: MovementPlay, // the movement to play
+    },
+
+    /// A variant to start a pixel generator on a pixel map
+    PlayPixels {
+        pixels: PixelPlay, // the pixel generator to play
+    },
+
+    /// A variant to black out a group of channels (or release it)
+    SetGroupBlackout {
+        name: String,      // the name of the group
+        is_blackout: bool, // a flag to hold the channels of the group at zero
+    },
+
+    /// A variant to tap the beat of the beat clock
+    Tap,
+
+    /// A variant to set all the lights to their emergency values
+    AllStop,
+
+    /// A variant to crossfade back to the previous look
+    Undo,
+
+    /// A variant to crossfade forward to the most recently undone look
+    Redo,
+
+    /// A variant to run the next cue of a cue list
+    Go {
+        #[serde(default = "main_cue_list")]
+        list: String, // the name of the cue list (the main list by default)
+    },
+
+    /// A variant to return a cue list to the first cue
+    ResetCues {
+        #[serde(default = "main_cue_list")]
+        list: String, // the name of the cue list (the main list by default)
+    },
+
+    /// A variant to play, pause, stop, seek, or loop a timeline
+    ControlTimeline {
+        name: String,             // the name of the timeline
+        control: TimelineControl, // the change to the transport
+    },
+
+    /// A variant to start a script (or restart it, if it is running)
+    RunScript {
+        name: String, // the name of the script
+    },
+
+    /// A variant to stop a script, if it is running
+    StopScript {
+        name: String, // the name of the script
+    },
+}
+
+// Implement key features of the trigger action
+impl TriggerAction {
+    /// A method to convert the action into the matching request
+    ///
+    pub fn to_request(&self) -> Request {
+        match self {
+            TriggerAction::RecallScene { name, duration } => Request::RecallScene {
+                recall: SceneRecall {
+                    name: name.clone(),
+                    duration: *duration,
+                },
+            },
+            TriggerAction::PlayFade { fade } => Request::PlayFade { fade: fade.clone() },
+            TriggerAction::PlayEffect { effect } => Request::PlayEffect {
+                effect: effect.clone(),
+            },
+            TriggerAction::PlayPreset { name } => Request::PlayPreset {
+                preset: PresetPlay { name: name.clone() },
+            },
+            TriggerAction::StopEffect {
+                channels,
+                fixtures,
+                pixel_maps,
+            } => Request::StopEffect {
+                stop: EffectStop {
+                    channels: channels.clone(),
+                    fixtures: fixtures.clone(),
+                    pixel_maps: pixel_maps.clone(),
+                },
+            },
+            TriggerAction::PlayMovement { movement } => Request::PlayMovement {
+                movement: movement.clone(),
+            },
+            TriggerAction::PlayPixels { pixels } => Request::PlayPixels {
+                play: pixels.clone(),
+            },
+            TriggerAction::SetGroupBlackout { name, is_blackout } => Request::SetGroupBlackout {
+                name: name.clone(),
+                blackout: GroupBlackout {
+                    is_blackout: *is_blackout,
+                },
+            },
+            TriggerAction::Tap => Request::Tap,
+            TriggerAction::AllStop => Request::AllStop,
+            TriggerAction::Undo => Request::Undo,
+            TriggerAction::Redo => Request::Redo,
+            TriggerAction::Go { list } => Request::Go { list: list.clone() },
+            TriggerAction::ResetCues { list } => Request::ResetCues { list: list.clone() },
+            TriggerAction::ControlTimeline { name, control } => Request::ControlTimeline {
+                name: name.clone(),
+                control: control.clone(),
+            },
+            TriggerAction::RunScript { name } => Request::RunScript { name: name.clone() },
+            TriggerAction::StopScript { name } => Request::StopScript { name: name.clone() },
+        }
+    }
+}
+
+/// A function to find the request for an input, if there is a trigger bound
+/// to the input
+///
+pub fn find_trigger(triggers: &[Trigger], input: &TriggerInput) -> Option<Request> {
+    triggers
+        .iter()
+        .find(|trigger| trigger.input == *input)
+        .map(|trigger| trigger.action.to_request())
+}
+
+/// A helper function to default the cue list of an action to the main list
+///
+fn main_cue_list() -> String {
+    MAIN_CUE_LIST.to_string()
+}
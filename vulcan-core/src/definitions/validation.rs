@@ -0,0 +1,585 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the validation of request bodies, so that every
+//! problem with a request is reported before the request is processed.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::Duration;
+
+/// A struct to describe a problem with one field of a request
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    pub field: String,   // the name of the field
+    pub message: String, // a description of the problem
+}
+
+// Implement key features of the field error
+impl FieldError {
+    /// A function to create a new field error
+    ///
+    pub fn new<S>(field: &str, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// A trait for request bodies that can be checked before they are processed
+///
+pub trait Validate {
+    /// A method to return every problem with the body (if none, the body is
+    /// valid). Durations must be no longer than the maximum duration.
+    ///
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError>;
+}
+
+/// A helper function to check an optional duration
+///
+fn validate_duration(duration: Option<Duration>, max_duration: Duration) -> Option<FieldError> {
+    duration
+        .filter(|duration| *duration > max_duration)
+        .map(|_| {
+            FieldError::new(
+                "duration",
+                format!("Must be at most {} secs.", max_duration.as_secs()),
+            )
+        })
+}
+
+/// A helper function to check an optional share, from zero to one
+///
+fn validate_share(field: &str, share: Option<f64>, errors: &mut Vec<FieldError>) {
+    if share.is_some_and(|share| !(0.0..=1.0).contains(&share)) {
+        errors.push(FieldError::new(field, "Must be between 0 and 1."));
+    }
+}
+
+/// A helper function to check the cycle of an effect, which is either a
+/// number of beats or a period
+///
+fn validate_cycle(
+    period: Duration,
+    beats: Option<f64>,
+    max_duration: Duration,
+    errors: &mut Vec<FieldError>,
+) {
+    match beats {
+        Some(beats) => {
+            if !(beats > 0.0 && beats <= MAX_BEATS) {
+                errors.push(FieldError::new(
+                    "beats",
+                    format!("Must be more than zero and at most {}.", MAX_BEATS),
+                ));
+            }
+        }
+        None => {
+            if period.is_zero() || period > max_duration {
+                errors.push(FieldError::new(
+                    "period",
+                    format!(
+                        "Must be more than zero and at most {} secs.",
+                        max_duration.as_secs()
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+// Implement validation of fades
+impl Validate for Fade {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !(1..=DMX_MAX).contains(&self.channel) {
+            errors.push(FieldError::new(
+                "channel",
+                format!("Must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        errors
+    }
+}
+
+// Implement validation of scene recalls
+impl Validate for SceneRecall {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.name.is_empty() {
+            errors.push(FieldError::new("name", "Must not be empty."));
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        errors
+    }
+}
+
+// Implement validation of effect presets
+impl Validate for PresetPlay {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        if self.name.is_empty() {
+            return vec![FieldError::new("name", "Must not be empty.")];
+        }
+        Vec::new()
+    }
+}
+
+// Implement validation of effects
+impl Validate for EffectPlay {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.effect.is_empty() {
+            errors.push(FieldError::new("effect", "Must not be empty."));
+        }
+        if self.channels.is_empty() {
+            errors.push(FieldError::new("channels", "Must not be empty."));
+        }
+        if let Some(index) = self
+            .channels
+            .iter()
+            .position(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            errors.push(FieldError::new(
+                &format!("channels[{}]", index),
+                format!("Must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        validate_cycle(self.period, self.beats, max_duration, &mut errors);
+        validate_share("chance", Some(self.chance), &mut errors);
+        validate_share("intensity", Some(self.intensity), &mut errors);
+        errors
+    }
+}
+
+// Implement validation of effect tuning
+impl Validate for EffectTune {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(index) = self
+            .channels
+            .iter()
+            .position(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            errors.push(FieldError::new(
+                &format!("channels[{}]", index),
+                format!("Must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        validate_share("chance", self.chance, &mut errors);
+        validate_share("intensity", self.intensity, &mut errors);
+        errors
+    }
+}
+
+// Implement validation of pixel generators
+impl Validate for PixelPlay {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.map.is_empty() {
+            errors.push(FieldError::new("map", "Must not be empty."));
+        }
+        if self.generator.is_empty() {
+            errors.push(FieldError::new("generator", "Must not be empty."));
+        }
+        validate_cycle(self.period, self.beats, max_duration, &mut errors);
+        errors
+    }
+}
+
+// Implement validation of tempo changes
+impl Validate for Tempo {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !(MIN_BPM..=MAX_BPM).contains(&self.bpm) {
+            errors.push(FieldError::new(
+                "bpm",
+                format!("Must be between {} and {}.", MIN_BPM, MAX_BPM),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of fade multipliers
+impl Validate for FadeMultiplier {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !(self.multiplier > 0.0 && self.multiplier <= MAX_FADE_MULTIPLIER) {
+            errors.push(FieldError::new(
+                "multiplier",
+                format!(
+                    "Must be more than zero and at most {}.",
+                    MAX_FADE_MULTIPLIER
+                ),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of changes to the speed of the effects
+impl Validate for EffectSpeedUpdate {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        [
+            ("master", self.master),
+            ("effects", self.effects),
+            ("movements", self.movements),
+            ("pixels", self.pixels),
+        ]
+        .into_iter()
+        .filter(|(_, speed)| speed.is_some_and(|speed| !(speed > 0.0 && speed <= MAX_EFFECT_SPEED)))
+        .map(|(field, _)| {
+            FieldError::new(
+                field,
+                format!("Must be more than zero and at most {}.", MAX_EFFECT_SPEED),
+            )
+        })
+        .collect()
+    }
+}
+
+// Implement validation of stopping effects
+impl Validate for EffectStop {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        self.channels
+            .iter()
+            .position(|channel| !(1..=DMX_MAX).contains(channel))
+            .map(|index| {
+                FieldError::new(
+                    &format!("channels[{}]", index),
+                    format!("Must be between 1 and {}.", DMX_MAX),
+                )
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+// Implement validation of movement effects (the movement of each fixture is
+// limited to the range of its profile)
+impl Validate for MovementPlay {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.fixtures.is_empty() {
+            errors.push(FieldError::new("fixtures", "Must not be empty."));
+        }
+        for (field, degrees) in [
+            ("pan", self.pan),
+            ("tilt", self.tilt),
+            ("radius", self.radius),
+        ] {
+            if !degrees.is_finite() || degrees < 0.0 {
+                errors.push(FieldError::new(field, "Must be zero or more degrees."));
+            }
+        }
+        if !self.phase.is_finite() {
+            errors.push(FieldError::new("phase", "Must be a number."));
+        }
+        validate_cycle(self.period, self.beats, max_duration, &mut errors);
+        errors
+    }
+}
+
+// Implement validation of positions (the range of each axis is checked
+// against the profile of the fixture)
+impl Validate for Position {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.fixture.is_empty() {
+            errors.push(FieldError::new("fixture", "Must not be empty."));
+        }
+        if self.pan.is_none() && self.tilt.is_none() {
+            errors.push(FieldError::new("pan", "Must include a pan or a tilt."));
+        }
+        for (field, degrees) in [("pan", self.pan), ("tilt", self.tilt)] {
+            if degrees.is_some_and(|degrees| !degrees.is_finite() || degrees < 0.0) {
+                errors.push(FieldError::new(field, "Must be zero or more degrees."));
+            }
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        errors
+    }
+}
+
+// Implement validation of colors (the emitters are checked against the
+// profile of the fixture)
+impl Validate for Chromaticity {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.fixture.is_empty() {
+            errors.push(FieldError::new("fixture", "Must not be empty."));
+        }
+        if !(self.x.is_finite() && (0.0..1.0).contains(&self.x)) {
+            errors.push(FieldError::new("x", "Must be from 0 up to 1."));
+        }
+        if !(self.y.is_finite() && self.y > 0.0 && self.y <= 1.0) {
+            errors.push(FieldError::new("y", "Must be above 0 and up to 1."));
+        } else if self.x + self.y > 1.0 {
+            errors.push(FieldError::new("y", "Must be no more than 1 minus x."));
+        }
+        if !(self.brightness.is_finite() && (0.0..=1.0).contains(&self.brightness)) {
+            errors.push(FieldError::new("brightness", "Must be between 0 and 1."));
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        errors
+    }
+}
+
+// Implement validation of group fans
+impl Validate for GroupFan {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.group.is_empty() {
+            errors.push(FieldError::new("group", "Must not be empty."));
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        if let Some(mut error) = validate_duration(self.spread, max_duration) {
+            error.field = "spread".into();
+            errors.push(error);
+        }
+        errors
+    }
+}
+
+// Implement validation of group levels
+impl Validate for GroupLevel {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.level > FULL_LEVEL {
+            errors.push(FieldError::new(
+                "level",
+                format!("Must be between 0 and {}.", FULL_LEVEL),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of layer changes
+impl Validate for LayerUpdate {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.opacity.is_some_and(|opacity| opacity > FULL_LEVEL) {
+            errors.push(FieldError::new(
+                "opacity",
+                format!("Must be between 0 and {}.", FULL_LEVEL),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of manual overrides
+impl Validate for Override {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.values.is_empty() {
+            errors.push(FieldError::new("values", "Must not be empty."));
+        }
+        let mut channels: Vec<u32> = self.values.keys().copied().collect();
+        channels.sort_unstable();
+        if let Some(channel) = channels
+            .into_iter()
+            .find(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            errors.push(FieldError::new(
+                &format!("values.{}", channel),
+                format!("Channel must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of releasing manual overrides
+impl Validate for OverrideRelease {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(index) = self
+            .channels
+            .iter()
+            .position(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            errors.push(FieldError::new(
+                &format!("channels[{}]", index),
+                format!("Must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        errors.extend(validate_duration(self.duration, max_duration));
+        errors
+    }
+}
+
+// Implement validation of palettes
+impl Validate for Palette {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        if self.values.is_empty() {
+            return vec![FieldError::new(
+                "values",
+                "A palette needs at least one value.",
+            )];
+        }
+        Vec::new()
+    }
+}
+
+// Implement validation of fixture patches (the mode and the end of the
+// fixture are checked against its profile)
+impl Validate for FixturePatch {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !(1..=DMX_MAX).contains(&self.address) {
+            errors.push(FieldError::new(
+                "address",
+                format!("Must be between 1 and {}.", DMX_MAX),
+            ));
+        }
+        if self.mode.as_ref().is_some_and(String::is_empty) {
+            errors.push(FieldError::new("mode", "Must not be empty."));
+        }
+        errors
+    }
+}
+
+// Implement validation of labels (a label without a name is removed)
+impl Validate for Labels {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut channels: Vec<u32> = self.channels.keys().copied().collect();
+        channels.sort_unstable();
+        channels
+            .into_iter()
+            .filter(|channel| !(1..=DMX_MAX).contains(channel))
+            .map(|channel| {
+                FieldError::new(
+                    &format!("channels.{}", channel),
+                    format!("Channel must be between 1 and {}.", DMX_MAX),
+                )
+            })
+            .collect()
+    }
+}
+
+// Implement validation of heartbeats
+impl Validate for Heartbeat {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.client_id.is_empty() {
+            errors.push(FieldError::new("clientId", "Must not be empty."));
+        } else if self.client_id.len() > MAX_CLIENT_ID {
+            errors.push(FieldError::new(
+                "clientId",
+                format!("Must be at most {} characters.", MAX_CLIENT_ID),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of universes
+impl Validate for Universe {
+    fn validate(&self, _max_duration: Duration) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.as_bytes().len() != DMX_MAX as usize {
+            errors.push(FieldError::new(
+                "values",
+                format!("Must contain exactly {} values.", DMX_MAX),
+            ));
+        }
+        errors
+    }
+}
+
+// Implement validation of the requests of a batch
+impl Validate for BatchRequest {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        match self {
+            BatchRequest::PlayFade(fade) => fade.validate(max_duration),
+            BatchRequest::LoadUniverse(universe) | BatchRequest::DefineAllStop(universe) => {
+                universe.validate(max_duration)
+            }
+            BatchRequest::RecallScene(recall) => recall.validate(max_duration),
+            BatchRequest::PlayEffect(effect) => effect.validate(max_duration),
+            BatchRequest::PlayPreset(preset) => preset.validate(max_duration),
+            BatchRequest::StopEffect(stop) => stop.validate(max_duration),
+            BatchRequest::PlayMovement(movement) => movement.validate(max_duration),
+            BatchRequest::PlayPixels(play) => play.validate(max_duration),
+            BatchRequest::SetPosition(position) => position.validate(max_duration),
+            BatchRequest::SetColor(color) => color.validate(max_duration),
+            BatchRequest::SetOverride(values) => values.validate(max_duration),
+            BatchRequest::ReleaseOverride(release) => release.validate(max_duration),
+            BatchRequest::AllStop | BatchRequest::Undo | BatchRequest::Redo => Vec::new(),
+        }
+    }
+}
+
+// Implement validation of a batch, noting the position of each request
+impl Validate for Vec<BatchRequest> {
+    fn validate(&self, max_duration: Duration) -> Vec<FieldError> {
+        self.iter()
+            .enumerate()
+            .flat_map(|(index, request)| {
+                request
+                    .validate(max_duration)
+                    .into_iter()
+                    .map(move |error| FieldError {
+                        field: format!("[{}].{}", index, error.field),
+                        message: error.message,
+                    })
+            })
+            .collect()
+    }
+}
+
+// Tests of the validation module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that every problem with a fade is reported
+    #[test]
+    fn validate_fade() {
+        let max_duration = Duration::from_secs(60);
+        let fade = Fade {
+            channel: 513,
+            value: 255,
+            duration: Some(Duration::from_secs(61)),
+        };
+        let fields: Vec<String> = fade
+            .validate(max_duration)
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, vec!["channel", "duration"]);
+
+        // A valid fade has no problems
+        let fade = Fade {
+            channel: 512,
+            value: 0,
+            duration: Some(max_duration),
+        };
+        assert!(fade.validate(max_duration).is_empty());
+    }
+}
@@ -0,0 +1,1681 @@
+// Copyright (c) 2019 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to control the DMX hardware. The controller runs the fade and
+//! effect engines in a background task, composes their layers into each
+//! frame, and writes the frame through a DMX driver.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Import the engines, the effects, and the drivers
+use crate::channel_counters::ChannelCounters;
+use crate::clock::{Clock, SystemClock};
+use crate::compositor::Compositor;
+use crate::driver::{DmxDriver, EnttecUsbPro};
+use crate::effect_engine::EffectEngine;
+use crate::effects::EffectRegistry;
+use crate::fade_engine::FadeEngine;
+use crate::fade_limits::FadeLimits;
+use crate::group_blackouts::GroupBlackouts;
+use crate::overrides::Overrides;
+use crate::preheat::Preheat;
+use crate::submasters::Submasters;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import the tokio features
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{Instant as TimerInstant, Interval, MissedTickBehavior, interval, timeout};
+
+// Import tracing features
+use tracing::{Instrument, Span, error, info, trace, warn};
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the time that writes must fail before the hardware is unavailable
+const UNAVAILABLE_TIME: Duration = Duration::from_secs(1);
+
+// Define the time beyond the limit that a driver may take before the write
+// is abandoned
+const WRITE_GRACE: Duration = Duration::from_millis(100);
+
+// Define the number of written frames held for slow captures
+const CAPTURE_BUFFER: usize = 256;
+
+/// A structure to hold and manipulate the DMX hardware and manage updates
+/// including universe updates and fades. This struct passes updates to the
+/// DMX driver as needed. Each clone of the controller shares the same
+/// queue.
+///
+#[derive(Clone)]
+pub struct DmxController {
+    load_fade: QueueLine, // a line to load the dmx updates into the queue
+    snapshot: watch::Receiver<Snapshot>, // the latest universe requested of the queue
+    driver: &'static str, // the type of the DMX hardware
+    effects: EffectRegistry, // the available effects
+    frames: watch::Receiver<Universe>, // the most recent frame of the output
+    captures: broadcast::Sender<CapturedFrame>, // the line to share written frames
+    next_fade_id: Arc<AtomicU64>, // the identifier of the next fade
+    clock: Arc<dyn Clock>, // the source of the time of the fades
+}
+
+// Implement key functionality for the DMX structure
+impl DmxController {
+    /// A function to create a new DMX controller that writes through the
+    /// driver, with the built-in effects. This function must be called
+    /// within a tokio runtime.
+    ///
+    /// The resolution sets the interval between fade updates. Updates are
+    /// scheduled against fixed deadlines, so a slow frame does not push back
+    /// the timing of every frame after it. Completed fades and changes in the
+    /// availability of the hardware are announced on the event line.
+    ///
+    pub fn new<D: DmxDriver>(driver: D, resolution: Duration, events: EventSend) -> Self {
+        Self::with_effects(driver, EffectRegistry::builtin(), resolution, events)
+    }
+
+    /// A function to create a new DMX controller that writes through the
+    /// driver, with the effects in the registry. This function must be
+    /// called within a tokio runtime.
+    ///
+    pub fn with_effects<D: DmxDriver>(
+        driver: D,
+        effects: EffectRegistry,
+        resolution: Duration,
+        events: EventSend,
+    ) -> Self {
+        Self::with_clock(driver, effects, resolution, events, Arc::new(SystemClock))
+    }
+
+    /// A function to create a new DMX controller that reads the time from
+    /// the clock, rather than the system. With a manual clock and the step
+    /// method, every frame is calculated at an exact time. This function
+    /// must be called within a tokio runtime.
+    ///
+    pub fn with_clock<D: DmxDriver>(
+        driver: D,
+        effects: EffectRegistry,
+        resolution: Duration,
+        events: EventSend,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        // Create a new DMX queue
+        let name = driver.name();
+        let (line, receive_fade) = mpsc::channel(128);
+        let (send_frame, frames) = watch::channel(Universe::new());
+        let (captures, _) = broadcast::channel(CAPTURE_BUFFER);
+        let mut dmx_queue = Queue::new(
+            driver,
+            EffectEngine::new(effects.clone()),
+            receive_fade,
+            send_frame,
+            captures.clone(),
+            resolution,
+            events,
+        );
+
+        // Read the time of the queue from the clock
+        dmx_queue.clock = clock.clone();
+        let snapshot = dmx_queue.send_snapshot.subscribe();
+
+        // Start the dmx queue thread
+        tokio::spawn(async move {
+            dmx_queue.run_loop().await;
+        });
+
+        // Return the new controller
+        Self {
+            load_fade: QueueLine {
+                line,
+                sent: Arc::new(AtomicU64::new(0)),
+            },
+            snapshot,
+            driver: name,
+            effects,
+            frames,
+            captures,
+            next_fade_id: Arc::new(AtomicU64::new(1)),
+            clock,
+        }
+    }
+
+    /// A function to create a new DMX controller for Enttec DMX USB
+    /// Pro-compatible hardware at the serial port
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the serial port cannot be
+    /// opened.
+    ///
+    pub fn open(path: &Path, resolution: Duration, events: EventSend) -> Result<Self> {
+        Ok(Self::new(EnttecUsbPro::open(path)?, resolution, events))
+    }
+
+    /// A method to return the type of the DMX hardware
+    ///
+    pub fn driver(&self) -> &'static str {
+        self.driver
+    }
+
+    /// A method to list the names of the available effects
+    ///
+    pub fn effects(&self) -> Vec<String> {
+        self.effects.names()
+    }
+
+    /// A method to follow the frames of the output, as they are composed.
+    /// Only changes to the frame are announced.
+    ///
+    pub fn frames(&self) -> watch::Receiver<Universe> {
+        self.frames.clone()
+    }
+
+    /// A method to capture every frame as it is written to the hardware
+    /// (including repeated frames and failed writes), starting from the
+    /// next write. Frames are only captured while a receiver exists.
+    ///
+    pub fn captures(&self) -> broadcast::Receiver<CapturedFrame> {
+        self.captures.subscribe()
+    }
+
+    /// A method to list the names of the available pixel generators
+    ///
+    pub fn pixel_generators(&self) -> Vec<String> {
+        self.effects.generator_names()
+    }
+
+    /// A method to start an effect on some channels, replacing any fades or
+    /// effects on those channels
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the effect does not exist or a
+    /// channel is outside the universe.
+    ///
+    pub async fn play_effect(&self, effect: EffectPlay) -> Result<()> {
+        // Verify the effect and the range of every channel
+        if self.effects.get(&effect.effect).is_none() {
+            return Err(ErrorCode::EffectNotFound {
+                effect: effect.effect,
+            }
+            .into());
+        }
+        if let Some(channel) = effect
+            .channels
+            .iter()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the effect to the background thread
+        self.load_fade
+            .send(QueueUpdate::PlayEffect(effect, Span::current()))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to start a movement on the axes of some fixtures, replacing
+    /// any fades or effects on those channels
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn play_movement(&self, movement: Movement) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = movement
+            .channels()
+            .find(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel }.into());
+        }
+
+        // Send the movement to the background thread
+        self.load_fade
+            .send(QueueUpdate::PlayMovement(movement, Span::current()))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to start a pixel generator on a pixel map, replacing any
+    /// fades or effects on those channels
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the generator does not exist or
+    /// the map is outside the universe.
+    ///
+    pub async fn play_pixels(&self, play: PixelPlay, map: PixelMap) -> Result<()> {
+        // Verify the generator and the range of the map
+        if self.effects.generator(&play.generator).is_none() {
+            return Err(ErrorCode::EffectNotFound {
+                effect: play.generator,
+            }
+            .into());
+        }
+        if map.width == 0 || map.height == 0 || map.start < 1 {
+            return Err(ErrorCode::ChannelOutOfRange { channel: map.start }.into());
+        }
+        if map.last_channel() > DMX_MAX {
+            return Err(ErrorCode::ChannelOutOfRange {
+                channel: map.last_channel(),
+            }
+            .into());
+        }
+
+        // Send the generator to the background thread
+        self.load_fade
+            .send(QueueUpdate::PlayPixels(play, map, Span::current()))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to stop the effects on the channels (or every effect, if
+    /// there are no channels). The channels hold their current values.
+    ///
+    pub async fn stop_effect(&self, channels: Vec<u32>) {
+        self.load_fade
+            .send(QueueUpdate::StopEffect(channels))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to change the speed of the effects that are not locked to
+    /// the beat, without a jump in any effect
+    ///
+    pub async fn set_effect_speed(&self, speed: EffectSpeed) {
+        self.load_fade
+            .send(QueueUpdate::SetEffectSpeed(speed, self.clock.now()))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to change the intensity or the chance of the effects on the
+    /// channels (or every effect, if there are no channels)
+    ///
+    pub async fn tune_effect(&self, tune: EffectTune) {
+        self.load_fade
+            .send(QueueUpdate::TuneEffect(tune))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to pause the fades on the channels (or every fade, if there
+    /// are no channels). The channels hold their current values until the
+    /// fades resume.
+    ///
+    pub async fn pause_fades(&self, channels: Vec<u32>) {
+        self.load_fade
+            .send(QueueUpdate::PauseFades(channels, true))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to resume the paused fades on the channels (or every paused
+    /// fade, if there are no channels), from the values they were paused at
+    ///
+    pub async fn resume_fades(&self, channels: Vec<u32>) {
+        self.load_fade
+            .send(QueueUpdate::PauseFades(channels, false))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to set the level of a group of channels, in percent. The
+    /// level scales the output of each channel, without changing the values
+    /// of the universe.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn set_group_level(&self, name: &str, channels: Vec<u32>, level: u8) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = channels
+            .iter()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the level to the background thread
+        self.load_fade
+            .send(QueueUpdate::GroupLevel(name.to_string(), channels, level))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to black out (or release) a group of channels. Blacked out
+    /// channels are held at zero above every playback, but the values of the
+    /// universe are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn set_group_blackout(
+        &self,
+        name: &str,
+        channels: Vec<u32>,
+        is_blackout: bool,
+    ) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = channels
+            .iter()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the blackout to the background thread
+        self.load_fade
+            .send(QueueUpdate::GroupBlackout(
+                name.to_string(),
+                channels,
+                is_blackout,
+            ))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to replace the maximum rate of change of each channel, in
+    /// units per second. Like the groups, the limits only change the output,
+    /// not the values of the universe.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn set_fade_limits(&self, rates: FnvHashMap<u32, f64>) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = rates
+            .keys()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the limits to the background thread
+        self.load_fade
+            .send(QueueUpdate::FadeLimits(rates))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to replace the preheat value of each channel. Warmed channels
+    /// never drop below their preheat in the output, but the values of the
+    /// universe are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn set_preheat(&self, values: FnvHashMap<u32, u8>) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = values
+            .keys()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the preheat to the background thread
+        self.load_fade
+            .send(QueueUpdate::Preheat(values))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to change the opacity, mute, or solo of a layer. The layers
+    /// only change the output, not the values of the universe.
+    ///
+    pub async fn set_layer(&self, layer: Layer, update: LayerUpdate) {
+        self.load_fade
+            .send(QueueUpdate::SetLayer(layer, update))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to get the current state of every layer, from the lowest
+    /// priority to the highest
+    ///
+    pub async fn get_layers(&self) -> Result<Vec<LayerState>> {
+        // Request the layers from the background thread
+        let (reply_to, reply) = oneshot::channel();
+        self.load_fade
+            .send(QueueUpdate::GetLayers(reply_to))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Wait for the reply
+        Ok(reply.await.map_err(|_| ErrorCode::DeviceUnavailable)?)
+    }
+
+    /// A method to get the statistics of every channel, from the first
+    /// channel to the last
+    ///
+    pub async fn get_channel_stats(&self) -> Result<Vec<ChannelStats>> {
+        // Request the statistics from the background thread
+        let (reply_to, reply) = oneshot::channel();
+        self.load_fade
+            .send(QueueUpdate::GetChannelStats(reply_to))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Wait for the reply
+        Ok(reply.await.map_err(|_| ErrorCode::DeviceUnavailable)?)
+    }
+
+    /// A method to hold some channels above every playback until they are
+    /// released
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub async fn set_override(&self, values: Override) -> Result<()> {
+        // Verify the range of every channel
+        if let Some(channel) = values
+            .values
+            .keys()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Send the overrides to the background thread
+        self.load_fade
+            .send(QueueUpdate::SetOverride(values))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to release the overrides of the channels (or every channel,
+    /// if there are no channels), fading each back to the layers below over
+    /// the duration
+    ///
+    pub async fn release_override(&self, channels: Vec<u32>, duration: Duration) {
+        self.load_fade
+            .send(QueueUpdate::ReleaseOverride(channels, duration))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to tap the beat of the beat clock, returning the new tempo
+    /// in beats per minute
+    ///
+    pub async fn tap(&self) -> Result<f64> {
+        // Send the time of the tap to the background thread
+        let (reply_to, reply) = oneshot::channel();
+        self.load_fade
+            .send(QueueUpdate::Tap(self.clock.now(), reply_to))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Wait for the reply
+        Ok(reply.await.map_err(|_| ErrorCode::DeviceUnavailable)?)
+    }
+
+    /// A method to change the tempo of the beat clock, in beats per minute,
+    /// starting a new beat
+    ///
+    pub async fn set_tempo(&self, bpm: f64) {
+        self.load_fade
+            .send(QueueUpdate::SetTempo(bpm, self.clock.now()))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to get the tempo of the beat clock, in beats per minute, and
+    /// the number of beats since the clock started counting
+    ///
+    pub async fn get_tempo(&self) -> Result<(f64, f64)> {
+        // Request the tempo from the background thread
+        let (reply_to, reply) = oneshot::channel();
+        self.load_fade
+            .send(QueueUpdate::GetTempo(reply_to))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Wait for the reply
+        Ok(reply.await.map_err(|_| ErrorCode::DeviceUnavailable)?)
+    }
+
+    /// A method to play a new Dmx fade, returning the identifier of the
+    /// fade. When the fade reaches its value (or right away, without a
+    /// duration), a fadeFinished event is announced with the identifier. If
+    /// anything replaces the fade first, a fadeCancelled event is announced
+    /// instead.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the channel is outside the
+    /// universe.
+    ///
+    pub async fn play_fade(&self, fade: Fade) -> Result<u64> {
+        // Verify the range of the selected channel
+        if (fade.channel > DMX_MAX) | (fade.channel < 1) {
+            return Err(ErrorCode::ChannelOutOfRange {
+                channel: fade.channel,
+            }
+            .into());
+        }
+
+        // Send the fade to the background thread
+        let id = self.next_fade_id.fetch_add(1, Ordering::Relaxed);
+        if self
+            .load_fade
+            .send(QueueUpdate::Fade(fade, Some(id), Span::current()))
+            .await
+            .is_err()
+        {
+            error!("Background DMX thread has crashed.");
+            return Err(ErrorCode::DeviceUnavailable.into());
+        }
+
+        // If the fade was processed correctly, return the identifier
+        Ok(id)
+    }
+
+    /// A method to reserve the identifier of a fade that is never played
+    /// (such as one replaced by a later fade before it started), so that
+    /// every fade request still has its own identifier
+    ///
+    pub fn reserve_fade_id(&self) -> u64 {
+        self.next_fade_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// A method to play a new fade of a 16-bit value across a coarse and a
+    /// fine channel
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if either channel is outside the
+    /// universe.
+    ///
+    pub async fn play_wide_fade(&self, fade: WideFade) -> Result<()> {
+        // Verify the range of the selected channels
+        if let Some(channel) = Some(fade.channel)
+            .into_iter()
+            .chain(fade.fine_channel)
+            .find(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel }.into());
+        }
+
+        // Send the fade to the background thread
+        self.load_fade
+            .send(QueueUpdate::WideFade(fade, Span::current()))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+        Ok(())
+    }
+
+    /// A method to load all values for an entire the DMX universe
+    ///
+    pub async fn set_universe(&self, universe: Universe) {
+        self.fade_universe(&universe, None).await;
+    }
+
+    /// A method to load the values of some channels right away, leaving the
+    /// other channels as they are
+    ///
+    pub async fn set_channels(&self, values: Vec<(u32, u8)>) {
+        for (channel, value) in values {
+            self.load_fade
+                .send(QueueUpdate::Fade(
+                    Fade {
+                        channel,
+                        value,
+                        duration: None,
+                    },
+                    None,
+                    Span::current(),
+                ))
+                .await
+                .unwrap_or(()); // fail silently
+        }
+    }
+
+    /// A method to fade all values of the DMX universe to the provided
+    /// universe over the provided duration
+    ///
+    pub async fn fade_universe(&self, universe: &Universe, duration: Option<Duration>) {
+        // For each channel, send a fade with the duration
+        for channel in 1..=DMX_MAX {
+            self.load_fade
+                .send(QueueUpdate::Fade(
+                    Fade {
+                        channel,
+                        value: universe.get(channel),
+                        duration,
+                    },
+                    None,
+                    Span::current(),
+                ))
+                .await
+                .unwrap_or(()); // fail silently
+        }
+    }
+
+    /// A method to get the current value of all the channels. The universe
+    /// is read from the latest snapshot of the queue (without waiting for
+    /// the queue to write a frame), once the queue has processed every
+    /// update sent before this call.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the background queue has stopped.
+    ///
+    pub async fn get_universe(&self) -> Result<Universe> {
+        // Wait for a snapshot that includes every update sent so far
+        let sent = self.load_fade.sent.load(Ordering::SeqCst);
+        let mut snapshot = self.snapshot.clone();
+        let snapshot = snapshot
+            .wait_for(|snapshot| snapshot.processed >= sent)
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Return the universe (sharing the values of the snapshot)
+        Ok(snapshot.universe.clone())
+    }
+
+    /// A method to change the time resolution of the fades
+    ///
+    pub async fn set_resolution(&self, resolution: Duration) {
+        self.load_fade
+            .send(QueueUpdate::Resolution(resolution))
+            .await
+            .unwrap_or(()); // fail silently
+    }
+
+    /// A method to get the current state of the background DMX thread.
+    /// Returns None if the thread does not reply within the timeout.
+    ///
+    pub async fn get_status(&self, limit: Duration) -> Option<QueueStatus> {
+        // Request the status from the background thread
+        let (reply_to, reply) = oneshot::channel();
+        let status = async {
+            self.load_fade
+                .send(QueueUpdate::GetStatus(reply_to))
+                .await
+                .ok()?;
+            reply.await.ok()
+        };
+
+        // Return the status if it arrived in time
+        timeout(limit, status).await.ok().flatten()
+    }
+
+    /// A method to update every fade and effect at the current time of the
+    /// clock and write the frame, without waiting for the next deadline.
+    /// Returns once the frame is written.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the background queue has stopped.
+    ///
+    pub async fn step(&self) -> Result<()> {
+        // Ask the background thread to update the fades
+        let (reply_to, reply) = oneshot::channel();
+        self.load_fade
+            .send(QueueUpdate::Step(reply_to))
+            .await
+            .map_err(|_| ErrorCode::DeviceUnavailable)?;
+
+        // Wait for the reply
+        Ok(reply.await.map_err(|_| ErrorCode::DeviceUnavailable)?)
+    }
+
+    /// A method to check that the background DMX thread is still processing
+    /// updates. Returns false if the thread does not reply within the timeout.
+    ///
+    pub async fn is_responsive(&self, limit: Duration) -> bool {
+        // Send a ping to the background thread and wait for the reply
+        let (reply_to, reply) = oneshot::channel();
+        let ping = async {
+            self.load_fade
+                .send(QueueUpdate::Ping(reply_to))
+                .await
+                .ok()?;
+            reply.await.ok()
+        };
+
+        // Indicate whether the reply arrived in time
+        matches!(timeout(limit, ping).await, Ok(Some(())))
+    }
+}
+
+/// An enum to carry updates to the background dmx queue
+///
+enum QueueUpdate {
+    /// a variant to play a new fade with its identifier (if any), within the
+    /// span of the original request
+    Fade(Fade, Option<u64>, Span),
+
+    /// a variant to play a new fade of a 16-bit value, within the span of
+    /// the original request
+    WideFade(WideFade, Span),
+
+    /// a variant to start an effect, within the span of the original request
+    PlayEffect(EffectPlay, Span),
+
+    /// a variant to start a movement, within the span of the original request
+    PlayMovement(Movement, Span),
+
+    /// a variant to start a pixel generator on a pixel map, within the span
+    /// of the original request
+    PlayPixels(PixelPlay, PixelMap, Span),
+
+    /// a variant to stop the effects on some channels (or every effect)
+    StopEffect(Vec<u32>),
+
+    /// a variant to tune the effects on some channels (or every effect)
+    TuneEffect(EffectTune),
+
+    /// a variant to change the speed of the effects, at the given time
+    SetEffectSpeed(EffectSpeed, Instant),
+
+    /// a variant to pause (or resume) the fades on some channels (or every
+    /// fade)
+    PauseFades(Vec<u32>, bool),
+
+    /// a variant to change the level of a group of channels
+    GroupLevel(String, Vec<u32>, u8),
+
+    /// a variant to black out (or release) a group of channels
+    GroupBlackout(String, Vec<u32>, bool),
+
+    /// a variant to replace the maximum rate of change of each channel
+    FadeLimits(FnvHashMap<u32, f64>),
+
+    /// a variant to replace the preheat value of each channel
+    Preheat(FnvHashMap<u32, u8>),
+
+    /// a variant to change the state of a layer
+    SetLayer(Layer, LayerUpdate),
+
+    /// a variant to hold some channels above every playback
+    SetOverride(Override),
+
+    /// a variant to release the overrides of some channels (or every channel)
+    ReleaseOverride(Vec<u32>, Duration),
+
+    /// a variant to tap the beat at the given time
+    Tap(Instant, oneshot::Sender<f64>),
+
+    /// a variant to change the tempo, with a beat at the given time
+    SetTempo(f64, Instant),
+
+    /// a variant to get the tempo and the number of beats
+    GetTempo(oneshot::Sender<(f64, f64)>),
+
+    /// a variant to get the current state of every layer
+    GetLayers(oneshot::Sender<Vec<LayerState>>),
+
+    /// a variant to get the statistics of every channel
+    GetChannelStats(oneshot::Sender<Vec<ChannelStats>>),
+
+    /// a variant to change the time resolution of the fades
+    Resolution(Duration),
+
+    /// a variant to get the current state of the queue
+    GetStatus(oneshot::Sender<QueueStatus>),
+
+    /// a variant to confirm that the queue is still responsive
+    Ping(oneshot::Sender<()>),
+
+    /// a variant to update the fades and write the frame right away
+    Step(oneshot::Sender<()>),
+}
+
+/// A structure to send updates to the background dmx queue, counting every
+/// update so that a read can wait for the updates sent before it
+///
+#[derive(Clone)]
+struct QueueLine {
+    line: mpsc::Sender<QueueUpdate>, // the line to the queue
+    sent: Arc<AtomicU64>,            // the number of updates sent to the queue
+}
+
+// Implement key features of the queue line
+impl QueueLine {
+    /// A method to send an update to the queue
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the queue has stopped.
+    ///
+    async fn send(&self, update: QueueUpdate) -> Result<(), mpsc::error::SendError<QueueUpdate>> {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        self.line.send(update).await
+    }
+}
+
+/// A struct to share the latest universe requested of the queue, with the
+/// number of updates the queue has processed
+///
+#[derive(Clone, Default)]
+struct Snapshot {
+    processed: u64,     // the number of updates processed when the snapshot was taken
+    universe: Universe, // the universe requested of the queue (before the groups and the limits)
+}
+
+/// A struct to report the current state of the background dmx queue
+///
+pub struct QueueStatus {
+    pub active_fades: usize,   // the number of fades in progress
+    pub active_effects: usize, // the number of effects in progress
+    pub is_writing: bool,      // a flag to indicate the last frame was written to the hardware
+    pub timings: FrameTimings, // the timing of the frames since startup
+    pub writes: WriteStatus,   // the frames that could not be written
+}
+
+/// A struct to hold a queue of future dmx changes. This struct should be opened
+/// in a separate thread. This version preserves the proper order of the dmx
+/// changes.
+///
+struct Queue<D: DmxDriver> {
+    driver: D,                                      // the driver for the DMX hardware
+    engine: FadeEngine,                             // the base layer and the fades in progress
+    effects: EffectEngine,                          // the effects in progress
+    effect_layer: Universe,                         // the values of the channels with effects
+    overrides: Overrides,                           // the manual overrides above every playback
+    compositor: Compositor,                         // the state of each layer of the output
+    submasters: Submasters,                         // the levels of the groups of channels
+    preheat: Preheat,                               // the lowest output of each warmed channel
+    blackouts: GroupBlackouts,                      // the groups of channels held at zero
+    fade_limits: FadeLimits,                        // the maximum rate of change of each channel
+    counters: ChannelCounters,                      // the changes of each channel that was written
+    queue_receive: mpsc::Receiver<QueueUpdate>, // the queue receiving line that sends additional updates to the daemon
+    send_frame: watch::Sender<Universe>,        // the line to share each new frame of the output
+    send_capture: broadcast::Sender<CapturedFrame>, // the line to share every written frame
+    send_snapshot: watch::Sender<Snapshot>,     // the line to share the latest requested universe
+    is_write_waiting: bool, // a flag to indicate that a write is still waiting to be sent
+    resolution: Duration,   // the time resolution of each fade
+    ticker: Interval,       // the deadline timer for fade updates
+    last_deadline: Option<TimerInstant>, // the deadline of the previous update, while the updates are running
+    timings: FrameTimings,               // the timing of the frames since startup
+    is_available: bool,                  // a flag to indicate that the hardware is accepting frames
+    failing_since: Option<Instant>, // the time of the first failed write since the last success, if any
+    writes: WriteStatus,            // the frames that could not be written
+    events: EventSend,              // the line to announce events
+    clock: Arc<dyn Clock>,          // the source of the time of the fades
+}
+
+// Implement the Dmx Queue methods
+impl<D: DmxDriver> Queue<D> {
+    /// A function to create a new dmx queue.
+    ///
+    /// This function returns a new dmx queue which will send segments of a fade
+    /// (at the provided time resolution) to the driver.
+    ///
+    fn new(
+        driver: D,
+        effects: EffectEngine,
+        queue_receive: mpsc::Receiver<QueueUpdate>,
+        send_frame: watch::Sender<Universe>,
+        send_capture: broadcast::Sender<CapturedFrame>,
+        resolution: Duration,
+        events: EventSend,
+    ) -> Self {
+        // Return the newly constructed dmx queue (with the system clock)
+        Queue {
+            driver,
+            engine: FadeEngine::new(),
+            effects,
+            effect_layer: Universe::new(),
+            overrides: Overrides::new(),
+            compositor: Compositor::new(),
+            submasters: Submasters::new(),
+            preheat: Preheat::new(),
+            blackouts: GroupBlackouts::new(),
+            fade_limits: FadeLimits::new(),
+            counters: ChannelCounters::new(),
+            queue_receive,
+            send_frame,
+            send_capture,
+            send_snapshot: watch::Sender::new(Snapshot::default()),
+            is_write_waiting: false,
+            resolution,
+            ticker: Self::new_ticker(resolution),
+            last_deadline: None,
+            timings: FrameTimings::default(),
+            is_available: true,
+            failing_since: None,
+            writes: WriteStatus::default(),
+            events,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// A helper function to create the fade timer, skipping any ticks that
+    /// were missed so that the timer stays locked to the original deadlines
+    ///
+    fn new_ticker(resolution: Duration) -> Interval {
+        let mut ticker = interval(resolution);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        ticker
+    }
+
+    /// An internal function to run the queue in an infinite loop. This function
+    /// should be launched as a new background thread for the queue.
+    ///
+    async fn run_loop(&mut self) {
+        // Run the background process indefinitely
+        loop {
+            // Check to see if there are changes in the queue or a write waiting
+            if self.engine.active_fades() > 0
+                || self.effects.active_effects() > 0
+                || self.overrides.is_releasing()
+                || self.fade_limits.is_limiting()
+                || self.is_write_waiting
+            {
+                // Look for a new fade message or the next deadline
+                tokio::select! {
+                    // If a message was recieved, process the update
+                    Some(update) = self.queue_receive.recv() => self.process_update(update).await,
+
+                    // Otherwise, update the fades at the next deadline
+                    deadline = self.ticker.tick() => self.update_deadline(deadline).await,
+                }
+
+            // Otherwise just wait for new message indefinitely
+            } else {
+                // Process a message when received
+                if let Some(update) = self.queue_receive.recv().await {
+                    self.process_update(update).await;
+
+                    // Restart the deadlines from the beginning of the fade
+                    self.ticker.reset_immediately();
+                    self.last_deadline = None;
+                }
+            }
+        }
+    }
+
+    /// A helper function to update the fades at a deadline of the timer,
+    /// recording the timing of the frame against the deadline
+    ///
+    async fn update_deadline(&mut self, deadline: TimerInstant) {
+        // Update the fades and write the frame
+        self.update_fades().await;
+
+        // Count any deadlines skipped since the previous update, and the
+        // time until the frame was written
+        let skipped = self.last_deadline.map_or(0, |last| {
+            let ticks =
+                deadline.duration_since(last).as_nanos() / self.resolution.as_nanos().max(1);
+            (ticks as u64).saturating_sub(1)
+        });
+        self.last_deadline = Some(deadline);
+        self.timings
+            .record(skipped, deadline.elapsed(), self.resolution);
+    }
+
+    /// A helper function to update the current value of every fade and write
+    /// the resulting frame
+    ///
+    async fn update_fades(&mut self) {
+        // Update the current status for every fade
+        let now = self.clock.now();
+        let complete = self.engine.update(now);
+
+        // Announce any completed fades
+        if !complete.is_empty() {
+            self.events.send(Event::FadeComplete { channels: complete });
+        }
+        self.announce_ended();
+
+        // Update the channels with effects
+        self.effects.update(now, &mut self.effect_layer);
+
+        // Share the new universe and write the changed values
+        self.share_snapshot(false);
+        self.write_frame().await;
+    }
+
+    /// A helper function to process new queue updates
+    ///
+    async fn process_update(&mut self, update: QueueUpdate) {
+        match update {
+            // Process a new fade
+            QueueUpdate::Fade(fade, id, span) => {
+                self.process_fade(fade.into(), id).instrument(span).await
+            }
+            QueueUpdate::WideFade(fade, span) => {
+                self.process_fade(fade, None).instrument(span).await
+            }
+
+            // Start a new effect, replacing any fades on the channels
+            QueueUpdate::PlayEffect(effect, span) => {
+                let _entered = span.enter();
+                for channel in effect.channels.iter() {
+                    self.engine.cancel(*channel);
+                }
+                let now = self.clock.now();
+                self.hold_effects(|effects| {
+                    if let Err(error) = effects.play(&effect, now) {
+                        warn!("Dropped effect: {}.", error);
+                    }
+                });
+            }
+
+            // Start a new movement, replacing any fades on the channels
+            QueueUpdate::PlayMovement(movement, span) => {
+                let _entered = span.enter();
+                for channel in movement.channels().collect::<Vec<u32>>() {
+                    self.engine.cancel(channel);
+                }
+                let now = self.clock.now();
+                self.hold_effects(|effects| {
+                    if let Err(error) = effects.play_movement(movement, now) {
+                        warn!("Dropped movement: {}.", error);
+                    }
+                });
+            }
+
+            // Start a new pixel generator, replacing any fades on the channels
+            QueueUpdate::PlayPixels(play, map, span) => {
+                let _entered = span.enter();
+                for channel in map.channels() {
+                    self.engine.cancel(channel);
+                }
+                let now = self.clock.now();
+                self.hold_effects(|effects| {
+                    if let Err(error) = effects.play_pixels(&play, map, now) {
+                        warn!("Dropped pixel generator: {}.", error);
+                    }
+                });
+            }
+
+            // Stop the effects
+            QueueUpdate::StopEffect(channels) => {
+                self.hold_effects(|effects| effects.stop(&channels))
+            }
+
+            // Tune the effects
+            QueueUpdate::TuneEffect(tune) => self.effects.tune(&tune),
+
+            // Change the speed of the effects
+            QueueUpdate::SetEffectSpeed(speed, now) => self.effects.set_speed(speed, now),
+
+            // Pause or resume the fades
+            QueueUpdate::PauseFades(channels, is_paused) => {
+                let now = self.clock.now();
+                if is_paused {
+                    self.engine.pause(&channels, now);
+                } else {
+                    self.engine.resume(&channels, now);
+                }
+            }
+
+            // Hold the channels and write the new output
+            QueueUpdate::SetOverride(values) => {
+                for (channel, value) in values.values {
+                    self.overrides.set(channel, value);
+                }
+                self.write_frame().await;
+            }
+
+            // Start the release of the channels
+            QueueUpdate::ReleaseOverride(channels, duration) => {
+                self.overrides
+                    .release(&channels, duration, self.clock.now());
+                self.write_frame().await;
+            }
+
+            // Change the layer and write the new output
+            QueueUpdate::SetLayer(layer, update) => {
+                self.compositor.update(layer, &update);
+                self.write_frame().await;
+            }
+
+            // Reply with the state of every layer
+            QueueUpdate::GetLayers(reply_to) => reply_to
+                .send(self.compositor.layers().to_vec())
+                .unwrap_or(()),
+
+            // Reply with the statistics of every channel
+            QueueUpdate::GetChannelStats(reply_to) => {
+                let effects = self.effects.channels();
+                let stats = (1..=DMX_MAX)
+                    .map(|channel| {
+                        let source = self.source(channel, &effects);
+                        self.counters.stats(channel, source)
+                    })
+                    .collect();
+                reply_to.send(stats).unwrap_or(());
+            }
+
+            // Change the level of the group and write the new output
+            QueueUpdate::GroupLevel(name, channels, level) => {
+                self.submasters.set_level(&name, channels, level);
+                self.write_frame().await;
+            }
+
+            // Black out (or release) a group of channels
+            QueueUpdate::GroupBlackout(name, channels, is_blackout) => {
+                self.blackouts.set_blackout(&name, channels, is_blackout);
+                self.write_frame().await;
+            }
+
+            // Replace the maximum rate of each channel
+            QueueUpdate::FadeLimits(rates) => {
+                self.fade_limits.set_rates(rates);
+                self.write_frame().await;
+            }
+
+            // Replace the preheat of each channel
+            QueueUpdate::Preheat(values) => {
+                self.preheat.set_values(values);
+                self.write_frame().await;
+            }
+
+            // Tap the beat and reply with the new tempo
+            QueueUpdate::Tap(now, reply_to) => reply_to
+                .send(self.effects.clock_mut().tap(now))
+                .unwrap_or(()),
+
+            // Change the tempo
+            QueueUpdate::SetTempo(bpm, now) => self.effects.clock_mut().set_bpm(bpm, now),
+
+            // Reply with the tempo and the number of beats
+            QueueUpdate::GetTempo(reply_to) => {
+                let clock = self.effects.clock();
+                reply_to
+                    .send((clock.bpm(), clock.beats(self.clock.now())))
+                    .unwrap_or(())
+            }
+
+            // Replace the fade timer
+            QueueUpdate::Resolution(resolution) => {
+                self.resolution = resolution;
+                self.ticker = Self::new_ticker(resolution);
+                self.last_deadline = None;
+            }
+
+            // Reply with the current state of the queue
+            QueueUpdate::GetStatus(reply_to) => reply_to
+                .send(QueueStatus {
+                    active_fades: self.engine.active_fades(),
+                    active_effects: self.effects.active_effects(),
+                    is_writing: !self.is_write_waiting,
+                    timings: self.timings,
+                    writes: WriteStatus {
+                        failing_for: self.failing_since.map(|since| {
+                            self.clock
+                                .now()
+                                .saturating_duration_since(since)
+                                .as_millis() as u64
+                        }),
+                        ..self.writes.clone()
+                    },
+                })
+                .unwrap_or(()),
+
+            // Reply to a ping
+            QueueUpdate::Ping(reply_to) => reply_to.send(()).unwrap_or(()),
+
+            // Update the fades and reply once the frame is written
+            QueueUpdate::Step(reply_to) => {
+                self.update_fades().await;
+                reply_to.send(()).unwrap_or(());
+            }
+        }
+
+        // Announce any fades that ended or were replaced, and share the
+        // universe
+        self.announce_ended();
+        self.share_snapshot(true);
+    }
+
+    /// A helper function to share the universe requested of the queue, if
+    /// it changed or an update was processed
+    ///
+    fn share_snapshot(&mut self, is_processed: bool) {
+        let universe = self.requested_universe();
+        self.send_snapshot.send_if_modified(|snapshot| {
+            let is_changed = snapshot.universe != universe;
+            if is_changed {
+                snapshot.universe = universe;
+            }
+            if is_processed {
+                snapshot.processed += 1;
+            }
+            is_changed || is_processed
+        });
+    }
+
+    /// A helper function to process new dmx fade messages
+    ///
+    async fn process_fade(&mut self, fade: WideFade, id: Option<u64>) {
+        // Release the channels from any effect
+        self.hold_effects(|effects| {
+            effects.release(fade.channel);
+            if let Some(fine_channel) = fade.fine_channel {
+                effects.release(fine_channel);
+            }
+        });
+
+        // Start the fade, and make any immediate change right away
+        match self.engine.play_tracked_fade(fade, id, self.clock.now()) {
+            Ok(true) => self.write_frame().await,
+            Ok(false) => (),
+            Err(error) => warn!("Dropped fade: {}.", error),
+        }
+    }
+
+    /// A helper function to announce the end of every fade with an
+    /// identifier since the last announcement
+    ///
+    fn announce_ended(&mut self) {
+        for end in self.engine.take_ended() {
+            self.events.send(if end.is_cancelled {
+                Event::FadeCancelled {
+                    id: end.id,
+                    channel: end.channel,
+                }
+            } else {
+                Event::FadeFinished {
+                    id: end.id,
+                    channel: end.channel,
+                }
+            });
+        }
+    }
+
+    /// A helper function to change the effects, moving any channel that
+    /// leaves the effects back to the base layer at its current value
+    ///
+    fn hold_effects<F>(&mut self, change: F)
+    where
+        F: FnOnce(&mut EffectEngine),
+    {
+        // Note the channels with effects before and after the change
+        let before = self.effects.channels();
+        change(&mut self.effects);
+        let after = self.effects.channels();
+
+        // Hold the value of any released channel
+        for channel in before
+            .into_iter()
+            .filter(|channel| !after.contains(channel))
+        {
+            self.engine
+                .universe_mut()
+                .set(channel, self.effect_layer.get(channel));
+        }
+
+        // Bring the effects layer up to date
+        self.effects
+            .update(self.clock.now(), &mut self.effect_layer);
+    }
+
+    /// A helper function to return the values of every channel, as
+    /// requested, with the effects over the base layer and the held
+    /// overrides over both
+    ///
+    fn requested_universe(&self) -> Universe {
+        let mut universe = self.engine.universe().clone();
+        for channel in self.effects.channels() {
+            universe.set(channel, self.effect_layer.get(channel));
+        }
+        for channel in self.overrides.held() {
+            universe.set(channel, self.overrides.values().get(channel));
+        }
+        universe
+    }
+
+    /// A helper function to compose the layers into the existing frame and
+    /// write it to the driver, scaled by the levels of the groups
+    ///
+    async fn write_frame(&mut self) {
+        // Compose the layers
+        let effects: Vec<(u32, f64)> = self
+            .effects
+            .channels()
+            .into_iter()
+            .map(|channel| (channel, 1.0))
+            .collect();
+        let overrides = self.overrides.update(self.clock.now());
+        let composed = self.compositor.compose(&[
+            (Layer::Base, self.engine.universe(), None),
+            (Layer::Effects, &self.effect_layer, Some(&effects)),
+            (Layer::Override, self.overrides.values(), Some(&overrides)),
+        ]);
+
+        // Scale the groups, warm the channels, black out any groups, and limit
+        // the rate of change
+        let scaled = self.submasters.apply(&composed);
+        let warmed = self.preheat.apply(&scaled);
+        let blacked_out = self.blackouts.apply(&warmed);
+        let frame = self.fade_limits.apply(&blacked_out, self.clock.now());
+
+        // Share the frame if it changed
+        self.send_frame.send_if_modified(|last| {
+            if *last == *frame {
+                return false;
+            }
+            *last = frame.clone().into_owned();
+            true
+        });
+
+        // Try to write the frame, waiting at most one resolution (and
+        // abandoning a driver that does not return soon after)
+        let result = timeout(
+            self.resolution + WRITE_GRACE,
+            self.driver.write_frame(&frame, self.resolution),
+        )
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("The driver did not return in time.")));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_micros() as u64)
+            .unwrap_or(0);
+        self.capture(&frame, timestamp, result.is_ok());
+        match result {
+            // Mark the write as complete
+            Ok(()) => {
+                self.is_write_waiting = false;
+                self.writes.consecutive_failures = 0;
+                self.counters.record(&frame, timestamp / 1000);
+                self.set_available(true);
+                return;
+            }
+
+            // Otherwise, mark the write as still waiting
+            Err(error) => {
+                trace!("Frame not written: {}.", error);
+                self.is_write_waiting = true;
+                self.writes.failures += 1;
+                self.writes.consecutive_failures += 1;
+                self.writes.last_error = Some(error.to_string());
+            }
+        }
+
+        // If writes have failed for too long, note that the hardware is unavailable
+        let now = self.clock.now();
+        let failing_since = *self.failing_since.get_or_insert(now);
+        if now.saturating_duration_since(failing_since) >= UNAVAILABLE_TIME {
+            self.set_available(false);
+        }
+    }
+
+    /// A helper function to find the highest layer that is controlling a
+    /// channel of the output, given the channels with effects
+    ///
+    fn source(&self, channel: u32, effects: &[u32]) -> ChannelSource {
+        if self.overrides.contains(channel) && self.compositor.is_heard(Layer::Override) {
+            ChannelSource::Override
+        } else if effects.contains(&channel) && self.compositor.is_heard(Layer::Effects) {
+            ChannelSource::Effect
+        } else if self.engine.is_fading(channel) {
+            ChannelSource::Fade
+        } else {
+            ChannelSource::Base
+        }
+    }
+
+    /// A helper function to share a written frame with any captures in
+    /// progress (timestamped in microseconds since the unix epoch)
+    ///
+    fn capture(&self, frame: &Universe, timestamp: u64, is_written: bool) {
+        // Skip the copy if nothing is capturing
+        if self.send_capture.receiver_count() == 0 {
+            return;
+        }
+        self.send_capture
+            .send(CapturedFrame {
+                timestamp,
+                is_written,
+                frame: frame.clone(),
+            })
+            .ok();
+    }
+
+    /// A helper function to announce any change in the availability of the
+    /// hardware
+    ///
+    fn set_available(&mut self, is_available: bool) {
+        // Clear any failed writes once a write succeeds
+        if is_available {
+            self.failing_since = None;
+        }
+
+        // Only announce if the availability changed
+        if is_available != self.is_available {
+            self.is_available = is_available;
+            if is_available {
+                info!("DMX hardware is available again.");
+                self.events.send(Event::DeviceAvailable);
+            } else {
+                let reason = self.writes.last_error.clone().unwrap_or_default();
+                warn!("DMX hardware is not accepting frames: {}", reason);
+                self.events.send(Event::DeviceUnavailable { reason });
+            }
+        }
+    }
+}
+
+// Tests of the DMX controller module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::driver::DryRunDriver;
+
+    // Test the fading of a single dmx channel
+    #[tokio::test]
+    async fn test_light() {
+        // Import standard library features
+        use std::thread;
+        use std::time::Duration;
+
+        // Create a DMX controller on USB0
+        let interface = DmxController::open(
+            Path::new("/dev/ttyUSB0"),
+            Duration::from_millis(50),
+            EventSend::new(),
+        )
+        .expect("Unable to connect to DMX on USB0.");
+
+        // Play a fade up on channel 1
+        interface
+            .play_fade(Fade {
+                channel: 1,
+                value: 255,
+                duration: Some(Duration::from_secs(3)),
+            })
+            .await
+            .unwrap();
+        thread::sleep(Duration::from_secs(5));
+
+        // Play a fade down on channel 1
+        interface
+            .play_fade(Fade {
+                channel: 1,
+                value: 0,
+                duration: Some(Duration::from_secs(3)),
+            })
+            .await
+            .unwrap();
+        thread::sleep(Duration::from_secs(5));
+    }
+
+    // Test that the output follows a manual clock exactly
+    #[tokio::test]
+    async fn step_manual_clock() {
+        // Create a controller that reads a manual clock
+        let clock = ManualClock::new(Instant::now());
+        let interface = DmxController::with_clock(
+            DryRunDriver,
+            EffectRegistry::builtin(),
+            Duration::from_millis(50),
+            EventSend::new(),
+            Arc::new(clock.clone()),
+        );
+        let frames = interface.frames();
+
+        // Fade up for one second, and check the frame at exact times
+        interface
+            .play_fade(Fade {
+                channel: 1,
+                value: 255,
+                duration: Some(Duration::from_secs(1)),
+            })
+            .await
+            .unwrap();
+        interface.step().await.unwrap();
+        assert_eq!(frames.borrow().get(1), 0);
+        clock.advance(Duration::from_millis(500));
+        interface.step().await.unwrap();
+        assert_eq!(frames.borrow().get(1), 128);
+        clock.advance(Duration::from_millis(500));
+        interface.step().await.unwrap();
+        assert_eq!(frames.borrow().get(1), 255);
+    }
+
+    // A driver that takes longer than a resolution to write each frame
+    struct SlowDriver;
+
+    // Implement the slow driver
+    impl DmxDriver for SlowDriver {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn write_frame(&mut self, _universe: &Universe, _limit: Duration) -> Result<()> {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            Ok(())
+        }
+    }
+
+    // Test that frames written after the next deadline are counted
+    #[tokio::test]
+    async fn count_late_frames() {
+        // Fade with a driver slower than the resolution
+        let interface = DmxController::new(SlowDriver, Duration::from_millis(10), EventSend::new());
+        interface
+            .play_fade(Fade {
+                channel: 1,
+                value: 255,
+                duration: Some(Duration::from_millis(300)),
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        // Every frame ran late, and the deadlines in between were skipped
+        let timings = interface
+            .get_status(Duration::from_secs(1))
+            .await
+            .unwrap()
+            .timings;
+        assert!(timings.frames > 0);
+        assert_eq!(timings.late_frames, timings.frames);
+        assert!(timings.ticks_missed >= timings.frames);
+        assert!(timings.longest_frame >= 25_000);
+    }
+
+    // A driver that stalls on every frame
+    struct StalledDriver;
+
+    // Implement the stalled driver
+    impl DmxDriver for StalledDriver {
+        fn name(&self) -> &'static str {
+            "stalled"
+        }
+
+        async fn write_frame(&mut self, _universe: &Universe, _limit: Duration) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    // Test that a stalled driver is abandoned, counted, and announced
+    #[tokio::test]
+    async fn surface_stalled_writes() {
+        // Fade with a driver that never returns
+        let events = EventSend::new();
+        let mut receive = events.subscribe();
+        let interface = DmxController::new(StalledDriver, Duration::from_millis(10), events);
+        interface
+            .play_fade(Fade {
+                channel: 1,
+                value: 255,
+                duration: None,
+            })
+            .await
+            .unwrap();
+
+        // The hardware is announced unavailable with the reason
+        let event = timeout(Duration::from_secs(3), async {
+            loop {
+                if let Ok(event @ Event::DeviceUnavailable { .. }) = receive.recv().await {
+                    return event;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            event,
+            Event::DeviceUnavailable {
+                reason: "The driver did not return in time.".into()
+            }
+        );
+
+        // And the status reports the failures
+        let writes = interface
+            .get_status(Duration::from_secs(1))
+            .await
+            .unwrap()
+            .writes;
+        assert!(writes.failures > 1);
+        assert_eq!(writes.consecutive_failures, writes.failures);
+        assert!(writes.failing_for.unwrap() >= 1000);
+    }
+
+    // Test that a read includes every update sent before it
+    #[tokio::test]
+    async fn read_after_write() {
+        let interface =
+            DmxController::new(DryRunDriver, Duration::from_millis(50), EventSend::new());
+        for value in 1..=20 {
+            interface.set_channels(vec![(3, value)]).await;
+            assert_eq!(interface.get_universe().await.unwrap().get(3), value);
+        }
+    }
+}
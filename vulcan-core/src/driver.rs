@@ -0,0 +1,208 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to define the drivers that write frames to the DMX hardware.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
+// Import the tokio and tokio serial features
+use tokio::time::timeout;
+use tokio_serial as serial;
+use tokio_serial::SerialPort;
+
+// Import tracing features
+use tracing::trace;
+
+// Import anyhow features
+use anyhow::Result;
+
+// Define the communication constants
+const COMMAND_START: u8 = 0x7E; // the start of the command
+const MESSAGE_LABEL: u8 = 0x06; // the message type label
+const DATA_LSB: u8 = 0x01; // the data least significant bit
+const DATA_MSB: u8 = 0x02; // the data most significant bit
+const DMX_START_CODE: u8 = 0x00; // the DMX start code
+const COMMAND_END: u8 = 0xE7; // the end of the command
+
+/// A trait for the DMX hardware. A driver writes complete frames; the DMX
+/// controller decides when to write them.
+///
+pub trait DmxDriver: Send + 'static {
+    /// A method to return the type of the hardware
+    ///
+    fn name(&self) -> &'static str;
+
+    /// A method to write the values of every channel to the hardware,
+    /// waiting at most the limit for the hardware to be ready
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the whole frame could not be
+    /// written in time. The controller tries again with the next frame.
+    ///
+    fn write_frame(
+        &mut self,
+        universe: &Universe,
+        limit: Duration,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// A driver for Enttec DMX USB Pro-compatible hardware
+///
+pub struct EnttecUsbPro {
+    stream: serial::SerialStream, // the serial port connection
+}
+
+// Implement key features of the Enttec driver
+impl EnttecUsbPro {
+    /// The type of the hardware
+    pub const NAME: &'static str = "enttec-usb-pro";
+
+    /// A function to connect to the hardware at the serial port
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the serial port cannot be
+    /// opened.
+    ///
+    pub fn open(path: &Path) -> Result<Self> {
+        // Create and configure a builder to connect to the underlying serial port
+        let builder = serial::new(path.to_str().unwrap_or(""), 9600)
+            .data_bits(serial::DataBits::Eight)
+            .parity(serial::Parity::None)
+            .stop_bits(serial::StopBits::One)
+            .flow_control(serial::FlowControl::None);
+
+        // Try to open the serial port
+        Ok(Self {
+            stream: serial::SerialStream::open(&builder)?,
+        })
+    }
+}
+
+// Implement the driver for the Enttec hardware
+impl DmxDriver for EnttecUsbPro {
+    fn name(&self) -> &'static str {
+        EnttecUsbPro::NAME
+    }
+
+    async fn write_frame(&mut self, universe: &Universe, limit: Duration) -> Result<()> {
+        // Add the message header
+        let mut bytes = vec![
+            COMMAND_START,
+            MESSAGE_LABEL,
+            DATA_LSB,
+            DATA_MSB,
+            DMX_START_CODE,
+        ];
+
+        // Add the current universe to the message
+        bytes.append(&mut universe.as_bytes());
+
+        // Add the message ending
+        bytes.push(COMMAND_END);
+
+        // Check that the serial port is ready, waiting at most the limit
+        timeout(limit, self.stream.writable())
+            .await
+            .map_err(|_| anyhow!("Serial port was not ready."))??;
+
+        // Try to send the universe to the DMX contoller
+        let sent_bytes = self.stream.try_write(bytes.as_slice())?;
+        if sent_bytes != bytes.len() {
+            return Err(anyhow!(
+                "Only wrote {} of {} bytes.",
+                sent_bytes,
+                bytes.len()
+            ));
+        }
+        trace!("Wrote to serial: {:?}", bytes.as_slice());
+        Ok(())
+    }
+}
+
+/// A structure to read one control line of a serial port, for inputs wired
+/// to the port (such as an emergency stop between DTR and CTS)
+///
+pub struct SerialLine {
+    stream: serial::SerialStream, // the serial port connection
+    line: ControlLine,            // the control line to read
+}
+
+// Implement key features of the serial line
+impl SerialLine {
+    /// A function to open the serial port and raise DTR (and RTS), so that a
+    /// switch can connect them to the control line
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the serial port cannot be
+    /// opened.
+    ///
+    pub fn open(path: &Path, line: ControlLine) -> Result<Self> {
+        let builder = serial::new(path.to_str().unwrap_or(""), 9600);
+        let mut stream = serial::SerialStream::open(&builder)?;
+        stream.write_data_terminal_ready(true)?;
+        stream.write_request_to_send(true)?;
+        Ok(Self { stream, line })
+    }
+
+    /// A method to read the current level of the control line
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the serial port is no longer
+    /// available.
+    ///
+    pub fn read(&mut self) -> Result<bool> {
+        Ok(match self.line {
+            ControlLine::Cts => self.stream.read_clear_to_send()?,
+            ControlLine::Dsr => self.stream.read_data_set_ready()?,
+            ControlLine::Dcd => self.stream.read_carrier_detect()?,
+            ControlLine::Ri => self.stream.read_ring_indicator()?,
+        })
+    }
+}
+
+/// A driver that accepts every frame without writing it anywhere, for
+/// testing cues without the DMX hardware
+///
+pub struct DryRunDriver;
+
+// Implement key features of the dry run driver
+impl DryRunDriver {
+    /// The type of the hardware
+    pub const NAME: &'static str = "dry-run";
+}
+
+// Implement the driver without any hardware
+impl DmxDriver for DryRunDriver {
+    fn name(&self) -> &'static str {
+        DryRunDriver::NAME
+    }
+
+    async fn write_frame(&mut self, universe: &Universe, _limit: Duration) -> Result<()> {
+        trace!("Dry run frame: {:?}", universe.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,719 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to calculate the value of every channel with an effect. Each
+//! channel follows at most one effect (or movement), and a fade on the
+//! channel releases it from its effect. Effects locked to the beat clock
+//! follow the beat instead of their own start time. Pixel generators are
+//! played the same way, across every channel of a pixel map.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import the effects and the beat clock
+use crate::beat_clock::BeatClock;
+use crate::effects::{Effect, EffectRegistry, PixelGenerator};
+
+// Import standard library features
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A structure to hold the available effects and the effects in progress
+///
+#[derive(Clone, Default)]
+pub struct EffectEngine {
+    registry: EffectRegistry,        // the available effects
+    effects: Vec<RunningEffect>,     // the effects in progress, oldest first
+    movements: Vec<RunningMovement>, // the movements in progress, oldest first
+    pixels: Vec<RunningPixels>,      // the pixel generators in progress, oldest first
+    clock: BeatClock,                // the tempo and beat of the effects locked to the beat
+    speed: EffectSpeed,              // the speed of the effects that are not locked to the beat
+}
+
+/// A helper structure to count the cycles of an effect across changes of
+/// speed
+///
+#[derive(Clone)]
+struct Progress {
+    since: Instant, // the time of the last change of speed (or the start of the effect)
+    cycles: f64,    // the cycles of the effect before that time
+}
+
+/// A helper structure to hold one effect in progress
+///
+#[derive(Clone)]
+struct RunningEffect {
+    effect: Arc<dyn Effect>, // the generator of the effect
+    channels: Vec<u32>,      // the channels that follow the effect
+    low: u8,                 // the value at the bottom of the effect
+    high: u8,                // the value at the top of the effect
+    period: Duration,        // the time for one cycle of the effect
+    beats: Option<f64>,      // the beats in one cycle of the effect, if locked to the beat clock
+    seed: u64,               // the seed of the random pattern of the effect
+    chance: f64,             // the chance of each random event of the effect
+    intensity: f64,          // the share of the range from low to high that the effect uses
+    progress: Progress,      // the cycles of the effect so far
+}
+
+/// A helper structure to hold one movement in progress
+///
+#[derive(Clone)]
+struct RunningMovement {
+    movement: Movement, // the shape and the fixtures of the movement
+    progress: Progress, // the cycles of the movement so far
+}
+
+/// A helper structure to hold one pixel generator in progress
+///
+#[derive(Clone)]
+struct RunningPixels {
+    generator: Arc<dyn PixelGenerator>, // the generator of the colors
+    map: PixelMap,                      // the pixels to color
+    colors: Vec<Rgb>,                   // the colors of the generator
+    period: Duration,                   // the time for one cycle of the animation
+    beats: Option<f64>, // the beats in one cycle of the animation, if locked to the beat clock
+    progress: Progress, // the cycles of the animation so far
+}
+
+// Implement key features of the effect engine
+impl EffectEngine {
+    /// A function to create a new effect engine with the available effects
+    ///
+    pub fn new(registry: EffectRegistry) -> Self {
+        Self {
+            registry,
+            effects: Vec::new(),
+            movements: Vec::new(),
+            pixels: Vec::new(),
+            clock: BeatClock::default(),
+            speed: EffectSpeed::default(),
+        }
+    }
+
+    /// A method to return the beat clock
+    ///
+    pub fn clock(&self) -> &BeatClock {
+        &self.clock
+    }
+
+    /// A method to change the tempo or tap the beat of the beat clock
+    ///
+    pub fn clock_mut(&mut self) -> &mut BeatClock {
+        &mut self.clock
+    }
+
+    /// A method to return the available effects
+    ///
+    pub fn registry(&self) -> &EffectRegistry {
+        &self.registry
+    }
+
+    /// A method to return the number of effects (including movements and
+    /// pixel generators) in progress
+    ///
+    pub fn active_effects(&self) -> usize {
+        self.effects.len() + self.movements.len() + self.pixels.len()
+    }
+
+    /// A method to list every channel with an effect (or movement)
+    ///
+    pub fn channels(&self) -> Vec<u32> {
+        let mut channels: Vec<u32> = self
+            .effects
+            .iter()
+            .flat_map(|running| running.channels.iter().copied())
+            .chain(
+                self.movements
+                    .iter()
+                    .flat_map(|running| running.movement.channels()),
+            )
+            .chain(
+                self.pixels
+                    .iter()
+                    .flat_map(|running| running.map.channels()),
+            )
+            .collect();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+
+    /// A method to start an effect, taking the channels from any other
+    /// effects
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the effect does not exist or a
+    /// channel is outside the universe.
+    ///
+    pub fn play(&mut self, play: &EffectPlay, now: Instant) -> Result<()> {
+        // Try to find the effect
+        let effect = self
+            .registry
+            .get(&play.effect)
+            .ok_or_else(|| ErrorCode::EffectNotFound {
+                effect: play.effect.clone(),
+            })?;
+
+        // Verify the range of every channel
+        if let Some(channel) = play
+            .channels
+            .iter()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Take the channels from other effects and start the effect
+        for channel in play.channels.iter() {
+            self.release(*channel);
+        }
+        self.effects.push(RunningEffect {
+            effect,
+            channels: play.channels.clone(),
+            low: play.low,
+            high: play.high,
+            period: play.period,
+            beats: play.beats,
+            seed: play.seed,
+            chance: play.chance,
+            intensity: play.intensity,
+            progress: Progress::new(now),
+        });
+        Ok(())
+    }
+
+    /// A method to change the intensity or the chance of the effects on any
+    /// of the channels (or every effect, if there are no channels), without
+    /// restarting them
+    ///
+    pub fn tune(&mut self, tune: &EffectTune) {
+        for running in self.effects.iter_mut().filter(|running| {
+            tune.channels.is_empty()
+                || running
+                    .channels
+                    .iter()
+                    .any(|channel| tune.channels.contains(channel))
+        }) {
+            if let Some(intensity) = tune.intensity {
+                running.intensity = intensity;
+            }
+            if let Some(chance) = tune.chance {
+                running.chance = chance;
+            }
+        }
+    }
+
+    /// A method to change the speed of the effects that are not locked to
+    /// the beat. Each effect continues from its current position.
+    ///
+    pub fn set_speed(&mut self, speed: EffectSpeed, now: Instant) {
+        // Count the cycles so far at the previous speed
+        let previous = self.speed;
+        for running in self.effects.iter_mut() {
+            let speed = previous.master * previous.effects;
+            running.progress.rebase(running.period, speed, now);
+        }
+        for running in self.movements.iter_mut() {
+            let speed = previous.master * previous.movements;
+            running.progress.rebase(running.movement.period, speed, now);
+        }
+        for running in self.pixels.iter_mut() {
+            let speed = previous.master * previous.pixels;
+            running.progress.rebase(running.period, speed, now);
+        }
+
+        // Continue at the new speed
+        self.speed = speed;
+    }
+
+    /// A method to start a movement, taking the channels from any other
+    /// effects
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if a channel is outside the universe.
+    ///
+    pub fn play_movement(&mut self, movement: Movement, now: Instant) -> Result<()> {
+        // Verify the range of every channel
+        let channels: Vec<u32> = movement.channels().collect();
+        if let Some(channel) = channels
+            .iter()
+            .find(|channel| !(1..=DMX_MAX).contains(*channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel: *channel }.into());
+        }
+
+        // Take the channels from other effects and start the movement
+        for channel in channels {
+            self.release(channel);
+        }
+        self.movements.push(RunningMovement {
+            movement,
+            progress: Progress::new(now),
+        });
+        Ok(())
+    }
+
+    /// A method to start a pixel generator on a pixel map, taking the
+    /// channels from any other effects
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the generator does not exist or
+    /// the map is outside the universe.
+    ///
+    pub fn play_pixels(&mut self, play: &PixelPlay, map: PixelMap, now: Instant) -> Result<()> {
+        // Try to find the generator
+        let generator =
+            self.registry
+                .generator(&play.generator)
+                .ok_or_else(|| ErrorCode::EffectNotFound {
+                    effect: play.generator.clone(),
+                })?;
+
+        // Verify the range of the map
+        if map.width == 0 || map.height == 0 || map.start < 1 {
+            return Err(ErrorCode::ChannelOutOfRange { channel: map.start }.into());
+        }
+        if map.last_channel() > DMX_MAX {
+            return Err(ErrorCode::ChannelOutOfRange {
+                channel: map.last_channel(),
+            }
+            .into());
+        }
+
+        // Take the channels from other effects and start the generator
+        for channel in map.channels() {
+            self.release(channel);
+        }
+        let mut colors = play.colors.clone();
+        if colors.is_empty() {
+            colors = vec![
+                Rgb::new(u8::MAX, 0, 0),
+                Rgb::new(0, u8::MAX, 0),
+                Rgb::new(0, 0, u8::MAX),
+            ];
+        }
+        self.pixels.push(RunningPixels {
+            generator,
+            map,
+            colors,
+            period: play.period,
+            beats: play.beats,
+            progress: Progress::new(now),
+        });
+        Ok(())
+    }
+
+    /// A method to release a channel from its effect, if any. The channel
+    /// holds its current value. A fixture in a movement is released from the
+    /// movement entirely, and a pixel map from its generator entirely.
+    ///
+    pub fn release(&mut self, channel: u32) {
+        for running in self.effects.iter_mut() {
+            running.channels.retain(|other| *other != channel);
+        }
+        self.effects.retain(|running| !running.channels.is_empty());
+        for running in self.movements.iter_mut() {
+            running
+                .movement
+                .heads
+                .retain(|head| !head.channels().any(|other| other == channel));
+        }
+        self.movements
+            .retain(|running| !running.movement.heads.is_empty());
+        self.pixels
+            .retain(|running| !running.map.channels().any(|other| other == channel));
+    }
+
+    /// A method to stop the effects on the channels (or every effect, if
+    /// there are no channels)
+    ///
+    pub fn stop(&mut self, channels: &[u32]) {
+        if channels.is_empty() {
+            self.effects.clear();
+            self.movements.clear();
+            self.pixels.clear();
+        } else {
+            for channel in channels {
+                self.release(*channel);
+            }
+        }
+    }
+
+    /// A method to set the value of every channel with an effect at the
+    /// given time
+    ///
+    pub fn update(&self, now: Instant, universe: &mut Universe) {
+        for running in self.effects.iter() {
+            // Find the level of the effect on each channel
+            let speed = self.speed.master * self.speed.effects;
+            let cycles = self.cycles(&running.progress, running.period, running.beats, speed, now);
+            let low = running.low as f64;
+            let range = (running.high as f64 - low) * running.intensity;
+            for channel in running.channels.iter() {
+                let level = running
+                    .effect
+                    .channel_level(cycles, *channel, running.seed, running.chance)
+                    .clamp(0.0, 1.0);
+
+                // Scale the level to the values of the channel
+                universe.set(*channel, (low + range * level).round() as u8);
+            }
+        }
+
+        // Color every pixel of each pixel map
+        for running in self.pixels.iter() {
+            let speed = self.speed.master * self.speed.pixels;
+            let cycles = self.cycles(&running.progress, running.period, running.beats, speed, now);
+            for (index, (channel, x, y)) in running.map.pixels().enumerate() {
+                let color = running
+                    .generator
+                    .color(index as u32, x, y, cycles, &running.colors);
+                universe.set(channel, color.red);
+                universe.set(channel + 1, color.green);
+                universe.set(channel + 2, color.blue);
+            }
+        }
+
+        // Move every fixture with a movement
+        for running in self.movements.iter() {
+            let movement = &running.movement;
+            let speed = self.speed.master * self.speed.movements;
+            let cycles = self.cycles(
+                &running.progress,
+                movement.period,
+                movement.beats,
+                speed,
+                now,
+            );
+            for head in movement.heads.iter() {
+                // Find the offset of the fixture on each axis
+                let (pan, tilt) = movement.shape.offsets(cycles + head.phase);
+                for (axis, offset) in [(&head.pan, pan), (&head.tilt, tilt)] {
+                    if let Some(axis) = axis {
+                        let value = axis.to_value(axis.center + movement.radius * offset);
+                        universe.set(axis.channel, (value >> 8) as u8);
+                        if let Some(fine_channel) = axis.fine_channel {
+                            universe.set(fine_channel, value as u8);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Implement the helper functions of the effect engine
+impl EffectEngine {
+    /// A helper function to find the number of cycles of an effect at the
+    /// given time, counted from the beat clock if the effect is locked to
+    /// the beat (otherwise from the progress of the effect at the speed)
+    ///
+    fn cycles(
+        &self,
+        progress: &Progress,
+        period: Duration,
+        beats: Option<f64>,
+        speed: f64,
+        now: Instant,
+    ) -> f64 {
+        match beats {
+            Some(beats) => self.clock.beats(now) / beats.max(f64::EPSILON),
+            None => progress.count(period, speed, now),
+        }
+    }
+}
+
+// Implement key features of the progress of an effect
+impl Progress {
+    /// A function to start counting the cycles of an effect
+    ///
+    fn new(now: Instant) -> Self {
+        Self {
+            since: now,
+            cycles: 0.0,
+        }
+    }
+
+    /// A method to count the cycles of the effect at the given time and
+    /// speed
+    ///
+    fn count(&self, period: Duration, speed: f64, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.since);
+        self.cycles + elapsed.as_secs_f64() * speed / period.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// A method to keep the cycles so far at the previous speed, before the
+    /// speed changes
+    ///
+    fn rebase(&mut self, period: Duration, speed: f64, now: Instant) {
+        self.cycles = self.count(period, speed, now);
+        self.since = now;
+    }
+}
+
+// Tests of the effect engine module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that an effect moves its channels until released or stopped
+    #[test]
+    fn effect_over_time() {
+        let mut engine = EffectEngine::new(EffectRegistry::builtin());
+        let mut universe = Universe::new();
+        let start = Instant::now();
+
+        // Unknown effects and channels are rejected
+        let mut play = EffectPlay {
+            effect: "wobble".into(),
+            channels: vec![1, 2],
+            low: 10,
+            high: 200,
+            period: Duration::from_secs(2),
+            beats: None,
+            seed: 0,
+            chance: DEFAULT_EFFECT_CHANCE,
+            intensity: 1.0,
+        };
+        assert!(engine.play(&play, start).is_err());
+        play.effect = "square".into();
+        play.channels.push(513);
+        assert!(engine.play(&play, start).is_err());
+        play.channels.pop();
+
+        // A square effect alternates between the values
+        engine.play(&play, start).unwrap();
+        engine.update(start, &mut universe);
+        assert_eq!((universe.get(1), universe.get(2)), (10, 10));
+        engine.update(start + Duration::from_millis(1500), &mut universe);
+        assert_eq!((universe.get(1), universe.get(2)), (200, 200));
+
+        // Tuning the intensity narrows the range without a restart
+        engine.tune(&EffectTune {
+            intensity: Some(0.5),
+            ..Default::default()
+        });
+        engine.update(start + Duration::from_millis(1500), &mut universe);
+        assert_eq!((universe.get(1), universe.get(2)), (105, 105));
+        engine.tune(&EffectTune {
+            channels: vec![2],
+            intensity: Some(1.0),
+            chance: None,
+        });
+        engine.update(start + Duration::from_millis(1500), &mut universe);
+        assert_eq!((universe.get(1), universe.get(2)), (200, 200));
+
+        // A released channel holds its value
+        engine.release(1);
+        engine.update(start + Duration::from_secs(2), &mut universe);
+        assert_eq!((universe.get(1), universe.get(2)), (200, 10));
+        assert_eq!(engine.active_effects(), 1);
+        assert_eq!(engine.channels(), vec![2]);
+
+        // An effect locked to the beat follows the clock, not its start time
+        engine.clock_mut().set_bpm(120.0, start);
+        play.channels = vec![3];
+        play.beats = Some(2.0);
+        engine
+            .play(&play, start + Duration::from_millis(400))
+            .unwrap();
+        engine.update(start + Duration::from_millis(400), &mut universe);
+        assert_eq!(universe.get(3), 10);
+        engine.update(start + Duration::from_millis(600), &mut universe);
+        assert_eq!(universe.get(3), 200);
+
+        // Halving the speed continues from the current position
+        engine.stop(&[]);
+        play.channels = vec![4];
+        play.beats = None;
+        engine.play(&play, start).unwrap();
+        engine.set_speed(
+            EffectSpeed {
+                master: 0.5,
+                ..Default::default()
+            },
+            start + Duration::from_millis(1500),
+        );
+        engine.update(start + Duration::from_millis(2000), &mut universe);
+        assert_eq!(universe.get(4), 200);
+        engine.update(start + Duration::from_millis(3100), &mut universe);
+        assert_eq!(universe.get(4), 10);
+
+        // Stopping every effect
+        engine.stop(&[]);
+        assert_eq!(engine.active_effects(), 0);
+    }
+
+    // Test that a change of speed stretches the rest of the cycle in progress
+    #[test]
+    fn speed_stretch() {
+        let mut engine = EffectEngine::new(EffectRegistry::builtin());
+        let mut universe = Universe::new();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        // A square effect is low for the first second of its cycle
+        let play = EffectPlay {
+            effect: "square".into(),
+            channels: vec![1],
+            low: 10,
+            high: 200,
+            period: Duration::from_secs(2),
+            beats: None,
+            seed: 0,
+            chance: DEFAULT_EFFECT_CHANCE,
+            intensity: 1.0,
+        };
+        engine.play(&play, start).unwrap();
+
+        // Halving the master speed halfway through doubles the remaining
+        // half second until the switch
+        engine.set_speed(
+            EffectSpeed {
+                master: 0.5,
+                ..Default::default()
+            },
+            at(500),
+        );
+        engine.update(at(1400), &mut universe);
+        assert_eq!(universe.get(1), 10);
+        engine.update(at(1600), &mut universe);
+        assert_eq!(universe.get(1), 200);
+
+        // The speed of the effects combines with the master, so the rest of
+        // the cycle runs at the full speed again
+        engine.set_speed(
+            EffectSpeed {
+                master: 2.0,
+                effects: 0.5,
+                ..Default::default()
+            },
+            at(1600),
+        );
+        engine.update(at(2400), &mut universe);
+        assert_eq!(universe.get(1), 200);
+        engine.update(at(2600), &mut universe);
+        assert_eq!(universe.get(1), 10);
+    }
+
+    // Test that a movement moves each fixture around the center
+    #[test]
+    fn movement_over_time() {
+        let mut engine = EffectEngine::new(EffectRegistry::builtin());
+        let mut universe = Universe::new();
+        let start = Instant::now();
+
+        // Create a circle for two fixtures, half a cycle apart
+        let axis = |channel, center| Axis {
+            channel,
+            fine_channel: Some(channel + 1),
+            range: 360,
+            center,
+        };
+        let movement = Movement {
+            shape: Shape::Circle,
+            radius: 90.0,
+            period: Duration::from_secs(4),
+            beats: None,
+            heads: vec![
+                MovingHead {
+                    pan: Some(axis(1, 180.0)),
+                    tilt: Some(axis(3, 180.0)),
+                    phase: 0.0,
+                },
+                MovingHead {
+                    pan: Some(axis(5, 180.0)),
+                    tilt: None,
+                    phase: 0.5,
+                },
+            ],
+        };
+        engine.play_movement(movement, start).unwrap();
+
+        // A quarter of the way around, the fixtures are at opposite edges
+        engine.update(start + Duration::from_secs(1), &mut universe);
+        assert_eq!((universe.get(1), universe.get(3)), (191, 128));
+        assert_eq!(universe.get(5), 64);
+
+        // A fade on a channel releases that fixture only
+        engine.release(6);
+        assert_eq!(engine.active_effects(), 1);
+        engine.release(2);
+        assert_eq!(engine.active_effects(), 0);
+    }
+
+    // Test that a pixel generator colors every pixel of its map
+    #[test]
+    fn pixels_over_time() {
+        let mut engine = EffectEngine::new(EffectRegistry::builtin());
+        let mut universe = Universe::new();
+        let start = Instant::now();
+
+        // Scroll from red to blue along a strip of three pixels
+        let map = PixelMap {
+            start: 1,
+            width: 3,
+            height: 1,
+            serpentine: false,
+        };
+        let mut play = PixelPlay {
+            map: "strip".into(),
+            generator: "scroll".into(),
+            colors: vec![Rgb::new(255, 0, 0), Rgb::new(0, 0, 255)],
+            period: Duration::from_secs(2),
+            beats: None,
+        };
+        engine.play_pixels(&play, map.clone(), start).unwrap();
+        assert_eq!(engine.channels(), (1..=9).collect::<Vec<u32>>());
+        engine.update(start, &mut universe);
+        let pixel = |universe: &Universe, channel| {
+            Rgb::new(
+                universe.get(channel),
+                universe.get(channel + 1),
+                universe.get(channel + 2),
+            )
+        };
+        assert_eq!(pixel(&universe, 1), Rgb::new(255, 0, 0));
+        assert_eq!(pixel(&universe, 4), Rgb::new(0, 0, 255));
+
+        // Half a cycle later, the colors have moved along the strip
+        engine.update(start + Duration::from_secs(1), &mut universe);
+        assert_eq!(pixel(&universe, 1), Rgb::new(0, 0, 255));
+        assert_eq!(pixel(&universe, 4), Rgb::new(255, 0, 0));
+
+        // Unknown generators and maps beyond the universe are rejected
+        play.generator = "fireworks".into();
+        assert!(engine.play_pixels(&play, map.clone(), start).is_err());
+        play.generator = "sparkle".into();
+        let wide = PixelMap {
+            start: 400,
+            width: 50,
+            ..map
+        };
+        assert!(engine.play_pixels(&play, wide, start).is_err());
+
+        // A fade on any channel releases the whole map
+        engine.release(8);
+        assert_eq!(engine.active_effects(), 0);
+    }
+}
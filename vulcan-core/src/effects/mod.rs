@@ -0,0 +1,175 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to define the effects and pixel generators, and the registry of
+//! those available to the effect engine. The built-in effects are registered
+//! the same way as any other effect, so a program using this crate can add
+//! its own.
+
+// Define private submodules
+mod pixels;
+mod random;
+mod waveforms;
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Reexport the built-in effects and generators
+pub use pixels::{Gradient, Plasma, Scroll, Sparkle};
+pub use random::{ChannelSparkle, RandomWalk};
+pub use waveforms::{Sawtooth, Sine, Square, Triangle};
+
+// Import standard library features
+use std::sync::Arc;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A trait for the generator of an effect. The effect engine scales the
+/// level of the effect to the values of each channel.
+///
+pub trait Effect: Send + Sync + 'static {
+    /// A method to return the name of the effect, as used in requests
+    ///
+    fn name(&self) -> &'static str;
+
+    /// A method to return the level of the effect, from zero to one, after
+    /// some number of cycles. The fraction is the position within the
+    /// current cycle (levels beyond zero or one are clamped).
+    ///
+    fn level(&self, cycles: f64) -> f64;
+
+    /// A method to return the level of one channel of the effect, from zero
+    /// to one, after some number of cycles. Random effects choose a pattern
+    /// for each channel from the seed, and use the chance (from zero to one)
+    /// for each random event. By default, every channel follows the level
+    /// of the effect.
+    ///
+    fn channel_level(&self, cycles: f64, _channel: u32, _seed: u64, _chance: f64) -> f64 {
+        self.level(cycles)
+    }
+}
+
+/// A trait for a pixel generator, which colors every pixel of a pixel map
+///
+pub trait PixelGenerator: Send + Sync + 'static {
+    /// A method to return the name of the generator, as used in requests
+    ///
+    fn name(&self) -> &'static str;
+
+    /// A method to return the color of a pixel after some number of cycles.
+    /// The index is the order of the pixel in the map, and the position of
+    /// the pixel is from zero to one in each direction. There is always at
+    /// least one color.
+    ///
+    fn color(&self, index: u32, x: f64, y: f64, cycles: f64, colors: &[Rgb]) -> Rgb;
+}
+
+/// A structure to hold the effects and pixel generators available to the
+/// effect engine, by name
+///
+#[derive(Clone, Default)]
+pub struct EffectRegistry {
+    effects: FnvHashMap<&'static str, Arc<dyn Effect>>, // the available effects, by name
+    generators: FnvHashMap<&'static str, Arc<dyn PixelGenerator>>, // the available pixel generators, by name
+}
+
+// Implement key features of the effect registry
+impl EffectRegistry {
+    /// A function to create a new, empty registry
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A function to create a new registry with the built-in effects
+    ///
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(Sine);
+        registry.register(Square);
+        registry.register(Triangle);
+        registry.register(Sawtooth);
+        registry.register(RandomWalk);
+        registry.register(ChannelSparkle);
+        registry.register_generator(Gradient);
+        registry.register_generator(Scroll);
+        registry.register_generator(Plasma);
+        registry.register_generator(Sparkle);
+        registry
+    }
+
+    /// A method to add an effect, replacing any effect with the same name
+    ///
+    pub fn register<E: Effect>(&mut self, effect: E) {
+        self.effects.insert(effect.name(), Arc::new(effect));
+    }
+
+    /// A method to find an effect by name
+    ///
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Effect>> {
+        self.effects.get(name).cloned()
+    }
+
+    /// A method to list the names of the available effects, in order
+    ///
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.effects.keys().map(|name| name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// A method to add a pixel generator, replacing any generator with the
+    /// same name
+    ///
+    pub fn register_generator<G: PixelGenerator>(&mut self, generator: G) {
+        self.generators
+            .insert(generator.name(), Arc::new(generator));
+    }
+
+    /// A method to find a pixel generator by name
+    ///
+    pub fn generator(&self, name: &str) -> Option<Arc<dyn PixelGenerator>> {
+        self.generators.get(name).cloned()
+    }
+
+    /// A method to list the names of the available pixel generators, in
+    /// order
+    ///
+    pub fn generator_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .generators
+            .keys()
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+/// A helper function to return a repeatable random number, from zero to one,
+/// for a channel (or pixel) on a step of an effect with the seed
+///
+fn random(seed: u64, index: u32, step: i64) -> f64 {
+    let mut value =
+        ((index as u64) << 32) ^ (step as u64) ^ seed.wrapping_mul(0xD1B5_4A32_D192_ED03);
+    value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^= value >> 31;
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,123 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to define the built-in pixel generators. Each generator blends
+//! the colors of the request across the pixel map.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import the pixel generator trait and the random numbers
+use super::{PixelGenerator, random};
+
+// Import standard library features
+use std::f64::consts::TAU;
+
+// Define the sparkle constants
+const SPARKLE_STEPS: f64 = 8.0; // the number of flashes in each cycle of a sparkle
+const SPARKLE_CHANCE: f64 = 0.1; // the share of the pixels that flash on each step
+
+/// A still gradient from the first color at one end of each row to the last
+/// color at the other end
+///
+pub struct Gradient;
+
+// Implement the gradient generator
+impl PixelGenerator for Gradient {
+    fn name(&self) -> &'static str {
+        "gradient"
+    }
+
+    fn color(&self, _index: u32, x: f64, _y: f64, _cycles: f64, colors: &[Rgb]) -> Rgb {
+        let Some(last) = colors.len().checked_sub(1) else {
+            return Rgb::default();
+        };
+        let position = x.clamp(0.0, 1.0) * last as f64;
+        let index = (position.floor() as usize).min(last);
+        colors[index].mix(&colors[(index + 1).min(last)], position - index as f64)
+    }
+}
+
+/// The colors scrolling along each row, once across the row per cycle
+///
+pub struct Scroll;
+
+// Implement the scroll generator
+impl PixelGenerator for Scroll {
+    fn name(&self) -> &'static str {
+        "scroll"
+    }
+
+    fn color(&self, _index: u32, x: f64, _y: f64, cycles: f64, colors: &[Rgb]) -> Rgb {
+        palette(colors, x - cycles)
+    }
+}
+
+/// Overlapping waves of color, flowing across the map
+///
+pub struct Plasma;
+
+// Implement the plasma generator
+impl PixelGenerator for Plasma {
+    fn name(&self) -> &'static str {
+        "plasma"
+    }
+
+    fn color(&self, _index: u32, x: f64, y: f64, cycles: f64, colors: &[Rgb]) -> Rgb {
+        let time = cycles * TAU;
+        let wave = (x * TAU + time).sin()
+            + (y * TAU - time).sin()
+            + ((x + y) * TAU / 2.0 + time).sin()
+            + ((x * x + y * y).sqrt() * TAU + time).sin();
+        palette(colors, (wave / 4.0 + 1.0) / 2.0)
+    }
+}
+
+/// Random pixels flashing in the first color and fading back to the second
+/// color (or black)
+///
+pub struct Sparkle;
+
+// Implement the sparkle generator
+impl PixelGenerator for Sparkle {
+    fn name(&self) -> &'static str {
+        "sparkle"
+    }
+
+    fn color(&self, index: u32, _x: f64, _y: f64, cycles: f64, colors: &[Rgb]) -> Rgb {
+        let flash = colors.first().copied().unwrap_or_default();
+        let background = colors.get(1).copied().unwrap_or_default();
+        let steps = cycles * SPARKLE_STEPS;
+        if random(0, index, steps.floor() as i64) < SPARKLE_CHANCE {
+            background.mix(&flash, 1.0 - steps.fract())
+        } else {
+            background
+        }
+    }
+}
+
+/// A helper function to blend the colors around a loop, so the last color
+/// leads back to the first
+///
+fn palette(colors: &[Rgb], position: f64) -> Rgb {
+    if colors.is_empty() {
+        return Rgb::default();
+    }
+    let position = position.rem_euclid(1.0) * colors.len() as f64;
+    let index = (position.floor() as usize) % colors.len();
+    colors[index].mix(&colors[(index + 1) % colors.len()], position.fract())
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to define the built-in random effects. Each channel follows its
+//! own random pattern, chosen by the seed of the effect, so that a preview
+//! with the same seed repeats exactly.
+
+// Import the effect trait and the random numbers
+use super::{Effect, random};
+
+/// A smooth wander between random levels, reaching a new level at the end
+/// of each cycle
+///
+pub struct RandomWalk;
+
+// Implement the random walk effect
+impl Effect for RandomWalk {
+    fn name(&self) -> &'static str {
+        "randomWalk"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        self.channel_level(cycles, 0, 0, 1.0)
+    }
+
+    fn channel_level(&self, cycles: f64, channel: u32, seed: u64, _chance: f64) -> f64 {
+        let step = cycles.floor() as i64;
+        let from = random(seed, channel, step);
+        let to = random(seed, channel, step + 1);
+        let share = cycles.fract();
+        from + (to - from) * share * share * (3.0 - 2.0 * share)
+    }
+}
+
+/// Random flashes to the high value, fading back to the low value over the
+/// cycle. Each channel flashes at the start of a cycle by the chance of the
+/// effect.
+///
+pub struct ChannelSparkle;
+
+// Implement the sparkle effect
+impl Effect for ChannelSparkle {
+    fn name(&self) -> &'static str {
+        "sparkle"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        self.channel_level(cycles, 0, 0, 1.0)
+    }
+
+    fn channel_level(&self, cycles: f64, channel: u32, seed: u64, chance: f64) -> f64 {
+        if random(seed, channel, cycles.floor() as i64) < chance {
+            1.0 - cycles.fract()
+        } else {
+            0.0
+        }
+    }
+}
+
+// Tests of the random module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the random effects repeat with the same seed
+    #[test]
+    fn repeat_with_seed() {
+        // The same seed and channel give the same walk
+        let walk = |channel, seed| -> Vec<f64> {
+            (0..20)
+                .map(|step| RandomWalk.channel_level(step as f64 / 4.0, channel, seed, 1.0))
+                .collect()
+        };
+        assert_eq!(walk(1, 7), walk(1, 7));
+        assert_ne!(walk(1, 7), walk(1, 8));
+        assert_ne!(walk(1, 7), walk(2, 7));
+        assert!(walk(1, 7).iter().all(|level| (0.0..=1.0).contains(level)));
+
+        // The walk reaches each new level smoothly
+        let start = RandomWalk.channel_level(3.0, 1, 7, 1.0);
+        assert!((RandomWalk.channel_level(2.999, 1, 7, 1.0) - start).abs() < 0.001);
+
+        // Sparkles follow the chance
+        let flashes = |chance| {
+            (0..100)
+                .filter(|cycle| ChannelSparkle.channel_level(*cycle as f64, 1, 7, chance) > 0.0)
+                .count()
+        };
+        assert_eq!(flashes(0.0), 0);
+        assert_eq!(flashes(1.0), 100);
+        assert!((10..40).contains(&flashes(0.25)));
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to define the built-in waveform effects. Each waveform starts
+//! the cycle at the low value.
+
+// Import the effect trait
+use super::Effect;
+
+// Import standard library features
+use std::f64::consts::TAU;
+
+/// A smooth rise and fall
+///
+pub struct Sine;
+
+// Implement the sine effect
+impl Effect for Sine {
+    fn name(&self) -> &'static str {
+        "sine"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        (1.0 - (cycles.fract() * TAU).cos()) / 2.0
+    }
+}
+
+/// An alternation between low and high, for half of each cycle
+///
+pub struct Square;
+
+// Implement the square effect
+impl Effect for Square {
+    fn name(&self) -> &'static str {
+        "square"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        if cycles.fract() < 0.5 { 0.0 } else { 1.0 }
+    }
+}
+
+/// A steady rise and fall
+///
+pub struct Triangle;
+
+// Implement the triangle effect
+impl Effect for Triangle {
+    fn name(&self) -> &'static str {
+        "triangle"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        1.0 - (2.0 * cycles.fract() - 1.0).abs()
+    }
+}
+
+/// A steady rise, followed by a snap back to low
+///
+pub struct Sawtooth;
+
+// Implement the sawtooth effect
+impl Effect for Sawtooth {
+    fn name(&self) -> &'static str {
+        "sawtooth"
+    }
+
+    fn level(&self, cycles: f64) -> f64 {
+        cycles.fract()
+    }
+}
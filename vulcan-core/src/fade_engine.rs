@@ -0,0 +1,587 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to calculate the value of every channel as fades progress. The
+//! fade engine doesn't write to any hardware, so it can drive any output.
+//! Every fade is calculated as a 16-bit value, so that a fade across a
+//! coarse and a fine channel moves smoothly. Each value is calculated from
+//! the start time and the duration of the fade (not added up frame by
+//! frame), so even a long fade lands exactly on time and a paused fade
+//! resumes from where it stopped.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A struct to report the end of a fade with an identifier, either because
+/// it reached its value or because something replaced it
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FadeEnd {
+    pub id: u64,            // the identifier of the fade
+    pub channel: u32,       // the channel of the fade (the coarse channel of a wide fade)
+    pub is_cancelled: bool, // a flag to indicate the fade was replaced before it finished
+}
+
+/// A structure to hold the current universe and the fades in progress
+///
+#[derive(Clone, Debug, Default)]
+pub struct FadeEngine {
+    universe: Universe,               // the current universe of all the channels
+    changes: FnvHashMap<u32, Change>, // the fades in progress, by coarse channel
+    ended: Vec<FadeEnd>,              // the fades with identifiers that ended since last taken
+}
+
+// Implement key features of the fade engine
+impl FadeEngine {
+    /// A function to create a new fade engine with every channel at zero
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to return the current value of every channel
+    ///
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
+
+    /// A method to return the number of fades in progress
+    ///
+    pub fn active_fades(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// A method to indicate whether a fade is in progress on a channel (as
+    /// the coarse or the fine channel)
+    ///
+    pub fn is_fading(&self, channel: u32) -> bool {
+        self.changes
+            .iter()
+            .any(|(coarse, change)| *coarse == channel || change.fine_channel == Some(channel))
+    }
+
+    /// A method to start a fade from the current value of the channel,
+    /// replacing any fade on that channel. A fade without a duration is
+    /// applied immediately. Returns true if the universe changed immediately.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if the channel is outside the
+    /// universe.
+    ///
+    pub fn play_fade(&mut self, fade: Fade, now: Instant) -> Result<bool> {
+        self.play_wide_fade(fade.into(), now)
+    }
+
+    /// A method to start a fade of a 16-bit value across the coarse and fine
+    /// channels, replacing any fade on either channel. Otherwise, the fade
+    /// behaves the same as any other fade.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if either channel is outside the
+    /// universe.
+    ///
+    pub fn play_wide_fade(&mut self, fade: WideFade, now: Instant) -> Result<bool> {
+        self.play_tracked_fade(fade, None, now)
+    }
+
+    /// A method to start a fade with an identifier (if any), so that its end
+    /// is reported by take_ended. A fade without a duration ends right away.
+    /// Otherwise, the fade behaves the same as any other fade.
+    ///
+    /// # Errors
+    ///
+    /// This method will raise an error if either channel is outside the
+    /// universe.
+    ///
+    pub fn play_tracked_fade(
+        &mut self,
+        fade: WideFade,
+        id: Option<u64>,
+        now: Instant,
+    ) -> Result<bool> {
+        // Verify the range of the selected channels
+        if let Some(channel) = Some(fade.channel)
+            .into_iter()
+            .chain(fade.fine_channel)
+            .find(|channel| !(1..=DMX_MAX).contains(channel))
+        {
+            return Err(ErrorCode::ChannelOutOfRange { channel }.into());
+        }
+
+        // Replace any fades on the channels
+        self.cancel(fade.channel);
+        if let Some(fine_channel) = fade.fine_channel {
+            self.cancel(fine_channel);
+        }
+
+        // Check whether there is a fade duration specified
+        match fade.duration {
+            // If a fade duration was specified, save the new fade
+            Some(duration) => {
+                let start_value = self.wide_value(fade.channel, fade.fine_channel);
+                let mut change =
+                    Change::new(start_value, fade.value, fade.fine_channel, duration, now);
+                change.id = id;
+                self.changes.insert(fade.channel, change);
+                Ok(false)
+            }
+
+            // Otherwise, make the change immediately
+            None => {
+                Change::write(
+                    &mut self.universe,
+                    fade.channel,
+                    fade.fine_channel,
+                    fade.value,
+                );
+                self.ended.extend(id.map(|id| FadeEnd {
+                    id,
+                    channel: fade.channel,
+                    is_cancelled: false,
+                }));
+                Ok(true)
+            }
+        }
+    }
+
+    /// A method to change the current value of every channel directly (such
+    /// as for effects), without affecting the fades in progress
+    ///
+    pub fn universe_mut(&mut self) -> &mut Universe {
+        &mut self.universe
+    }
+
+    /// A method to stop the fade on a channel (as the coarse or the fine
+    /// channel), if any. The channels hold their current values.
+    ///
+    pub fn cancel(&mut self, channel: u32) {
+        let ended = &mut self.ended;
+        self.changes.retain(|coarse, change| {
+            if *coarse != channel && change.fine_channel != Some(channel) {
+                return true;
+            }
+            ended.extend(change.id.map(|id| FadeEnd {
+                id,
+                channel: *coarse,
+                is_cancelled: true,
+            }));
+            false
+        });
+    }
+
+    /// A method to pause the fades on some channels (as the coarse or the
+    /// fine channel), or every fade if no channels are given. The channels
+    /// hold their current values until the fades resume.
+    ///
+    pub fn pause(&mut self, channels: &[u32], now: Instant) {
+        for (coarse, change) in self.changes.iter_mut() {
+            if Change::is_selected(channels, *coarse, change.fine_channel) {
+                change.paused_at.get_or_insert(now);
+            }
+        }
+    }
+
+    /// A method to resume the paused fades on some channels (as the coarse
+    /// or the fine channel), or every paused fade if no channels are given.
+    /// Each fade finishes as late as it was paused.
+    ///
+    pub fn resume(&mut self, channels: &[u32], now: Instant) {
+        for (coarse, change) in self.changes.iter_mut() {
+            if Change::is_selected(channels, *coarse, change.fine_channel)
+                && let Some(paused_at) = change.paused_at.take()
+            {
+                change.start_time += now.saturating_duration_since(paused_at);
+            }
+        }
+    }
+
+    /// A method to return (and forget) the fades with identifiers that have
+    /// ended, in order
+    ///
+    pub fn take_ended(&mut self) -> Vec<FadeEnd> {
+        std::mem::take(&mut self.ended)
+    }
+
+    /// A helper method to find the current 16-bit value of the channels
+    ///
+    fn wide_value(&self, channel: u32, fine_channel: Option<u32>) -> u16 {
+        let coarse = self.universe.get(channel) as u16;
+        match fine_channel {
+            Some(fine_channel) => (coarse << 8) | self.universe.get(fine_channel) as u16,
+            None => coarse * 257,
+        }
+    }
+
+    /// A method to update the value of every fade at the given time. Returns
+    /// the channels whose fades completed, in order.
+    ///
+    pub fn update(&mut self, now: Instant) -> Vec<u32> {
+        // Update the current status for every fade
+        let mut complete = Vec::new();
+        let mut ended = Vec::new();
+        let universe = &mut self.universe;
+        self.changes
+            .retain(|channel, change| match change.current_fade(now) {
+                // If ongoing, keep the change
+                FadeStatus::Ongoing(value) => {
+                    Change::write(universe, *channel, change.fine_channel, value);
+                    true
+                }
+
+                // If complete, drop the change
+                FadeStatus::Complete(value) => {
+                    Change::write(universe, *channel, change.fine_channel, value);
+                    complete.push(*channel);
+                    ended.extend(change.id.map(|id| FadeEnd {
+                        id,
+                        channel: *channel,
+                        is_cancelled: false,
+                    }));
+                    false
+                }
+            });
+
+        // Note any fades with identifiers, and return the completed channels
+        ended.sort_unstable_by_key(|end: &FadeEnd| end.id);
+        self.ended.extend(ended);
+        complete.sort_unstable();
+        complete
+    }
+}
+
+/// A convenience enum to indicate whether the dmx fade is still ongoing or is
+/// complete.
+enum FadeStatus {
+    /// a variant indicating the fade is still in progress
+    Ongoing(u16),
+
+    /// a variant indicating that the fade is complete
+    Complete(u16),
+}
+
+/// A struct to allow easier manipulation of queued DMX changes.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Change {
+    start_time: Instant, // the start time of the fade (moved later by any pause)
+    start_value: u16,    // the value at the start of the fade
+    end_value: u16,      // the final value at the end of the fade
+    fine_channel: Option<u32>, // the channel for the low byte of the value, if any
+    duration: Duration,  // the duration of the fade (fades without one are written right away)
+    id: Option<u64>,     // the identifier of the fade, if any
+    paused_at: Option<Instant>, // the time the fade was paused, if it is paused
+}
+
+// Implement the DMX Change features
+impl Change {
+    /// A function to return a new DmxChange by composing the elements of the
+    /// fade
+    ///
+    fn new(
+        start_value: u16,
+        end_value: u16,
+        fine_channel: Option<u32>,
+        duration: Duration,
+        now: Instant,
+    ) -> Change {
+        // Compose and return the new dmx change
+        Change {
+            start_time: now,
+            start_value,
+            end_value,
+            fine_channel,
+            duration,
+            id: None,
+            paused_at: None,
+        }
+    }
+
+    /// A helper function to indicate whether a fade is on one of the
+    /// channels (as the coarse or the fine channel), or if no channels are
+    /// given
+    ///
+    fn is_selected(channels: &[u32], coarse: u32, fine_channel: Option<u32>) -> bool {
+        channels.is_empty()
+            || channels.contains(&coarse)
+            || fine_channel.is_some_and(|fine| channels.contains(&fine))
+    }
+
+    /// A helper function to write a 16-bit value to the coarse channel and
+    /// the fine channel, if any
+    ///
+    fn write(universe: &mut Universe, channel: u32, fine_channel: Option<u32>, value: u16) {
+        universe.set(channel, (value >> 8) as u8);
+        if let Some(fine_channel) = fine_channel {
+            universe.set(fine_channel, value as u8);
+        }
+    }
+
+    /// A method to calculate the value of the fade at the given time (or at
+    /// the time it was paused). Returns Ongoing if the fade is still in
+    /// progess and Complete if the fade is complete.
+    ///
+    fn current_fade(&self, now: Instant) -> FadeStatus {
+        // Find the time the fade has been running, without any pause
+        let now = self.paused_at.unwrap_or(now);
+        let elapsed = now.saturating_duration_since(self.start_time);
+
+        // If the duration has passed, return the final value
+        if elapsed >= self.duration {
+            return FadeStatus::Complete(self.end_value);
+        }
+
+        // Otherwise, return the value at this point of the fade
+        let progress = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        let difference = self.end_value as f64 - self.start_value as f64;
+        FadeStatus::Ongoing((self.start_value as f64 + difference * progress).round() as u16)
+    }
+}
+
+// Tests of the fade engine module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that a fade moves toward its value and completes on time
+    #[test]
+    fn fade_over_time() {
+        let mut engine = FadeEngine::new();
+        let start = Instant::now();
+
+        // A fade with a duration starts from the current value
+        let fade = Fade {
+            channel: 2,
+            value: 200,
+            duration: Some(Duration::from_secs(2)),
+        };
+        assert!(!engine.play_fade(fade, start).unwrap());
+        assert!(engine.update(start + Duration::from_secs(1)).is_empty());
+        assert!((99..=100).contains(&engine.universe().get(2)));
+        assert_eq!(engine.active_fades(), 1);
+
+        // The fade completes with the final value after the duration
+        assert_eq!(engine.update(start + Duration::from_millis(2001)), vec![2]);
+        assert_eq!(engine.universe().get(2), 200);
+        assert_eq!(engine.active_fades(), 0);
+
+        // A fade without a duration is immediate, and channels are checked
+        let fade = Fade {
+            channel: 512,
+            value: 7,
+            duration: None,
+        };
+        assert!(engine.play_fade(fade, start).unwrap());
+        assert_eq!(engine.universe().get(512), 7);
+        let fade = Fade {
+            channel: 513,
+            value: 7,
+            duration: None,
+        };
+        assert!(engine.play_fade(fade, start).is_err());
+    }
+
+    // Test that a wide fade moves both channels, until one is replaced
+    #[test]
+    fn wide_fade_over_time() {
+        let mut engine = FadeEngine::new();
+        let start = Instant::now();
+
+        // Fade from zero to the middle of the range
+        let fade = WideFade {
+            channel: 1,
+            fine_channel: Some(2),
+            value: 0x8000,
+            duration: Some(Duration::from_secs(2)),
+        };
+        assert!(!engine.play_wide_fade(fade, start).unwrap());
+        engine.update(start + Duration::from_secs(1));
+        let value = (engine.universe().get(1) as u16) << 8 | engine.universe().get(2) as u16;
+        assert!((0x3FE0..=0x4000).contains(&value));
+
+        // A fade on the fine channel replaces the wide fade
+        let fade = Fade {
+            channel: 2,
+            value: 9,
+            duration: None,
+        };
+        engine.play_fade(fade, start).unwrap();
+        assert_eq!(engine.active_fades(), 0);
+        assert_eq!(engine.universe().get(2), 9);
+    }
+
+    // Test that the end of each fade with an identifier is reported
+    #[test]
+    fn tracked_fades_end() {
+        let mut engine = FadeEngine::new();
+        let start = Instant::now();
+        let fade = |channel, duration| WideFade {
+            channel,
+            fine_channel: None,
+            value: 0xFFFF,
+            duration,
+        };
+
+        // Start two timed fades and one instant fade
+        let second = Some(Duration::from_secs(1));
+        engine
+            .play_tracked_fade(fade(1, second), Some(1), start)
+            .unwrap();
+        engine
+            .play_tracked_fade(fade(2, second), Some(2), start)
+            .unwrap();
+        engine
+            .play_tracked_fade(fade(3, None), Some(3), start)
+            .unwrap();
+        let finished = |id, channel| FadeEnd {
+            id,
+            channel,
+            is_cancelled: false,
+        };
+        assert_eq!(engine.take_ended(), vec![finished(3, 3)]);
+
+        // Replacing one fade cancels it, and the other finishes on time
+        engine.play_wide_fade(fade(2, None), start).unwrap();
+        engine.update(start + Duration::from_millis(1001));
+        assert_eq!(
+            engine.take_ended(),
+            vec![
+                FadeEnd {
+                    id: 2,
+                    channel: 2,
+                    is_cancelled: true,
+                },
+                finished(1, 1),
+            ]
+        );
+        assert!(engine.take_ended().is_empty());
+    }
+
+    // Test the exact values of a wide fade at exact times
+    #[test]
+    fn golden_fade_values() {
+        let mut engine = FadeEngine::new();
+        let start = Instant::now();
+        let fade = WideFade {
+            channel: 1,
+            fine_channel: Some(2),
+            value: 0xFFFF,
+            duration: Some(Duration::from_secs(1)),
+        };
+        engine.play_wide_fade(fade, start).unwrap();
+
+        // Check the value every eighth of the fade
+        let golden = [
+            (0, 0),
+            (125, 8192),
+            (250, 16384),
+            (375, 24576),
+            (500, 32768),
+            (625, 40959),
+            (750, 49151),
+            (875, 57343),
+            (1000, 65535),
+        ];
+        for (millis, expected) in golden {
+            engine.update(start + Duration::from_millis(millis));
+            let value = (engine.universe().get(1) as u16) << 8 | engine.universe().get(2) as u16;
+            assert_eq!(value, expected, "at {} ms", millis);
+        }
+        assert_eq!(engine.active_fades(), 0);
+    }
+
+    // Test that every fade stays between its start and end values, never
+    // reverses, and completes on time
+    #[test]
+    fn fade_properties() {
+        let start = Instant::now();
+        for start_value in [0, 1, 100, 254, 255] {
+            for end_value in [0, 3, 128, 255] {
+                for millis in [1, 50, 999, 3000] {
+                    // Start the fade from the starting value
+                    let mut engine = FadeEngine::new();
+                    engine.universe_mut().set(7, start_value);
+                    let fade = Fade {
+                        channel: 7,
+                        value: end_value,
+                        duration: Some(Duration::from_millis(millis)),
+                    };
+                    engine.play_fade(fade, start).unwrap();
+
+                    // Step through the fade in uneven steps
+                    let (low, high) = (start_value.min(end_value), start_value.max(end_value));
+                    let mut last = start_value;
+                    let mut elapsed = 0;
+                    while elapsed <= millis {
+                        engine.update(start + Duration::from_millis(elapsed));
+                        let value = engine.universe().get(7);
+                        assert!((low..=high).contains(&value));
+                        assert!(if end_value >= start_value {
+                            value >= last
+                        } else {
+                            value <= last
+                        });
+                        last = value;
+                        elapsed += 1 + elapsed / 3;
+                    }
+
+                    // The fade always ends at its value
+                    engine.update(start + Duration::from_millis(millis + 1));
+                    assert_eq!(engine.universe().get(7), end_value);
+                    assert_eq!(engine.active_fades(), 0);
+                }
+            }
+        }
+    }
+
+    // Test that a paused fade holds its value and finishes as late as it
+    // was paused
+    #[test]
+    fn pause_and_resume() {
+        let mut engine = FadeEngine::new();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let fade = Fade {
+            channel: 3,
+            value: 100,
+            duration: Some(Duration::from_secs(1)),
+        };
+        engine.play_fade(fade, start).unwrap();
+
+        // Pause halfway, and the value holds
+        engine.pause(&[3], at(500));
+        engine.update(at(5000));
+        assert_eq!(engine.universe().get(3), 50);
+
+        // Resume after four and a half secs, and the fade finishes late
+        engine.resume(&[], at(5000));
+        engine.update(at(5250));
+        assert_eq!(engine.universe().get(3), 75);
+        assert!(engine.update(at(5499)).is_empty());
+        assert_eq!(engine.update(at(5500)), vec![3]);
+        assert_eq!(engine.universe().get(3), 100);
+    }
+}
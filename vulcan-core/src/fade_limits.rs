@@ -0,0 +1,148 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to limit how fast channels may change, for loads that cannot
+//! take a sudden change (such as motors, relays, and arc lamps). The limits
+//! only change the frames that are written, so the universe keeps the values
+//! that were requested and each limited channel moves toward its value at no
+//! more than its rate.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::borrow::Cow;
+use std::time::Instant;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the maximum rate and the current output of every
+/// limited channel
+///
+#[derive(Clone, Default)]
+pub struct FadeLimits {
+    rates: FnvHashMap<u32, f64>, // the maximum rate of each limited channel, in units per second
+    outputs: FnvHashMap<u32, f64>, // the current output of each limited channel
+    last_update: Option<Instant>, // the time of the last frame, while any channel is limited
+}
+
+// Implement key features of the fade limits
+impl FadeLimits {
+    /// A function to create new fade limits, without any limited channels
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to replace the maximum rate of every channel, in units per
+    /// second. Channels outside the universe and rates that are not
+    /// positive are ignored. Channels that remain limited continue from
+    /// their current output.
+    ///
+    pub fn set_rates(&mut self, rates: FnvHashMap<u32, f64>) {
+        self.rates = rates
+            .into_iter()
+            .filter(|(channel, rate)| (1..=DMX_MAX).contains(channel) && *rate > 0.0)
+            .collect();
+        self.outputs
+            .retain(|channel, _| self.rates.contains_key(channel));
+        if self.rates.is_empty() {
+            self.last_update = None;
+        }
+    }
+
+    /// A method to check if any channel is still moving toward its value
+    ///
+    pub fn is_limiting(&self) -> bool {
+        self.last_update.is_some()
+    }
+
+    /// A method to return the universe as it should be written, with each
+    /// limited channel moved toward its value by no more than its rate since
+    /// the last frame
+    ///
+    pub fn apply<'a>(&mut self, universe: &'a Universe, now: Instant) -> Cow<'a, Universe> {
+        // Skip the copy if no channel is limited
+        if self.rates.is_empty() {
+            return Cow::Borrowed(universe);
+        }
+
+        // Find the time since the last frame (none if every channel had
+        // reached its value, so that a new value starts from the last output)
+        let elapsed = self
+            .last_update
+            .map(|last| now.saturating_duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+
+        // Move each limited channel toward its value
+        let mut output = universe.clone();
+        let mut is_limiting = false;
+        for (channel, rate) in self.rates.iter() {
+            let target = universe.get(*channel) as f64;
+            let current = self.outputs.entry(*channel).or_insert(target);
+            let step = rate * elapsed;
+            *current = if target > *current {
+                (*current + step).min(target)
+            } else {
+                (*current - step).max(target)
+            };
+            is_limiting |= *current != target;
+            output.set(*channel, current.round() as u8);
+        }
+
+        // Note the time of this frame if a channel is still moving
+        self.last_update = is_limiting.then_some(now);
+        Cow::Owned(output)
+    }
+}
+
+// Tests of the fade limits module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Test that a limited channel moves no faster than its rate
+    #[test]
+    fn limit_rate() {
+        // Limit channel 1 to 100 units per second
+        let mut limits = FadeLimits::new();
+        limits.set_rates(FnvHashMap::from_iter([(1, 100.0), (2, 0.0), (600, 10.0)]));
+        let start = Instant::now();
+        let mut universe = Universe::new();
+        assert_eq!(limits.apply(&universe, start).get(1), 0);
+        assert!(!limits.is_limiting());
+
+        // A snap to full takes more than two seconds, but other channels snap
+        universe.set(1, 255);
+        universe.set(2, 255);
+        let output = limits.apply(&universe, start + Duration::from_secs(10));
+        assert_eq!((output.get(1), output.get(2)), (0, 255));
+        assert!(limits.is_limiting());
+        let output = limits.apply(&universe, start + Duration::from_millis(11_500));
+        assert_eq!(output.get(1), 150);
+        let output = limits.apply(&universe, start + Duration::from_secs(13));
+        assert_eq!(output.get(1), 255);
+        assert!(!limits.is_limiting());
+
+        // Removing the limit releases the channel
+        limits.set_rates(FnvHashMap::default());
+        universe.set(1, 0);
+        assert!(matches!(limits.apply(&universe, start), Cow::Borrowed(_)));
+    }
+}
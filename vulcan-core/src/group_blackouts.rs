@@ -0,0 +1,109 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to black out groups of channels by hand, such as killing only
+//! the front wash. A blacked out channel is held at zero above every
+//! playback (and the preheat) until its group is released. Like the levels
+//! of the groups, the blackouts only change the frames that are written.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::borrow::Cow;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the channels of every group that is blacked out
+///
+#[derive(Clone, Default)]
+pub struct GroupBlackouts {
+    groups: FnvHashMap<String, Vec<u32>>, // the channels of each blacked out group
+}
+
+// Implement key features of the group blackouts
+impl GroupBlackouts {
+    /// A function to create new group blackouts, without any blacked out
+    /// group
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to black out (or release) a group. A group without channels
+    /// is released.
+    ///
+    pub fn set_blackout(&mut self, name: &str, channels: Vec<u32>, is_blackout: bool) {
+        if is_blackout && !channels.is_empty() {
+            self.groups.insert(name.to_string(), channels);
+        } else {
+            self.groups.remove(name);
+        }
+    }
+
+    /// A method to return the universe as it should be written, with every
+    /// channel of the blacked out groups at zero
+    ///
+    pub fn apply<'a>(&self, universe: &'a Universe) -> Cow<'a, Universe> {
+        // Skip the copy if no group is blacked out
+        if self.groups.is_empty() {
+            return Cow::Borrowed(universe);
+        }
+
+        // Zero every channel of the groups
+        let mut output = universe.clone();
+        for channel in self
+            .groups
+            .values()
+            .flatten()
+            .filter(|channel| (1..=DMX_MAX).contains(*channel))
+        {
+            output.set(*channel, 0);
+        }
+        Cow::Owned(output)
+    }
+}
+
+// Tests of the group blackouts module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that blacked out groups hold their channels at zero until released
+    #[test]
+    fn black_out_groups() {
+        let mut blackouts = GroupBlackouts::new();
+        let mut universe = Universe::new();
+        universe.set(1, 200);
+        universe.set(2, 200);
+        universe.set(3, 200);
+
+        // Overlapping groups zero each of their channels
+        blackouts.set_blackout("front", vec![1, 2], true);
+        blackouts.set_blackout("side", vec![2, 513], true);
+        let output = blackouts.apply(&universe);
+        assert_eq!((output.get(1), output.get(2), output.get(3)), (0, 0, 200));
+
+        // A channel stays dark while any of its groups is blacked out
+        blackouts.set_blackout("front", vec![1, 2], false);
+        let output = blackouts.apply(&universe);
+        assert_eq!((output.get(1), output.get(2)), (200, 0));
+        blackouts.set_blackout("side", vec![2, 513], false);
+        assert!(matches!(blackouts.apply(&universe), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The core of Vulcan: the definitions shared across the program, the fade
+//! and effect engines, the beat clock, the layers, overrides, and submasters, and the DMX controller with its hardware drivers. Other Rust
+//! applications can use this crate to embed the engine of Vulcan directly,
+//! without going through the web interface.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use std::time::Duration;
+//! use vulcan_core::definitions::{EventSend, Fade};
+//! use vulcan_core::DmxController;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! // Connect to an Enttec DMX USB Pro and fade up the first channel
+//! let controller = DmxController::open(
+//!     Path::new("/dev/ttyUSB0"),
+//!     Duration::from_millis(50),
+//!     EventSend::new(),
+//! )?;
+//! controller
+//!     .play_fade(Fade {
+//!         channel: 1,
+//!         value: 255,
+//!         duration: Some(Duration::from_secs(3)),
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+// Import serde features
+#[macro_use]
+extern crate serde;
+
+// Import anyhow features
+#[macro_use]
+extern crate anyhow;
+
+// Define the public modules
+pub mod definitions;
+pub mod effects;
+
+// Define private submodules
+mod beat_clock;
+mod channel_counters;
+mod clock;
+mod compositor;
+mod dmx_controller;
+mod driver;
+mod effect_engine;
+mod fade_engine;
+mod fade_limits;
+mod group_blackouts;
+mod overrides;
+mod preheat;
+mod submasters;
+mod threaded_driver;
+
+// Reexport the engines and the drivers
+pub use beat_clock::BeatClock;
+pub use channel_counters::ChannelCounters;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use compositor::{Compositor, LayerOutput};
+pub use dmx_controller::{DmxController, QueueStatus};
+pub use driver::{DmxDriver, DryRunDriver, EnttecUsbPro, SerialLine};
+pub use effect_engine::EffectEngine;
+pub use fade_engine::FadeEngine;
+pub use fade_limits::FadeLimits;
+pub use overrides::Overrides;
+pub use submasters::Submasters;
+pub use threaded_driver::ThreadedDriver;
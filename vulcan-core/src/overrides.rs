@@ -0,0 +1,172 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to hold the manual overrides, which sit above every playback
+//! until they are released. A released channel fades back to the layers
+//! below it over the release time.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::time::{Duration, Instant};
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the values of the overrides and any releases in
+/// progress
+///
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    values: Universe, // the value of each channel with an override
+    channels: FnvHashMap<u32, Option<(Instant, Duration)>>, // the channels with an override, and the start and length of any release
+}
+
+// Implement key features of the overrides
+impl Overrides {
+    /// A function to create new overrides, without any channels
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to return the values of the overrides
+    ///
+    pub fn values(&self) -> &Universe {
+        &self.values
+    }
+
+    /// A method to indicate whether any channel is being released
+    ///
+    pub fn is_releasing(&self) -> bool {
+        self.channels.values().any(Option::is_some)
+    }
+
+    /// A method to indicate whether a channel is overridden (including a
+    /// channel that is being released)
+    ///
+    pub fn contains(&self, channel: u32) -> bool {
+        self.channels.contains_key(&channel)
+    }
+
+    /// A method to list every channel that is held, without a release in
+    /// progress
+    ///
+    pub fn held(&self) -> Vec<u32> {
+        let mut channels: Vec<u32> = self
+            .channels
+            .iter()
+            .filter(|(_, release)| release.is_none())
+            .map(|(channel, _)| *channel)
+            .collect();
+        channels.sort_unstable();
+        channels
+    }
+
+    /// A method to override a channel, cancelling any release of the channel
+    ///
+    pub fn set(&mut self, channel: u32, value: u8) {
+        if (1..=DMX_MAX).contains(&channel) {
+            self.values.set(channel, value);
+            self.channels.insert(channel, None);
+        }
+    }
+
+    /// A method to release the channels (or every channel, if there are no
+    /// channels) over the duration. A release that is already in progress
+    /// is left alone, unless the duration is zero.
+    ///
+    pub fn release(&mut self, channels: &[u32], duration: Duration, now: Instant) {
+        // Release the channels right away, without a duration
+        let is_selected = |channel: &u32| channels.is_empty() || channels.contains(channel);
+        if duration.is_zero() {
+            self.channels.retain(|channel, _| !is_selected(channel));
+            return;
+        }
+
+        // Otherwise, start the release of each channel
+        for (channel, release) in self.channels.iter_mut() {
+            if is_selected(channel) && release.is_none() {
+                *release = Some((now, duration));
+            }
+        }
+    }
+
+    /// A method to remove every finished release and return the share of
+    /// each channel with an override at the given time
+    ///
+    pub fn update(&mut self, now: Instant) -> Vec<(u32, f64)> {
+        // Find the share of each channel
+        let shares: Vec<(u32, f64)> = self
+            .channels
+            .iter()
+            .map(|(channel, release)| {
+                let share = match release {
+                    Some((start, duration)) => {
+                        let elapsed = now.saturating_duration_since(*start);
+                        1.0 - elapsed.as_secs_f64() / duration.as_secs_f64().max(f64::EPSILON)
+                    }
+                    None => 1.0,
+                };
+                (*channel, share)
+            })
+            .collect();
+
+        // Forget the channels that are fully released
+        for (channel, share) in shares.iter() {
+            if *share <= 0.0 {
+                self.channels.remove(channel);
+            }
+        }
+        shares
+            .into_iter()
+            .filter(|(_, share)| *share > 0.0)
+            .collect()
+    }
+}
+
+// Tests of the overrides module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that an override holds until it is released over time
+    #[test]
+    fn release_over_time() {
+        let mut overrides = Overrides::new();
+        let start = Instant::now();
+        overrides.set(1, 255);
+        overrides.set(2, 128);
+        overrides.set(513, 1);
+        assert_eq!(overrides.held(), vec![1, 2]);
+
+        // Release one channel over two seconds
+        overrides.release(&[1], Duration::from_secs(2), start);
+        assert!(overrides.is_releasing());
+        let mut shares = overrides.update(start + Duration::from_secs(1));
+        shares.sort_by_key(|(channel, _)| *channel);
+        assert_eq!(shares, vec![(1, 0.5), (2, 1.0)]);
+
+        // The channel is forgotten once released, and the rest release at once
+        overrides.update(start + Duration::from_secs(2));
+        assert_eq!(overrides.held(), vec![2]);
+        overrides.release(&[], Duration::ZERO, start);
+        assert!(overrides.update(start).is_empty());
+        assert_eq!(overrides.values().get(2), 128);
+    }
+}
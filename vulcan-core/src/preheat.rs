@@ -0,0 +1,103 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to keep tungsten filaments warm, so that lamps respond at once
+//! to a bump instead of lagging while the filament heats. Like the groups,
+//! the preheat only changes the frames that are written, so the universe
+//! keeps the values that were requested.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::borrow::Cow;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the preheat value of each warmed channel
+///
+#[derive(Clone, Default)]
+pub struct Preheat {
+    values: FnvHashMap<u32, u8>, // the lowest output of each warmed channel
+}
+
+// Implement key features of the preheat
+impl Preheat {
+    /// A function to create a new preheat, without any warmed channels
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A method to replace the preheat value of every channel. Channels
+    /// outside the universe and values of zero are ignored.
+    ///
+    pub fn set_values(&mut self, values: FnvHashMap<u32, u8>) {
+        self.values = values
+            .into_iter()
+            .filter(|(channel, value)| (1..=DMX_MAX).contains(channel) && *value > 0)
+            .collect();
+    }
+
+    /// A method to return the universe as it should be written, with each
+    /// warmed channel raised to its preheat value when it would otherwise be
+    /// at zero (or below the preheat)
+    ///
+    pub fn apply<'a>(&self, universe: &'a Universe) -> Cow<'a, Universe> {
+        // Skip the copy if no channel needs to be raised
+        if self
+            .values
+            .iter()
+            .all(|(channel, value)| universe.get(*channel) >= *value)
+        {
+            return Cow::Borrowed(universe);
+        }
+
+        // Raise each channel below its preheat
+        let mut output = universe.clone();
+        for (channel, value) in self.values.iter() {
+            if output.get(*channel) < *value {
+                output.set(*channel, *value);
+            }
+        }
+        Cow::Owned(output)
+    }
+}
+
+// Tests of the preheat module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that warmed channels never drop below their preheat
+    #[test]
+    fn warm_channels() {
+        let mut preheat = Preheat::new();
+        let mut universe = Universe::new();
+        universe.set(2, 100);
+
+        // Dark channels rise to their preheat, and brighter ones are unchanged
+        preheat.set_values(FnvHashMap::from_iter([(1, 5), (2, 5), (513, 5)]));
+        let output = preheat.apply(&universe);
+        assert_eq!((output.get(1), output.get(2), output.get(3)), (5, 100, 0));
+
+        // Without any warmed channels, the frame is not copied
+        preheat.set_values(FnvHashMap::default());
+        assert!(matches!(preheat.apply(&universe), Cow::Borrowed(_)));
+    }
+}
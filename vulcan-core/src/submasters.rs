@@ -0,0 +1,134 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to scale the output of groups of channels, like the submasters
+//! of a console. The levels only change the frames that are written, so the
+//! universe keeps the values that were requested. A channel in several groups
+//! is scaled by each of them.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::borrow::Cow;
+
+// Import FNV HashMap
+use fnv::FnvHashMap;
+
+/// A structure to hold the level of every group and the resulting scale of
+/// each channel
+///
+#[derive(Clone)]
+pub struct Submasters {
+    groups: FnvHashMap<String, (Vec<u32>, u8)>, // the channels and level of each group below full
+    scales: Vec<f64>,                           // the scale of each channel, zero-indexed
+}
+
+// Implement the default submasters, with every channel at full
+impl Default for Submasters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement key features of the submasters
+impl Submasters {
+    /// A function to create new submasters, with every channel at full
+    ///
+    pub fn new() -> Self {
+        Self {
+            groups: FnvHashMap::default(),
+            scales: vec![1.0; DMX_MAX as usize],
+        }
+    }
+
+    /// A method to set the level of a group, in percent. A group at full
+    /// (or without channels) is forgotten.
+    ///
+    pub fn set_level(&mut self, name: &str, channels: Vec<u32>, level: u8) {
+        // Replace the group
+        let level = level.min(FULL_LEVEL);
+        if level == FULL_LEVEL || channels.is_empty() {
+            self.groups.remove(name);
+        } else {
+            self.groups.insert(name.to_string(), (channels, level));
+        }
+
+        // Recalculate the scale of every channel
+        self.scales.fill(1.0);
+        for (channels, level) in self.groups.values() {
+            for channel in channels
+                .iter()
+                .filter(|channel| (1..=DMX_MAX).contains(*channel))
+            {
+                self.scales[*channel as usize - 1] *= *level as f64 / FULL_LEVEL as f64;
+            }
+        }
+    }
+
+    /// A method to return the universe as it should be written, with each
+    /// channel scaled by its groups
+    ///
+    pub fn apply<'a>(&self, universe: &'a Universe) -> Cow<'a, Universe> {
+        // Skip the copy if every group is at full
+        if self.groups.is_empty() {
+            return Cow::Borrowed(universe);
+        }
+
+        // Scale every channel
+        let mut output = universe.clone();
+        for (index, scale) in self.scales.iter().enumerate() {
+            let channel = index as u32 + 1;
+            output.set(
+                channel,
+                (universe.get(channel) as f64 * scale).round() as u8,
+            );
+        }
+        Cow::Owned(output)
+    }
+}
+
+// Tests of the submasters module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that each group scales its channels proportionally
+    #[test]
+    fn scale_groups() {
+        let mut submasters = Submasters::new();
+        let mut universe = Universe::new();
+        universe.set(1, 200);
+        universe.set(2, 200);
+        universe.set(3, 200);
+
+        // Overlapping groups multiply
+        submasters.set_level("front", vec![1, 2], 50);
+        submasters.set_level("side", vec![2, 513], 50);
+        let output = submasters.apply(&universe);
+        assert_eq!(
+            (output.get(1), output.get(2), output.get(3)),
+            (100, 50, 200)
+        );
+
+        // A group at full no longer changes the output
+        submasters.set_level("side", vec![2, 513], FULL_LEVEL);
+        assert_eq!(submasters.apply(&universe).get(2), 100);
+        submasters.set_level("front", vec![1, 2], FULL_LEVEL);
+        assert!(matches!(submasters.apply(&universe), Cow::Borrowed(_)));
+    }
+}
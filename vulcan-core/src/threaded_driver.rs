@@ -0,0 +1,238 @@
+// Copyright (c) 2024 Decode Detroit
+// Author: Patton Doyle
+// Licence: GNU GPLv3
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module to write the frames from a dedicated thread. The driver runs on
+//! its own operating system thread (optionally at a real-time priority), so
+//! a busy async runtime never delays a write to the hardware once the frame
+//! is ready.
+
+// Import crate definitions
+use crate::definitions::*;
+
+// Import standard library features
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+// Import the driver trait
+use crate::driver::DmxDriver;
+
+// Import the tokio features
+use tokio::runtime::Builder;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+// Import tracing features
+use tracing::{info, warn};
+
+// Import anyhow features
+use anyhow::Result;
+
+/// A type for a frame sent to the thread: the frame, the limit to write it,
+/// and the line to return the result
+///
+type Transmission = (Universe, Duration, oneshot::Sender<Result<()>>);
+
+/// A driver that passes each frame to another driver on a dedicated thread.
+/// The frames are passed through a channel with room for one frame waiting,
+/// so a frame is refused (and written again by the controller) while the
+/// thread is behind.
+///
+pub struct ThreadedDriver {
+    name: &'static str, // the type of the hardware of the driver on the thread
+    transmit: SyncSender<Transmission>, // the line to send frames to the thread
+}
+
+// Implement key features of the threaded driver
+impl ThreadedDriver {
+    /// A function to start the thread and open the driver on it. The driver
+    /// is opened on the thread, so that it is registered with the runtime
+    /// of that thread. If a priority is specified (from 1 to 99), the
+    /// thread is scheduled first-in first-out at that priority.
+    ///
+    /// # Errors
+    ///
+    /// This function will raise an error if the thread cannot be started or
+    /// the driver cannot be opened. A priority that cannot be set is only
+    /// logged, and the thread continues at the normal priority.
+    ///
+    pub fn spawn<D, F>(open: F, priority: Option<u8>) -> Result<Self>
+    where
+        D: DmxDriver,
+        F: FnOnce() -> Result<D> + Send + 'static,
+    {
+        // Start the thread, which reports back once the driver is open
+        let (transmit, receive) = mpsc::sync_channel::<Transmission>(1);
+        let (report, opened) = mpsc::sync_channel(1);
+        thread::Builder::new()
+            .name("vulcan-dmx".to_string())
+            .spawn(move || {
+                // Raise the priority, if requested
+                if let Some(priority) = priority {
+                    set_priority(priority);
+                }
+
+                // Create a runtime for the driver and open the driver
+                let runtime = match Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(error) => {
+                        report.send(Err(error.into())).unwrap_or(());
+                        return;
+                    }
+                };
+                let mut driver = match runtime.block_on(async { open() }) {
+                    Ok(driver) => driver,
+                    Err(error) => {
+                        report.send(Err(error)).unwrap_or(());
+                        return;
+                    }
+                };
+                report.send(Ok(driver.name())).unwrap_or(());
+
+                // Write each frame until the controller is dropped
+                while let Ok((frame, limit, reply_to)) = receive.recv() {
+                    let result = runtime.block_on(driver.write_frame(&frame, limit));
+                    reply_to.send(result).unwrap_or(());
+                }
+            })?;
+
+        // Wait for the driver to open
+        let name = opened
+            .recv()
+            .map_err(|_| anyhow!("The DMX thread stopped unexpectedly."))??;
+        Ok(Self { name, transmit })
+    }
+}
+
+// Implement the driver for the thread
+impl DmxDriver for ThreadedDriver {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn write_frame(&mut self, universe: &Universe, limit: Duration) -> Result<()> {
+        // Pass the frame to the thread, unless it is still writing
+        let (reply_to, reply) = oneshot::channel();
+        match self.transmit.try_send((universe.clone(), limit, reply_to)) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                return Err(anyhow!("The DMX thread is still writing the last frames."));
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                return Err(anyhow!("The DMX thread has stopped."));
+            }
+        }
+
+        // Wait for the result, at most the limit
+        timeout(limit, reply)
+            .await
+            .map_err(|_| anyhow!("The DMX thread did not write the frame in time."))?
+            .map_err(|_| anyhow!("The DMX thread has stopped."))?
+    }
+}
+
+/// A helper function to schedule the current thread first-in first-out at
+/// the priority (which usually requires the CAP_SYS_NICE capability)
+///
+fn set_priority(priority: u8) {
+    let param = libc::sched_param {
+        sched_priority: priority.clamp(1, 99) as i32,
+    };
+
+    // Safety: the parameters are valid for the duration of the call, and
+    // zero selects the current thread
+    let result = unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) };
+    if result == 0 {
+        info!("Writing DMX at real-time priority {}.", priority);
+    } else {
+        warn!(
+            "Unable to raise the priority of the DMX thread: {}.",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+// Tests of the threaded driver module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A driver that counts the frames it writes, slowly
+    struct CountingDriver {
+        frames: Arc<AtomicUsize>, // the number of frames written
+    }
+
+    // Implement the counting driver
+    impl DmxDriver for CountingDriver {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn write_frame(&mut self, _universe: &Universe, _limit: Duration) -> Result<()> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.frames.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    // Test that frames are written on the thread, and refused while busy
+    #[tokio::test]
+    async fn write_on_thread() {
+        // Open the driver on the thread
+        let frames = Arc::new(AtomicUsize::new(0));
+        let counted = frames.clone();
+        let mut driver =
+            ThreadedDriver::spawn(move || Ok(CountingDriver { frames: counted }), None).unwrap();
+        assert_eq!(driver.name(), "counting");
+
+        // A frame is written within the limit
+        let universe = Universe::new();
+        driver
+            .write_frame(&universe, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(frames.load(Ordering::SeqCst), 1);
+
+        // Frames that are not written in time hold the thread, and one more
+        // frame waits for it
+        for _ in 0..2 {
+            assert!(
+                driver
+                    .write_frame(&universe, Duration::from_millis(1))
+                    .await
+                    .is_err()
+            );
+        }
+
+        // Any other frame is refused until the thread catches up
+        assert!(
+            driver
+                .write_frame(&universe, Duration::from_secs(1))
+                .await
+                .is_err()
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(frames.load(Ordering::SeqCst), 3);
+
+        // A driver that fails to open is reported
+        let failed =
+            ThreadedDriver::spawn(|| -> Result<CountingDriver> { Err(anyhow!("no")) }, None);
+        assert!(failed.is_err());
+    }
+}